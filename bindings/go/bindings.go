@@ -47,9 +47,9 @@ var (
 	fnOpen       func(path, cfg, outHandle, errOut unsafe.Pointer) int32
 	fnClose      func(h uintptr, errOut unsafe.Pointer) int32
 
-	fnUpsert           func(h uintptr, ns, id unsafe.Pointer, x, y, z float64, meta, opts, errOut unsafe.Pointer) int32
-	fnDelete           func(h uintptr, ns, id, errOut unsafe.Pointer) int32
-	fnInsertTrajectory func(h uintptr, ns, id, traj, errOut unsafe.Pointer) int32
+	fnUpsert           func(h uintptr, ns, id unsafe.Pointer, x, y, z float64, meta, opts, seqOut, errOut unsafe.Pointer) int32
+	fnDelete           func(h uintptr, ns, id, seqOut, errOut unsafe.Pointer) int32
+	fnInsertTrajectory func(h uintptr, ns, id, traj, seqOut, errOut unsafe.Pointer) int32
 
 	// Result-set functions write a packed binary buffer (ptr,len) the caller
 	// frees with fnBufferFree. See wire.rs for the layout.
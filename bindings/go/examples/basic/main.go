@@ -25,11 +25,11 @@ func main() {
 	defer db.Close()
 
 	nyc := geom.NewPointFlat(geom.XY, []float64{-74.0060, 40.7128})
-	if err := db.Upsert("cities", "nyc", nyc, map[string]any{"population": 8_000_000}); err != nil {
+	if _, err := db.Upsert("cities", "nyc", nyc, map[string]any{"population": 8_000_000}); err != nil {
 		log.Fatalf("upsert nyc: %v", err)
 	}
 	newark := geom.NewPointFlat(geom.XY, []float64{-74.1724, 40.7357})
-	if err := db.Upsert("cities", "newark", newark, map[string]any{"population": 311_000}); err != nil {
+	if _, err := db.Upsert("cities", "newark", newark, map[string]any{"population": 311_000}); err != nil {
 		log.Fatalf("upsert newark: %v", err)
 	}
 
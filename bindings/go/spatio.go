@@ -98,20 +98,21 @@ func (db *DB) Close() error {
 	return decode(code, errOut)
 }
 
-// Upsert inserts or updates an object's current location and metadata.
-func (db *DB) Upsert(namespace, objectID string, point *geom.Point, metadata map[string]any, opts ...WriteOption) error {
+// Upsert inserts or updates an object's current location and metadata,
+// returning the write's sequence number.
+func (db *DB) Upsert(namespace, objectID string, point *geom.Point, metadata map[string]any, opts ...WriteOption) (uint64, error) {
 	db.mu.RLock()
 	defer db.mu.RUnlock()
 	if db.handle == 0 {
-		return ErrClosed
+		return 0, ErrClosed
 	}
 	x, y, z, err := pointXYZ(point)
 	if err != nil {
-		return err
+		return 0, err
 	}
 	metaJSON, err := metadataJSON(metadata)
 	if err != nil {
-		return fmt.Errorf("spatio: encoding metadata: %w", err)
+		return 0, fmt.Errorf("spatio: encoding metadata: %w", err)
 	}
 	var wo writeOptions
 	for _, o := range opts {
@@ -119,49 +120,52 @@ func (db *DB) Upsert(namespace, objectID string, point *geom.Point, metadata map
 	}
 	optsJSON, err := wo.json()
 	if err != nil {
-		return fmt.Errorf("spatio: encoding write options: %w", err)
+		return 0, fmt.Errorf("spatio: encoding write options: %w", err)
 	}
 
 	nsC := newCString(namespace)
 	idC := newCString(objectID)
 	metaC := optCString(metaJSON)
 	optsC := optCString(optsJSON)
+	var seq uint64
 	var errOut unsafe.Pointer
-	code := fnUpsert(db.handle, nsC.ptr(), idC.ptr(), x, y, z, metaC.ptr(), optsC.ptr(), unsafe.Pointer(&errOut))
+	code := fnUpsert(db.handle, nsC.ptr(), idC.ptr(), x, y, z, metaC.ptr(), optsC.ptr(), unsafe.Pointer(&seq), unsafe.Pointer(&errOut))
 	keep(nsC, idC, metaC, optsC)
-	return decode(code, errOut)
+	return seq, decode(code, errOut)
 }
 
-// Delete removes an object.
-func (db *DB) Delete(namespace, objectID string) error {
+// Delete removes an object, returning the deletion's sequence number.
+func (db *DB) Delete(namespace, objectID string) (uint64, error) {
 	db.mu.RLock()
 	defer db.mu.RUnlock()
 	if db.handle == 0 {
-		return ErrClosed
+		return 0, ErrClosed
 	}
 	nsC := newCString(namespace)
 	idC := newCString(objectID)
+	var seq uint64
 	var errOut unsafe.Pointer
-	code := fnDelete(db.handle, nsC.ptr(), idC.ptr(), unsafe.Pointer(&errOut))
+	code := fnDelete(db.handle, nsC.ptr(), idC.ptr(), unsafe.Pointer(&seq), unsafe.Pointer(&errOut))
 	keep(nsC, idC)
-	return decode(code, errOut)
+	return seq, decode(code, errOut)
 }
 
-// InsertTrajectory appends a sequence of timestamped positions for an object.
-// The line's layout must carry an M ordinate holding unix-seconds timestamps
-// (geom.XYM or geom.XYZM).
-func (db *DB) InsertTrajectory(namespace, objectID string, line *geom.LineString) error {
+// InsertTrajectory appends a sequence of timestamped positions for an object,
+// returning the sequence number of the last position written. The line's
+// layout must carry an M ordinate holding unix-seconds timestamps (geom.XYM
+// or geom.XYZM).
+func (db *DB) InsertTrajectory(namespace, objectID string, line *geom.LineString) (uint64, error) {
 	db.mu.RLock()
 	defer db.mu.RUnlock()
 	if db.handle == 0 {
-		return ErrClosed
+		return 0, ErrClosed
 	}
 	if line == nil {
-		return fmt.Errorf("%w: line is nil", ErrInvalidInput)
+		return 0, fmt.Errorf("%w: line is nil", ErrInvalidInput)
 	}
 	mi := line.Layout().MIndex()
 	if mi == -1 {
-		return fmt.Errorf("%w: trajectory line needs an M ordinate for timestamps (use geom.XYM)", ErrInvalidInput)
+		return 0, fmt.Errorf("%w: trajectory line needs an M ordinate for timestamps (use geom.XYM)", ErrInvalidInput)
 	}
 	coords := line.Coords()
 	traj := make([]trajIn, len(coords))
@@ -170,16 +174,17 @@ func (db *DB) InsertTrajectory(namespace, objectID string, line *geom.LineString
 	}
 	payload, err := json.Marshal(traj)
 	if err != nil {
-		return fmt.Errorf("spatio: encoding trajectory: %w", err)
+		return 0, fmt.Errorf("spatio: encoding trajectory: %w", err)
 	}
 
 	nsC := newCString(namespace)
 	idC := newCString(objectID)
 	trajC := newCString(string(payload))
+	var seq uint64
 	var errOut unsafe.Pointer
-	code := fnInsertTrajectory(db.handle, nsC.ptr(), idC.ptr(), trajC.ptr(), unsafe.Pointer(&errOut))
+	code := fnInsertTrajectory(db.handle, nsC.ptr(), idC.ptr(), trajC.ptr(), unsafe.Pointer(&seq), unsafe.Pointer(&errOut))
 	keep(nsC, idC, trajC)
-	return decode(code, errOut)
+	return seq, decode(code, errOut)
 }
 
 type trajIn struct {
@@ -33,9 +33,13 @@ func TestUpsertGetDelete(t *testing.T) {
 	db := openTestDB(t)
 
 	nyc := point(-74.0060, 40.7128)
-	if err := db.Upsert("cities", "nyc", nyc, map[string]any{"population": 8_000_000}); err != nil {
+	seq, err := db.Upsert("cities", "nyc", nyc, map[string]any{"population": 8_000_000})
+	if err != nil {
 		t.Fatalf("upsert: %v", err)
 	}
+	if seq != 1 {
+		t.Errorf("upsert sequence = %d, want 1", seq)
+	}
 
 	loc, err := db.Get("cities", "nyc")
 	if err != nil {
@@ -70,9 +74,13 @@ func TestUpsertGetDelete(t *testing.T) {
 		t.Error("expected nil for missing object")
 	}
 
-	if err := db.Delete("cities", "nyc"); err != nil {
+	seq, err = db.Delete("cities", "nyc")
+	if err != nil {
 		t.Fatalf("delete: %v", err)
 	}
+	if seq != 2 {
+		t.Errorf("delete sequence = %d, want 2", seq)
+	}
 	gone, _ := db.Get("cities", "nyc")
 	if gone != nil {
 		t.Error("expected nil after delete")
@@ -87,7 +95,7 @@ func TestRadiusKNNNearAndBBox(t *testing.T) {
 		"sf":     point(-122.4194, 37.7749),
 	}
 	for id, p := range cities {
-		if err := db.Upsert("cities", id, p, map[string]any{"name": id}); err != nil {
+		if _, err := db.Upsert("cities", id, p, map[string]any{"name": id}); err != nil {
 			t.Fatalf("upsert %s: %v", id, err)
 		}
 	}
@@ -144,7 +152,7 @@ func TestPolygonHullAndBounds(t *testing.T) {
 		"e": point(5, 5),
 	}
 	for id, p := range pts {
-		if err := db.Upsert("grid", id, p, nil); err != nil {
+		if _, err := db.Upsert("grid", id, p, nil); err != nil {
 			t.Fatalf("upsert %s: %v", id, err)
 		}
 	}
@@ -199,7 +207,7 @@ func TestTrajectory(t *testing.T) {
 		-74.02, 40.72, float64(base.Add(2 * time.Minute).Unix()),
 	}
 	line := geom.NewLineStringFlat(geom.XYM, flat)
-	if err := db.InsertTrajectory("fleet", "truck-1", line); err != nil {
+	if _, err := db.InsertTrajectory("fleet", "truck-1", line); err != nil {
 		t.Fatalf("insert_trajectory: %v", err)
 	}
 
@@ -219,8 +227,8 @@ func TestTrajectory(t *testing.T) {
 
 func TestDistance(t *testing.T) {
 	db := openTestDB(t)
-	_ = db.Upsert("cities", "nyc", point(-74.0060, 40.7128), nil)
-	_ = db.Upsert("cities", "sf", point(-122.4194, 37.7749), nil)
+	_, _ = db.Upsert("cities", "nyc", point(-74.0060, 40.7128), nil)
+	_, _ = db.Upsert("cities", "sf", point(-122.4194, 37.7749), nil)
 
 	d, err := db.DistanceBetween("cities", "nyc", "sf", spatio.Haversine)
 	if err != nil {
@@ -261,8 +269,8 @@ func TestErrorsAfterClose(t *testing.T) {
 
 func TestStats(t *testing.T) {
 	db := openTestDB(t)
-	_ = db.Upsert("ns", "a", point(1, 1), nil)
-	_ = db.Upsert("ns", "b", point(2, 2), nil)
+	_, _ = db.Upsert("ns", "a", point(1, 1), nil)
+	_, _ = db.Upsert("ns", "b", point(2, 2), nil)
 
 	stats, err := db.Stats()
 	if err != nil {
@@ -286,7 +294,7 @@ func BenchmarkQueryRadius(b *testing.B) {
 	for i := 0; i < 10_000; i++ {
 		lon := -74.0 + float64(i%100)*0.001
 		lat := 40.0 + float64(i/100)*0.001
-		if err := db.Upsert("f", fmt.Sprintf("o%d", i), point(lon, lat), map[string]any{"i": i}); err != nil {
+		if _, err := db.Upsert("f", fmt.Sprintf("o%d", i), point(lon, lat), map[string]any{"i": i}); err != nil {
 			b.Fatal(err)
 		}
 	}
@@ -310,7 +318,7 @@ func BenchmarkQueryRadius(b *testing.B) {
 // flight. After Close, operations must return ErrClosed rather than crash.
 func TestCloseRace(t *testing.T) {
 	db := openTestDB(t)
-	_ = db.Upsert("ns", "a", point(1, 1), nil)
+	_, _ = db.Upsert("ns", "a", point(1, 1), nil)
 
 	var wg sync.WaitGroup
 	for i := 0; i < 8; i++ {
@@ -12,8 +12,13 @@
 use pyo3::exceptions::{PyIOError, PyKeyError, PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
 use pyo3::types::PyList;
+use spatio::compute::validation;
+use spatio::db::{CurrentLocation, LocationUpdate};
 use spatio::error::SpatioError;
-use spatio::{DistanceMetric as RustDistanceMetric, Point3d, Polygon as RustPolygon, Spatio};
+use spatio::{
+    DistanceMetric as RustDistanceMetric, HistoryEventKind, NearbyHit, Point3d,
+    Polygon as RustPolygon, Spatio, TimeRange,
+};
 use spatio::{config::Config as RustConfig, error::Result as RustResult};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -24,11 +29,14 @@ fn to_py_err(e: SpatioError) -> PyErr {
     let msg = e.to_string();
     match e {
         SpatioError::InvalidInput(_)
+        | SpatioError::Validation(_)
         | SpatioError::InvalidTimestamp
         | SpatioError::SerializationError
         | SpatioError::SerializationErrorWithContext(_) => PyValueError::new_err(msg),
         SpatioError::ObjectNotFound => PyKeyError::new_err(msg),
-        SpatioError::Io(_) => PyIOError::new_err(msg),
+        SpatioError::Io(_)
+        | SpatioError::DegradedPersistence(_)
+        | SpatioError::InsufficientDiskSpace { .. } => PyIOError::new_err(msg),
         _ => PyRuntimeError::new_err(msg),
     }
 }
@@ -44,6 +52,66 @@ fn systemtime_from_secs(secs: f64) -> PyResult<SystemTime> {
     spatio_types::time::system_time_from_secs(secs).map_err(PyValueError::new_err)
 }
 
+fn time_range(start: Option<f64>, end: Option<f64>, last: Option<f64>) -> PyResult<TimeRange> {
+    TimeRange::from_secs(start, end, last).map_err(PyValueError::new_err)
+}
+
+/// Convert query results paired with distances into a list of
+/// `(object_id, point, metadata, distance)` tuples.
+fn distances_to_py(py: Python<'_>, results: Vec<NearbyHit>) -> PyResult<Py<PyList>> {
+    let py_list = PyList::empty(py);
+    for NearbyHit {
+        location: loc,
+        distance: dist,
+        ..
+    } in results
+    {
+        let py_point = PyPoint {
+            inner: loc.position.clone(),
+        };
+        let py_meta = pythonize::pythonize(py, &loc.metadata)?;
+        let tuple = (loc.object_id.clone(), py_point, py_meta, dist).into_pyobject(py)?;
+        py_list.append(tuple)?;
+    }
+    Ok(py_list.unbind())
+}
+
+/// Convert current locations into a list of `(object_id, point, metadata)`
+/// tuples.
+fn locations_to_py(py: Python<'_>, results: Vec<Arc<CurrentLocation>>) -> PyResult<Py<PyList>> {
+    let py_list = PyList::empty(py);
+    for loc in results {
+        let py_point = PyPoint {
+            inner: loc.position.clone(),
+        };
+        let py_meta = pythonize::pythonize(py, &loc.metadata)?;
+        let tuple = (loc.object_id.clone(), py_point, py_meta).into_pyobject(py)?;
+        py_list.append(tuple)?;
+    }
+    Ok(py_list.unbind())
+}
+
+/// Convert trajectory points into a list of `(point, metadata, timestamp)`
+/// tuples.
+fn updates_to_py(py: Python<'_>, results: Vec<LocationUpdate>) -> PyResult<Py<PyList>> {
+    let py_list = PyList::empty(py);
+    for update in results {
+        let py_point = PyPoint {
+            inner: update.position,
+        };
+        let py_meta = pythonize::pythonize(py, &update.metadata)?;
+        let ts = update
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+
+        let tuple = (py_point, py_meta, ts).into_pyobject(py)?;
+        py_list.append(tuple)?;
+    }
+    Ok(py_list.unbind())
+}
+
 /// Python wrapper for geographic Point (3D)
 #[pyclass(name = "Point")]
 #[derive(Clone, Debug)]
@@ -62,8 +130,12 @@ impl PyPoint {
     #[new]
     #[pyo3(signature = (x, y, z=None))]
     fn new(x: f64, y: f64, z: Option<f64>) -> PyResult<Self> {
+        let z = z.unwrap_or(0.0);
+        for (field, value) in [("x", x), ("y", y), ("z", z)] {
+            validation::validate_finite(field, value).map_err(to_py_err)?;
+        }
         Ok(PyPoint {
-            inner: Point3d::new(x, y, z.unwrap_or(0.0)),
+            inner: Point3d::new(x, y, z),
         })
     }
 
@@ -333,7 +405,7 @@ impl PySpatio {
         Ok(PySpatio { db: Arc::new(db) })
     }
 
-    /// Upsert an object's location
+    /// Upsert an object's location, returning the write's sequence number
     #[pyo3(signature = (namespace, object_id, point, metadata=None, opts=None))]
     fn upsert(
         &self,
@@ -343,7 +415,7 @@ impl PySpatio {
         point: &PyPoint,
         metadata: Option<&Bound<'_, PyAny>>,
         opts: Option<PySetOptions>,
-    ) -> PyResult<()> {
+    ) -> PyResult<u64> {
         let pos = point.inner.clone();
 
         let metadata_value = if let Some(meta) = metadata {
@@ -370,11 +442,12 @@ impl PySpatio {
         point: &PyPoint,
         metadata: Option<&Bound<'_, PyAny>>,
         opts: Option<PySetOptions>,
-    ) -> PyResult<()> {
+    ) -> PyResult<u64> {
         self.upsert(py, namespace, object_id, point, metadata, opts)
     }
 
-    /// Insert a trajectory (sequence of points)
+    /// Insert a trajectory (sequence of points), returning the sequence of the
+    /// last point written
     #[pyo3(signature = (namespace, object_id, trajectory))]
     fn insert_trajectory(
         &self,
@@ -382,7 +455,7 @@ impl PySpatio {
         namespace: &str,
         object_id: &str,
         trajectory: Vec<PyTemporalPoint>,
-    ) -> PyResult<()> {
+    ) -> PyResult<u64> {
         let mut core_trajectory = Vec::with_capacity(trajectory.len());
         for tp in trajectory {
             core_trajectory.push(spatio::TemporalPoint {
@@ -398,8 +471,9 @@ impl PySpatio {
         handle_error(result)
     }
 
-    /// Query current locations within radius
-    #[pyo3(signature = (namespace, center, radius, limit=100))]
+    /// Query current locations within radius, in the namespace's configured
+    /// metric unless `metric` is given
+    #[pyo3(signature = (namespace, center, radius, limit=100, metric=None))]
     fn query_radius(
         &self,
         py: Python<'_>,
@@ -407,14 +481,26 @@ impl PySpatio {
         center: &PyPoint,
         radius: f64,
         limit: usize,
+        metric: Option<&PyDistanceMetric>,
     ) -> PyResult<Py<PyList>> {
         let center_pos = center.inner.clone();
+        let metric = metric.map(|m| m.inner);
         // Release the GIL for the spatial query so other Python threads run.
-        let results = py.detach(|| self.db.query_radius(namespace, &center_pos, radius, limit));
+        let results = py.detach(|| match metric {
+            Some(m) => self
+                .db
+                .query_radius_with_metric(namespace, &center_pos, radius, limit, m),
+            None => self.db.query_radius(namespace, &center_pos, radius, limit),
+        });
         let results = handle_error(results)?;
 
         let py_list = PyList::empty(py);
-        for (loc, dist) in results {
+        for NearbyHit {
+            location: loc,
+            distance: dist,
+            ..
+        } in results
+        {
             let py_point = PyPoint {
                 inner: loc.position.clone(),
             };
@@ -425,6 +511,27 @@ impl PySpatio {
         Ok(py_list.unbind())
     }
 
+    /// One page of `query_radius`, nearest first, as `(results, next_token)`.
+    /// Pass `token=None` for the first page and each `next_token` after
+    /// that, until it is `None`.
+    #[pyo3(signature = (namespace, center, radius, page_size=1000, token=None))]
+    fn query_radius_page(
+        &self,
+        py: Python<'_>,
+        namespace: &str,
+        center: &PyPoint,
+        radius: f64,
+        page_size: usize,
+        token: Option<&str>,
+    ) -> PyResult<(Py<PyList>, Option<String>)> {
+        let page = py.detach(|| {
+            self.db
+                .query_radius_page(namespace, &center.inner, radius, page_size, token)
+        });
+        let page = handle_error(page)?;
+        Ok((distances_to_py(py, page.items)?, page.next_token))
+    }
+
     /// Query objects near another object
     #[pyo3(signature = (namespace, object_id, radius, limit=100))]
     fn query_near(
@@ -439,7 +546,12 @@ impl PySpatio {
         let results = handle_error(results)?;
 
         let py_list = PyList::empty(py);
-        for (loc, dist) in results {
+        for NearbyHit {
+            location: loc,
+            distance: dist,
+            ..
+        } in results
+        {
             let py_point = PyPoint {
                 inner: loc.position.clone(),
             };
@@ -464,7 +576,45 @@ impl PySpatio {
         let results = handle_error(results)?;
 
         let py_list = PyList::empty(py);
-        for (loc, dist) in results {
+        for NearbyHit {
+            location: loc,
+            distance: dist,
+            ..
+        } in results
+        {
+            let py_point = PyPoint {
+                inner: loc.position.clone(),
+            };
+            let py_meta = pythonize::pythonize(py, &loc.metadata)?;
+            let tuple = (loc.object_id.clone(), py_point, py_meta, dist).into_pyobject(py)?;
+            py_list.append(tuple)?;
+        }
+        Ok(py_list.unbind())
+    }
+
+    /// Find k nearest neighbors by horizontal distance, ignoring altitude
+    #[pyo3(signature = (namespace, center, k, max_distance=None, metric=None))]
+    fn knn_2d(
+        &self,
+        py: Python<'_>,
+        namespace: &str,
+        center: &PyPoint,
+        k: usize,
+        max_distance: Option<f64>,
+        metric: Option<&PyDistanceMetric>,
+    ) -> PyResult<Py<PyList>> {
+        let c = spatio::Point::new(center.inner.x(), center.inner.y());
+        let m = metric.map(|m| m.inner).unwrap_or_default();
+        let results = py.detach(|| self.db.knn_2d(namespace, &c, k, max_distance, m));
+        let results = handle_error(results)?;
+
+        let py_list = PyList::empty(py);
+        for NearbyHit {
+            location: loc,
+            distance: dist,
+            ..
+        } in results
+        {
             let py_point = PyPoint {
                 inner: loc.position.clone(),
             };
@@ -488,7 +638,12 @@ impl PySpatio {
         let results = handle_error(results)?;
 
         let py_list = PyList::empty(py);
-        for (loc, dist) in results {
+        for NearbyHit {
+            location: loc,
+            distance: dist,
+            ..
+        } in results
+        {
             let py_point = PyPoint {
                 inner: loc.position.clone(),
             };
@@ -499,47 +654,112 @@ impl PySpatio {
         Ok(py_list.unbind())
     }
 
-    /// Query trajectory
-    #[pyo3(signature = (namespace, object_id, start_time, end_time, limit=100))]
+    /// Query trajectory, newest first. Either end of the time range may be
+    /// left open, and `last` seconds can stand in for `start_time`.
+    #[pyo3(signature = (namespace, object_id, start_time=None, end_time=None, limit=100, last=None))]
+    #[allow(clippy::too_many_arguments)]
     fn query_trajectory(
         &self,
         py: Python<'_>,
         namespace: &str,
         object_id: &str,
-        start_time: f64,
-        end_time: f64,
+        start_time: Option<f64>,
+        end_time: Option<f64>,
         limit: usize,
+        last: Option<f64>,
     ) -> PyResult<Py<PyList>> {
-        let start = systemtime_from_secs(start_time)?;
-        let end = systemtime_from_secs(end_time)?;
+        let range = time_range(start_time, end_time, last)?;
 
-        let results = py.detach(|| {
+        let results = py.detach(|| self.db.query_trajectory(namespace, object_id, range, limit));
+        updates_to_py(py, handle_error(results)?)
+    }
+
+    /// One page of `query_trajectory`, as `(points, next_token)`. Pass
+    /// `token=None` for the first page and each `next_token` after that,
+    /// until it is `None`.
+    #[pyo3(signature = (namespace, object_id, page_size=1000, token=None, start_time=None, end_time=None, last=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn query_trajectory_page(
+        &self,
+        py: Python<'_>,
+        namespace: &str,
+        object_id: &str,
+        page_size: usize,
+        token: Option<&str>,
+        start_time: Option<f64>,
+        end_time: Option<f64>,
+        last: Option<f64>,
+    ) -> PyResult<(Py<PyList>, Option<String>)> {
+        let range = time_range(start_time, end_time, last)?;
+        let page = py.detach(|| {
             self.db
-                .query_trajectory(namespace, object_id, start, end, limit)
+                .query_trajectory_page(namespace, object_id, range, page_size, token)
         });
+        let page = handle_error(page)?;
+        Ok((updates_to_py(py, page.items)?, page.next_token))
+    }
+
+    /// Recorded writes and deletes of an object, oldest first, optionally
+    /// within a time range (see `query_trajectory`) and of one kind ("set"
+    /// or "delete")
+    #[pyo3(signature = (namespace, object_id, start_time=None, end_time=None, kind=None, last=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn history(
+        &self,
+        py: Python<'_>,
+        namespace: &str,
+        object_id: &str,
+        start_time: Option<f64>,
+        end_time: Option<f64>,
+        kind: Option<&str>,
+        last: Option<f64>,
+    ) -> PyResult<Py<PyList>> {
+        let range = time_range(start_time, end_time, last)?;
+        let kind = match kind.map(str::to_lowercase).as_deref() {
+            None => None,
+            Some("set") => Some(HistoryEventKind::Set),
+            Some("delete") => Some(HistoryEventKind::Delete),
+            Some(_) => {
+                return Err(PyValueError::new_err("Invalid kind. Use 'set' or 'delete'"));
+            }
+        };
+
+        let results = py.detach(|| self.db.history_between(namespace, object_id, range, kind));
         let results = handle_error(results)?;
 
         let py_list = PyList::empty(py);
-        for update in results {
-            let py_point = PyPoint {
-                inner: update.position,
+        for entry in results {
+            let kind = match entry.kind {
+                HistoryEventKind::Set => "set",
+                HistoryEventKind::Delete => "delete",
             };
-            let py_meta = pythonize::pythonize(py, &update.metadata)?;
-            let ts = update
+            let ts = entry
                 .timestamp
                 .duration_since(UNIX_EPOCH)
                 .unwrap_or_default()
                 .as_secs_f64();
+            let (py_point, py_meta) = match entry.update {
+                Some(update) => (
+                    Some(PyPoint {
+                        inner: update.position,
+                    }),
+                    Some(pythonize::pythonize(py, &update.metadata)?),
+                ),
+                None => (None, None),
+            };
 
-            // (point, metadata, timestamp)
-            let tuple = (py_point, py_meta, ts).into_pyobject(py)?;
+            // (kind, point, metadata, timestamp); point and metadata are None for deletes
+            let tuple = (kind, py_point, py_meta, ts).into_pyobject(py)?;
             py_list.append(tuple)?;
         }
         Ok(py_list.unbind())
     }
 
-    /// Query objects within a 2D bounding box
-    #[pyo3(signature = (namespace, min_x, min_y, max_x, max_y, limit=100))]
+    /// Query objects within a 2D bounding box. With `with_distances`, each
+    /// result also carries its distance in meters from `anchor`, or from the
+    /// center of the box when no anchor is given
+    #[pyo3(signature = (namespace, min_x, min_y, max_x, max_y, limit=100, with_distances=false, anchor=None))]
+    #[allow(clippy::too_many_arguments)]
     fn query_bbox(
         &self,
         py: Python<'_>,
@@ -549,27 +769,59 @@ impl PySpatio {
         max_x: f64,
         max_y: f64,
         limit: usize,
+        with_distances: bool,
+        anchor: Option<&PyPoint>,
     ) -> PyResult<Py<PyList>> {
+        if with_distances || anchor.is_some() {
+            let anchor = anchor.map(|a| a.inner.clone());
+            let results = py.detach(|| {
+                self.db.query_bbox_with_distances(
+                    namespace,
+                    min_x,
+                    min_y,
+                    max_x,
+                    max_y,
+                    anchor.as_ref(),
+                    limit,
+                )
+            });
+            return distances_to_py(py, handle_error(results)?);
+        }
         let results = py.detach(|| {
             self.db
                 .query_bbox(namespace, min_x, min_y, max_x, max_y, limit)
         });
-        let results = handle_error(results)?;
+        locations_to_py(py, handle_error(results)?)
+    }
 
-        let py_list = PyList::empty(py);
-        for loc in results {
-            let py_point = PyPoint {
-                inner: loc.position.clone(),
-            };
-            let py_meta = pythonize::pythonize(py, &loc.metadata)?;
-            let tuple = (loc.object_id.clone(), py_point, py_meta).into_pyobject(py)?;
-            py_list.append(tuple)?;
-        }
-        Ok(py_list.unbind())
+    /// One page of `query_bbox`, as `(results, next_token)`. Pass
+    /// `token=None` for the first page and each `next_token` after that,
+    /// until it is `None`.
+    #[pyo3(signature = (namespace, min_x, min_y, max_x, max_y, page_size=1000, token=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn query_bbox_page(
+        &self,
+        py: Python<'_>,
+        namespace: &str,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        page_size: usize,
+        token: Option<&str>,
+    ) -> PyResult<(Py<PyList>, Option<String>)> {
+        let page = py.detach(|| {
+            self.db
+                .query_bbox_page(namespace, min_x, min_y, max_x, max_y, page_size, token)
+        });
+        let page = handle_error(page)?;
+        Ok((locations_to_py(py, page.items)?, page.next_token))
     }
 
-    /// Query objects within a cylindrical volume
-    #[pyo3(signature = (namespace, center, min_z, max_z, radius, limit=100))]
+    /// Query objects within a cylindrical volume, with `metric` as for
+    /// `query_radius`
+    #[pyo3(signature = (namespace, center, min_z, max_z, radius, limit=100, metric=None))]
+    #[allow(clippy::too_many_arguments)]
     fn query_within_cylinder(
         &self,
         py: Python<'_>,
@@ -579,16 +831,27 @@ impl PySpatio {
         max_z: f64,
         radius: f64,
         limit: usize,
+        metric: Option<&PyDistanceMetric>,
     ) -> PyResult<Py<PyList>> {
         let center_geo = spatio::Point::new(center.inner.x(), center.inner.y());
-        let results = py.detach(|| {
-            self.db
-                .query_within_cylinder(namespace, center_geo, min_z, max_z, radius, limit)
+        let metric = metric.map(|m| m.inner);
+        let results = py.detach(|| match metric {
+            Some(m) => self.db.query_within_cylinder_with_metric(
+                namespace, center_geo, min_z, max_z, radius, limit, m,
+            ),
+            None => self
+                .db
+                .query_within_cylinder(namespace, center_geo, min_z, max_z, radius, limit),
         });
         let results = handle_error(results)?;
 
         let py_list = PyList::empty(py);
-        for (loc, dist) in results {
+        for NearbyHit {
+            location: loc,
+            distance: dist,
+            ..
+        } in results
+        {
             let py_point = PyPoint {
                 inner: loc.position.clone(),
             };
@@ -679,7 +942,12 @@ impl PySpatio {
         let results = handle_error(results)?;
 
         let py_list = PyList::empty(py);
-        for (loc, dist) in results {
+        for NearbyHit {
+            location: loc,
+            distance: dist,
+            ..
+        } in results
+        {
             let py_point = PyPoint {
                 inner: loc.position.clone(),
             };
@@ -745,22 +1013,52 @@ impl PySpatio {
         }
     }
 
-    /// Delete an object
+    /// Delete an object, returning the deletion's sequence number
     #[pyo3(signature = (namespace, object_id))]
-    fn delete(&self, py: Python<'_>, namespace: &str, object_id: &str) -> PyResult<()> {
+    fn delete(&self, py: Python<'_>, namespace: &str, object_id: &str) -> PyResult<u64> {
         handle_error(py.detach(|| self.db.delete(namespace, object_id)))
     }
 
-    /// Query objects within a polygon
-    #[pyo3(signature = (namespace, polygon, limit=100))]
+    /// Names of the namespaces holding objects or zones, sorted
+    fn list_namespaces(&self, py: Python<'_>) -> PyResult<Vec<String>> {
+        handle_error(py.detach(|| self.db.list_namespaces()))
+    }
+
+    /// Delete every object and zone of a namespace, keeping their history.
+    /// Returns how many were removed
+    #[pyo3(signature = (namespace))]
+    fn clear_namespace(&self, py: Python<'_>, namespace: &str) -> PyResult<u64> {
+        handle_error(py.detach(|| self.db.clear_namespace(namespace)))
+    }
+
+    /// Like `clear_namespace`, also erasing the namespace's history from disk
+    #[pyo3(signature = (namespace))]
+    fn drop_namespace(&self, py: Python<'_>, namespace: &str) -> PyResult<u64> {
+        handle_error(py.detach(|| self.db.drop_namespace(namespace)))
+    }
+
+    /// Query objects within a polygon. With `with_distances`, each result
+    /// also carries its distance in meters from `anchor`, or from the
+    /// centroid of the polygon when no anchor is given
+    #[pyo3(signature = (namespace, polygon, limit=100, with_distances=false, anchor=None))]
     fn query_polygon(
         &self,
         py: Python<'_>,
         namespace: &str,
         polygon: &PyPolygon,
         limit: usize,
+        with_distances: bool,
+        anchor: Option<&PyPoint>,
     ) -> PyResult<Py<PyList>> {
         let poly = polygon.inner.clone();
+        if with_distances || anchor.is_some() {
+            let anchor = anchor.map(|a| a.inner.clone());
+            let results = py.detach(|| {
+                self.db
+                    .query_polygon_with_distances(namespace, &poly, anchor.as_ref(), limit)
+            });
+            return distances_to_py(py, handle_error(results)?);
+        }
         let results = py.detach(|| self.db.query_polygon(namespace, &poly, limit));
         let results = handle_error(results)?;
 
@@ -833,9 +1131,14 @@ impl PySpatio {
             dict.set_item("operations_count", stats.operations_count)?;
             dict.set_item("size_bytes", stats.size_bytes)?;
             dict.set_item("hot_state_objects", stats.hot_state_objects)?;
+            dict.set_item("namespace_objects", stats.namespace_objects)?;
+            dict.set_item("hot_state_memory_bytes", stats.hot_state_memory_bytes)?;
             dict.set_item("cold_state_trajectories", stats.cold_state_trajectories)?;
             dict.set_item("cold_state_buffer_bytes", stats.cold_state_buffer_bytes)?;
             dict.set_item("memory_usage_bytes", stats.memory_usage_bytes)?;
+            dict.set_item("cold_log_bytes", stats.cold_log_bytes)?;
+            dict.set_item("queries_count", stats.queries_count)?;
+            dict.set_item("updates_per_sec", stats.updates_per_sec)?;
             Ok(dict.into_any().unbind())
         })
     }
@@ -860,12 +1163,13 @@ pub struct PySetOptions {
 #[pymethods]
 impl PySetOptions {
     #[new]
-    #[pyo3(signature = (timestamp=None))]
-    fn new(timestamp: Option<f64>) -> PyResult<Self> {
-        let inner = match timestamp {
+    #[pyo3(signature = (timestamp=None, track_history=false))]
+    fn new(timestamp: Option<f64>, track_history: bool) -> PyResult<Self> {
+        let mut inner = match timestamp {
             Some(secs) => spatio::config::SetOptions::with_timestamp(systemtime_from_secs(secs)?),
             None => spatio::config::SetOptions::default(),
         };
+        inner.track_history = track_history;
         Ok(PySetOptions { inner })
     }
 }
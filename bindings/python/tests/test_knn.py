@@ -1,5 +1,6 @@
 import pytest
 
+from spatio import DistanceMetric
 from spatio import Point
 from spatio import Spatio
 
@@ -77,3 +78,17 @@ def test_knn_3d(db):
 
     air_point = next(p for i, p, m, d in results if i == "air")
     assert air_point.z == 1000
+
+
+def test_knn_2d_ignores_altitude(db):
+    namespace = "knn_2d_test"
+    db.upsert(namespace, "drone", Point(0, 0.001, 5000), {})
+    db.upsert(namespace, "truck", Point(0, 0.002, 0), {})
+    db.upsert(namespace, "far", Point(0, 1, 0), {})
+
+    results = db.knn_2d(namespace, Point(0, 0, 0), 10, max_distance=1000.0)
+    assert [r[0] for r in results] == ["drone", "truck"]
+
+    results = db.knn_2d(namespace, Point(0, 0, 0), 1, metric=DistanceMetric("euclidean"))
+    assert results[0][0] == "drone"
+    assert results[0][3] == pytest.approx(0.001)
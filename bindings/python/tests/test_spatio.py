@@ -49,6 +49,21 @@ class TestPoint:
         point = spatio.Point(-74.0060, 40.7128)
         assert "Point(x=-74.0060, y=40.7128, z=0.0000)" in str(point)
 
+    @pytest.mark.parametrize(
+        "coords",
+        [(float("nan"), 0.0), (0.0, float("inf")), (0.0, 0.0, float("-inf"))],
+    )
+    def test_non_finite_point_rejected(self, coords):
+        """Test that NaN and infinite coordinates raise ValueError"""
+        with pytest.raises(ValueError, match="must be finite"):
+            spatio.Point(*coords)
+
+    def test_non_finite_radius_rejected(self):
+        """Test that queries reject non-finite radii with ValueError"""
+        db = spatio.Spatio.memory()
+        with pytest.raises(ValueError, match="radius must be finite"):
+            db.query_radius("cities", spatio.Point(-74.0, 40.7), float("nan"), 10)
+
 
 class TestConfig:
     """Test Config class functionality"""
@@ -98,6 +113,28 @@ class TestSpatio:
         assert meta == {"name": "New York"}
         assert distance < 1.0  # Should be 0 since it's the same point
 
+    def test_writes_return_sequence_numbers(self):
+        """Test that every write returns the next sequence number"""
+        db = spatio.Spatio.memory()
+        nyc = spatio.Point(-74.0060, 40.7128)
+
+        assert db.upsert("cities", "nyc", nyc) == 1
+        assert db.upsert("cities", "nyc", nyc, {"name": "New York"}) == 2
+        assert db.delete("cities", "nyc") == 3
+
+    def test_namespace_management(self):
+        """Test listing, clearing and dropping namespaces"""
+        db = spatio.Spatio.memory()
+        db.upsert("cities", "nyc", spatio.Point(-74.0060, 40.7128))
+        db.upsert("cities", "la", spatio.Point(-118.2437, 34.0522))
+        db.upsert("cars", "c1", spatio.Point(-74.0, 40.7))
+
+        assert db.list_namespaces() == ["cars", "cities"]
+        assert db.clear_namespace("cities") == 2
+        assert db.list_namespaces() == ["cars"]
+        assert db.drop_namespace("cars") == 1
+        assert db.list_namespaces() == []
+
     def test_update_location_no_metadata(self):
         """Test updating location without metadata"""
         db = spatio.Spatio.memory()
@@ -171,6 +208,30 @@ class TestSpatio:
         assert path[0][1] == {"step": 2}  # metadata
         assert path[1][1] == {"step": 1}
 
+        # Open-ended and relative ranges
+        assert len(db.query_trajectory("vehicle", "truck1")) == 2
+        assert len(db.query_trajectory("vehicle", "truck1", end_time=start_time - 1.0)) == 0
+        assert len(db.query_trajectory("vehicle", "truck1", last=60.0)) == 2
+        with pytest.raises(ValueError):
+            db.query_trajectory("vehicle", "truck1", start_time, last=60.0)
+
+    def test_history_kind_filter(self):
+        """History includes deletes and can be filtered by kind"""
+        db = spatio.Spatio.memory()
+        db.upsert("vehicle", "truck1", spatio.Point(-74.0, 40.7), {"step": 1})
+        db.delete("vehicle", "truck1")
+
+        history = db.history("vehicle", "truck1")
+        assert [entry[0] for entry in history] == ["set", "delete"]
+        assert history[0][2] == {"step": 1}
+        assert history[1][1] is None
+
+        deletes = db.history("vehicle", "truck1", kind="delete")
+        assert len(deletes) == 1
+
+        with pytest.raises(ValueError):
+            db.history("vehicle", "truck1", kind="bogus")
+
     def test_close_operation(self):
         """Test database close operation"""
         db = spatio.Spatio.memory()
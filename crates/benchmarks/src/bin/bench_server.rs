@@ -305,7 +305,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         let cy = (i / side_len % side_len) as f64 * 0.01;
                         async move {
                             let _ = client
-                                .query_radius("bench", Point3d::new(cx, cy, 0.0), 0.05, 100)
+                                .query_radius("bench", Point3d::new(cx, cy, 0.0), 0.05, 100, None)
                                 .await;
                         }
                     })
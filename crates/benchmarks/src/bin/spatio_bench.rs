@@ -0,0 +1,366 @@
+//! `spatio-bench replay <access.log> --db <path>`
+//!
+//! Replays a sampled query access log (see `Config::with_access_log`) against
+//! an existing database and reports per-operation latency, so tuning is driven
+//! by production query shapes rather than synthetic grids.
+//!
+//! The database is opened read-only, so a replay leaves its files untouched
+//! and can't run while a writer has it open.
+
+use serde::Serialize;
+use spatio::db::{AccessLogEntry, AccessQuery};
+use spatio::{DBBuilder, Point, Point3d, Polygon, Spatio};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::time::{Duration, Instant, SystemTime};
+
+const USAGE: &str =
+    "usage: spatio-bench replay <access.log> --db <path> [-r runs] [-q] [--json <out>]";
+
+struct ReplayConfig {
+    log_path: String,
+    db_path: String,
+    measurement_runs: usize,
+    quiet: bool,
+    json_output: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct OpMetrics {
+    name: String,
+    ops_count: usize,
+    total_duration_secs: f64,
+    p50_us: f64,
+    p99_us: f64,
+    max_us: f64,
+    avg_results: f64,
+}
+
+impl OpMetrics {
+    fn from_samples(name: &str, mut latencies: Vec<Duration>, results: usize) -> Self {
+        latencies.sort_unstable();
+        let percentile = |p: f64| {
+            let idx = ((latencies.len() as f64 - 1.0) * p).round() as usize;
+            latencies[idx].as_secs_f64() * 1_000_000.0
+        };
+        Self {
+            name: name.to_string(),
+            ops_count: latencies.len(),
+            total_duration_secs: latencies.iter().sum::<Duration>().as_secs_f64(),
+            p50_us: percentile(0.50),
+            p99_us: percentile(0.99),
+            max_us: percentile(1.0),
+            avg_results: results as f64 / latencies.len() as f64,
+        }
+    }
+
+    fn throughput(&self) -> f64 {
+        self.ops_count as f64 / self.total_duration_secs
+    }
+
+    fn print(&self) {
+        println!(
+            "  {:<10} {:>8} ops | {:>12.2} ops/s | p50: {:>9.2}µs | p99: {:>9.2}µs | max: {:>9.2}µs | avg hits: {:.1}",
+            self.name,
+            self.ops_count,
+            self.throughput(),
+            self.p50_us,
+            self.p99_us,
+            self.max_us,
+            self.avg_results
+        );
+    }
+}
+
+fn op_name(query: &AccessQuery) -> &'static str {
+    match query {
+        AccessQuery::Radius { .. } => "RADIUS",
+        AccessQuery::Bbox { .. } => "BBOX",
+        AccessQuery::Bbox3d { .. } => "BBOX3D",
+        AccessQuery::Cylinder { .. } => "CYLINDER",
+        AccessQuery::Knn { .. } => "KNN",
+        AccessQuery::Knn2d { .. } => "KNN2D",
+        AccessQuery::Polygon { .. } => "POLYGON",
+        AccessQuery::Trajectory { .. } => "TRAJECTORY",
+        AccessQuery::Composite { .. } => "COMPOSITE",
+        AccessQuery::Near { .. } => "NEAR",
+        AccessQuery::BboxNear { .. } => "BBOX_NEAR",
+        AccessQuery::Bbox3dNear { .. } => "BBOX3D_NEAR",
+        AccessQuery::CylinderNear { .. } => "CYL_NEAR",
+        AccessQuery::KnnNear { .. } => "KNN_NEAR",
+    }
+}
+
+/// Execute one logged query, returning the number of results.
+fn execute(db: &Spatio, query: &AccessQuery) -> spatio::Result<usize> {
+    let from_micros = |us: u64| SystemTime::UNIX_EPOCH + Duration::from_micros(us);
+    Ok(match query {
+        AccessQuery::Radius {
+            namespace,
+            center,
+            radius,
+            limit,
+            metric,
+        } => {
+            let center = Point3d::new(center[0], center[1], center[2]);
+            db.query_radius_with_metric(namespace, &center, *radius, *limit, *metric)?
+                .len()
+        }
+        AccessQuery::Bbox {
+            namespace,
+            min,
+            max,
+            limit,
+        } => db
+            .query_bbox(namespace, min[0], min[1], max[0], max[1], *limit)?
+            .len(),
+        AccessQuery::Bbox3d {
+            namespace,
+            min,
+            max,
+            limit,
+        } => db
+            .query_within_bbox_3d(
+                namespace, min[0], min[1], min[2], max[0], max[1], max[2], *limit,
+            )?
+            .len(),
+        AccessQuery::Cylinder {
+            namespace,
+            center,
+            min_z,
+            max_z,
+            radius,
+            limit,
+            metric,
+        } => db
+            .query_within_cylinder_with_metric(
+                namespace,
+                Point::new(center[0], center[1]),
+                *min_z,
+                *max_z,
+                *radius,
+                *limit,
+                *metric,
+            )?
+            .len(),
+        AccessQuery::Knn {
+            namespace,
+            center,
+            k,
+        } => {
+            let center = Point3d::new(center[0], center[1], center[2]);
+            db.knn(namespace, &center, *k)?.len()
+        }
+        AccessQuery::Knn2d {
+            namespace,
+            center,
+            k,
+            max_distance,
+            metric,
+        } => db
+            .knn_2d(
+                namespace,
+                &Point::new(center[0], center[1]),
+                *k,
+                *max_distance,
+                *metric,
+            )?
+            .len(),
+        AccessQuery::Polygon {
+            namespace,
+            exterior,
+            limit,
+        } => {
+            let coords: Vec<(f64, f64)> = exterior.iter().map(|c| (c[0], c[1])).collect();
+            let polygon = Polygon::from_coords(&coords, vec![]);
+            db.query_polygon(namespace, &polygon, *limit)?.len()
+        }
+        AccessQuery::Trajectory {
+            namespace,
+            object_id,
+            start_micros,
+            end_micros,
+            limit,
+        } => db
+            .query_trajectory(
+                namespace,
+                object_id,
+                from_micros(*start_micros)..=from_micros(*end_micros),
+                *limit,
+            )?
+            .len(),
+        AccessQuery::Composite {
+            namespace,
+            predicate,
+            limit,
+        } => db.query(namespace, predicate, *limit)?.len(),
+        AccessQuery::Near {
+            namespace,
+            object_id,
+            radius,
+            limit,
+        } => db.query_near(namespace, object_id, *radius, *limit)?.len(),
+        AccessQuery::BboxNear {
+            namespace,
+            object_id,
+            width,
+            height,
+            limit,
+        } => db
+            .query_bbox_near_object(namespace, object_id, *width, *height, *limit)?
+            .len(),
+        AccessQuery::Bbox3dNear {
+            namespace,
+            object_id,
+            width,
+            height,
+            depth,
+            limit,
+        } => db
+            .query_bbox_3d_near_object(namespace, object_id, *width, *height, *depth, *limit)?
+            .len(),
+        AccessQuery::CylinderNear {
+            namespace,
+            object_id,
+            min_z,
+            max_z,
+            radius,
+            limit,
+        } => db
+            .query_cylinder_near_object(namespace, object_id, *min_z, *max_z, *radius, *limit)?
+            .len(),
+        AccessQuery::KnnNear {
+            namespace,
+            object_id,
+            k,
+        } => db.knn_near_object(namespace, object_id, *k)?.len(),
+    })
+}
+
+fn parse_args() -> Result<ReplayConfig, String> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) != Some("replay") {
+        return Err(USAGE.to_string());
+    }
+    let log_path = args
+        .get(2)
+        .filter(|a| !a.starts_with('-'))
+        .cloned()
+        .ok_or_else(|| USAGE.to_string())?;
+    let flag = |names: &[&str]| {
+        args.iter()
+            .position(|a| names.contains(&a.as_str()))
+            .and_then(|i| args.get(i + 1))
+            .cloned()
+    };
+
+    Ok(ReplayConfig {
+        log_path,
+        db_path: flag(&["--db"]).ok_or_else(|| USAGE.to_string())?,
+        measurement_runs: flag(&["-r"]).and_then(|s| s.parse().ok()).unwrap_or(1),
+        quiet: args.iter().any(|a| a == "-q"),
+        json_output: flag(&["--json", "-o"]),
+    })
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config = match parse_args() {
+        Ok(config) => config,
+        Err(usage) => {
+            eprintln!("{}", usage);
+            std::process::exit(2);
+        }
+    };
+
+    let mut entries = Vec::new();
+    let mut skipped = 0usize;
+    for line in BufReader::new(File::open(&config.log_path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match AccessLogEntry::parse(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(_) => skipped += 1,
+        }
+    }
+
+    let db = DBBuilder::new().path(&config.db_path).read_only().build()?;
+
+    if !config.quiet {
+        println!("════════════════════════════════════════════════════════════════");
+        println!("  Spatio Access Log Replay");
+        println!("════════════════════════════════════════════════════════════════");
+        println!("  Access log: {}", config.log_path);
+        println!("  Database: {}", config.db_path);
+        println!(
+            "  Queries: {} ({} unparseable lines skipped)",
+            entries.len(),
+            skipped
+        );
+        println!("  Measurement runs: {}", config.measurement_runs);
+        println!("════════════════════════════════════════════════════════════════\n");
+    }
+
+    let mut latencies: BTreeMap<&'static str, Vec<Duration>> = BTreeMap::new();
+    let mut results: BTreeMap<&'static str, usize> = BTreeMap::new();
+    let mut errors = 0usize;
+
+    for _ in 0..config.measurement_runs {
+        for entry in &entries {
+            let name = op_name(&entry.query);
+            let start = Instant::now();
+            let outcome = execute(&db, &entry.query);
+            let elapsed = start.elapsed();
+            match outcome {
+                Ok(count) => {
+                    latencies.entry(name).or_default().push(elapsed);
+                    *results.entry(name).or_default() += count;
+                }
+                Err(_) => errors += 1,
+            }
+        }
+    }
+
+    let metrics: Vec<OpMetrics> = latencies
+        .into_iter()
+        .map(|(name, samples)| {
+            OpMetrics::from_samples(name, samples, results.get(name).copied().unwrap_or(0))
+        })
+        .collect();
+
+    println!("════════════════════════════════════════════════════════════════");
+    println!("  REPLAY SUMMARY");
+    println!("════════════════════════════════════════════════════════════════");
+    for m in &metrics {
+        m.print();
+    }
+    if errors > 0 {
+        println!("  {} queries failed", errors);
+    }
+    println!("════════════════════════════════════════════════════════════════\n");
+
+    if let Some(path) = config.json_output {
+        #[derive(Serialize)]
+        struct Output {
+            timestamp: String,
+            access_log: String,
+            queries: usize,
+            errors: usize,
+            ops: Vec<OpMetrics>,
+        }
+
+        let output = Output {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            access_log: config.log_path,
+            queries: entries.len(),
+            errors,
+            ops: metrics,
+        };
+        let file = File::create(&path)?;
+        serde_json::to_writer_pretty(file, &output)?;
+    }
+
+    Ok(())
+}
@@ -28,9 +28,11 @@ pub fn status_code(err: &SpatioError) -> i32 {
             SPATIO_ERR_SERIALIZATION
         }
         SpatioError::InvalidTimestamp => SPATIO_ERR_INVALID_TIMESTAMP,
-        SpatioError::InvalidInput(_) => SPATIO_ERR_INVALID_INPUT,
+        SpatioError::InvalidInput(_) | SpatioError::Validation(_) => SPATIO_ERR_INVALID_INPUT,
         SpatioError::ObjectNotFound => SPATIO_ERR_NOT_FOUND,
-        SpatioError::Io(_) => SPATIO_ERR_IO,
+        SpatioError::Io(_)
+        | SpatioError::DegradedPersistence(_)
+        | SpatioError::InsufficientDiskSpace { .. } => SPATIO_ERR_IO,
         _ => SPATIO_ERR_OTHER,
     }
 }
@@ -108,6 +110,17 @@ pub unsafe fn out_string(out: *mut *mut c_char, s: String) -> i32 {
     }
 }
 
+/// Store a write's sequence number through an optional out-param.
+///
+/// # Safety
+/// `out` must be null or a valid, writable `*mut u64`.
+pub unsafe fn out_sequence(out: *mut u64, sequence: u64) -> i32 {
+    if !out.is_null() {
+        unsafe { *out = sequence };
+    }
+    SPATIO_OK
+}
+
 /// Borrow the database behind an opaque handle.
 ///
 /// # Safety
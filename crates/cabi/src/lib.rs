@@ -34,7 +34,7 @@ mod wire;
 use ffi::*;
 use serde::Deserialize;
 use spatio::config::{Config, SetOptions};
-use spatio::db::CurrentLocation;
+use spatio::db::{CurrentLocation, NearbyHit};
 use spatio::{DistanceMetric, Point, Point3d, Polygon, Spatio, TemporalPoint};
 use spatio_types::time::system_time_from_secs;
 use std::ffi::{CString, c_char, c_void};
@@ -165,7 +165,7 @@ fn parse_metric(s: &str) -> Result<DistanceMetric, i32> {
 unsafe fn emit_neighbors(
     out_ptr: *mut *mut u8,
     out_len: *mut usize,
-    results: Vec<(Arc<CurrentLocation>, f64)>,
+    results: Vec<NearbyHit>,
 ) -> i32 {
     unsafe { emit_buffer(out_ptr, out_len, wire::encode_neighbors(&results)) }
 }
@@ -241,6 +241,7 @@ pub extern "C" fn spatio_close(handle_ptr: *mut c_void, err: *mut *mut c_char) -
 // Writes
 
 /// Upsert an object's location. `metadata_json` and `opts_json` may be null.
+/// The write's sequence number is stored in `sequence_out` unless it is null.
 #[unsafe(no_mangle)]
 pub extern "C" fn spatio_upsert(
     handle_ptr: *mut c_void,
@@ -251,6 +252,7 @@ pub extern "C" fn spatio_upsert(
     z: f64,
     metadata_json: *const c_char,
     opts_json: *const c_char,
+    sequence_out: *mut u64,
     err: *mut *mut c_char,
 ) -> i32 {
     let db = tri!(unsafe { handle(handle_ptr) }, err);
@@ -262,24 +264,26 @@ pub extern "C" fn spatio_upsert(
     );
     let opts = tri!(build_opts(tri!(unsafe { cstr_opt(opts_json) }, err)), err);
     match db.upsert(ns, id, Point3d::new(x, y, z), meta, opts) {
-        Ok(()) => SPATIO_OK,
+        Ok(sequence) => unsafe { out_sequence(sequence_out, sequence) },
         Err(e) => unsafe { report(err, &e) },
     }
 }
 
-/// Delete an object.
+/// Delete an object. The deletion's sequence number is stored in
+/// `sequence_out` unless it is null.
 #[unsafe(no_mangle)]
 pub extern "C" fn spatio_delete(
     handle_ptr: *mut c_void,
     namespace: *const c_char,
     object_id: *const c_char,
+    sequence_out: *mut u64,
     err: *mut *mut c_char,
 ) -> i32 {
     let db = tri!(unsafe { handle(handle_ptr) }, err);
     let ns = tri!(unsafe { cstr(namespace) }, err);
     let id = tri!(unsafe { cstr(object_id) }, err);
     match db.delete(ns, id) {
-        Ok(()) => SPATIO_OK,
+        Ok(sequence) => unsafe { out_sequence(sequence_out, sequence) },
         Err(e) => unsafe { report(err, &e) },
     }
 }
@@ -291,13 +295,16 @@ struct TrajInputPoint {
     t: f64,
 }
 
-/// Insert a trajectory from JSON: `[{"x":..,"y":..,"t":secs}, ...]`.
+/// Insert a trajectory from JSON: `[{"x":..,"y":..,"t":secs}, ...]`. The
+/// sequence of the last point written is stored in `sequence_out` unless it
+/// is null.
 #[unsafe(no_mangle)]
 pub extern "C" fn spatio_insert_trajectory(
     handle_ptr: *mut c_void,
     namespace: *const c_char,
     object_id: *const c_char,
     trajectory_json: *const c_char,
+    sequence_out: *mut u64,
     err: *mut *mut c_char,
 ) -> i32 {
     let db = tri!(unsafe { handle(handle_ptr) }, err);
@@ -321,7 +328,7 @@ pub extern "C" fn spatio_insert_trajectory(
         });
     }
     match db.insert_trajectory(ns, id, &trajectory) {
-        Ok(()) => SPATIO_OK,
+        Ok(sequence) => unsafe { out_sequence(sequence_out, sequence) },
         Err(e) => unsafe { report(err, &e) },
     }
 }
@@ -649,7 +656,7 @@ pub extern "C" fn spatio_query_trajectory(
         system_time_from_secs(end_secs).map_err(|_| SPATIO_ERR_INVALID_TIMESTAMP),
         err
     );
-    match db.query_trajectory(ns, id, start, end, limit) {
+    match db.query_trajectory(ns, id, start..=end, limit) {
         Ok(updates) => unsafe { emit_buffer(out_ptr, out_len, wire::encode_trajectory(&updates)) },
         Err(e) => unsafe { report(err, &e) },
     }
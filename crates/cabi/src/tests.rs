@@ -62,6 +62,7 @@ fn open_upsert_query_get_close() {
     let sf = CString::new("sf").unwrap();
     let meta = CString::new(r#"{"population":8000000}"#).unwrap();
 
+    let mut sequence: u64 = 0;
     assert_eq!(
         spatio_upsert(
             handle_ptr,
@@ -72,10 +73,12 @@ fn open_upsert_query_get_close() {
             0.0,
             meta.as_ptr(),
             ptr::null(),
+            &mut sequence,
             &mut err,
         ),
         SPATIO_OK
     );
+    assert_eq!(sequence, 1);
     assert_eq!(
         spatio_upsert(
             handle_ptr,
@@ -86,10 +89,12 @@ fn open_upsert_query_get_close() {
             0.0,
             ptr::null(),
             ptr::null(),
+            &mut sequence,
             &mut err,
         ),
         SPATIO_OK
     );
+    assert_eq!(sequence, 2);
 
     // get returns a 1-record location buffer: x y z ts, id, meta.
     let mut ptr_out: *mut u8 = ptr::null_mut();
@@ -188,7 +193,13 @@ fn errors_are_reported() {
     // null handle -> NULL_ARG with a message.
     let ns = CString::new("x").unwrap();
     let id = CString::new("y").unwrap();
-    let code = spatio_delete(ptr::null_mut(), ns.as_ptr(), id.as_ptr(), &mut err);
+    let code = spatio_delete(
+        ptr::null_mut(),
+        ns.as_ptr(),
+        id.as_ptr(),
+        ptr::null_mut(),
+        &mut err,
+    );
     assert_eq!(code, SPATIO_ERR_NULL_ARG);
     let msg = unsafe { CStr::from_ptr(err) }
         .to_string_lossy()
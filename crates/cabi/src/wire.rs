@@ -21,7 +21,7 @@
 //!
 //! `get` reuses the **location** shape with a count of 0 or 1.
 
-use spatio::db::{CurrentLocation, LocationUpdate};
+use spatio::db::{CurrentLocation, LocationUpdate, NearbyHit};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -84,9 +84,14 @@ fn write_location(w: &mut Writer, loc: &CurrentLocation) {
 }
 
 /// Encode `(location, distance)` results (radius/knn/cylinder queries).
-pub fn encode_neighbors(results: &[(Arc<CurrentLocation>, f64)]) -> Box<[u8]> {
+pub fn encode_neighbors(results: &[NearbyHit]) -> Box<[u8]> {
     let mut w = Writer::with_count(results.len());
-    for (loc, dist) in results {
+    for NearbyHit {
+        location: loc,
+        distance: dist,
+        ..
+    } in results
+    {
         w.f64(loc.position.x());
         w.f64(loc.position.y());
         w.f64(loc.position.z());
@@ -0,0 +1,399 @@
+//! `spatio-cli`: inspect and query a Spatio database from the shell.
+//!
+//! Commands run against a database opened in-process (`--data-dir`) or a
+//! running server (`--server`) and print their results as JSON. `repl` runs
+//! statements of a small query language read from standard input.
+
+mod repl;
+mod target;
+
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand};
+use spatio::HistoryCompaction;
+use spatio_server::{CurrentLocation, LocationUpdate, QueryHit};
+use spatio_types::point::Point3d;
+use spatio_types::time::TimeRange;
+use std::io::IsTerminal;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use target::Target;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Cli {
+    /// Open the database at this path
+    #[arg(
+        short,
+        long,
+        conflicts_with = "server",
+        required_unless_present = "server"
+    )]
+    data_dir: Option<PathBuf>,
+
+    /// Connect to a spatio-server at this address
+    #[arg(short, long)]
+    server: Option<SocketAddr>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print an object's current location
+    Get { namespace: String, id: String },
+    /// Insert or move an object
+    #[command(allow_negative_numbers = true)]
+    Insert {
+        namespace: String,
+        id: String,
+        lon: f64,
+        lat: f64,
+        #[arg(long, default_value_t = 0.0)]
+        alt: f64,
+        /// Metadata as a JSON value
+        #[arg(long, default_value = "{}")]
+        metadata: String,
+    },
+    /// Objects within RADIUS meters of a point, nearest first
+    #[command(allow_negative_numbers = true)]
+    Radius {
+        namespace: String,
+        lon: f64,
+        lat: f64,
+        radius: f64,
+        #[arg(long, default_value_t = 100)]
+        limit: usize,
+    },
+    /// Objects within a bounding box
+    #[command(allow_negative_numbers = true)]
+    Bbox {
+        namespace: String,
+        min_lon: f64,
+        min_lat: f64,
+        max_lon: f64,
+        max_lat: f64,
+        #[arg(long, default_value_t = 100)]
+        limit: usize,
+    },
+    /// An object's trajectory, newest first
+    Traj {
+        namespace: String,
+        id: String,
+        /// Only points at or after this Unix time, in seconds
+        #[arg(long)]
+        since: Option<u64>,
+        /// Only points before this Unix time, in seconds
+        #[arg(long)]
+        until: Option<u64>,
+        #[arg(long, default_value_t = 100)]
+        limit: usize,
+    },
+    /// Database statistics
+    Stats,
+    /// Thin a namespace's trajectory history (local databases only)
+    Compact {
+        namespace: String,
+        #[command(flatten)]
+        keep: Keep,
+    },
+    /// Write a namespace as a GeoJSON FeatureCollection (local databases only)
+    ExportGeojson {
+        namespace: String,
+        /// Write to this file instead of standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Run query-language statements read from standard input; `help` lists them
+    Repl,
+}
+
+impl Command {
+    /// Whether the command may write to the database; the others open a
+    /// local one read-only.
+    fn writes(&self) -> bool {
+        matches!(
+            self,
+            Self::Insert { .. } | Self::Compact { .. } | Self::Repl
+        )
+    }
+}
+
+/// What `compact` keeps of each object's history.
+#[derive(Args, Debug)]
+#[group(required = true, multiple = false)]
+struct Keep {
+    /// Keep the newest N points of each object
+    #[arg(long, value_name = "N")]
+    keep_last: Option<usize>,
+    /// Keep the points from the last SECS seconds
+    #[arg(long, value_name = "SECS")]
+    max_age: Option<u64>,
+}
+
+impl Keep {
+    fn policy(&self) -> HistoryCompaction {
+        match (self.keep_last, self.max_age) {
+            (Some(n), _) => HistoryCompaction::KeepLast(n),
+            (None, secs) => HistoryCompaction::KeepSince(
+                SystemTime::now() - Duration::from_secs(secs.unwrap_or_default()),
+            ),
+        }
+    }
+}
+
+fn unix(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+fn time_range(since: Option<u64>, until: Option<u64>) -> TimeRange {
+    match (since.map(unix), until.map(unix)) {
+        (Some(start), Some(end)) => TimeRange::between(start, end),
+        (Some(start), None) => TimeRange::since(start),
+        (None, Some(end)) => TimeRange::until(end),
+        (None, None) => TimeRange::all(),
+    }
+}
+
+/// Wire metadata is JSON-encoded bytes; show it as the JSON it holds.
+fn metadata(bytes: &[u8]) -> serde_json::Value {
+    serde_json::from_slice(bytes).unwrap_or(serde_json::Value::Null)
+}
+
+fn position(point: &Point3d) -> serde_json::Value {
+    serde_json::json!([point.x(), point.y(), point.z()])
+}
+
+fn location_json(loc: &CurrentLocation) -> serde_json::Value {
+    serde_json::json!({
+        "object_id": loc.object_id,
+        "position": position(&loc.position),
+        "metadata": metadata(&loc.metadata),
+    })
+}
+
+fn hit_json(hit: &QueryHit) -> serde_json::Value {
+    serde_json::json!({
+        "object_id": hit.object_id,
+        "position": position(&hit.position),
+        "metadata": metadata(&hit.metadata),
+        "distance": hit.distance,
+    })
+}
+
+fn update_json(update: &LocationUpdate) -> serde_json::Value {
+    serde_json::json!({
+        "timestamp": update.timestamp,
+        "position": position(&update.position),
+        "metadata": metadata(&update.metadata),
+    })
+}
+
+/// Run `command` against `target`, returning what to print.
+async fn run(target: &Target, command: Command) -> Result<serde_json::Value> {
+    Ok(match command {
+        Command::Get { namespace, id } => match target.get(&namespace, &id).await? {
+            Some(loc) => location_json(&loc),
+            None => anyhow::bail!("no object {id} in {namespace}"),
+        },
+        Command::Insert {
+            namespace,
+            id,
+            lon,
+            lat,
+            alt,
+            metadata,
+        } => {
+            let metadata = serde_json::from_str(&metadata)
+                .map_err(|e| anyhow::anyhow!("--metadata is not valid JSON: {e}"))?;
+            let sequence = target
+                .insert(&namespace, &id, Point3d::new(lon, lat, alt), metadata)
+                .await?;
+            serde_json::json!({ "sequence": sequence })
+        }
+        Command::Radius {
+            namespace,
+            lon,
+            lat,
+            radius,
+            limit,
+        } => {
+            let center = Point3d::new(lon, lat, 0.0);
+            let hits = target.radius(&namespace, center, radius, limit).await?;
+            hits.iter().map(hit_json).collect()
+        }
+        Command::Bbox {
+            namespace,
+            min_lon,
+            min_lat,
+            max_lon,
+            max_lat,
+            limit,
+        } => target
+            .bbox(&namespace, [min_lon, min_lat], [max_lon, max_lat], limit)
+            .await?
+            .iter()
+            .map(location_json)
+            .collect(),
+        Command::Traj {
+            namespace,
+            id,
+            since,
+            until,
+            limit,
+        } => target
+            .trajectory(&namespace, &id, time_range(since, until), limit)
+            .await?
+            .iter()
+            .map(update_json)
+            .collect(),
+        Command::Stats => serde_json::to_value(target.stats().await?)?,
+        Command::Compact { namespace, keep } => {
+            let removed = target.compact(&namespace, keep.policy())?;
+            serde_json::json!({ "records_removed": removed })
+        }
+        Command::ExportGeojson { namespace, output } => {
+            let collection = target.export_geojson(&namespace)?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, serde_json::to_vec(&collection)?)?;
+                    let features = collection["features"].as_array().map_or(0, Vec::len);
+                    serde_json::json!({ "features": features, "path": path })
+                }
+                None => collection,
+            }
+        }
+        Command::Repl => anyhow::bail!("`repl` runs on its own, not as a statement"),
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let target = match (&cli.data_dir, cli.server) {
+        (Some(path), _) => Target::open(path, !cli.command.writes())?,
+        (None, Some(addr)) => Target::connect(addr).await?,
+        (None, None) => unreachable!("clap requires --data-dir or --server"),
+    };
+    let result = match cli.command {
+        Command::Repl => {
+            let stdin = std::io::stdin();
+            let prompt = stdin.is_terminal();
+            repl::run(&target, stdin.lock(), std::io::stdout(), prompt).await
+        }
+        command => run(&target, command).await.and_then(|value| {
+            println!("{}", serde_json::to_string_pretty(&value)?);
+            Ok(())
+        }),
+    };
+    target.close()?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_commands_on_local_database() {
+        let target = Target::memory().unwrap();
+        let insert = |id: &str, lon: f64| Command::Insert {
+            namespace: "fleet".into(),
+            id: id.into(),
+            lon,
+            lat: 40.7,
+            alt: 0.0,
+            metadata: r#"{"kind": "van"}"#.into(),
+        };
+        run(&target, insert("van-1", -74.0)).await.unwrap();
+        run(&target, insert("van-2", -73.9)).await.unwrap();
+
+        let found = run(
+            &target,
+            Command::Get {
+                namespace: "fleet".into(),
+                id: "van-1".into(),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(found["metadata"]["kind"], "van");
+        assert_eq!(found["position"][0], -74.0);
+
+        let hits = run(
+            &target,
+            Command::Radius {
+                namespace: "fleet".into(),
+                lon: -74.0,
+                lat: 40.7,
+                radius: 1000.0,
+                limit: 10,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(hits.as_array().unwrap().len(), 1);
+        assert_eq!(hits[0]["object_id"], "van-1");
+
+        let boxed = run(
+            &target,
+            Command::Bbox {
+                namespace: "fleet".into(),
+                min_lon: -75.0,
+                min_lat: 40.0,
+                max_lon: -73.0,
+                max_lat: 41.0,
+                limit: 10,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(boxed.as_array().unwrap().len(), 2);
+
+        let stats = run(&target, Command::Stats).await.unwrap();
+        assert_eq!(stats["object_count"], 2);
+
+        let geojson = run(
+            &target,
+            Command::ExportGeojson {
+                namespace: "fleet".into(),
+                output: None,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(geojson["type"], "FeatureCollection");
+
+        let missing = run(
+            &target,
+            Command::Get {
+                namespace: "fleet".into(),
+                id: "van-3".into(),
+            },
+        )
+        .await;
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_parses_negative_coordinates() {
+        let cli = Cli::try_parse_from([
+            "spatio-cli",
+            "--data-dir",
+            "fleet.db",
+            "radius",
+            "fleet",
+            "-74.0",
+            "40.7",
+            "500",
+        ])
+        .unwrap();
+        assert!(matches!(cli.command, Command::Radius { lon, .. } if lon == -74.0));
+
+        // Exactly one of --data-dir and --server.
+        assert!(Cli::try_parse_from(["spatio-cli", "stats"]).is_err());
+        // `compact` needs to be told what to keep.
+        assert!(Cli::try_parse_from(["spatio-cli", "-d", "fleet.db", "compact", "fleet"]).is_err());
+    }
+}
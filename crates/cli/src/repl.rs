@@ -0,0 +1,152 @@
+//! `spatio-cli repl`: run [statements](spatio_types::statement) a line at a
+//! time and print each result as JSON.
+//!
+//! A failing statement prints its error and the session goes on, so a file
+//! of statements can be piped in as a script.
+
+use crate::target::Target;
+use crate::{hit_json, location_json, update_json};
+use anyhow::Result;
+use spatio_types::statement::Statement;
+use std::io::{BufRead, Write};
+
+const HELP: &str = "\
+Statements (keywords in any case; distances in meters, times in Unix seconds):
+  GET <ns> <id>
+  NEARBY <ns> POINT(<lon> <lat>) <meters> [LIMIT n]
+  NEAREST <ns> POINT(<lon> <lat>) <k>
+  WITHIN <ns> BOX(<min_lon> <min_lat>, <max_lon> <max_lat>) [LIMIT n]
+  HISTORY <ns> <id> [SINCE t | LAST secs] [UNTIL t] [LIMIT n]
+  INSERT <ns> <id> POINT(<lon> <lat>) [metadata JSON]
+Lines starting with -- are comments. `help` shows this, `quit` leaves.";
+
+/// Read statements from `input` until it ends or says `quit`, writing
+/// results and errors to `output`. `prompt` when a person is typing.
+pub async fn run(
+    target: &Target,
+    input: impl BufRead,
+    mut output: impl Write,
+    prompt: bool,
+) -> Result<()> {
+    let mut lines = input.lines();
+    loop {
+        if prompt {
+            write!(output, "spatio> ")?;
+            output.flush()?;
+        }
+        let Some(line) = lines.next() else {
+            break;
+        };
+        let line = line?;
+        let line = line.trim();
+        match line.to_ascii_lowercase().as_str() {
+            "" => continue,
+            "help" => writeln!(output, "{HELP}")?,
+            "quit" | "exit" => break,
+            _ if line.starts_with("--") => continue,
+            _ => match execute(target, line).await {
+                Ok(result) => writeln!(output, "{}", serde_json::to_string_pretty(&result)?)?,
+                Err(e) => writeln!(output, "error: {e:#}")?,
+            },
+        }
+    }
+    Ok(())
+}
+
+async fn execute(target: &Target, line: &str) -> Result<serde_json::Value> {
+    Ok(match line.parse::<Statement>()? {
+        Statement::Get {
+            namespace,
+            object_id,
+        } => match target.get(&namespace, &object_id).await? {
+            Some(loc) => location_json(&loc),
+            None => serde_json::Value::Null,
+        },
+        Statement::Nearby(query) => target
+            .radius(&query.namespace, query.center, query.radius, query.limit)
+            .await?
+            .iter()
+            .map(hit_json)
+            .collect(),
+        Statement::Nearest(query) => target
+            .nearest(&query.namespace, query.center, query.k)
+            .await?
+            .iter()
+            .map(hit_json)
+            .collect(),
+        Statement::Within {
+            namespace,
+            bbox,
+            limit,
+        } => target
+            .bbox(
+                &namespace,
+                [bbox.min_x(), bbox.min_y()],
+                [bbox.max_x(), bbox.max_y()],
+                limit,
+            )
+            .await?
+            .iter()
+            .map(location_json)
+            .collect(),
+        Statement::History(slice) => target
+            .trajectory(&slice.namespace, &slice.object_id, slice.range, slice.limit)
+            .await?
+            .iter()
+            .map(update_json)
+            .collect(),
+        Statement::Insert {
+            namespace,
+            object_id,
+            position,
+            metadata,
+        } => {
+            let sequence = target
+                .insert(&namespace, &object_id, position, metadata)
+                .await?;
+            serde_json::json!({ "sequence": sequence })
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_runs_a_script() {
+        let target = Target::memory().unwrap();
+        let script = r#"
+-- two vans in Manhattan
+INSERT fleet van-1 POINT(-74.0 40.7) {"kind": "van"}
+insert fleet van-2 POINT(-73.9 40.7)
+NEARBY fleet POINT(-74 40.7) 500 LIMIT 10
+NEAREST fleet POINT(-74 40.7) 2
+WITHIN fleet BOX(-75 40, -73 41)
+GET fleet van-3
+NEARBY fleet 500
+quit
+GET fleet van-1
+"#;
+        let mut output = Vec::new();
+        run(&target, script.as_bytes(), &mut output, false)
+            .await
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        let results: Vec<serde_json::Value> =
+            serde_json::Deserializer::from_str(output.split("error:").next().unwrap())
+                .into_iter()
+                .collect::<Result<_, _>>()
+                .unwrap();
+        assert_eq!(results.len(), 6);
+        assert_eq!(results[2].as_array().unwrap().len(), 1);
+        assert_eq!(results[2][0]["metadata"]["kind"], "van");
+        assert_eq!(results[3][1]["object_id"], "van-2");
+        assert_eq!(results[4].as_array().unwrap().len(), 2);
+        assert!(results[5].is_null());
+        // The bad statement is reported and nothing after `quit` runs.
+        assert_eq!(output.matches("error: Syntax error").count(), 1);
+        assert!(output.trim_end().ends_with(r#"expected POINT(...)"#));
+    }
+}
@@ -0,0 +1,185 @@
+//! What the CLI runs commands against: a database opened in this process, or
+//! a server reached over RPC.
+//!
+//! Both answer with the server's wire types, so a command prints the same
+//! thing either way. Local reads go through [`Reader`], the code the server
+//! itself answers with.
+
+use anyhow::{Context, Result, bail};
+use spatio::{HistoryCompaction, Spatio};
+use spatio_client::SpatioClient;
+use spatio_server::reader::Reader;
+use spatio_server::{
+    CurrentLocation, KnnQuery, LocationUpdate, QueryHit, RadiusQuery, Stats, TrajectorySlice,
+};
+use spatio_types::point::Point3d;
+use spatio_types::time::TimeRange;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+
+pub enum Target {
+    Local { db: Arc<Spatio>, reader: Reader },
+    Remote(SpatioClient),
+}
+
+impl Target {
+    /// Open the database at `path`, which must already exist. A read-only
+    /// open writes nothing to the data directory and can share it with other
+    /// readers, but not with a writer.
+    pub fn open(path: &Path, read_only: bool) -> Result<Self> {
+        anyhow::ensure!(path.exists(), "no database at {}", path.display());
+        let builder = Spatio::builder().path(path);
+        let builder = if read_only {
+            builder.read_only()
+        } else {
+            builder
+        };
+        let db = Arc::new(builder.build()?);
+        Ok(Self::Local {
+            reader: Reader::new(db.clone()),
+            db,
+        })
+    }
+
+    /// A database in memory, for tests.
+    #[cfg(test)]
+    pub fn memory() -> Result<Self> {
+        let db = Arc::new(Spatio::memory()?);
+        Ok(Self::Local {
+            reader: Reader::new(db.clone()),
+            db,
+        })
+    }
+
+    pub async fn connect(addr: SocketAddr) -> Result<Self> {
+        let client = SpatioClient::connect(addr)
+            .await
+            .with_context(|| format!("connecting to {addr}"))?;
+        Ok(Self::Remote(client))
+    }
+
+    /// The local database, for commands the server doesn't expose.
+    fn local(&self, command: &str) -> Result<&Spatio> {
+        match self {
+            Self::Local { db, .. } => Ok(db),
+            Self::Remote(_) => bail!("`{command}` needs a local database (--data-dir)"),
+        }
+    }
+
+    pub async fn get(&self, namespace: &str, id: &str) -> Result<Option<CurrentLocation>> {
+        match self {
+            Self::Local { reader, .. } => reader.get(namespace, id).map_err(anyhow::Error::msg),
+            Self::Remote(client) => Ok(client.get(namespace, id).await?),
+        }
+    }
+
+    /// Upsert an object, returning the write's sequence.
+    pub async fn insert(
+        &self,
+        namespace: &str,
+        id: &str,
+        position: Point3d,
+        metadata: serde_json::Value,
+    ) -> Result<u64> {
+        match self {
+            Self::Local { db, .. } => Ok(db.upsert(namespace, id, position, metadata, None)?),
+            Self::Remote(client) => Ok(client.upsert(namespace, id, position, metadata).await?),
+        }
+    }
+
+    pub async fn radius(
+        &self,
+        namespace: &str,
+        center: Point3d,
+        radius: f64,
+        limit: usize,
+    ) -> Result<Vec<QueryHit>> {
+        match self {
+            Self::Local { reader, .. } => reader
+                .query_radius(&RadiusQuery::new(namespace, center, radius, limit))
+                .map_err(anyhow::Error::msg),
+            Self::Remote(client) => Ok(client
+                .query_radius(namespace, center, radius, limit, None)
+                .await?),
+        }
+    }
+
+    pub async fn bbox(
+        &self,
+        namespace: &str,
+        min: [f64; 2],
+        max: [f64; 2],
+        limit: usize,
+    ) -> Result<Vec<CurrentLocation>> {
+        let [min_x, min_y] = min;
+        let [max_x, max_y] = max;
+        match self {
+            Self::Local { reader, .. } => reader
+                .query_bbox(namespace, min_x, min_y, max_x, max_y, limit)
+                .map_err(anyhow::Error::msg),
+            Self::Remote(client) => Ok(client
+                .query_bbox(namespace, min_x, min_y, max_x, max_y, limit)
+                .await?),
+        }
+    }
+
+    /// The `k` objects nearest to `center`, nearest first.
+    pub async fn nearest(
+        &self,
+        namespace: &str,
+        center: Point3d,
+        k: usize,
+    ) -> Result<Vec<QueryHit>> {
+        match self {
+            Self::Local { reader, .. } => reader
+                .knn(&KnnQuery::new(namespace, center, k))
+                .map_err(anyhow::Error::msg),
+            Self::Remote(client) => Ok(client.knn(namespace, center, k).await?),
+        }
+    }
+
+    /// Points of an object's trajectory within `range`, newest first.
+    pub async fn trajectory(
+        &self,
+        namespace: &str,
+        id: &str,
+        range: TimeRange,
+        limit: usize,
+    ) -> Result<Vec<LocationUpdate>> {
+        match self {
+            Self::Local { reader, .. } => reader
+                .query_trajectory(&TrajectorySlice::new(namespace, id, range, limit))
+                .map_err(anyhow::Error::msg),
+            Self::Remote(client) => {
+                Ok(client.query_trajectory(namespace, id, range, limit).await?)
+            }
+        }
+    }
+
+    pub async fn stats(&self) -> Result<Stats> {
+        match self {
+            Self::Local { reader, .. } => Ok(reader.stats()),
+            Self::Remote(client) => Ok(client.stats().await?),
+        }
+    }
+
+    /// Thin the history of `namespace`, returning the log records removed.
+    pub fn compact(&self, namespace: &str, policy: HistoryCompaction) -> Result<u64> {
+        Ok(self.local("compact")?.compact_history(namespace, policy)?)
+    }
+
+    /// Everything in `namespace` as a GeoJSON `FeatureCollection`.
+    pub fn export_geojson(&self, namespace: &str) -> Result<serde_json::Value> {
+        let collection = self.local("export-geojson")?.export_geojson(namespace)?;
+        Ok(serde_json::to_value(collection)?)
+    }
+
+    /// Flush and close a local database; nothing to do for a server.
+    pub fn close(&self) -> Result<()> {
+        if let Self::Local { db, .. } = self {
+            db.close()?;
+        }
+        Ok(())
+    }
+}
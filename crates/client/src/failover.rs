@@ -0,0 +1,271 @@
+//! A client that follows the primary of a primary/replica deployment.
+//!
+//! [`FailoverClient`] is given some of the deployment's servers as seeds. It
+//! asks them for their [`Topology`] and connects to the primary: the server
+//! claiming that role in the highest failover term. [`FailoverClient::call`]
+//! runs requests there, reads included.
+//!
+//! A write refused by a server that has become a replica is sent again to
+//! the primary named in the refusal. When the primary stops answering, the
+//! seeds and the servers heard of since are asked again, backing off while
+//! none has taken over yet. Either way this happens up to
+//! [`FailoverConfig::max_attempts`] times, so callers see a failover as a
+//! slow request rather than an error.
+//!
+//! Only requests that can't have been applied are sent again: refused ones
+//! and ones that never reached the server. A request lost along with its
+//! connection fails with its error; use the idempotent write methods to
+//! retry those safely.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use spatio_client::FailoverClient;
+//!
+//! let seeds = vec!["10.0.0.1:3000".parse()?, "10.0.0.2:3000".parse()?];
+//! let client = FailoverClient::connect(seeds).await?;
+//! client
+//!     .call(|c| async move { c.upsert("fleet", "van", point, metadata).await })
+//!     .await?;
+//! ```
+
+use crate::{ClientError, Result, SpatioClient};
+use futures::future::join_all;
+use spatio_server::{Role, Topology};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// How a [`FailoverClient`] finds the primary and retries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FailoverConfig {
+    /// How long asking one server for its topology may take, connecting
+    /// included.
+    pub connect_timeout: Duration,
+    /// Wait after a round of discovery that found no primary; doubled after
+    /// each further one up to `max_backoff`.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Rounds of discovery, and sends of a refused or unsent request, before
+    /// giving up with the last error.
+    pub max_attempts: u32,
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(2),
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(2),
+            max_attempts: 8,
+        }
+    }
+}
+
+/// A client of whichever server is the primary (see the
+/// [module docs](self)). Cloning shares the connection.
+#[derive(Clone)]
+pub struct FailoverClient {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    config: FailoverConfig,
+    state: Mutex<State>,
+}
+
+struct State {
+    /// Seeds first, then servers named as primary since.
+    known: Vec<SocketAddr>,
+    primary: Option<Primary>,
+    /// Bumped on every switch of primary, so a failure reported against an
+    /// old one doesn't drop its successor.
+    generation: u64,
+}
+
+#[derive(Clone)]
+struct Primary {
+    addr: SocketAddr,
+    term: u64,
+    client: SpatioClient,
+    generation: u64,
+}
+
+impl FailoverClient {
+    /// Find the primary among `seeds` with the default [`FailoverConfig`].
+    pub async fn connect(seeds: Vec<SocketAddr>) -> Result<Self> {
+        Self::with_config(seeds, FailoverConfig::default()).await
+    }
+
+    /// Find the primary among `seeds`. Fails if none of them is or knows of
+    /// one within `config.max_attempts` rounds of discovery.
+    pub async fn with_config(seeds: Vec<SocketAddr>, config: FailoverConfig) -> Result<Self> {
+        let mut known = Vec::new();
+        for seed in seeds {
+            if !known.contains(&seed) {
+                known.push(seed);
+            }
+        }
+        let client = Self {
+            inner: Arc::new(Inner {
+                config,
+                state: Mutex::new(State {
+                    known,
+                    primary: None,
+                    generation: 0,
+                }),
+            }),
+        };
+        client.inner.primary().await?;
+        Ok(client)
+    }
+
+    /// Run `request` on the primary. If the server turns out to be a
+    /// replica, or the connection fails before the request is sent, the
+    /// primary is found again and the request sent there, up to
+    /// [`FailoverConfig::max_attempts`] times. A request the primary refuses
+    /// as busy is sent again after [`ClientError::retry_after`].
+    pub async fn call<T, F, Fut>(&self, request: F) -> Result<T>
+    where
+        F: Fn(SpatioClient) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let primary = self.inner.primary().await?;
+            let error = match request(primary.client.clone()).await {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+            let retry = match &error {
+                ClientError::NotPrimary { primary: hint, .. } => {
+                    tracing::debug!(addr = %primary.addr, "no longer the primary");
+                    self.inner.demote(&primary, *hint).await;
+                    true
+                }
+                ClientError::Busy { retry_after, .. } => {
+                    tokio::time::sleep((*retry_after).min(self.inner.config.max_backoff)).await;
+                    true
+                }
+                error if error.is_disconnect() => {
+                    self.inner.demote(&primary, None).await;
+                    error.is_unsent()
+                }
+                _ => false,
+            };
+            if !retry || attempt >= self.inner.config.max_attempts {
+                return Err(error);
+            }
+            attempt += 1;
+        }
+    }
+
+    /// The primary requests currently go to, if one is known.
+    pub async fn primary(&self) -> Option<SocketAddr> {
+        let state = self.inner.state.lock().await;
+        state.primary.as_ref().map(|primary| primary.addr)
+    }
+}
+
+impl Inner {
+    /// The known primary, or the one discovery finds, backing off between
+    /// rounds that find none.
+    async fn primary(&self) -> Result<Primary> {
+        let mut state = self.state.lock().await;
+        if let Some(primary) = &state.primary {
+            return Ok(primary.clone());
+        }
+        let mut backoff = self.config.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            match self.discover(&state.known).await {
+                Ok((addr, term, client)) => {
+                    state.generation += 1;
+                    let primary = Primary {
+                        addr,
+                        term,
+                        client,
+                        generation: state.generation,
+                    };
+                    tracing::debug!(%addr, term, "found the primary");
+                    state.primary = Some(primary.clone());
+                    return Ok(primary);
+                }
+                Err(e) if attempt >= self.config.max_attempts => return Err(e),
+                Err(e) => {
+                    tracing::debug!(attempt, "no primary found: {e}");
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.config.max_backoff);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Ask every server in `known` for its topology and connect to the one
+    /// claiming to be primary in the highest term.
+    async fn discover(&self, known: &[SocketAddr]) -> Result<(SocketAddr, u64, SpatioClient)> {
+        let reports = join_all(known.iter().map(|&addr| self.ask(addr))).await;
+        let mut best: Option<(SocketAddr, u64, SpatioClient)> = None;
+        let mut last_error = None;
+        for (addr, report) in known.iter().zip(reports) {
+            match report {
+                Ok((
+                    Topology {
+                        role: Role::Primary,
+                        term,
+                        ..
+                    },
+                    client,
+                )) if best.as_ref().is_none_or(|(_, best, _)| term > *best) => {
+                    best = Some((*addr, term, client));
+                }
+                Ok(_) => {}
+                Err(e) => last_error = Some(e),
+            }
+        }
+        best.ok_or_else(|| {
+            last_error.unwrap_or_else(|| {
+                ClientError::Connection(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "no server claims to be the primary",
+                ))
+            })
+        })
+    }
+
+    async fn ask(&self, addr: SocketAddr) -> Result<(Topology, SpatioClient)> {
+        let ask = async {
+            let client = SpatioClient::connect(addr).await?;
+            Ok((client.topology().await?, client))
+        };
+        match tokio::time::timeout(self.config.connect_timeout, ask).await {
+            Ok(result) => result,
+            Err(_) => Err(ClientError::Connection(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("asking {addr} for its topology timed out"),
+            ))),
+        }
+    }
+
+    /// Stop sending to `primary`, unless it was replaced already, and
+    /// remember the server named in its place.
+    async fn demote(&self, primary: &Primary, successor: Option<SocketAddr>) {
+        let mut state = self.state.lock().await;
+        if let Some(successor) = successor
+            && !state.known.contains(&successor)
+        {
+            state.known.push(successor);
+        }
+        if state
+            .primary
+            .as_ref()
+            .is_some_and(|current| current.generation == primary.generation)
+        {
+            tracing::debug!(addr = %primary.addr, term = primary.term, "dropping the primary");
+            state.primary = None;
+        }
+    }
+}
@@ -11,10 +11,35 @@
 //! client.upsert("ns", "id", point, metadata).await?;
 //! ```
 
+mod failover;
+mod pool;
+mod session;
 mod transport;
 
 // Re-export transport
-pub use transport::rpc::{ClientError, Result, SpatioClient};
+pub use failover::{FailoverClient, FailoverConfig};
+pub use pool::{PoolConfig, SpatioClientPool};
+pub use session::{Session, SessionToken};
+pub use transport::rpc::{ClientError, Result, SpatioClient, WARM_UP_TIMEOUT, with_traceparent};
+
+#[cfg(feature = "grpc")]
+pub use transport::grpc::GrpcClient;
+
+/// The rustls version [`SpatioClient::connect_tls`] takes its config from.
+#[cfg(feature = "tls")]
+pub use spatio_server::rustls;
+
+// W3C `traceparent` conversions for bridging HTTP tracing into RPC calls
+pub use spatio_server::trace_context;
 
 // Re-export server types for convenience
-pub use spatio_server::{CurrentLocation, LocationUpdate, Stats};
+pub use spatio_server::{
+    AggregateCell, BboxPage, CurrentLocation, HistoryEntry, KnnQuery, LocationUpdate, Page,
+    QueryArgs, QueryHit, QueryTemplate, RadiusQuery, RegionEvent, Role, Stats, Topology,
+    TrajectorySlice, WarmUpReport,
+};
+pub use spatio_types::config::{HistoryEventKind, ScanDirection};
+pub use spatio_types::geo::DistanceMetric;
+pub use spatio_types::query::Predicate;
+pub use spatio_types::time::{TimeBound, TimeRange};
+pub use spatio_types::trajectory::TrajectorySummary;
@@ -0,0 +1,319 @@
+//! A pool of RPC connections that reconnects on its own.
+//!
+//! [`SpatioClientPool`] keeps `size` connections to one server and hands
+//! them out round-robin. Each connection multiplexes any number of requests
+//! in flight, so concurrent callers pipeline over the pool rather than wait
+//! for one another.
+//!
+//! A connection that fails is dropped and dialed again on next use, backing
+//! off exponentially while the server is down; a background task also checks
+//! idle connections periodically. [`SpatioClientPool::call`] retries a
+//! request on a fresh connection when it failed before reaching the server,
+//! so a server restart is invisible to callers beyond the reconnect delay.
+//! Requests already sent when a connection drops fail with their error,
+//! since they may have been applied; use the idempotent write methods to
+//! retry those safely. Requests the server refuses as busy while it
+//! compacts or checkpoints its log are run again after the delay it asks
+//! for, so writers slow down instead of queueing behind the rewrite.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use spatio_client::SpatioClientPool;
+//!
+//! let pool = SpatioClientPool::new("127.0.0.1:3000".parse()?, 4).await?;
+//! let stats = pool.call(|client| async move { client.stats().await }).await?;
+//! ```
+
+use crate::{ClientError, Result, SpatioClient};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// How a [`SpatioClientPool`] connects, reconnects and checks connections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// Connections kept open.
+    pub size: usize,
+    /// How long one connection attempt may take.
+    pub connect_timeout: Duration,
+    /// Wait before the second connection attempt; doubled after each
+    /// further failure up to `max_backoff`.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Connection attempts, and retries of unsent or busy requests, before a
+    /// call gives up with the last error. A busy request waits as long as the
+    /// server asks, up to `max_backoff`.
+    pub max_attempts: u32,
+    /// How often idle connections are checked, and broken ones redialed;
+    /// `None` checks them only when used.
+    pub health_check_interval: Option<Duration>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            size: 4,
+            connect_timeout: Duration::from_secs(5),
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(5),
+            max_attempts: 8,
+            health_check_interval: Some(Duration::from_secs(10)),
+        }
+    }
+}
+
+/// A pool of [`SpatioClient`] connections to one server (see the
+/// [module docs](self)). Cloning shares the pool.
+#[derive(Clone)]
+pub struct SpatioClientPool {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    addr: SocketAddr,
+    config: PoolConfig,
+    slots: Vec<Mutex<Slot>>,
+    next: AtomicUsize,
+}
+
+#[derive(Default)]
+struct Slot {
+    client: Option<SpatioClient>,
+    /// Bumped on every reconnect, so a failure reported against an old
+    /// connection doesn't drop its replacement.
+    generation: u64,
+}
+
+/// A connection handed out by [`Inner::checkout`].
+struct Checkout {
+    slot: usize,
+    generation: u64,
+    client: SpatioClient,
+}
+
+impl SpatioClientPool {
+    /// Open `size` connections to `addr` with the default [`PoolConfig`].
+    pub async fn new(addr: SocketAddr, size: usize) -> Result<Self> {
+        Self::with_config(
+            addr,
+            PoolConfig {
+                size,
+                ..PoolConfig::default()
+            },
+        )
+        .await
+    }
+
+    /// Open `config.size` connections to `addr`. Fails if the server can't be
+    /// reached at all; connections that fail individually are retried later.
+    pub async fn with_config(addr: SocketAddr, config: PoolConfig) -> Result<Self> {
+        let size = config.size.max(1);
+        let inner = Arc::new(Inner {
+            addr,
+            config,
+            slots: (0..size).map(|_| Mutex::default()).collect(),
+            next: AtomicUsize::new(0),
+        });
+
+        let mut connected = 0;
+        let mut last_error = None;
+        for slot in &inner.slots {
+            match inner.connect().await {
+                Ok(client) => {
+                    slot.lock().await.client = Some(client);
+                    connected += 1;
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        if connected == 0
+            && let Some(e) = last_error
+        {
+            return Err(e);
+        }
+
+        if let Some(interval) = config.health_check_interval {
+            tokio::spawn(health_check(Arc::downgrade(&inner), interval));
+        }
+        Ok(Self { inner })
+    }
+
+    /// A connection from the pool, redialed first if it was found broken.
+    ///
+    /// Errors from requests made on it aren't seen by the pool; prefer
+    /// [`SpatioClientPool::call`], which reports them and retries.
+    pub async fn client(&self) -> Result<SpatioClient> {
+        Ok(self.inner.checkout().await?.client)
+    }
+
+    /// Run `request` on a pooled connection. If it fails because the
+    /// connection is gone, the connection is replaced, and a request that
+    /// never reached the server is run again on another connection. One the
+    /// server refused as busy is run again after [`ClientError::retry_after`].
+    /// Either way, up to [`PoolConfig::max_attempts`] times.
+    pub async fn call<T, F, Fut>(&self, request: F) -> Result<T>
+    where
+        F: Fn(SpatioClient) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let checkout = self.inner.checkout().await?;
+            let error = match request(checkout.client.clone()).await {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+            if error.is_disconnect() {
+                self.inner.discard(&checkout).await;
+            }
+            let busy = error.retry_after();
+            if !(error.is_unsent() || busy.is_some()) || attempt >= self.inner.config.max_attempts {
+                return Err(error);
+            }
+            if let Some(wait) = busy {
+                tokio::time::sleep(wait.min(self.inner.config.max_backoff)).await;
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Number of connections currently open.
+    pub async fn connected(&self) -> usize {
+        let mut connected = 0;
+        for slot in &self.inner.slots {
+            connected += usize::from(slot.lock().await.client.is_some());
+        }
+        connected
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.inner.addr
+    }
+}
+
+impl Inner {
+    async fn connect(&self) -> Result<SpatioClient> {
+        match tokio::time::timeout(
+            self.config.connect_timeout,
+            SpatioClient::connect(self.addr),
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(_) => Err(ClientError::Connection(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("connecting to {} timed out", self.addr),
+            ))),
+        }
+    }
+
+    /// The next connection in turn, skipping broken ones and ones being
+    /// redialed. When none is open, the next one is redialed with backoff.
+    async fn checkout(&self) -> Result<Checkout> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        for offset in 0..self.slots.len() {
+            let index = (start + offset) % self.slots.len();
+            let Ok(slot) = self.slots[index].try_lock() else {
+                continue;
+            };
+            if let Some(client) = &slot.client {
+                return Ok(Checkout {
+                    slot: index,
+                    generation: slot.generation,
+                    client: client.clone(),
+                });
+            }
+        }
+
+        let index = start % self.slots.len();
+        let mut slot = self.slots[index].lock().await;
+        // Redialed by someone else while we waited for the lock.
+        if let Some(client) = &slot.client {
+            return Ok(Checkout {
+                slot: index,
+                generation: slot.generation,
+                client: client.clone(),
+            });
+        }
+        let client = self.reconnect().await?;
+        slot.client = Some(client.clone());
+        slot.generation += 1;
+        Ok(Checkout {
+            slot: index,
+            generation: slot.generation,
+            client,
+        })
+    }
+
+    /// Dial until connected, sleeping between attempts with exponential
+    /// backoff, or fail after `max_attempts` attempts.
+    async fn reconnect(&self) -> Result<SpatioClient> {
+        let mut backoff = self.config.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            match self.connect().await {
+                Ok(client) => return Ok(client),
+                Err(e) if attempt >= self.config.max_attempts => return Err(e),
+                Err(e) => {
+                    tracing::debug!(addr = %self.addr, attempt, "reconnect failed: {e}");
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.config.max_backoff);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Drop the connection of `checkout`, unless it was replaced already.
+    async fn discard(&self, checkout: &Checkout) {
+        let mut slot = self.slots[checkout.slot].lock().await;
+        if slot.generation == checkout.generation {
+            slot.client = None;
+        }
+    }
+}
+
+/// Every `interval`, check each open connection with a `stats` request and
+/// make one attempt to redial each broken one, until the pool is dropped.
+async fn health_check(pool: Weak<Inner>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let Some(inner) = pool.upgrade() else {
+            return;
+        };
+        for index in 0..inner.slots.len() {
+            let open = {
+                let slot = inner.slots[index].lock().await;
+                slot.client.clone().map(|client| Checkout {
+                    slot: index,
+                    generation: slot.generation,
+                    client,
+                })
+            };
+            match open {
+                Some(checkout) => {
+                    let check = tokio::time::timeout(interval, checkout.client.stats()).await;
+                    if !matches!(check, Ok(Ok(_))) {
+                        tracing::debug!(addr = %inner.addr, "dropping unhealthy connection");
+                        inner.discard(&checkout).await;
+                    }
+                }
+                None => {
+                    if let Ok(client) = inner.connect().await {
+                        let mut slot = inner.slots[index].lock().await;
+                        if slot.client.is_none() {
+                            slot.client = Some(client);
+                            slot.generation += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
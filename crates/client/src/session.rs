@@ -0,0 +1,133 @@
+//! Reading your own writes.
+//!
+//! Every write returns its sequence in the log of the server that took it.
+//! A [`Session`] keeps a [`SessionToken`] naming that log
+//! ([`SpatioClient::log_id`]) and the highest sequence its writes returned.
+//! Before each read it asks the server to wait until it has applied that
+//! write ([`SpatioClient::wait_applied`]), so reads in the session see its
+//! writes even when they race them on other connections. Reads also raise
+//! the token to the sequence the server had applied, so a later read never
+//! sees an older state than an earlier one did.
+//!
+//! Spatio doesn't copy writes between servers, so a token only holds on the
+//! server whose log issued it, or on a copy of that log's data directory. A
+//! server with another log refuses it with [`ClientError::ForeignLog`]
+//! instead of answering from a state that may not have the write. A copy
+//! that hasn't caught up within its configured wait fails the read with
+//! [`ClientError::StaleReplica`].
+//!
+//! # Example
+//!
+//! ```ignore
+//! use spatio_client::Session;
+//!
+//! let session = Session::new();
+//! session.write(&client, client.upsert("fleet", "van", point, metadata)).await?;
+//! let van = session.read(&client, |c| async move { c.get("fleet", "van").await }).await?;
+//! ```
+//!
+//! [`ClientError::ForeignLog`]: crate::ClientError::ForeignLog
+//! [`ClientError::StaleReplica`]: crate::ClientError::StaleReplica
+
+use crate::{Result, SpatioClient};
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// The write a session's reads wait for: `sequence` in log `log_id`.
+///
+/// Formats as `{log_id:016x}:{sequence}`, the form the HTTP transport's
+/// `Spatio-Min-Sequence` header takes, so a token can be passed along with a
+/// request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionToken {
+    pub log_id: u64,
+    pub sequence: u64,
+}
+
+impl fmt::Display for SessionToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}:{}", self.log_id, self.sequence)
+    }
+}
+
+impl FromStr for SessionToken {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || format!("invalid session token {s:?}; expected <log id>:<sequence>");
+        let (log_id, sequence) = s.split_once(':').ok_or_else(invalid)?;
+        Ok(Self {
+            log_id: u64::from_str_radix(log_id, 16).map_err(|_| invalid())?,
+            sequence: sequence.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// A token for read-your-writes consistency (see the [module docs](self)).
+/// Cloning shares the token, so tasks serving one user can share a session.
+#[derive(Clone, Default)]
+pub struct Session {
+    token: Arc<Mutex<Option<SessionToken>>>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Continue a session whose token was passed along, e.g. from the service
+    /// that made the writes.
+    pub fn with_token(token: SessionToken) -> Self {
+        let session = Self::new();
+        session.observe(token);
+        session
+    }
+
+    /// The write reads wait for; `None` before any write.
+    pub fn token(&self) -> Option<SessionToken> {
+        *self.token.lock().unwrap()
+    }
+
+    /// Record `token`, a write made outside [`Session::write`]. A token from
+    /// the session's log raises its sequence; one from another log replaces
+    /// it, as the session's writes have moved to that log's server.
+    pub fn observe(&self, token: SessionToken) {
+        let mut current = self.token.lock().unwrap();
+        match current.as_mut() {
+            Some(current) if current.log_id == token.log_id => {
+                current.sequence = current.sequence.max(token.sequence);
+            }
+            _ => *current = Some(token),
+        }
+    }
+
+    /// Run `write` against `client`, recording the sequence it returns.
+    pub async fn write<F>(&self, client: &SpatioClient, write: F) -> Result<u64>
+    where
+        F: Future<Output = Result<u64>>,
+    {
+        let sequence = write.await?;
+        let log_id = client.log_id().await?;
+        self.observe(SessionToken { log_id, sequence });
+        Ok(sequence)
+    }
+
+    /// Run `read` on `client` once its server has applied the session's
+    /// writes.
+    pub async fn read<T, F, Fut>(&self, client: &SpatioClient, read: F) -> Result<T>
+    where
+        F: FnOnce(SpatioClient) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        if let Some(token) = self.token() {
+            let applied = client.wait_applied(token.log_id, token.sequence).await?;
+            self.observe(SessionToken {
+                sequence: applied,
+                ..token
+            });
+        }
+        read(client.clone()).await
+    }
+}
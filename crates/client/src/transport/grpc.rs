@@ -0,0 +1,175 @@
+//! gRPC transport for Spatio client
+//!
+//! Talks to the server's gRPC API, which covers writes, lookups, radius,
+//! nearest-neighbor, box and trajectory queries, and stats. Handler errors
+//! come back as [`ClientError::Grpc`](super::rpc::ClientError::Grpc), or
+//! [`ClientError::Busy`](super::rpc::ClientError::Busy) when the server
+//! asks to retry later.
+
+use spatio_server::transport::grpc::proto;
+use spatio_server::transport::grpc::proto::spatio_service_client::SpatioServiceClient;
+use spatio_server::{
+    CurrentLocation, KnnQuery, LocationUpdate, QueryHit, RadiusQuery, Stats, TrajectorySlice,
+};
+use spatio_types::geo::DistanceMetric;
+use spatio_types::point::Point3d;
+use spatio_types::time::TimeRange;
+use std::net::SocketAddr;
+use tonic::transport::Channel;
+
+use super::rpc::Result;
+
+#[derive(Clone)]
+pub struct GrpcClient {
+    client: SpatioServiceClient<Channel>,
+}
+
+impl GrpcClient {
+    pub async fn connect(addr: SocketAddr) -> Result<Self> {
+        let client = SpatioServiceClient::connect(format!("http://{addr}")).await?;
+        Ok(Self { client })
+    }
+
+    /// Upsert an object's location. A write sent again with the same
+    /// `idempotency_key` is applied only once.
+    pub async fn upsert(
+        &self,
+        namespace: &str,
+        id: &str,
+        point: Point3d,
+        metadata: serde_json::Value,
+        idempotency_key: Option<String>,
+    ) -> Result<u64> {
+        let request = proto::UpsertRequest {
+            namespace: namespace.to_string(),
+            object_id: id.to_string(),
+            position: Some((&point).into()),
+            metadata_json: serde_json::to_string(&metadata)?,
+            idempotency_key,
+        };
+        let reply = self.client.clone().upsert(request).await?;
+        Ok(reply.into_inner().sequence)
+    }
+
+    pub async fn get(&self, namespace: &str, id: &str) -> Result<Option<CurrentLocation>> {
+        let request = proto::GetRequest {
+            namespace: namespace.to_string(),
+            object_id: id.to_string(),
+        };
+        let reply = self.client.clone().get(request).await?;
+        Ok(reply.into_inner().location.map(Into::into))
+    }
+
+    pub async fn delete(&self, namespace: &str, id: &str) -> Result<u64> {
+        let request = proto::DeleteRequest {
+            namespace: namespace.to_string(),
+            object_id: id.to_string(),
+        };
+        let reply = self.client.clone().delete(request).await?;
+        Ok(reply.into_inner().sequence)
+    }
+
+    /// Objects within `radius` of `center`, nearest first. `metric` defaults
+    /// to the namespace's configured metric on the server.
+    pub async fn query_radius(
+        &self,
+        namespace: &str,
+        center: Point3d,
+        radius: f64,
+        limit: usize,
+        metric: Option<DistanceMetric>,
+    ) -> Result<Vec<QueryHit>> {
+        let mut query = RadiusQuery::new(namespace, center, radius, limit);
+        query.metric = metric;
+        let reply = self
+            .client
+            .clone()
+            .query_radius(proto::RadiusQuery::from(query))
+            .await?;
+        Ok(reply
+            .into_inner()
+            .hits
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    pub async fn knn(&self, namespace: &str, center: Point3d, k: usize) -> Result<Vec<QueryHit>> {
+        let query = proto::KnnQuery::from(KnnQuery::new(namespace, center, k));
+        let reply = self.client.clone().knn(query).await?;
+        Ok(reply
+            .into_inner()
+            .hits
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    pub async fn query_bbox(
+        &self,
+        namespace: &str,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        limit: usize,
+    ) -> Result<Vec<CurrentLocation>> {
+        let request = proto::BboxQuery {
+            namespace: namespace.to_string(),
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+            limit: limit as u64,
+        };
+        let reply = self.client.clone().query_bbox(request).await?;
+        Ok(reply
+            .into_inner()
+            .locations
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Points of the object's trajectory within `range`, newest first.
+    pub async fn query_trajectory(
+        &self,
+        namespace: &str,
+        id: &str,
+        range: impl Into<TimeRange>,
+        limit: usize,
+    ) -> Result<Vec<LocationUpdate>> {
+        let slice = proto::TrajectorySlice::from(TrajectorySlice::new(namespace, id, range, limit));
+        let reply = self.client.clone().query_trajectory(slice).await?;
+        Ok(reply
+            .into_inner()
+            .updates
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Object counts, memory use and throughput. Per-minute operation counts
+    /// are RPC-only and come back empty.
+    pub async fn stats(&self) -> Result<Stats> {
+        let reply = self
+            .client
+            .clone()
+            .stats(proto::StatsRequest {})
+            .await?
+            .into_inner();
+        Ok(Stats {
+            object_count: reply.object_count as usize,
+            memory_usage_bytes: reply.memory_usage_bytes as usize,
+            namespace_objects: reply
+                .namespace_objects
+                .into_iter()
+                .map(|(namespace, count)| (namespace, count as usize))
+                .collect(),
+            cold_log_bytes: reply.cold_log_bytes,
+            queries_count: reply.queries_count,
+            updates_per_sec: reply.updates_per_sec,
+            per_minute: Vec::new(),
+        })
+    }
+}
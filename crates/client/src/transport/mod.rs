@@ -3,5 +3,8 @@
 //! Available transports:
 //! - `rpc` - tarpc-based RPC (default, high performance)
 //! - `http` - HTTP/REST API (requires `http` feature)
+//! - `grpc` - gRPC, for servers run with `--grpc-port` (requires `grpc` feature)
 
+#[cfg(feature = "grpc")]
+pub mod grpc;
 pub mod rpc;
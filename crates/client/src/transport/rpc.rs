@@ -5,26 +5,164 @@
 #![allow(clippy::too_many_arguments)]
 
 use spatio_server::SpatioServiceClient;
+use spatio_server::{
+    AggregateCell, KnnQuery, QueryArgs, QueryHit, QueryTemplate, RadiusQuery, TrajectorySlice,
+};
+use spatio_types::config::{HistoryEventKind, ScanDirection};
 use spatio_types::geo::{DistanceMetric, Point, Polygon};
 use spatio_types::point::Point3d;
+use spatio_types::query::Predicate;
+use spatio_types::time::TimeRange;
+use spatio_types::trajectory::TrajectorySummary;
 use std::net::SocketAddr;
+use std::ops::RangeBounds;
+use std::sync::Arc;
 use std::time::Duration;
-use tarpc::client;
+use tarpc::client::{self, RpcError};
 use tarpc::context;
 use tarpc::tokio_serde::formats::Json;
 use thiserror::Error;
+use tokio::sync::OnceCell;
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
+use tracing::Instrument;
+
+/// How long [`SpatioClient::warm_up`] waits for the server.
+pub const WARM_UP_TIMEOUT: Duration = Duration::from_secs(600);
+
+tokio::task_local! {
+    /// Remote parent installed by [`with_traceparent`].
+    static TRACE_PARENT: tarpc::trace::Context;
+}
+
+/// Run `fut` with every RPC it makes traced as part of the caller's trace,
+/// given as a W3C `traceparent` header (e.g. taken from an inbound HTTP
+/// request). A malformed header is ignored and the calls start a new trace.
+///
+/// The trace context travels in each RPC frame, so server-side spans join the
+/// same trace. With a `tracing_opentelemetry` layer installed the client spans
+/// are exported too, under a `spatio.client` span parented to the header.
+pub async fn with_traceparent<F: Future>(traceparent: &str, fut: F) -> F::Output {
+    let Some(parent) = spatio_server::trace_context::parse_traceparent(traceparent) else {
+        return fut.await;
+    };
+    let span = tracing::info_span!("spatio.client");
+    spatio_server::trace_context::set_remote_parent(&span, &parent);
+    TRACE_PARENT.scope(parent, fut.instrument(span)).await
+}
 
 #[derive(Error, Debug)]
 pub enum ClientError {
     #[error("Connection error: {0}")]
     Connection(#[from] std::io::Error),
     #[error("RPC error: {0}")]
-    Rpc(#[from] tarpc::client::RpcError),
+    Rpc(#[from] RpcError),
     #[error("Server error: {0}")]
     Server(String),
+    /// The server refused the request while rewriting its log; sending it
+    /// again after `retry_after` is safe, as it was never applied.
+    #[error("Server error: {message}")]
+    Busy {
+        message: String,
+        retry_after: Duration,
+    },
+    /// The server is a replica and refused the write, so it was never
+    /// applied; `primary` is the primary the replica follows, if it knows
+    /// one.
+    #[error("Server error: {message}")]
+    NotPrimary {
+        message: String,
+        primary: Option<SocketAddr>,
+    },
+    /// The server hadn't applied write `required` by the end of its wait
+    /// and was still at `applied`; read from another server instead.
+    #[error("Server error: {message}")]
+    StaleReplica {
+        message: String,
+        required: u64,
+        applied: u64,
+    },
+    /// The sequence waited for came from another server's log, so this
+    /// server can never apply it; read from the server that took the write.
+    #[error("Server error: {0}")]
+    ForeignLog(String),
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
+    #[cfg(feature = "grpc")]
+    #[error("gRPC transport error: {0}")]
+    GrpcTransport(#[from] tonic::transport::Error),
+    /// Boxed: a `Status` is several times larger than the other variants.
+    #[cfg(feature = "grpc")]
+    #[error("gRPC error: {0}")]
+    Grpc(Box<tonic::Status>),
+}
+
+impl ClientError {
+    /// A handler error from the server, recognizing busy, not-primary,
+    /// stale-replica and foreign-log refusals.
+    fn server(message: String) -> Self {
+        if let Some(retry_after) = spatio_server::retry_after(&message) {
+            return Self::Busy {
+                message,
+                retry_after,
+            };
+        }
+        if let Some((required, applied)) = spatio_server::stale_sequences(&message) {
+            return Self::StaleReplica {
+                message,
+                required,
+                applied,
+            };
+        }
+        if message.starts_with(spatio_server::FOREIGN_LOG_ERROR_PREFIX) {
+            return Self::ForeignLog(message);
+        }
+        match spatio_server::primary_hint(&message) {
+            Some(primary) => Self::NotPrimary { message, primary },
+            None => Self::Server(message),
+        }
+    }
+
+    /// How long to wait before sending the request again, if the server
+    /// refused it as busy.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Busy { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        }
+    }
+
+    /// The connection is gone: requests on it can't succeed any more.
+    pub(crate) fn is_disconnect(&self) -> bool {
+        matches!(
+            self,
+            Self::Connection(_)
+                | Self::Rpc(RpcError::Shutdown | RpcError::Send(_) | RpcError::Receive(_))
+        )
+    }
+
+    /// The request failed before the server could have seen it, so running it
+    /// again can't apply it twice.
+    pub(crate) fn is_unsent(&self) -> bool {
+        matches!(
+            self,
+            Self::Connection(_) | Self::Rpc(RpcError::Shutdown | RpcError::Send(_))
+        )
+    }
+}
+
+#[cfg(feature = "grpc")]
+impl From<tonic::Status> for ClientError {
+    fn from(status: tonic::Status) -> Self {
+        if status.code() == tonic::Code::Unavailable
+            && let Some(retry_after) = spatio_server::retry_after(status.message())
+        {
+            return Self::Busy {
+                message: status.message().to_string(),
+                retry_after,
+            };
+        }
+        Self::Grpc(Box::new(status))
+    }
 }
 
 pub type Result<T> = std::result::Result<T, ClientError>;
@@ -32,19 +170,53 @@ pub type Result<T> = std::result::Result<T, ClientError>;
 #[derive(Clone)]
 pub struct SpatioClient {
     client: SpatioServiceClient,
+    /// The server's log ID, fetched on first use; it doesn't change while
+    /// the server runs.
+    log_id: Arc<OnceCell<u64>>,
 }
 
 impl SpatioClient {
     pub async fn connect(addr: SocketAddr) -> Result<Self> {
         let socket = tokio::net::TcpStream::connect(addr).await?;
-        let framed = Framed::new(socket, LengthDelimitedCodec::new());
+        Ok(Self::over(socket))
+    }
+
+    /// Connect to a server run with TLS, checking its certificate against
+    /// `config`'s roots and `server_name`.
+    #[cfg(feature = "tls")]
+    pub async fn connect_tls(
+        addr: SocketAddr,
+        server_name: &str,
+        config: std::sync::Arc<tokio_rustls::rustls::ClientConfig>,
+    ) -> Result<Self> {
+        let server_name = tokio_rustls::rustls::pki_types::ServerName::try_from(server_name)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?
+            .to_owned();
+        let socket = tokio::net::TcpStream::connect(addr).await?;
+        let stream = tokio_rustls::TlsConnector::from(config)
+            .connect(server_name, socket)
+            .await?;
+        Ok(Self::over(stream))
+    }
+
+    fn over<S>(stream: S) -> Self
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+    {
+        let framed = Framed::new(stream, LengthDelimitedCodec::new());
         let transport = tarpc::serde_transport::new(framed, Json::default());
         let client = SpatioServiceClient::new(client::Config::default(), transport).spawn();
-        Ok(Self { client })
+        Self {
+            client,
+            log_id: Arc::default(),
+        }
     }
 
     fn make_context(&self) -> context::Context {
         let mut ctx = context::current();
+        if let Ok(parent) = TRACE_PARENT.try_with(|parent| *parent) {
+            ctx.trace_context = parent;
+        }
         ctx.deadline = std::time::SystemTime::now() + Duration::from_secs(30);
         ctx
     }
@@ -55,7 +227,31 @@ impl SpatioClient {
         id: &str,
         point: Point3d,
         metadata: serde_json::Value,
-    ) -> Result<()> {
+    ) -> Result<u64> {
+        self.client
+            .upsert(
+                self.make_context(),
+                namespace.to_string(),
+                id.to_string(),
+                point,
+                metadata,
+                None,
+            )
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Upsert that the server applies at most once per `idempotency_key`,
+    /// so it can be retried safely after a timeout. Use a fresh key (a UUID,
+    /// say) for each distinct write and the same key for its retries.
+    pub async fn upsert_idempotent(
+        &self,
+        namespace: &str,
+        id: &str,
+        point: Point3d,
+        metadata: serde_json::Value,
+        idempotency_key: &str,
+    ) -> Result<u64> {
         self.client
             .upsert(
                 self.make_context(),
@@ -63,9 +259,43 @@ impl SpatioClient {
                 id.to_string(),
                 point,
                 metadata,
+                Some(idempotency_key.to_string()),
+            )
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Upsert many objects of `namespace` in one round trip, all or nothing:
+    /// an invalid item rejects the whole batch. Returns the sequence of the
+    /// last write.
+    pub async fn upsert_batch(
+        &self,
+        namespace: &str,
+        items: Vec<(String, Point3d, serde_json::Value)>,
+    ) -> Result<u64> {
+        self.client
+            .upsert_batch(self.make_context(), namespace.to_string(), items, None)
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Batch upsert that the server applies at most once per
+    /// `idempotency_key` (see [`Self::upsert_idempotent`]).
+    pub async fn upsert_batch_idempotent(
+        &self,
+        namespace: &str,
+        items: Vec<(String, Point3d, serde_json::Value)>,
+        idempotency_key: &str,
+    ) -> Result<u64> {
+        self.client
+            .upsert_batch(
+                self.make_context(),
+                namespace.to_string(),
+                items,
+                Some(idempotency_key.to_string()),
             )
             .await?
-            .map_err(ClientError::Server)
+            .map_err(ClientError::server)
     }
 
     pub async fn get(
@@ -76,51 +306,224 @@ impl SpatioClient {
         self.client
             .get(self.make_context(), namespace.to_string(), id.to_string())
             .await?
-            .map_err(ClientError::Server)
+            .map_err(ClientError::server)
     }
 
-    pub async fn delete(&self, namespace: &str, id: &str) -> Result<()> {
+    /// Objects whose IDs fall in `range`, in ID order (see `DB::range`).
+    pub async fn range<'a>(
+        &self,
+        namespace: &str,
+        range: impl RangeBounds<&'a str>,
+        limit: usize,
+        direction: ScanDirection,
+    ) -> Result<Vec<spatio_server::CurrentLocation>> {
+        let owned = |bound: std::ops::Bound<&&str>| bound.map(|id| id.to_string());
+        self.client
+            .range(
+                self.make_context(),
+                namespace.to_string(),
+                owned(range.start_bound()),
+                owned(range.end_bound()),
+                limit,
+                direction,
+            )
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    pub async fn delete(&self, namespace: &str, id: &str) -> Result<u64> {
         self.client
             .delete(self.make_context(), namespace.to_string(), id.to_string())
             .await?
-            .map_err(ClientError::Server)
+            .map_err(ClientError::server)
+    }
+
+    /// Names of the namespaces holding objects or zones, sorted.
+    pub async fn list_namespaces(&self) -> Result<Vec<String>> {
+        self.client
+            .list_namespaces(self.make_context())
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Delete every object and zone of `namespace`, keeping their history,
+    /// and return how many were removed.
+    pub async fn clear_namespace(&self, namespace: &str) -> Result<u64> {
+        self.client
+            .clear_namespace(self.make_context(), namespace.to_string())
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// [`Self::clear_namespace`], also erasing the namespace's history from
+    /// the server's disk.
+    pub async fn drop_namespace(&self, namespace: &str) -> Result<u64> {
+        self.client
+            .drop_namespace(self.make_context(), namespace.to_string())
+            .await?
+            .map_err(ClientError::server)
     }
 
+    /// Objects within `radius` of `center`, nearest first. `metric` defaults
+    /// to the namespace's configured metric on the server.
     pub async fn query_radius(
         &self,
         namespace: &str,
         center: Point3d,
         radius: f64,
         limit: usize,
-    ) -> Result<Vec<(spatio_server::CurrentLocation, f64)>> {
+        metric: Option<DistanceMetric>,
+    ) -> Result<Vec<QueryHit>> {
+        let mut query = RadiusQuery::new(namespace, center, radius, limit);
+        query.metric = metric;
+        self.client
+            .query_radius(self.make_context(), query)
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// [`Self::query_radius`] for public outputs, with each hit moved by up to
+    /// `jitter` meters under the server's jitter key.
+    pub async fn query_radius_jittered(
+        &self,
+        namespace: &str,
+        center: Point3d,
+        radius: f64,
+        limit: usize,
+        jitter: f64,
+    ) -> Result<Vec<QueryHit>> {
         self.client
-            .query_radius(
+            .query_radius_jittered(
                 self.make_context(),
                 namespace.to_string(),
                 center,
                 radius,
                 limit,
+                jitter,
             )
             .await?
-            .map_err(ClientError::Server)
+            .map_err(ClientError::server)
+    }
+
+    pub async fn knn(&self, namespace: &str, center: Point3d, k: usize) -> Result<Vec<QueryHit>> {
+        self.client
+            .knn(self.make_context(), KnnQuery::new(namespace, center, k))
+            .await?
+            .map_err(ClientError::server)
     }
 
-    pub async fn knn(
+    /// The `k` objects nearest to `center` by horizontal distance, ignoring
+    /// altitude, optionally only those within `max_distance`. `metric`
+    /// defaults to haversine.
+    pub async fn knn_2d(
         &self,
         namespace: &str,
-        center: Point3d,
+        center: Point,
         k: usize,
-    ) -> Result<Vec<(spatio_server::CurrentLocation, f64)>> {
+        max_distance: Option<f64>,
+        metric: Option<DistanceMetric>,
+    ) -> Result<Vec<QueryHit>> {
         self.client
-            .knn(self.make_context(), namespace.to_string(), center, k)
+            .knn_2d(
+                self.make_context(),
+                namespace.to_string(),
+                center,
+                k,
+                max_distance,
+                metric,
+            )
             .await?
-            .map_err(ClientError::Server)
+            .map_err(ClientError::server)
+    }
+
+    /// Subscribe to writes of objects of `namespace` inside `region`,
+    /// returning an ID for [`Self::poll_subscription`]. The server closes
+    /// subscriptions left unpolled for a minute.
+    pub async fn subscribe(
+        &self,
+        namespace: &str,
+        region: spatio_types::bbox::BoundingBox2D,
+    ) -> Result<u64> {
+        self.client
+            .subscribe(self.make_context(), namespace.to_string(), region)
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Up to `max_events` events of a subscription, waiting up to `timeout`
+    /// (capped by the server) for the first; an empty result means none
+    /// arrived. Fails once the subscription fell too far behind and was
+    /// closed: re-read the region and subscribe again.
+    pub async fn poll_subscription(
+        &self,
+        id: u64,
+        max_events: usize,
+        timeout: Duration,
+    ) -> Result<Vec<spatio_server::RegionEvent>> {
+        self.client
+            .poll_subscription(
+                self.make_context(),
+                id,
+                max_events,
+                timeout.as_millis() as u64,
+            )
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Close a subscription, returning whether it was open.
+    pub async fn unsubscribe(&self, id: u64) -> Result<bool> {
+        Ok(self.client.unsubscribe(self.make_context(), id).await?)
     }
 
     pub async fn stats(&self) -> Result<spatio_server::Stats> {
         Ok(self.client.stats(self.make_context()).await?)
     }
 
+    /// Have the server load its spatial indexes and the log segments with
+    /// updates from the last `recent`, so a standby serves its first queries
+    /// at full speed once traffic fails over to it.
+    ///
+    /// Reading a large log takes a while, so the call may take up to
+    /// [`WARM_UP_TIMEOUT`] rather than the usual 30 seconds.
+    pub async fn warm_up(&self, recent: Duration) -> Result<spatio_server::WarmUpReport> {
+        let mut ctx = self.make_context();
+        ctx.deadline = std::time::SystemTime::now() + WARM_UP_TIMEOUT;
+        self.client
+            .warm_up(ctx, recent.as_secs())
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// The server's role, and the primary it follows if it's a replica.
+    pub async fn topology(&self) -> Result<spatio_server::Topology> {
+        Ok(self.client.topology(self.make_context()).await?)
+    }
+
+    /// The ID of the server's log, which the sequences its writes return
+    /// belong to. Sequences from different logs aren't comparable.
+    pub async fn log_id(&self) -> Result<u64> {
+        self.log_id
+            .get_or_try_init(|| async { Ok(self.client.log_id(self.make_context()).await?) })
+            .await
+            .copied()
+    }
+
+    /// Wait until the server has applied write `sequence` of log `log_id`,
+    /// as returned by an earlier write to the same server, so reads sent
+    /// after it see the write. Returns the server's last applied sequence,
+    /// [`ClientError::StaleReplica`] if it doesn't catch up within its
+    /// configured wait, or [`ClientError::ForeignLog`] if `log_id` isn't the
+    /// server's log. See [`Session`].
+    ///
+    /// [`Session`]: crate::Session
+    pub async fn wait_applied(&self, log_id: u64, sequence: u64) -> Result<u64> {
+        self.client
+            .wait_applied(self.make_context(), log_id, sequence)
+            .await?
+            .map_err(ClientError::server)
+    }
+
     pub async fn query_bbox(
         &self,
         namespace: &str,
@@ -141,9 +544,88 @@ impl SpatioClient {
                 limit,
             )
             .await?
-            .map_err(ClientError::Server)
+            .map_err(ClientError::server)
+    }
+
+    /// Objects within a bounding box with their distance in meters from
+    /// `anchor`, or from the center of the box when `anchor` is `None`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn query_bbox_with_distances(
+        &self,
+        namespace: &str,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        anchor: Option<Point3d>,
+        limit: usize,
+    ) -> Result<Vec<QueryHit>> {
+        self.client
+            .query_bbox_with_distances(
+                self.make_context(),
+                namespace.to_string(),
+                min_x,
+                min_y,
+                max_x,
+                max_y,
+                anchor,
+                limit,
+            )
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Fetch one page of a bounding-box scan. Start with `token = None` and
+    /// pass each page's `next_token` back, possibly to another server.
+    pub async fn query_bbox_page(
+        &self,
+        namespace: &str,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        page_size: usize,
+        token: Option<String>,
+    ) -> Result<spatio_server::BboxPage> {
+        self.client
+            .query_bbox_page(
+                self.make_context(),
+                namespace.to_string(),
+                min_x,
+                min_y,
+                max_x,
+                max_y,
+                page_size,
+                token,
+            )
+            .await?
+            .map_err(ClientError::server)
     }
 
+    /// Fetch one page of the objects within a radius, nearest first. Tokens
+    /// work as for [`query_bbox_page`](Self::query_bbox_page).
+    pub async fn query_radius_page(
+        &self,
+        namespace: &str,
+        center: Point3d,
+        radius: f64,
+        page_size: usize,
+        token: Option<String>,
+    ) -> Result<spatio_server::Page<QueryHit>> {
+        self.client
+            .query_radius_page(
+                self.make_context(),
+                namespace.to_string(),
+                center,
+                radius,
+                page_size,
+                token,
+            )
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub async fn query_cylinder(
         &self,
         namespace: &str,
@@ -152,7 +634,8 @@ impl SpatioClient {
         max_z: f64,
         radius: f64,
         limit: usize,
-    ) -> Result<Vec<(spatio_server::CurrentLocation, f64)>> {
+        metric: Option<DistanceMetric>,
+    ) -> Result<Vec<QueryHit>> {
         self.client
             .query_cylinder(
                 self.make_context(),
@@ -162,30 +645,142 @@ impl SpatioClient {
                 max_z,
                 radius,
                 limit,
+                metric,
             )
             .await?
-            .map_err(ClientError::Server)
+            .map_err(ClientError::server)
     }
 
     pub async fn query_trajectory(
         &self,
         namespace: &str,
         id: &str,
-        start_time: Option<f64>,
-        end_time: Option<f64>,
+        range: impl Into<TimeRange>,
         limit: usize,
     ) -> Result<Vec<spatio_server::LocationUpdate>> {
         self.client
             .query_trajectory(
+                self.make_context(),
+                TrajectorySlice::new(namespace, id, range, limit),
+            )
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Fetch one page of an object's trajectory, newest first. Tokens work
+    /// as for [`query_bbox_page`](Self::query_bbox_page).
+    pub async fn query_trajectory_page(
+        &self,
+        namespace: &str,
+        id: &str,
+        range: impl Into<TimeRange>,
+        page_size: usize,
+        token: Option<String>,
+    ) -> Result<spatio_server::Page<spatio_server::LocationUpdate>> {
+        self.client
+            .query_trajectory_page(
+                self.make_context(),
+                namespace.to_string(),
+                id.to_string(),
+                range.into(),
+                page_size,
+                token,
+            )
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Writes and deletes of an object within the time range, oldest first,
+    /// optionally only those of `kind`.
+    pub async fn history(
+        &self,
+        namespace: &str,
+        id: &str,
+        range: impl Into<TimeRange>,
+        kind: Option<HistoryEventKind>,
+        limit: usize,
+    ) -> Result<Vec<spatio_server::HistoryEntry>> {
+        self.client
+            .history(
+                self.make_context(),
+                namespace.to_string(),
+                id.to_string(),
+                range.into(),
+                kind,
+                limit,
+            )
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Distance, duration and speeds of an object's history within the time
+    /// range, computed by the server.
+    pub async fn trajectory_summary(
+        &self,
+        namespace: &str,
+        id: &str,
+        range: impl Into<TimeRange>,
+    ) -> Result<TrajectorySummary> {
+        self.client
+            .trajectory_summary(
                 self.make_context(),
                 namespace.to_string(),
                 id.to_string(),
-                start_time,
-                end_time,
+                range.into(),
+            )
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Objects whose trajectory within the time range passed through the
+    /// bounding box, with the stretches that did, ordered by object ID.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn query_trajectories_intersecting_bbox(
+        &self,
+        namespace: &str,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        range: impl Into<TimeRange>,
+        limit: usize,
+    ) -> Result<Vec<spatio_server::TrajectoryMatch>> {
+        self.client
+            .query_trajectories_intersecting_bbox(
+                self.make_context(),
+                namespace.to_string(),
+                min_x,
+                min_y,
+                max_x,
+                max_y,
+                range.into(),
+                limit,
+            )
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Objects whose trajectory within the time range came within `radius`
+    /// meters of `center`, with the stretches that did, ordered by object ID.
+    pub async fn query_trajectories_within_radius(
+        &self,
+        namespace: &str,
+        center: Point3d,
+        radius: f64,
+        range: impl Into<TimeRange>,
+        limit: usize,
+    ) -> Result<Vec<spatio_server::TrajectoryMatch>> {
+        self.client
+            .query_trajectories_within_radius(
+                self.make_context(),
+                namespace.to_string(),
+                center,
+                radius,
+                range.into(),
                 limit,
             )
             .await?
-            .map_err(ClientError::Server)
+            .map_err(ClientError::server)
     }
 
     pub async fn insert_trajectory(
@@ -193,16 +788,38 @@ impl SpatioClient {
         namespace: &str,
         id: &str,
         trajectory: Vec<(f64, Point3d, serde_json::Value)>,
-    ) -> Result<()> {
+    ) -> Result<u64> {
         self.client
             .insert_trajectory(
                 self.make_context(),
                 namespace.to_string(),
                 id.to_string(),
                 trajectory,
+                None,
             )
             .await?
-            .map_err(ClientError::Server)
+            .map_err(ClientError::server)
+    }
+
+    /// Insert a trajectory that the server applies at most once per
+    /// `idempotency_key` (see [`Self::upsert_idempotent`]).
+    pub async fn insert_trajectory_idempotent(
+        &self,
+        namespace: &str,
+        id: &str,
+        trajectory: Vec<(f64, Point3d, serde_json::Value)>,
+        idempotency_key: &str,
+    ) -> Result<u64> {
+        self.client
+            .insert_trajectory(
+                self.make_context(),
+                namespace.to_string(),
+                id.to_string(),
+                trajectory,
+                Some(idempotency_key.to_string()),
+            )
+            .await?
+            .map_err(ClientError::server)
     }
 
     pub async fn query_bbox_3d(
@@ -229,7 +846,7 @@ impl SpatioClient {
                 limit,
             )
             .await?
-            .map_err(ClientError::Server)
+            .map_err(ClientError::server)
     }
 
     pub async fn query_near(
@@ -238,7 +855,7 @@ impl SpatioClient {
         id: &str,
         radius: f64,
         limit: usize,
-    ) -> Result<Vec<(spatio_server::CurrentLocation, f64)>> {
+    ) -> Result<Vec<QueryHit>> {
         self.client
             .query_near(
                 self.make_context(),
@@ -248,7 +865,7 @@ impl SpatioClient {
                 limit,
             )
             .await?
-            .map_err(ClientError::Server)
+            .map_err(ClientError::server)
     }
 
     pub async fn contains(
@@ -260,7 +877,118 @@ impl SpatioClient {
         self.client
             .contains(self.make_context(), namespace.to_string(), polygon, limit)
             .await?
-            .map_err(ClientError::Server)
+            .map_err(ClientError::server)
+    }
+
+    /// Objects inside a polygon with their distance in meters from `anchor`,
+    /// or from the centroid of the polygon when `anchor` is `None`.
+    pub async fn contains_with_distances(
+        &self,
+        namespace: &str,
+        polygon: Polygon,
+        anchor: Option<Point3d>,
+        limit: usize,
+    ) -> Result<Vec<QueryHit>> {
+        self.client
+            .contains_with_distances(
+                self.make_context(),
+                namespace.to_string(),
+                polygon,
+                anchor,
+                limit,
+            )
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Objects matching a composite predicate, such as "inside polygon A,
+    /// not inside polygon B, below 500 m".
+    pub async fn query(
+        &self,
+        namespace: &str,
+        predicate: Predicate,
+        limit: usize,
+    ) -> Result<Vec<spatio_server::CurrentLocation>> {
+        self.client
+            .query(self.make_context(), namespace.to_string(), predicate, limit)
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Define a materialized view of the objects in `namespace` matching
+    /// `predicate`, kept up to date by the server as objects are written.
+    pub async fn create_view(
+        &self,
+        name: &str,
+        namespace: &str,
+        predicate: Predicate,
+    ) -> Result<()> {
+        self.client
+            .create_view(
+                self.make_context(),
+                name.to_string(),
+                namespace.to_string(),
+                predicate,
+            )
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Remove a view, returning whether it existed.
+    pub async fn drop_view(&self, name: &str) -> Result<bool> {
+        self.client
+            .drop_view(self.make_context(), name.to_string())
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Current members of a view, in object ID order.
+    pub async fn view(
+        &self,
+        name: &str,
+        limit: usize,
+    ) -> Result<Vec<spatio_server::CurrentLocation>> {
+        self.client
+            .view(self.make_context(), name.to_string(), limit)
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Register `template` on the server under `name`, replacing any query of
+    /// that name.
+    pub async fn register_query(&self, name: &str, template: QueryTemplate) -> Result<()> {
+        self.client
+            .register_query(self.make_context(), name.to_string(), template)
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Remove a saved query, returning whether it existed.
+    pub async fn unregister_query(&self, name: &str) -> Result<bool> {
+        self.client
+            .unregister_query(self.make_context(), name.to_string())
+            .await?
+            .map_err(ClientError::server)
+    }
+
+    /// Run the saved query `name`, binding `args` to its parameters.
+    pub async fn run_query(
+        &self,
+        namespace: &str,
+        name: &str,
+        args: QueryArgs,
+        limit: usize,
+    ) -> Result<Vec<spatio_server::CurrentLocation>> {
+        self.client
+            .run_query(
+                self.make_context(),
+                namespace.to_string(),
+                name.to_string(),
+                args,
+                limit,
+            )
+            .await?
+            .map_err(ClientError::server)
     }
 
     pub async fn distance(
@@ -279,7 +1007,7 @@ impl SpatioClient {
                 metric,
             )
             .await?
-            .map_err(ClientError::Server)
+            .map_err(ClientError::server)
     }
 
     pub async fn distance_to(
@@ -298,14 +1026,14 @@ impl SpatioClient {
                 metric,
             )
             .await?
-            .map_err(ClientError::Server)
+            .map_err(ClientError::server)
     }
 
     pub async fn convex_hull(&self, namespace: &str) -> Result<Option<Polygon>> {
         self.client
             .convex_hull(self.make_context(), namespace.to_string())
             .await?
-            .map_err(ClientError::Server)
+            .map_err(ClientError::server)
     }
 
     pub async fn bounding_box(
@@ -315,6 +1043,20 @@ impl SpatioClient {
         self.client
             .bounding_box(self.make_context(), namespace.to_string())
             .await?
-            .map_err(ClientError::Server)
+            .map_err(ClientError::server)
+    }
+
+    /// Objects of `namespace` counted in `cell_size`-degree grid cells, only
+    /// the cells holding at least `k` of them.
+    pub async fn k_anonymous_cells(
+        &self,
+        namespace: &str,
+        cell_size: f64,
+        k: usize,
+    ) -> Result<Vec<AggregateCell>> {
+        self.client
+            .k_anonymous_cells(self.make_context(), namespace.to_string(), cell_size, k)
+            .await?
+            .map_err(ClientError::server)
     }
 }
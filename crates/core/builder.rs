@@ -1,16 +1,30 @@
 //! Database builder
 
 use crate::config::Config;
-use crate::db::DB;
+use crate::db::{CompactionPolicy, DB, OpenProgress, ProgressFn};
 use crate::error::Result;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 /// Builder for database configuration with custom persistence paths and settings.
-#[derive(Debug)]
 pub struct DBBuilder {
     path: Option<PathBuf>,
     config: Config,
     in_memory: bool,
+    progress: Option<ProgressFn>,
+    compaction: Option<Arc<dyn CompactionPolicy>>,
+}
+
+impl std::fmt::Debug for DBBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DBBuilder")
+            .field("path", &self.path)
+            .field("config", &self.config)
+            .field("in_memory", &self.in_memory)
+            .field("progress", &self.progress.is_some())
+            .field("compaction", &self.compaction.is_some())
+            .finish()
+    }
 }
 
 impl DBBuilder {
@@ -20,6 +34,8 @@ impl DBBuilder {
             path: None,
             config: Config::default(),
             in_memory: true,
+            progress: None,
+            compaction: None,
         }
     }
 
@@ -43,6 +59,14 @@ impl DBBuilder {
         self
     }
 
+    /// Open the database read-only (see [`PersistenceConfig::read_only`]).
+    ///
+    /// [`PersistenceConfig::read_only`]: crate::config::PersistenceConfig::read_only
+    pub fn read_only(mut self) -> Self {
+        self.config.persistence.read_only = true;
+        self
+    }
+
     /// Enable history tracking with a fixed per-key capacity.
     #[cfg(feature = "time-index")]
     pub fn history_capacity(mut self, capacity: usize) -> Self {
@@ -50,15 +74,44 @@ impl DBBuilder {
         self
     }
 
+    /// Call `progress` with each step of opening the database: snapshot
+    /// load, log replay, and each namespace as it becomes queryable.
+    pub fn on_progress(mut self, progress: impl Fn(&OpenProgress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    /// Decide when history expiration rewrites the log on open and in the
+    /// background (see [`crate::db::CompactionPolicy`]). Without a policy it
+    /// runs every time.
+    pub fn compaction_policy(mut self, policy: impl CompactionPolicy) -> Self {
+        self.compaction = Some(Arc::new(policy));
+        self
+    }
+
     /// Build the database.
     pub fn build(self) -> Result<DB> {
-        if self.in_memory {
-            DB::memory_with_config(self.config)
-        } else if let Some(path) = self.path {
-            DB::open_with_config(path, self.config)
-        } else {
-            DB::memory_with_config(self.config)
-        }
+        self.open(false)
+    }
+
+    /// Build the database, returning before current locations are loaded
+    /// (see [`DB::open_background`]).
+    pub fn build_background(self) -> Result<DB> {
+        self.open(true)
+    }
+
+    fn open(self, background: bool) -> Result<DB> {
+        let path = match &self.path {
+            Some(path) if !self.in_memory => path.as_path(),
+            _ => Path::new(":memory:"),
+        };
+        DB::open_inner(
+            path,
+            self.config,
+            self.progress,
+            self.compaction,
+            background,
+        )
     }
 }
 
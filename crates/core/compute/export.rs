@@ -0,0 +1,452 @@
+//! Export of a namespace's records for sharing outside the database.
+//!
+//! An [`ExportSpec`] picks the records (current locations, or the trajectory
+//! points of a time range), the metadata fields kept, and the anonymization
+//! applied before anything is written. Records are written one per line, as
+//! GeoJSON features or CSV rows.
+//!
+//! Anonymization steps run in the order given, then coordinates are rounded
+//! to the requested precision, so rounding always applies to the released
+//! position.
+
+use crate::compute::privacy::{jitter_point, pseudonym};
+use crate::compute::validation;
+use crate::error::{Result, SpatioError};
+use geojson::{Feature, Geometry, Value};
+use serde_json::Map;
+use spatio_types::geo::Point;
+use spatio_types::point::Point3d;
+use std::io::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Most decimal places [`ExportSpec::with_precision`] accepts; finer than an
+/// `f64` degree can hold.
+pub const MAX_EXPORT_PRECISION: u32 = 15;
+
+/// Output encoding of an export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Newline-delimited GeoJSON: one `Feature` per line, with the object ID,
+    /// timestamp and metadata as properties.
+    GeoJsonLines,
+    /// CSV with a header row. Selected metadata fields get a column each;
+    /// without a selection the whole metadata is one JSON column.
+    Csv,
+}
+
+/// One anonymization step of an export.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Anonymization {
+    /// Replace object IDs with keyed pseudonyms (see [`pseudonym`]).
+    Pseudonymize { key: u128 },
+    /// Displace positions by up to `radius` meters, by an offset drawn per
+    /// object and timestamp (see [`jitter_point`]).
+    Jitter { radius: f64, key: u128 },
+    /// Round timestamps down to a multiple of `step`.
+    CoarsenTime { step: Duration },
+    /// Omit altitudes.
+    DropAltitude,
+}
+
+/// What to export from a namespace and how.
+///
+/// # Examples
+///
+/// ```
+/// use spatio::compute::export::{Anonymization, ExportFormat, ExportSpec};
+/// use std::time::Duration;
+///
+/// let spec = ExportSpec::new(ExportFormat::Csv)
+///     .with_fields(["speed"])
+///     .with_precision(3)
+///     .anonymize(Anonymization::Pseudonymize { key: 7 })
+///     .anonymize(Anonymization::CoarsenTime {
+///         step: Duration::from_secs(900),
+///     });
+/// assert!(spec.validate().is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportSpec {
+    pub format: ExportFormat,
+    /// Export the trajectory points in `[start, end]` instead of current
+    /// locations.
+    pub time_range: Option<(SystemTime, SystemTime)>,
+    /// Top-level metadata fields to keep; `None` keeps the whole metadata.
+    pub fields: Option<Vec<String>>,
+    /// Decimal places kept in longitude and latitude; `None` keeps them all.
+    pub precision: Option<u32>,
+    pub anonymization: Vec<Anonymization>,
+}
+
+impl ExportSpec {
+    /// Current locations in `format`, unmodified.
+    pub fn new(format: ExportFormat) -> Self {
+        Self {
+            format,
+            time_range: None,
+            fields: None,
+            precision: None,
+            anonymization: Vec::new(),
+        }
+    }
+
+    pub fn with_time_range(mut self, start: SystemTime, end: SystemTime) -> Self {
+        self.time_range = Some((start, end));
+        self
+    }
+
+    pub fn with_fields<I, S>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.fields = Some(fields.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn with_precision(mut self, decimals: u32) -> Self {
+        self.precision = Some(decimals);
+        self
+    }
+
+    /// Append an anonymization step.
+    pub fn anonymize(mut self, step: Anonymization) -> Self {
+        self.anonymization.push(step);
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some((start, end)) = self.time_range
+            && start > end
+        {
+            return Err(SpatioError::InvalidInput(
+                "Export time range starts after it ends".to_string(),
+            ));
+        }
+        if let Some(decimals) = self.precision
+            && decimals > MAX_EXPORT_PRECISION
+        {
+            return Err(SpatioError::InvalidInput(format!(
+                "Export precision must be at most {MAX_EXPORT_PRECISION} decimal places"
+            )));
+        }
+        for step in &self.anonymization {
+            match step {
+                Anonymization::Jitter { radius, .. } => validation::validate_radius(*radius)?,
+                Anonymization::CoarsenTime { step } if step.is_zero() => {
+                    return Err(SpatioError::InvalidInput(
+                        "Time coarsening step must be greater than zero".to_string(),
+                    ));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A record about to be exported.
+pub(crate) struct ExportRecord {
+    pub object_id: String,
+    pub timestamp: SystemTime,
+    pub position: Point3d,
+    pub metadata: serde_json::Value,
+}
+
+/// Anonymize and encode `records` of `namespace` into `out` per `spec`,
+/// returning the number written.
+pub(crate) fn write_records<W: Write>(
+    namespace: &str,
+    spec: &ExportSpec,
+    records: impl IntoIterator<Item = ExportRecord>,
+    mut out: W,
+) -> Result<usize> {
+    if spec.format == ExportFormat::Csv {
+        let mut header = vec![
+            "object_id",
+            "timestamp",
+            "longitude",
+            "latitude",
+            "altitude",
+        ];
+        match &spec.fields {
+            Some(fields) => header.extend(fields.iter().map(String::as_str)),
+            None => header.push("metadata"),
+        }
+        let header: Vec<String> = header.into_iter().map(csv_field).collect();
+        writeln!(out, "{}", header.join(","))?;
+    }
+
+    let mut written = 0;
+    for record in records {
+        let row = Row::anonymized(namespace, spec, record)?;
+        match spec.format {
+            ExportFormat::GeoJsonLines => {
+                let line = serde_json::to_string(&row.feature(spec)).map_err(|e| {
+                    SpatioError::SerializationErrorWithContext(format!(
+                        "Failed to serialize export record: {}",
+                        e
+                    ))
+                })?;
+                writeln!(out, "{}", line)?;
+            }
+            ExportFormat::Csv => writeln!(out, "{}", row.csv(spec))?,
+        }
+        written += 1;
+    }
+    out.flush()?;
+    Ok(written)
+}
+
+/// An [`ExportRecord`] after anonymization.
+struct Row {
+    object_id: String,
+    timestamp: SystemTime,
+    x: f64,
+    y: f64,
+    z: Option<f64>,
+    metadata: serde_json::Value,
+}
+
+impl Row {
+    fn anonymized(namespace: &str, spec: &ExportSpec, record: ExportRecord) -> Result<Row> {
+        let mut row = Row {
+            object_id: record.object_id.clone(),
+            timestamp: record.timestamp,
+            x: record.position.x(),
+            y: record.position.y(),
+            z: Some(record.position.z()),
+            metadata: select_fields(record.metadata, spec.fields.as_deref()),
+        };
+        for step in &spec.anonymization {
+            match step {
+                Anonymization::Pseudonymize { key } => {
+                    row.object_id = pseudonym(&record.object_id, *key);
+                }
+                Anonymization::Jitter { radius, key } => {
+                    // Identified like `CurrentLocation::jittered`, so exports
+                    // and jittered queries displace an object identically.
+                    let id = format!("{}::{}", namespace, record.object_id);
+                    let origin = Point::new(row.x, row.y);
+                    let moved = jitter_point(&origin, &id, record.timestamp, *radius, *key)?;
+                    (row.x, row.y) = (moved.x(), moved.y());
+                }
+                Anonymization::CoarsenTime { step } => {
+                    let since_epoch = row.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+                    let steps = since_epoch.as_nanos() / step.as_nanos();
+                    let nanos = u64::try_from(steps * step.as_nanos()).unwrap_or(u64::MAX);
+                    row.timestamp = UNIX_EPOCH + Duration::from_nanos(nanos);
+                }
+                Anonymization::DropAltitude => row.z = None,
+            }
+        }
+        if let Some(decimals) = spec.precision {
+            let scale = 10f64.powi(decimals as i32);
+            row.x = (row.x * scale).round() / scale;
+            row.y = (row.y * scale).round() / scale;
+        }
+        Ok(row)
+    }
+
+    fn seconds(&self) -> f64 {
+        self.timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64()
+    }
+
+    fn feature(&self, spec: &ExportSpec) -> Feature {
+        let mut coords = vec![self.x, self.y];
+        coords.extend(self.z);
+
+        let mut properties = Map::new();
+        properties.insert("object_id".to_string(), self.object_id.clone().into());
+        properties.insert("timestamp".to_string(), self.seconds().into());
+        match (&spec.fields, &self.metadata) {
+            (Some(_), serde_json::Value::Object(fields)) => {
+                properties.extend(fields.iter().map(|(k, v)| (k.clone(), v.clone())));
+            }
+            (Some(_), _) => {}
+            (None, metadata) => {
+                properties.insert("metadata".to_string(), metadata.clone());
+            }
+        }
+
+        Feature {
+            bbox: None,
+            geometry: Some(Geometry::new(Value::Point(coords))),
+            id: None,
+            properties: Some(properties),
+            foreign_members: None,
+        }
+    }
+
+    fn csv(&self, spec: &ExportSpec) -> String {
+        let mut fields = vec![
+            csv_field(&self.object_id),
+            self.seconds().to_string(),
+            self.x.to_string(),
+            self.y.to_string(),
+            self.z.map(|z| z.to_string()).unwrap_or_default(),
+        ];
+        match &spec.fields {
+            Some(names) => fields.extend(names.iter().map(|name| match self.metadata.get(name) {
+                None | Some(serde_json::Value::Null) => String::new(),
+                Some(serde_json::Value::String(s)) => csv_field(s),
+                Some(other) => csv_field(&other.to_string()),
+            })),
+            None => fields.push(csv_field(&self.metadata.to_string())),
+        }
+        fields.join(",")
+    }
+}
+
+/// Keep only `fields` of an object `metadata`; other values have no fields to
+/// keep.
+fn select_fields(metadata: serde_json::Value, fields: Option<&[String]>) -> serde_json::Value {
+    let Some(fields) = fields else {
+        return metadata;
+    };
+    match metadata {
+        serde_json::Value::Object(mut map) => {
+            map.retain(|key, _| fields.contains(key));
+            serde_json::Value::Object(map)
+        }
+        _ => serde_json::Value::Object(Map::new()),
+    }
+}
+
+/// Quote a CSV field if it holds a delimiter, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, secs: u64, metadata: serde_json::Value) -> ExportRecord {
+        ExportRecord {
+            object_id: id.to_string(),
+            timestamp: UNIX_EPOCH + Duration::from_secs(secs),
+            position: Point3d::new(13.404954, 52.520008, 34.0),
+            metadata,
+        }
+    }
+
+    fn export(spec: &ExportSpec, records: Vec<ExportRecord>) -> Vec<String> {
+        let mut out = Vec::new();
+        let written = write_records("fleet", spec, records, &mut out).unwrap();
+        let lines: Vec<String> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        let header = usize::from(spec.format == ExportFormat::Csv);
+        assert_eq!(lines.len(), written + header);
+        lines
+    }
+
+    #[test]
+    fn test_csv_selects_fields_and_reduces_precision() {
+        let spec = ExportSpec::new(ExportFormat::Csv)
+            .with_fields(["driver", "speed"])
+            .with_precision(2)
+            .anonymize(Anonymization::DropAltitude);
+        let lines = export(
+            &spec,
+            vec![
+                record(
+                    "van,1",
+                    1_000,
+                    serde_json::json!({"driver": "Ann \"A\"", "speed": 12.5, "vin": "secret"}),
+                ),
+                record("van2", 2_000, serde_json::json!("not an object")),
+            ],
+        );
+        assert_eq!(
+            lines,
+            [
+                "object_id,timestamp,longitude,latitude,altitude,driver,speed",
+                "\"van,1\",1000,13.4,52.52,,\"Ann \"\"A\"\"\",12.5",
+                "van2,2000,13.4,52.52,,,",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_geojson_lines_anonymize() {
+        let spec = ExportSpec::new(ExportFormat::GeoJsonLines)
+            .anonymize(Anonymization::Pseudonymize { key: 1 })
+            .anonymize(Anonymization::CoarsenTime {
+                step: Duration::from_secs(600),
+            })
+            .anonymize(Anonymization::Jitter {
+                radius: 100.0,
+                key: 2,
+            })
+            .with_precision(4);
+        let lines = export(
+            &spec,
+            vec![record("van1", 1_234, serde_json::json!({"vin": "secret"}))],
+        );
+        let feature: Feature = lines[0]
+            .parse::<geojson::GeoJson>()
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let properties = feature.properties.unwrap();
+        assert_eq!(properties["object_id"], pseudonym("van1", 1));
+        assert_eq!(properties["timestamp"], 1_200.0);
+        assert_eq!(properties["metadata"], serde_json::json!({"vin": "secret"}));
+
+        let Some(Value::Point(coords)) = feature.geometry.map(|g| g.value) else {
+            panic!("expected a point");
+        };
+        assert_eq!(coords.len(), 3);
+        let jittered = jitter_point(
+            &Point::new(13.404954, 52.520008),
+            "fleet::van1",
+            UNIX_EPOCH + Duration::from_secs(1_234),
+            100.0,
+            2,
+        )
+        .unwrap();
+        assert_eq!(coords[0], (jittered.x() * 1e4).round() / 1e4);
+        assert_eq!(coords[1], (jittered.y() * 1e4).round() / 1e4);
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_specs() {
+        let spec = || ExportSpec::new(ExportFormat::GeoJsonLines);
+        assert!(spec().validate().is_ok());
+        assert!(
+            spec()
+                .with_time_range(UNIX_EPOCH + Duration::from_secs(1), UNIX_EPOCH)
+                .validate()
+                .is_err()
+        );
+        assert!(spec().with_precision(16).validate().is_err());
+        assert!(
+            spec()
+                .anonymize(Anonymization::Jitter {
+                    radius: -1.0,
+                    key: 0
+                })
+                .validate()
+                .is_err()
+        );
+        assert!(
+            spec()
+                .anonymize(Anonymization::CoarsenTime {
+                    step: Duration::ZERO
+                })
+                .validate()
+                .is_err()
+        );
+    }
+}
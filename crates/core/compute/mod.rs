@@ -1,5 +1,13 @@
-//! Query processing, spatial algorithms, validation, and GeoJSON conversion.
+//! Query processing, spatial algorithms, validation, privacy helpers, GeoJSON conversion, trip
+//! detection, speed-limit checks, trajectory passes, and data export and import.
 
+pub mod export;
 pub mod geojson;
+pub mod import;
+pub mod passes;
+pub mod privacy;
+pub mod query;
 pub mod spatial;
+pub mod trips;
 pub mod validation;
+pub mod violations;
@@ -0,0 +1,52 @@
+//! Trajectory passes: the stretches of stored trajectories that run through a
+//! region.
+//!
+//! A pass is a maximal run of consecutive points of one object that are all
+//! inside the region. The region is tested point by point, so an object whose
+//! consecutive points straddle a region without either landing in it does not
+//! pass through it.
+
+use crate::db::LocationUpdate;
+
+/// An object whose trajectory passed through a region, with the points of
+/// each pass.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrajectoryMatch {
+    pub object_id: String,
+    /// The passes, oldest first, each a run of points ordered by time.
+    pub segments: Vec<Vec<LocationUpdate>>,
+}
+
+/// Find the passes of the objects in `points` through the region `inside`
+/// accepts. `points` must be grouped by object ID and ordered by time within
+/// each object (as
+/// [`ColdState::scan_namespace`](crate::db::ColdState::scan_namespace) returns
+/// them). Objects that never entered are left out, and matches come out
+/// ordered by object ID.
+pub fn passes(
+    points: &[(String, LocationUpdate)],
+    inside: impl Fn(&LocationUpdate) -> bool,
+) -> Vec<TrajectoryMatch> {
+    let mut matches = Vec::new();
+    for track in points.chunk_by(|(a, _), (b, _)| a == b) {
+        let mut segments = Vec::new();
+        let mut current: Vec<LocationUpdate> = Vec::new();
+        for (_, update) in track {
+            if inside(update) {
+                current.push(update.clone());
+            } else if !current.is_empty() {
+                segments.push(std::mem::take(&mut current));
+            }
+        }
+        if !current.is_empty() {
+            segments.push(current);
+        }
+        if !segments.is_empty() {
+            matches.push(TrajectoryMatch {
+                object_id: track[0].0.clone(),
+                segments,
+            });
+        }
+    }
+    matches
+}
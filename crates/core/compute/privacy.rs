@@ -0,0 +1,305 @@
+//! Location privacy helpers for public-facing outputs.
+//!
+//! - [`jitter_point`] displaces a point by a deterministic pseudo-random offset
+//!   within a radius. The offset depends on the object key, the point's
+//!   timestamp and a secret key: repeated queries return the same displaced
+//!   position, while each point of a track gets its own offset, so the track
+//!   isn't just shifted as a whole.
+//! - [`k_anonymous_cells`] aggregates points into a fixed grid and only releases
+//!   cells holding at least `k` objects.
+//! - [`pseudonym`] replaces an identifier with a keyed hash.
+//!
+//! Offsets and pseudonyms come from SipHash-2-4, a keyed pseudo-random
+//! function, under a secret 128-bit key: without the key they can't be
+//! recomputed, undone or checked against guessed identifiers.
+
+use crate::compute::validation;
+use crate::error::{Result, SpatioError};
+use geo::{Destination, Haversine, Rect};
+use spatio_types::geo::Point;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Displace `point`, reported by `id` at `timestamp`, by up to `radius`
+/// meters in a direction and distance derived from all three under `key`.
+///
+/// The displacement is uniform over the disc (not clustered at the center) and
+/// stable across calls and process restarts for the same inputs. Each
+/// timestamp draws a fresh offset, so many reports of an object that stays put
+/// average towards its true location; coarsen or aggregate those instead (see
+/// [`k_anonymous_cells`]). Keep `key` secret: anyone who knows it can
+/// recompute and undo the offset. Fails for a negative or non-finite radius;
+/// a zero radius leaves the point where it is.
+///
+/// ```
+/// use spatio::compute::privacy::jitter_point;
+/// use spatio::Point;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let home = Point::new(-74.0, 40.7);
+/// let at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+/// let key = 0x5eed_u128;
+/// let a = jitter_point(&home, "user_42", at, 250.0, key).unwrap();
+/// let b = jitter_point(&home, "user_42", at, 250.0, key).unwrap();
+/// assert_eq!(a, b);
+/// assert!(home.haversine_distance(&a) <= 250.0 + 1e-6);
+/// assert!(jitter_point(&home, "user_42", at, -1.0, key).is_err());
+/// ```
+pub fn jitter_point(
+    point: &Point,
+    id: &str,
+    timestamp: SystemTime,
+    radius: f64,
+    key: u128,
+) -> Result<Point> {
+    if radius != 0.0 {
+        validation::validate_radius(radius)?;
+    }
+    let micros = timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64;
+    // The ID, then a byte no UTF-8 text holds, so no two inputs collide.
+    let mut message = [id.as_bytes(), &[0xff], &micros.to_le_bytes(), &[0]].concat();
+    let bearing = unit_f64(siphash24(key, &message)) * 360.0;
+    *message.last_mut().expect("not empty") = 1;
+    // sqrt keeps the density uniform over the disc area.
+    let distance = radius * unit_f64(siphash24(key, &message)).sqrt();
+
+    Ok(Haversine
+        .destination(*point.inner(), bearing, distance)
+        .into())
+}
+
+/// A stable stand-in for `id`: 16 hex digits of its SipHash-2-4 under `key`.
+///
+/// The same ID always maps to the same pseudonym under one key, so records
+/// of one object stay linkable without naming it. Keep `key` secret: with it
+/// known, guessed IDs can be checked.
+///
+/// ```
+/// use spatio::compute::privacy::pseudonym;
+///
+/// assert_eq!(pseudonym("user_42", 7), pseudonym("user_42", 7));
+/// assert_ne!(pseudonym("user_42", 7), pseudonym("user_42", 8));
+/// assert_eq!(pseudonym("user_42", 7).len(), 16);
+/// ```
+pub fn pseudonym(id: &str, key: u128) -> String {
+    format!("{:016x}", siphash24(key, id.as_bytes()))
+}
+
+/// A released grid cell of a k-anonymous aggregation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateCell {
+    /// Cell extent in degrees.
+    pub bounds: Rect,
+    /// Number of objects in the cell (always `>= k`).
+    pub count: usize,
+}
+
+impl AggregateCell {
+    /// Geometric center of the cell (not the centroid of its members, which
+    /// would leak information about their positions).
+    pub fn center(&self) -> Point {
+        let c = self.bounds.center();
+        Point::new(c.x, c.y)
+    }
+}
+
+/// Bucket points into a `cell_size`-degree grid and release only cells with at
+/// least `k` members. Cells are returned in row-major order (south to north,
+/// west to east).
+///
+/// ```
+/// use spatio::compute::privacy::k_anonymous_cells;
+/// use spatio::Point;
+///
+/// let points = vec![
+///     Point::new(0.1, 0.1),
+///     Point::new(0.2, 0.3),
+///     Point::new(5.5, 5.5), // alone in its cell: suppressed
+/// ];
+/// let cells = k_anonymous_cells(&points, 1.0, 2).unwrap();
+/// assert_eq!(cells.len(), 1);
+/// assert_eq!(cells[0].count, 2);
+/// ```
+pub fn k_anonymous_cells(points: &[Point], cell_size: f64, k: usize) -> Result<Vec<AggregateCell>> {
+    validation::validate_positive("cell_size", cell_size)?;
+    if k == 0 {
+        return Err(SpatioError::InvalidInput(
+            "k must be greater than zero".to_string(),
+        ));
+    }
+
+    // Points on the east edge or the north pole belong to the last cell, not
+    // to one past the grid.
+    let last_col = (360.0 / cell_size).ceil() as i64 - 1;
+    let last_row = (180.0 / cell_size).ceil() as i64 - 1;
+    let mut counts: HashMap<(i64, i64), usize> = HashMap::new();
+    for p in points {
+        let col = ((p.x() + 180.0) / cell_size).floor() as i64;
+        let row = ((p.y() + 90.0) / cell_size).floor() as i64;
+        *counts
+            .entry((row.clamp(0, last_row), col.clamp(0, last_col)))
+            .or_default() += 1;
+    }
+
+    let mut released: Vec<((i64, i64), usize)> = counts
+        .into_iter()
+        .filter(|(_, count)| *count >= k)
+        .collect();
+    released.sort_unstable_by_key(|(cell, _)| *cell);
+
+    Ok(released
+        .into_iter()
+        .map(|((row, col), count)| {
+            let min_x = col as f64 * cell_size - 180.0;
+            let min_y = row as f64 * cell_size - 90.0;
+            AggregateCell {
+                bounds: Rect::new(
+                    geo::coord! { x: min_x, y: min_y },
+                    geo::coord! { x: min_x + cell_size, y: min_y + cell_size },
+                ),
+                count,
+            }
+        })
+        .collect())
+}
+
+/// SipHash-2-4 of `data` under `key` (its 16 little-endian bytes).
+fn siphash24(key: u128, data: &[u8]) -> u64 {
+    let (k0, k1) = (key as u64, (key >> 64) as u64);
+    let mut v = [
+        k0 ^ 0x736f_6d65_7073_6575,
+        k1 ^ 0x646f_7261_6e64_6f6d,
+        k0 ^ 0x6c79_6765_6e65_7261,
+        k1 ^ 0x7465_6462_7974_6573,
+    ];
+    let round = |v: &mut [u64; 4]| {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    };
+    let compress = |v: &mut [u64; 4], m: u64| {
+        v[3] ^= m;
+        round(v);
+        round(v);
+        v[0] ^= m;
+    };
+
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        compress(
+            &mut v,
+            u64::from_le_bytes(chunk.try_into().expect("8 bytes")),
+        );
+    }
+    let mut last = [0u8; 8];
+    last[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+    last[7] = data.len() as u8;
+    compress(&mut v, u64::from_le_bytes(last));
+
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        round(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+/// Map 64 random bits to a float in `[0, 1)`.
+fn unit_f64(bits: u64) -> f64 {
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jitter_is_deterministic_and_bounded() {
+        let origin = Point::new(13.4, 52.5);
+        let at = UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        let jitter = |id: &str, at: SystemTime, radius: f64, key: u128| {
+            jitter_point(&origin, id, at, radius, key).unwrap()
+        };
+
+        let a = jitter("device-1", at, 500.0, 99);
+        assert_eq!(a, jitter("device-1", at, 500.0, 99));
+        assert!(origin.haversine_distance(&a) <= 500.0 + 1e-6);
+
+        // Different IDs and different keys move the point differently.
+        assert_ne!(a, jitter("device-2", at, 500.0, 99));
+        assert_ne!(a, jitter("device-1", at, 500.0, 100));
+
+        // So do different times: a track isn't shifted as one piece.
+        let later = at + std::time::Duration::from_secs(1);
+        assert_ne!(a, jitter("device-1", later, 500.0, 99));
+
+        // Zero radius is the identity.
+        let same = jitter("device-1", at, 0.0, 99);
+        assert!(origin.haversine_distance(&same) < 1e-6);
+
+        for radius in [-1.0, f64::NAN, f64::INFINITY] {
+            assert!(jitter_point(&origin, "device-1", at, radius, 99).is_err());
+        }
+    }
+
+    #[test]
+    fn test_siphash_matches_reference_vectors() {
+        let key = u128::from_le_bytes(std::array::from_fn(|i| i as u8));
+        let message: Vec<u8> = (0..15).collect();
+        assert_eq!(siphash24(key, &[]), 0x726f_db47_dd0e_0e31);
+        assert_eq!(siphash24(key, &message[..8]), 0x93f5_f579_9a93_2462);
+        assert_eq!(siphash24(key, &message), 0xa129_ca61_49be_45e5);
+    }
+
+    #[test]
+    fn test_k_anonymous_cells_suppresses_small_cells() {
+        let mut points: Vec<Point> = (0..5).map(|i| Point::new(0.1 * i as f64, 0.5)).collect();
+        points.push(Point::new(10.5, 10.5));
+        points.push(Point::new(10.6, 10.4));
+
+        let cells = k_anonymous_cells(&points, 1.0, 3).unwrap();
+        assert_eq!(cells.len(), 1);
+        assert_eq!(cells[0].count, 5);
+        assert_eq!(cells[0].bounds.min().x, 0.0);
+        assert_eq!(cells[0].bounds.min().y, 0.0);
+        assert_eq!(cells[0].center(), Point::new(0.5, 0.5));
+
+        let cells = k_anonymous_cells(&points, 1.0, 2).unwrap();
+        assert_eq!(cells.len(), 2);
+
+        assert!(k_anonymous_cells(&points, 0.0, 2).is_err());
+        assert!(k_anonymous_cells(&points, 1.0, 0).is_err());
+    }
+
+    #[test]
+    fn test_k_anonymous_cells_keep_edge_points_on_the_grid() {
+        let corner = [
+            Point::new(180.0, 90.0),
+            Point::new(179.5, 89.5),
+            Point::new(180.0, 89.9),
+        ];
+        let cells = k_anonymous_cells(&corner, 1.0, 3).unwrap();
+        assert_eq!(cells.len(), 1);
+        assert_eq!(cells[0].bounds.min().x, 179.0);
+        assert_eq!(cells[0].bounds.max().y, 90.0);
+
+        // A cell size that doesn't divide the globe leaves a partial last
+        // cell, which still holds the edge.
+        let cells = k_anonymous_cells(&[Point::new(180.0, 90.0)], 7.0, 1).unwrap();
+        assert_eq!(cells[0].bounds.min().x, 177.0);
+        assert_eq!(cells[0].bounds.min().y, 85.0);
+
+        let cells = k_anonymous_cells(&[Point::new(-180.0, -90.0)], 1.0, 1).unwrap();
+        assert_eq!(cells[0].bounds.min().x, -180.0);
+        assert_eq!(cells[0].bounds.min().y, -90.0);
+    }
+}
@@ -0,0 +1,234 @@
+//! Evaluation of composite [`Predicate`]s.
+//!
+//! A predicate runs in two steps: [`envelope`] derives a box guaranteed to
+//! contain every match, which the spatial index scans once, and [`matches`]
+//! tests each candidate exactly. Negations cannot narrow the box, so a
+//! predicate that is only constrained by `Not` scans the whole namespace.
+//! Neither can metadata conditions; those narrow the scan through a metadata
+//! index instead (see [`DB::create_metadata_index`](crate::DB::create_metadata_index)).
+
+use crate::compute::spatial::rtree::circle_bounds;
+use geo::BoundingRect;
+use spatio_types::geo::Point;
+use spatio_types::point::Point3d;
+pub use spatio_types::query::Predicate;
+
+/// Search box over longitude, latitude and altitude, bounds inclusive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Envelope {
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+}
+
+impl Envelope {
+    const WORLD: Envelope = Envelope {
+        min: [-180.0, -90.0, f64::NEG_INFINITY],
+        max: [180.0, 90.0, f64::INFINITY],
+    };
+
+    const EMPTY: Envelope = Envelope {
+        min: [f64::INFINITY; 3],
+        max: [f64::NEG_INFINITY; 3],
+    };
+
+    fn horizontal(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Envelope {
+        Envelope {
+            min: [min_x, min_y, f64::NEG_INFINITY],
+            max: [max_x, max_y, f64::INFINITY],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|axis| self.min[axis] > self.max[axis])
+    }
+
+    fn intersect(self, other: Envelope) -> Envelope {
+        Envelope {
+            min: std::array::from_fn(|axis| self.min[axis].max(other.min[axis])),
+            max: std::array::from_fn(|axis| self.max[axis].min(other.max[axis])),
+        }
+    }
+
+    fn union(self, other: Envelope) -> Envelope {
+        if self.is_empty() {
+            return other;
+        }
+        if other.is_empty() {
+            return self;
+        }
+        Envelope {
+            min: std::array::from_fn(|axis| self.min[axis].min(other.min[axis])),
+            max: std::array::from_fn(|axis| self.max[axis].max(other.max[axis])),
+        }
+    }
+}
+
+/// Smallest box this module can prove contains every position matching
+/// `predicate`. Empty when nothing can match.
+pub(crate) fn envelope(predicate: &Predicate) -> Envelope {
+    match predicate {
+        Predicate::WithinRadius { center, radius } => circle_bounds(center, *radius)
+            .into_iter()
+            .flatten()
+            .fold(Envelope::EMPTY, |acc, [min_x, min_y, max_x, max_y]| {
+                acc.union(Envelope::horizontal(min_x, min_y, max_x, max_y))
+            }),
+        Predicate::WithinBbox {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        } => Envelope::horizontal(*min_x, *min_y, *max_x, *max_y),
+        Predicate::WithinPolygon(polygon) => match polygon.inner().bounding_rect() {
+            Some(rect) => {
+                Envelope::horizontal(rect.min().x, rect.min().y, rect.max().x, rect.max().y)
+            }
+            None => Envelope::EMPTY,
+        },
+        Predicate::Altitude { min, max } => Envelope {
+            min: [-180.0, -90.0, min.unwrap_or(f64::NEG_INFINITY)],
+            max: [180.0, 90.0, max.unwrap_or(f64::INFINITY)],
+        },
+        Predicate::All(all) => all
+            .iter()
+            .fold(Envelope::WORLD, |acc, p| acc.intersect(envelope(p))),
+        Predicate::Any(any) => any
+            .iter()
+            .fold(Envelope::EMPTY, |acc, p| acc.union(envelope(p))),
+        Predicate::MetadataEquals { .. } | Predicate::Not(_) => Envelope::WORLD,
+    }
+}
+
+/// Whether an object at `position` carrying `metadata` satisfies `predicate`.
+pub(crate) fn matches(
+    predicate: &Predicate,
+    position: &Point3d,
+    metadata: &serde_json::Value,
+) -> bool {
+    match predicate {
+        Predicate::WithinRadius { center, radius } => {
+            center.haversine_distance(&Point::new(position.x(), position.y())) <= *radius
+        }
+        Predicate::WithinBbox {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        } => (*min_x..=*max_x).contains(&position.x()) && (*min_y..=*max_y).contains(&position.y()),
+        Predicate::WithinPolygon(polygon) => {
+            polygon.contains(&Point::new(position.x(), position.y()))
+        }
+        Predicate::Altitude { min, max } => {
+            min.is_none_or(|min| position.z() >= min) && max.is_none_or(|max| position.z() <= max)
+        }
+        Predicate::MetadataEquals { field, value } => metadata.get(field) == Some(value),
+        Predicate::All(all) => all.iter().all(|p| matches(p, position, metadata)),
+        Predicate::Any(any) => any.iter().any(|p| matches(p, position, metadata)),
+        Predicate::Not(inner) => !matches(inner, position, metadata),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+    use spatio_types::geo::Polygon;
+
+    fn square(x: f64, y: f64, size: f64) -> Predicate {
+        Predicate::WithinPolygon(Polygon::from_coords(
+            &[
+                (x, y),
+                (x + size, y),
+                (x + size, y + size),
+                (x, y + size),
+                (x, y),
+            ],
+            vec![],
+        ))
+    }
+
+    #[test]
+    fn test_envelope_narrows_conjunctions_and_widens_disjunctions() {
+        let low = Predicate::Altitude {
+            min: None,
+            max: Some(500.0),
+        };
+        let both = square(0.0, 0.0, 4.0).and(!square(1.0, 1.0, 1.0)).and(low);
+        assert_eq!(
+            envelope(&both),
+            Envelope {
+                min: [0.0, 0.0, f64::NEG_INFINITY],
+                max: [4.0, 4.0, 500.0],
+            }
+        );
+
+        let either = square(0.0, 0.0, 1.0).or(square(10.0, 10.0, 1.0));
+        assert_eq!(
+            envelope(&either),
+            Envelope::horizontal(0.0, 0.0, 11.0, 11.0)
+        );
+
+        let disjoint = square(0.0, 0.0, 1.0).and(square(10.0, 10.0, 1.0));
+        assert!(envelope(&disjoint).is_empty());
+        assert!(envelope(&Predicate::Any(vec![])).is_empty());
+        assert_eq!(envelope(&Predicate::All(vec![])), Envelope::WORLD);
+    }
+
+    #[test]
+    fn test_matches_evaluates_the_tree() {
+        let predicate =
+            square(0.0, 0.0, 4.0)
+                .and(!square(1.0, 1.0, 1.0))
+                .and(Predicate::Altitude {
+                    min: Some(100.0),
+                    max: Some(500.0),
+                });
+        assert!(matches(
+            &predicate,
+            &Point3d::new(3.0, 3.0, 200.0),
+            &Value::Null
+        ));
+        assert!(!matches(
+            &predicate,
+            &Point3d::new(1.5, 1.5, 200.0),
+            &Value::Null
+        ));
+        assert!(!matches(
+            &predicate,
+            &Point3d::new(3.0, 3.0, 600.0),
+            &Value::Null
+        ));
+        assert!(!matches(
+            &predicate,
+            &Point3d::new(5.0, 3.0, 200.0),
+            &Value::Null
+        ));
+
+        let edge = Predicate::WithinBbox {
+            min_x: 0.0,
+            min_y: 0.0,
+            max_x: 1.0,
+            max_y: 1.0,
+        };
+        assert!(matches(&edge, &Point3d::new(1.0, 1.0, 0.0), &Value::Null));
+    }
+
+    #[test]
+    fn test_metadata_equals_compares_top_level_fields() {
+        let diesel = Predicate::MetadataEquals {
+            field: "fuel".into(),
+            value: json!("diesel"),
+        };
+        let here = Point3d::new(0.0, 0.0, 0.0);
+        assert!(matches(&diesel, &here, &json!({"fuel": "diesel"})));
+        assert!(!matches(&diesel, &here, &json!({"fuel": "petrol"})));
+        assert!(!matches(
+            &diesel,
+            &here,
+            &json!({"engine": {"fuel": "diesel"}})
+        ));
+        assert!(!matches(&diesel, &here, &Value::Null));
+        assert!(matches(&!diesel.clone(), &here, &Value::Null));
+        assert_eq!(envelope(&diesel), Envelope::WORLD);
+    }
+}
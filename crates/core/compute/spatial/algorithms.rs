@@ -2,10 +2,11 @@
 
 use crate::error::{Result, SpatioError};
 use geo::{
-    BoundingRect, ChamberlainDuquetteArea, Contains, ConvexHull, Distance, GeodesicArea,
-    Intersects, Rect, Rhumb,
+    BoundingRect, ChamberlainDuquetteArea, Closest, Contains, ConvexHull, Distance, Geodesic,
+    GeodesicArea, HaversineClosestPoint, Intersects, LineString, Rect, Rhumb,
 };
 use spatio_types::geo::{Point, Polygon};
+use spatio_types::point::Point3d;
 use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 
@@ -23,6 +24,90 @@ pub fn distance_between(point1: &Point, point2: &Point, metric: DistanceMetric)
     }
 }
 
+/// Round a point's longitude/latitude to `decimals` decimal places.
+///
+/// Altitude is left untouched. Rounding is half-away-from-zero, so jittery
+/// readings around the same spot snap to one canonical coordinate.
+///
+/// ```
+/// use spatio::compute::spatial::snap_to_precision;
+/// use spatio::Point3d;
+///
+/// let snapped = snap_to_precision(&Point3d::new(-74.006_04, 40.712_76, 12.5), 3);
+/// assert_eq!(snapped, Point3d::new(-74.006, 40.713, 12.5));
+/// ```
+pub fn snap_to_precision(point: &Point3d, decimals: u32) -> Point3d {
+    let scale = 10f64.powi(decimals as i32);
+    let snap = |v: f64| (v * scale).round() / scale;
+    Point3d::new(snap(point.x()), snap(point.y()), point.z())
+}
+
+/// Move a point's longitude/latitude to the center of its geohash cell of
+/// `precision` characters.
+///
+/// Altitude is left untouched. Every point of a cell snaps to the same
+/// coordinate, so precision 7 (a cell of about 150 m) coarsens positions to
+/// roughly city-block resolution.
+///
+/// ```
+/// use spatio::compute::spatial::snap_to_geohash_cell;
+/// use spatio::Point3d;
+///
+/// let a = snap_to_geohash_cell(&Point3d::new(-74.006_04, 40.712_76, 12.5), 6);
+/// let b = snap_to_geohash_cell(&Point3d::new(-74.005_99, 40.712_80, 3.0), 6);
+/// assert_eq!((a.x(), a.y()), (b.x(), b.y()));
+/// assert_eq!(a.z(), 12.5);
+/// ```
+pub fn snap_to_geohash_cell(point: &Point3d, precision: usize) -> Point3d {
+    let (x, y) = spatio_types::geohash::cell_center(point.x(), point.y(), precision);
+    Point3d::new(x, y, point.z())
+}
+
+/// Geodesic distance in meters from `point` to the nearest point of `polygon`.
+///
+/// Returns `0.0` when the point lies inside the polygon or on its boundary
+/// (points inside a hole are measured to the hole's ring). The nearest
+/// boundary point is located along great-circle edges and then measured with
+/// the Karney geodesic on the WGS84 ellipsoid, so large or elongated polygons
+/// are handled correctly where a centroid distance would not be.
+/// Returns `f64::INFINITY` for an empty polygon.
+///
+/// # Examples
+///
+/// ```
+/// use spatio::compute::spatial::distance_to_polygon;
+/// use spatio::{Point, Polygon};
+///
+/// let square = Polygon::from_coords(
+///     &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.0, 0.0)],
+///     vec![],
+/// );
+/// assert_eq!(distance_to_polygon(&Point::new(0.5, 0.5), &square), 0.0);
+/// assert!(distance_to_polygon(&Point::new(2.0, 0.5), &square) > 100_000.0);
+/// ```
+pub fn distance_to_polygon(point: &Point, polygon: &Polygon) -> f64 {
+    geodesic_distance_to(
+        point,
+        polygon.inner().haversine_closest_point(point.inner()),
+    )
+}
+
+/// Geodesic distance in meters from `point` to the nearest point of `line`.
+///
+/// Returns `0.0` when the point lies on the line and `f64::INFINITY` for an
+/// empty line string. See [`distance_to_polygon`] for the method used.
+pub fn distance_to_linestring(point: &Point, line: &LineString) -> f64 {
+    geodesic_distance_to(point, line.haversine_closest_point(point.inner()))
+}
+
+fn geodesic_distance_to(point: &Point, closest: Closest<f64>) -> f64 {
+    match closest {
+        Closest::Intersection(_) => 0.0,
+        Closest::SinglePoint(nearest) => Geodesic.distance(*point.inner(), nearest),
+        Closest::Indeterminate => f64::INFINITY,
+    }
+}
+
 /// Helper struct for KNN heap ordering (max-heap by distance, so largest is popped)
 #[derive(Clone)]
 struct KnnEntry<'a, T> {
@@ -331,6 +416,51 @@ mod tests {
         assert!(diff < 10_000.0);
     }
 
+    #[test]
+    fn test_snap_to_precision() {
+        let a = snap_to_precision(&Point3d::new(-74.000_04, 40.700_01, 0.0), 4);
+        let b = snap_to_precision(&Point3d::new(-73.999_96, 40.699_99, 0.0), 4);
+        assert_eq!(a, b);
+        assert_eq!(a, Point3d::new(-74.0, 40.7, 0.0));
+
+        let p = Point3d::new(1.234_567, 2.345_678, 9.876_5);
+        assert_eq!(snap_to_precision(&p, 0), Point3d::new(1.0, 2.0, 9.876_5));
+    }
+
+    #[test]
+    fn test_distance_to_polygon() {
+        let square = Polygon::from_coords(
+            &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.0, 0.0)],
+            vec![],
+        );
+
+        assert_eq!(distance_to_polygon(&Point::new(0.5, 0.5), &square), 0.0);
+
+        // Due east of the square: nearest boundary point is (1.0, 0.5), not
+        // the centroid, so the distance is ~1 degree of longitude, not ~1.5.
+        let outside = Point::new(2.0, 0.5);
+        let expected = distance_between(&outside, &Point::new(1.0, 0.5), DistanceMetric::Geodesic);
+        let actual = distance_to_polygon(&outside, &square);
+        assert!((actual - expected).abs() < 1.0, "{actual} vs {expected}");
+
+        let empty = Polygon::from_coords(&[], vec![]);
+        assert!(distance_to_polygon(&outside, &empty).is_infinite());
+    }
+
+    #[test]
+    fn test_distance_to_linestring() {
+        let line = LineString::from(vec![(0.0, 0.0), (0.0, 1.0), (1.0, 1.0)]);
+
+        assert_eq!(distance_to_linestring(&Point::new(0.0, 0.5), &line), 0.0);
+
+        let west = Point::new(-0.1, 0.5);
+        let expected = distance_between(&west, &Point::new(0.0, 0.5), DistanceMetric::Geodesic);
+        let actual = distance_to_linestring(&west, &line);
+        assert!((actual - expected).abs() < 1.0, "{actual} vs {expected}");
+
+        assert!(distance_to_linestring(&west, &LineString::new(vec![])).is_infinite());
+    }
+
     #[test]
     fn test_knn() {
         let center = Point::new(-74.0060, 40.7128);
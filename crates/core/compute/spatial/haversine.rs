@@ -0,0 +1,180 @@
+//! Batch haversine distance kernel.
+//!
+//! Radius, sphere and cylinder queries compute one great-circle distance per
+//! envelope candidate, and for large radii that trig dominates query time.
+//! `geo`'s scalar haversine makes five libm calls per pair (two `sin`, two
+//! `cos`, one `asin`), none of which vectorize. This kernel:
+//!
+//! - hoists the query center's trig out of the loop,
+//! - evaluates the remaining sines with a branch-free polynomial over
+//!   fixed-width lanes, so the compiler emits SIMD code for the whole batch,
+//! - tests the radius against the haversine term `a` directly, leaving
+//!   `sqrt`/`asin` to candidates that pass the filter.
+//!
+//! Distances agree with `geo::Haversine` to within a few nanometers, except for
+//! near-antipodal pairs, where the haversine formula itself is ill-conditioned
+//! and both implementations drift by up to a fraction of a millimeter.
+
+use geo::HaversineMeasure;
+use spatio_types::geo::Point;
+use std::f64::consts::FRAC_PI_2;
+
+/// Number of candidates evaluated per kernel iteration.
+const LANES: usize = 4;
+
+/// Mean Earth radius used by `geo::Haversine`, so results are interchangeable.
+const EARTH_RADIUS: f64 = HaversineMeasure::GRS80_MEAN_RADIUS.radius();
+
+/// Haversine distances from one center to many points.
+#[derive(Debug, Clone, Copy)]
+pub struct HaversineBatch {
+    lon: f64,
+    lat: f64,
+    cos_lat: f64,
+}
+
+impl HaversineBatch {
+    pub fn new(center: &Point) -> Self {
+        Self {
+            lon: center.x(),
+            lat: center.y(),
+            cos_lat: center.y().to_radians().cos(),
+        }
+    }
+
+    /// Write the haversine term `a` of each `(lons[i], lats[i])` into `out`.
+    ///
+    /// `a` is monotonic in distance; convert with [`Self::distance`] or
+    /// compare against [`Self::term_threshold`].
+    pub fn terms(&self, lons: &[f64], lats: &[f64], out: &mut Vec<f64>) {
+        assert_eq!(lons.len(), lats.len(), "coordinate slices differ in length");
+
+        out.clear();
+        out.reserve(lons.len());
+
+        let mut lon_chunks = lons.chunks_exact(LANES);
+        let mut lat_chunks = lats.chunks_exact(LANES);
+        for (lon, lat) in (&mut lon_chunks).zip(&mut lat_chunks) {
+            let mut a = [0.0; LANES];
+            for i in 0..LANES {
+                a[i] = self.term(lon[i], lat[i]);
+            }
+            out.extend_from_slice(&a);
+        }
+        for (&lon, &lat) in lon_chunks.remainder().iter().zip(lat_chunks.remainder()) {
+            out.push(self.term(lon, lat));
+        }
+    }
+
+    /// Convenience wrapper returning distances in meters.
+    pub fn distances(&self, lons: &[f64], lats: &[f64]) -> Vec<f64> {
+        let mut out = Vec::new();
+        self.terms(lons, lats, &mut out);
+        out.iter_mut().for_each(|a| *a = Self::distance(*a));
+        out
+    }
+
+    /// Largest haversine term whose distance is within `radius` meters.
+    pub fn term_threshold(radius: f64) -> f64 {
+        let half_angle = radius / (2.0 * EARTH_RADIUS);
+        if half_angle >= FRAC_PI_2 {
+            // The radius covers the whole sphere.
+            1.0
+        } else {
+            half_angle.sin().powi(2)
+        }
+    }
+
+    /// Convert a haversine term to a distance in meters.
+    #[inline]
+    pub fn distance(term: f64) -> f64 {
+        2.0 * EARTH_RADIUS * term.clamp(0.0, 1.0).sqrt().asin()
+    }
+
+    #[inline(always)]
+    fn term(&self, lon: f64, lat: f64) -> f64 {
+        let sin_dlat = sin_abs(((lat - self.lat) * 0.5).to_radians());
+        let sin_dlon = sin_abs(((lon - self.lon) * 0.5).to_radians());
+        let cos_lat = sin_abs(FRAC_PI_2 - lat.to_radians().abs());
+        sin_dlat * sin_dlat + self.cos_lat * cos_lat * sin_dlon * sin_dlon
+    }
+}
+
+/// `|sin(x)|` for `x` in `[-π, π]`, branch-free.
+///
+/// Folds `x` into `[0, π/2]` (where `|sin|` is symmetric) and evaluates the
+/// Taylor series through `x^19`; the truncation error there is below `3e-16`.
+#[inline(always)]
+fn sin_abs(x: f64) -> f64 {
+    let r = FRAC_PI_2 - (x.abs() - FRAC_PI_2).abs();
+    let r2 = r * r;
+    let mut p = -1.0 / 121_645_100_408_832_000.0;
+    p = p * r2 + 1.0 / 355_687_428_096_000.0;
+    p = p * r2 - 1.0 / 1_307_674_368_000.0;
+    p = p * r2 + 1.0 / 6_227_020_800.0;
+    p = p * r2 - 1.0 / 39_916_800.0;
+    p = p * r2 + 1.0 / 362_880.0;
+    p = p * r2 - 1.0 / 5_040.0;
+    p = p * r2 + 1.0 / 120.0;
+    p = p * r2 - 1.0 / 6.0;
+    p = p * r2 + 1.0;
+    p * r
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_scalar_haversine() {
+        let centers = [
+            Point::new(0.0, 0.0),
+            Point::new(-74.0, 40.7),
+            Point::new(179.9, -33.0),
+            Point::new(10.0, 89.99),
+        ];
+        let mut lons = Vec::new();
+        let mut lats = Vec::new();
+        for i in -17..=17 {
+            for j in -8..=8 {
+                lons.push(i as f64 * 10.0 + 0.37);
+                lats.push(j as f64 * 10.0 - 0.11);
+            }
+        }
+        // Exercise the antimeridian and the remainder (non-multiple of LANES) path.
+        lons.extend([-179.95, 180.0, 0.0]);
+        lats.extend([-33.0, 90.0, -90.0]);
+        assert_ne!(lons.len() % LANES, 0);
+
+        for center in &centers {
+            let batch = HaversineBatch::new(center).distances(&lons, &lats);
+            for ((&lon, &lat), &d) in lons.iter().zip(&lats).zip(&batch) {
+                let expected = center.haversine_distance(&Point::new(lon, lat));
+                // Absolute tolerance for short ranges, relative near antipodes.
+                let tolerance = (expected * 1e-10).max(1e-6);
+                assert!(
+                    (d - expected).abs() < tolerance,
+                    "({}, {}): batch {} vs scalar {}",
+                    lon,
+                    lat,
+                    d,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_term_threshold() {
+        let center = Point::new(13.4, 52.5);
+        let near = Point::new(13.41, 52.5);
+        let d = center.haversine_distance(&near);
+        let batch = HaversineBatch::new(&center);
+
+        let mut terms = Vec::new();
+        batch.terms(&[near.x()], &[near.y()], &mut terms);
+        assert!(terms[0] <= HaversineBatch::term_threshold(d + 1e-3));
+        assert!(terms[0] > HaversineBatch::term_threshold(d - 1e-3));
+        assert_eq!(HaversineBatch::term_threshold(f64::MAX), 1.0);
+    }
+}
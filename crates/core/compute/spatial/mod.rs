@@ -1,8 +1,18 @@
 pub mod algorithms;
 pub use algorithms::{
     DistanceMetric, bounding_box, bounding_rect_for_points, convex_hull, distance_between,
-    expand_bbox, geodesic_polygon_area, knn, point_in_polygon, polygon_area,
+    distance_to_linestring, distance_to_polygon, expand_bbox, geodesic_polygon_area, knn,
+    point_in_polygon, polygon_area, snap_to_geohash_cell, snap_to_precision,
 };
 
+pub mod haversine;
+pub use haversine::HaversineBatch;
+
+pub mod point_index;
+pub use point_index::{DEFAULT_WRITE_BUFFER_CAPACITY, PointIndex};
+
+pub mod projection;
+pub use projection::LocalProjection;
+
 pub mod rtree;
-pub use rtree::{BBoxQuery, CylinderQuery, SpatialIndexManager};
+pub use rtree::{BBoxQuery, CylinderQuery, SpatialIndexManager, ZoneGeometry};
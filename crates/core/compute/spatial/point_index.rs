@@ -0,0 +1,413 @@
+//! Per-namespace point index with an optional write buffer.
+//!
+//! By default points go straight into the namespace's R*-tree. A namespace
+//! can instead run in write-optimized mode (LSM-like): new points are appended
+//! to a small unsorted buffer, which queries scan linearly alongside the tree,
+//! and the buffer is bulk-merged into the tree once it reaches its capacity,
+//! or on the next insert once its oldest point has waited
+//! [`WRITE_BUFFER_MAX_AGE`], so a slow trickle of writes doesn't keep a full
+//! buffer's worth of points out of the tree for long. Appending is O(1) and
+//! merges amortize the tree maintenance, so sustained insert rates go up at
+//! the cost of scanning at most `capacity` extra points per query.
+//!
+//! Each key's indexed point is also kept in a side table, so a key's position
+//! can be looked up, and its entry removed, without scanning the index.
+//!
+//! Searches count the points they hand out on the calling thread (see
+//! [`candidates_examined`]), for the slow-query log.
+
+use super::rtree::{IndexedPoint3D, KeyId};
+use rstar::{AABB, Envelope, PointDistance, RTree, RTreeObject};
+use rustc_hash::FxHashMap;
+use std::cell::Cell;
+use std::iter::Peekable;
+use std::time::{Duration, Instant};
+
+thread_local! {
+    static CANDIDATES: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Points handed out by envelope and nearest-neighbor searches on this
+/// thread so far: the candidates left after envelope pruning, before exact
+/// distance or containment checks. Only differences between two calls mean
+/// anything.
+pub(crate) fn candidates_examined() -> u64 {
+    CANDIDATES.with(Cell::get)
+}
+
+/// Counts the points drawn from a search, adding them to [`CANDIDATES`]
+/// once when dropped.
+struct Counted<I> {
+    inner: I,
+    count: u64,
+}
+
+impl<I> Counted<I> {
+    fn new(inner: I) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl<I: Iterator> Iterator for Counted<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.inner.next();
+        self.count += u64::from(item.is_some());
+        item
+    }
+}
+
+impl<I> Drop for Counted<I> {
+    fn drop(&mut self) {
+        if self.count > 0 {
+            CANDIDATES.with(|candidates| candidates.set(candidates.get() + self.count));
+        }
+    }
+}
+
+/// Default write buffer capacity for write-optimized namespaces.
+pub const DEFAULT_WRITE_BUFFER_CAPACITY: usize = 1024;
+
+/// A write buffer is merged by the first insert after its oldest point has
+/// waited this long, even if it isn't full.
+pub const WRITE_BUFFER_MAX_AGE: Duration = Duration::from_secs(1);
+
+/// A merge rebuilds the tree with `bulk_load` (much faster per point than
+/// incremental inserts) when the buffer is at least this fraction of the tree.
+const BULK_REBUILD_RATIO: usize = 4;
+
+/// R*-tree of points plus an optional unsorted write buffer.
+///
+/// Mirrors the subset of the `RTree` API used by the spatial index manager,
+/// so queries see buffered points transparently.
+#[derive(Clone, Default)]
+pub struct PointIndex {
+    tree: RTree<IndexedPoint3D>,
+    buffer: Vec<IndexedPoint3D>,
+    /// When the oldest buffered point was buffered; `None` while empty.
+    buffered_since: Option<Instant>,
+    /// Buffer capacity; `None` inserts directly into the tree.
+    buffer_capacity: Option<usize>,
+    /// The most recently inserted point of each key.
+    positions: FxHashMap<KeyId, IndexedPoint3D>,
+}
+
+impl PointIndex {
+    pub fn new(buffer_capacity: Option<usize>) -> Self {
+        Self {
+            tree: RTree::new(),
+            buffer: Vec::new(),
+            buffered_since: None,
+            buffer_capacity,
+            positions: FxHashMap::default(),
+        }
+    }
+
+    /// Switch write-optimized mode on (`Some(capacity)`) or off (`None`).
+    ///
+    /// Turning it off merges any buffered points.
+    pub fn set_buffer_capacity(&mut self, buffer_capacity: Option<usize>) {
+        self.buffer_capacity = buffer_capacity;
+        if buffer_capacity.is_none_or(|capacity| self.buffer.len() >= capacity) {
+            self.merge();
+        }
+    }
+
+    pub fn buffer_capacity(&self) -> Option<usize> {
+        self.buffer_capacity
+    }
+
+    /// Number of points waiting in the write buffer.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn insert(&mut self, point: IndexedPoint3D) {
+        self.positions.insert(point.id, point);
+        match self.buffer_capacity {
+            Some(capacity) => {
+                self.buffer.push(point);
+                let since = *self.buffered_since.get_or_insert_with(Instant::now);
+                if self.buffer.len() >= capacity || since.elapsed() >= WRITE_BUFFER_MAX_AGE {
+                    self.merge();
+                }
+            }
+            None => self.tree.insert(point),
+        }
+    }
+
+    /// Move all buffered points into the tree.
+    pub fn merge(&mut self) {
+        self.buffered_since = None;
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        if self.buffer.len() * BULK_REBUILD_RATIO >= self.tree.size() {
+            let mut points: Vec<IndexedPoint3D> = self.tree.iter().copied().collect();
+            points.append(&mut self.buffer);
+            self.tree = RTree::bulk_load(points);
+        } else {
+            for point in self.buffer.drain(..) {
+                self.tree.insert(point);
+            }
+        }
+    }
+
+    /// Remove a point equal to `point` (see `IndexedPoint3D`'s `PartialEq`).
+    pub fn remove(&mut self, point: &IndexedPoint3D) -> Option<IndexedPoint3D> {
+        let removed = match self.buffer.iter().position(|p| p == point) {
+            Some(pos) => Some(self.buffer.swap_remove(pos)),
+            None => self.tree.remove(point),
+        };
+        if self.buffer.is_empty() {
+            self.buffered_since = None;
+        }
+        if removed.is_some() && self.positions.get(&point.id) == Some(point) {
+            self.positions.remove(&point.id);
+        }
+        removed
+    }
+
+    /// The most recently inserted point of key `id`, if it is still indexed.
+    pub fn get(&self, id: KeyId) -> Option<&IndexedPoint3D> {
+        self.positions.get(&id)
+    }
+
+    /// Remove the most recently inserted point of key `id`.
+    pub fn remove_id(&mut self, id: KeyId) -> Option<IndexedPoint3D> {
+        let point = *self.positions.get(&id)?;
+        self.remove(&point)
+    }
+
+    /// An index over a previously built `tree` and `buffer`, taken as they
+    /// are rather than re-inserted.
+    pub fn restore(
+        tree: RTree<IndexedPoint3D>,
+        buffer: Vec<IndexedPoint3D>,
+        buffer_capacity: Option<usize>,
+    ) -> Self {
+        let positions = tree
+            .iter()
+            .chain(buffer.iter())
+            .map(|point| (point.id, *point))
+            .collect();
+        let buffered_since = (!buffer.is_empty()).then(Instant::now);
+        let mut index = Self {
+            tree,
+            buffer,
+            buffered_since,
+            buffer_capacity,
+            positions,
+        };
+        if buffer_capacity.is_none_or(|capacity| index.buffer.len() >= capacity) {
+            index.merge();
+        }
+        index
+    }
+
+    /// The R-tree and the write buffer.
+    pub fn parts(&self) -> (&RTree<IndexedPoint3D>, &[IndexedPoint3D]) {
+        (&self.tree, &self.buffer)
+    }
+
+    /// Rebuild the index with every point passed through `f`.
+    pub fn rebuild(&mut self, f: impl Fn(&IndexedPoint3D) -> IndexedPoint3D) {
+        let points: Vec<IndexedPoint3D> = self.iter().map(f).collect();
+        self.buffer.clear();
+        self.buffered_since = None;
+        self.positions = points.iter().map(|point| (point.id, *point)).collect();
+        self.tree = RTree::bulk_load(points);
+    }
+
+    pub fn size(&self) -> usize {
+        self.tree.size() + self.buffer.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &IndexedPoint3D> {
+        self.tree.iter().chain(self.buffer.iter())
+    }
+
+    /// 3D envelope of all points (empty when the index is empty).
+    pub fn envelope(&self) -> AABB<IndexedPoint3D> {
+        self.buffer
+            .iter()
+            .fold(self.tree.root().envelope(), |mut envelope, point| {
+                envelope.merge(&point.envelope());
+                envelope
+            })
+    }
+
+    pub fn locate_in_envelope<'a>(
+        &'a self,
+        envelope: &'a AABB<IndexedPoint3D>,
+    ) -> impl Iterator<Item = &'a IndexedPoint3D> {
+        self.locate_in_envelope_intersecting(envelope)
+    }
+
+    pub fn locate_in_envelope_intersecting<'a>(
+        &'a self,
+        envelope: &'a AABB<IndexedPoint3D>,
+    ) -> impl Iterator<Item = &'a IndexedPoint3D> {
+        Counted::new(
+            self.tree.locate_in_envelope_intersecting(envelope).chain(
+                self.buffer
+                    .iter()
+                    .filter(move |point| envelope.contains_point(point)),
+            ),
+        )
+    }
+
+    /// Points in ascending (squared Euclidean) distance from `query`, merging
+    /// the tree's incremental nearest-neighbor walk with the sorted buffer.
+    pub fn nearest_neighbor_iter<'a>(
+        &'a self,
+        query: &'a IndexedPoint3D,
+    ) -> impl Iterator<Item = &'a IndexedPoint3D> {
+        let mut buffered: Vec<(f64, &IndexedPoint3D)> = self
+            .buffer
+            .iter()
+            .map(|point| (point.distance_2(query), point))
+            .collect();
+        buffered.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+        Counted::new(NearestMerge {
+            tree: self
+                .tree
+                .nearest_neighbor_iter_with_distance_2(query)
+                .peekable(),
+            buffer: buffered.into_iter().peekable(),
+        })
+    }
+}
+
+struct NearestMerge<'a, T, B>
+where
+    T: Iterator<Item = (&'a IndexedPoint3D, f64)>,
+    B: Iterator<Item = (f64, &'a IndexedPoint3D)>,
+{
+    tree: Peekable<T>,
+    buffer: Peekable<B>,
+}
+
+impl<'a, T, B> Iterator for NearestMerge<'a, T, B>
+where
+    T: Iterator<Item = (&'a IndexedPoint3D, f64)>,
+    B: Iterator<Item = (f64, &'a IndexedPoint3D)>,
+{
+    type Item = &'a IndexedPoint3D;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let take_buffer = match (self.tree.peek(), self.buffer.peek()) {
+            (Some((_, tree_distance)), Some((buffer_distance, _))) => {
+                buffer_distance < tree_distance
+            }
+            (None, Some(_)) => true,
+            _ => false,
+        };
+
+        if take_buffer {
+            self.buffer.next().map(|(_, point)| point)
+        } else {
+            self.tree.next().map(|(point, _)| point)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f64, y: f64, id: u64) -> IndexedPoint3D {
+        IndexedPoint3D::new(x, y, 0.0, id)
+    }
+
+    #[test]
+    fn test_buffered_points_are_queryable_and_merged() {
+        let mut index = PointIndex::new(Some(4));
+        for i in 0..3 {
+            index.insert(point(i as f64, 0.0, i));
+        }
+        assert_eq!(index.buffered(), 3);
+        assert_eq!(index.size(), 3);
+
+        let envelope = AABB::from_corners(point(0.5, -1.0, 0), point(5.0, 1.0, 0));
+        let mut found: Vec<u64> = index
+            .locate_in_envelope_intersecting(&envelope)
+            .map(|p| p.id)
+            .collect();
+        found.sort_unstable();
+        assert_eq!(found, vec![1, 2]);
+
+        // Reaching capacity merges the buffer into the tree.
+        index.insert(point(3.0, 0.0, 3));
+        assert_eq!(index.buffered(), 0);
+        assert_eq!(index.size(), 4);
+
+        index.insert(point(10.0, 0.0, 4));
+        assert!(index.remove(&point(10.0, 0.0, 4)).is_some());
+        assert!(index.remove(&point(0.0, 0.0, 0)).is_some());
+        assert_eq!(index.size(), 3);
+
+        index.insert(point(20.0, 0.0, 5));
+        index.set_buffer_capacity(None);
+        assert_eq!(index.buffered(), 0);
+        assert_eq!(index.size(), 4);
+    }
+
+    #[test]
+    fn test_old_buffer_is_merged_by_the_next_insert() {
+        let mut index = PointIndex::new(Some(100));
+        index.insert(point(0.0, 0.0, 0));
+        index.insert(point(1.0, 0.0, 1));
+        assert_eq!(index.buffered(), 2);
+
+        index.buffered_since = Some(Instant::now() - WRITE_BUFFER_MAX_AGE);
+        index.insert(point(2.0, 0.0, 2));
+        assert_eq!(index.buffered(), 0);
+        assert_eq!(index.size(), 3);
+
+        // The next point starts a new buffer.
+        index.insert(point(3.0, 0.0, 3));
+        assert_eq!(index.buffered(), 1);
+    }
+
+    #[test]
+    fn test_points_are_found_by_key() {
+        let mut index = PointIndex::new(Some(2));
+        index.insert(point(1.0, 0.0, 1));
+        index.insert(point(2.0, 0.0, 2));
+        index.insert(point(3.0, 0.0, 3));
+        assert_eq!(index.get(1).map(|p| p.x), Some(1.0));
+        assert_eq!(index.get(3).map(|p| p.x), Some(3.0));
+
+        assert!(index.remove_id(1).is_some());
+        assert!(index.remove(&point(3.0, 0.0, 3)).is_some());
+        assert!(index.get(1).is_none() && index.get(3).is_none());
+        assert!(index.remove_id(1).is_none());
+        assert_eq!(index.size(), 1);
+
+        index.rebuild(|p| IndexedPoint3D::new(p.x * 10.0, p.y, p.z, p.id));
+        assert_eq!(index.get(2).map(|p| p.x), Some(20.0));
+    }
+
+    #[test]
+    fn test_nearest_neighbors_interleave_tree_and_buffer() {
+        let mut index = PointIndex::new(Some(100));
+        for i in [0u64, 2, 4] {
+            index.tree.insert(point(i as f64, 0.0, i));
+        }
+        for i in [1u64, 3, 5] {
+            index.insert(point(i as f64, 0.0, i));
+        }
+
+        let query = point(0.0, 0.0, 0);
+        let order: Vec<u64> = index.nearest_neighbor_iter(&query).map(|p| p.id).collect();
+        assert_eq!(order, vec![0, 1, 2, 3, 4, 5]);
+
+        let envelope = index.envelope();
+        assert_eq!(envelope.lower().x, 0.0);
+        assert_eq!(envelope.upper().x, 5.0);
+    }
+}
@@ -0,0 +1,199 @@
+//! Local map projections for namespaces confined to a small area.
+//!
+//! A namespace whose objects stay within one region (a city, a port, a mine
+//! site) can declare a [`LocalProjection`]. Points are then also stored with
+//! projected planar coordinates, and radius/cylinder/sphere filtering inside
+//! the projection's zone uses Euclidean math instead of haversine trig.
+//!
+//! # Error bound
+//!
+//! The projection is a transverse Mercator on the same sphere as
+//! `geo::Haversine`, aligned with a UTM zone (6° wide, scale `0.9996` on the
+//! central meridian). Planar distances are corrected for the projection's
+//! scale factor, and for pairs inside the zone and up to
+//! [`LocalProjection::MAX_RADIUS`] apart they stay within
+//! [`LocalProjection::MAX_RELATIVE_ERROR`] of the haversine distance.
+//!
+//! Queries fall back to haversine for points near or beyond the zone edges,
+//! above 84°N / below 80°S (the UTM limits), and for radii larger than
+//! [`LocalProjection::MAX_RADIUS`].
+//!
+//! Projected coordinates are spherical, not WGS84 UTM grid coordinates, and
+//! are only used for distance filtering.
+
+use crate::error::{Result, SpatioError};
+use geo::HaversineMeasure;
+use serde::{Deserialize, Serialize};
+
+/// Sphere radius shared with `geo::Haversine`.
+const EARTH_RADIUS: f64 = HaversineMeasure::GRS80_MEAN_RADIUS.radius();
+
+/// UTM central meridian scale factor.
+const SCALE_FACTOR: f64 = 0.9996;
+
+/// Longitude offset from the central meridian covered by the projection:
+/// the 3° zone half-width plus half a degree of overlap into neighbours.
+const ZONE_HALF_WIDTH: f64 = 3.5;
+
+/// Latitude limits of the UTM system.
+const MAX_LATITUDE: f64 = 84.0;
+const MIN_LATITUDE: f64 = -80.0;
+
+/// UTM-zone-aligned local projection declared for a namespace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalProjection {
+    zone: u8,
+}
+
+impl LocalProjection {
+    /// Largest query radius (meters) served with planar distances.
+    pub const MAX_RADIUS: f64 = 100_000.0;
+
+    /// Worst-case relative difference from the haversine distance for pairs
+    /// inside the zone and within [`Self::MAX_RADIUS`] of each other (1 cm at
+    /// 100 km).
+    pub const MAX_RELATIVE_ERROR: f64 = 1e-7;
+
+    /// Projection aligned with UTM zone `zone` (1-60).
+    pub fn utm_zone(zone: u8) -> Result<Self> {
+        if !(1..=60).contains(&zone) {
+            return Err(SpatioError::InvalidInput(format!(
+                "UTM zone must be between 1 and 60, got: {}",
+                zone
+            )));
+        }
+        Ok(Self { zone })
+    }
+
+    /// Projection for the UTM zone containing `lon`.
+    pub fn for_longitude(lon: f64) -> Result<Self> {
+        if !lon.is_finite() || !(-180.0..=180.0).contains(&lon) {
+            return Err(SpatioError::InvalidInput(format!(
+                "Longitude must be between -180 and 180, got: {}",
+                lon
+            )));
+        }
+        let zone = (((lon + 180.0) / 6.0).floor() as u8 + 1).min(60);
+        Self::utm_zone(zone)
+    }
+
+    pub fn zone(&self) -> u8 {
+        self.zone
+    }
+
+    /// Central meridian of the zone in degrees.
+    pub fn central_meridian(&self) -> f64 {
+        f64::from(self.zone) * 6.0 - 183.0
+    }
+
+    /// Whether planar distances are accurate at `(lon, lat)`.
+    pub fn covers(&self, lon: f64, lat: f64) -> bool {
+        let mut offset = lon - self.central_meridian();
+        if offset > 180.0 {
+            offset -= 360.0;
+        } else if offset < -180.0 {
+            offset += 360.0;
+        }
+        offset.abs() <= ZONE_HALF_WIDTH && (MIN_LATITUDE..=MAX_LATITUDE).contains(&lat)
+    }
+
+    /// Project `(lon, lat)` to planar meters, or `None` outside the zone.
+    pub fn project(&self, lon: f64, lat: f64) -> Option<(f64, f64)> {
+        if !self.covers(lon, lat) {
+            return None;
+        }
+        let lambda = (lon - self.central_meridian()).to_radians();
+        let phi = lat.to_radians();
+        let b = phi.cos() * lambda.sin();
+        let x = SCALE_FACTOR * EARTH_RADIUS * b.atanh();
+        let y = SCALE_FACTOR * EARTH_RADIUS * phi.sin().atan2(phi.cos() * lambda.cos());
+        Some((x, y))
+    }
+
+    /// Distance in meters between two projected points.
+    ///
+    /// The planar length is divided by the projection's scale factor averaged
+    /// along the segment (Simpson's rule), since it grows away from the
+    /// central meridian.
+    #[inline]
+    pub fn distance(&self, a: (f64, f64), b: (f64, f64)) -> f64 {
+        let planar = (b.0 - a.0).hypot(b.1 - a.1);
+        let inverse_scale =
+            (inverse_scale(a.0) + 4.0 * inverse_scale((a.0 + b.0) * 0.5) + inverse_scale(b.0))
+                / 6.0;
+        planar * inverse_scale
+    }
+}
+
+/// `1 / k(x)` where `k = k0 * cosh(x / (k0 * R))` is the point scale factor.
+///
+/// Uses the series of `cosh` through `u^4`; within the zone `|u| < 0.07`, so
+/// the truncation error is below `2e-10`.
+#[inline(always)]
+fn inverse_scale(x: f64) -> f64 {
+    let u = x / (SCALE_FACTOR * EARTH_RADIUS);
+    let u2 = u * u;
+    1.0 / (SCALE_FACTOR * (1.0 + u2 * (0.5 + u2 / 24.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::{Destination, Haversine};
+    use spatio_types::geo::Point;
+
+    #[test]
+    fn test_zone_selection() {
+        assert_eq!(LocalProjection::for_longitude(-74.0).unwrap().zone(), 18);
+        assert_eq!(LocalProjection::for_longitude(180.0).unwrap().zone(), 60);
+        assert_eq!(
+            LocalProjection::utm_zone(33).unwrap().central_meridian(),
+            15.0
+        );
+        assert!(LocalProjection::utm_zone(0).is_err());
+        assert!(LocalProjection::for_longitude(f64::NAN).is_err());
+
+        let zone = LocalProjection::utm_zone(18).unwrap();
+        assert!(zone.covers(-74.0, 40.7));
+        assert!(!zone.covers(-80.0, 40.7));
+        assert!(!zone.covers(-74.0, 85.0));
+        assert!(zone.project(-80.0, 40.7).is_none());
+    }
+
+    #[test]
+    fn test_planar_distance_error_bound() {
+        let projection = LocalProjection::utm_zone(18).unwrap();
+        let cm = projection.central_meridian();
+
+        for lat in [-79.0, -45.0, 0.0, 40.7, 70.0, 83.5] {
+            for lon_offset in [-3.4, -1.5, 0.0, 2.0, 3.4] {
+                let origin = Point::new(cm + lon_offset, lat);
+                for bearing in [0.0, 45.0, 90.0, 135.0, 200.0, 300.0] {
+                    for distance in [10.0, 1_000.0, 25_000.0, LocalProjection::MAX_RADIUS] {
+                        let target: Point = Haversine
+                            .destination(*origin.inner(), bearing, distance)
+                            .into();
+                        let (Some(a), Some(b)) = (
+                            projection.project(origin.x(), origin.y()),
+                            projection.project(target.x(), target.y()),
+                        ) else {
+                            continue;
+                        };
+
+                        let expected = origin.haversine_distance(&target);
+                        let error = (projection.distance(a, b) - expected).abs() / expected;
+                        assert!(
+                            error <= LocalProjection::MAX_RELATIVE_ERROR,
+                            "lat {} offset {} bearing {} distance {}: error {}",
+                            lat,
+                            lon_offset,
+                            bearing,
+                            distance,
+                            error
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
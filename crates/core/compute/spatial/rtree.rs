@@ -20,15 +20,22 @@
 //! let results = db.query_radius("aircraft", &center, 10000.0, 100).unwrap();
 //! ```
 
+use super::algorithms::{DistanceMetric, distance_between, knn};
+use super::haversine::HaversineBatch;
+use super::point_index::PointIndex;
+use super::projection::LocalProjection;
 use crate::config::BoundingBox2D;
 use bytes::Bytes;
-use geo::HaversineMeasure;
+use geo::{BoundingRect, Closest, HaversineClosestPoint, HaversineMeasure, Intersects};
 use rstar::{AABB, Point as RstarPoint, RTree};
 use rustc_hash::FxHashMap;
-use spatio_types::geo::Point as GeoPoint;
+use serde::{Deserialize, Serialize};
+use spatio_types::geo::{Point as GeoPoint, Polygon as GeoPolygon};
 use spatio_types::point::Point3d;
+use std::cell::RefCell;
 use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, HashSet};
+use std::sync::Arc;
 
 /// Query parameters for bounding box queries.
 #[derive(Debug, Clone, Copy)]
@@ -48,20 +55,121 @@ pub struct CylinderQuery {
     pub min_z: f64,
     pub max_z: f64,
     pub radius: f64,
+    /// Metric the horizontal distance to `center` is measured in.
+    pub metric: DistanceMetric,
 }
 
+/// Interned identifier of an indexed point key.
+pub type KeyId = u64;
+
 /// 3D point for R*-tree indexing.
-#[derive(Debug, Clone, PartialEq)]
+///
+/// Entries carry an interned [`KeyId`] rather than the key itself, so they are
+/// plain `Copy` data: moving candidates through queries never allocates.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct IndexedPoint3D {
     pub x: f64,
     pub y: f64,
     pub z: f64,
-    pub key: String,
+    pub id: KeyId,
+    /// Planar coordinates under the namespace's [`LocalProjection`]
+    /// (`NaN` when the namespace has none or the point is outside its zone).
+    pub px: f64,
+    pub py: f64,
 }
 
 impl IndexedPoint3D {
-    pub fn new(x: f64, y: f64, z: f64, key: String) -> Self {
-        Self { x, y, z, key }
+    pub fn new(x: f64, y: f64, z: f64, id: KeyId) -> Self {
+        Self {
+            x,
+            y,
+            z,
+            id,
+            px: f64::NAN,
+            py: f64::NAN,
+        }
+    }
+
+    /// Attach (or drop) projected coordinates for `projection`.
+    fn with_projection(mut self, projection: Option<&LocalProjection>) -> Self {
+        let (px, py) = projection
+            .and_then(|p| p.project(self.x, self.y))
+            .unwrap_or((f64::NAN, f64::NAN));
+        self.px = px;
+        self.py = py;
+        self
+    }
+
+    #[inline]
+    fn projected(&self) -> Option<(f64, f64)> {
+        (!self.px.is_nan()).then_some((self.px, self.py))
+    }
+}
+
+// Projected coordinates are derived data, so equality (used by R-tree
+// removal) only considers the position and key.
+impl PartialEq for IndexedPoint3D {
+    fn eq(&self, other: &Self) -> bool {
+        self.x == other.x && self.y == other.y && self.z == other.z && self.id == other.id
+    }
+}
+
+/// Side table mapping point keys to compact ids and back.
+///
+/// Keys are reference-counted per index entry, so a key is forgotten once its
+/// last entry is removed. Resolving an id hands out a shared `Arc<str>`
+/// (a refcount bump) instead of cloning a `String` per query hit.
+#[derive(Clone, Default)]
+struct KeyInterner {
+    ids: FxHashMap<Arc<str>, KeyId>,
+    keys: FxHashMap<KeyId, (Arc<str>, usize)>,
+    next_id: KeyId,
+}
+
+impl KeyInterner {
+    fn acquire(&mut self, key: String) -> KeyId {
+        if let Some(&id) = self.ids.get(key.as_str()) {
+            if let Some((_, refs)) = self.keys.get_mut(&id) {
+                *refs += 1;
+            }
+            return id;
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        let key: Arc<str> = Arc::from(key);
+        self.ids.insert(key.clone(), id);
+        self.keys.insert(id, (key, 1));
+        id
+    }
+
+    fn release(&mut self, id: KeyId) {
+        let Some((key, refs)) = self.keys.get_mut(&id) else {
+            return;
+        };
+        *refs -= 1;
+        if *refs == 0 {
+            let key = key.clone();
+            self.keys.remove(&id);
+            self.ids.remove(&key);
+        }
+    }
+
+    fn id_of(&self, key: &str) -> Option<KeyId> {
+        self.ids.get(key).copied()
+    }
+
+    fn key_of(&self, id: KeyId) -> Option<&str> {
+        self.keys.get(&id).map(|(key, _)| key.as_ref())
+    }
+
+    fn resolve(&self, id: KeyId) -> Option<Arc<str>> {
+        self.keys.get(&id).map(|(key, _)| key.clone())
+    }
+
+    fn clear(&mut self) {
+        self.ids.clear();
+        self.keys.clear();
     }
 }
 
@@ -74,7 +182,9 @@ impl RstarPoint for IndexedPoint3D {
             x: generator(0),
             y: generator(1),
             z: generator(2),
-            key: String::new(),
+            id: 0,
+            px: f64::NAN,
+            py: f64::NAN,
         }
     }
 
@@ -116,8 +226,96 @@ impl rstar::RTreeObject for IndexedBBox {
     }
 }
 
+/// Geometry of a stored zone (service area, geofence, region).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ZoneGeometry {
+    BBox(BoundingBox2D),
+    Polygon(GeoPolygon),
+}
+
+impl ZoneGeometry {
+    /// 2D envelope as `(min_x, min_y, max_x, max_y)`, `None` for an empty polygon.
+    pub fn bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        match self {
+            ZoneGeometry::BBox(bbox) => {
+                Some((bbox.min_x(), bbox.min_y(), bbox.max_x(), bbox.max_y()))
+            }
+            ZoneGeometry::Polygon(polygon) => {
+                let rect = polygon.inner().bounding_rect()?;
+                Some((rect.min().x, rect.min().y, rect.max().x, rect.max().y))
+            }
+        }
+    }
+
+    /// Whether `point` lies inside the zone or on its boundary.
+    pub fn contains(&self, point: &GeoPoint) -> bool {
+        match self {
+            ZoneGeometry::BBox(bbox) => bbox.rect.intersects(point.inner()),
+            ZoneGeometry::Polygon(polygon) => polygon.inner().intersects(point.inner()),
+        }
+    }
+
+    /// Whether the zone and `polygon` share any point, boundaries included.
+    pub fn intersects_polygon(&self, polygon: &GeoPolygon) -> bool {
+        match self {
+            ZoneGeometry::BBox(bbox) => bbox.rect.intersects(polygon.inner()),
+            ZoneGeometry::Polygon(zone) => zone.inner().intersects(polygon.inner()),
+        }
+    }
+
+    /// Haversine distance in meters from `point` to the zone boundary.
+    ///
+    /// Points inside the zone (or on its boundary) are at distance `0.0`.
+    pub fn boundary_distance(&self, point: &GeoPoint) -> f64 {
+        let closest = match self {
+            ZoneGeometry::BBox(bbox) => bbox.rect.haversine_closest_point(point.inner()),
+            ZoneGeometry::Polygon(polygon) => {
+                polygon.inner().haversine_closest_point(point.inner())
+            }
+        };
+
+        match closest {
+            Closest::Intersection(_) => 0.0,
+            Closest::SinglePoint(p) => point.haversine_distance(&GeoPoint::from(p)),
+            Closest::Indeterminate => f64::INFINITY,
+        }
+    }
+}
+
+/// Zone entry for the geometry R*-tree, indexed by its 2D envelope.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedZone {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+    pub key: String,
+    pub geometry: ZoneGeometry,
+}
+
+impl rstar::RTreeObject for IndexedZone {
+    type Envelope = AABB<[f64; 2]>;
+
+    fn envelope(&self) -> Self::Envelope {
+        AABB::from_corners([self.min_x, self.min_y], [self.max_x, self.max_y])
+    }
+}
+
+/// Initial search radius (meters) for nearest-zone queries; doubled until
+/// enough zones are found.
+const ZONE_SEARCH_INITIAL_RADIUS: f64 = 1_000.0;
+
+/// Half the Earth's circumference: any zone is within this distance, so the
+/// nearest-zone search falls back to a full scan beyond it.
+const ZONE_SEARCH_MAX_RADIUS: f64 = 20_037_508.0;
+
+/// Relative amount geodesic and rhumb-line distances can exceed the
+/// haversine distance between the same points; the ellipsoid accounts for
+/// about half a percent.
+const METRIC_SLACK: f64 = 0.01;
+
 /// Helper struct for heap-based top-k selection (max-heap by distance)
-#[derive(Clone)]
+#[derive(Clone, Copy)]
 struct QueryCandidate {
     point: IndexedPoint3D,
     distance: f64,
@@ -143,14 +341,173 @@ impl Ord for QueryCandidate {
     }
 }
 
+/// Upper bound on the capacity retained by the per-thread candidate heap
+/// between queries, so one huge-limit query doesn't pin memory forever.
+const RETAINED_HEAP_CAPACITY: usize = 4096;
+
+thread_local! {
+    /// Per-thread top-k heap reused across queries instead of reallocating.
+    static CANDIDATE_HEAP: RefCell<BinaryHeap<QueryCandidate>> =
+        const { RefCell::new(BinaryHeap::new()) };
+}
+
+/// Select the `limit` nearest candidates, returned by ascending distance.
+///
+/// Uses a bounded max-heap borrowed from a thread-local buffer, so repeated
+/// queries on the same thread don't allocate a fresh heap each time.
+fn top_k_nearest(
+    limit: usize,
+    candidates: impl Iterator<Item = (IndexedPoint3D, f64)>,
+) -> Vec<(IndexedPoint3D, f64)> {
+    let select = |heap: &mut BinaryHeap<QueryCandidate>| {
+        for (point, distance) in candidates {
+            if heap.len() < limit {
+                heap.push(QueryCandidate { point, distance });
+            } else if let Some(worst) = heap.peek()
+                && distance < worst.distance
+            {
+                heap.pop();
+                heap.push(QueryCandidate { point, distance });
+            }
+        }
+
+        // Drain the max-heap (largest first), then reverse to ascending.
+        let mut results = Vec::with_capacity(heap.len());
+        while let Some(candidate) = heap.pop() {
+            results.push((candidate.point, candidate.distance));
+        }
+        results.reverse();
+        heap.shrink_to(RETAINED_HEAP_CAPACITY);
+        results
+    };
+
+    CANDIDATE_HEAP.with(|cell| match cell.try_borrow_mut() {
+        Ok(mut heap) => select(&mut heap),
+        // Re-entrant use on this thread: fall back to a private heap.
+        Err(_) => select(&mut BinaryHeap::new()),
+    })
+}
+
+/// Structure-of-arrays scratch space for envelope candidates, laid out for the
+/// batch haversine kernel.
+#[derive(Default)]
+struct CandidateBatch {
+    points: Vec<IndexedPoint3D>,
+    lons: Vec<f64>,
+    lats: Vec<f64>,
+    terms: Vec<f64>,
+}
+
+impl CandidateBatch {
+    fn clear(&mut self) {
+        self.points.clear();
+        self.lons.clear();
+        self.lats.clear();
+        self.terms.clear();
+    }
+}
+
+thread_local! {
+    /// Per-thread candidate batch reused across queries.
+    static CANDIDATE_BATCH: RefCell<CandidateBatch> = RefCell::new(CandidateBatch::default());
+}
+
+/// Filter envelope candidates to those within `radius` meters (haversine) of
+/// `center`, handing `(point, horizontal distance)` pairs to `consume`.
+///
+/// When the namespace has a [`LocalProjection`] covering `center` and the
+/// radius is small enough, distances use the stored planar coordinates and
+/// only points outside the zone fall back to haversine. Otherwise candidates
+/// are gathered into a thread-local batch and filtered with
+/// [`HaversineBatch`], so distances are only materialized for survivors.
+fn within_radius<'a, R>(
+    projection: Option<&LocalProjection>,
+    center: &GeoPoint,
+    radius: f64,
+    candidates: impl Iterator<Item = &'a IndexedPoint3D>,
+    consume: impl FnOnce(&mut dyn Iterator<Item = (IndexedPoint3D, f64)>) -> R,
+) -> R {
+    if let Some(projection) = projection.filter(|_| radius <= LocalProjection::MAX_RADIUS)
+        && let Some(origin) = projection.project(center.x(), center.y())
+    {
+        let mut hits = candidates.filter_map(|point| {
+            let distance = match point.projected() {
+                Some(target) => projection.distance(origin, target),
+                None => center.haversine_distance(&GeoPoint::new(point.x, point.y)),
+            };
+            (distance <= radius).then_some((*point, distance))
+        });
+        return consume(&mut hits);
+    }
+
+    let run = |batch: &mut CandidateBatch| {
+        batch.clear();
+        for point in candidates {
+            batch.points.push(*point);
+            batch.lons.push(point.x);
+            batch.lats.push(point.y);
+        }
+
+        let kernel = HaversineBatch::new(center);
+        kernel.terms(&batch.lons, &batch.lats, &mut batch.terms);
+
+        let threshold = HaversineBatch::term_threshold(radius);
+        let mut survivors = batch
+            .points
+            .iter()
+            .zip(&batch.terms)
+            .filter(|(_, term)| **term <= threshold)
+            .map(|(point, term)| (*point, HaversineBatch::distance(*term)))
+            .filter(|(_, distance)| distance.is_finite() && *distance <= radius);
+        let result = consume(&mut survivors);
+
+        batch.points.shrink_to(RETAINED_HEAP_CAPACITY);
+        batch.lons.shrink_to(RETAINED_HEAP_CAPACITY);
+        batch.lats.shrink_to(RETAINED_HEAP_CAPACITY);
+        batch.terms.shrink_to(RETAINED_HEAP_CAPACITY);
+        result
+    };
+
+    CANDIDATE_BATCH.with(|cell| match cell.try_borrow_mut() {
+        Ok(mut batch) => run(&mut batch),
+        Err(_) => run(&mut CandidateBatch::default()),
+    })
+}
+
+/// A namespace's built point index as saved by
+/// [`SpatialIndexManager::point_image`], borrowing from the live index.
+#[derive(Serialize)]
+pub struct PointImageRef<'a> {
+    projection: Option<LocalProjection>,
+    keys: Vec<(KeyId, &'a str)>,
+    tree: &'a RTree<IndexedPoint3D>,
+    buffer: &'a [IndexedPoint3D],
+}
+
+/// A saved point index read back, for [`SpatialIndexManager::restore_points`].
+/// Encodes the same as [`PointImageRef`].
+#[derive(Deserialize)]
+pub struct PointImage {
+    projection: Option<LocalProjection>,
+    keys: Vec<(KeyId, String)>,
+    tree: RTree<IndexedPoint3D>,
+    buffer: Vec<IndexedPoint3D>,
+}
+
 /// Unified spatial index manager for all spatial queries.
 ///
 /// Maintains per-prefix 3D R*-trees that handle both 2D and 3D points efficiently.
 /// 2D points are stored with z=0 coordinate in the 3D structure, allowing a single
-/// index implementation to serve all spatial query types.
+/// index implementation to serve all spatial query types. Namespaces can opt into
+/// a write-optimized mode that buffers inserts (see [`PointIndex`]).
+#[derive(Clone)]
 pub struct SpatialIndexManager {
-    pub(crate) indexes: FxHashMap<String, RTree<IndexedPoint3D>>,
+    pub(crate) indexes: FxHashMap<String, PointIndex>,
     pub(crate) bbox_indexes: FxHashMap<String, RTree<IndexedBBox>>,
+    pub(crate) zone_indexes: FxHashMap<String, RTree<IndexedZone>>,
+    projections: FxHashMap<String, LocalProjection>,
+    write_buffers: FxHashMap<String, usize>,
+    keys: KeyInterner,
 }
 
 impl SpatialIndexManager {
@@ -158,6 +515,10 @@ impl SpatialIndexManager {
         Self {
             indexes: FxHashMap::default(),
             bbox_indexes: FxHashMap::default(),
+            zone_indexes: FxHashMap::default(),
+            projections: FxHashMap::default(),
+            write_buffers: FxHashMap::default(),
+            keys: KeyInterner::default(),
         }
     }
 
@@ -166,20 +527,64 @@ impl SpatialIndexManager {
     }
 
     pub fn insert_point(&mut self, prefix: &str, x: f64, y: f64, z: f64, key: String) {
-        let point = IndexedPoint3D::new(x, y, z, key);
+        let point = IndexedPoint3D::new(x, y, z, self.keys.acquire(key))
+            .with_projection(self.projections.get(prefix));
 
         // Avoid allocating an owned prefix on the hot path: only the first
         // insert into a namespace needs to create the map entry.
         if let Some(tree) = self.indexes.get_mut(prefix) {
             tree.insert(point);
         } else {
+            let buffer_capacity = self.write_buffers.get(prefix).copied();
             self.indexes
                 .entry(prefix.to_string())
-                .or_default()
+                .or_insert_with(|| PointIndex::new(buffer_capacity))
                 .insert(point);
         }
     }
 
+    /// Switch a namespace to write-optimized mode with a write buffer of
+    /// `capacity` points, or back to direct R-tree inserts with `None`.
+    pub fn set_write_buffer(&mut self, prefix: &str, capacity: Option<usize>) {
+        match capacity {
+            Some(capacity) => self.write_buffers.insert(prefix.to_string(), capacity),
+            None => self.write_buffers.remove(prefix),
+        };
+
+        if let Some(tree) = self.indexes.get_mut(prefix) {
+            tree.set_buffer_capacity(capacity);
+        }
+    }
+
+    pub fn write_buffer(&self, prefix: &str) -> Option<usize> {
+        self.write_buffers.get(prefix).copied()
+    }
+
+    /// Merge every namespace's write buffer into its R-tree.
+    pub fn merge_write_buffers(&mut self) {
+        for tree in self.indexes.values_mut() {
+            tree.merge();
+        }
+    }
+
+    /// Declare (or with `None`, remove) the local projection of a namespace.
+    ///
+    /// Points already indexed under `prefix` are reprojected.
+    pub fn set_projection(&mut self, prefix: &str, projection: Option<LocalProjection>) {
+        match projection {
+            Some(projection) => self.projections.insert(prefix.to_string(), projection),
+            None => self.projections.remove(prefix),
+        };
+
+        if let Some(tree) = self.indexes.get_mut(prefix) {
+            tree.rebuild(|p| p.with_projection(projection.as_ref()));
+        }
+    }
+
+    pub fn projection(&self, prefix: &str) -> Option<LocalProjection> {
+        self.projections.get(prefix).copied()
+    }
+
     pub fn insert_bbox(&mut self, prefix: &str, bbox: &BoundingBox2D, key: String, data: Bytes) {
         let indexed_bbox = IndexedBBox {
             min_x: bbox.min_x(),
@@ -207,7 +612,8 @@ impl SpatialIndexManager {
     ///
     /// # Distance Metric
     ///
-    /// Hybrid 3D distance: `√(haversine_horizontal² + euclidean_vertical²)`
+    /// Hybrid 3D distance: `√(horizontal² + euclidean_vertical²)`, with the
+    /// horizontal distance under `metric`.
     ///
     /// # Assumptions
     ///
@@ -225,43 +631,113 @@ impl SpatialIndexManager {
         center: &Point3d,
         radius: f64,
         limit: usize,
-    ) -> Vec<(String, f64)> {
+        metric: DistanceMetric,
+    ) -> Vec<(Arc<str>, f64)> {
         let Some(tree) = self.indexes.get(prefix) else {
             return Vec::new();
         };
 
-        let envelope = compute_spherical_envelope(center, radius);
-        let mut heap = BinaryHeap::with_capacity(limit);
+        let center_2d = GeoPoint::new(center.x(), center.y());
+        // The horizontal distance never exceeds the 3D distance, so the
+        // horizontal filter on `radius` is a safe first pass.
+        let hits = self.within_metric_radius(
+            prefix,
+            tree,
+            &center_2d,
+            radius,
+            (center.z() - radius, center.z() + radius),
+            metric,
+            |candidates| {
+                top_k_nearest(
+                    limit,
+                    candidates.filter_map(|(point, horizontal)| {
+                        let vertical = point.z - center.z();
+                        let distance = (horizontal.powi(2) + vertical.powi(2)).sqrt();
+                        (distance <= radius).then_some((point, distance))
+                    }),
+                )
+            },
+        );
 
-        for point in tree.locate_in_envelope_intersecting(&envelope) {
-            let p2 = Point3d::new(point.x, point.y, point.z);
-            let distance = geographic_3d_distance(center, &p2);
+        self.resolve_keyed(hits)
+    }
 
-            if distance.is_finite() && distance <= radius {
-                if heap.len() < limit {
-                    heap.push(QueryCandidate {
-                        point: point.clone(),
-                        distance,
-                    });
-                } else if let Some(worst) = heap.peek()
-                    && distance < worst.distance
-                {
-                    heap.pop();
-                    heap.push(QueryCandidate {
-                        point: point.clone(),
-                        distance,
+    /// Points of `tree` with altitude in `z_range` that lie within `radius`
+    /// of `center` horizontally under `metric`, handed to `consume` with
+    /// their horizontal distances.
+    ///
+    /// Haversine goes straight through [`within_radius`]. Geodesic and rhumb
+    /// distances stay within [`METRIC_SLACK`] of haversine, so they reuse
+    /// it with a widened radius and then measure the survivors exactly.
+    /// Euclidean distances are in coordinate units and get a plain box.
+    #[allow(clippy::too_many_arguments)]
+    fn within_metric_radius<R>(
+        &self,
+        prefix: &str,
+        tree: &PointIndex,
+        center: &GeoPoint,
+        radius: f64,
+        (min_z, max_z): (f64, f64),
+        metric: DistanceMetric,
+        consume: impl FnOnce(&mut dyn Iterator<Item = (IndexedPoint3D, f64)>) -> R,
+    ) -> R {
+        let search = match metric {
+            DistanceMetric::Geodesic | DistanceMetric::Rhumb => radius * (1.0 + METRIC_SLACK),
+            DistanceMetric::Haversine | DistanceMetric::Euclidean => radius,
+        };
+        let envelopes = circle_envelopes(center, search, min_z, max_z);
+        let candidates = envelopes
+            .iter()
+            .flatten()
+            .flat_map(|envelope| tree.locate_in_envelope_intersecting(envelope))
+            .filter(|point| point.z >= min_z && point.z <= max_z);
+        match metric {
+            DistanceMetric::Haversine => within_radius(
+                self.projections.get(prefix),
+                center,
+                radius,
+                candidates,
+                consume,
+            ),
+            DistanceMetric::Geodesic | DistanceMetric::Rhumb => within_radius(
+                self.projections.get(prefix),
+                center,
+                search,
+                candidates,
+                |candidates| {
+                    let mut hits = candidates.filter_map(|(point, _)| {
+                        let distance =
+                            distance_between(center, &GeoPoint::new(point.x, point.y), metric);
+                        (distance <= radius).then_some((point, distance))
                     });
-                }
+                    consume(&mut hits)
+                },
+            ),
+            DistanceMetric::Euclidean => {
+                let envelope = AABB::from_corners(
+                    IndexedPoint3D::new(center.x() - radius, center.y() - radius, min_z, 0),
+                    IndexedPoint3D::new(center.x() + radius, center.y() + radius, max_z, 0),
+                );
+                let mut hits = tree.locate_in_envelope(&envelope).filter_map(|point| {
+                    let distance = center.euclidean_distance(&GeoPoint::new(point.x, point.y));
+                    (distance <= radius).then_some((*point, distance))
+                });
+                consume(&mut hits)
             }
         }
+    }
 
-        // Convert heap to sorted vector (ascending distance)
-        let mut results = Vec::with_capacity(heap.len());
-        while let Some(candidate) = heap.pop() {
-            results.push((candidate.point.key, candidate.distance));
-        }
-        results.reverse();
-        results
+    /// Resolve interned ids of `(point, distance)` hits back to their keys.
+    fn resolve_keyed(&self, hits: Vec<(IndexedPoint3D, f64)>) -> Vec<(Arc<str>, f64)> {
+        hits.into_iter()
+            .filter_map(|(point, distance)| Some((self.keys.resolve(point.id)?, distance)))
+            .collect()
+    }
+
+    /// Look up the key of an indexed point.
+    #[inline]
+    fn key_of(&self, point: &IndexedPoint3D) -> Option<Arc<str>> {
+        self.keys.resolve(point.id)
     }
 
     /// Query 2D points within a circular radius (internal, assumes validated input).
@@ -284,46 +760,28 @@ impl SpatialIndexManager {
         center: &GeoPoint,
         radius: f64,
         limit: usize,
-    ) -> Vec<(f64, f64, String, f64)> {
+    ) -> Vec<(f64, f64, Arc<str>, f64)> {
         let Some(tree) = self.indexes.get(prefix) else {
             return Vec::new();
         };
 
-        let envelope = compute_2d_envelope(center, radius);
-        let mut heap = BinaryHeap::with_capacity(limit);
-
-        for point in tree.locate_in_envelope_intersecting(&envelope) {
-            let p2 = GeoPoint::new(point.x, point.y);
-            let distance = center.haversine_distance(&p2);
-            if distance.is_finite() && distance <= radius {
-                if heap.len() < limit {
-                    heap.push(QueryCandidate {
-                        point: point.clone(),
-                        distance,
-                    });
-                } else if let Some(worst) = heap.peek()
-                    && distance < worst.distance
-                {
-                    heap.pop();
-                    heap.push(QueryCandidate {
-                        point: point.clone(),
-                        distance,
-                    });
-                }
-            }
-        }
+        let envelopes = compute_2d_envelopes(center, radius);
+        let hits = within_radius(
+            self.projections.get(prefix),
+            center,
+            radius,
+            envelopes
+                .iter()
+                .flatten()
+                .flat_map(|envelope| tree.locate_in_envelope_intersecting(envelope)),
+            |candidates| top_k_nearest(limit, candidates),
+        );
 
-        let mut results = Vec::with_capacity(heap.len());
-        while let Some(candidate) = heap.pop() {
-            results.push((
-                candidate.point.x,
-                candidate.point.y,
-                candidate.point.key,
-                candidate.distance,
-            ));
-        }
-        results.reverse();
-        results
+        hits.into_iter()
+            .filter_map(|(point, distance)| {
+                Some((point.x, point.y, self.key_of(&point)?, distance))
+            })
+            .collect()
     }
 
     /// Query points within a 2D bounding box, returning coordinates.
@@ -335,19 +793,19 @@ impl SpatialIndexManager {
         max_x: f64,
         max_y: f64,
         limit: usize,
-    ) -> Vec<(f64, f64, String)> {
+    ) -> Vec<(f64, f64, Arc<str>)> {
         let Some(tree) = self.indexes.get(prefix) else {
             return Vec::new();
         };
 
         let envelope = AABB::from_corners(
-            IndexedPoint3D::new(min_x, min_y, f64::NEG_INFINITY, String::new()),
-            IndexedPoint3D::new(max_x, max_y, f64::INFINITY, String::new()),
+            IndexedPoint3D::new(min_x, min_y, f64::NEG_INFINITY, 0),
+            IndexedPoint3D::new(max_x, max_y, f64::INFINITY, 0),
         );
 
         tree.locate_in_envelope(&envelope)
+            .filter_map(|p| Some((p.x, p.y, self.key_of(p)?)))
             .take(limit)
-            .map(|p| (p.x, p.y, p.key.clone()))
             .collect()
     }
 
@@ -359,7 +817,7 @@ impl SpatialIndexManager {
         prefix: &str,
         query: BBoxQuery,
         limit: usize,
-    ) -> Vec<(String,)> {
+    ) -> Vec<(Arc<str>,)> {
         let min_x = query.min_x;
         let min_y = query.min_y;
         let min_z = query.min_z;
@@ -381,13 +839,13 @@ impl SpatialIndexManager {
             return Vec::new();
         };
 
-        let min_corner = IndexedPoint3D::new(min_x, min_y, min_z, String::new());
-        let max_corner = IndexedPoint3D::new(max_x, max_y, max_z, String::new());
+        let min_corner = IndexedPoint3D::new(min_x, min_y, min_z, 0);
+        let max_corner = IndexedPoint3D::new(max_x, max_y, max_z, 0);
         let envelope = rstar::AABB::from_corners(min_corner, max_corner);
 
         tree.locate_in_envelope_intersecting(&envelope)
+            .filter_map(|point| Some((self.key_of(point)?,)))
             .take(limit)
-            .map(|point| (point.key.clone(),))
             .collect()
     }
 
@@ -399,7 +857,7 @@ impl SpatialIndexManager {
         min_y: f64,
         max_x: f64,
         max_y: f64,
-    ) -> Vec<(String,)> {
+    ) -> Vec<(Arc<str>,)> {
         self.query_within_bbox(
             prefix,
             BBoxQuery {
@@ -419,9 +877,10 @@ impl SpatialIndexManager {
             return 0;
         };
 
-        let envelope = compute_2d_envelope(center, radius);
-
-        tree.locate_in_envelope_intersecting(&envelope)
+        compute_2d_envelopes(center, radius)
+            .iter()
+            .flatten()
+            .flat_map(|envelope| tree.locate_in_envelope_intersecting(envelope))
             .filter(|point| {
                 let p2 = GeoPoint::new(point.x, point.y);
                 let distance = center.haversine_distance(&p2);
@@ -439,9 +898,10 @@ impl SpatialIndexManager {
             return false;
         };
 
-        let envelope = compute_2d_envelope(center, radius);
-
-        tree.locate_in_envelope_intersecting(&envelope)
+        compute_2d_envelopes(center, radius)
+            .iter()
+            .flatten()
+            .flat_map(|envelope| tree.locate_in_envelope_intersecting(envelope))
             .any(|point| {
                 let p2 = GeoPoint::new(point.x, point.y);
                 let distance = center.haversine_distance(&p2);
@@ -449,71 +909,31 @@ impl SpatialIndexManager {
             })
     }
 
+    /// Find the k nearest neighbors by horizontal distance under `metric`,
+    /// ignoring altitude, optionally only those within `max_distance`.
+    ///
+    /// Index order is 3D and in coordinate units, which disagrees with every
+    /// metric but `Euclidean` at altitude zero, so this scans the namespace's
+    /// points rather than walking the tree.
     pub fn knn_2d(
         &self,
         prefix: &str,
         center: &GeoPoint,
         k: usize,
-    ) -> Vec<(f64, f64, String, f64)> {
-        let Some(tree) = self.indexes.get(prefix) else {
-            return Vec::new();
-        };
-
-        let query_point = IndexedPoint3D::generate(|i| match i {
-            0 => center.x(),
-            1 => center.y(),
-            2 => 0.0,
-            _ => 0.0,
-        });
-
-        tree.nearest_neighbor_iter(&query_point)
-            .take(k)
-            .filter_map(|point| {
-                let p2 = GeoPoint::new(point.x, point.y);
-                let distance = center.haversine_distance(&p2);
-                if distance.is_finite() {
-                    Some((point.x, point.y, point.key.clone(), distance))
-                } else {
-                    None
-                }
-            })
-            .collect()
-    }
-
-    /// Find k nearest neighbors in 2D with optional max distance filter.
-    pub fn knn_2d_with_max_distance(
-        &self,
-        prefix: &str,
-        center: &GeoPoint,
-        k: usize,
         max_distance: Option<f64>,
-    ) -> Vec<(f64, f64, String, f64)> {
+        metric: DistanceMetric,
+    ) -> Vec<(Arc<str>, f64)> {
         let Some(tree) = self.indexes.get(prefix) else {
             return Vec::new();
         };
-
-        let query_point = IndexedPoint3D::generate(|i| match i {
-            0 => center.x(),
-            1 => center.y(),
-            2 => 0.0,
-            _ => 0.0,
-        });
-
-        tree.nearest_neighbor_iter(&query_point)
-            .filter_map(|point| {
-                let p2 = GeoPoint::new(point.x, point.y);
-                let distance = center.haversine_distance(&p2);
-                if !distance.is_finite() {
-                    return None;
-                }
-                if let Some(max_dist) = max_distance
-                    && distance > max_dist
-                {
-                    return None;
-                }
-                Some((point.x, point.y, point.key.clone(), distance))
-            })
-            .take(k)
+        let candidates: Vec<(GeoPoint, &IndexedPoint3D)> = tree
+            .iter()
+            .map(|point| (GeoPoint::new(point.x, point.y), point))
+            .collect();
+        knn(center, &candidates, k, metric)
+            .into_iter()
+            .take_while(|(_, distance, _)| max_distance.is_none_or(|max| *distance <= max))
+            .filter_map(|(_, distance, point)| Some((self.key_of(point)?, distance)))
             .collect()
     }
 
@@ -523,53 +943,26 @@ impl SpatialIndexManager {
         prefix: &str,
         query: CylinderQuery,
         limit: usize,
-    ) -> Vec<(String, f64)> {
-        let center = query.center;
-        let min_z = query.min_z;
-        let max_z = query.max_z;
-        let radius = query.radius;
+    ) -> Vec<(Arc<str>, f64)> {
         let Some(tree) = self.indexes.get(prefix) else {
             return Vec::new();
         };
 
-        let envelope = compute_cylindrical_envelope(&center, min_z, max_z, radius);
-        let mut heap = BinaryHeap::with_capacity(limit);
-
-        for point in tree.locate_in_envelope_intersecting(&envelope) {
-            if point.z < min_z || point.z > max_z {
-                continue;
-            }
-
-            let p2 = GeoPoint::new(point.x, point.y);
-            let h_dist = center.haversine_distance(&p2);
-            if h_dist <= radius {
-                if heap.len() < limit {
-                    heap.push(QueryCandidate {
-                        point: point.clone(),
-                        distance: h_dist,
-                    });
-                } else if let Some(worst) = heap.peek()
-                    && h_dist < worst.distance
-                {
-                    heap.pop();
-                    heap.push(QueryCandidate {
-                        point: point.clone(),
-                        distance: h_dist,
-                    });
-                }
-            }
-        }
+        let hits = self.within_metric_radius(
+            prefix,
+            tree,
+            &query.center,
+            query.radius,
+            (query.min_z, query.max_z),
+            query.metric,
+            |candidates| top_k_nearest(limit, candidates),
+        );
 
-        let mut results = Vec::with_capacity(heap.len());
-        while let Some(candidate) = heap.pop() {
-            results.push((candidate.point.key, candidate.distance));
-        }
-        results.reverse();
-        results
+        self.resolve_keyed(hits)
     }
 
     /// Find k nearest neighbors in 3D space.
-    pub fn knn_3d(&self, prefix: &str, center: &Point3d, k: usize) -> Vec<(String, f64)> {
+    pub fn knn_3d(&self, prefix: &str, center: &Point3d, k: usize) -> Vec<(Arc<str>, f64)> {
         let Some(tree) = self.indexes.get(prefix) else {
             return Vec::new();
         };
@@ -587,7 +980,7 @@ impl SpatialIndexManager {
                 let p2 = Point3d::new(point.x, point.y, point.z);
                 let distance = geographic_3d_distance(center, &p2);
                 if distance.is_finite() {
-                    Some((point.key.clone(), distance))
+                    Some((self.key_of(point)?, distance))
                 } else {
                     None
                 }
@@ -608,9 +1001,10 @@ impl SpatialIndexManager {
             return false;
         };
 
-        let envelope = compute_cylindrical_envelope(center, min_z, max_z, tolerance);
-
-        tree.locate_in_envelope_intersecting(&envelope)
+        compute_cylindrical_envelopes(center, min_z, max_z, tolerance)
+            .iter()
+            .flatten()
+            .flat_map(|envelope| tree.locate_in_envelope_intersecting(envelope))
             .any(|point| {
                 let p2 = GeoPoint::new(point.x, point.y);
                 let horizontal_distance = center.haversine_distance(&p2);
@@ -627,23 +1021,22 @@ impl SpatialIndexManager {
         let Some(tree) = self.indexes.get_mut(prefix) else {
             return false;
         };
-
-        if let Some((x, y, z)) = old_coords {
+        let mut removed = false;
+        if let Some(id) = self.keys.id_of(key) {
             // Fast path: O(log N) removal using known coordinates
-            let point_to_remove = IndexedPoint3D::new(x, y, z, key.to_string());
-            if tree.remove(&point_to_remove).is_some() {
-                return true;
+            if let Some((x, y, z)) = old_coords {
+                removed = tree.remove(&IndexedPoint3D::new(x, y, z, id)).is_some();
             }
-        }
 
-        // Slow path: O(N) scan
-        let to_remove: Option<IndexedPoint3D> = tree.iter().find(|p| p.key == key).cloned();
+            // Unknown or stale coordinates: look the entry up by key.
+            if !removed {
+                removed = tree.remove_id(id).is_some();
+            }
 
-        let removed = if let Some(point) = to_remove {
-            tree.remove(&point).is_some()
-        } else {
-            false
-        };
+            if removed {
+                self.keys.release(id);
+            }
+        }
 
         // Also remove from bbox index if present
         if let Some(bbox_tree) = self.bbox_indexes.get_mut(prefix) {
@@ -656,6 +1049,75 @@ impl SpatialIndexManager {
         removed
     }
 
+    /// The point index of `prefix` as it is built, R-tree nodes included, so
+    /// it can be saved and later restored without re-inserting its points.
+    pub fn point_image(&self, prefix: &str) -> Option<PointImageRef<'_>> {
+        let index = self.indexes.get(prefix)?;
+        let (tree, buffer) = index.parts();
+        let keys = index
+            .iter()
+            .filter_map(|point| Some((point.id, self.keys.key_of(point.id)?)))
+            .collect();
+        Some(PointImageRef {
+            projection: self.projection(prefix),
+            keys,
+            tree,
+            buffer,
+        })
+    }
+
+    /// Install a saved point index as the index of `prefix`, reprojecting it
+    /// if the namespace's projection changed since it was saved. Returns
+    /// `false`, leaving the index alone, if `prefix` already has points.
+    pub fn restore_points(&mut self, prefix: &str, image: PointImage) -> bool {
+        if self
+            .indexes
+            .get(prefix)
+            .is_some_and(|index| index.size() > 0)
+        {
+            return false;
+        }
+        for (id, key) in image.keys {
+            let key: Arc<str> = Arc::from(key);
+            self.keys.ids.insert(key.clone(), id);
+            self.keys.keys.insert(id, (key, 1));
+            self.keys.next_id = self.keys.next_id.max(id + 1);
+        }
+        let mut index = PointIndex::restore(
+            image.tree,
+            image.buffer,
+            self.write_buffers.get(prefix).copied(),
+        );
+        let projection = self.projection(prefix);
+        if image.projection != projection {
+            index.rebuild(|p| p.with_projection(projection.as_ref()));
+        }
+        self.indexes.insert(prefix.to_string(), index);
+        true
+    }
+
+    /// Number of points indexed under `prefix`.
+    pub fn point_count(&self, prefix: &str) -> usize {
+        self.indexes.get(prefix).map_or(0, PointIndex::size)
+    }
+
+    /// Keys of the points indexed under `prefix`.
+    pub fn point_keys(&self, prefix: &str) -> Vec<Arc<str>> {
+        let Some(index) = self.indexes.get(prefix) else {
+            return Vec::new();
+        };
+        index
+            .iter()
+            .filter_map(|point| self.keys.resolve(point.id))
+            .collect()
+    }
+
+    /// Indexed position of `key` under `prefix`, found without scanning.
+    pub fn position_of(&self, prefix: &str, key: &str) -> Option<(f64, f64, f64)> {
+        let point = self.indexes.get(prefix)?.get(self.keys.id_of(key)?)?;
+        Some((point.x, point.y, point.z))
+    }
+
     /// Find intersecting bounding boxes.
     pub fn find_intersecting_bboxes(&self, prefix: &str, bbox: &BoundingBox2D) -> Vec<(String,)> {
         let Some(tree) = self.bbox_indexes.get(prefix) else {
@@ -670,6 +1132,155 @@ impl SpatialIndexManager {
             .collect()
     }
 
+    /// Index a zone geometry under `key`, replacing any previous zone with the same key.
+    ///
+    /// Returns `false` (and indexes nothing) if the geometry has no envelope.
+    pub fn insert_zone(&mut self, prefix: &str, key: String, geometry: ZoneGeometry) -> bool {
+        let Some((min_x, min_y, max_x, max_y)) = geometry.bounds() else {
+            return false;
+        };
+        self.remove_zone(prefix, &key);
+
+        let zone = IndexedZone {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+            key,
+            geometry,
+        };
+        if let Some(tree) = self.zone_indexes.get_mut(prefix) {
+            tree.insert(zone);
+        } else {
+            self.zone_indexes
+                .entry(prefix.to_string())
+                .or_default()
+                .insert(zone);
+        }
+        true
+    }
+
+    /// Remove a zone by key. Returns `true` if an entry was removed.
+    pub fn remove_zone(&mut self, prefix: &str, key: &str) -> bool {
+        let Some(tree) = self.zone_indexes.get_mut(prefix) else {
+            return false;
+        };
+
+        let to_remove = tree.iter().find(|z| z.key == key).cloned();
+        match to_remove {
+            Some(zone) => tree.remove(&zone).is_some(),
+            None => false,
+        }
+    }
+
+    /// Keys of the zones containing `point`, boundary included.
+    pub fn zones_containing(&self, prefix: &str, point: &GeoPoint) -> Vec<String> {
+        let Some(tree) = self.zone_indexes.get(prefix) else {
+            return Vec::new();
+        };
+        tree.locate_in_envelope_intersecting(&AABB::from_point([point.x(), point.y()]))
+            .filter(|zone| zone.geometry.contains(point))
+            .map(|zone| zone.key.clone())
+            .collect()
+    }
+
+    /// Keys of the zones sharing any point with `polygon`.
+    pub fn zones_intersecting(&self, prefix: &str, polygon: &GeoPolygon) -> Vec<String> {
+        let Some(tree) = self.zone_indexes.get(prefix) else {
+            return Vec::new();
+        };
+        let Some(rect) = polygon.inner().bounding_rect() else {
+            return Vec::new();
+        };
+        let envelope =
+            AABB::from_corners([rect.min().x, rect.min().y], [rect.max().x, rect.max().y]);
+        tree.locate_in_envelope_intersecting(&envelope)
+            .filter(|zone| zone.geometry.intersects_polygon(polygon))
+            .map(|zone| zone.key.clone())
+            .collect()
+    }
+
+    /// Zones whose boundary is within `radius` meters of `center`, with that
+    /// distance, nearest first. Zones containing `center` are at `0.0`.
+    pub fn zones_within_radius(
+        &self,
+        prefix: &str,
+        center: &GeoPoint,
+        radius: f64,
+    ) -> Vec<(String, f64)> {
+        let Some(tree) = self.zone_indexes.get(prefix) else {
+            return Vec::new();
+        };
+        let envelopes = circle_bounds(center, radius).map(|bounds| {
+            bounds.map(|[min_x, min_y, max_x, max_y]| {
+                AABB::from_corners([min_x, min_y], [max_x, max_y])
+            })
+        });
+        let mut seen = HashSet::new();
+        let mut hits: Vec<(String, f64)> = envelopes
+            .iter()
+            .flatten()
+            .flat_map(|envelope| tree.locate_in_envelope_intersecting(envelope))
+            .filter(|z| seen.insert(z.key.as_str()))
+            .map(|z| (z.key.clone(), z.geometry.boundary_distance(center)))
+            .filter(|(_, d)| *d <= radius)
+            .collect();
+        hits.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+        hits
+    }
+
+    /// Find the `k` zones nearest to `center` by boundary distance (meters).
+    ///
+    /// Zones containing `center` are at distance `0.0`. The search starts with
+    /// a small envelope around `center` and doubles it until `k` zones lie
+    /// within the searched radius, so only nearby subtrees are visited in the
+    /// common case. Results are sorted by ascending distance.
+    pub fn knn_zones(&self, prefix: &str, center: &GeoPoint, k: usize) -> Vec<(String, f64)> {
+        let Some(tree) = self.zone_indexes.get(prefix) else {
+            return Vec::new();
+        };
+        let k = k.min(tree.size());
+        if k == 0 {
+            return Vec::new();
+        }
+
+        let mut radius = ZONE_SEARCH_INITIAL_RADIUS;
+        loop {
+            let mut hits: Vec<(String, f64)> = if radius >= ZONE_SEARCH_MAX_RADIUS {
+                tree.iter()
+                    .map(|z| (z.key.clone(), z.geometry.boundary_distance(center)))
+                    .filter(|(_, d)| d.is_finite())
+                    .collect()
+            } else {
+                let envelopes = circle_bounds(center, radius).map(|bounds| {
+                    bounds.map(|[min_x, min_y, max_x, max_y]| {
+                        AABB::from_corners([min_x, min_y], [max_x, max_y])
+                    })
+                });
+                // A zone reaching across the antimeridian can intersect both
+                // envelopes; it is measured once.
+                let mut seen = HashSet::new();
+                envelopes
+                    .iter()
+                    .flatten()
+                    .flat_map(|envelope| tree.locate_in_envelope_intersecting(envelope))
+                    .filter(|z| seen.insert(z.key.as_str()))
+                    .map(|z| (z.key.clone(), z.geometry.boundary_distance(center)))
+                    .filter(|(_, d)| *d <= radius)
+                    .collect()
+            };
+
+            // Any zone within `radius` intersects the search envelope, so once
+            // `k` hits are inside the radius no unseen zone can be closer.
+            if hits.len() >= k || radius >= ZONE_SEARCH_MAX_RADIUS {
+                hits.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+                hits.truncate(k);
+                return hits;
+            }
+            radius *= 2.0;
+        }
+    }
+
     /// Get statistics about the spatial indexes.
     pub fn stats(&self) -> SpatialIndexStats {
         let mut total_points = 0;
@@ -690,7 +1301,7 @@ impl SpatialIndexManager {
             return None;
         }
 
-        let envelope = tree.root().envelope();
+        let envelope = tree.envelope();
         let min = envelope.lower();
         let max = envelope.upper();
 
@@ -706,6 +1317,25 @@ impl SpatialIndexManager {
         tree.iter().map(|p| GeoPoint::new(p.x, p.y)).collect()
     }
 
+    /// Every point entry of a namespace as its key and `[x, y, z]` position.
+    /// The key is `None` for an entry whose interned key was released.
+    pub fn namespace_entries(&self, prefix: &str) -> Vec<(Option<Arc<str>>, [f64; 3])> {
+        let Some(tree) = self.indexes.get(prefix) else {
+            return Vec::new();
+        };
+        tree.iter()
+            .map(|p| (self.key_of(p), [p.x, p.y, p.z]))
+            .collect()
+    }
+
+    /// Keys of every zone indexed in a namespace.
+    pub fn namespace_zone_keys(&self, prefix: &str) -> Vec<String> {
+        let Some(tree) = self.zone_indexes.get(prefix) else {
+            return Vec::new();
+        };
+        tree.iter().map(|zone| zone.key.clone()).collect()
+    }
+
     /// Query points within a polygon (2D).
     ///
     /// Performs exact polygon containment check on points within the polygon's bounding box.
@@ -714,9 +1344,7 @@ impl SpatialIndexManager {
         prefix: &str,
         polygon: &spatio_types::geo::Polygon,
         limit: usize,
-    ) -> Vec<(f64, f64, String)> {
-        use geo::BoundingRect;
-
+    ) -> Vec<(f64, f64, Arc<str>)> {
         let Some(tree) = self.indexes.get(prefix) else {
             return Vec::new();
         };
@@ -729,8 +1357,8 @@ impl SpatialIndexManager {
         let min = bbox.min();
         let max = bbox.max();
 
-        let min_corner = IndexedPoint3D::new(min.x, min.y, f64::NEG_INFINITY, String::new());
-        let max_corner = IndexedPoint3D::new(max.x, max.y, f64::INFINITY, String::new());
+        let min_corner = IndexedPoint3D::new(min.x, min.y, f64::NEG_INFINITY, 0);
+        let max_corner = IndexedPoint3D::new(max.x, max.y, f64::INFINITY, 0);
         let envelope = rstar::AABB::from_corners(min_corner, max_corner);
 
         // 2. Iterate, filter by polygon containment, then take(limit)
@@ -739,8 +1367,8 @@ impl SpatialIndexManager {
                 let pt = GeoPoint::new(p.x, p.y);
                 polygon.contains(&pt)
             })
+            .filter_map(|p| Some((p.x, p.y, self.key_of(p)?)))
             .take(limit)
-            .map(|p| (p.x, p.y, p.key.clone()))
             .collect()
     }
 
@@ -748,6 +1376,8 @@ impl SpatialIndexManager {
     pub fn clear(&mut self) {
         self.indexes.clear();
         self.bbox_indexes.clear();
+        self.zone_indexes.clear();
+        self.keys.clear();
     }
 }
 
@@ -766,107 +1396,84 @@ pub struct SpatialIndexStats {
     pub total_points: usize,
 }
 
-/// Compute approximate lat/lon degrees for a given radius at a latitude.
-///
-/// Uses geodesic approximations:
-/// - Latitude: Simple linear (1° ≈ 111 km everywhere)
-/// - Longitude: Cosine-corrected based on latitude
+/// Longitude/latitude boxes `[min_x, min_y, max_x, max_y]` covering every
+/// point within `radius` meters of `center`.
 ///
-/// # Polar Region Handling
+/// Uses the exact bounding coordinates of a circle on the sphere: the
+/// latitude extent is the angular radius, and the longitude extent
+/// `asin(sin(r) / cos(lat))` widens towards the poles. A circle reaching a
+/// pole spans every longitude.
 ///
-/// Near the poles, longitude degrees per meter increases dramatically as cos(latitude) → 0.
-/// To prevent extreme expansion or division by zero:
-/// - Latitude is clamped to ±89.9° for calculations
-/// - At 89.9°, cos(lat) ≈ 0.00175, giving ~6.4° longitude per km
-/// - Queries at exactly ±90° are handled safely
-///
-/// **Not recommended for queries above ±80° latitude** due to large envelope sizes.
-fn compute_lat_lon_degrees(lat: f64, radius: f64) -> (f64, f64) {
-    let lat_degrees = (radius / HaversineMeasure::GRS80_MEAN_RADIUS.radius()).to_degrees();
-
-    // Clamp latitude to avoid extreme expansion near poles
-    // At 89.9°, cos(lat) ≈ 0.00175 (conservative but prevents issues)
-    // At exactly 90°, cos would be 0 (division by zero)
-    let safe_lat = lat.abs().min(89.9);
-
-    let lon_degrees = (radius
-        / (HaversineMeasure::GRS80_MEAN_RADIUS.radius() * safe_lat.to_radians().cos()))
-    .to_degrees();
-
-    (lat_degrees, lon_degrees)
+/// The index does not wrap longitude, so a circle crossing the antimeridian
+/// is covered by two boxes, one on each side of it.
+pub(crate) fn circle_bounds(center: &GeoPoint, radius: f64) -> [Option<[f64; 4]>; 2] {
+    let angular = radius / HaversineMeasure::GRS80_MEAN_RADIUS.radius();
+    let lat_degrees = angular.to_degrees();
+    let min_y = (center.y() - lat_degrees).max(-90.0);
+    let max_y = (center.y() + lat_degrees).min(90.0);
+
+    let reaches_pole = center.y() - lat_degrees <= -90.0 || center.y() + lat_degrees >= 90.0;
+    let lon_degrees = if reaches_pole {
+        180.0
+    } else {
+        // Below the poles `sin(r) < cos(lat)`; clamp against rounding anyway.
+        (angular.sin() / center.y().to_radians().cos())
+            .min(1.0)
+            .asin()
+            .to_degrees()
+    };
+
+    let (min_x, max_x) = (center.x() - lon_degrees, center.x() + lon_degrees);
+    let (first, second) = if lon_degrees >= 180.0 {
+        ([-180.0, min_y, 180.0, max_y], None)
+    } else if min_x < -180.0 {
+        (
+            [-180.0, min_y, max_x, max_y],
+            Some([min_x + 360.0, min_y, 180.0, max_y]),
+        )
+    } else if max_x > 180.0 {
+        (
+            [min_x, min_y, 180.0, max_y],
+            Some([-180.0, min_y, max_x - 360.0, max_y]),
+        )
+    } else {
+        ([min_x, min_y, max_x, max_y], None)
+    };
+    [Some(first), second]
 }
 
-/// Compute AABB envelope for a 2D spherical query (circle).
-#[inline]
-fn compute_2d_envelope(center: &GeoPoint, radius: f64) -> rstar::AABB<IndexedPoint3D> {
-    let (lat_degrees, lon_degrees) = compute_lat_lon_degrees(center.y(), radius);
-
-    let min_x = center.x() - lon_degrees;
-    let max_x = center.x() + lon_degrees;
-    let min_y = center.y() - lat_degrees;
-    let max_y = center.y() + lat_degrees;
+/// Up to two envelopes; see [`circle_bounds`].
+type Envelopes = [Option<rstar::AABB<IndexedPoint3D>>; 2];
 
-    let min_corner = IndexedPoint3D::new(min_x, min_y, f64::NEG_INFINITY, String::new());
-    let max_corner = IndexedPoint3D::new(max_x, max_y, f64::INFINITY, String::new());
-    rstar::AABB::from_corners(min_corner, max_corner)
+/// 3D envelopes for a circle of `radius` meters around `center` between
+/// `min_z` and `max_z` (see [`circle_bounds`]).
+#[inline]
+fn circle_envelopes(center: &GeoPoint, radius: f64, min_z: f64, max_z: f64) -> Envelopes {
+    circle_bounds(center, radius).map(|bounds| {
+        bounds.map(|[min_x, min_y, max_x, max_y]| {
+            rstar::AABB::from_corners(
+                IndexedPoint3D::new(min_x, min_y, min_z, 0),
+                IndexedPoint3D::new(max_x, max_y, max_z, 0),
+            )
+        })
+    })
 }
 
-/// Compute AABB envelope for a spherical query volume.
-///
-/// # Distance Metric
-///
-/// This creates an envelope for a **hybrid 3D distance** query:
-/// - Horizontal: Geodesic (haversine) distance on Earth's surface
-/// - Vertical: Euclidean (straight-line) altitude difference
-/// - Combined: `√(horizontal² + vertical²)`
-///
-/// # Envelope Approximation
-///
-/// The envelope uses ±radius for altitude bounds, which is an **over-approximation**:
-/// - A point at `(center.lon, center.lat, center.alt ± radius)` has distance exactly `radius`
-/// - But envelope also includes points far horizontally that are within altitude range
-/// - All candidates are filtered by actual distance calculation
-///
-/// This ensures no false negatives while accepting some false positives in the envelope.
-///
-/// # Limitations
-///
-/// - Not recommended for queries above ±80° latitude (use cylindrical queries instead)
-/// - Envelope may include many points that will be filtered out by distance check
+/// Envelopes for a 2D circle query, at any altitude.
 #[inline]
-fn compute_spherical_envelope(center: &Point3d, radius: f64) -> rstar::AABB<IndexedPoint3D> {
-    let (lat_degrees, lon_degrees) = compute_lat_lon_degrees(center.y(), radius);
-
-    let min_x = center.x() - lon_degrees;
-    let max_x = center.x() + lon_degrees;
-    let min_y = center.y() - lat_degrees;
-    let max_y = center.y() + lat_degrees;
-    let min_z = center.z() - radius;
-    let max_z = center.z() + radius;
-
-    let min_corner = IndexedPoint3D::new(min_x, min_y, min_z, String::new());
-    let max_corner = IndexedPoint3D::new(max_x, max_y, max_z, String::new());
-    rstar::AABB::from_corners(min_corner, max_corner)
+fn compute_2d_envelopes(center: &GeoPoint, radius: f64) -> Envelopes {
+    circle_envelopes(center, radius, f64::NEG_INFINITY, f64::INFINITY)
 }
 
-/// Compute AABB envelope for a cylindrical query volume.
+/// Envelopes for a cylindrical query volume.
 #[inline]
-fn compute_cylindrical_envelope(
+fn compute_cylindrical_envelopes(
     center: &GeoPoint,
     min_z: f64,
     max_z: f64,
     radius: f64,
-) -> rstar::AABB<IndexedPoint3D> {
-    let (lat_degrees, lon_degrees) = compute_lat_lon_degrees(center.y(), radius);
-
-    let min_x = center.x() - lon_degrees;
-    let max_x = center.x() + lon_degrees;
-    let min_y = center.y() - lat_degrees;
-    let max_y = center.y() + lat_degrees;
-
-    let min_corner = IndexedPoint3D::new(min_x, min_y, min_z, String::new());
-    let max_corner = IndexedPoint3D::new(max_x, max_y, max_z, String::new());
-    rstar::AABB::from_corners(min_corner, max_corner)
+) -> Envelopes {
+    circle_envelopes(center, radius, min_z, max_z)
 }
 
 /// Calculate hybrid 3D distance between two points (meters).
@@ -898,7 +1505,8 @@ mod tests {
         index.insert_point("drones", -74.0, 40.7, 50.0, "drone3".to_string());
 
         let center = Point3d::new(-74.0, 40.7, 100.0);
-        let results = index.query_within_sphere("drones", &center, 1000.0, 10);
+        let results =
+            index.query_within_sphere("drones", &center, 1000.0, 10, DistanceMetric::Haversine);
         assert!(results.len() >= 2);
     }
 
@@ -924,7 +1532,7 @@ mod tests {
         );
 
         assert_eq!(results.len(), 1);
-        assert_eq!(results[0].0, "plane1");
+        assert_eq!(&*results[0].0, "plane1");
     }
 
     #[test]
@@ -944,12 +1552,13 @@ mod tests {
                 min_z: 3000.0,
                 max_z: 7000.0,
                 radius: 10000.0,
+                metric: DistanceMetric::Haversine,
             },
             10,
         );
 
         assert_eq!(results.len(), 1);
-        assert_eq!(results[0].0, "mid");
+        assert_eq!(&*results[0].0, "mid");
     }
 
     #[test]
@@ -968,6 +1577,191 @@ mod tests {
         assert_eq!(results_miss.len(), 0);
     }
 
+    #[test]
+    fn test_knn_zones_by_boundary_distance() {
+        let mut index = SpatialIndexManager::new();
+        // Large zone whose center is far away but whose edge is close.
+        let large = BoundingBox2D::new(-74.0, 40.0, -73.0, 41.0);
+        index.insert_zone("zones", "large".to_string(), ZoneGeometry::BBox(large));
+        // Small zone whose center is closer than the large zone's center.
+        let small = GeoPolygon::from_coords(
+            &[
+                (-74.2, 40.5),
+                (-74.15, 40.5),
+                (-74.15, 40.55),
+                (-74.2, 40.55),
+                (-74.2, 40.5),
+            ],
+            vec![],
+        );
+        index.insert_zone("zones", "small".to_string(), ZoneGeometry::Polygon(small));
+
+        let center = GeoPoint::new(-74.01, 40.5);
+        let results = index.knn_zones("zones", &center, 2);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, "large");
+        assert_eq!(results[1].0, "small");
+
+        // A point inside a zone is at distance zero.
+        let inside = GeoPoint::new(-73.5, 40.5);
+        let results = index.knn_zones("zones", &inside, 1);
+        assert_eq!(results[0].0, "large");
+        assert_eq!(results[0].1, 0.0);
+
+        // Far-away queries still find zones via the fallback scan.
+        let far = GeoPoint::new(100.0, -30.0);
+        assert_eq!(index.knn_zones("zones", &far, 5).len(), 2);
+
+        assert!(index.remove_zone("zones", "large"));
+        let results = index.knn_zones("zones", &center, 2);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, "small");
+    }
+
+    #[test]
+    fn test_knn_zones_across_antimeridian_are_unique() {
+        let mut index = SpatialIndexManager::new();
+        // Spans every longitude, so it meets both halves of a search circle
+        // split at the antimeridian.
+        let band = BoundingBox2D::new(-180.0, 0.0, 180.0, 1.0);
+        index.insert_zone("zones", "band".to_string(), ZoneGeometry::BBox(band));
+        let far = BoundingBox2D::new(0.0, 50.0, 1.0, 51.0);
+        index.insert_zone("zones", "far".to_string(), ZoneGeometry::BBox(far));
+
+        let center = GeoPoint::new(179.999, 0.5);
+        let keys: Vec<String> = index
+            .knn_zones("zones", &center, 2)
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, ["band", "far"]);
+    }
+
+    #[test]
+    fn test_zones_containing_and_intersecting() {
+        let mut index = SpatialIndexManager::new();
+        let square = BoundingBox2D::new(0.0, 0.0, 2.0, 2.0);
+        index.insert_zone("zones", "square".to_string(), ZoneGeometry::BBox(square));
+        // A triangle whose envelope covers (1.5, 0.5) but whose area doesn't.
+        let triangle =
+            GeoPolygon::from_coords(&[(1.0, 1.0), (3.0, 1.0), (1.0, 3.0), (1.0, 1.0)], vec![]);
+        index.insert_zone(
+            "zones",
+            "triangle".to_string(),
+            ZoneGeometry::Polygon(triangle),
+        );
+
+        let mut inside = index.zones_containing("zones", &GeoPoint::new(1.5, 1.5));
+        inside.sort();
+        assert_eq!(inside, ["square", "triangle"]);
+        assert_eq!(
+            index.zones_containing("zones", &GeoPoint::new(1.5, 0.5)),
+            ["square"]
+        );
+        assert!(
+            index
+                .zones_containing("zones", &GeoPoint::new(2.9, 2.9))
+                .is_empty()
+        );
+        assert!(
+            index
+                .zones_containing("other", &GeoPoint::new(1.5, 1.5))
+                .is_empty()
+        );
+
+        let corner = GeoPolygon::from_coords(
+            &[(2.5, 2.5), (4.0, 2.5), (4.0, 4.0), (2.5, 4.0), (2.5, 2.5)],
+            vec![],
+        );
+        assert!(index.zones_intersecting("zones", &corner).is_empty());
+        let strip = GeoPolygon::from_coords(
+            &[
+                (1.9, -1.0),
+                (2.5, -1.0),
+                (2.5, 1.2),
+                (1.9, 1.2),
+                (1.9, -1.0),
+            ],
+            vec![],
+        );
+        let mut touched = index.zones_intersecting("zones", &strip);
+        touched.sort();
+        assert_eq!(touched, ["square", "triangle"]);
+    }
+
+    #[test]
+    fn test_interned_keys_follow_entries() {
+        let mut index = SpatialIndexManager::new();
+        index.insert_point("a", 1.0, 1.0, 0.0, "obj".to_string());
+        index.insert_point("b", 2.0, 2.0, 0.0, "obj".to_string());
+        let id = index.keys.id_of("obj").unwrap();
+        assert_eq!(index.position_of("b", "obj"), Some((2.0, 2.0, 0.0)));
+        assert_eq!(index.position_of("c", "obj"), None);
+
+        // The key stays interned until its last entry is removed.
+        assert!(index.remove_entry("a", "obj", Some((1.0, 1.0, 0.0))));
+        assert_eq!(index.keys.id_of("obj"), Some(id));
+        assert!(index.remove_entry("b", "obj", Some((9.0, 9.0, 0.0))));
+        assert_eq!(index.keys.id_of("obj"), None);
+        assert_eq!(index.position_of("b", "obj"), None);
+        assert!(!index.remove_entry("b", "obj", None));
+
+        index.insert_point("a", 3.0, 3.0, 0.0, "obj".to_string());
+        let results = index.knn_3d("a", &Point3d::new(3.0, 3.0, 0.0), 1);
+        assert_eq!(&*results[0].0, "obj");
+
+        index.clear();
+        assert_eq!(index.keys.id_of("obj"), None);
+    }
+
+    #[test]
+    fn test_projected_namespace_matches_haversine() {
+        let mut plain = SpatialIndexManager::new();
+        let mut projected = SpatialIndexManager::new();
+        // Projection declared after some inserts: existing points are reprojected.
+        projected.insert_point("nyc", -74.0, 40.7, 0.0, "first".to_string());
+        projected.set_projection("nyc", Some(LocalProjection::for_longitude(-74.0).unwrap()));
+        plain.insert_point("nyc", -74.0, 40.7, 0.0, "first".to_string());
+
+        for i in 0..200 {
+            // Includes points beyond the zone edge at -78°, which use haversine.
+            let (x, y) = (-79.0 + i as f64 * 0.025, 40.0 + (i % 20) as f64 * 0.07);
+            plain.insert_point("nyc", x, y, 0.0, format!("p{}", i));
+            projected.insert_point("nyc", x, y, 0.0, format!("p{}", i));
+        }
+
+        let center = GeoPoint::new(-77.913, 40.5);
+        let expected = plain.query_within_radius_2d("nyc", &center, 90_000.0, 50);
+        let actual = projected.query_within_radius_2d("nyc", &center, 90_000.0, 50);
+        assert_eq!(expected.len(), actual.len());
+        for (e, a) in expected.iter().zip(&actual) {
+            assert_eq!(e.2, a.2);
+            assert!((e.3 - a.3).abs() <= e.3 * LocalProjection::MAX_RELATIVE_ERROR + 1e-6);
+        }
+
+        let sphere_center = Point3d::new(-74.0, 40.7, 0.0);
+        let results = projected.query_within_sphere(
+            "nyc",
+            &sphere_center,
+            1_000.0,
+            10,
+            DistanceMetric::Haversine,
+        );
+        assert_eq!(&*results[0].0, "first");
+        assert!(results[0].1 < 1e-6);
+
+        // Removal still matches entries that carry projected coordinates.
+        assert!(projected.remove_entry("nyc", "first", Some((-74.0, 40.7, 0.0))));
+        projected.set_projection("nyc", None);
+        assert_eq!(projected.projection("nyc"), None);
+        assert_eq!(
+            projected
+                .query_within_radius_2d("nyc", &center, 90_000.0, 50)
+                .len(),
+            expected.len()
+        );
+    }
+
     #[test]
     fn test_polar_region_query_doesnt_panic() {
         // Test near North Pole
@@ -978,11 +1772,12 @@ mod tests {
 
         // Query near pole should not panic or produce invalid envelopes
         let center = Point3d::new(0.0, 89.5, 1000.0);
-        let results = index.query_within_sphere("arctic", &center, 5000.0, 10);
+        let results =
+            index.query_within_sphere("arctic", &center, 5000.0, 10, DistanceMetric::Haversine);
 
         // Should find the station
         assert_eq!(results.len(), 1);
-        assert_eq!(results[0].0, "station1");
+        assert_eq!(&*results[0].0, "station1");
     }
 
     #[test]
@@ -994,7 +1789,8 @@ mod tests {
 
         // Query at pole should not panic (latitude is clamped internally)
         let center = Point3d::new(0.0, 90.0, 0.0);
-        let results = index.query_within_sphere("pole", &center, 1000.0, 10);
+        let results =
+            index.query_within_sphere("pole", &center, 1000.0, 10, DistanceMetric::Haversine);
 
         assert_eq!(results.len(), 1);
     }
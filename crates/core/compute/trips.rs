@@ -0,0 +1,158 @@
+//! Trip detection: splitting an object's trajectory into the separate
+//! journeys it made.
+//!
+//! A trip ends when the object stops reporting for longer than a gap
+//! threshold, or when it reports its ignition off: a point whose metadata has
+//! `"ignition": false` closes the trip it arrives in, and the object is parked
+//! until it next reports without it. Fixes made while parked belong to no
+//! trip.
+
+use crate::db::LocationUpdate;
+use std::time::Duration;
+
+/// Metadata key trackers set to `false` when the engine is switched off.
+pub const IGNITION_KEY: &str = "ignition";
+
+/// Summary of one trip.
+#[derive(Debug, Clone)]
+pub struct Trip {
+    /// First point of the trip.
+    pub start: LocationUpdate,
+    /// Last point of the trip.
+    pub end: LocationUpdate,
+    /// Number of points recorded during the trip, both ends included.
+    pub points: usize,
+    /// Haversine distance along the recorded points, in meters. Altitude is
+    /// ignored.
+    pub distance: f64,
+}
+
+impl Trip {
+    /// Time from the first point to the last.
+    pub fn duration(&self) -> Duration {
+        self.end
+            .timestamp
+            .duration_since(self.start.timestamp)
+            .unwrap_or_default()
+    }
+}
+
+fn ignition_off(update: &LocationUpdate) -> bool {
+    update.metadata.get(IGNITION_KEY) == Some(&serde_json::Value::Bool(false))
+}
+
+/// Split `updates` into trips separated by gaps longer than `gap_threshold`
+/// or by ignition-off reports, oldest trip first. `updates` may be in any
+/// order. Trips of a single point (a lone fix between two gaps) are dropped.
+///
+/// ```
+/// use spatio::compute::trips::split_trips;
+/// use spatio::db::LocationUpdate;
+/// use spatio::Point3d;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let at = |secs: u64, x: f64| LocationUpdate {
+///     timestamp: UNIX_EPOCH + Duration::from_secs(secs),
+///     position: Point3d::new(x, 0.0, 0.0),
+///     metadata: serde_json::Value::Null,
+/// };
+/// // Two drives with an hour parked in between.
+/// let updates = [at(0, 0.0), at(60, 0.01), at(3660, 0.01), at(3720, 0.02)];
+/// let trips = split_trips(&updates, Duration::from_secs(600));
+/// assert_eq!(trips.len(), 2);
+/// assert_eq!(trips[1].duration(), Duration::from_secs(60));
+/// ```
+pub fn split_trips(updates: &[LocationUpdate], gap_threshold: Duration) -> Vec<Trip> {
+    let mut sorted: Vec<&LocationUpdate> = updates.iter().collect();
+    sorted.sort_by_key(|u| u.timestamp);
+
+    let mut trips = Vec::new();
+    let mut current: Option<Trip> = None;
+    for update in sorted {
+        if let Some(trip) = current.as_mut() {
+            let gap = update
+                .timestamp
+                .duration_since(trip.end.timestamp)
+                .unwrap_or_default();
+            if gap <= gap_threshold {
+                trip.distance += trip.end.position.haversine_2d(&update.position);
+                trip.points += 1;
+                trip.end = update.clone();
+                if ignition_off(update) {
+                    trips.extend(current.take());
+                }
+                continue;
+            }
+            trips.extend(current.take());
+        }
+        if !ignition_off(update) {
+            current = Some(Trip {
+                start: update.clone(),
+                end: update.clone(),
+                points: 1,
+                distance: 0.0,
+            });
+        }
+    }
+    trips.extend(current);
+    trips.retain(|trip| trip.points > 1);
+    trips
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use spatio_types::point::Point3d;
+    use std::time::UNIX_EPOCH;
+
+    fn at(secs: u64, x: f64, metadata: serde_json::Value) -> LocationUpdate {
+        LocationUpdate {
+            timestamp: UNIX_EPOCH + Duration::from_secs(secs),
+            position: Point3d::new(x, 0.0, 0.0),
+            metadata,
+        }
+    }
+
+    #[test]
+    fn test_ignition_off_ends_trip() {
+        let updates = vec![
+            at(0, 0.0, json!({})),
+            at(10, 0.01, json!({})),
+            at(20, 0.02, json!({"ignition": false})),
+            // Parked: a fix while the engine is off starts nothing.
+            at(30, 0.02, json!({"ignition": false})),
+            at(40, 0.02, json!({"ignition": true})),
+            at(50, 0.03, json!({})),
+        ];
+        let trips = split_trips(&updates, Duration::from_secs(600));
+
+        assert_eq!(trips.len(), 2);
+        assert_eq!(trips[0].points, 3);
+        assert_eq!(trips[0].duration(), Duration::from_secs(20));
+        let expected = Point3d::new(0.0, 0.0, 0.0).haversine_2d(&Point3d::new(0.02, 0.0, 0.0));
+        assert!((trips[0].distance - expected).abs() < 1e-6);
+        assert_eq!(
+            trips[1].start.timestamp,
+            UNIX_EPOCH + Duration::from_secs(40)
+        );
+        assert_eq!(trips[1].points, 2);
+    }
+
+    #[test]
+    fn test_lone_fixes_are_not_trips() {
+        let updates = vec![
+            at(0, 0.0, json!({})),
+            at(1000, 0.0, json!({})),
+            at(1010, 0.01, json!({})),
+            at(5000, 0.0, json!({})),
+        ];
+        let trips = split_trips(&updates, Duration::from_secs(60));
+
+        assert_eq!(trips.len(), 1);
+        assert_eq!(
+            trips[0].start.timestamp,
+            UNIX_EPOCH + Duration::from_secs(1000)
+        );
+    }
+}
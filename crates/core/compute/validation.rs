@@ -1,8 +1,178 @@
 //! Validation for geographic coordinates.
+//!
+//! Every public entry point (the `DB` API, the RPC server, and the Python and
+//! C bindings, which all go through `DB`) validates coordinates, distances and
+//! extents with these functions before touching an index, so NaN and infinite
+//! values are rejected the same way everywhere. Failures are reported as
+//! [`SpatioError::Validation`] carrying a [`ValidationError`].
 
 use crate::error::{Result, SpatioError};
 use spatio_types::geo::Point;
 use spatio_types::point::Point3d;
+use spatio_types::query::Predicate;
+use std::fmt;
+
+/// Lowest accepted altitude in meters (Mariana Trench).
+const MIN_ALTITUDE: f64 = -11000.0;
+/// Highest accepted altitude in meters (Kármán line).
+const MAX_ALTITUDE: f64 = 100000.0;
+/// Largest accepted radius in meters (Earth's circumference).
+const EARTH_CIRCUMFERENCE: f64 = 40_075_000.0;
+
+/// Why a coordinate, distance, or extent was rejected.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ValidationError {
+    /// The value was NaN or infinite.
+    NonFinite { field: &'static str, value: f64 },
+    /// The value was outside `[min, max]`.
+    OutOfRange {
+        field: &'static str,
+        value: f64,
+        min: f64,
+        max: f64,
+    },
+    /// The value had to be greater than zero.
+    NotPositive { field: &'static str, value: f64 },
+    /// A range's lower bound was above its upper bound (or equal to it, for
+    /// ranges that must have an extent).
+    EmptyRange {
+        field: &'static str,
+        min: f64,
+        max: f64,
+    },
+    /// One element of a list or ring was invalid, e.g. `"point at index 3"`.
+    Element {
+        location: String,
+        error: Box<ValidationError>,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::NonFinite { field, value } => {
+                write!(f, "{} must be finite, got: {}", field, value)
+            }
+            ValidationError::OutOfRange {
+                field,
+                value,
+                min,
+                max,
+            } => write!(
+                f,
+                "{} out of range [{:?}, {:?}]: {}",
+                field, min, max, value
+            ),
+            ValidationError::NotPositive { field, value } => {
+                write!(f, "{} must be positive, got: {}", field, value)
+            }
+            ValidationError::EmptyRange { field, min, max } => {
+                write!(
+                    f,
+                    "{} range is empty or inverted: [{}, {}]",
+                    field, min, max
+                )
+            }
+            ValidationError::Element { location, error } => write!(f, "{}: {}", location, error),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl ValidationError {
+    fn at(self, location: String) -> Self {
+        ValidationError::Element {
+            location,
+            error: Box::new(self),
+        }
+    }
+}
+
+/// Reject NaN and infinite values.
+///
+/// # Examples
+///
+/// ```
+/// use spatio::compute::validation::validate_finite;
+///
+/// assert!(validate_finite("min_z", 0.0).is_ok());
+/// assert!(validate_finite("min_z", f64::NAN).is_err());
+/// ```
+pub fn validate_finite(field: &'static str, value: f64) -> Result<()> {
+    if !value.is_finite() {
+        return Err(ValidationError::NonFinite { field, value }.into());
+    }
+    Ok(())
+}
+
+/// Require a finite value greater than zero (widths, cell sizes, ...).
+///
+/// # Examples
+///
+/// ```
+/// use spatio::compute::validation::validate_positive;
+///
+/// assert!(validate_positive("width", 10.0).is_ok());
+/// assert!(validate_positive("width", 0.0).is_err());
+/// assert!(validate_positive("width", f64::INFINITY).is_err());
+/// ```
+pub fn validate_positive(field: &'static str, value: f64) -> Result<()> {
+    validate_finite(field, value)?;
+    if value <= 0.0 {
+        return Err(ValidationError::NotPositive { field, value }.into());
+    }
+    Ok(())
+}
+
+fn validate_in_range(field: &'static str, value: f64, min: f64, max: f64) -> Result<()> {
+    validate_finite(field, value)?;
+    if !(min..=max).contains(&value) {
+        return Err(ValidationError::OutOfRange {
+            field,
+            value,
+            min,
+            max,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Validates a finite altitude range with `min_z <= max_z` (a zero-height
+/// range is allowed).
+///
+/// # Examples
+///
+/// ```
+/// use spatio::compute::validation::validate_z_range;
+///
+/// assert!(validate_z_range(0.0, 100.0).is_ok());
+/// assert!(validate_z_range(0.0, 0.0).is_ok());
+/// assert!(validate_z_range(100.0, 0.0).is_err());
+/// assert!(validate_z_range(f64::NAN, 0.0).is_err());
+/// ```
+pub fn validate_z_range(min_z: f64, max_z: f64) -> Result<()> {
+    validate_finite("min_z", min_z)?;
+    validate_finite("max_z", max_z)?;
+    if min_z > max_z {
+        return Err(ValidationError::EmptyRange {
+            field: "z",
+            min: min_z,
+            max: max_z,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+fn validate_ordered(field: &'static str, min: f64, max: f64) -> Result<()> {
+    if min >= max {
+        return Err(ValidationError::EmptyRange { field, min, max }.into());
+    }
+    Ok(())
+}
 
 /// Validates a 2D point has valid longitude and latitude.
 ///
@@ -28,36 +198,10 @@ use spatio_types::point::Point3d;
 /// ```
 pub fn validate_geographic_point(point: &Point) -> Result<()> {
     let (x, y) = (point.x(), point.y());
-
-    if !x.is_finite() {
-        return Err(SpatioError::InvalidInput(format!(
-            "Longitude must be finite, got: {}",
-            x
-        )));
-    }
-
-    if !y.is_finite() {
-        return Err(SpatioError::InvalidInput(format!(
-            "Latitude must be finite, got: {}",
-            y
-        )));
-    }
-
-    if !(-180.0..=180.0).contains(&x) {
-        return Err(SpatioError::InvalidInput(format!(
-            "Longitude out of range [-180.0, 180.0]: {}",
-            x
-        )));
-    }
-
-    if !(-90.0..=90.0).contains(&y) {
-        return Err(SpatioError::InvalidInput(format!(
-            "Latitude out of range [-90.0, 90.0]: {}",
-            y
-        )));
-    }
-
-    Ok(())
+    validate_finite("longitude", x)?;
+    validate_finite("latitude", y)?;
+    validate_in_range("longitude", x, -180.0, 180.0)?;
+    validate_in_range("latitude", y, -90.0, 90.0)
 }
 
 /// Validates a 3D point including altitude.
@@ -80,27 +224,15 @@ pub fn validate_geographic_point(point: &Point) -> Result<()> {
 /// ```
 pub fn validate_geographic_point_3d(point: &Point3d) -> Result<()> {
     validate_geographic_point(&point.to_2d())?;
+    validate_in_range("altitude", point.z(), MIN_ALTITUDE, MAX_ALTITUDE)
+}
 
-    let z = point.z();
-
-    if !z.is_finite() {
-        return Err(SpatioError::InvalidInput(format!(
-            "Altitude must be finite, got: {}",
-            z
-        )));
-    }
-
-    const MIN_ALTITUDE: f64 = -11000.0;
-    const MAX_ALTITUDE: f64 = 100000.0;
-
-    if !(MIN_ALTITUDE..=MAX_ALTITUDE).contains(&z) {
-        return Err(SpatioError::InvalidInput(format!(
-            "Altitude out of reasonable range [{}, {}] meters: {}",
-            MIN_ALTITUDE, MAX_ALTITUDE, z
-        )));
-    }
-
-    Ok(())
+/// Tag a validation failure with where in a collection it occurred.
+fn at_element(result: Result<()>, location: impl FnOnce() -> String) -> Result<()> {
+    result.map_err(|e| match e {
+        SpatioError::Validation(error) => error.at(location()).into(),
+        other => other,
+    })
 }
 
 /// Validates multiple points.
@@ -122,8 +254,9 @@ pub fn validate_geographic_point_3d(point: &Point3d) -> Result<()> {
 /// ```
 pub fn validate_points(points: &[Point]) -> Result<()> {
     for (idx, point) in points.iter().enumerate() {
-        validate_geographic_point(point)
-            .map_err(|e| SpatioError::InvalidInput(format!("Point at index {}: {}", idx, e)))?;
+        at_element(validate_geographic_point(point), || {
+            format!("point at index {}", idx)
+        })?;
     }
     Ok(())
 }
@@ -131,8 +264,9 @@ pub fn validate_points(points: &[Point]) -> Result<()> {
 /// Validates multiple 3D points.
 pub fn validate_points_3d(points: &[Point3d]) -> Result<()> {
     for (idx, point) in points.iter().enumerate() {
-        validate_geographic_point_3d(point)
-            .map_err(|e| SpatioError::InvalidInput(format!("Point at index {}: {}", idx, e)))?;
+        at_element(validate_geographic_point_3d(point), || {
+            format!("point at index {}", idx)
+        })?;
     }
     Ok(())
 }
@@ -159,21 +293,18 @@ pub fn validate_points_3d(points: &[Point3d]) -> Result<()> {
 /// ```
 pub fn validate_polygon(polygon: &spatio_types::geo::Polygon) -> Result<()> {
     for (idx, coord) in polygon.exterior().coords().enumerate() {
-        let point = Point::new(coord.x, coord.y);
-        validate_geographic_point(&point).map_err(|e| {
-            SpatioError::InvalidInput(format!("Exterior ring point at index {}: {}", idx, e))
-        })?;
+        at_element(
+            validate_geographic_point(&Point::new(coord.x, coord.y)),
+            || format!("exterior ring point at index {}", idx),
+        )?;
     }
 
     for (ring_idx, interior) in polygon.interiors().iter().enumerate() {
         for (idx, coord) in interior.coords().enumerate() {
-            let point = Point::new(coord.x, coord.y);
-            validate_geographic_point(&point).map_err(|e| {
-                SpatioError::InvalidInput(format!(
-                    "Interior ring {} point at index {}: {}",
-                    ring_idx, idx, e
-                ))
-            })?;
+            at_element(
+                validate_geographic_point(&Point::new(coord.x, coord.y)),
+                || format!("interior ring {} point at index {}", ring_idx, idx),
+            )?;
         }
     }
 
@@ -195,26 +326,8 @@ pub fn validate_polygon(polygon: &spatio_types::geo::Polygon) -> Result<()> {
 /// assert!(validate_radius(f64::NAN).is_err());
 /// ```
 pub fn validate_radius(radius: f64) -> Result<()> {
-    if !radius.is_finite() {
-        return Err(SpatioError::InvalidInput(format!(
-            "Radius must be finite, got: {}",
-            radius
-        )));
-    }
-    if radius <= 0.0 {
-        return Err(SpatioError::InvalidInput(format!(
-            "Radius must be positive, got: {}",
-            radius
-        )));
-    }
-    const EARTH_CIRCUMFERENCE: f64 = 40_075_000.0; // meters
-    if radius > EARTH_CIRCUMFERENCE {
-        return Err(SpatioError::InvalidInput(format!(
-            "Radius {} exceeds Earth's circumference ({} meters)",
-            radius, EARTH_CIRCUMFERENCE
-        )));
-    }
-    Ok(())
+    validate_positive("radius", radius)?;
+    validate_in_range("radius", radius, 0.0, EARTH_CIRCUMFERENCE)
 }
 
 /// Validates a bounding box.
@@ -230,27 +343,10 @@ pub fn validate_radius(radius: f64) -> Result<()> {
 /// assert!(validate_bbox(10.0, -10.0, -10.0, 10.0).is_err()); // min > max
 /// ```
 pub fn validate_bbox(min_lon: f64, min_lat: f64, max_lon: f64, max_lat: f64) -> Result<()> {
-    // Validate all coordinates
-    let min_point = Point::new(min_lon, min_lat);
-    let max_point = Point::new(max_lon, max_lat);
-    validate_geographic_point(&min_point)?;
-    validate_geographic_point(&max_point)?;
-
-    // Ensure min < max
-    if min_lon >= max_lon {
-        return Err(SpatioError::InvalidInput(format!(
-            "min_lon ({}) must be < max_lon ({})",
-            min_lon, max_lon
-        )));
-    }
-    if min_lat >= max_lat {
-        return Err(SpatioError::InvalidInput(format!(
-            "min_lat ({}) must be < max_lat ({})",
-            min_lat, max_lat
-        )));
-    }
-
-    Ok(())
+    validate_geographic_point(&Point::new(min_lon, min_lat))?;
+    validate_geographic_point(&Point::new(max_lon, max_lat))?;
+    validate_ordered("lon", min_lon, max_lon)?;
+    validate_ordered("lat", min_lat, max_lat)
 }
 
 /// Validates a 3D bounding box.
@@ -262,31 +358,43 @@ pub fn validate_bbox_3d(
     max_lat: f64,
     max_alt: f64,
 ) -> Result<()> {
-    let min_point = Point3d::new(min_lon, min_lat, min_alt);
-    let max_point = Point3d::new(max_lon, max_lat, max_alt);
-    validate_geographic_point_3d(&min_point)?;
-    validate_geographic_point_3d(&max_point)?;
-
-    if min_lon >= max_lon {
-        return Err(SpatioError::InvalidInput(format!(
-            "min_lon ({}) must be < max_lon ({})",
-            min_lon, max_lon
-        )));
-    }
-    if min_lat >= max_lat {
-        return Err(SpatioError::InvalidInput(format!(
-            "min_lat ({}) must be < max_lat ({})",
-            min_lat, max_lat
-        )));
-    }
-    if min_alt >= max_alt {
-        return Err(SpatioError::InvalidInput(format!(
-            "min_alt ({}) must be < max_alt ({})",
-            min_alt, max_alt
-        )));
-    }
+    validate_geographic_point_3d(&Point3d::new(min_lon, min_lat, min_alt))?;
+    validate_geographic_point_3d(&Point3d::new(max_lon, max_lat, max_alt))?;
+    validate_ordered("lon", min_lon, max_lon)?;
+    validate_ordered("lat", min_lat, max_lat)?;
+    validate_ordered("alt", min_alt, max_alt)
+}
 
-    Ok(())
+/// Validates every leaf of a composite predicate.
+///
+/// Altitude bounds may be open on either side, but must be finite and
+/// ordered when given.
+pub fn validate_predicate(predicate: &Predicate) -> Result<()> {
+    match predicate {
+        Predicate::WithinRadius { center, radius } => {
+            validate_geographic_point(center)?;
+            validate_radius(*radius)
+        }
+        Predicate::WithinBbox {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        } => validate_bbox(*min_x, *min_y, *max_x, *max_y),
+        Predicate::WithinPolygon(polygon) => validate_polygon(polygon),
+        Predicate::Altitude { min, max } => match (*min, *max) {
+            (Some(min), Some(max)) => validate_z_range(min, max),
+            (min, max) => {
+                min.map_or(Ok(()), |min| validate_finite("min_z", min))?;
+                max.map_or(Ok(()), |max| validate_finite("max_z", max))
+            }
+        },
+        Predicate::All(predicates) | Predicate::Any(predicates) => {
+            predicates.iter().try_for_each(validate_predicate)
+        }
+        Predicate::MetadataEquals { .. } => Ok(()),
+        Predicate::Not(inner) => validate_predicate(inner),
+    }
 }
 
 #[cfg(test)]
@@ -460,4 +568,47 @@ mod tests {
         assert!(validate_bbox_3d(-10.0, -10.0, 1000.0, 10.0, 10.0, 0.0).is_err());
         assert!(validate_bbox_3d(-10.0, -10.0, -20000.0, 10.0, 10.0, 0.0).is_err());
     }
+
+    #[test]
+    fn test_typed_errors() {
+        let err = |r: Result<()>| match r {
+            Err(SpatioError::Validation(e)) => e,
+            other => panic!("expected a validation error, got {other:?}"),
+        };
+
+        assert!(matches!(
+            err(validate_geographic_point(&Point::new(f64::NAN, 0.0))),
+            ValidationError::NonFinite {
+                field: "longitude",
+                ..
+            }
+        ));
+        assert!(matches!(
+            err(validate_radius(0.0)),
+            ValidationError::NotPositive {
+                field: "radius",
+                ..
+            }
+        ));
+        assert!(matches!(
+            err(validate_geographic_point_3d(&Point3d::new(0.0, 0.0, 2e5))),
+            ValidationError::OutOfRange {
+                field: "altitude",
+                ..
+            }
+        ));
+        assert_eq!(
+            err(validate_z_range(10.0, 0.0)).to_string(),
+            "z range is empty or inverted: [10, 0]"
+        );
+
+        let e = err(validate_points(&[
+            Point::new(0.0, 0.0),
+            Point::new(0.0, f64::INFINITY),
+        ]));
+        assert_eq!(
+            e.to_string(),
+            "point at index 1: latitude must be finite, got: inf"
+        );
+    }
 }
@@ -0,0 +1,101 @@
+//! Speed-limit violations: trajectories checked against the limits of the
+//! zones they pass through.
+//!
+//! A zone's limit is its [`speed_limit`](crate::db::Zone::speed_limit)
+//! attribute. Speed is the haversine distance between consecutive points over
+//! the time between them, and a segment counts against a zone only when both
+//! of its points are inside it, so an object crossing a zone boundary at speed
+//! is judged by the zone it stayed in.
+
+use crate::db::{LocationUpdate, Zone};
+use spatio_types::geo::Point;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// An object exceeding a zone's speed limit, over one or more consecutive
+/// segments of its trajectory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeedViolation {
+    pub object_id: String,
+    pub zone_id: String,
+    /// Timestamp of the point where the object started speeding.
+    pub start: SystemTime,
+    /// Timestamp of the last point of the last speeding segment.
+    pub end: SystemTime,
+    /// Highest segment speed during the violation, in meters per second.
+    pub max_speed: f64,
+    /// The zone's limit, in meters per second.
+    pub speed_limit: f64,
+}
+
+/// Find the violations of the zones with a speed limit in `points`, which
+/// must be grouped by object ID and ordered by time within each object (as
+/// [`ColdState::scan_namespace`](crate::db::ColdState::scan_namespace) returns
+/// them). Violations come out ordered by object ID, then start time.
+pub fn speed_violations(
+    points: &[(String, LocationUpdate)],
+    zones: &[Arc<Zone>],
+) -> Vec<SpeedViolation> {
+    let limited: Vec<(&Zone, f64)> = zones
+        .iter()
+        .filter_map(|zone| Some((zone.as_ref(), zone.speed_limit()?)))
+        .collect();
+    let mut violations = Vec::new();
+    if limited.is_empty() {
+        return violations;
+    }
+
+    for track in points.chunk_by(|(a, _), (b, _)| a == b) {
+        let object_id = &track[0].0;
+        // Which limited zones each point is in.
+        let inside: Vec<Vec<bool>> = track
+            .iter()
+            .map(|(_, update)| {
+                let point = Point::new(update.position.x(), update.position.y());
+                limited
+                    .iter()
+                    .map(|(zone, _)| zone.geometry.contains(&point))
+                    .collect()
+            })
+            .collect();
+        let first = violations.len();
+        // The violation each zone has open, as an index into `violations`.
+        let mut open: Vec<Option<usize>> = vec![None; limited.len()];
+        for (i, pair) in track.windows(2).enumerate() {
+            let (from, to) = (&pair[0].1, &pair[1].1);
+            let Ok(elapsed) = to.timestamp.duration_since(from.timestamp) else {
+                continue;
+            };
+            if elapsed.is_zero() {
+                continue;
+            }
+            let speed = from.position.haversine_2d(&to.position) / elapsed.as_secs_f64();
+            for (z, (zone, limit)) in limited.iter().enumerate() {
+                if !(inside[i][z] && inside[i + 1][z] && speed > *limit) {
+                    open[z] = None;
+                    continue;
+                }
+                match open[z] {
+                    Some(index) => {
+                        let violation = &mut violations[index];
+                        violation.end = to.timestamp;
+                        violation.max_speed = violation.max_speed.max(speed);
+                    }
+                    None => {
+                        open[z] = Some(violations.len());
+                        violations.push(SpeedViolation {
+                            object_id: object_id.clone(),
+                            zone_id: zone.zone_id.clone(),
+                            start: from.timestamp,
+                            end: to.timestamp,
+                            max_speed: speed,
+                            speed_limit: *limit,
+                        });
+                    }
+                }
+            }
+        }
+        violations[first..].sort_by(|a, b| a.start.cmp(&b.start).then(a.zone_id.cmp(&b.zone_id)));
+    }
+    violations
+}
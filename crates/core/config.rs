@@ -4,15 +4,20 @@
 //! from the `spatio-types` crate for convenience.
 use bytes::Bytes;
 use serde::de::Error;
+use std::collections::{HashMap, HashSet};
 use std::time::SystemTime;
 
+pub use crate::compute::spatial::{DistanceMetric, LocalProjection};
+
 pub use spatio_types::bbox::{
     BoundingBox2D, BoundingBox3D, TemporalBoundingBox2D, TemporalBoundingBox3D,
 };
 pub use spatio_types::point::{Point3d, TemporalPoint, TemporalPoint3D};
 pub use spatio_types::polygon::{Polygon3D, PolygonDynamic, PolygonDynamic3D};
+pub use spatio_types::time::{TimeBound, TimeRange};
+pub use spatio_types::trajectory::{Trajectory, TrajectorySummary};
 
-pub use spatio_types::config::{SyncMode, SyncPolicy};
+pub use spatio_types::config::{LogCompression, SyncMode, SyncPolicy};
 
 /// Database configuration
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -27,6 +32,17 @@ pub struct Config {
     #[serde(default = "Config::default_sync_batch_size")]
     pub sync_batch_size: usize,
 
+    /// Interval between background syncs under [`SyncPolicy::Background`]
+    #[serde(default = "Config::default_sync_interval_ms")]
+    pub sync_interval_ms: u64,
+
+    /// Under [`SyncPolicy::Always`], how long a write waits for others to
+    /// share its `fsync`, in microseconds (one `fsync` per write when `None`).
+    /// Supersedes `sync_batch_size`: every write still returns durable, only
+    /// later.
+    #[serde(default)]
+    pub group_commit_us: Option<u64>,
+
     #[cfg(feature = "time-index")]
     #[serde(default)]
     pub history_capacity: Option<usize>,
@@ -35,9 +51,290 @@ pub struct Config {
     #[serde(default = "Config::default_buffer_capacity")]
     pub buffer_capacity: usize,
 
+    /// Namespaces whose objects keep a recent-history buffer (every namespace
+    /// when `None`). Writes with [`SetOptions::track_history`] buffer their
+    /// object's history in any namespace
+    #[serde(default)]
+    pub history_buffer_namespaces: Option<HashSet<String>>,
+
+    /// Most points all recent-history buffers hold together; past it the
+    /// buffers of the least recently updated objects are dropped and their
+    /// reads go to the log (unbounded when `None`)
+    #[serde(default)]
+    pub history_buffer_max_points: Option<usize>,
+
     /// Persistence configuration
     #[serde(default)]
     pub persistence: PersistenceConfig,
+
+    /// Decimal places longitude/latitude are rounded to on write (`None` keeps
+    /// full precision). Updates that snap to an object's current position
+    /// with unchanged metadata are dropped (see [`crate::DB::upsert`]).
+    #[serde(default)]
+    pub coordinate_precision: Option<u32>,
+
+    /// Sampled query access log (disabled when `None`)
+    #[serde(default)]
+    pub access_log: Option<AccessLogConfig>,
+
+    /// Rate-limited log of writes rejected by validation (disabled when
+    /// `None`)
+    #[serde(default)]
+    pub rejection_log: Option<RejectionLogConfig>,
+
+    /// Read queries taking at least this many milliseconds are logged as slow,
+    /// with the index candidates they examined (untimed when `None`)
+    #[serde(default)]
+    pub slow_query_threshold_ms: Option<u64>,
+
+    /// Local projections for namespaces confined to one area; distance
+    /// filtering in these namespaces uses planar math (see [`LocalProjection`])
+    #[serde(default)]
+    pub namespace_projections: HashMap<String, LocalProjection>,
+
+    /// Metric radius and cylinder queries measure horizontal distance in,
+    /// per namespace (haversine when absent)
+    #[serde(default)]
+    pub namespace_distance_metrics: HashMap<String, DistanceMetric>,
+
+    /// Ingest-heavy namespaces whose inserts are buffered (capacity in points)
+    /// and periodically bulk-merged into the spatial index
+    #[serde(default)]
+    pub write_optimized_namespaces: HashMap<String, usize>,
+
+    /// How long trajectory history is kept per namespace, in seconds. Older
+    /// points are hidden from trajectory queries and removed from the log on
+    /// open, by `DB::expire_history` and by active expiration; current
+    /// locations are unaffected
+    #[serde(default)]
+    pub history_retention_secs: HashMap<String, u64>,
+
+    /// Background removal of expired trajectory history (disabled when
+    /// `None`)
+    #[serde(default)]
+    pub active_expiration: Option<ActiveExpirationConfig>,
+
+    /// Free disk space limits for the log's file system (unchecked when
+    /// `None`)
+    #[serde(default)]
+    pub disk_watermarks: Option<DiskWatermarkConfig>,
+
+    /// Per-namespace object TTL, history retention and geohash precision,
+    /// applied on open (see `DB::configure_namespace` to change them later).
+    /// A namespace's retention here overrides `history_retention_secs`
+    #[serde(default)]
+    pub namespaces: HashMap<String, NamespaceConfig>,
+
+    /// How stale a snapshot handed out by `DB::reader` may get, in
+    /// milliseconds, before the next call replaces it. Zero rebuilds it after
+    /// every write
+    #[serde(default = "Config::default_reader_refresh_ms")]
+    pub reader_refresh_ms: u64,
+}
+
+/// Configuration for the sampled query access log
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccessLogConfig {
+    /// File the NDJSON access log is appended to
+    pub path: std::path::PathBuf,
+    /// Fraction of queries recorded, in `(0, 1]`
+    #[serde(default = "AccessLogConfig::default_sample_rate")]
+    pub sample_rate: f64,
+}
+
+impl AccessLogConfig {
+    const fn default_sample_rate() -> f64 {
+        0.01
+    }
+
+    pub fn new<P: Into<std::path::PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            sample_rate: Self::default_sample_rate(),
+        }
+    }
+
+    pub fn with_sample_rate(mut self, sample_rate: f64) -> Self {
+        assert!(
+            sample_rate > 0.0 && sample_rate <= 1.0,
+            "Sample rate must be in (0, 1]"
+        );
+        self.sample_rate = sample_rate;
+        self
+    }
+}
+
+/// Configuration for the log of rejected writes
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RejectionLogConfig {
+    /// File the NDJSON rejection log is appended to
+    pub path: std::path::PathBuf,
+    /// Most entries written per second; further rejections in the same second
+    /// are only counted
+    #[serde(default = "RejectionLogConfig::default_max_per_second")]
+    pub max_per_second: u32,
+    /// Bytes of the rejected payload's JSON kept in each entry (0 keeps only
+    /// its digest)
+    #[serde(default = "RejectionLogConfig::default_sample_bytes")]
+    pub sample_bytes: usize,
+}
+
+impl RejectionLogConfig {
+    const fn default_max_per_second() -> u32 {
+        10
+    }
+
+    const fn default_sample_bytes() -> usize {
+        256
+    }
+
+    pub fn new<P: Into<std::path::PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            max_per_second: Self::default_max_per_second(),
+            sample_bytes: Self::default_sample_bytes(),
+        }
+    }
+
+    pub fn with_max_per_second(mut self, max_per_second: u32) -> Self {
+        assert!(
+            max_per_second > 0,
+            "Max per second must be greater than zero"
+        );
+        self.max_per_second = max_per_second;
+        self
+    }
+
+    pub fn with_sample_bytes(mut self, sample_bytes: usize) -> Self {
+        self.sample_bytes = sample_bytes;
+        self
+    }
+}
+
+/// Configuration for background history expiration
+///
+/// Without it, history past its retention is hidden from queries at once but
+/// stays in the log, and counts toward `stats()`, until the database is
+/// reopened or `DB::expire_history` is called.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ActiveExpirationConfig {
+    /// Time between expiration passes, in milliseconds
+    pub interval_ms: u64,
+    /// Expired records a pass must find before it rewrites the log
+    pub batch_size: usize,
+}
+
+/// Settings of one namespace
+///
+/// Every field is optional: without a TTL objects stay until deleted, without
+/// a retention their trajectory history is kept forever, and without a
+/// geohash precision positions are stored as written.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NamespaceConfig {
+    /// Seconds after its last update an object is deleted, by active
+    /// expiration or `DB::expire_objects`
+    #[serde(default)]
+    pub default_ttl_secs: Option<u64>,
+    /// How long trajectory history is kept, in seconds (see
+    /// `Config::history_retention_secs`)
+    #[serde(default)]
+    pub retention_secs: Option<u64>,
+    /// Updates of trajectory history kept per object, newest first. Older
+    /// ones are removed with expired history, on open, by
+    /// `DB::expire_history` and by active expiration; until then they are
+    /// still returned by trajectory queries
+    #[serde(default)]
+    pub retention_updates: Option<usize>,
+    /// Geohash length positions are snapped to on write: each is moved to the
+    /// center of its cell, like `Config::coordinate_precision` rounds them
+    #[serde(default)]
+    pub geohash_precision: Option<usize>,
+}
+
+impl NamespaceConfig {
+    /// Delete objects not updated for `ttl` (rounded up to whole seconds).
+    pub fn with_default_ttl(mut self, ttl: std::time::Duration) -> Self {
+        let secs = ttl.as_secs() + u64::from(ttl.subsec_nanos() > 0);
+        assert!(secs > 0, "Default TTL must be greater than zero");
+        self.default_ttl_secs = Some(secs);
+        self
+    }
+
+    /// Keep trajectory history for `retention` (rounded up to whole seconds).
+    pub fn with_retention(mut self, retention: std::time::Duration) -> Self {
+        let secs = retention.as_secs() + u64::from(retention.subsec_nanos() > 0);
+        assert!(secs > 0, "History retention must be greater than zero");
+        self.retention_secs = Some(secs);
+        self
+    }
+
+    /// Keep only the newest `updates` trajectory points of each object.
+    pub fn with_retention_updates(mut self, updates: usize) -> Self {
+        assert!(updates > 0, "Retained updates must be greater than zero");
+        self.retention_updates = Some(updates);
+        self
+    }
+
+    /// Snap positions to the center of their geohash cell of `precision`
+    /// characters.
+    pub fn with_geohash_precision(mut self, precision: usize) -> Self {
+        assert!(
+            (1..=spatio_types::geohash::MAX_PRECISION).contains(&precision),
+            "Geohash precision must be between 1 and {}",
+            spatio_types::geohash::MAX_PRECISION
+        );
+        self.geohash_precision = Some(precision);
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.default_ttl_secs == Some(0) {
+            return Err("Default TTL must be greater than zero".to_string());
+        }
+        if self.retention_secs == Some(0) {
+            return Err("History retention must be greater than zero".to_string());
+        }
+        if self.retention_updates == Some(0) {
+            return Err("Retained updates must be greater than zero".to_string());
+        }
+        if let Some(precision) = self.geohash_precision
+            && !(1..=spatio_types::geohash::MAX_PRECISION).contains(&precision)
+        {
+            return Err(format!(
+                "Geohash precision must be between 1 and {}",
+                spatio_types::geohash::MAX_PRECISION
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Free disk space limits, checked periodically on the file system holding
+/// the log
+///
+/// Below `soft_bytes` a warning is logged and expired trajectory history is
+/// removed to reclaim space. Below `hard_bytes` writes that append to the log
+/// fail with `SpatioError::InsufficientDiskSpace` until space is freed, so
+/// the disk never fills up mid-record. Set `hard_bytes` to cover the writes
+/// of one check interval.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiskWatermarkConfig {
+    pub soft_bytes: u64,
+    pub hard_bytes: u64,
+    /// Time between checks, in milliseconds
+    #[serde(default = "DiskWatermarkConfig::default_check_interval_ms")]
+    pub check_interval_ms: u64,
+}
+
+impl DiskWatermarkConfig {
+    const fn default_check_interval_ms() -> u64 {
+        1000
+    }
 }
 
 /// Configuration for data persistence and durability
@@ -47,6 +344,26 @@ pub struct PersistenceConfig {
     /// Number of writes to buffer in memory before flushing to disk
     #[serde(default = "PersistenceConfig::default_buffer_size")]
     pub buffer_size: usize,
+
+    /// Compression of records in new trajectory logs. An existing log keeps
+    /// the compression it was created with. Records written between two
+    /// flushes (up to 256) are compressed together, so syncing less often
+    /// compresses better
+    #[serde(default)]
+    pub compression: LogCompression,
+
+    /// Split the trajectory log into a segment file per this many seconds
+    /// of writes, so trajectory queries read only the segments holding the
+    /// object and time range they ask for. `None` keeps one log file
+    #[serde(default)]
+    pub segment_secs: Option<u64>,
+
+    /// Open the log for reading only: writes fail with
+    /// `SpatioError::ReadOnly` and nothing in the data directory is written,
+    /// not even a checkpoint or the saved indexes. Any number of read-only
+    /// opens can share a database no writer has open
+    #[serde(default)]
+    pub read_only: bool,
 }
 
 impl PersistenceConfig {
@@ -59,6 +376,9 @@ impl Default for PersistenceConfig {
     fn default() -> Self {
         Self {
             buffer_size: Self::default_buffer_size(),
+            compression: LogCompression::default(),
+            segment_secs: None,
+            read_only: false,
         }
     }
 }
@@ -68,6 +388,14 @@ impl Config {
         1
     }
 
+    const fn default_sync_interval_ms() -> u64 {
+        100
+    }
+
+    const fn default_reader_refresh_ms() -> u64 {
+        100
+    }
+
     const fn default_sync_policy() -> SyncPolicy {
         SyncPolicy::EverySecond
     }
@@ -88,6 +416,26 @@ impl Config {
         self
     }
 
+    pub fn with_sync_interval(mut self, interval: std::time::Duration) -> Self {
+        let millis = interval.as_millis() as u64;
+        assert!(millis > 0, "Sync interval must be at least one millisecond");
+        self.sync_interval_ms = millis;
+        self
+    }
+
+    /// Under [`SyncPolicy::Always`], make writes arriving within `window` of
+    /// each other share one `fsync`. Each write still returns once it is on
+    /// stable storage, after up to `window` plus the `fsync` itself.
+    pub fn with_group_commit(mut self, window: std::time::Duration) -> Self {
+        let micros = window.as_micros() as u64;
+        assert!(
+            micros > 0,
+            "Group commit window must be at least one microsecond"
+        );
+        self.group_commit_us = Some(micros);
+        self
+    }
+
     #[cfg(feature = "time-index")]
     pub fn with_history_capacity(mut self, capacity: usize) -> Self {
         assert!(capacity > 0, "History capacity must be greater than zero");
@@ -114,11 +462,192 @@ impl Config {
         self
     }
 
+    /// Keep recent-history buffers only for objects of the namespaces named
+    /// this way and objects written with [`SetOptions::track_history`].
+    pub fn with_history_buffer_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.history_buffer_namespaces
+            .get_or_insert_with(HashSet::new)
+            .insert(namespace.into());
+        self
+    }
+
+    /// Bound the points held by all recent-history buffers together, evicting
+    /// the buffers of the least recently updated objects first.
+    pub fn with_history_buffer_max_points(mut self, max_points: usize) -> Self {
+        assert!(
+            max_points > 0,
+            "History buffer bound must be greater than zero"
+        );
+        self.history_buffer_max_points = Some(max_points);
+        self
+    }
+
     pub fn with_persistence(mut self, config: PersistenceConfig) -> Self {
         self.persistence = config;
         self
     }
 
+    /// Compress the records of a newly created trajectory log with `codec`
+    /// (see [`PersistenceConfig::compression`]).
+    pub fn with_log_compression(mut self, codec: LogCompression) -> Self {
+        self.persistence.compression = codec;
+        self
+    }
+
+    /// Start a new trajectory log segment every `period` of writes (see
+    /// [`PersistenceConfig::segment_secs`]).
+    pub fn with_log_segments(mut self, period: std::time::Duration) -> Self {
+        let secs = period.as_secs();
+        assert!(secs > 0, "Log segment period must be at least one second");
+        self.persistence.segment_secs = Some(secs);
+        self
+    }
+
+    /// Record a sample of read queries to an NDJSON access log.
+    pub fn with_access_log(mut self, config: AccessLogConfig) -> Self {
+        self.access_log = Some(config);
+        self
+    }
+
+    /// Log read queries that take at least `threshold` (see
+    /// `DB::slow_queries`).
+    pub fn with_slow_query_threshold(mut self, threshold: std::time::Duration) -> Self {
+        self.slow_query_threshold_ms = Some(threshold.as_millis() as u64);
+        self
+    }
+
+    /// Record writes rejected by validation to an NDJSON log.
+    pub fn with_rejection_log(mut self, config: RejectionLogConfig) -> Self {
+        self.rejection_log = Some(config);
+        self
+    }
+
+    /// Declare a local projection for `namespace`.
+    pub fn with_namespace_projection(
+        mut self,
+        namespace: impl Into<String>,
+        projection: LocalProjection,
+    ) -> Self {
+        self.namespace_projections
+            .insert(namespace.into(), projection);
+        self
+    }
+
+    /// Measure radius and cylinder queries in `namespace` with `metric`
+    /// instead of haversine, matching its k-nearest and distance queries.
+    pub fn with_namespace_distance_metric(
+        mut self,
+        namespace: impl Into<String>,
+        metric: DistanceMetric,
+    ) -> Self {
+        self.namespace_distance_metrics
+            .insert(namespace.into(), metric);
+        self
+    }
+
+    /// Run `namespace` in write-optimized index mode: inserts are appended to
+    /// a buffer of up to `buffer_capacity` points that queries scan linearly,
+    /// and the buffer is bulk-merged into the R-tree when full, or by the next
+    /// insert once its oldest point has waited a second.
+    pub fn with_write_optimized_namespace(
+        mut self,
+        namespace: impl Into<String>,
+        buffer_capacity: usize,
+    ) -> Self {
+        assert!(
+            buffer_capacity > 0,
+            "Write buffer capacity must be greater than zero"
+        );
+        self.write_optimized_namespaces
+            .insert(namespace.into(), buffer_capacity);
+        self
+    }
+
+    /// Keep trajectory history of `namespace` for `retention` (rounded up to
+    /// whole seconds). Objects keep their current location however old it is.
+    pub fn with_history_retention(
+        mut self,
+        namespace: impl Into<String>,
+        retention: std::time::Duration,
+    ) -> Self {
+        let secs = retention.as_secs() + u64::from(retention.subsec_nanos() > 0);
+        assert!(secs > 0, "History retention must be greater than zero");
+        self.history_retention_secs.insert(namespace.into(), secs);
+        self
+    }
+
+    /// Apply `config` to `namespace` (see [`NamespaceConfig`]).
+    pub fn with_namespace(mut self, namespace: impl Into<String>, config: NamespaceConfig) -> Self {
+        if let Err(e) = config.validate() {
+            panic!("{}", e);
+        }
+        self.namespaces.insert(namespace.into(), config);
+        self
+    }
+
+    /// Remove expired trajectory history in the background every `interval`,
+    /// rewriting the log once at least `batch_size` records have expired (see
+    /// [`Config::with_history_retention`]).
+    pub fn with_active_expiration(
+        mut self,
+        interval: std::time::Duration,
+        batch_size: usize,
+    ) -> Self {
+        let interval_ms = interval.as_millis() as u64;
+        assert!(
+            interval_ms > 0,
+            "Expiration interval must be at least one millisecond"
+        );
+        assert!(
+            batch_size > 0,
+            "Expiration batch size must be greater than zero"
+        );
+        self.active_expiration = Some(ActiveExpirationConfig {
+            interval_ms,
+            batch_size,
+        });
+        self
+    }
+
+    /// Warn and reclaim space when the log's file system has less than
+    /// `soft_bytes` free, and refuse writes below `hard_bytes` (see
+    /// [`DiskWatermarkConfig`]).
+    pub fn with_disk_watermarks(mut self, soft_bytes: u64, hard_bytes: u64) -> Self {
+        assert!(
+            soft_bytes >= hard_bytes,
+            "Soft disk watermark must be at least the hard watermark"
+        );
+        self.disk_watermarks = Some(DiskWatermarkConfig {
+            soft_bytes,
+            hard_bytes,
+            check_interval_ms: DiskWatermarkConfig::default_check_interval_ms(),
+        });
+        self
+    }
+
+    /// Let snapshot readers lag writes by up to `interval` (see `DB::reader`).
+    pub fn with_reader_refresh_interval(mut self, interval: std::time::Duration) -> Self {
+        self.reader_refresh_ms = interval.as_millis() as u64;
+        self
+    }
+
+    /// Maximum supported coordinate precision; beyond this f64 rounding is a no-op.
+    pub const MAX_COORDINATE_PRECISION: u32 = 15;
+
+    /// Quantize stored longitude/latitude to `decimals` decimal places.
+    ///
+    /// Five decimals is roughly 1.1 m at the equator, which absorbs typical GPS
+    /// jitter so a stationary device keeps a single indexed position.
+    pub fn with_coordinate_precision(mut self, decimals: u32) -> Self {
+        assert!(
+            decimals <= Self::MAX_COORDINATE_PRECISION,
+            "Coordinate precision must be at most {} decimals",
+            Self::MAX_COORDINATE_PRECISION
+        );
+        self.coordinate_precision = Some(decimals);
+        self
+    }
+
     pub fn validate(&self) -> Result<(), String> {
         #[cfg(feature = "time-index")]
         if let Some(capacity) = self.history_capacity
@@ -131,6 +660,99 @@ impl Config {
             return Err("Sync batch size must be greater than zero".to_string());
         }
 
+        if self.sync_interval_ms == 0 {
+            return Err("Sync interval must be greater than zero".to_string());
+        }
+
+        if self.group_commit_us == Some(0) {
+            return Err("Group commit window must be greater than zero".to_string());
+        }
+
+        if self.persistence.segment_secs == Some(0) {
+            return Err("Log segment period must be greater than zero".to_string());
+        }
+
+        if let Some(decimals) = self.coordinate_precision
+            && decimals > Self::MAX_COORDINATE_PRECISION
+        {
+            return Err(format!(
+                "Coordinate precision must be at most {} decimals",
+                Self::MAX_COORDINATE_PRECISION
+            ));
+        }
+
+        if let Some(access_log) = &self.access_log
+            && !(access_log.sample_rate > 0.0 && access_log.sample_rate <= 1.0)
+        {
+            return Err("Access log sample rate must be in (0, 1]".to_string());
+        }
+
+        if let Some(rejection_log) = &self.rejection_log
+            && rejection_log.max_per_second == 0
+        {
+            return Err("Rejection log rate must be greater than zero".to_string());
+        }
+
+        if let Some(expiration) = &self.active_expiration {
+            if expiration.interval_ms == 0 {
+                return Err("Expiration interval must be greater than zero".to_string());
+            }
+            if expiration.batch_size == 0 {
+                return Err("Expiration batch size must be greater than zero".to_string());
+            }
+        }
+
+        if let Some(watermarks) = &self.disk_watermarks {
+            if watermarks.soft_bytes < watermarks.hard_bytes {
+                return Err("Soft disk watermark must be at least the hard watermark".to_string());
+            }
+            if watermarks.check_interval_ms == 0 {
+                return Err("Disk check interval must be greater than zero".to_string());
+            }
+        }
+
+        if self.history_buffer_max_points == Some(0) {
+            return Err("History buffer bound must be greater than zero".to_string());
+        }
+
+        if let Some(namespace) = self
+            .write_optimized_namespaces
+            .iter()
+            .find_map(|(namespace, capacity)| (*capacity == 0).then_some(namespace))
+        {
+            return Err(format!(
+                "Write buffer capacity for namespace '{}' must be greater than zero",
+                namespace
+            ));
+        }
+
+        if let Some(namespace) = self
+            .history_retention_secs
+            .iter()
+            .find_map(|(namespace, secs)| (*secs == 0).then_some(namespace))
+        {
+            return Err(format!(
+                "History retention for namespace '{}' must be greater than zero",
+                namespace
+            ));
+        }
+
+        for (namespace, config) in &self.namespaces {
+            if let Err(e) = config.validate() {
+                return Err(format!("Namespace '{}': {}", namespace, e));
+            }
+        }
+
+        for (namespace, projection) in &self.namespace_projections {
+            if !(1..=60).contains(&projection.zone()) {
+                return Err(format!(
+                    "Projection for namespace '{}' has invalid UTM zone {}",
+                    namespace,
+                    projection.zone()
+                ));
+            }
+        }
+
         Ok(())
     }
 
@@ -167,15 +789,31 @@ impl Default for Config {
             sync_policy: SyncPolicy::default(),
             sync_mode: SyncMode::default(),
             sync_batch_size: Self::default_sync_batch_size(),
+            sync_interval_ms: Self::default_sync_interval_ms(),
+            group_commit_us: None,
             #[cfg(feature = "time-index")]
             history_capacity: None,
             buffer_capacity: Self::default_buffer_capacity(),
+            history_buffer_namespaces: None,
+            history_buffer_max_points: None,
             persistence: PersistenceConfig::default(),
+            coordinate_precision: None,
+            access_log: None,
+            rejection_log: None,
+            slow_query_threshold_ms: None,
+            namespace_projections: HashMap::new(),
+            namespace_distance_metrics: HashMap::new(),
+            write_optimized_namespaces: HashMap::new(),
+            history_retention_secs: HashMap::new(),
+            active_expiration: None,
+            disk_watermarks: None,
+            namespaces: HashMap::new(),
+            reader_refresh_ms: Self::default_reader_refresh_ms(),
         }
     }
 }
 
-pub use spatio_types::config::SetOptions;
+pub use spatio_types::config::{HistoryEventKind, ScanDirection, SetOptions};
 
 /// Internal representation of a database item.
 #[derive(Debug, Clone)]
@@ -185,19 +823,14 @@ pub struct DbItem {
     pub created_at: SystemTime,
 }
 
-/// Operation types captured in history tracking.
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum HistoryEventKind {
-    Set,
-    Delete,
-}
-
-/// Historical record for key mutations.
-#[derive(Debug, Clone)]
+/// One recorded mutation of an object, as returned by [`crate::DB::history`].
+#[derive(Debug, Clone, PartialEq)]
 pub struct HistoryEntry {
+    /// The write's timestamp, or when the object was deleted.
     pub timestamp: SystemTime,
     pub kind: HistoryEventKind,
-    pub value: Option<Bytes>,
+    /// The location written; `None` for deletes.
+    pub update: Option<crate::db::LocationUpdate>,
 }
 
 impl DbItem {
@@ -219,7 +852,7 @@ impl DbItem {
     }
 }
 
-pub use spatio_types::stats::DbStats;
+pub use spatio_types::stats::{DbStats, MinuteStats, Operation, PersistenceStatus};
 
 #[cfg(test)]
 mod tests {
@@ -248,6 +881,38 @@ mod tests {
         assert_eq!(deserialized.sync_policy, SyncPolicy::Always);
         assert_eq!(deserialized.sync_mode, SyncMode::Data);
         assert_eq!(deserialized.sync_batch_size, 8);
+
+        let barrier = Config::from_json(r#"{"sync_mode": "barrier"}"#).unwrap();
+        assert_eq!(barrier.sync_mode, SyncMode::Barrier);
+    }
+
+    #[test]
+    fn test_config_background_sync() {
+        let config = Config::default()
+            .with_sync_policy(SyncPolicy::Background)
+            .with_sync_interval(std::time::Duration::from_millis(20));
+
+        let json = config.to_json().unwrap();
+        assert!(json.contains("\"background\""));
+        let deserialized = Config::from_json(&json).unwrap();
+        assert_eq!(deserialized.sync_policy, SyncPolicy::Background);
+        assert_eq!(deserialized.sync_interval_ms, 20);
+
+        assert!(Config::from_json(r#"{"sync_interval_ms": 0}"#).is_err());
+    }
+
+    #[test]
+    fn test_config_group_commit() {
+        let config = Config::default()
+            .with_sync_policy(SyncPolicy::Always)
+            .with_group_commit(std::time::Duration::from_millis(2));
+        assert_eq!(config.group_commit_us, Some(2000));
+
+        let deserialized = Config::from_json(&config.to_json().unwrap()).unwrap();
+        assert_eq!(deserialized.group_commit_us, Some(2000));
+        assert_eq!(Config::default().group_commit_us, None);
+
+        assert!(Config::from_json(r#"{"group_commit_us": 0}"#).is_err());
     }
 
     #[cfg(feature = "time-index")]
@@ -283,4 +948,114 @@ mod tests {
         let config = Config::default();
         assert!(config.validate().is_ok());
     }
+
+    #[test]
+    fn test_config_coordinate_precision() {
+        let config = Config::default().with_coordinate_precision(5);
+        assert_eq!(config.coordinate_precision, Some(5));
+
+        let json = config.to_json().unwrap();
+        assert_eq!(
+            Config::from_json(&json).unwrap().coordinate_precision,
+            Some(5)
+        );
+
+        assert!(Config::from_json(r#"{"coordinate_precision": 16}"#).is_err());
+    }
+
+    #[test]
+    fn test_config_namespace_projection() {
+        let projection = LocalProjection::utm_zone(18).unwrap();
+        let config = Config::default().with_namespace_projection("nyc", projection);
+
+        let json = config.to_json().unwrap();
+        let parsed = Config::from_json(&json).unwrap();
+        assert_eq!(parsed.namespace_projections.get("nyc"), Some(&projection));
+
+        assert!(Config::from_json(r#"{"namespace_projections": {"x": {"zone": 61}}}"#).is_err());
+    }
+
+    #[test]
+    fn test_config_write_optimized_namespace() {
+        let config = Config::default().with_write_optimized_namespace("ingest", 256);
+        let parsed = Config::from_json(&config.to_json().unwrap()).unwrap();
+        assert_eq!(parsed.write_optimized_namespaces.get("ingest"), Some(&256));
+
+        assert!(Config::from_json(r#"{"write_optimized_namespaces": {"x": 0}}"#).is_err());
+    }
+
+    #[test]
+    fn test_config_history_buffering() {
+        let config = Config::default()
+            .with_history_buffer_namespace("fleet")
+            .with_history_buffer_max_points(10_000);
+        let parsed = Config::from_json(&config.to_json().unwrap()).unwrap();
+        assert_eq!(
+            parsed.history_buffer_namespaces,
+            Some(HashSet::from(["fleet".to_string()]))
+        );
+        assert_eq!(parsed.history_buffer_max_points, Some(10_000));
+
+        assert!(Config::from_json(r#"{"history_buffer_max_points": 0}"#).is_err());
+    }
+
+    #[test]
+    fn test_config_history_retention() {
+        let config = Config::default()
+            .with_history_retention("fleet", std::time::Duration::from_secs(30 * 86_400));
+        let parsed = Config::from_json(&config.to_json().unwrap()).unwrap();
+        assert_eq!(
+            parsed.history_retention_secs.get("fleet"),
+            Some(&(30 * 86_400))
+        );
+
+        assert!(Config::from_json(r#"{"history_retention_secs": {"x": 0}}"#).is_err());
+    }
+
+    #[test]
+    fn test_config_namespaces() {
+        let vehicles = NamespaceConfig::default()
+            .with_default_ttl(std::time::Duration::from_secs(300))
+            .with_retention(std::time::Duration::from_secs(86_400))
+            .with_retention_updates(1_000)
+            .with_geohash_precision(7);
+        let config = Config::default().with_namespace("vehicles", vehicles.clone());
+        let parsed = Config::from_json(&config.to_json().unwrap()).unwrap();
+        assert_eq!(parsed.namespaces.get("vehicles"), Some(&vehicles));
+
+        assert!(Config::from_json(r#"{"namespaces": {"x": {"default_ttl_secs": 0}}}"#).is_err());
+        assert!(Config::from_json(r#"{"namespaces": {"x": {"geohash_precision": 13}}}"#).is_err());
+        assert!(Config::from_json(r#"{"namespaces": {"x": {"retention_updates": 0}}}"#).is_err());
+        assert!(Config::from_json(r#"{"namespaces": {"x": {"ttl": 5}}}"#).is_err());
+    }
+
+    #[test]
+    fn test_config_active_expiration() {
+        let config =
+            Config::default().with_active_expiration(std::time::Duration::from_secs(60), 1_000);
+        let parsed = Config::from_json(&config.to_json().unwrap()).unwrap();
+        let expiration = parsed.active_expiration.unwrap();
+        assert_eq!(expiration.interval_ms, 60_000);
+        assert_eq!(expiration.batch_size, 1_000);
+
+        assert!(
+            Config::from_json(r#"{"active_expiration": {"interval_ms": 0, "batch_size": 1}}"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_config_disk_watermarks() {
+        let config = Config::default().with_disk_watermarks(1 << 30, 1 << 28);
+        let parsed = Config::from_json(&config.to_json().unwrap()).unwrap();
+        let watermarks = parsed.disk_watermarks.unwrap();
+        assert_eq!(watermarks.soft_bytes, 1 << 30);
+        assert_eq!(watermarks.hard_bytes, 1 << 28);
+        assert_eq!(watermarks.check_interval_ms, 1000);
+
+        assert!(
+            Config::from_json(r#"{"disk_watermarks": {"soft_bytes": 1, "hard_bytes": 2}}"#)
+                .is_err()
+        );
+    }
 }
@@ -0,0 +1,315 @@
+//! Sampled access log of read queries.
+//!
+//! Records the shape and parameters of a sample of queries (never results or
+//! metadata) as newline-delimited JSON, one [`AccessLogEntry`] per line. The
+//! log can be replayed against a dataset with `spatio-bench replay` so that
+//! performance tuning uses real traffic patterns instead of synthetic grids.
+
+use super::fsync::sync_file;
+use crate::config::{AccessLogConfig, SyncMode};
+use crate::error::Result;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use spatio_types::geo::DistanceMetric;
+use spatio_types::query::Predicate;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A logged query: operation name plus the parameters needed to re-issue it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum AccessQuery {
+    Radius {
+        namespace: String,
+        center: [f64; 3],
+        radius: f64,
+        limit: usize,
+        #[serde(default)]
+        metric: DistanceMetric,
+    },
+    Bbox {
+        namespace: String,
+        min: [f64; 2],
+        max: [f64; 2],
+        limit: usize,
+    },
+    Bbox3d {
+        namespace: String,
+        min: [f64; 3],
+        max: [f64; 3],
+        limit: usize,
+    },
+    Cylinder {
+        namespace: String,
+        center: [f64; 2],
+        min_z: f64,
+        max_z: f64,
+        radius: f64,
+        limit: usize,
+        #[serde(default)]
+        metric: DistanceMetric,
+    },
+    Knn {
+        namespace: String,
+        center: [f64; 3],
+        k: usize,
+    },
+    Knn2d {
+        namespace: String,
+        center: [f64; 2],
+        k: usize,
+        max_distance: Option<f64>,
+        metric: DistanceMetric,
+    },
+    Polygon {
+        namespace: String,
+        exterior: Vec<[f64; 2]>,
+        limit: usize,
+    },
+    Trajectory {
+        namespace: String,
+        object_id: String,
+        start_micros: u64,
+        end_micros: u64,
+        limit: usize,
+    },
+    Composite {
+        namespace: String,
+        predicate: Predicate,
+        limit: usize,
+    },
+    /// The queries relative to another object name that object rather than
+    /// its position, which would otherwise be logged as the query's center.
+    Near {
+        namespace: String,
+        object_id: String,
+        radius: f64,
+        limit: usize,
+    },
+    BboxNear {
+        namespace: String,
+        object_id: String,
+        width: f64,
+        height: f64,
+        limit: usize,
+    },
+    Bbox3dNear {
+        namespace: String,
+        object_id: String,
+        width: f64,
+        height: f64,
+        depth: f64,
+        limit: usize,
+    },
+    CylinderNear {
+        namespace: String,
+        object_id: String,
+        min_z: f64,
+        max_z: f64,
+        radius: f64,
+        limit: usize,
+    },
+    KnnNear {
+        namespace: String,
+        object_id: String,
+        k: usize,
+    },
+}
+
+impl AccessQuery {
+    /// Namespace and ID of the object the query names, if any.
+    pub fn object(&self) -> Option<(&str, &str)> {
+        match self {
+            AccessQuery::Trajectory {
+                namespace,
+                object_id,
+                ..
+            }
+            | AccessQuery::Near {
+                namespace,
+                object_id,
+                ..
+            }
+            | AccessQuery::BboxNear {
+                namespace,
+                object_id,
+                ..
+            }
+            | AccessQuery::Bbox3dNear {
+                namespace,
+                object_id,
+                ..
+            }
+            | AccessQuery::CylinderNear {
+                namespace,
+                object_id,
+                ..
+            }
+            | AccessQuery::KnnNear {
+                namespace,
+                object_id,
+                ..
+            } => Some((namespace, object_id)),
+            _ => None,
+        }
+    }
+}
+
+/// One line of the access log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccessLogEntry {
+    /// Wall-clock time the query was issued (microseconds since the Unix epoch).
+    pub ts_micros: u64,
+    #[serde(flatten)]
+    pub query: AccessQuery,
+}
+
+impl AccessLogEntry {
+    /// Parse one NDJSON line of an access log.
+    pub fn parse(line: &str) -> Result<Self> {
+        serde_json::from_str(line).map_err(|e| {
+            crate::error::SpatioError::SerializationErrorWithContext(format!(
+                "Invalid access log entry: {}",
+                e
+            ))
+        })
+    }
+}
+
+pub(crate) fn micros(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as u64
+}
+
+/// Append-only sampled writer for [`AccessLogEntry`] lines.
+pub(crate) struct AccessLog {
+    path: PathBuf,
+    writer: Mutex<BufWriter<File>>,
+    sample_rate: f64,
+    seen: AtomicU64,
+}
+
+impl AccessLog {
+    pub(crate) fn open(config: &AccessLogConfig) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)?;
+        Ok(Self {
+            path: config.path.clone(),
+            writer: Mutex::new(BufWriter::new(file)),
+            sample_rate: config.sample_rate,
+            seen: AtomicU64::new(0),
+        })
+    }
+
+    /// Deterministic sampling: the n-th query is kept whenever `n * rate`
+    /// crosses an integer, so exactly `rate` of the traffic is recorded
+    /// without a random number generator on the query path.
+    fn should_sample(&self) -> bool {
+        let n = self.seen.fetch_add(1, Ordering::Relaxed) + 1;
+        (n as f64 * self.sample_rate).floor() > ((n - 1) as f64 * self.sample_rate).floor()
+    }
+
+    /// Record a query if it is sampled. `query` is only built for sampled calls.
+    pub(crate) fn record(&self, query: impl FnOnce() -> AccessQuery) {
+        if !self.should_sample() {
+            return;
+        }
+        let entry = AccessLogEntry {
+            ts_micros: micros(SystemTime::now()),
+            query: query(),
+        };
+        let Ok(line) = serde_json::to_string(&entry) else {
+            return;
+        };
+
+        // Best-effort: profiling data must never fail a query.
+        let mut writer = self.writer.lock();
+        if let Err(e) = writeln!(writer, "{}", line) {
+            log::warn!("Failed to write access log entry: {}", e);
+        }
+    }
+
+    pub(crate) fn flush(&self) -> Result<()> {
+        self.writer.lock().flush()?;
+        Ok(())
+    }
+
+    /// Rewrite the log without entries naming `object_id` in `namespace`,
+    /// returning how many were removed. Unparseable lines are kept.
+    pub(crate) fn forget_object(&self, namespace: &str, object_id: &str) -> Result<u64> {
+        rewrite_without(&self.path, &mut self.writer.lock(), |line| {
+            AccessLogEntry::parse(line)
+                .is_ok_and(|entry| entry.query.object() == Some((namespace, object_id)))
+        })
+    }
+}
+
+/// Rewrite the NDJSON log at `path`, appended to through `writer`, without
+/// the lines `mentions` picks, returning how many were removed.
+pub(crate) fn rewrite_without(
+    path: &Path,
+    writer: &mut BufWriter<File>,
+    mentions: impl Fn(&str) -> bool,
+) -> Result<u64> {
+    writer.flush()?;
+    let content = std::fs::read_to_string(path)?;
+    let mut removed = 0u64;
+    let mut tmp = path.as_os_str().to_os_string();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    {
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        for line in content.lines() {
+            if mentions(line) {
+                removed += 1;
+            } else {
+                writeln!(out, "{}", line)?;
+            }
+        }
+        out.flush()?;
+        sync_file(out.get_ref(), SyncMode::All)?;
+    }
+    std::fs::rename(&tmp, path)?;
+
+    let file = OpenOptions::new().append(true).open(path)?;
+    *writer = BufWriter::new(file);
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_round_trip() {
+        let entry = AccessLogEntry {
+            ts_micros: 42,
+            query: AccessQuery::Knn {
+                namespace: "fleet".to_string(),
+                center: [1.0, 2.0, 3.0],
+                k: 5,
+            },
+        };
+        let line = serde_json::to_string(&entry).unwrap();
+        assert!(line.contains(r#""op":"knn""#));
+        assert_eq!(AccessLogEntry::parse(&line).unwrap(), entry);
+        assert!(AccessLogEntry::parse("{not json").is_err());
+    }
+
+    #[test]
+    fn test_sampling_rate() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AccessLog::open(&AccessLogConfig {
+            path: dir.path().join("access.log"),
+            sample_rate: 0.25,
+        })
+        .unwrap();
+
+        let sampled = (0..100).filter(|_| log.should_sample()).count();
+        assert_eq!(sampled, 25);
+    }
+}
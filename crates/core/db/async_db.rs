@@ -0,0 +1,309 @@
+//! Database handle for async code.
+//!
+//! With [`SyncPolicy::Always`](crate::SyncPolicy::Always) every write fsyncs
+//! the log before returning, which would stall a tokio worker thread for the
+//! length of a disk flush. [`AsyncDB`] hands writes to a dedicated writer
+//! thread through a bounded queue and awaits the result instead, so the
+//! runtime keeps serving other tasks meanwhile. When the queue is full,
+//! writers wait for room rather than queueing without limit.
+//!
+//! Writes run in the order they were queued. Reads of current locations are
+//! answered from memory on the calling task; trajectory queries, which may
+//! read the log from disk, run on tokio's blocking pool.
+//!
+//! ```ignore
+//! use spatio::{AsyncDB, Point3d};
+//!
+//! let db = AsyncDB::open("fleet.db").await?;
+//! db.upsert("cars", "car1", Point3d::new(-74.0, 40.7, 0.0), serde_json::json!({}), None)
+//!     .await?;
+//! db.sync().await?;
+//! ```
+
+use crate::config::{Config, DbStats, SetOptions, TemporalPoint, TimeRange};
+use crate::db::{CurrentLocation, DB, LocationUpdate, NearbyHit};
+use crate::error::{Result, SpatioError};
+use spatio_types::point::Point3d;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
+
+/// Writes [`AsyncDB`] queues before callers wait for room, by default.
+pub const DEFAULT_WRITE_QUEUE_CAPACITY: usize = 1024;
+
+type Write = Box<dyn FnOnce(&DB) + Send>;
+
+/// Async handle to a database (see the [module docs](self)). Clones share
+/// the database and its writer thread, which exits once every clone is
+/// dropped.
+#[derive(Clone)]
+pub struct AsyncDB {
+    db: DB,
+    writes: mpsc::Sender<Write>,
+}
+
+impl AsyncDB {
+    /// Open a database with default configuration, recovering it on the
+    /// blocking pool.
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with_config(path, Config::default()).await
+    }
+
+    /// Open a database with custom configuration.
+    pub async fn open_with_config<P: AsRef<Path>>(path: P, config: Config) -> Result<Self> {
+        let path: PathBuf = path.as_ref().to_path_buf();
+        let db = blocking(move || DB::open_with_config(path, config)).await?;
+        Self::new(db)
+    }
+
+    /// Create an in-memory database.
+    pub fn memory() -> Result<Self> {
+        Self::new(DB::memory()?)
+    }
+
+    /// Wrap an open database, with a write queue of
+    /// [`DEFAULT_WRITE_QUEUE_CAPACITY`].
+    pub fn new(db: DB) -> Result<Self> {
+        Self::with_queue_capacity(db, DEFAULT_WRITE_QUEUE_CAPACITY)
+    }
+
+    /// Wrap an open database, queueing up to `capacity` writes.
+    pub fn with_queue_capacity(db: DB, capacity: usize) -> Result<Self> {
+        let (writes, mut queue) = mpsc::channel::<Write>(capacity.max(1));
+        let writer = db.clone();
+        std::thread::Builder::new()
+            .name("spatio-writer".to_string())
+            .spawn(move || {
+                while let Some(write) = queue.blocking_recv() {
+                    write(&writer);
+                }
+            })?;
+        Ok(Self { db, writes })
+    }
+
+    /// The wrapped database, for operations without an async counterpart.
+    /// Its methods block; call writes through it from the blocking pool.
+    pub fn db(&self) -> &DB {
+        &self.db
+    }
+
+    /// Get database statistics.
+    pub fn stats(&self) -> DbStats {
+        self.db.stats()
+    }
+
+    /// Upsert an object's location, returning the write's sequence number
+    /// once the writer thread has applied it (and, per the sync policy,
+    /// made it durable).
+    pub async fn upsert(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        position: Point3d,
+        metadata: serde_json::Value,
+        opts: Option<SetOptions>,
+    ) -> Result<u64> {
+        let (namespace, object_id) = (namespace.to_string(), object_id.to_string());
+        self.write(move |db| db.upsert(&namespace, &object_id, position, metadata, opts))
+            .await
+    }
+
+    /// Upsert several objects of `namespace` as one queued write (see
+    /// [`DB::upsert_batch`]).
+    pub async fn upsert_batch(
+        &self,
+        namespace: &str,
+        items: Vec<(String, Point3d, serde_json::Value)>,
+    ) -> Result<u64> {
+        let namespace = namespace.to_string();
+        self.write(move |db| db.upsert_batch(&namespace, items))
+            .await
+    }
+
+    /// Insert a trajectory, returning the sequence of its last point.
+    pub async fn insert_trajectory(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        trajectory: Vec<TemporalPoint>,
+    ) -> Result<u64> {
+        let (namespace, object_id) = (namespace.to_string(), object_id.to_string());
+        self.write(move |db| db.insert_trajectory(&namespace, &object_id, &trajectory))
+            .await
+    }
+
+    /// Delete an object, returning the deletion's sequence.
+    pub async fn delete(&self, namespace: &str, object_id: &str) -> Result<u64> {
+        let (namespace, object_id) = (namespace.to_string(), object_id.to_string());
+        self.write(move |db| db.delete(&namespace, &object_id))
+            .await
+    }
+
+    /// Flush and fsync the log once every write queued before this call has
+    /// been applied, whatever the sync policy.
+    pub async fn sync(&self) -> Result<()> {
+        self.write(|db| db.cold.fsync()).await
+    }
+
+    /// Get current location of an object.
+    pub fn get(&self, namespace: &str, object_id: &str) -> Result<Option<Arc<CurrentLocation>>> {
+        self.db.get(namespace, object_id)
+    }
+
+    /// Query objects within radius (returns location and distance)
+    pub fn query_radius(
+        &self,
+        namespace: &str,
+        center: &Point3d,
+        radius: f64,
+        limit: usize,
+    ) -> Result<Vec<NearbyHit>> {
+        self.db.query_radius(namespace, center, radius, limit)
+    }
+
+    /// Query objects near another object (returns location and distance)
+    pub fn query_near(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        radius: f64,
+        limit: usize,
+    ) -> Result<Vec<NearbyHit>> {
+        self.db.query_near(namespace, object_id, radius, limit)
+    }
+
+    /// Query historical trajectory, on the blocking pool.
+    pub async fn query_trajectory(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        range: impl Into<TimeRange>,
+        limit: usize,
+    ) -> Result<Vec<LocationUpdate>> {
+        let db = self.db.clone();
+        let (namespace, object_id) = (namespace.to_string(), object_id.to_string());
+        let range = range.into();
+        blocking(move || db.query_trajectory(&namespace, &object_id, range, limit)).await
+    }
+
+    /// Close the database once the writes queued before this call are
+    /// applied.
+    pub async fn close(&self) -> Result<()> {
+        self.write(|db| db.close()).await
+    }
+
+    /// Queue `write` for the writer thread and await its result.
+    async fn write<T: Send + 'static>(
+        &self,
+        write: impl FnOnce(&DB) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let (done, result) = oneshot::channel();
+        self.writes
+            .send(Box::new(move |db: &DB| {
+                let _ = done.send(write(db));
+            }))
+            .await
+            .map_err(|_| SpatioError::DatabaseClosed)?;
+        result.await.map_err(|_| SpatioError::DatabaseClosed)?
+    }
+}
+
+/// Run `f` on tokio's blocking pool.
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> Result<T> + Send + 'static) -> Result<T> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| SpatioError::Other(format!("blocking task failed: {e}")))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SyncPolicy;
+
+    #[tokio::test]
+    async fn test_writes_apply_in_order_and_sync() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db");
+        let config = Config::default().with_sync_policy(SyncPolicy::Always);
+        let db = AsyncDB::open_with_config(&path, config).await.unwrap();
+
+        let writes = (0..50).map(|i| {
+            let db = db.clone();
+            async move {
+                let position = Point3d::new(f64::from(i) * 0.001, 0.0, 0.0);
+                db.upsert(
+                    "cars",
+                    &format!("c{i}"),
+                    position,
+                    serde_json::json!({}),
+                    None,
+                )
+                .await
+                .unwrap()
+            }
+        });
+        let mut sequences = spawn_all(writes).await;
+        sequences.sort_unstable();
+        sequences.dedup();
+        assert_eq!(sequences.len(), 50);
+
+        db.delete("cars", "c0").await.unwrap();
+        db.sync().await.unwrap();
+        assert!(db.get("cars", "c0").unwrap().is_none());
+        let found = db
+            .query_radius("cars", &Point3d::new(0.0, 0.0, 0.0), 100_000.0, 100)
+            .unwrap();
+        assert_eq!(found.len(), 49);
+        let history = db
+            .query_trajectory("cars", "c1", TimeRange::all(), 10)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 1);
+
+        db.close().await.unwrap();
+        assert!(matches!(
+            db.upsert(
+                "cars",
+                "late",
+                Point3d::new(0.0, 0.0, 0.0),
+                serde_json::json!({}),
+                None
+            )
+            .await,
+            Err(SpatioError::DatabaseClosed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sync_fsyncs_under_never() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::default().with_sync_policy(SyncPolicy::Never);
+        let db = AsyncDB::open_with_config(dir.path().join("db"), config)
+            .await
+            .unwrap();
+        let sequence = db
+            .upsert(
+                "cars",
+                "c",
+                Point3d::new(0.0, 0.0, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(db.db().flush_watermark(), 0);
+        db.sync().await.unwrap();
+        assert_eq!(db.db().flush_watermark(), sequence);
+    }
+
+    async fn spawn_all<F: std::future::Future<Output = u64> + Send + 'static>(
+        futures: impl Iterator<Item = F>,
+    ) -> Vec<u64> {
+        let tasks: Vec<_> = futures.map(tokio::spawn).collect();
+        let mut results = Vec::new();
+        for task in tasks {
+            results.push(task.await.unwrap());
+        }
+        results
+    }
+}
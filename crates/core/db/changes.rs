@@ -0,0 +1,95 @@
+//! Change feed: a stream of the writes applied to current locations.
+//!
+//! Subscribers get a [`ChangeEvent`] for every object inserted, moved or
+//! deleted, so downstream consumers can maintain their own views of the data
+//! without polling. Writes re-read the object's previous location only while
+//! someone is watching, so an unwatched database pays one atomic load per
+//! write.
+
+use crate::db::CurrentLocation;
+use std::sync::Arc;
+use std::sync::mpsc::Receiver;
+
+use super::subscribers::Subscribers;
+
+/// A change to an object's current location.
+#[derive(Debug, Clone)]
+pub enum ChangeEvent {
+    /// The object had no current location before this write.
+    Inserted(Arc<CurrentLocation>),
+    /// The object's location or metadata was replaced.
+    Updated(Arc<CurrentLocation>),
+    /// The object was deleted or forgotten. Carries its last location.
+    Deleted(Arc<CurrentLocation>),
+}
+
+impl ChangeEvent {
+    pub fn location(&self) -> &Arc<CurrentLocation> {
+        match self {
+            Self::Inserted(loc) | Self::Updated(loc) | Self::Deleted(loc) => loc,
+        }
+    }
+}
+
+/// Receiving end of a change feed, bounded as described in
+/// [`subscribers`](super::subscribers).
+pub type ChangeFeed = Receiver<ChangeEvent>;
+
+/// The objects a change feed follows.
+struct Filter {
+    /// Only objects of this namespace, or all when `None`.
+    namespace: Option<String>,
+    /// Only objects whose ID starts with this.
+    prefix: String,
+}
+
+impl Filter {
+    fn wants(&self, location: &CurrentLocation) -> bool {
+        self.namespace
+            .as_ref()
+            .is_none_or(|namespace| *namespace == location.namespace)
+            && location.object_id.starts_with(&self.prefix)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct ChangeBus {
+    subscribers: Subscribers<ChangeEvent, Filter>,
+}
+
+impl ChangeBus {
+    pub fn subscribe(&self, namespace: Option<&str>, prefix: &str) -> ChangeFeed {
+        self.subscribers.subscribe(Filter {
+            namespace: namespace.map(str::to_string),
+            prefix: prefix.to_string(),
+        })
+    }
+
+    /// Whether any feed is open, i.e. whether writers should report changes.
+    pub fn is_watched(&self) -> bool {
+        !self.subscribers.is_empty()
+    }
+
+    /// Report a write that took an object from `previous` to `current`. Writes
+    /// that changed nothing (an older timestamp losing to the stored one, say)
+    /// are not reported.
+    pub fn publish(
+        &self,
+        previous: Option<Arc<CurrentLocation>>,
+        current: Option<Arc<CurrentLocation>>,
+    ) {
+        if !self.is_watched() {
+            return;
+        }
+        let event = match (previous, current) {
+            (None, Some(current)) => ChangeEvent::Inserted(current),
+            (Some(previous), Some(current)) if !Arc::ptr_eq(&previous, &current) => {
+                ChangeEvent::Updated(current)
+            }
+            (Some(previous), None) => ChangeEvent::Deleted(previous),
+            _ => return,
+        };
+        self.subscribers
+            .publish(&event, |filter| filter.wants(event.location()));
+    }
+}
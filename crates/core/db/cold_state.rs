@@ -4,19 +4,34 @@
 //! append-only writes and time-range queries. It uses a persistent log for
 //! durability and a memory buffer for recent history access.
 
-use dashmap::DashMap;
-use parking_lot::Mutex;
+use dashmap::{DashMap, DashSet};
+use parking_lot::{Condvar, Mutex};
 use serde::{Deserialize, Serialize};
 use spatio_types::config::{SyncMode, SyncPolicy};
 use spatio_types::point::Point3d;
-use std::collections::VecDeque;
+use spatio_types::stats::LatencyHistogram;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque};
 use std::fs::{File, OpenOptions};
-use std::io::{BufWriter, Write};
+use std::io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::thread::JoinHandle;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::config::PersistenceConfig;
-use crate::error::Result;
+use super::OpenProgress;
+use super::compression;
+use super::durability::DurabilityWatermark;
+use super::fsync;
+use super::log_writer::LogWriter;
+use super::maintenance::{Maintenance, MaintenanceKind, MaintenanceTracker};
+use super::segments::{self, SegmentReader};
+use super::{CheckpointKind, CheckpointReport, HistoryCompaction};
+use crate::config::{HistoryEntry, HistoryEventKind, LogCompression, PersistenceConfig};
+use crate::error::{Result, SpatioError};
 
 /// Durability settings governing when buffered writes are flushed to the OS
 /// and synced to stable storage.
@@ -25,6 +40,11 @@ pub struct SyncSettings {
     pub policy: SyncPolicy,
     pub mode: SyncMode,
     pub batch_size: usize,
+    /// Interval between syncs under [`SyncPolicy::Background`].
+    pub interval: Duration,
+    /// How long a write waits for others to share its `fsync` under
+    /// [`SyncPolicy::Always`] (see [`ColdState::group`]).
+    pub group_commit: Option<Duration>,
 }
 
 impl Default for SyncSettings {
@@ -33,32 +53,119 @@ impl Default for SyncSettings {
             policy: SyncPolicy::default(),
             mode: SyncMode::default(),
             batch_size: 1,
+            interval: Duration::from_millis(100),
+            group_commit: None,
         }
     }
 }
 
 /// Single location update in history
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LocationUpdate {
     pub timestamp: SystemTime,
     pub position: Point3d,
     pub metadata: serde_json::Value,
 }
 
+/// The recent updates of one object, oldest first.
+struct RecentHistory {
+    updates: VecDeque<LocationUpdate>,
+    /// Whether `updates` is the object's whole history in the log. Buffers
+    /// created for objects that may already have logged records, and buffers
+    /// that have dropped their oldest update, are not.
+    complete: bool,
+    /// Tick of the last update, for least-recently-updated eviction.
+    touched: u64,
+}
+
+/// Which objects keep a recent-history buffer, and how many points the
+/// buffers may hold together.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryBuffering {
+    /// Namespaces whose objects are buffered; every namespace when `None`.
+    pub namespaces: Option<HashSet<String>>,
+    /// Most points held across all buffers; unbounded when `None`.
+    pub max_points: Option<usize>,
+}
+
 /// Cold state: historical trajectories
 pub struct ColdState {
     /// Append-only log file
-    trajectory_log: Mutex<TrajectoryLog>,
+    trajectory_log: Arc<Mutex<TrajectoryLog>>,
+
+    /// Highest log sequence known to be on stable storage.
+    watermark: Arc<DurabilityWatermark>,
+
+    /// Highest sequence whose write is visible to reads, recovered ones
+    /// included. Lags [`ColdState::last_sequence`] while a write is under way.
+    applied: DurabilityWatermark,
+
+    /// Background `fsync` thread, under [`SyncPolicy::Background`].
+    background_sync: Option<BackgroundSync>,
 
     /// Recent history buffer for fast access
     /// Maps "namespace::object_id" -> recent updates
-    recent_buffer: DashMap<String, VecDeque<LocationUpdate>>,
+    recent_buffer: DashMap<String, RecentHistory>,
 
     /// Buffer size per object (e.g., last 100 updates)
     buffer_capacity: usize,
 
+    buffering: HistoryBuffering,
+
+    /// Keys whose logged history a new buffer would miss: recovered objects
+    /// and objects whose buffer was dropped while the log kept records.
+    spilled: DashSet<String>,
+
+    /// Points held across all buffers.
+    buffered_points: AtomicUsize,
+
+    /// Buffered keys by the tick of their last update, oldest first.
+    /// Maintained only under a `max_points` bound.
+    lru: Mutex<BTreeMap<u64, String>>,
+    clock: AtomicU64,
+
     /// Path of the file-backed log, if any (used for checkpoint/recovery).
     log_path: Option<std::path::PathBuf>,
+
+    /// Why the log stopped accepting writes, once it has (see
+    /// [`ColdState::degraded`]). Shared with the log.
+    degraded: Arc<OnceLock<String>>,
+
+    /// Writes must be durable on return ([`SyncPolicy::Always`]), so they
+    /// fail while persistence is degraded.
+    durable_writes: bool,
+
+    /// Shared `fsync`s, when writes must be durable and may wait for others.
+    group_commit: Option<GroupCommit>,
+
+    /// The compaction or checkpoint holding the log, if any.
+    maintenance: MaintenanceTracker,
+
+    /// Opened read-only (see [`PersistenceConfig::read_only`]).
+    read_only: bool,
+
+    /// Lock on the database against other processes, held until closed (see
+    /// [`lock_database`]).
+    lock: Mutex<Option<File>>,
+
+    /// Identifies the log, whose sequences mean nothing without it (see
+    /// [`ColdState::log_id`]).
+    log_id: u64,
+}
+
+/// Group commit state: writes append without syncing, then one of them syncs
+/// for every write appended meanwhile.
+struct GroupCommit {
+    window: Duration,
+    mode: SyncMode,
+    /// Held by the write syncing for the group.
+    leader: Mutex<()>,
+}
+
+thread_local! {
+    /// Set while [`ColdState::group`] runs on this thread; appends then leave
+    /// waiting for durability to it.
+    static GROUPED: Cell<bool> = const { Cell::new(false) };
 }
 
 impl ColdState {
@@ -70,19 +177,59 @@ impl ColdState {
         sync: SyncSettings,
     ) -> Result<Self> {
         // Ensure directory exists
-        if let Some(parent) = log_path.parent() {
+        if let Some(parent) = log_path.parent()
+            && !config.read_only
+        {
             std::fs::create_dir_all(parent)?;
         }
-
-        Ok(Self {
-            trajectory_log: Mutex::new(TrajectoryLog::open_file(
-                log_path,
-                config.buffer_size,
+        let lock = lock_database(log_path, config.read_only)?;
+        let log_id = open_log_id(log_path, config.read_only);
+
+        let watermark = Arc::new(DurabilityWatermark::default());
+        compression::check_supported(config.compression)?;
+        let trajectory_log = Arc::new(Mutex::new(TrajectoryLog::open_file(
+            log_path,
+            &config,
+            sync,
+            watermark.clone(),
+        )?));
+        let degraded = trajectory_log.lock().degraded.clone();
+        let background_sync = match sync.policy {
+            SyncPolicy::Background if !config.read_only => Some(BackgroundSync::spawn(
+                trajectory_log.clone(),
+                watermark.clone(),
                 sync,
             )?),
+            _ => None,
+        };
+
+        Ok(Self {
+            trajectory_log,
+            watermark,
+            applied: DurabilityWatermark::default(),
+            background_sync,
             recent_buffer: DashMap::new(),
             buffer_capacity,
+            buffering: HistoryBuffering::default(),
+            spilled: DashSet::new(),
+            buffered_points: AtomicUsize::new(0),
+            lru: Mutex::new(BTreeMap::new()),
+            clock: AtomicU64::new(0),
             log_path: Some(log_path.to_path_buf()),
+            degraded,
+            durable_writes: sync.policy == SyncPolicy::Always,
+            group_commit: sync
+                .group_commit
+                .filter(|_| sync.policy == SyncPolicy::Always)
+                .map(|window| GroupCommit {
+                    window,
+                    mode: sync.mode,
+                    leader: Mutex::new(()),
+                }),
+            maintenance: MaintenanceTracker::default(),
+            read_only: config.read_only,
+            lock: Mutex::new(lock),
+            log_id,
         })
     }
 
@@ -92,15 +239,39 @@ impl ColdState {
     /// is touched. Trajectory history lives in an in-memory append log, so
     /// `query_trajectory` returns the same results a file-backed DB would,
     /// without paying for text serialization, `BufWriter` flushes, or `fsync`.
+    /// With nothing to sync, every append is reported durable immediately.
     pub fn new_memory(buffer_capacity: usize) -> Self {
+        let watermark = Arc::new(DurabilityWatermark::default());
         Self {
-            trajectory_log: Mutex::new(TrajectoryLog::open_memory()),
+            trajectory_log: Arc::new(Mutex::new(TrajectoryLog::open_memory(watermark.clone()))),
+            watermark,
+            applied: DurabilityWatermark::default(),
+            background_sync: None,
             recent_buffer: DashMap::new(),
             buffer_capacity,
+            buffering: HistoryBuffering::default(),
+            spilled: DashSet::new(),
+            buffered_points: AtomicUsize::new(0),
+            lru: Mutex::new(BTreeMap::new()),
+            clock: AtomicU64::new(0),
             log_path: None,
+            degraded: Arc::default(),
+            durable_writes: false,
+            group_commit: None,
+            maintenance: MaintenanceTracker::default(),
+            read_only: false,
+            lock: Mutex::new(None),
+            log_id: new_log_id(),
         }
     }
 
+    /// Limit which objects keep a recent-history buffer and how many points
+    /// the buffers hold together.
+    pub fn with_history_buffering(mut self, buffering: HistoryBuffering) -> Self {
+        self.buffering = buffering;
+        self
+    }
+
     /// Create a composite key from namespace and object ID
     #[inline]
     fn make_key(namespace: &str, object_id: &str) -> String {
@@ -115,13 +286,15 @@ impl ColdState {
         let buffer_bytes = self
             .recent_buffer
             .iter()
-            .map(|entry| entry.value().len() * 100)
+            .map(|entry| entry.value().updates.len() * 100)
             .sum();
 
         (trajectory_count, buffer_bytes)
     }
 
-    /// Append location update to persistent log + buffer
+    /// Append location update to persistent log + buffer, returning the
+    /// record's log sequence. The update is buffered if its namespace keeps
+    /// buffers or its object already has one.
     pub fn append_update(
         &self,
         namespace: &str,
@@ -129,7 +302,38 @@ impl ColdState {
         position: Point3d,
         metadata: serde_json::Value,
         timestamp: SystemTime,
-    ) -> Result<()> {
+    ) -> Result<u64> {
+        let sequence = self.append(namespace, object_id, position, metadata, timestamp, false)?;
+        self.applied.advance(sequence);
+        self.commit(sequence)?;
+        Ok(sequence)
+    }
+
+    /// [`ColdState::append_update`], but buffer the object's history whatever
+    /// its namespace.
+    pub fn append_tracked_update(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        position: Point3d,
+        metadata: serde_json::Value,
+        timestamp: SystemTime,
+    ) -> Result<u64> {
+        let sequence = self.append(namespace, object_id, position, metadata, timestamp, true)?;
+        self.applied.advance(sequence);
+        self.commit(sequence)?;
+        Ok(sequence)
+    }
+
+    fn append(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        position: Point3d,
+        metadata: serde_json::Value,
+        timestamp: SystemTime,
+        track: bool,
+    ) -> Result<u64> {
         // Truncate timestamp to microseconds to match disk storage precision,
         // preventing duplicates when merging buffer and disk results.
         let micros = micros_since_epoch(timestamp);
@@ -142,42 +346,322 @@ impl ColdState {
         };
 
         // 1. Write to persistent log (serialized via Mutex)
-        {
+        let sequence = {
             let mut log = self.trajectory_log.lock();
-            log.append(namespace, object_id, &update)?;
-        }
+            log.append(namespace, object_id, &update)?
+        };
 
         // 2. Add to recent buffer (concurrent via DashMap)
+        self.buffer(namespace, object_id, update, track);
+        Ok(sequence)
+    }
+
+    /// Append updates of `namespace`, all stamped `timestamp`, to the log as
+    /// one batch and buffer them, returning the sequence of the last. A V2
+    /// file log keeps either all of them or none across a crash; a V1 log
+    /// writes one line per update, so a crash may keep only the first few.
+    pub fn append_batch(
+        &self,
+        namespace: &str,
+        items: &[(String, Point3d, serde_json::Value)],
+        timestamp: SystemTime,
+    ) -> Result<u64> {
+        let micros = micros_since_epoch(timestamp);
+        let timestamp = UNIX_EPOCH + std::time::Duration::from_micros(micros as u64);
+        let updates: Vec<(String, LocationUpdate)> = items
+            .iter()
+            .map(|(object_id, position, metadata)| {
+                let update = LocationUpdate {
+                    timestamp,
+                    position: position.clone(),
+                    metadata: metadata.clone(),
+                };
+                (object_id.clone(), update)
+            })
+            .collect();
+        let sequence = self
+            .trajectory_log
+            .lock()
+            .append_batch(namespace, &updates)?;
+        for (object_id, update) in updates {
+            self.buffer(namespace, &object_id, update, false);
+        }
+        self.applied.advance(sequence);
+        self.commit(sequence)?;
+        Ok(sequence)
+    }
+
+    /// Add an appended update to its object's buffer (see
+    /// [`ColdState::append_update`]).
+    fn buffer(&self, namespace: &str, object_id: &str, update: LocationUpdate, track: bool) {
         let full_key = Self::make_key(namespace, object_id);
-        let mut buffer = self.recent_buffer.entry(full_key).or_default();
+        let namespace_buffered = self
+            .buffering
+            .namespaces
+            .as_ref()
+            .is_none_or(|namespaces| namespaces.contains(namespace));
+        let mut buffer = if track || namespace_buffered {
+            self.recent_buffer
+                .entry(full_key.clone())
+                .or_insert_with(|| RecentHistory {
+                    updates: VecDeque::new(),
+                    // Outside a buffered namespace earlier updates may have
+                    // gone only to the log.
+                    complete: namespace_buffered && !self.spilled.contains(&full_key),
+                    touched: 0,
+                })
+        } else {
+            match self.recent_buffer.get_mut(&full_key) {
+                Some(buffer) => buffer,
+                None => return,
+            }
+        };
 
-        buffer.push_back(update);
+        buffer.updates.push_back(update);
+        self.buffered_points.fetch_add(1, Ordering::Relaxed);
 
         // Keep only the last N updates. Each call appends exactly one record to
         // a buffer that already held <= capacity, so at most one eviction is
         // ever needed — no loop required.
-        if buffer.len() > self.buffer_capacity {
-            buffer.pop_front();
+        if buffer.updates.len() > self.buffer_capacity {
+            buffer.updates.pop_front();
+            buffer.complete = false;
+            self.buffered_points.fetch_sub(1, Ordering::Relaxed);
         }
 
-        Ok(())
+        if let Some(max_points) = self.buffering.max_points {
+            let tick = self.clock.fetch_add(1, Ordering::Relaxed) + 1;
+            {
+                let mut lru = self.lru.lock();
+                lru.remove(&buffer.touched);
+                lru.insert(tick, full_key);
+            }
+            buffer.touched = tick;
+            drop(buffer);
+            self.evict_buffers(max_points);
+        }
+    }
+
+    /// Drop the buffers of the least recently updated objects until at most
+    /// `max_points` points are buffered. Their history stays in the log.
+    fn evict_buffers(&self, max_points: usize) {
+        while self.buffered_points.load(Ordering::Relaxed) > max_points {
+            let Some((tick, key)) = self.lru.lock().pop_first() else {
+                break;
+            };
+            // Skip a key updated since it was popped; its new tick is queued.
+            if let Some((key, buffer)) = self
+                .recent_buffer
+                .remove_if(&key, |_, buffer| buffer.touched == tick)
+            {
+                self.buffered_points
+                    .fetch_sub(buffer.updates.len(), Ordering::Relaxed);
+                self.spilled.insert(key);
+            }
+        }
+    }
+
+    /// Drop the buffer of `key`, returning whether it had one.
+    fn remove_buffer(&self, key: &str) -> bool {
+        let Some((_, buffer)) = self.recent_buffer.remove(key) else {
+            return false;
+        };
+        self.buffered_points
+            .fetch_sub(buffer.updates.len(), Ordering::Relaxed);
+        self.lru.lock().remove(&buffer.touched);
+        true
     }
 
     /// Append a deletion marker for an object. On recovery, tombstones are
     /// resolved by append order (a tombstone hides any earlier record; a later
     /// update revives the object) — unlike updates, which resolve by timestamp.
-    pub fn append_tombstone(&self, namespace: &str, object_id: &str) -> Result<()> {
+    /// Returns the tombstone's log sequence.
+    pub fn append_tombstone(&self, namespace: &str, object_id: &str) -> Result<u64> {
         let micros = micros_since_epoch(SystemTime::now());
-        let mut log = self.trajectory_log.lock();
-        log.append_tombstone(micros, namespace, object_id)
+        let sequence = self
+            .trajectory_log
+            .lock()
+            .append_tombstone(micros, namespace, object_id)?;
+        self.applied.advance(sequence);
+        self.commit(sequence)?;
+        Ok(sequence)
+    }
+
+    /// Whether the database was opened read-only (see
+    /// [`PersistenceConfig::read_only`]).
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Let other processes open the database, once it is closed.
+    /// Random ID of the log, created with it and kept beside it
+    /// (`<log>.id`); a new one for each memory log.
+    pub fn log_id(&self) -> u64 {
+        self.log_id
+    }
+
+    pub(crate) fn unlock(&self) {
+        self.lock.lock().take();
+    }
+
+    /// Directory holding the log, for file-backed logs.
+    pub(crate) fn log_dir(&self) -> Option<&Path> {
+        let parent = self.log_path.as_deref()?.parent()?;
+        Some(if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        })
+    }
+
+    /// Why the log can no longer be written, if it can't: the data directory
+    /// is read-only or out of space. Writes then go on in memory, with
+    /// sequences that are never made durable, until the database is reopened;
+    /// under [`SyncPolicy::Always`] they return
+    /// [`SpatioError::DegradedPersistence`] after applying.
+    pub fn degraded(&self) -> Option<&str> {
+        self.degraded.get().map(String::as_str)
+    }
+
+    /// Finish a write that appended record `sequence`: under group commit,
+    /// wait until it is durable; then fail it if it was applied only in
+    /// memory where writes must be durable.
+    fn commit(&self, sequence: u64) -> Result<()> {
+        if let Some(group) = &self.group_commit
+            && !GROUPED.get()
+        {
+            self.group_sync(group, sequence)?;
+        }
+        match self.degraded.get() {
+            Some(reason) if self.durable_writes => {
+                Err(SpatioError::DegradedPersistence(reason.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Wait until record `sequence` is durable. The first writer to get here
+    /// leads: it waits out the window so others can append, then syncs them
+    /// all at once while the rest wait on the watermark.
+    fn group_sync(&self, group: &GroupCommit, sequence: u64) -> Result<()> {
+        while self.watermark.get() < sequence && self.degraded.get().is_none() {
+            let Some(_leader) = group.leader.try_lock() else {
+                // A leader's sync that fails leaves its group to the next.
+                self.watermark.wait_for(sequence, group.window);
+                continue;
+            };
+            if self.watermark.get() >= sequence {
+                break;
+            }
+            std::thread::sleep(group.window);
+            match sync_log(&self.trajectory_log, &self.watermark, group.mode) {
+                Err(SpatioError::Io(e)) if storage_unavailable(&e) => degrade(&self.degraded, &e),
+                result => result?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the appends of `f` as one group commit: they wait for durability
+    /// once, at the end, instead of one window each. Without group commit
+    /// this just runs `f`.
+    pub fn group<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        if self.group_commit.is_none() || GROUPED.get() {
+            return f();
+        }
+        // Clears the flag even if `f` panics, so later appends on this
+        // thread aren't taken for part of a group that is gone.
+        struct Grouped;
+        impl Drop for Grouped {
+            fn drop(&mut self) {
+                GROUPED.set(false);
+            }
+        }
+        let result = {
+            GROUPED.set(true);
+            let _grouped = Grouped;
+            f()
+        };
+        // Appends that came before a failure were applied and must be durable
+        // too.
+        let committed = self.commit(self.last_sequence());
+        let value = result?;
+        committed?;
+        Ok(value)
     }
 
-    /// Force flush of the trajectory log to disk
+    /// Force flush of the trajectory log to disk. Fails with
+    /// [`SpatioError::DegradedPersistence`] once the log can't be written.
     pub fn flush(&self) -> Result<()> {
         let mut log = self.trajectory_log.lock();
         log.flush()
     }
 
+    /// Flush the log and `fsync` it, even under [`SyncPolicy::Never`].
+    pub fn fsync(&self) -> Result<()> {
+        self.trajectory_log.lock().fsync()
+    }
+
+    /// Sequence of the last record appended to the log (0 if none yet).
+    pub fn last_sequence(&self) -> u64 {
+        self.trajectory_log.lock().sequence
+    }
+
+    /// Bytes written to the log, including ones still buffered (0 for an
+    /// in-memory log).
+    pub fn log_bytes(&self) -> u64 {
+        match &self.trajectory_log.lock().backend {
+            LogBackend::File { writer, .. } => writer.len,
+            LogBackend::Memory { .. } => 0,
+        }
+    }
+
+    /// The compaction or checkpoint holding the log, if any.
+    pub fn maintenance(&self) -> Option<Maintenance> {
+        self.maintenance.current()
+    }
+
+    /// Rewrite the log by `plan`, marked as a compaction while it runs.
+    fn compact_log<F: Fn(&str, SystemTime) -> bool>(&self, plan: RetentionPlan<F>) -> Result<u64> {
+        let mut log = self.trajectory_log.lock();
+        let _compacting = self.maintenance.begin(MaintenanceKind::Compaction);
+        log.compact(plan)
+    }
+
+    /// Time taken by each `fsync` of the log so far.
+    pub fn sync_latency(&self) -> LatencyHistogram {
+        self.trajectory_log.lock().sync_latency.lock().clone()
+    }
+
+    /// Sequence of the last record known to be on stable storage.
+    pub fn durable_sequence(&self) -> u64 {
+        self.watermark.get()
+    }
+
+    /// Block until record `sequence` is durable or `timeout` elapses, returning
+    /// whether it is. Under [`SyncPolicy::Background`] this asks the sync
+    /// thread to sync now rather than at its next interval. Returns `false`
+    /// at once for a record that can't become durable, persistence being
+    /// degraded.
+    pub fn wait_durable(&self, sequence: u64, timeout: Duration) -> bool {
+        if self.watermark.get() >= sequence {
+            return true;
+        }
+        if self.degraded.get().is_some() {
+            return false;
+        }
+        if let Some(background) = &self.background_sync {
+            background.request();
+        }
+        self.watermark.wait_for(sequence, timeout)
+    }
+
+    /// Wait until write `sequence` has been applied, for at most `timeout`.
+    /// Returns whether it has.
+    pub fn wait_applied(&self, sequence: u64, timeout: Duration) -> bool {
+        self.applied.wait_for(sequence, timeout)
+    }
+
     /// Query trajectory history
     pub fn query_trajectory(
         &self,
@@ -192,12 +676,13 @@ impl ColdState {
         // Try buffer first (fast path)
         let mut from_buffer = Vec::new();
         if let Some(buffer) = self.recent_buffer.get(&full_key) {
-            // Below capacity the buffer holds this key's complete history; at or
-            // above it, some records (including newer ones, under out-of-order
-            // timestamps) live only on disk, so fall through to the disk merge.
-            let buffer_is_complete = buffer.len() < self.buffer_capacity;
+            // A complete buffer holds this key's whole history; otherwise some
+            // records (including newer ones, under out-of-order timestamps)
+            // live only on disk, so fall through to the disk merge.
+            let buffer_is_complete = buffer.complete;
 
             from_buffer = buffer
+                .updates
                 .iter()
                 .filter(|u| u.timestamp >= start_time && u.timestamp <= end_time)
                 .cloned()
@@ -217,30 +702,29 @@ impl ColdState {
         let buffer_timestamps: std::collections::HashSet<SystemTime> =
             from_buffer.iter().map(|u| u.timestamp).collect();
 
+        // Only the newest `limit` records outside the buffer can make the
+        // result, so no more positions than that (plus the buffered stamps
+        // they may repeat) are read.
         let from_disk = {
             let mut log = self.trajectory_log.lock();
-            match log.flush_and_file_target()? {
-                // File backend: flush done; scan the stable on-disk prefix with
-                // the lock released so a long scan doesn't stall the writer.
+            let target = log.flush_and_file_target()?;
+            let positions = log.indexed_positions(
+                &full_key,
+                start_time,
+                end_time,
+                limit.saturating_add(buffer_timestamps.len()),
+            )?;
+            match target {
+                // File backend: open the segments under the lock, so a
+                // compaction can't swap the files the offsets point into,
+                // then read the records with the lock released.
                 Some(target) => {
+                    let reader = SegmentReader::open(&target.paths)?;
                     drop(log);
-                    scan_file(
-                        &target,
-                        namespace,
-                        object_id,
-                        start_time,
-                        end_time,
-                        &buffer_timestamps,
-                    )?
+                    read_file_updates(reader, &target, &positions, &buffer_timestamps, limit)?
                 }
-                // Memory backend: scan in place (fast, no I/O).
-                None => log.scan_memory(
-                    namespace,
-                    object_id,
-                    start_time,
-                    end_time,
-                    &buffer_timestamps,
-                ),
+                // Memory backend: read in place (fast, no I/O).
+                None => log.read_memory_updates(&positions, &buffer_timestamps, limit),
             }
         };
 
@@ -252,6 +736,285 @@ impl ColdState {
         Ok(from_buffer)
     }
 
+    /// Index the log for trajectory queries and read the sealed segments
+    /// holding updates from `since` on (see [`DB::warm_up`]), returning the
+    /// segments and bytes read. Writers wait meanwhile.
+    ///
+    /// [`DB::warm_up`]: super::DB::warm_up
+    pub(crate) fn warm_up(&self, since: SystemTime) -> Result<(usize, u64)> {
+        self.trajectory_log.lock().warm_up(since)
+    }
+
+    /// Every trajectory point of `namespace` within `[start, end]`, ordered by
+    /// object ID and then time (oldest first).
+    pub fn scan_namespace(
+        &self,
+        namespace: &str,
+        start_time: SystemTime,
+        end_time: SystemTime,
+    ) -> Result<Vec<(String, LocationUpdate)>> {
+        let mut points = {
+            let mut log = self.trajectory_log.lock();
+            match log.flush_and_file_target()? {
+                Some(target) => {
+                    drop(log);
+                    let mut points = Vec::new();
+                    visit_file_updates(&target, |ns, id, update| {
+                        if ns == namespace
+                            && update.timestamp >= start_time
+                            && update.timestamp <= end_time
+                        {
+                            points.push((id.to_string(), update));
+                        }
+                    })?;
+                    points
+                }
+                None => log.scan_memory_namespace(namespace, start_time, end_time),
+            }
+        };
+        points.sort_by(|(a, x), (b, y)| a.cmp(b).then(x.timestamp.cmp(&y.timestamp)));
+        Ok(points)
+    }
+
+    /// The current location of every object of `namespace` as of `at`,
+    /// replayed from the whole log and keyed by object ID.
+    pub fn namespace_state_at(
+        &self,
+        namespace: &str,
+        at: SystemTime,
+    ) -> Result<HashMap<String, LocationUpdate>> {
+        let mut state = StateAt {
+            namespace,
+            at,
+            objects: HashMap::new(),
+        };
+        self.visit_records(|record| state.apply(record))?;
+        Ok(state.into_locations())
+    }
+
+    /// Time each object of `namespace` spent where `inside` holds during
+    /// `[start, end]`, keyed by object ID; objects that never were inside are
+    /// left out.
+    ///
+    /// An object is taken to stay where it was last seen until its next
+    /// point, or until it was deleted. Besides the points in the window, only
+    /// the last point before it and the first after it count; `inside` is not
+    /// called for points outside the window that the log has already
+    /// superseded.
+    pub fn dwell_times(
+        &self,
+        namespace: &str,
+        start: SystemTime,
+        end: SystemTime,
+        inside: impl Fn(&Point3d) -> bool,
+    ) -> Result<BTreeMap<String, Duration>> {
+        let mut dwell = DwellTimes {
+            namespace,
+            start,
+            end,
+            inside,
+            objects: HashMap::new(),
+        };
+        self.visit_records(|record| dwell.apply(record))?;
+        Ok(dwell.into_totals())
+    }
+
+    /// The writes and deletes of one object within `[start, end]` whose kind
+    /// `kind` accepts, oldest first.
+    pub fn object_history(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        start: SystemTime,
+        end: SystemTime,
+        kind: impl Fn(HistoryEventKind) -> bool,
+    ) -> Result<Vec<HistoryEntry>> {
+        let mut entries = Vec::new();
+        self.visit_records(|record| {
+            let entry = match record {
+                RetentionRecord::Update {
+                    namespace: ns,
+                    object_id: id,
+                    update,
+                } if ns == namespace && id == object_id => HistoryEntry {
+                    timestamp: update.timestamp,
+                    kind: HistoryEventKind::Set,
+                    update: Some(update),
+                },
+                RetentionRecord::Tombstone {
+                    namespace: ns,
+                    object_id: id,
+                    deleted_at,
+                } if ns == namespace && id == object_id => HistoryEntry {
+                    timestamp: deleted_at,
+                    kind: HistoryEventKind::Delete,
+                    update: None,
+                },
+                _ => return,
+            };
+            if entry.timestamp >= start && entry.timestamp <= end && kind(entry.kind) {
+                entries.push(entry);
+            }
+        })?;
+        // Stable, so records with the same stamp keep their log order.
+        entries.sort_by_key(|entry| entry.timestamp);
+        Ok(entries)
+    }
+
+    /// Replay every record of the log, oldest first.
+    fn visit_records(&self, mut visit: impl FnMut(RetentionRecord<'_>)) -> Result<()> {
+        self.visit_numbered_records(|_, record| visit(record))
+    }
+
+    /// Replay every record of the log with its sequence, oldest first.
+    fn visit_numbered_records(
+        &self,
+        mut visit: impl FnMut(u64, RetentionRecord<'_>),
+    ) -> Result<()> {
+        let mut log = self.trajectory_log.lock();
+        match log.flush_and_file_target()? {
+            Some(target) => {
+                drop(log);
+                visit_file_records(&target, visit)?;
+            }
+            None => {
+                if let LogBackend::Memory { records } = &log.backend {
+                    for record in records {
+                        visit(record.sequence(), record.as_retention());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Up to `limit` records of `namespace` with sequences in
+    /// `(after, through]`, oldest first: each its sequence, object ID, and
+    /// update, or `None` for a deletion. Costs a pass over the whole log.
+    pub(crate) fn namespace_records(
+        &self,
+        namespace: &str,
+        after: u64,
+        through: u64,
+        limit: usize,
+    ) -> Result<Vec<(u64, String, Option<LocationUpdate>)>> {
+        let mut records = Vec::new();
+        self.visit_numbered_records(|sequence, record| {
+            if sequence <= after
+                || sequence > through
+                || records.len() >= limit
+                || record.namespace() != namespace
+            {
+                return;
+            }
+            records.push(match record {
+                RetentionRecord::Update {
+                    object_id, update, ..
+                } => (sequence, object_id.to_string(), Some(update)),
+                RetentionRecord::Tombstone { object_id, .. } => {
+                    (sequence, object_id.to_string(), None)
+                }
+            });
+        })?;
+        Ok(records)
+    }
+
+    /// The cursor of each write hook, by name, as last saved by
+    /// [`Self::write_hook_cursors`]. Empty for memory logs, or if the file
+    /// is missing or fails its checksum.
+    pub(crate) fn read_hook_cursors(&self) -> HashMap<String, u64> {
+        let Some(log_path) = &self.log_path else {
+            return HashMap::new();
+        };
+        let Ok(content) = std::fs::read_to_string(hook_cursors_path_for(log_path)) else {
+            return HashMap::new();
+        };
+        let Some((_, records, _)) = checked_snapshot_parts(&content, HOOK_CURSORS_HEADER) else {
+            return HashMap::new();
+        };
+        records
+            .lines()
+            .filter_map(|line| {
+                let (name, cursor) = line.rsplit_once('|')?;
+                Some((name.to_string(), cursor.parse().ok()?))
+            })
+            .collect()
+    }
+
+    /// Save the cursor of each write hook beside the log (`<log>.hooks`), as
+    /// `name|sequence` lines. No-op for memory logs and read-only opens.
+    pub(crate) fn write_hook_cursors(&self, cursors: &HashMap<String, u64>) -> Result<()> {
+        let Some(log_path) = self.log_path.as_ref().filter(|_| !self.read_only) else {
+            return Ok(());
+        };
+        write_with_trailer(
+            &hook_cursors_path_for(log_path),
+            HOOK_CURSORS_HEADER,
+            cursors
+                .iter()
+                .map(|(name, cursor)| format!("{}|{}", name, cursor)),
+        )?;
+        Ok(())
+    }
+
+    /// Zones saved beside the log (`<log>.zones`). Empty for memory logs, or
+    /// if the file is missing or fails its checksum.
+    pub(crate) fn read_zones(&self) -> Vec<super::Zone> {
+        let Some(log_path) = &self.log_path else {
+            return Vec::new();
+        };
+        let Ok(content) = std::fs::read_to_string(zones_path_for(log_path)) else {
+            return Vec::new();
+        };
+        let Some((_, records, _)) = checked_snapshot_parts(&content, ZONES_HEADER) else {
+            log::warn!("Ignoring zones file that fails its checksum");
+            return Vec::new();
+        };
+        records
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect()
+    }
+
+    /// Save every zone beside the log (`<log>.zones`), one JSON line each,
+    /// replacing the previous file. No-op for memory logs and read-only opens.
+    pub(crate) fn write_zones(&self, zones: &[Arc<super::Zone>]) -> Result<()> {
+        let Some(log_path) = self.log_path.as_ref().filter(|_| !self.read_only) else {
+            return Ok(());
+        };
+        let lines = zones
+            .iter()
+            .map(|zone| serde_json::to_string(zone.as_ref()))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| SpatioError::SerializationErrorWithContext(format!("zone: {e}")))?;
+        write_with_trailer(&zones_path_for(log_path), ZONES_HEADER, lines.into_iter())?;
+        Ok(())
+    }
+
+    /// Replay the whole log, ignoring the checkpoint, and count the records
+    /// that fail their checksum or don't parse. The log is locked for the
+    /// replay.
+    pub(crate) fn verify_log(&self) -> Result<LogCheck> {
+        let mut entries = HashMap::new();
+        let (records, target) = {
+            let mut log = self.trajectory_log.lock();
+            let target = log.flush_and_file_target()?;
+            (log.replay(0, 0, &mut entries, &mut |_| {})?.0, target)
+        };
+        let corrupt = match target {
+            Some(target) => count_corrupt_records(&target)?,
+            None => 0,
+        };
+        Ok(LogCheck {
+            records,
+            corrupt,
+            latest: entries
+                .into_iter()
+                .filter_map(|(key, slot)| slot.map(|u| (key, u)))
+                .collect(),
+        })
+    }
+
     /// Recover current locations on startup.
     ///
     /// Returns a map of "namespace::object_id" → latest surviving LocationUpdate.
@@ -261,28 +1024,52 @@ impl ColdState {
     /// corrupt snapshot safely falls back to a full replay.
     pub fn recover_current_locations(
         &self,
+    ) -> Result<std::collections::HashMap<String, LocationUpdate>> {
+        self.recover_current_locations_with_progress(&|_| {})
+    }
+
+    /// [`ColdState::recover_current_locations`], reporting the snapshot load
+    /// and log replay to `report`.
+    pub(crate) fn recover_current_locations_with_progress(
+        &self,
+        report: &dyn Fn(&OpenProgress),
     ) -> Result<std::collections::HashMap<String, LocationUpdate>> {
         use std::collections::HashMap;
         let mut entries: HashMap<String, Option<LocationUpdate>> = HashMap::new();
         let mut from_offset = 0u64;
+        let mut base_sequence = 0u64;
+        let mut log_len = 0u64;
 
         if let Some(log_path) = &self.log_path {
-            let log_len = std::fs::metadata(log_path).map(|m| m.len()).unwrap_or(0);
+            log_len = segments::total_len(&segments::paths(log_path, &segments::list(log_path)?));
             // Only trust the snapshot if it covers a prefix the log still has;
             // a shorter log means the snapshot is stale, so full-replay instead.
-            if let Some((snapshot, covered_len)) = read_snapshot(&snapshot_path_for(log_path))
-                && covered_len <= log_len
-            {
-                for (key, update) in snapshot {
-                    entries.insert(key, Some(update));
-                }
-                from_offset = covered_len;
+            if let Some(snapshot) = read_checkpoint(log_path, log_len) {
+                entries = snapshot.objects;
+                from_offset = snapshot.covered_len;
+                base_sequence = snapshot.sequence;
+                report(&OpenProgress::SnapshotLoaded {
+                    objects: entries.values().filter(|slot| slot.is_some()).count(),
+                });
             }
         }
 
         {
-            let log = self.trajectory_log.lock();
-            log.replay(from_offset, &mut entries)?;
+            let mut log = self.trajectory_log.lock();
+            let total = log_len - from_offset;
+            let (_, sequence) =
+                log.replay(from_offset, base_sequence, &mut entries, &mut |read| {
+                    report(&OpenProgress::ReplayingLog { read, total })
+                })?;
+            // Numbering continues from the last record, so sequences keep
+            // increasing across restarts and every record keeps the sequence
+            // it was written with (see `SEQUENCE_MARKER`).
+            log.resume_sequence(sequence);
+            self.applied.advance(sequence);
+        }
+        // These objects' history is in the log, not in any buffer.
+        for key in entries.keys() {
+            self.spilled.insert(key.clone());
         }
 
         Ok(entries
@@ -292,58 +1079,655 @@ impl ColdState {
     }
 
     /// Persist a checkpoint snapshot of `state` (the recovered current
-    /// locations) covering the current on-disk log length, so the next startup
+    /// locations) covering the current on-disk log length and the sequence of
+    /// its last record, so the next startup
     /// replays only records appended afterwards. The full history log is left
-    /// intact (trajectory queries still see everything). No-op for memory logs.
+    /// intact (trajectory queries still see everything). Any incremental
+    /// checkpoint is removed, since the snapshot covers it. No-op for memory
+    /// logs and read-only opens.
     pub fn write_checkpoint(
         &self,
         state: &std::collections::HashMap<String, LocationUpdate>,
     ) -> Result<()> {
-        let Some(log_path) = &self.log_path else {
+        let Some(log_path) = self.log_path.as_ref().filter(|_| !self.read_only) else {
             return Ok(());
         };
         // The snapshot covers exactly the bytes recovery read. write_checkpoint
         // runs at open with no concurrent writers, so the current on-disk length
         // is that boundary — no flush needed (which keeps buffered writes
         // buffered). Any not-yet-flushed bytes are simply replayed next time.
-        let covered_len = std::fs::metadata(log_path).map(|m| m.len()).unwrap_or(0);
-        write_snapshot(&snapshot_path_for(log_path), state, covered_len)
+        let covered_len =
+            segments::total_len(&segments::paths(log_path, &segments::list(log_path)?));
+        write_snapshot(
+            &snapshot_path_for(log_path),
+            state,
+            covered_len,
+            self.last_sequence(),
+        )?;
+        remove_if_exists(&delta_path_for(log_path))
     }
-}
-
-/// On-disk log format version.
-///
-/// Legacy `V1` logs have no header and no per-record checksum. `V2` logs begin
-/// with [`LOG_HEADER_V2`] and prefix each record with a CRC32 of the record
-/// body, so torn/merged/corrupt lines are detected and skipped on recovery.
-/// Existing V1 logs are still read; new logs are written as V2.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum LogVersion {
-    V1,
-    V2,
-}
-
-const LOG_HEADER_V2: &str = "#spatio-log v2";
+
+    /// Write a checkpoint of the log as it stands (see [`DB::checkpoint`]).
+    ///
+    /// A full checkpoint is built from the current checkpoint and the log
+    /// after it, as recovery would; an incremental one from the log after
+    /// the full snapshot only. The log is locked throughout, so writers wait
+    /// for it.
+    ///
+    /// [`DB::checkpoint`]: super::DB::checkpoint
+    pub(crate) fn checkpoint(&self, kind: CheckpointKind) -> Result<CheckpointReport> {
+        let Some(log_path) = &self.log_path else {
+            return Err(SpatioError::InvalidInput(
+                "in-memory databases have no checkpoint".to_string(),
+            ));
+        };
+        if self.read_only {
+            return Err(SpatioError::ReadOnly);
+        }
+        let mut log = self.trajectory_log.lock();
+        if let Some(reason) = log.degraded.get() {
+            return Err(SpatioError::DegradedPersistence(reason.clone()));
+        }
+        let _checkpointing = self.maintenance.begin(MaintenanceKind::Checkpoint);
+        let covered_len = match log.flush_and_file_target()? {
+            Some(target) => target.len,
+            None => 0,
+        };
+        let sequence = log.sequence;
+        let snapshot_path = snapshot_path_for(log_path);
+
+        // A delta needs a checksummed snapshot to be based on.
+        if kind == CheckpointKind::Incremental
+            && let Some(base) =
+                read_snapshot(&snapshot_path).filter(|s| s.covered_len <= covered_len)
+            && let Some(base_checksum) = base.checksum
+        {
+            let mut changes = HashMap::new();
+            log.replay(base.covered_len, base.sequence, &mut changes, &mut |_| {})?;
+            let checksum = write_delta(
+                &delta_path_for(log_path),
+                base_checksum,
+                &changes,
+                covered_len,
+                sequence,
+            )?;
+            return Ok(CheckpointReport {
+                kind,
+                objects: changes.len(),
+                sequence,
+                checksum,
+            });
+        }
+
+        let (mut entries, from_offset) = match read_checkpoint(log_path, covered_len) {
+            Some(snapshot) => (snapshot.objects, snapshot.covered_len),
+            None => (HashMap::new(), 0),
+        };
+        log.replay(from_offset, 0, &mut entries, &mut |_| {})?;
+        let state: HashMap<String, LocationUpdate> = entries
+            .into_iter()
+            .filter_map(|(key, slot)| Some((key, slot?)))
+            .collect();
+        let checksum = write_snapshot(&snapshot_path, &state, covered_len, sequence)?;
+        remove_if_exists(&delta_path_for(log_path))?;
+        Ok(CheckpointReport {
+            kind: CheckpointKind::Full,
+            objects: state.len(),
+            sequence,
+            checksum,
+        })
+    }
+
+    /// Save spatial indexes beside the log, written by `write` and followed by
+    /// a CRC32 of what it wrote, so the next startup can load them instead of
+    /// rebuilding them (see [`ColdState::read_index_snapshot`]). The file is
+    /// replaced atomically. No-op for memory logs and read-only opens.
+    pub(crate) fn write_index_snapshot(
+        &self,
+        write: impl FnOnce(&mut dyn Write) -> Result<()>,
+    ) -> Result<()> {
+        let Some(log_path) = self.log_path.as_ref().filter(|_| !self.read_only) else {
+            return Ok(());
+        };
+        write_atomically(&index_snapshot_path_for(log_path), |w| {
+            w.write_all(INDEX_SNAPSHOT_HEADER)?;
+            let mut body = ChecksummedWriter {
+                inner: w,
+                crc: Crc32::new(),
+            };
+            write(&mut body)?;
+            let checksum = body.crc.finish();
+            w.write_all(&checksum.to_le_bytes())?;
+            Ok(())
+        })
+    }
+
+    /// Read the saved spatial indexes through `read`. `None` if there are
+    /// none, or they are from another format version, fail their checksum or
+    /// fail to read; the caller then rebuilds the indexes from the recovered
+    /// locations.
+    pub(crate) fn read_index_snapshot<T>(
+        &self,
+        read: impl FnOnce(&mut dyn std::io::Read) -> Result<T>,
+    ) -> Option<T> {
+        let path = index_snapshot_path_for(self.log_path.as_ref()?);
+        let content = std::fs::read(&path).ok()?;
+        let Some(rest) = content.strip_prefix(INDEX_SNAPSHOT_HEADER) else {
+            log::warn!(
+                "Ignoring index snapshot {} of unknown format",
+                path.display()
+            );
+            return None;
+        };
+        let checksum_start = rest.len().checked_sub(4)?;
+        let (mut body, checksum) = rest.split_at(checksum_start);
+        if checksum != crc32(body).to_le_bytes() {
+            log::warn!(
+                "Ignoring index snapshot {} that fails its checksum",
+                path.display()
+            );
+            return None;
+        }
+        match read(&mut body) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                log::warn!(
+                    "Ignoring unreadable index snapshot {}: {}",
+                    path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Remove trajectory history past each namespace's retention as of
+    /// `now`, returning the number of log records removed.
+    ///
+    /// The log is rewritten without the expired records. Each live object's
+    /// current record is kept however old, since recovery rebuilds current
+    /// locations from it. Writers wait for the rewrite to finish.
+    pub fn expire_history(&self, retention: &HistoryRetention, now: SystemTime) -> Result<u64> {
+        self.expire_history_batch(retention, now, 1)
+    }
+
+    /// [`ColdState::expire_history`], but leave the log alone unless at least
+    /// `min_removed` records have expired, so frequent passes do not rewrite
+    /// it for a handful of records.
+    pub fn expire_history_batch(
+        &self,
+        retention: &HistoryRetention,
+        now: SystemTime,
+        min_removed: u64,
+    ) -> Result<u64> {
+        if retention.is_empty() {
+            return Ok(0);
+        }
+        let cutoffs: HashMap<&str, SystemTime> = retention
+            .max_age_secs
+            .iter()
+            .map(|(namespace, secs)| {
+                let cutoff = now
+                    .checked_sub(Duration::from_secs(*secs))
+                    .unwrap_or(UNIX_EPOCH);
+                (namespace.as_str(), cutoff)
+            })
+            .collect();
+        let expired = |namespace: &str, timestamp: SystemTime| {
+            cutoffs
+                .get(namespace)
+                .is_some_and(|cutoff| timestamp < *cutoff)
+        };
+
+        let plan = retention.max_updates.iter().fold(
+            RetentionPlan::new(expired).removing_at_least(min_removed),
+            |plan, (namespace, n)| plan.keeping_last(namespace, *n),
+        );
+        let removed = self.compact_log(plan)?;
+
+        // Buffers drop the same records. An emptied buffer is removed; if it
+        // was incomplete, the log may still hold records of its key.
+        let mut dropped = Vec::new();
+        self.recent_buffer.retain(|key, buffer| {
+            let Some((namespace, _)) = key.split_once("::") else {
+                return true;
+            };
+            let before = buffer.updates.len();
+            buffer.updates.retain(|u| !expired(namespace, u.timestamp));
+            self.buffered_points
+                .fetch_sub(before - buffer.updates.len(), Ordering::Relaxed);
+            if buffer.updates.is_empty() {
+                dropped.push((buffer.touched, (!buffer.complete).then(|| key.clone())));
+                return false;
+            }
+            true
+        });
+        let mut lru = self.lru.lock();
+        for (touched, spilled) in dropped {
+            lru.remove(&touched);
+            if let Some(key) = spilled {
+                self.spilled.insert(key);
+            }
+        }
+        drop(lru);
+        // Buffers may still hold updates beyond an object's newest N; reads
+        // fall back to the log.
+        if removed > 0 {
+            for namespace in retention.max_updates.keys() {
+                self.spill_namespace(namespace);
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Thin the history of `namespace` to what `policy` keeps, returning the
+    /// number of log records removed. Each live object's current record is
+    /// kept regardless. The log is rewritten as by
+    /// [`ColdState::expire_history`].
+    pub fn compact_history(&self, namespace: &str, policy: HistoryCompaction) -> Result<u64> {
+        let removed = match policy {
+            HistoryCompaction::KeepSince(since) => {
+                let expired =
+                    move |ns: &str, timestamp: SystemTime| ns == namespace && timestamp < since;
+                self.compact_log(RetentionPlan::new(expired))?
+            }
+            HistoryCompaction::KeepLast(n) => {
+                let plan =
+                    RetentionPlan::new(|_: &str, _: SystemTime| false).keeping_last(namespace, n);
+                self.compact_log(plan)?
+            }
+        };
+        // Buffers may hold points the log no longer has; reads fall back to
+        // the log.
+        self.spill_namespace(namespace);
+        Ok(removed)
+    }
+
+    /// Drop the recent buffers of `namespace`, sending its reads to the log.
+    fn spill_namespace(&self, namespace: &str) {
+        let prefix = Self::make_key(namespace, "");
+        let keys: Vec<String> = self
+            .recent_buffer
+            .iter()
+            .filter(|entry| entry.key().starts_with(&prefix))
+            .map(|entry| entry.key().clone())
+            .collect();
+        for key in keys {
+            if self.remove_buffer(&key) {
+                self.spilled.insert(key);
+            }
+        }
+    }
+
+    /// Remove the updates of an object stamped with one of `timestamps` from
+    /// the log, returning the number of log records removed. The object's
+    /// current record is kept regardless. The log is rewritten as by
+    /// [`ColdState::expire_history`].
+    pub fn drop_updates(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        timestamps: HashSet<SystemTime>,
+    ) -> Result<u64> {
+        if timestamps.is_empty() {
+            return Ok(0);
+        }
+        let key = Self::make_key(namespace, object_id);
+        let plan =
+            RetentionPlan::new(|_: &str, _: SystemTime| false).dropping(key.clone(), timestamps);
+        let removed = self.compact_log(plan)?;
+        // The buffer may hold dropped points; reads fall back to the log.
+        if self.remove_buffer(&key) {
+            self.spilled.insert(key);
+        }
+        Ok(removed)
+    }
+
+    /// Remove every record of an object from the log and the recent buffer,
+    /// returning the number of log records removed. The log is rewritten as by
+    /// [`ColdState::expire_history`], so the data is gone from disk, not just
+    /// hidden.
+    pub fn forget_object(&self, namespace: &str, object_id: &str) -> Result<u64> {
+        let key = Self::make_key(namespace, object_id);
+        let plan = RetentionPlan::new(|_: &str, _: SystemTime| false).forgetting(key.clone());
+        let removed = self.compact_log(plan)?;
+        self.remove_buffer(&key);
+        self.spilled.remove(&key);
+        Ok(removed)
+    }
+
+    /// [`ColdState::forget_object`] for every object of `namespace`.
+    pub fn forget_namespace(&self, namespace: &str) -> Result<u64> {
+        let plan =
+            RetentionPlan::new(|_: &str, _: SystemTime| false).forgetting_namespace(namespace);
+        let removed = self.compact_log(plan)?;
+        let prefix = Self::make_key(namespace, "");
+        let keys: Vec<String> = self
+            .recent_buffer
+            .iter()
+            .filter(|buffer| buffer.key().starts_with(&prefix))
+            .map(|buffer| buffer.key().clone())
+            .collect();
+        for key in keys {
+            self.remove_buffer(&key);
+        }
+        self.spilled.retain(|key| !key.starts_with(&prefix));
+        Ok(removed)
+    }
+}
+
+/// How much trajectory history each namespace keeps. Namespaces in neither
+/// map keep all of it.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryRetention {
+    /// Updates older than this many seconds expire.
+    pub max_age_secs: HashMap<String, u64>,
+    /// All but the newest N updates of each object expire.
+    pub max_updates: HashMap<String, usize>,
+}
+
+impl HistoryRetention {
+    pub fn is_empty(&self) -> bool {
+        self.max_age_secs.is_empty() && self.max_updates.is_empty()
+    }
+}
+
+/// A log record as seen by a retention pass or a point-in-time replay.
+enum RetentionRecord<'a> {
+    Update {
+        namespace: &'a str,
+        object_id: &'a str,
+        update: LocationUpdate,
+    },
+    Tombstone {
+        namespace: &'a str,
+        object_id: &'a str,
+        deleted_at: SystemTime,
+    },
+}
+
+impl RetentionRecord<'_> {
+    fn namespace(&self) -> &str {
+        match self {
+            Self::Update { namespace, .. } | Self::Tombstone { namespace, .. } => namespace,
+        }
+    }
+
+    fn key(&self) -> String {
+        match self {
+            Self::Update {
+                namespace,
+                object_id,
+                ..
+            }
+            | Self::Tombstone {
+                namespace,
+                object_id,
+                ..
+            } => format!("{}::{}", namespace, object_id),
+        }
+    }
+}
+
+/// Parse a record body for a retention pass; `None` for corrupt records.
+fn parse_retention_record(body: &str) -> Option<RetentionRecord<'_>> {
+    if let Some(rest) = body.strip_prefix("TOMBSTONE|") {
+        let mut parts = rest.splitn(3, '|');
+        let (micros, namespace, object_id) = (parts.next()?, parts.next()?, parts.next()?);
+        let micros: u64 = micros.parse().ok()?;
+        return Some(RetentionRecord::Tombstone {
+            namespace,
+            object_id,
+            deleted_at: UNIX_EPOCH + Duration::from_micros(micros),
+        });
+    }
+    let (timestamp, namespace, object_id, position, metadata) = parse_update_body(body)?;
+    Some(RetentionRecord::Update {
+        namespace,
+        object_id,
+        update: LocationUpdate {
+            timestamp,
+            position,
+            metadata,
+        },
+    })
+}
+
+/// Decides which records survive a retention pass, in two passes over the
+/// log: [`Self::observe`] every record in order, then [`Self::keep`] them in
+/// the same order.
+///
+/// An update survives if it is not expired or is its object's current record
+/// (resolved as recovery does). A tombstone survives only if an update it
+/// hides does, so deleted objects whose history expired leave nothing behind.
+/// Records of a forgotten object never survive.
+struct RetentionPlan<F> {
+    expired: F,
+    forget: Option<String>,
+    forget_namespace: Option<String>,
+    /// Expire the updates of one key (`namespace::object_id`) stamped with
+    /// these timestamps.
+    drop_updates: Option<(String, HashSet<SystemTime>)>,
+    /// Expire all but the newest N updates of each object, per namespace.
+    keep_last: HashMap<String, usize>,
+    /// Newest update timestamps of each object under `keep_last`, at most N.
+    newest: HashMap<String, BinaryHeap<Reverse<SystemTime>>>,
+    /// Skip the rewrite unless at least this many records go.
+    min_removed: u64,
+    /// Current record of every live object: its index and update.
+    live: HashMap<String, Option<(u64, LocationUpdate)>>,
+    live_indexes: HashSet<u64>,
+    kept_keys: HashSet<String>,
+}
+
+impl<F: Fn(&str, SystemTime) -> bool> RetentionPlan<F> {
+    fn new(expired: F) -> Self {
+        Self {
+            expired,
+            forget: None,
+            forget_namespace: None,
+            drop_updates: None,
+            keep_last: HashMap::new(),
+            newest: HashMap::new(),
+            min_removed: 0,
+            live: HashMap::new(),
+            live_indexes: HashSet::new(),
+            kept_keys: HashSet::new(),
+        }
+    }
+
+    /// Drop every record of `key` (`namespace::object_id`).
+    fn forgetting(mut self, key: String) -> Self {
+        self.forget = Some(key);
+        self
+    }
+
+    /// Drop every record of every object of `namespace`.
+    fn forgetting_namespace(mut self, namespace: &str) -> Self {
+        self.forget_namespace = Some(namespace.to_string());
+        self
+    }
+
+    /// Also expire the updates of `key` stamped with one of `timestamps`.
+    fn dropping(mut self, key: String, timestamps: HashSet<SystemTime>) -> Self {
+        self.drop_updates = Some((key, timestamps));
+        self
+    }
+
+    /// Also expire all but the newest `n` updates of each object of
+    /// `namespace`.
+    fn keeping_last(mut self, namespace: &str, n: usize) -> Self {
+        self.keep_last.insert(namespace.to_string(), n);
+        self
+    }
+
+    /// Leave the log untouched unless at least `n` records would be removed.
+    fn removing_at_least(mut self, n: u64) -> Self {
+        self.min_removed = n;
+        self
+    }
+
+    /// Forget what a dry run of [`Self::keep`] saw, so the real pass starts
+    /// afresh.
+    fn rewind(&mut self) {
+        self.kept_keys.clear();
+    }
+
+    fn forgets(&self, record: &RetentionRecord<'_>) -> bool {
+        self.forget_namespace.as_deref() == Some(record.namespace())
+            || self.forget.as_deref() == Some(record.key().as_str())
+    }
+
+    fn drops(&self, key: &str, timestamp: SystemTime) -> bool {
+        self.drop_updates
+            .as_ref()
+            .is_some_and(|(k, timestamps)| k == key && timestamps.contains(&timestamp))
+    }
+
+    /// Whether an update of `key` stamped `timestamp` is older than the
+    /// newest N of its object. Ties with the Nth newest are kept.
+    fn beyond_last(&self, namespace: &str, key: &str, timestamp: SystemTime) -> bool {
+        let Some(n) = self.keep_last.get(namespace) else {
+            return false;
+        };
+        match self.newest.get(key) {
+            Some(newest) if newest.len() >= *n => newest
+                .peek()
+                .is_none_or(|Reverse(oldest)| timestamp < *oldest),
+            _ => false,
+        }
+    }
+
+    fn observe(&mut self, index: u64, record: &RetentionRecord<'_>) {
+        if self.forgets(record) {
+            return;
+        }
+        let key = record.key();
+        if let RetentionRecord::Update { update, .. } = record
+            && let Some(&n) = self.keep_last.get(record.namespace())
+        {
+            let newest = self.newest.entry(key.clone()).or_default();
+            newest.push(Reverse(update.timestamp));
+            if newest.len() > n {
+                newest.pop();
+            }
+        }
+        let slot = self.live.entry(key).or_insert(None);
+        match record {
+            RetentionRecord::Update { update, .. } => match slot {
+                Some((_, current)) if update.timestamp <= current.timestamp => {}
+                _ => *slot = Some((index, update.clone())),
+            },
+            RetentionRecord::Tombstone { .. } => *slot = None,
+        }
+    }
+
+    /// Finish the first pass, returning the current location of every live
+    /// object.
+    fn current_locations(&mut self) -> HashMap<String, LocationUpdate> {
+        self.live
+            .drain()
+            .filter_map(|(key, slot)| {
+                let (index, update) = slot?;
+                self.live_indexes.insert(index);
+                Some((key, update))
+            })
+            .collect()
+    }
+
+    fn keep(&mut self, index: u64, record: &RetentionRecord<'_>) -> bool {
+        if self.forgets(record) {
+            return false;
+        }
+        match record {
+            RetentionRecord::Update {
+                namespace, update, ..
+            } => {
+                let expired = (self.expired)(namespace, update.timestamp)
+                    || self.beyond_last(namespace, &record.key(), update.timestamp)
+                    || self.drops(&record.key(), update.timestamp);
+                let keep = !expired || self.live_indexes.contains(&index);
+                if keep {
+                    self.kept_keys.insert(record.key());
+                }
+                keep
+            }
+            RetentionRecord::Tombstone { .. } => self.kept_keys.contains(&record.key()),
+        }
+    }
+}
+
+/// On-disk log format version.
+///
+/// Legacy `V1` logs have no header and no per-record checksum. `V2` logs begin
+/// with [`LOG_HEADER_V2`] and prefix each record with a CRC32 of the record
+/// body, so torn/merged/corrupt lines are detected and skipped on recovery.
+/// Existing V1 logs are still read; new logs are written as V2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogVersion {
+    V1,
+    V2,
+}
+
+const LOG_HEADER_V2: &str = "#spatio-log v2";
 
 /// CRC32 (IEEE 802.3 / ISO-HDLC, reflected). Implemented inline to avoid adding
 /// a dependency. Check value: `crc32(b"123456789") == 0xCBF43926`.
-fn crc32(bytes: &[u8]) -> u32 {
-    let mut crc: u32 = 0xFFFF_FFFF;
-    for &byte in bytes {
-        crc ^= byte as u32;
-        for _ in 0..8 {
-            let mask = (crc & 1).wrapping_neg();
-            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(bytes);
+    crc.finish()
+}
+
+/// [`crc32`] of data fed in pieces.
+struct Crc32(u32);
+
+/// Passes writes through to `inner`, keeping the [`Crc32`] of what passed.
+struct ChecksummedWriter<'a> {
+    inner: &'a mut dyn Write,
+    crc: Crc32,
+}
+
+impl Write for ChecksummedWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.crc.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Crc32 {
+    fn new() -> Self {
+        Self(0xFFFF_FFFF)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u32;
+            for _ in 0..8 {
+                let mask = (self.0 & 1).wrapping_neg();
+                self.0 = (self.0 >> 1) ^ (0xEDB8_8320 & mask);
+            }
         }
     }
-    !crc
+
+    fn finish(self) -> u32 {
+        !self.0
+    }
 }
 
-/// Extract the parseable record body from a raw log line for `version`,
-/// returning `None` for the header, comments, and CRC-failed records.
-fn record_body(line: &str, version: LogVersion) -> Option<&str> {
+/// Extract the parseable record bodies from a raw log line for `version`,
+/// returning `None` for the header, comments, and CRC-failed records. The
+/// bodies are newline-separated: a compressed block holds several (see
+/// [`compression`]), any other line one.
+fn record_bodies(line: &str, version: LogVersion) -> Option<Cow<'_, str>> {
     match version {
-        LogVersion::V1 => Some(line),
+        LogVersion::V1 => Some(Cow::Borrowed(line)),
         LogVersion::V2 => {
             if line.is_empty() || line.starts_with('#') {
                 return None;
@@ -354,9 +1738,87 @@ fn record_body(line: &str, version: LogVersion) -> Option<&str> {
                 log::warn!("Skipping log record with CRC mismatch (corrupt or torn write)");
                 return None;
             }
-            Some(body)
+            compression::decompress(body)
+        }
+    }
+}
+
+/// Whether `line`, for which [`record_bodies`] found nothing, held records
+/// that were lost, rather than being a header or comment.
+fn lost_records(line: &str, version: LogVersion) -> bool {
+    !line.is_empty() && (version == LogVersion::V1 || !line.starts_with('#'))
+}
+
+/// Prefix of a record that numbers the records after it instead of holding
+/// data: `SEQ|n` gives the next record sequence `n + 1`.
+///
+/// A record's sequence is otherwise one more than the record before it, so
+/// a marker goes wherever that would be wrong: after a line holding several
+/// records (a compressed block), which counts as one if it is lost; where a
+/// failed write left a gap; and where compaction removed records. Replay
+/// then numbers every surviving record as it was first numbered.
+const SEQUENCE_MARKER: &str = "SEQ|";
+
+/// The sequence a [`SEQUENCE_MARKER`] body sets.
+fn parse_sequence_marker(body: &str) -> Option<u64> {
+    body.strip_prefix(SEQUENCE_MARKER)?.parse().ok()
+}
+
+/// Numbers the records of a log as they are read back, oldest first.
+struct Numbering {
+    /// Sequence of the last record read.
+    last: u64,
+}
+
+impl Numbering {
+    /// Numbering for records read after the one with sequence `last`.
+    fn after(last: u64) -> Self {
+        Self { last }
+    }
+
+    /// The sequence of `body`, or `None` if it is a marker.
+    fn number(&mut self, body: &str) -> Option<u64> {
+        match parse_sequence_marker(body) {
+            Some(sequence) => {
+                self.last = sequence;
+                None
+            }
+            None => {
+                self.last += 1;
+                Some(self.last)
+            }
         }
     }
+
+    /// Count a line that could not be read as one lost record, which it was
+    /// unless a marker follows to say otherwise.
+    fn lose_line(&mut self) {
+        self.last += 1;
+    }
+}
+
+/// Header line of a new V2 log whose records are compressed with `codec`.
+fn log_header(codec: LogCompression) -> String {
+    match codec.name() {
+        Some(name) => format!("{} {}", LOG_HEADER_V2, name),
+        None => LOG_HEADER_V2.to_string(),
+    }
+}
+
+/// Format and record compression of an existing log with header `line`.
+fn parse_log_header(line: &str) -> (LogVersion, LogCompression) {
+    let line = line.trim_end_matches(['\n', '\r']);
+    if line == LOG_HEADER_V2 {
+        return (LogVersion::V2, LogCompression::None);
+    }
+    match line
+        .strip_prefix(LOG_HEADER_V2)
+        .and_then(|rest| rest.strip_prefix(' '))
+        .and_then(compression::codec_named)
+    {
+        Some(codec) => (LogVersion::V2, codec),
+        None => (LogVersion::V1, LogCompression::None),
+    }
 }
 
 /// Best-effort `fsync` of a file's parent directory so a newly created file's
@@ -373,6 +1835,29 @@ fn sync_parent_dir(path: &Path) {
     }
 }
 
+/// The log can't be written at all for now: the file system is read-only or
+/// full, or the log isn't writable by this process.
+fn storage_unavailable(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    matches!(
+        error.kind(),
+        ErrorKind::ReadOnlyFilesystem
+            | ErrorKind::StorageFull
+            | ErrorKind::QuotaExceeded
+            | ErrorKind::PermissionDenied
+    )
+}
+
+/// Record that the log stopped accepting writes because of `error`.
+fn degrade(degraded: &OnceLock<String>, error: &std::io::Error) {
+    if degraded.set(error.to_string()).is_ok() {
+        log::error!(
+            "Trajectory log is not writable ({}); continuing with writes kept in memory only",
+            error
+        );
+    }
+}
+
 /// Write one record body as a newline-terminated log line, prefixing a CRC32
 /// (hex) under V2.
 fn write_record<W: Write>(w: &mut W, version: LogVersion, body: &str) -> std::io::Result<()> {
@@ -382,11 +1867,207 @@ fn write_record<W: Write>(w: &mut W, version: LogVersion, body: &str) -> std::io
     }
 }
 
+/// Bytes [`write_record`] writes for `body`.
+fn record_len(version: LogVersion, body: &str) -> u64 {
+    let crc = match version {
+        LogVersion::V2 => 9,
+        LogVersion::V1 => 0,
+    };
+    crc + body.len() as u64 + 1
+}
+
+/// Most records a compressed block holds.
+const BLOCK_RECORDS: usize = 256;
+
+/// Bytes of record bodies past which a compressed block is written out.
+const BLOCK_BYTES: usize = 64 * 1024;
+
+/// Most records one log line holds: a compressed block or a batch.
+pub(crate) const MAX_BATCH_RECORDS: usize = 1 << 16;
+
+/// Position of a record in a file-backed log: the byte offset of its line,
+/// scaled to make room for its slot within a block.
+fn file_position(offset: u64, slot: usize) -> u64 {
+    offset * MAX_BATCH_RECORDS as u64 + slot as u64
+}
+
+/// Line offset and block slot of a [`file_position`].
+fn split_file_position(position: u64) -> (u64, usize) {
+    let slots = MAX_BATCH_RECORDS as u64;
+    (position / slots, (position % slots) as usize)
+}
+
+/// Writes records to a log file in its format. A compressed log holds
+/// records back and writes them as one block when the writer is flushed or
+/// the block fills up.
+struct RecordWriter<W: Write> {
+    out: W,
+    version: LogVersion,
+    compression: LogCompression,
+    /// Bodies of the held-back records, newline-separated.
+    block: String,
+    block_records: usize,
+    /// Bytes written, buffered or not: the offset of the next line.
+    len: u64,
+    /// Sequence replay gives the last record written (see
+    /// [`SEQUENCE_MARKER`]).
+    sequence: u64,
+}
+
+impl<W: Write> RecordWriter<W> {
+    /// Writer appending to `out`, which already holds `len` bytes of log
+    /// whose last record has `sequence`.
+    fn new(
+        out: W,
+        len: u64,
+        sequence: u64,
+        version: LogVersion,
+        compression: LogCompression,
+    ) -> Self {
+        Self {
+            out,
+            version,
+            compression,
+            block: String::new(),
+            block_records: 0,
+            len,
+            sequence,
+        }
+    }
+
+    /// Writer for a new log segment starting at offset `start` of the log,
+    /// after a record with `sequence`, stamping the header first under V2.
+    fn create(
+        out: W,
+        start: u64,
+        sequence: u64,
+        version: LogVersion,
+        compression: LogCompression,
+    ) -> std::io::Result<Self> {
+        let mut writer = Self::new(out, start, sequence, version, compression);
+        if version == LogVersion::V2 {
+            let header = log_header(compression);
+            writeln!(writer.out, "{}", header)?;
+            writer.len += header.len() as u64 + 1;
+        }
+        Ok(writer)
+    }
+
+    /// Write (or hold back) the record with `sequence`, returning its
+    /// [`file_position`]. A marker goes first if replay would otherwise
+    /// number it differently.
+    fn append(&mut self, sequence: u64, body: &str) -> std::io::Result<u64> {
+        self.mark(sequence - 1)?;
+        self.sequence = sequence;
+        self.push(body)
+    }
+
+    /// Write the records with sequences from `first` on as one block line, so
+    /// replay reads all of them or none, returning their [`file_position`]s.
+    /// V1 logs have no blocks and get one line per record, so replay may read
+    /// only some of them.
+    fn append_batch(&mut self, first: u64, bodies: &[String]) -> std::io::Result<Vec<u64>> {
+        debug_assert!(bodies.len() <= MAX_BATCH_RECORDS);
+        if bodies.len() < 2 || self.version == LogVersion::V1 {
+            return (first..)
+                .zip(bodies)
+                .map(|(sequence, body)| self.append(sequence, body))
+                .collect();
+        }
+        self.mark(first - 1)?;
+        self.write_block()?;
+        let offset = self.len;
+        self.block = bodies.join("\n");
+        self.block_records = bodies.len();
+        self.sequence = first + bodies.len() as u64 - 1;
+        self.write_block()?;
+        Ok((0..bodies.len())
+            .map(|slot| file_position(offset, slot))
+            .collect())
+    }
+
+    /// Make the next record replay reads have `sequence + 1`, writing a
+    /// marker unless it already would.
+    fn mark(&mut self, sequence: u64) -> std::io::Result<()> {
+        if sequence != self.sequence {
+            self.sequence = sequence;
+            self.push(&format!("{}{}", SEQUENCE_MARKER, sequence))?;
+        }
+        Ok(())
+    }
+
+    /// Write (or hold back) a record body, returning its [`file_position`].
+    fn push(&mut self, body: &str) -> std::io::Result<u64> {
+        if self.compression == LogCompression::None {
+            let position = file_position(self.len, 0);
+            write_record(&mut self.out, self.version, body)?;
+            self.len += record_len(self.version, body);
+            return Ok(position);
+        }
+        let position = file_position(self.len, self.block_records);
+        if self.block_records > 0 {
+            self.block.push('\n');
+        }
+        self.block.push_str(body);
+        self.block_records += 1;
+        if self.block_records == BLOCK_RECORDS || self.block.len() >= BLOCK_BYTES {
+            self.write_block()?;
+        }
+        Ok(position)
+    }
+
+    /// Write the held-back records as one compressed line. A lone record
+    /// that compression wouldn't shrink is written plain.
+    fn write_block(&mut self) -> std::io::Result<()> {
+        if self.block_records == 0 {
+            return Ok(());
+        }
+        let compressed = compression::compress(&self.block, self.compression)?;
+        let line = if self.block_records == 1 && compressed.len() >= self.block.len() {
+            &self.block
+        } else {
+            &compressed
+        };
+        let result = write_record(&mut self.out, self.version, line);
+        self.len += record_len(self.version, line);
+        let records = std::mem::take(&mut self.block_records);
+        self.block.clear();
+        result?;
+        // Should the block be lost, replay counts it as one record; the
+        // marker puts the numbering right again after it.
+        if records > 1 {
+            let marker = format!("{}{}", SEQUENCE_MARKER, self.sequence);
+            write_record(&mut self.out, self.version, &marker)?;
+            self.len += record_len(self.version, &marker);
+        }
+        Ok(())
+    }
+
+    /// Write out held-back records and flush `out`.
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.write_block()?;
+        self.out.flush()
+    }
+}
+
+impl RecordWriter<LogWriter> {
+    /// Write out held-back records and sync the log as `mode` asks.
+    fn sync(&mut self, mode: SyncMode) -> std::io::Result<()> {
+        self.write_block()?;
+        self.out.sync(mode)
+    }
+}
+
 /// Microseconds since the Unix epoch (saturating at 0 for pre-epoch times).
 fn micros_since_epoch(t: SystemTime) -> u128 {
     t.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros()
 }
 
+/// Whole seconds since the Unix epoch (0 for pre-epoch times).
+fn secs_since_epoch(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
 /// The canonical update-record body: `micros|ns|id|lat|lon|alt|json_len|json`,
 /// coordinates at ~0.1 m precision. The single source of truth for the on-disk
 /// layout, shared by the log and the snapshot writers.
@@ -411,80 +2092,450 @@ fn format_update_body(
     )
 }
 
-/// Parse an update-record body into `(timestamp, namespace, object_id, position,
-/// metadata)`. Returns `None` for tombstones and malformed bodies (wrong field
-/// count or unparseable numbers) — the single parser shared by every read path.
-fn parse_update_body(body: &str) -> Option<(SystemTime, &str, &str, Point3d, serde_json::Value)> {
-    // splitn keeps the metadata (last field) intact even if it contains '|'.
-    let parts: Vec<&str> = body.splitn(8, '|').collect();
-    if parts.len() != 8 {
-        return None;
+/// Parse an update-record body into `(timestamp, namespace, object_id, position,
+/// metadata)`. Returns `None` for tombstones and malformed bodies (wrong field
+/// count or unparseable numbers) — the single parser shared by every read path.
+fn parse_update_body(body: &str) -> Option<(SystemTime, &str, &str, Point3d, serde_json::Value)> {
+    // splitn keeps the metadata (last field) intact even if it contains '|'.
+    let parts: Vec<&str> = body.splitn(8, '|').collect();
+    if parts.len() != 8 {
+        return None;
+    }
+    let micros: u128 = parts[0].parse().ok()?;
+    let timestamp = UNIX_EPOCH + Duration::from_micros(u64::try_from(micros).unwrap_or(u64::MAX));
+    let lat: f64 = parts[3].parse().ok()?;
+    let lon: f64 = parts[4].parse().ok()?;
+    let alt: f64 = parts[5].parse().ok()?;
+    let metadata = serde_json::from_str(parts[7]).unwrap_or(serde_json::Value::Null);
+    Some((
+        timestamp,
+        parts[1],
+        parts[2],
+        Point3d::new(lon, lat, alt),
+        metadata,
+    ))
+}
+
+/// Header of snapshots written before they carried a checksum, still read.
+const SNAPSHOT_HEADER_V1_PREFIX: &str = "#spatio-snap v1 ";
+const SNAPSHOT_HEADER_PREFIX: &str = "#spatio-snap v2 ";
+const DELTA_HEADER_PREFIX: &str = "#spatio-delta v1 ";
+/// Last line of a snapshot or delta: its record count and checksum.
+const SNAPSHOT_TRAILER_PREFIX: &str = "#end ";
+
+/// Path of the checkpoint snapshot beside a log file (`<log>.snap`).
+fn snapshot_path_for(log_path: &Path) -> std::path::PathBuf {
+    let mut s = log_path.as_os_str().to_os_string();
+    s.push(".snap");
+    std::path::PathBuf::from(s)
+}
+
+/// Path of the lock file beside a log file (`<log>.lock`).
+fn lock_path_for(log_path: &Path) -> std::path::PathBuf {
+    let mut s = log_path.as_os_str().to_os_string();
+    s.push(".lock");
+    std::path::PathBuf::from(s)
+}
+
+const HOOK_CURSORS_HEADER: &str = "#spatio-hooks v1";
+
+/// Path of the write hook cursors beside a log file (`<log>.hooks`).
+fn hook_cursors_path_for(log_path: &Path) -> std::path::PathBuf {
+    let mut s = log_path.as_os_str().to_os_string();
+    s.push(".hooks");
+    std::path::PathBuf::from(s)
+}
+
+const ZONES_HEADER: &str = "#spatio-zones v1";
+
+/// Path of the saved zones beside a log file (`<log>.zones`).
+fn zones_path_for(log_path: &Path) -> std::path::PathBuf {
+    let mut s = log_path.as_os_str().to_os_string();
+    s.push(".zones");
+    std::path::PathBuf::from(s)
+}
+
+/// Path of the log ID beside a log file (`<log>.id`).
+fn log_id_path_for(log_path: &Path) -> std::path::PathBuf {
+    let mut s = log_path.as_os_str().to_os_string();
+    s.push(".id");
+    std::path::PathBuf::from(s)
+}
+
+/// A random log ID.
+fn new_log_id() -> u64 {
+    use std::hash::BuildHasher;
+    std::collections::hash_map::RandomState::new().hash_one((SystemTime::now(), std::process::id()))
+}
+
+/// The ID of the log at `log_path`, created and saved if it has none yet.
+/// A read-only open, or one that can't save it, gets an ID that lasts until
+/// it closes.
+fn open_log_id(log_path: &Path, read_only: bool) -> u64 {
+    let path = log_id_path_for(log_path);
+    if let Some(id) = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|text| u64::from_str_radix(text.trim(), 16).ok())
+    {
+        return id;
+    }
+    let id = new_log_id();
+    if !read_only && let Err(e) = write_atomically(&path, |w| Ok(writeln!(w, "{:016x}", id)?)) {
+        log::warn!("Failed to save the log ID to {}: {}", path.display(), e);
+    }
+    id
+}
+
+/// Lock the database at `log_path` against other processes: exclusively to
+/// write it, shared to read it. The lock is held as long as the returned
+/// file is open.
+fn lock_database(log_path: &Path, read_only: bool) -> Result<Option<File>> {
+    let path = lock_path_for(log_path);
+    let file = if read_only {
+        match File::open(&path) {
+            Ok(file) => file,
+            // Every writer leaves the file behind, so none has it open.
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+    } else {
+        match OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+        {
+            Ok(file) => file,
+            // The log will be degraded too, so nothing is written to it.
+            Err(e) if storage_unavailable(&e) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+    };
+    let locked = if read_only {
+        file.try_lock_shared()
+    } else {
+        file.try_lock()
+    };
+    match locked {
+        Ok(()) => Ok(Some(file)),
+        Err(std::fs::TryLockError::WouldBlock) => {
+            Err(SpatioError::Locked(log_path.display().to_string()))
+        }
+        Err(std::fs::TryLockError::Error(e)) => Err(e.into()),
     }
-    let micros: u128 = parts[0].parse().ok()?;
-    let timestamp = UNIX_EPOCH + Duration::from_micros(u64::try_from(micros).unwrap_or(u64::MAX));
-    let lat: f64 = parts[3].parse().ok()?;
-    let lon: f64 = parts[4].parse().ok()?;
-    let alt: f64 = parts[5].parse().ok()?;
-    let metadata = serde_json::from_str(parts[7]).unwrap_or(serde_json::Value::Null);
-    Some((
-        timestamp,
-        parts[1],
-        parts[2],
-        Point3d::new(lon, lat, alt),
-        metadata,
-    ))
 }
 
-const SNAPSHOT_HEADER_PREFIX: &str = "#spatio-snap v1 ";
-
-/// Path of the checkpoint snapshot beside a log file (`<log>.snap`).
-fn snapshot_path_for(log_path: &Path) -> std::path::PathBuf {
+/// Path of the incremental checkpoint beside a log file
+/// (`<log>.snap.delta`).
+fn delta_path_for(log_path: &Path) -> std::path::PathBuf {
     let mut s = log_path.as_os_str().to_os_string();
-    s.push(".snap");
+    s.push(".snap.delta");
     std::path::PathBuf::from(s)
 }
 
+/// A checkpoint snapshot or delta as read from disk.
+struct Snapshot {
+    /// `namespace::object_id` -> location, or `None` for an object a delta
+    /// records as deleted.
+    objects: HashMap<String, Option<LocationUpdate>>,
+    /// Log bytes covered: recovery replays the log from here.
+    covered_len: u64,
+    /// Sequence of the last covered record.
+    sequence: u64,
+    /// CRC32 of the file up to its trailer; `None` for v1 snapshots.
+    checksum: Option<u32>,
+}
+
 /// Read a checkpoint snapshot: the current-locations map plus the log byte
-/// length it covers. Returns `None` (→ safe full replay) if the snapshot is
-/// absent, has a malformed header, or contains *any* corrupt record — never a
-/// partial or wrong state.
-fn read_snapshot(path: &Path) -> Option<(std::collections::HashMap<String, LocationUpdate>, u64)> {
-    use std::collections::HashMap;
+/// length it covers and the sequence of the last covered record. Returns `None`
+/// (→ safe full replay) if the snapshot is absent, has a malformed header
+/// (including snapshots written before the header carried a sequence), fails
+/// its checksum, or contains *any* corrupt record — never a partial or wrong
+/// state.
+fn read_snapshot(path: &Path) -> Option<Snapshot> {
+    let content = std::fs::read_to_string(path).ok()?;
+    if let Some(rest) = content.strip_prefix(SNAPSHOT_HEADER_V1_PREFIX) {
+        let (header, records) = rest.split_once('\n').unwrap_or((rest, ""));
+        let [covered_len, sequence] = parse_header_fields(header)?;
+        return Some(Snapshot {
+            objects: parse_snapshot_records(records, false)?,
+            covered_len,
+            sequence,
+            checksum: None,
+        });
+    }
+    let (header, records, checksum) = checked_snapshot_parts(&content, SNAPSHOT_HEADER_PREFIX)?;
+    let [covered_len, sequence] = parse_header_fields(header)?;
+    Some(Snapshot {
+        objects: parse_snapshot_records(records, false)?,
+        covered_len,
+        sequence,
+        checksum: Some(checksum),
+    })
+}
+
+/// Read the incremental checkpoint at `path`, if it is intact and based on
+/// `base`: the objects changed or deleted since `base`, and the log length
+/// and sequence it covers.
+fn read_delta(path: &Path, base: &Snapshot) -> Option<Snapshot> {
     let content = std::fs::read_to_string(path).ok()?;
-    let mut lines = content.lines();
-    let covered_len: u64 = lines
-        .next()?
-        .strip_prefix(SNAPSHOT_HEADER_PREFIX)?
-        .trim()
-        .parse()
-        .ok()?;
-
-    let mut map: HashMap<String, LocationUpdate> = HashMap::new();
-    for line in lines {
+    let (header, records, checksum) = checked_snapshot_parts(&content, DELTA_HEADER_PREFIX)?;
+    let (base_checksum, header) = header.split_once(' ')?;
+    if Some(u32::from_str_radix(base_checksum, 16).ok()?) != base.checksum {
+        return None;
+    }
+    let [covered_len, sequence] = parse_header_fields(header)?;
+    if covered_len < base.covered_len {
+        return None;
+    }
+    Some(Snapshot {
+        objects: parse_snapshot_records(records, true)?,
+        covered_len,
+        sequence,
+        checksum: Some(checksum),
+    })
+}
+
+/// Header fields after `prefix`, the records, and the checksum of a file
+/// with a trailer, if the trailer is there and both its record count and
+/// checksum match.
+fn checked_snapshot_parts<'a>(content: &'a str, prefix: &str) -> Option<(&'a str, &'a str, u32)> {
+    let rest = content.strip_prefix(prefix)?;
+    let body = content.strip_suffix('\n')?;
+    let trailer_start = body.rfind('\n')? + 1;
+    let (count, checksum) = body[trailer_start..]
+        .strip_prefix(SNAPSHOT_TRAILER_PREFIX)?
+        .split_once(' ')?;
+    let count: usize = count.parse().ok()?;
+    let checksum = u32::from_str_radix(checksum, 16).ok()?;
+    if crc32(&content.as_bytes()[..trailer_start]) != checksum {
+        log::warn!("Ignoring checkpoint file that fails its checksum");
+        return None;
+    }
+    let (header, records) = rest[..trailer_start - prefix.len()].split_once('\n')?;
+    if records.lines().filter(|line| !line.is_empty()).count() != count {
+        return None;
+    }
+    Some((header, records, checksum))
+}
+
+/// `N` space-separated decimal fields.
+fn parse_header_fields<const N: usize>(text: &str) -> Option<[u64; N]> {
+    let mut fields = text.trim().split(' ');
+    let mut out = [0; N];
+    for slot in &mut out {
+        *slot = fields.next()?.parse().ok()?;
+    }
+    fields.next().is_none().then_some(out)
+}
+
+/// Objects of snapshot record lines, with tombstones only if `deletions`;
+/// `None` if any line is corrupt.
+fn parse_snapshot_records(
+    records: &str,
+    deletions: bool,
+) -> Option<HashMap<String, Option<LocationUpdate>>> {
+    let mut objects = HashMap::new();
+    for line in records.lines() {
         if line.is_empty() {
             continue;
         }
         // A corrupt record invalidates the whole snapshot (caller full-replays).
-        let body = record_body(line, LogVersion::V2)?;
-        let (timestamp, ns, id, position, metadata) = parse_update_body(body)?;
-        map.insert(
-            format!("{}::{}", ns, id),
-            LocationUpdate {
-                timestamp,
-                position,
-                metadata,
-            },
-        );
+        let body = record_bodies(line, LogVersion::V2)?;
+        match parse_retention_record(&body)? {
+            RetentionRecord::Update {
+                namespace,
+                object_id,
+                update,
+            } => objects.insert(format!("{}::{}", namespace, object_id), Some(update)),
+            RetentionRecord::Tombstone {
+                namespace,
+                object_id,
+                ..
+            } if deletions => objects.insert(format!("{}::{}", namespace, object_id), None),
+            RetentionRecord::Tombstone { .. } => return None,
+        };
+    }
+    Some(objects)
+}
+
+/// The checkpoint recovery starts from: the snapshot beside `log_path`
+/// with its delta applied, if both are intact and cover no more than the
+/// `log_len` bytes the log has (a longer one is stale).
+fn read_checkpoint(log_path: &Path, log_len: u64) -> Option<Snapshot> {
+    let mut snapshot = read_snapshot(&snapshot_path_for(log_path))?;
+    if snapshot.covered_len > log_len {
+        return None;
+    }
+    if let Some(delta) = read_delta(&delta_path_for(log_path), &snapshot)
+        && delta.covered_len <= log_len
+    {
+        snapshot.objects.extend(delta.objects);
+        snapshot.covered_len = delta.covered_len;
+        snapshot.sequence = delta.sequence;
     }
-    Some((map, covered_len))
+    Some(snapshot)
 }
 
-/// Atomically write a checkpoint snapshot: temp file → fsync → rename → fsync
-/// parent dir, so a crash never leaves a half-written or stale-but-trusted snapshot.
+/// Write a checkpoint snapshot atomically (see [`write_atomically`]),
+/// returning its checksum.
 fn write_snapshot(
     path: &Path,
-    state: &std::collections::HashMap<String, LocationUpdate>,
+    state: &HashMap<String, LocationUpdate>,
     covered_len: u64,
+    sequence: u64,
+) -> Result<u32> {
+    let header = format!("{}{} {}", SNAPSHOT_HEADER_PREFIX, covered_len, sequence);
+    write_checked(
+        path,
+        &header,
+        state.iter().map(|(key, update)| (key, Some(update))),
+    )
+}
+
+/// Write an incremental checkpoint of `changes` since the snapshot with
+/// checksum `base`, returning its own checksum.
+fn write_delta(
+    path: &Path,
+    base: u32,
+    changes: &HashMap<String, Option<LocationUpdate>>,
+    covered_len: u64,
+    sequence: u64,
+) -> Result<u32> {
+    let header = format!(
+        "{}{:08x} {} {}",
+        DELTA_HEADER_PREFIX, base, covered_len, sequence
+    );
+    write_checked(
+        path,
+        &header,
+        changes.iter().map(|(key, update)| (key, update.as_ref())),
+    )
+}
+
+/// Write `header`, a record per object (a tombstone for `None`) and a
+/// trailer with the record count and the checksum of everything before it.
+fn write_checked<'a>(
+    path: &Path,
+    header: &str,
+    objects: impl Iterator<Item = (&'a String, Option<&'a LocationUpdate>)>,
+) -> Result<u32> {
+    write_with_trailer(
+        path,
+        header,
+        objects.map(|(key, update)| {
+            // Keys are validated delimiter-free, so the first "::" splits ns/id.
+            let (ns, id) = key.split_once("::").unwrap_or((key.as_str(), ""));
+            let body = match update {
+                Some(update) => {
+                    let micros = micros_since_epoch(update.timestamp);
+                    format_update_body(micros, ns, id, &update.position, &update.metadata)
+                }
+                // Recovery needs only the key of a deleted object.
+                None => format!("TOMBSTONE|0|{}|{}", ns, id),
+            };
+            format!("{:08x}|{}", crc32(body.as_bytes()), body)
+        }),
+    )
+}
+
+/// Write `header`, `lines` and a trailer with the line count and the
+/// checksum of everything before it (see [`checked_snapshot_parts`]),
+/// returning the checksum.
+fn write_with_trailer(
+    path: &Path,
+    header: &str,
+    lines: impl Iterator<Item = String>,
+) -> Result<u32> {
+    let mut checksum = 0;
+    write_atomically(path, |w| {
+        let mut crc = Crc32::new();
+        let mut count = 0usize;
+        for line in std::iter::once(header.to_string()).chain(lines) {
+            let line = line + "\n";
+            crc.update(line.as_bytes());
+            w.write_all(line.as_bytes())?;
+            count += 1;
+        }
+        checksum = crc.finish();
+        writeln!(
+            w,
+            "{}{} {:08x}",
+            SNAPSHOT_TRAILER_PREFIX,
+            count - 1,
+            checksum
+        )?;
+        Ok(())
+    })?;
+    Ok(checksum)
+}
+
+const SPANS_HEADER_PREFIX: &str = "#spatio-spans v1 ";
+
+/// Path of the spans of a sealed log segment (`<segment>.tidx`).
+fn spans_path_for(segment: &Path) -> std::path::PathBuf {
+    let mut s = segment.as_os_str().to_os_string();
+    s.push(".tidx");
+    std::path::PathBuf::from(s)
+}
+
+/// The time span of each object's updates in the sealed log segment at
+/// `segment`, as saved by [`write_spans`]. `None` if the file is missing,
+/// fails its checksum, or was saved for a segment of another length, which
+/// leaves the segment to be scanned.
+fn read_spans(segment: &Path, len: u64) -> Option<HashMap<String, (SystemTime, SystemTime)>> {
+    let content = std::fs::read_to_string(spans_path_for(segment)).ok()?;
+    let (header, records, _) = checked_snapshot_parts(&content, SPANS_HEADER_PREFIX)?;
+    if parse_header_fields(header)? != [len] {
+        return None;
+    }
+    let time = |micros: &str| Some(UNIX_EPOCH + Duration::from_micros(micros.parse().ok()?));
+    let mut spans = HashMap::new();
+    for line in records.lines().filter(|line| !line.is_empty()) {
+        let mut fields = line.rsplitn(3, '|');
+        let newest = time(fields.next()?)?;
+        let oldest = time(fields.next()?)?;
+        spans.insert(fields.next()?.to_string(), (oldest, newest));
+    }
+    Some(spans)
+}
+
+/// Save `spans` beside the sealed log segment at `segment`, `len` bytes
+/// long, as `key|oldest|newest` lines (in microseconds since the epoch).
+fn write_spans(
+    segment: &Path,
+    len: u64,
+    spans: &HashMap<String, (SystemTime, SystemTime)>,
+) -> Result<()> {
+    let header = format!("{}{}", SPANS_HEADER_PREFIX, len);
+    write_with_trailer(
+        &spans_path_for(segment),
+        &header,
+        spans.iter().map(|(key, (oldest, newest))| {
+            format!(
+                "{}|{}|{}",
+                key,
+                micros_since_epoch(*oldest),
+                micros_since_epoch(*newest)
+            )
+        }),
+    )?;
+    Ok(())
+}
+
+/// Remove the file at `path` if there is one.
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Write a file through `write`: temp file → fsync → rename → fsync parent
+/// dir, so a crash never leaves a half-written or stale-but-trusted file.
+fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<()>,
 ) -> Result<()> {
     let mut tmp = path.as_os_str().to_os_string();
     tmp.push(".tmp");
@@ -493,16 +2544,9 @@ fn write_snapshot(
     {
         let file = File::create(&tmp)?;
         let mut w = BufWriter::new(file);
-        writeln!(w, "{}{}", SNAPSHOT_HEADER_PREFIX, covered_len)?;
-        for (key, update) in state {
-            // Keys are validated delimiter-free, so the first "::" splits ns/id.
-            let (ns, id) = key.split_once("::").unwrap_or((key.as_str(), ""));
-            let micros = micros_since_epoch(update.timestamp);
-            let body = format_update_body(micros, ns, id, &update.position, &update.metadata);
-            write_record(&mut w, LogVersion::V2, &body)?;
-        }
+        write(&mut w)?;
         w.flush()?;
-        w.get_ref().sync_all()?;
+        fsync::sync_file(w.get_ref(), SyncMode::All)?;
     }
 
     std::fs::rename(&tmp, path)?;
@@ -510,39 +2554,105 @@ fn write_snapshot(
     Ok(())
 }
 
+const INDEX_SNAPSHOT_HEADER: &[u8] = b"#spatio-idx v2\n";
+
+/// How many bytes of log [`TrajectoryLog::replay`] reads between progress
+/// reports.
+const REPLAY_PROGRESS_BYTES: u64 = 4 << 20;
+
+/// Path of the saved spatial indexes beside a log file (`<log>.idx`).
+fn index_snapshot_path_for(log_path: &Path) -> std::path::PathBuf {
+    let mut s = log_path.as_os_str().to_os_string();
+    s.push(".idx");
+    std::path::PathBuf::from(s)
+}
+
 /// A point-in-time view of the file-backed log, captured under the log lock:
-/// the path, on-disk format, and the byte length to scan. Bounding reads to
-/// `len` keeps a concurrent writer's appended tail (possibly a half-written
-/// final line) out of the scan.
+/// the segment paths, on-disk format, and the byte length to scan. Bounding
+/// reads to `len` keeps a concurrent writer's appended tail (possibly a
+/// half-written final line) out of the scan.
 struct FileScanTarget {
-    path: std::path::PathBuf,
+    paths: Vec<std::path::PathBuf>,
     version: LogVersion,
     len: u64,
 }
 
-/// Scan a file-backed log for an object's updates within `[start, end]`,
-/// skipping `exclude`d timestamps. A free function so it can run *without* the
-/// log lock held (the [`FileScanTarget`] is captured under the lock first).
-/// Reading stops at `target.len` — the stable prefix that existed at capture
-/// time — so concurrent appends past it never present a torn line.
-fn scan_file(
+/// Read the update records at `positions` (see [`file_position`]) of a
+/// file-backed log, in the given order, skipping `exclude`d timestamps and
+/// stopping after `limit`. A free function so it can run *without* the log
+/// lock held (the segments are opened under the lock first).
+fn read_file_updates(
+    reader: SegmentReader,
     target: &FileScanTarget,
-    namespace: &str,
-    object_id: &str,
-    start_time: SystemTime,
-    end_time: SystemTime,
-    exclude: &std::collections::HashSet<SystemTime>,
+    positions: &[u64],
+    exclude: &HashSet<SystemTime>,
+    limit: usize,
 ) -> Result<Vec<LocationUpdate>> {
-    let FileScanTarget { path, version, len } = target;
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    // The bodies of the last line read, since a block's records tend to be
+    // read one after another.
+    let mut bodies: Option<(u64, String)> = None;
+    let mut out = Vec::new();
+    for &position in positions {
+        if out.len() >= limit {
+            break;
+        }
+        let (offset, slot) = split_file_position(position);
+        if offset >= target.len {
+            continue;
+        }
+        if bodies.as_ref().is_none_or(|(read, _)| *read != offset) {
+            reader.seek(SeekFrom::Start(offset))?;
+            line.clear();
+            reader.read_until(b'\n', &mut line)?;
+            bodies = std::str::from_utf8(&line)
+                .ok()
+                .and_then(|line| record_bodies(line.trim_end_matches(['\n', '\r']), target.version))
+                .map(|bodies| (offset, bodies.into_owned()));
+        }
+        let Some(body) = bodies
+            .as_ref()
+            .and_then(|(_, bodies)| bodies.split('\n').nth(slot))
+        else {
+            continue;
+        };
+        let Some((timestamp, _, _, position, metadata)) = parse_update_body(body) else {
+            continue;
+        };
+        if exclude.contains(&timestamp) {
+            continue;
+        }
+        out.push(LocationUpdate {
+            timestamp,
+            position,
+            metadata,
+        });
+    }
+    Ok(out)
+}
+
+/// Call `visit` with the namespace, object ID and contents of every update
+/// record in a file-backed log, in log order. Reading stops at `target.len` —
+/// the stable prefix that existed at capture time — so concurrent appends past
+/// it never present a torn line.
+fn visit_file_updates(
+    target: &FileScanTarget,
+    mut visit: impl FnMut(&str, &str, LocationUpdate),
+) -> Result<()> {
+    let FileScanTarget {
+        paths,
+        version,
+        len,
+    } = target;
     let (version, len) = (*version, *len);
-    let mut out: Vec<LocationUpdate> = Vec::new();
-    if !path.exists() || len == 0 {
-        return Ok(out);
+    if len == 0 {
+        return Ok(());
     }
-    let file = File::open(path)?;
+    let log = SegmentReader::open(paths)?;
     // Bound the read to the prefix captured under the lock; anything appended
     // afterwards (a possibly half-written final line) is intentionally ignored.
-    let reader = std::io::BufReader::new(std::io::Read::take(file, len));
+    let reader = std::io::BufReader::new(std::io::Read::take(log, len));
 
     for line_result in std::io::BufRead::lines(reader) {
         let line = match line_result {
@@ -552,55 +2662,486 @@ fn scan_file(
 
         // Strip the version header and verify the per-record CRC (V2); corrupt
         // / torn / tombstone lines are skipped by the parser.
-        let Some(body) = record_body(&line, version) else {
+        let Some(bodies) = record_bodies(&line, version) else {
             continue;
         };
-        let Some((timestamp, ns, id, position, metadata)) = parse_update_body(body) else {
+        for body in bodies.split('\n') {
+            let Some((timestamp, ns, id, position, metadata)) = parse_update_body(body) else {
+                continue;
+            };
+            visit(
+                ns,
+                id,
+                LocationUpdate {
+                    timestamp,
+                    position,
+                    metadata,
+                },
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Call `visit` with every record — updates and tombstones — of a file-backed
+/// log, in log order, reading up to `target.len` as [`visit_file_updates`]
+/// does.
+fn visit_file_records(
+    target: &FileScanTarget,
+    mut visit: impl FnMut(u64, RetentionRecord<'_>),
+) -> Result<()> {
+    let FileScanTarget {
+        paths,
+        version,
+        len,
+    } = target;
+    let (version, len) = (*version, *len);
+    if len == 0 {
+        return Ok(());
+    }
+    let log = SegmentReader::open(paths)?;
+    let reader = std::io::BufReader::new(std::io::Read::take(log, len));
+    let mut numbering = Numbering::after(0);
+    for line in std::io::BufRead::lines(reader).map_while(std::io::Result::ok) {
+        let Some(bodies) = record_bodies(&line, version) else {
+            if lost_records(&line, version) {
+                numbering.lose_line();
+            }
             continue;
         };
-        if ns != namespace || id != object_id {
-            continue;
+        for body in bodies.split('\n') {
+            if let Some(sequence) = numbering.number(body)
+                && let Some(record) = parse_retention_record(body)
+            {
+                visit(sequence, record);
+            }
         }
-        if exclude.contains(&timestamp) {
+    }
+    Ok(())
+}
+
+/// Result of [`ColdState::verify_log`].
+pub(crate) struct LogCheck {
+    /// Records replayed.
+    pub records: u64,
+    /// Records skipped as corrupt.
+    pub corrupt: u64,
+    /// Latest location of every live object, keyed `namespace::object_id`.
+    pub latest: HashMap<String, LocationUpdate>,
+}
+
+/// Records of a file-backed log that fail their checksum or don't parse.
+fn count_corrupt_records(target: &FileScanTarget) -> Result<u64> {
+    let FileScanTarget {
+        paths,
+        version,
+        len,
+    } = target;
+    if *len == 0 {
+        return Ok(0);
+    }
+    let log = SegmentReader::open(paths)?;
+    let reader = std::io::BufReader::new(std::io::Read::take(log, *len));
+    let mut corrupt = 0;
+    for line in std::io::BufRead::lines(reader).map_while(std::io::Result::ok) {
+        if line.is_empty() || (*version == LogVersion::V2 && line.starts_with('#')) {
             continue;
         }
-        if timestamp < start_time || timestamp > end_time {
-            continue;
+        match record_bodies(&line, *version) {
+            Some(bodies) => {
+                let unparsed = bodies
+                    .split('\n')
+                    .filter(|body| {
+                        parse_retention_record(body).is_none()
+                            && parse_sequence_marker(body).is_none()
+                    })
+                    .count();
+                corrupt += unparsed as u64;
+            }
+            // A block that fails its checksum counts once, since how many
+            // records it held is lost with it.
+            None => corrupt += 1,
         }
+    }
+    Ok(corrupt)
+}
 
-        out.push(LocationUpdate {
-            timestamp,
-            position,
-            metadata,
+/// Replays records of one namespace up to a point in time, resolving each
+/// object as recovery does: the newest update wins, and a tombstone clears the
+/// object until a later record revives it. Records stamped after the point in
+/// time are skipped.
+struct StateAt<'a> {
+    namespace: &'a str,
+    at: SystemTime,
+    objects: HashMap<String, Option<LocationUpdate>>,
+}
+
+impl StateAt<'_> {
+    fn apply(&mut self, record: RetentionRecord<'_>) {
+        match record {
+            RetentionRecord::Update {
+                namespace,
+                object_id,
+                update,
+            } if namespace == self.namespace && update.timestamp <= self.at => {
+                let slot = self.objects.entry(object_id.to_string()).or_insert(None);
+                if slot
+                    .as_ref()
+                    .is_none_or(|current| update.timestamp > current.timestamp)
+                {
+                    *slot = Some(update);
+                }
+            }
+            RetentionRecord::Tombstone {
+                namespace,
+                object_id,
+                deleted_at,
+            } if namespace == self.namespace && deleted_at <= self.at => {
+                self.objects.insert(object_id.to_string(), None);
+            }
+            _ => {}
+        }
+    }
+
+    fn into_locations(self) -> HashMap<String, LocationUpdate> {
+        self.objects
+            .into_iter()
+            .filter_map(|(id, update)| Some((id, update?)))
+            .collect()
+    }
+}
+
+/// Where an object was during a dwell window: whether it was inside at its
+/// last point before the window, at each point in it (in log order), and at
+/// its first point after it.
+#[derive(Default)]
+struct DwellTrack {
+    before: Option<(SystemTime, bool)>,
+    during: Vec<(SystemTime, bool)>,
+    after: Option<(SystemTime, bool)>,
+}
+
+impl DwellTrack {
+    /// Note a point at `at`. `inside` is only asked for points that replace
+    /// the track's current ones.
+    fn record(
+        &mut self,
+        at: SystemTime,
+        start: SystemTime,
+        end: SystemTime,
+        inside: impl FnOnce() -> bool,
+    ) {
+        if at < start {
+            if self.before.is_none_or(|(prev, _)| at >= prev) {
+                self.before = Some((at, inside()));
+            }
+        } else if at > end {
+            if self.after.is_none_or(|(next, _)| at < next) {
+                self.after = Some((at, inside()));
+            }
+        } else {
+            self.during.push((at, inside()));
+        }
+    }
+
+    /// Total time inside, clipped to `[start, end]`.
+    fn total(mut self, start: SystemTime, end: SystemTime) -> Duration {
+        // Stable, so a tombstone logged after an update with the same stamp
+        // still comes second.
+        self.during.sort_by_key(|(at, _)| *at);
+        let points: Vec<_> = self
+            .before
+            .into_iter()
+            .chain(self.during)
+            .chain(self.after)
+            .collect();
+        points
+            .windows(2)
+            .filter(|pair| pair[0].1)
+            .filter_map(|pair| {
+                let (from, to) = (pair[0].0.max(start), pair[1].0.min(end));
+                to.duration_since(from).ok()
+            })
+            .sum()
+    }
+}
+
+/// Replays records of one namespace into per-object [`DwellTrack`]s. Points
+/// are tested against the area as they are read; tombstones count as leaving
+/// it.
+struct DwellTimes<'a, F> {
+    namespace: &'a str,
+    start: SystemTime,
+    end: SystemTime,
+    inside: F,
+    objects: HashMap<String, DwellTrack>,
+}
+
+impl<F: Fn(&Point3d) -> bool> DwellTimes<'_, F> {
+    fn apply(&mut self, record: RetentionRecord<'_>) {
+        let (object_id, at, position) = match record {
+            RetentionRecord::Update {
+                namespace,
+                object_id,
+                update,
+            } if namespace == self.namespace => {
+                (object_id, update.timestamp, Some(update.position))
+            }
+            RetentionRecord::Tombstone {
+                namespace,
+                object_id,
+                deleted_at,
+            } if namespace == self.namespace => (object_id, deleted_at, None),
+            _ => return,
+        };
+        let track = match self.objects.get_mut(object_id) {
+            Some(track) => track,
+            None => self.objects.entry(object_id.to_string()).or_default(),
+        };
+        let inside = &self.inside;
+        track.record(at, self.start, self.end, || {
+            position.is_some_and(|position| inside(&position))
         });
     }
 
-    Ok(out)
+    fn into_totals(self) -> BTreeMap<String, Duration> {
+        let (start, end) = (self.start, self.end);
+        self.objects
+            .into_iter()
+            .map(|(id, track)| (id, track.total(start, end)))
+            .filter(|(_, total)| !total.is_zero())
+            .collect()
+    }
 }
 
 /// A single record in the in-memory trajectory log (memory-mode DBs).
 #[derive(Clone)]
 enum MemRecord {
     Update {
+        sequence: u64,
         namespace: String,
         object_id: String,
         update: LocationUpdate,
     },
     Tombstone {
+        sequence: u64,
         namespace: String,
         object_id: String,
+        deleted_at: SystemTime,
     },
 }
 
+impl MemRecord {
+    fn sequence(&self) -> u64 {
+        match self {
+            MemRecord::Update { sequence, .. } | MemRecord::Tombstone { sequence, .. } => *sequence,
+        }
+    }
+
+    fn as_retention(&self) -> RetentionRecord<'_> {
+        match self {
+            MemRecord::Update {
+                namespace,
+                object_id,
+                update,
+                ..
+            } => RetentionRecord::Update {
+                namespace,
+                object_id,
+                update: update.clone(),
+            },
+            MemRecord::Tombstone {
+                namespace,
+                object_id,
+                deleted_at,
+                ..
+            } => RetentionRecord::Tombstone {
+                namespace,
+                object_id,
+                deleted_at: *deleted_at,
+            },
+        }
+    }
+}
+
+/// Where each object's update records sit in the trajectory log, by
+/// timestamp, so a trajectory query reads only the records in its time range
+/// instead of scanning the whole log.
+///
+/// The sealed segments of a segmented log come with the time span of each
+/// object's updates (see [`read_spans`]), so their records are only indexed
+/// once a query asks for an object and time range they hold.
+#[derive(Default)]
+struct TimeIndex {
+    /// `namespace::object_id` -> `(timestamp, position)` of each update. The
+    /// position is a [`file_position`] in file logs and a record index in
+    /// memory logs.
+    objects: HashMap<String, BTreeSet<(SystemTime, u64)>>,
+    /// Sealed segments whose updates aren't in `objects` yet.
+    unloaded: Vec<UnloadedSegment>,
+}
+
+/// A sealed log segment known to the [`TimeIndex`] only by its spans.
+struct UnloadedSegment {
+    path: std::path::PathBuf,
+    /// Offset of the segment in the log, and its length.
+    start: u64,
+    len: u64,
+    /// `namespace::object_id` -> oldest and newest update in the segment.
+    spans: HashMap<String, (SystemTime, SystemTime)>,
+}
+
+impl UnloadedSegment {
+    /// Whether the segment may hold updates of `key` within `[start, end]`.
+    fn holds(&self, key: &str, start: SystemTime, end: SystemTime) -> bool {
+        self.spans
+            .get(key)
+            .is_some_and(|&(oldest, newest)| oldest <= end && newest >= start)
+    }
+}
+
+impl TimeIndex {
+    fn insert(&mut self, namespace: &str, object_id: &str, timestamp: SystemTime, position: u64) {
+        self.objects
+            .entry(format!("{}::{}", namespace, object_id))
+            .or_default()
+            .insert((timestamp, position));
+    }
+
+    /// Index the update records of the log segment at `path`, which starts
+    /// at `start` in the log and is `len` bytes long, returning the time
+    /// span of each object's updates in it.
+    fn index_segment(
+        &mut self,
+        path: &Path,
+        start: u64,
+        len: u64,
+        version: LogVersion,
+    ) -> Result<HashMap<String, (SystemTime, SystemTime)>> {
+        let mut spans: HashMap<String, (SystemTime, SystemTime)> = HashMap::new();
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(spans),
+            Err(e) => return Err(e.into()),
+        };
+        let mut reader = BufReader::new(std::io::Read::take(file, len));
+        let mut line = Vec::new();
+        let mut offset = start;
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 {
+                break;
+            }
+            let record_offset = offset;
+            offset += read as u64;
+            let Some(bodies) = std::str::from_utf8(&line)
+                .ok()
+                .and_then(|line| record_bodies(line.trim_end_matches(['\n', '\r']), version))
+            else {
+                continue;
+            };
+            for (slot, body) in bodies.split('\n').enumerate() {
+                let Some((timestamp, namespace, object_id, ..)) = parse_update_body(body) else {
+                    continue;
+                };
+                let position = file_position(record_offset, slot);
+                self.insert(namespace, object_id, timestamp, position);
+                spans
+                    .entry(format!("{}::{}", namespace, object_id))
+                    .and_modify(|(oldest, newest)| {
+                        *oldest = (*oldest).min(timestamp);
+                        *newest = (*newest).max(timestamp);
+                    })
+                    .or_insert((timestamp, timestamp));
+            }
+        }
+        Ok(spans)
+    }
+
+    /// Index the unloaded segments that may hold updates of `key` within
+    /// `[start, end]`.
+    fn load_segments(
+        &mut self,
+        key: &str,
+        start: SystemTime,
+        end: SystemTime,
+        version: LogVersion,
+    ) -> Result<()> {
+        let mut i = 0;
+        while i < self.unloaded.len() {
+            if self.unloaded[i].holds(key, start, end) {
+                let segment = self.unloaded.swap_remove(i);
+                self.index_segment(&segment.path, segment.start, segment.len, version)?;
+            } else {
+                i += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Index the sealed segments holding updates from `since` on, returning
+    /// how many were read and their bytes.
+    fn load_since(&mut self, since: SystemTime, version: LogVersion) -> Result<(usize, u64)> {
+        let (mut segments, mut bytes) = (0, 0);
+        let mut i = 0;
+        while i < self.unloaded.len() {
+            if self.unloaded[i]
+                .spans
+                .values()
+                .any(|&(_, newest)| newest >= since)
+            {
+                let segment = self.unloaded.swap_remove(i);
+                self.index_segment(&segment.path, segment.start, segment.len, version)?;
+                segments += 1;
+                bytes += segment.len;
+            } else {
+                i += 1;
+            }
+        }
+        Ok((segments, bytes))
+    }
+
+    /// Positions of up to `max` updates of `key` within `[start, end]`,
+    /// newest first.
+    fn newest_first(&self, key: &str, start: SystemTime, end: SystemTime, max: usize) -> Vec<u64> {
+        match self.objects.get(key) {
+            Some(updates) if start <= end => updates
+                .range((start, 0)..=(end, u64::MAX))
+                .rev()
+                .take(max)
+                .map(|&(_, position)| position)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
 /// Storage backend for the trajectory log.
 ///
 /// File-backed databases serialize records to a durable append-only text log;
 /// `:memory:` databases keep parsed records in memory and never touch the
 /// filesystem.
+// A database has one log, so the size of the file variant doesn't matter.
+#[allow(clippy::large_enum_variant)]
 enum LogBackend {
     File {
-        writer: BufWriter<File>,
+        writer: RecordWriter<LogWriter>,
+        /// Second handle to the segment being written, so an `fsync` can run
+        /// without the log lock (see [`TrajectoryLog::begin_sync`]).
+        sync_file: Arc<File>,
         path: std::path::PathBuf,
+        /// Numbers of the log's segments (see [`segments`]), the last one
+        /// being written.
+        segments: Vec<u64>,
+        /// Offset in the log where the segment being written starts.
+        segment_start: u64,
+        /// Seconds of writes per segment, if the log is split.
+        segment_secs: Option<u64>,
+        /// Time bucket (`secs since the epoch / segment_secs`) of the segment
+        /// being written.
+        segment_bucket: u64,
         /// Records buffered in `writer` that have not yet been pushed to the OS.
         pending_writes: usize,
         /// Records written since the last `fsync`.
@@ -609,8 +3150,6 @@ enum LogBackend {
         last_sync: Instant,
         buffer_limit: usize,
         sync: SyncSettings,
-        /// On-disk format of this log (V2 for new files, V1 for legacy logs).
-        version: LogVersion,
     },
     Memory {
         records: Vec<MemRecord>,
@@ -620,56 +3159,134 @@ enum LogBackend {
 /// Trajectory log: durable file-backed log or an in-memory log.
 struct TrajectoryLog {
     backend: LogBackend,
+    /// Sequence of the last appended record. Replay numbers the records
+    /// read back the same way (see [`SEQUENCE_MARKER`]), so numbering
+    /// continues across restarts.
+    sequence: u64,
+    watermark: Arc<DurabilityWatermark>,
+    /// Built by the first trajectory query that reaches the log and kept
+    /// current by appends; dropped when a compaction moves records.
+    index: Option<TimeIndex>,
+    /// Set when the log turns out not to be writable; appends then only
+    /// advance the sequence (see [`ColdState::degraded`]).
+    degraded: Arc<OnceLock<String>>,
+    /// Time taken by each `fsync` of the log, wherever it runs.
+    sync_latency: Arc<Mutex<LatencyHistogram>>,
+    /// Opened read-only: appends and compactions fail, and nothing is synced.
+    read_only: bool,
 }
 
 impl TrajectoryLog {
-    fn open_file(path: &Path, buffer_limit: usize, sync: SyncSettings) -> Result<Self> {
-        let existing_len = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    fn open_file(
+        path: &Path,
+        config: &PersistenceConfig,
+        sync: SyncSettings,
+        watermark: Arc<DurabilityWatermark>,
+    ) -> Result<Self> {
+        let segments = segments::list(path)?;
+        let segment_paths = segments::paths(path, &segments);
+        let active = segment_paths.last().expect("segment 0 is always listed");
+        let existing_len = std::fs::metadata(active).map(|m| m.len()).unwrap_or(0);
+        let segment_start = segments::total_len(&segment_paths) - existing_len;
+
+        // Detect the format of an existing log, which keeps the compression
+        // it was created with; brand-new logs are V2.
+        let first = segment_paths
+            .iter()
+            .find(|path| std::fs::metadata(path).is_ok_and(|m| m.len() > 0));
+        let (version, compression) = match first {
+            None => (LogVersion::V2, config.compression),
+            Some(first) => {
+                let mut first_line = String::new();
+                let probe = File::open(first)?;
+                std::io::BufRead::read_line(&mut std::io::BufReader::new(probe), &mut first_line)?;
+                parse_log_header(&first_line)
+            }
+        };
+
+        // A segment keeps being written until its time bucket has passed,
+        // across restarts.
+        let segment_bucket = config.segment_secs.map_or(0, |secs| {
+            let written = std::fs::metadata(active)
+                .and_then(|m| m.modified())
+                .ok()
+                .filter(|_| existing_len > 0)
+                .unwrap_or_else(SystemTime::now);
+            secs_since_epoch(written) / secs
+        });
 
-        // Detect the format of an existing log; brand-new logs are V2.
-        let version = if existing_len == 0 {
-            LogVersion::V2
+        let degraded = Arc::new(OnceLock::new());
+        let opened = if config.read_only {
+            File::open(active)
         } else {
-            let mut first_line = String::new();
-            let probe = File::open(path)?;
-            std::io::BufRead::read_line(&mut std::io::BufReader::new(probe), &mut first_line)?;
-            if first_line.trim_end_matches(['\n', '\r']) == LOG_HEADER_V2 {
-                LogVersion::V2
-            } else {
-                LogVersion::V1
+            OpenOptions::new().create(true).append(true).open(active)
+        };
+        let file = match opened {
+            Ok(file) => file,
+            Err(e) if storage_unavailable(&e) => {
+                degrade(&degraded, &e);
+                // Still recover and serve what the log holds.
+                match File::open(active) {
+                    Ok(file) => file,
+                    Err(_) => {
+                        let mut log = Self::open_memory(watermark);
+                        log.degraded = degraded;
+                        return Ok(log);
+                    }
+                }
             }
+            Err(e) => return Err(e.into()),
         };
-
-        let file = OpenOptions::new().create(true).append(true).open(path)?;
-        if existing_len == 0 {
+        let writable = degraded.get().is_none() && !config.read_only;
+        if existing_len == 0 && writable {
             // Make the newly created file's directory entry durable.
-            sync_parent_dir(path);
+            sync_parent_dir(active);
         }
-        let mut writer = BufWriter::new(file);
-        if existing_len == 0 {
+        let sync_file = Arc::new(file.try_clone()?);
+        let writer = if existing_len == 0 && writable {
             // Stamp the version header so later opens parse this log as V2.
-            writeln!(writer, "{}", LOG_HEADER_V2)?;
-        }
+            RecordWriter::create(LogWriter::new(file), segment_start, 0, version, compression)?
+        } else {
+            // Numbered once recovery has read the log (see `resume_sequence`).
+            let len = segment_start + existing_len;
+            RecordWriter::new(LogWriter::new(file), len, 0, version, compression)
+        };
 
         Ok(Self {
             backend: LogBackend::File {
                 writer,
+                sync_file,
                 path: path.to_path_buf(),
+                segments,
+                segment_start,
+                segment_secs: config.segment_secs,
+                segment_bucket,
                 pending_writes: 0,
                 writes_since_sync: 0,
                 last_sync: Instant::now(),
-                buffer_limit,
+                buffer_limit: config.buffer_size,
                 sync,
-                version,
             },
+            sequence: 0,
+            watermark,
+            index: None,
+            degraded,
+            sync_latency: Arc::default(),
+            read_only: config.read_only,
         })
     }
 
-    fn open_memory() -> Self {
+    fn open_memory(watermark: Arc<DurabilityWatermark>) -> Self {
         Self {
             backend: LogBackend::Memory {
                 records: Vec::new(),
             },
+            sequence: 0,
+            watermark,
+            index: None,
+            degraded: Arc::default(),
+            sync_latency: Arc::default(),
+            read_only: false,
         }
     }
 
@@ -679,7 +3296,37 @@ impl TrajectoryLog {
     /// `force` is set on explicit flush/close/drop: it triggers an `fsync`
     /// regardless of batch/interval thresholds (unless the policy is
     /// [`SyncPolicy::Never`], which never syncs). A no-op for memory logs.
+    ///
+    /// A read-only or full disk degrades the log rather than failing the
+    /// write that found it; a forced sync fails from then on.
     fn maybe_sync(&mut self, force: bool) -> Result<()> {
+        self.sync_with(force, false)
+    }
+
+    /// Flush and `fsync` the log whatever the [`SyncPolicy`], even
+    /// [`SyncPolicy::Never`].
+    fn fsync(&mut self) -> Result<()> {
+        self.sync_with(true, true)
+    }
+
+    fn sync_with(&mut self, force: bool, any_policy: bool) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        if self.degraded.get().is_none() {
+            match self.sync_to_disk(force, any_policy) {
+                Err(SpatioError::Io(e)) if storage_unavailable(&e) => degrade(&self.degraded, &e),
+                result => return result,
+            }
+        }
+        match self.degraded.get() {
+            Some(reason) if force => Err(SpatioError::DegradedPersistence(reason.clone())),
+            _ => Ok(()),
+        }
+    }
+
+    fn sync_to_disk(&mut self, force: bool, any_policy: bool) -> Result<()> {
+        let sequence = self.sequence;
         let LogBackend::File {
             writer,
             pending_writes,
@@ -693,21 +3340,29 @@ impl TrajectoryLog {
             return Ok(());
         };
 
-        let fsync = match sync.policy {
-            SyncPolicy::Never => false,
-            SyncPolicy::Always => force || *writes_since_sync >= sync.batch_size,
-            SyncPolicy::EverySecond => force || last_sync.elapsed() >= Duration::from_secs(1),
-        };
+        let fsync = any_policy
+            || match sync.policy {
+                SyncPolicy::Never => false,
+                // Under group commit the writer syncs after releasing the lock.
+                SyncPolicy::Always => {
+                    force || (sync.group_commit.is_none() && *writes_since_sync >= sync.batch_size)
+                }
+                SyncPolicy::EverySecond => force || last_sync.elapsed() >= Duration::from_secs(1),
+                // Periodic syncs happen on the background thread.
+                SyncPolicy::Background => force,
+            };
 
         if fsync {
-            writer.flush()?;
-            match sync.mode {
-                SyncMode::All => writer.get_ref().sync_all()?,
-                SyncMode::Data => writer.get_ref().sync_data()?,
-            }
+            db_span!("spatio.fsync", sequence);
+            let started = Instant::now();
+            writer.sync(sync.mode)?;
+            self.sync_latency
+                .lock()
+                .observe(started.elapsed().as_secs_f64());
             *pending_writes = 0;
             *writes_since_sync = 0;
             *last_sync = Instant::now();
+            self.watermark.advance(sequence);
         } else if force || *pending_writes >= *buffer_limit {
             // Push buffered bytes to the OS even when not syncing, so a clean
             // process exit doesn't lose writes still sitting in the BufWriter.
@@ -718,18 +3373,101 @@ impl TrajectoryLog {
         Ok(())
     }
 
-    fn append(&mut self, namespace: &str, object_id: &str, update: &LocationUpdate) -> Result<()> {
+    /// Start the next segment of a split file log if the time bucket of
+    /// the one being written has passed, degrading the log if the new
+    /// segment can't be created.
+    fn start_due_segment(&mut self) -> Result<()> {
+        let LogBackend::File {
+            segment_secs: Some(secs),
+            segment_bucket,
+            ..
+        } = &self.backend
+        else {
+            return Ok(());
+        };
+        if self.degraded.get().is_some()
+            || secs_since_epoch(SystemTime::now()) / *secs <= *segment_bucket
+        {
+            return Ok(());
+        }
+        match self.start_segment() {
+            Err(e) if storage_unavailable(&e) => {
+                degrade(&self.degraded, &e);
+                Ok(())
+            }
+            result => Ok(result?),
+        }
+    }
+
+    /// Seal the segment being written and start writing the next one.
+    ///
+    /// The sealed segment is synced first (unless the policy never syncs),
+    /// since later syncs only reach the new segment. Records of the sealed
+    /// segment stay in the time index; its spans are saved when an index is
+    /// next built.
+    fn start_segment(&mut self) -> std::io::Result<()> {
+        let LogBackend::File {
+            writer,
+            sync_file,
+            path,
+            segments,
+            segment_start,
+            segment_secs,
+            segment_bucket,
+            pending_writes,
+            sync,
+            ..
+        } = &mut self.backend
+        else {
+            return Ok(());
+        };
+        if sync.policy == SyncPolicy::Never {
+            writer.flush()?;
+        } else {
+            writer.sync(sync.mode)?;
+        }
+        *pending_writes = 0;
+
+        let number = segments.last().map_or(0, |last| last + 1);
+        let segment = segments::segment_path(path, number);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&segment)?;
+        sync_parent_dir(&segment);
+        let (start, sequence) = (writer.len, writer.sequence);
+        let (version, compression) = (writer.version, writer.compression);
+        *sync_file = Arc::new(file.try_clone()?);
+        *writer =
+            RecordWriter::create(LogWriter::new(file), start, sequence, version, compression)?;
+        segments.push(number);
+        *segment_start = start;
+        if let Some(secs) = segment_secs {
+            *segment_bucket = secs_since_epoch(SystemTime::now()) / *secs;
+        }
+        Ok(())
+    }
+
+    /// Append an update record, returning its sequence.
+    fn append(&mut self, namespace: &str, object_id: &str, update: &LocationUpdate) -> Result<u64> {
+        if self.read_only {
+            return Err(SpatioError::ReadOnly);
+        }
+        self.sequence += 1;
+        self.start_due_segment()?;
         match &mut self.backend {
             // Log format (pipe-separated, 8 fields per line):
             //   timestamp_micros|namespace|object_id|lat|lon|alt|json_len|json_metadata
             //
             // Coordinates are written to 6 decimal places (~0.1 m precision).
             // Namespace and object_id must not contain the `|` character.
+            LogBackend::File { .. } if self.degraded.get().is_some() => {
+                return Ok(self.sequence);
+            }
             LogBackend::File {
                 writer,
                 pending_writes,
                 writes_since_sync,
-                version,
                 ..
             } => {
                 let body = format_update_body(
@@ -739,55 +3477,194 @@ impl TrajectoryLog {
                     &update.position,
                     &update.metadata,
                 );
-                write_record(writer, *version, &body)?;
+                let position = match writer.append(self.sequence, &body) {
+                    Err(e) if storage_unavailable(&e) => {
+                        degrade(&self.degraded, &e);
+                        return Ok(self.sequence);
+                    }
+                    result => result?,
+                };
+                if let Some(index) = &mut self.index {
+                    index.insert(namespace, object_id, update.timestamp, position);
+                }
 
                 *pending_writes += 1;
                 *writes_since_sync += 1;
             }
             LogBackend::Memory { records } => {
+                if let Some(index) = &mut self.index {
+                    index.insert(namespace, object_id, update.timestamp, records.len() as u64);
+                }
                 records.push(MemRecord::Update {
+                    sequence: self.sequence,
                     namespace: namespace.to_string(),
                     object_id: object_id.to_string(),
                     update: update.clone(),
                 });
-                return Ok(());
+                if self.degraded.get().is_none() {
+                    self.watermark.advance(self.sequence);
+                }
+                return Ok(self.sequence);
+            }
+        }
+        self.maybe_sync(false)?;
+        Ok(self.sequence)
+    }
+
+    /// Append updates of `namespace` as one batch, returning the sequence of
+    /// the last. A V2 file log writes them as one line, so a crash keeps
+    /// either all of them or none. V1 logs have no batch lines and write one
+    /// line per update, so a crash may keep only the first few.
+    fn append_batch(
+        &mut self,
+        namespace: &str,
+        updates: &[(String, LocationUpdate)],
+    ) -> Result<u64> {
+        if self.read_only {
+            return Err(SpatioError::ReadOnly);
+        }
+        let first = self.sequence + 1;
+        self.sequence += updates.len() as u64;
+        self.start_due_segment()?;
+        match &mut self.backend {
+            LogBackend::File { .. } if self.degraded.get().is_some() => {
+                return Ok(self.sequence);
+            }
+            LogBackend::File {
+                writer,
+                pending_writes,
+                writes_since_sync,
+                ..
+            } => {
+                let bodies: Vec<String> = updates
+                    .iter()
+                    .map(|(object_id, update)| {
+                        format_update_body(
+                            micros_since_epoch(update.timestamp),
+                            namespace,
+                            object_id,
+                            &update.position,
+                            &update.metadata,
+                        )
+                    })
+                    .collect();
+                let positions = match writer.append_batch(first, &bodies) {
+                    Err(e) if storage_unavailable(&e) => {
+                        degrade(&self.degraded, &e);
+                        return Ok(self.sequence);
+                    }
+                    result => result?,
+                };
+                if let Some(index) = &mut self.index {
+                    for ((object_id, update), position) in updates.iter().zip(positions) {
+                        index.insert(namespace, object_id, update.timestamp, position);
+                    }
+                }
+
+                *pending_writes += updates.len();
+                *writes_since_sync += updates.len();
+            }
+            LogBackend::Memory { records } => {
+                for ((object_id, update), sequence) in updates.iter().zip(first..) {
+                    if let Some(index) = &mut self.index {
+                        index.insert(namespace, object_id, update.timestamp, records.len() as u64);
+                    }
+                    records.push(MemRecord::Update {
+                        sequence,
+                        namespace: namespace.to_string(),
+                        object_id: object_id.clone(),
+                        update: update.clone(),
+                    });
+                }
+                if self.degraded.get().is_none() {
+                    self.watermark.advance(self.sequence);
+                }
+                return Ok(self.sequence);
             }
         }
-        self.maybe_sync(false)
+        self.maybe_sync(false)?;
+        Ok(self.sequence)
     }
 
-    fn append_tombstone(&mut self, micros: u128, namespace: &str, object_id: &str) -> Result<()> {
+    /// Append a tombstone record, returning its sequence.
+    fn append_tombstone(&mut self, micros: u128, namespace: &str, object_id: &str) -> Result<u64> {
+        if self.read_only {
+            return Err(SpatioError::ReadOnly);
+        }
+        self.sequence += 1;
+        self.start_due_segment()?;
         match &mut self.backend {
+            LogBackend::File { .. } if self.degraded.get().is_some() => {
+                return Ok(self.sequence);
+            }
             LogBackend::File {
                 writer,
                 pending_writes,
                 writes_since_sync,
-                version,
                 ..
             } => {
                 let body = format!("TOMBSTONE|{}|{}|{}", micros, namespace, object_id);
-                write_record(writer, *version, &body)?;
+                match writer.append(self.sequence, &body) {
+                    Err(e) if storage_unavailable(&e) => {
+                        degrade(&self.degraded, &e);
+                        return Ok(self.sequence);
+                    }
+                    result => result?,
+                };
                 *pending_writes += 1;
                 *writes_since_sync += 1;
             }
             LogBackend::Memory { records } => {
-                // The tombstone's own timestamp is irrelevant to recovery, which
-                // resolves the latest state by append order, so we don't store it.
-                let _ = micros;
+                // Recovery resolves deletions by append order; the deletion
+                // time only matters for point-in-time replays.
                 records.push(MemRecord::Tombstone {
+                    sequence: self.sequence,
                     namespace: namespace.to_string(),
                     object_id: object_id.to_string(),
+                    deleted_at: UNIX_EPOCH
+                        + Duration::from_micros(u64::try_from(micros).unwrap_or(u64::MAX)),
                 });
-                return Ok(());
+                if self.degraded.get().is_none() {
+                    self.watermark.advance(self.sequence);
+                }
+                return Ok(self.sequence);
             }
         }
-        self.maybe_sync(false)
+        self.maybe_sync(false)?;
+        Ok(self.sequence)
     }
 
     fn flush(&mut self) -> Result<()> {
         self.maybe_sync(true)
     }
 
+    /// Push buffered records to the OS and return what an `fsync` outside the
+    /// log lock needs: a handle to the log file and the last sequence the sync
+    /// will cover. `None` when everything appended is already durable, or for
+    /// memory logs.
+    fn begin_sync(&mut self) -> Result<Option<(Arc<File>, u64)>> {
+        let LogBackend::File {
+            writer,
+            sync_file,
+            pending_writes,
+            writes_since_sync,
+            last_sync,
+            ..
+        } = &mut self.backend
+        else {
+            return Ok(None);
+        };
+        if self.sequence <= self.watermark.get() || self.degraded.get().is_some() {
+            return Ok(None);
+        }
+
+        writer.flush()?;
+        *pending_writes = 0;
+        *writes_since_sync = 0;
+        *last_sync = Instant::now();
+        Ok(Some((sync_file.clone(), self.sequence)))
+    }
+
     /// Prepare a file-backed trajectory scan: flush buffered writes to the OS so
     /// a fresh read sees every appended record (including ones already evicted
     /// from the recent buffer), and return the path, version, and the on-disk
@@ -801,18 +3678,24 @@ impl TrajectoryLog {
             LogBackend::File {
                 writer,
                 path,
+                segments,
+                segment_start,
                 pending_writes,
-                version,
                 ..
             } => {
                 // Push to the OS page cache (not a full fsync) so a subsequent
-                // File::open read observes all appended bytes.
-                writer.flush()?;
-                *pending_writes = 0;
-                let len = std::fs::metadata(&*path).map(|m| m.len()).unwrap_or(0);
+                // File::open read observes all appended bytes. A degraded log
+                // has nothing more it can push.
+                if self.degraded.get().is_none() {
+                    writer.flush()?;
+                    *pending_writes = 0;
+                }
+                let paths = segments::paths(path, segments);
+                let active = paths.last().expect("a log has a segment");
+                let len = *segment_start + std::fs::metadata(active).map_or(0, |m| m.len());
                 Ok(Some(FileScanTarget {
-                    path: path.clone(),
-                    version: *version,
+                    paths,
+                    version: writer.version,
                     len,
                 }))
             }
@@ -820,50 +3703,182 @@ impl TrajectoryLog {
         }
     }
 
-    /// Scan the in-memory log (memory backend) for an object's updates in range.
-    fn scan_memory(
-        &self,
-        namespace: &str,
-        object_id: &str,
+    /// Positions of up to `max` updates of `key` within `[start, end]`,
+    /// newest first, building the time index if this is the first query to
+    /// need it. The build reads the log once, under the lock; of a split
+    /// log, only the segment being written and sealed segments without
+    /// saved spans, and later queries read the sealed segments that may hold
+    /// what they ask for.
+    fn indexed_positions(
+        &mut self,
+        key: &str,
         start_time: SystemTime,
         end_time: SystemTime,
-        exclude: &std::collections::HashSet<SystemTime>,
-    ) -> Vec<LocationUpdate> {
-        let LogBackend::Memory { records } = &self.backend else {
-            return Vec::new();
+        max: usize,
+    ) -> Result<Vec<u64>> {
+        let index = match &mut self.index {
+            Some(index) => index,
+            None => {
+                let index = self.build_index()?;
+                self.index.insert(index)
+            }
         };
-        let mut out = Vec::new();
-        for rec in records {
-            let MemRecord::Update {
-                namespace: ns,
-                object_id: id,
-                update,
-            } = rec
-            else {
-                continue;
-            };
-            if ns != namespace || id != object_id {
-                continue;
+        if let LogBackend::File { writer, .. } = &self.backend {
+            index.load_segments(key, start_time, end_time, writer.version)?;
+        }
+        Ok(index.newest_first(key, start_time, end_time, max))
+    }
+
+    /// Build the time index if no query has yet, and index the sealed
+    /// segments holding updates from `since` on, returning the segments and
+    /// bytes read.
+    fn warm_up(&mut self, since: SystemTime) -> Result<(usize, u64)> {
+        let (mut segments, mut bytes) = (0, 0);
+        if self.index.is_none() {
+            let index = self.build_index()?;
+            if let LogBackend::File {
+                path,
+                segments: numbers,
+                ..
+            } = &self.backend
+            {
+                let paths = segments::paths(path, numbers);
+                segments = paths.len() - index.unloaded.len();
+                bytes = segments::total_len(&paths)
+                    .saturating_sub(index.unloaded.iter().map(|s| s.len).sum());
             }
-            if exclude.contains(&update.timestamp) {
-                continue;
+            self.index = Some(index);
+        }
+        let (Some(index), LogBackend::File { writer, .. }) = (&mut self.index, &self.backend)
+        else {
+            return Ok((segments, bytes));
+        };
+        let (loaded, loaded_bytes) = index.load_since(since, writer.version)?;
+        Ok((segments + loaded, bytes + loaded_bytes))
+    }
+
+    fn build_index(&mut self) -> Result<TimeIndex> {
+        let mut index = TimeIndex::default();
+        match &mut self.backend {
+            LogBackend::File {
+                writer,
+                path,
+                segments,
+                pending_writes,
+                ..
+            } => {
+                writer.flush()?;
+                *pending_writes = 0;
+                let version = writer.version;
+                let mut start = 0;
+                for (i, segment) in segments::paths(path, segments).into_iter().enumerate() {
+                    let len = std::fs::metadata(&segment).map_or(0, |m| m.len());
+                    if i + 1 == segments.len() {
+                        index.index_segment(&segment, start, len, version)?;
+                    } else if let Some(spans) = read_spans(&segment, len) {
+                        index.unloaded.push(UnloadedSegment {
+                            path: segment,
+                            start,
+                            len,
+                            spans,
+                        });
+                    } else {
+                        let spans = index.index_segment(&segment, start, len, version)?;
+                        if self.read_only {
+                            // Nothing is written beside a read-only log.
+                        } else if let Err(e) = write_spans(&segment, len, &spans) {
+                            log::warn!("Failed to save spans of {}: {}", segment.display(), e);
+                        }
+                    }
+                    start += len;
+                }
             }
-            if update.timestamp < start_time || update.timestamp > end_time {
-                continue;
+            LogBackend::Memory { records } => {
+                for (position, record) in records.iter().enumerate() {
+                    if let MemRecord::Update {
+                        namespace,
+                        object_id,
+                        update,
+                        ..
+                    } = record
+                    {
+                        index.insert(namespace, object_id, update.timestamp, position as u64);
+                    }
+                }
             }
-            out.push(update.clone());
         }
-        out
+        Ok(index)
+    }
+
+    /// The updates at `positions` of the in-memory log (memory backend), in
+    /// the given order, skipping `exclude`d timestamps and stopping after
+    /// `limit`.
+    fn read_memory_updates(
+        &self,
+        positions: &[u64],
+        exclude: &HashSet<SystemTime>,
+        limit: usize,
+    ) -> Vec<LocationUpdate> {
+        let LogBackend::Memory { records } = &self.backend else {
+            return Vec::new();
+        };
+        positions
+            .iter()
+            .filter_map(|&position| match records.get(position as usize) {
+                Some(MemRecord::Update { update, .. }) if !exclude.contains(&update.timestamp) => {
+                    Some(update.clone())
+                }
+                _ => None,
+            })
+            .take(limit)
+            .collect()
+    }
+
+    /// Every update of `namespace` in the in-memory log (memory backend) within
+    /// `[start, end]`, with its object ID.
+    fn scan_memory_namespace(
+        &self,
+        namespace: &str,
+        start_time: SystemTime,
+        end_time: SystemTime,
+    ) -> Vec<(String, LocationUpdate)> {
+        let LogBackend::Memory { records } = &self.backend else {
+            return Vec::new();
+        };
+        records
+            .iter()
+            .filter_map(|rec| match rec {
+                MemRecord::Update {
+                    namespace: ns,
+                    object_id,
+                    update,
+                    ..
+                } if ns == namespace
+                    && update.timestamp >= start_time
+                    && update.timestamp <= end_time =>
+                {
+                    Some((object_id.clone(), update.clone()))
+                }
+                _ => None,
+            })
+            .collect()
     }
 
     /// Apply log records — starting at byte `from_offset` for file logs, or all
     /// records for memory logs — into `entries`, resolving the latest surviving
     /// update per key (tombstones clear an object; a later update revives it).
+    /// Returns the number of records applied, corrupt lines not counted, and
+    /// the sequence of the last record read, numbering on from `after`, the
+    /// sequence of the last record before `from_offset`.
+    /// File logs pass the bytes read so far to `progress` every
+    /// [`REPLAY_PROGRESS_BYTES`] and once at the end.
     fn replay(
         &self,
         from_offset: u64,
+        after: u64,
         entries: &mut std::collections::HashMap<String, Option<LocationUpdate>>,
-    ) -> Result<()> {
+        progress: &mut dyn FnMut(u64),
+    ) -> Result<(u64, u64)> {
         // Keep an update if the slot is empty/tombstoned, or strictly newer.
         fn merge(slot: &mut Option<LocationUpdate>, update: LocationUpdate) {
             match slot {
@@ -873,20 +3888,29 @@ impl TrajectoryLog {
             }
         }
 
+        let mut applied = 0u64;
+        let mut numbering = Numbering::after(after);
         match &self.backend {
-            LogBackend::File { path, version, .. } => {
-                use std::io::{BufRead, BufReader, Seek, SeekFrom};
-                let version = *version;
-                if !path.exists() {
-                    return Ok(());
-                }
-                let mut file = std::fs::File::open(path)?;
+            LogBackend::File {
+                path,
+                segments,
+                writer,
+                ..
+            } => {
+                let version = writer.version;
+                let mut log = SegmentReader::open(&segments::paths(path, segments))?;
                 if from_offset > 0 {
-                    file.seek(SeekFrom::Start(from_offset))?;
+                    log.seek(SeekFrom::Start(from_offset))?;
                 }
-                let reader = BufReader::new(file);
+                let reader = BufReader::new(log);
+                let mut read = 0u64;
+                let mut reported = 0u64;
 
                 for (line_num, line_result) in reader.lines().enumerate() {
+                    if read - reported >= REPLAY_PROGRESS_BYTES {
+                        progress(read);
+                        reported = read;
+                    }
                     let line = match line_result {
                         Ok(l) => l,
                         Err(e) => {
@@ -895,77 +3919,422 @@ impl TrajectoryLog {
                                 line_num + 1,
                                 e
                             );
+                            numbering.lose_line();
                             continue;
                         }
                     };
 
+                    read += line.len() as u64 + 1;
+
                     // Strip header + verify CRC (V2); skip corrupt/torn lines.
-                    let Some(body) = record_body(&line, version) else {
+                    let Some(bodies) = record_bodies(&line, version) else {
+                        if lost_records(&line, version) {
+                            numbering.lose_line();
+                        }
                         continue;
                     };
 
-                    // Tombstone: TOMBSTONE|timestamp_micros|namespace|object_id
-                    if body.starts_with("TOMBSTONE|") {
-                        let parts: Vec<&str> = body.splitn(4, '|').collect();
-                        if parts.len() != 4 {
-                            log::warn!("Malformed tombstone on line {}", line_num + 1);
+                    for body in bodies.split('\n') {
+                        if numbering.number(body).is_none() {
+                            continue;
+                        }
+                        // Tombstone: TOMBSTONE|timestamp_micros|namespace|object_id
+                        if body.starts_with("TOMBSTONE|") {
+                            let parts: Vec<&str> = body.splitn(4, '|').collect();
+                            if parts.len() != 4 {
+                                log::warn!("Malformed tombstone on line {}", line_num + 1);
+                                continue;
+                            }
+                            entries.insert(format!("{}::{}", parts[2], parts[3]), None);
+                            applied += 1;
                             continue;
                         }
-                        entries.insert(format!("{}::{}", parts[2], parts[3]), None);
-                        continue;
-                    }
-
-                    let Some((timestamp, namespace, object_id, position, metadata)) =
-                        parse_update_body(body)
-                    else {
-                        log::warn!("Malformed log line {}", line_num + 1);
-                        continue;
-                    };
 
-                    let slot = entries
-                        .entry(format!("{}::{}", namespace, object_id))
-                        .or_insert(None);
-                    merge(
-                        slot,
-                        LocationUpdate {
-                            timestamp,
-                            position,
-                            metadata,
-                        },
-                    );
+                        let Some((timestamp, namespace, object_id, position, metadata)) =
+                            parse_update_body(body)
+                        else {
+                            log::warn!("Malformed log line {}", line_num + 1);
+                            continue;
+                        };
+
+                        applied += 1;
+                        let slot = entries
+                            .entry(format!("{}::{}", namespace, object_id))
+                            .or_insert(None);
+                        merge(
+                            slot,
+                            LocationUpdate {
+                                timestamp,
+                                position,
+                                metadata,
+                            },
+                        );
+                    }
                 }
+                progress(read);
             }
             LogBackend::Memory { records } => {
+                applied = records.len() as u64;
                 for rec in records {
                     match rec {
                         MemRecord::Update {
+                            sequence,
                             namespace,
                             object_id,
                             update,
                         } => {
+                            numbering.last = *sequence;
                             let slot = entries
                                 .entry(format!("{}::{}", namespace, object_id))
                                 .or_insert(None);
                             merge(slot, update.clone());
                         }
-                        MemRecord::Tombstone {
-                            namespace,
-                            object_id,
-                        } => {
-                            entries.insert(format!("{}::{}", namespace, object_id), None);
+                        MemRecord::Tombstone {
+                            sequence,
+                            namespace,
+                            object_id,
+                            ..
+                        } => {
+                            numbering.last = *sequence;
+                            entries.insert(format!("{}::{}", namespace, object_id), None);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok((applied, numbering.last))
+    }
+
+    /// Drop the records `plan` does not keep, returning how many were
+    /// removed.
+    ///
+    /// Each segment of a file log is rewritten to a temporary file, and the
+    /// rewrites replace the segments once synced, oldest first, so a crash
+    /// part way never leaves a newer segment rewritten without the older
+    /// ones. Sealed segments left without records are removed. The
+    /// checkpoint snapshot and delta are removed first and a snapshot
+    /// written against the new log afterwards, so neither ever describes the
+    /// wrong file; a crash in between leaves a full replay. The rewrites
+    /// carry [`SEQUENCE_MARKER`]s where records were removed, so that replay
+    /// gives every kept record, and the next write, the sequence it would
+    /// have had. Fails while the log is degraded, since the rewrite could not
+    /// be saved.
+    fn compact<F: Fn(&str, SystemTime) -> bool>(
+        &mut self,
+        mut plan: RetentionPlan<F>,
+    ) -> Result<u64> {
+        if self.read_only {
+            return Err(SpatioError::ReadOnly);
+        }
+        if let Some(reason) = self.degraded.get() {
+            return Err(SpatioError::DegradedPersistence(reason.clone()));
+        }
+        let sequence = self.sequence;
+        match &mut self.backend {
+            LogBackend::File {
+                writer,
+                sync_file,
+                path,
+                segments,
+                segment_start,
+                pending_writes,
+                ..
+            } => {
+                let (version, compression) = (writer.version, writer.compression);
+                writer.flush()?;
+                *pending_writes = 0;
+                let paths = segments::paths(path, segments);
+
+                // Every pass streams the segments, which cannot change under
+                // the lock, so a record's index identifies the same record in
+                // each. Each record comes with its sequence. Returns the index
+                // after the last record read.
+                let for_each_record =
+                    |paths: &[std::path::PathBuf],
+                     first: u64,
+                     numbering: &mut Numbering,
+                     f: &mut dyn FnMut(u64, u64, &str)| {
+                        let reader = BufReader::new(SegmentReader::open(paths)?);
+                        let mut index = first;
+                        for line in reader.lines() {
+                            let Some(bodies) = line
+                                .as_deref()
+                                .ok()
+                                .and_then(|line| record_bodies(line, version))
+                            else {
+                                if line
+                                    .as_deref()
+                                    .is_ok_and(|line| !lost_records(line, version))
+                                {
+                                    continue;
+                                }
+                                numbering.lose_line();
+                                continue;
+                            };
+                            for body in bodies.split('\n') {
+                                if let Some(sequence) = numbering.number(body) {
+                                    f(index, sequence, body);
+                                    index += 1;
+                                }
+                            }
+                        }
+                        Ok::<_, SpatioError>(index)
+                    };
+                for_each_record(
+                    &paths,
+                    0,
+                    &mut Numbering::after(0),
+                    &mut |index, _, body| {
+                        if let Some(record) = parse_retention_record(body) {
+                            plan.observe(index, &record);
+                        }
+                    },
+                )?;
+                let current = plan.current_locations();
+                if plan.min_removed > 1 {
+                    let mut removable = 0u64;
+                    for_each_record(
+                        &paths,
+                        0,
+                        &mut Numbering::after(0),
+                        &mut |index, _, body| {
+                            let keep = parse_retention_record(body)
+                                .is_some_and(|record| plan.keep(index, &record));
+                            removable += u64::from(!keep);
+                        },
+                    )?;
+                    plan.rewind();
+                    if removable < plan.min_removed {
+                        return Ok(0);
+                    }
+                }
+
+                let mut removed = 0u64;
+                let mut index = 0u64;
+                let mut numbering = Numbering::after(0);
+                // Sequence of the last record the rewrites hold so far.
+                let mut written = 0u64;
+                // Each segment's rewrite and how many records it kept.
+                let mut rewrites = Vec::with_capacity(paths.len());
+                for (i, segment) in paths.iter().enumerate() {
+                    let mut tmp = segment.as_os_str().to_os_string();
+                    tmp.push(".expire");
+                    let tmp = std::path::PathBuf::from(tmp);
+                    let out = BufWriter::new(File::create(&tmp)?);
+                    let mut w = RecordWriter::create(out, 0, written, version, compression)?;
+                    let mut kept = 0u64;
+                    let mut result = Ok(());
+                    index = for_each_record(
+                        std::slice::from_ref(segment),
+                        index,
+                        &mut numbering,
+                        &mut |index, sequence, body| match parse_retention_record(body) {
+                            Some(record) if plan.keep(index, &record) => {
+                                kept += 1;
+                                if result.is_ok() {
+                                    result = w.append(sequence, body).map(drop);
+                                }
+                            }
+                            _ => removed += 1,
+                        },
+                    )?;
+                    result?;
+                    // End numbered as the segment did, for the segments after
+                    // it, rewritten or not yet; removed records and failed
+                    // writes at the end of the log still took their sequences.
+                    let last = i + 1 == paths.len();
+                    w.mark(if last { sequence } else { numbering.last })?;
+                    w.flush()?;
+                    fsync::sync_file(w.out.get_ref(), SyncMode::All)?;
+                    // An emptied sealed segment is removed below.
+                    if kept > 0 || i == 0 || last {
+                        written = w.sequence;
+                    }
+                    rewrites.push((tmp, kept));
+                }
+
+                let snapshot = snapshot_path_for(path);
+                remove_if_exists(&delta_path_for(path))?;
+                remove_if_exists(&snapshot)?;
+                // The saved indexes may still hold removed records; the next
+                // checkpoint or close saves them again.
+                remove_if_exists(&index_snapshot_path_for(path))?;
+                let last = paths.len() - 1;
+                let mut kept_segments = Vec::with_capacity(paths.len());
+                for (i, ((segment, number), (tmp, kept))) in
+                    paths.iter().zip(segments.iter()).zip(rewrites).enumerate()
+                {
+                    // Spans saved for the old segment would be refused for
+                    // its new length anyway.
+                    remove_if_exists(&spans_path_for(segment))?;
+                    if kept == 0 && i != 0 && i != last {
+                        std::fs::remove_file(&tmp)?;
+                        remove_if_exists(segment)?;
+                    } else {
+                        std::fs::rename(&tmp, segment)?;
+                        kept_segments.push(*number);
+                    }
+                }
+                sync_parent_dir(path);
+                *segments = kept_segments;
+
+                let paths = segments::paths(path, segments);
+                let active = paths.last().expect("the last segment is kept");
+                let file = OpenOptions::new().append(true).open(active)?;
+                let covered_len = segments::total_len(&paths);
+                *segment_start = covered_len - file.metadata()?.len();
+                *sync_file = Arc::new(file.try_clone()?);
+                *writer = RecordWriter::new(
+                    LogWriter::new(file),
+                    covered_len,
+                    sequence,
+                    version,
+                    compression,
+                );
+                self.index = None;
+                self.watermark.advance(sequence);
+                write_snapshot(&snapshot, &current, covered_len, sequence)?;
+                Ok(removed)
+            }
+            LogBackend::Memory { records } => {
+                for (index, record) in records.iter().enumerate() {
+                    plan.observe(index as u64, &record.as_retention());
+                }
+                plan.current_locations();
+                if plan.min_removed > 1 {
+                    let removable = records
+                        .iter()
+                        .enumerate()
+                        .filter(|(index, record)| !plan.keep(*index as u64, &record.as_retention()))
+                        .count() as u64;
+                    plan.rewind();
+                    if removable < plan.min_removed {
+                        return Ok(0);
+                    }
+                }
+                let before = records.len();
+                let mut index = 0u64;
+                records.retain(|record| {
+                    let keep = plan.keep(index, &record.as_retention());
+                    index += 1;
+                    keep
+                });
+                self.index = None;
+                Ok((before - records.len()) as u64)
+            }
+        }
+    }
+
+    /// Continue numbering after `sequence` records recovered from the log,
+    /// all of which are on stable storage.
+    fn resume_sequence(&mut self, sequence: u64) {
+        self.sequence = sequence;
+        if let LogBackend::File { writer, .. } = &mut self.backend {
+            writer.sequence = sequence;
+        }
+        self.watermark.advance(sequence);
+    }
+}
+
+/// `fsync` what has been appended to `log` without holding its lock through
+/// the sync, then advance `watermark` past it.
+fn sync_log(
+    log: &Mutex<TrajectoryLog>,
+    watermark: &DurabilityWatermark,
+    mode: SyncMode,
+) -> Result<()> {
+    let (file, sequence, latency) = {
+        let mut log = log.lock();
+        let Some((file, sequence)) = log.begin_sync()? else {
+            return Ok(());
+        };
+        (file, sequence, log.sync_latency.clone())
+    };
+    let started = Instant::now();
+    fsync::sync_file(&file, mode)?;
+    latency.lock().observe(started.elapsed().as_secs_f64());
+    watermark.advance(sequence);
+    Ok(())
+}
+
+/// Wake-up state shared with the background sync thread.
+#[derive(Default)]
+struct SyncSignal {
+    /// A caller is waiting on the watermark; sync without waiting out the interval.
+    requested: bool,
+    shutdown: bool,
+}
+
+/// Thread that periodically `fsync`s the log under [`SyncPolicy::Background`].
+///
+/// Each round flushes the `BufWriter` under the log lock, then runs the
+/// `fsync` on a second file handle with the lock released, so appends never
+/// wait on the disk. Dropping it stops and joins the thread.
+struct BackgroundSync {
+    signal: Arc<(Mutex<SyncSignal>, Condvar)>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl BackgroundSync {
+    fn spawn(
+        log: Arc<Mutex<TrajectoryLog>>,
+        watermark: Arc<DurabilityWatermark>,
+        sync: SyncSettings,
+    ) -> Result<Self> {
+        let signal = Arc::new((Mutex::new(SyncSignal::default()), Condvar::new()));
+        let thread_signal = signal.clone();
+        let handle = std::thread::Builder::new()
+            .name("spatio-sync".to_string())
+            .spawn(move || {
+                let (state, wake) = &*thread_signal;
+                loop {
+                    {
+                        let mut state = state.lock();
+                        if !state.requested && !state.shutdown {
+                            wake.wait_for(&mut state, sync.interval);
+                        }
+                        if state.shutdown {
+                            return;
                         }
+                        state.requested = false;
+                    }
+                    if let Err(e) = sync_log(&log, &watermark, sync.mode) {
+                        log::warn!("Background sync of trajectory log failed: {}", e);
                     }
                 }
-            }
-        }
+            })?;
 
-        Ok(())
+        Ok(Self {
+            signal,
+            handle: Some(handle),
+        })
+    }
+
+    /// Ask for a sync now instead of at the next interval.
+    fn request(&self) {
+        let (state, wake) = &*self.signal;
+        state.lock().requested = true;
+        wake.notify_one();
+    }
+}
+
+impl Drop for BackgroundSync {
+    fn drop(&mut self) {
+        let (state, wake) = &*self.signal;
+        state.lock().shutdown = true;
+        wake.notify_one();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
     }
 }
 
 impl Drop for TrajectoryLog {
     fn drop(&mut self) {
-        if let Err(e) = self.maybe_sync(true) {
+        if self.degraded.get().is_none()
+            && let Err(e) = self.maybe_sync(true)
+        {
             log::warn!("Failed to flush trajectory log on drop: {}", e);
         }
     }
@@ -990,11 +4359,13 @@ mod tests {
             // Large buffer: without fsync, one write would not reach disk.
             PersistenceConfig {
                 buffer_size: 10_000,
+                ..Default::default()
             },
             SyncSettings {
                 policy: SyncPolicy::Always,
                 mode: SyncMode::Data,
                 batch_size: 1,
+                ..SyncSettings::default()
             },
         )
         .unwrap();
@@ -1016,6 +4387,81 @@ mod tests {
         );
     }
 
+    /// Under group commit, writes arriving together share `fsync`s and each
+    /// still returns only once it is durable.
+    #[test]
+    fn test_group_commit_shares_syncs() {
+        let dir = tempdir().unwrap();
+        let log_path = dir.path().join("traj.log");
+        let cold = ColdState::new(
+            &log_path,
+            10,
+            PersistenceConfig {
+                buffer_size: 10_000,
+                ..Default::default()
+            },
+            SyncSettings {
+                policy: SyncPolicy::Always,
+                group_commit: Some(Duration::from_millis(20)),
+                ..SyncSettings::default()
+            },
+        )
+        .unwrap();
+
+        let start = std::sync::Barrier::new(8);
+        let durable: Vec<u64> = std::thread::scope(|scope| {
+            let writers: Vec<_> = (0..8)
+                .map(|i| {
+                    let (cold, start) = (&cold, &start);
+                    scope.spawn(move || {
+                        start.wait();
+                        let sequence = cold
+                            .append_update(
+                                "v",
+                                &format!("o{i}"),
+                                Point3d::new(1.0, 2.0, 0.0),
+                                serde_json::json!({}),
+                                UNIX_EPOCH + Duration::from_secs(1),
+                            )
+                            .unwrap();
+                        let durable = cold.durable_sequence();
+                        assert!(durable >= sequence);
+                        durable
+                    })
+                })
+                .collect();
+            writers.into_iter().map(|w| w.join().unwrap()).collect()
+        });
+        let syncs: HashSet<u64> = durable.into_iter().collect();
+        assert!(syncs.len() < 8, "every write synced alone: {syncs:?}");
+        assert_eq!(
+            std::fs::read_to_string(&log_path)
+                .unwrap()
+                .matches("|v|o")
+                .count(),
+            8
+        );
+
+        // A group waits once, for its last append.
+        let last = cold
+            .group(|| {
+                for i in 0..5 {
+                    cold.append_tombstone("v", &format!("o{i}"))?;
+                }
+                Ok(cold.last_sequence())
+            })
+            .unwrap();
+        assert_eq!(last, 13);
+        assert_eq!(cold.durable_sequence(), 13);
+
+        // A group that panics doesn't leave the thread grouped.
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            cold.group(|| -> Result<()> { panic!("mid-group") })
+        }));
+        assert!(panicked.is_err());
+        assert!(!GROUPED.get());
+    }
+
     /// `flush()`/close must push buffered writes to disk even under
     /// `SyncPolicy::Never` (which otherwise never syncs).
     #[test]
@@ -1027,11 +4473,13 @@ mod tests {
             10,
             PersistenceConfig {
                 buffer_size: 10_000,
+                ..Default::default()
             },
             SyncSettings {
                 policy: SyncPolicy::Never,
                 mode: SyncMode::All,
                 batch_size: 1,
+                ..SyncSettings::default()
             },
         )
         .unwrap();
@@ -1056,6 +4504,60 @@ mod tests {
         );
     }
 
+    /// Under `SyncPolicy::Background` appends return without syncing; the
+    /// background thread advances the watermark, immediately when asked.
+    #[test]
+    fn test_background_sync_advances_watermark() {
+        let dir = tempdir().unwrap();
+        let log_path = dir.path().join("traj.log");
+        let cold = ColdState::new(
+            &log_path,
+            10,
+            PersistenceConfig {
+                buffer_size: 10_000,
+                ..Default::default()
+            },
+            SyncSettings {
+                policy: SyncPolicy::Background,
+                // Long enough that only an explicit request syncs in time.
+                interval: Duration::from_secs(3600),
+                ..SyncSettings::default()
+            },
+        )
+        .unwrap();
+
+        let mut sequence = 0;
+        for i in 0..3 {
+            sequence = cold
+                .append_update(
+                    "v",
+                    "o",
+                    Point3d::new(1.0, 2.0, 3.0),
+                    serde_json::json!({}),
+                    UNIX_EPOCH + Duration::from_secs(i),
+                )
+                .unwrap();
+        }
+        assert_eq!(sequence, 3);
+        assert_eq!(cold.last_sequence(), 3);
+        assert_eq!(cold.durable_sequence(), 0);
+
+        assert!(cold.wait_durable(sequence, Duration::from_secs(10)));
+        assert_eq!(cold.durable_sequence(), 3);
+        assert_eq!(
+            std::fs::read_to_string(&log_path)
+                .unwrap()
+                .matches("|v|o|")
+                .count(),
+            3
+        );
+
+        // A tombstone is a record too.
+        assert_eq!(cold.append_tombstone("v", "o").unwrap(), 4);
+        assert!(!cold.wait_durable(5, Duration::from_millis(20)));
+        drop(cold);
+    }
+
     #[test]
     fn test_append_and_query_buffer() {
         let dir = tempdir().unwrap();
@@ -1114,7 +4616,10 @@ mod tests {
         let cold = ColdState::new(
             &log_path,
             2,
-            PersistenceConfig { buffer_size: 0 },
+            PersistenceConfig {
+                buffer_size: 0,
+                ..Default::default()
+            },
             SyncSettings::default(),
         )
         .unwrap(); // Capacity 2
@@ -1130,9 +4635,15 @@ mod tests {
         // Check buffer directly - should only have last 2
         let key = ColdState::make_key("v", "o");
         let buffer = cold.recent_buffer.get(&key).unwrap();
-        assert_eq!(buffer.len(), 2);
-        assert_eq!(buffer[0].timestamp, UNIX_EPOCH + Duration::from_secs(3));
-        assert_eq!(buffer[1].timestamp, UNIX_EPOCH + Duration::from_secs(4));
+        assert_eq!(buffer.updates.len(), 2);
+        assert_eq!(
+            buffer.updates[0].timestamp,
+            UNIX_EPOCH + Duration::from_secs(3)
+        );
+        assert_eq!(
+            buffer.updates[1].timestamp,
+            UNIX_EPOCH + Duration::from_secs(4)
+        );
 
         // But query_trajectory should return all from disk
         let history = cold
@@ -1150,6 +4661,95 @@ mod tests {
         assert_eq!(history[1].timestamp, UNIX_EPOCH + Duration::from_secs(3));
     }
 
+    #[test]
+    fn test_history_buffering_opt_in_and_lru_bound() {
+        let cold = ColdState::new_memory(10).with_history_buffering(HistoryBuffering {
+            namespaces: Some(HashSet::from(["fleet".to_string()])),
+            max_points: Some(4),
+        });
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        let write = |ns: &str, id: &str, secs| {
+            cold.append_update(
+                ns,
+                id,
+                Point3d::new(0.0, 0.0, 0.0),
+                serde_json::json!({}),
+                at(secs),
+            )
+            .unwrap();
+        };
+        let history = |ns: &str, id: &str| {
+            cold.query_trajectory(ns, id, UNIX_EPOCH, at(100), 100)
+                .unwrap()
+                .len()
+        };
+
+        // Other namespaces are logged but not buffered, unless asked.
+        write("logs", "a", 1);
+        assert!(cold.recent_buffer.is_empty());
+        cold.append_tracked_update(
+            "logs",
+            "a",
+            Point3d::new(0.0, 0.0, 0.0),
+            serde_json::json!({}),
+            at(2),
+        )
+        .unwrap();
+        assert!(!cold.recent_buffer.get("logs::a").unwrap().complete);
+        assert_eq!(history("logs", "a"), 2);
+
+        // Past four buffered points the least recently updated buffers go.
+        write("fleet", "x", 1);
+        write("fleet", "y", 1);
+        write("fleet", "x", 2);
+        write("fleet", "z", 1);
+        assert!(cold.recent_buffer.get("logs::a").is_none());
+        write("fleet", "z", 2);
+        assert!(cold.recent_buffer.get("fleet::y").is_none());
+        assert!(cold.recent_buffer.get("fleet::x").is_some());
+        assert_eq!(cold.buffered_points.load(Ordering::Relaxed), 4);
+
+        // Evicted objects still read their whole history from the log.
+        write("fleet", "y", 2);
+        assert!(!cold.recent_buffer.get("fleet::y").unwrap().complete);
+        assert_eq!(history("fleet", "y"), 2);
+        assert_eq!(history("logs", "a"), 2);
+    }
+
+    #[test]
+    fn test_reopened_log_is_not_served_from_new_buffers() {
+        let dir = tempdir().unwrap();
+        let log_path = dir.path().join("traj.log");
+        let open = || {
+            ColdState::new(
+                &log_path,
+                10,
+                PersistenceConfig {
+                    buffer_size: 0,
+                    ..Default::default()
+                },
+                SyncSettings::default(),
+            )
+            .unwrap()
+        };
+        let pos = Point3d::new(0.0, 0.0, 0.0);
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+
+        let cold = open();
+        cold.append_update("v", "o", pos.clone(), serde_json::json!({}), at(1))
+            .unwrap();
+        drop(cold);
+
+        let cold = open();
+        cold.recover_current_locations().unwrap();
+        cold.append_update("v", "o", pos, serde_json::json!({}), at(2))
+            .unwrap();
+        let history = cold
+            .query_trajectory("v", "o", UNIX_EPOCH, at(10), 100)
+            .unwrap();
+        assert_eq!(history.len(), 2);
+    }
+
     #[test]
     fn test_recover_current_locations() {
         let dir = tempdir().unwrap();
@@ -1158,7 +4758,10 @@ mod tests {
         let cold = ColdState::new(
             &log_path,
             10,
-            PersistenceConfig { buffer_size: 0 },
+            PersistenceConfig {
+                buffer_size: 0,
+                ..Default::default()
+            },
             SyncSettings::default(),
         )
         .unwrap();
@@ -1217,6 +4820,77 @@ mod tests {
         assert_eq!(plane.timestamp, t3);
     }
 
+    #[test]
+    fn test_expire_history_keeps_current_locations() {
+        let dir = tempdir().unwrap();
+        let log_path = dir.path().join("traj.log");
+        let open = || {
+            ColdState::new(
+                &log_path,
+                10,
+                PersistenceConfig {
+                    buffer_size: 0,
+                    ..Default::default()
+                },
+                SyncSettings::default(),
+            )
+            .unwrap()
+        };
+        let day = |d: u64| UNIX_EPOCH + Duration::from_secs(d * 86_400);
+        let now = day(100);
+        let point = |x: f64| Point3d::new(x, 0.0, 0.0);
+        let meta = serde_json::json!({});
+
+        let cold = open();
+        // Moving truck: old history expires, its latest point stays.
+        cold.append_update("fleet", "truck", point(1.0), meta.clone(), day(10))
+            .unwrap();
+        cold.append_update("fleet", "truck", point(2.0), meta.clone(), day(90))
+            .unwrap();
+        // Parked car: its only point is expired but is still its location.
+        cold.append_update("fleet", "car", point(3.0), meta.clone(), day(20))
+            .unwrap();
+        // Deleted bike: nothing survives, including the tombstone.
+        cold.append_update("fleet", "bike", point(4.0), meta.clone(), day(20))
+            .unwrap();
+        cold.append_tombstone("fleet", "bike").unwrap();
+        // Other namespaces keep everything.
+        cold.append_update("audit", "truck", point(5.0), meta.clone(), day(10))
+            .unwrap();
+        let sequence = cold.last_sequence();
+
+        let retention = HistoryRetention {
+            max_age_secs: HashMap::from([("fleet".to_string(), 30 * 86_400)]),
+            ..Default::default()
+        };
+        assert_eq!(cold.expire_history(&retention, now).unwrap(), 3);
+        assert_eq!(cold.expire_history(&retention, now).unwrap(), 0);
+
+        let history = |cold: &ColdState, ns: &str, id: &str| {
+            cold.query_trajectory(ns, id, UNIX_EPOCH, now, 10)
+                .unwrap()
+                .iter()
+                .map(|u| u.position.x())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(history(&cold, "fleet", "truck"), [2.0]);
+        assert_eq!(history(&cold, "fleet", "car"), [3.0]);
+        assert_eq!(history(&cold, "audit", "truck"), [5.0]);
+        cold.append_update("fleet", "truck", point(6.0), meta.clone(), day(95))
+            .unwrap();
+        drop(cold);
+
+        // Recovery sees the rewritten log and keeps numbering sequences.
+        let cold = open();
+        let recovered = cold.recover_current_locations().unwrap();
+        assert_eq!(cold.last_sequence(), sequence + 1);
+        let mut keys: Vec<_> = recovered.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, ["audit::truck", "fleet::car", "fleet::truck"]);
+        assert_eq!(recovered["fleet::truck"].position.x(), 6.0);
+        assert_eq!(history(&cold, "fleet", "truck"), [6.0, 2.0]);
+    }
+
     #[test]
     fn test_tombstone_beats_future_timestamp_on_recovery() {
         // An object inserted with a future SetOptions timestamp must still stay deleted
@@ -1226,7 +4900,10 @@ mod tests {
         let cold = ColdState::new(
             &log_path,
             10,
-            PersistenceConfig { buffer_size: 0 },
+            PersistenceConfig {
+                buffer_size: 0,
+                ..Default::default()
+            },
             SyncSettings::default(),
         )
         .unwrap();
@@ -1258,7 +4935,10 @@ mod tests {
         let cold = ColdState::new(
             &log_path,
             10,
-            PersistenceConfig { buffer_size: 0 },
+            PersistenceConfig {
+                buffer_size: 0,
+                ..Default::default()
+            },
             SyncSettings::default(),
         )
         .unwrap();
@@ -1310,7 +4990,10 @@ mod tests {
         let cold = ColdState::new(
             &log_path,
             10,
-            PersistenceConfig { buffer_size: 0 },
+            PersistenceConfig {
+                buffer_size: 0,
+                ..Default::default()
+            },
             SyncSettings::default(),
         )
         .unwrap();
@@ -1356,7 +5039,10 @@ mod tests {
         let cold = ColdState::new(
             &log_path,
             2,
-            PersistenceConfig { buffer_size: 0 },
+            PersistenceConfig {
+                buffer_size: 0,
+                ..Default::default()
+            },
             SyncSettings::default(),
         )
         .unwrap();
@@ -1416,7 +5102,10 @@ mod tests {
         let cold = ColdState::new(
             &log_path,
             2,
-            PersistenceConfig { buffer_size: 0 },
+            PersistenceConfig {
+                buffer_size: 0,
+                ..Default::default()
+            },
             SyncSettings::default(),
         )
         .unwrap(); // Small buffer to force disk scan
@@ -1462,6 +5151,217 @@ mod tests {
         assert_eq!(limited[0].timestamp, t5);
     }
 
+    #[test]
+    fn test_time_index_tracks_appends_and_compaction() {
+        let dir = tempdir().unwrap();
+        let log_path = dir.path().join("traj.log");
+        let lz4_path = dir.path().join("lz4.log");
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        let open_with = |path: &Path, persistence| {
+            // No recent buffer, so every query reads the log.
+            ColdState::new(path, 0, persistence, SyncSettings::default()).unwrap()
+        };
+        let open = || {
+            let persistence = PersistenceConfig {
+                buffer_size: 0,
+                ..Default::default()
+            };
+            open_with(&log_path, persistence)
+        };
+        // Records gather in blocks until a query flushes them.
+        let open_lz4 = || {
+            let persistence = PersistenceConfig {
+                buffer_size: 100,
+                compression: LogCompression::Lz4,
+                ..Default::default()
+            };
+            open_with(&lz4_path, persistence)
+        };
+        let append = |cold: &ColdState, id: &str, secs| {
+            cold.append_update(
+                "v",
+                id,
+                Point3d::new(secs as f64, 0.0, 0.0),
+                serde_json::json!({}),
+                at(secs),
+            )
+            .unwrap();
+        };
+        let stamps = |cold: &ColdState, start, end| -> Vec<u64> {
+            cold.query_trajectory("v", "o", at(start), at(end), 100)
+                .unwrap()
+                .iter()
+                .map(|u| u.timestamp.duration_since(UNIX_EPOCH).unwrap().as_secs())
+                .collect()
+        };
+
+        for cold in [open(), open_lz4(), ColdState::new_memory(0)] {
+            for secs in [10, 30] {
+                append(&cold, "o", secs);
+                append(&cold, "other", secs);
+            }
+            // The first query builds the index; later appends extend it,
+            // out-of-order stamps included.
+            assert_eq!(stamps(&cold, 0, 100), [30, 10]);
+            append(&cold, "o", 20);
+            append(&cold, "o", 40);
+            assert_eq!(stamps(&cold, 15, 35), [30, 20]);
+
+            // Compaction moves records; the index is rebuilt.
+            cold.drop_updates("v", "o", HashSet::from([at(20)]))
+                .unwrap();
+            assert_eq!(stamps(&cold, 0, 100), [40, 30, 10]);
+            append(&cold, "o", 50);
+            assert_eq!(stamps(&cold, 45, 100), [50]);
+            assert!(stamps(&cold, 100, 0).is_empty());
+        }
+
+        // A reopened log is indexed from disk, and new records land after it.
+        for cold in [open(), open_lz4()] {
+            append(&cold, "o", 60);
+            assert_eq!(stamps(&cold, 0, 100), [60, 50, 40, 30, 10]);
+        }
+    }
+
+    #[test]
+    fn test_segmented_log_reads_only_segments_a_query_needs() {
+        let dir = tempdir().unwrap();
+        let log_path = dir.path().join("traj.log");
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        let open = || {
+            let persistence = PersistenceConfig {
+                buffer_size: 0,
+                segment_secs: Some(3600),
+                ..Default::default()
+            };
+            ColdState::new(&log_path, 0, persistence, SyncSettings::default()).unwrap()
+        };
+        let append = |cold: &ColdState, id: &str, secs| {
+            cold.append_update(
+                "v",
+                id,
+                Point3d::new(secs as f64, 0.0, 0.0),
+                serde_json::json!({}),
+                at(secs),
+            )
+            .unwrap();
+        };
+        let stamps = |cold: &ColdState, id: &str, start, end| -> Vec<u64> {
+            cold.query_trajectory("v", id, at(start), at(end), 100)
+                .unwrap()
+                .iter()
+                .map(|u| u.timestamp.duration_since(UNIX_EPOCH).unwrap().as_secs())
+                .collect()
+        };
+        let unloaded = |cold: &ColdState| {
+            let log = cold.trajectory_log.lock();
+            log.index.as_ref().map_or(0, |index| index.unloaded.len())
+        };
+        let segment = |n| segments::segment_path(&log_path, n);
+
+        {
+            let cold = open();
+            append(&cold, "a", 100);
+            append(&cold, "b", 100);
+            cold.trajectory_log.lock().start_segment().unwrap();
+            append(&cold, "a", 200);
+            cold.trajectory_log.lock().start_segment().unwrap();
+            append(&cold, "b", 300);
+            assert_eq!(stamps(&cold, "a", 0, 1000), [200, 100]);
+            assert_eq!(stamps(&cold, "b", 0, 1000), [300, 100]);
+        }
+        assert!(segment(1).exists() && segment(2).exists());
+
+        // The first index build scanned the sealed segments and saved their
+        // spans; the segment being written has none.
+        assert!(spans_path_for(&segment(0)).exists() && spans_path_for(&segment(1)).exists());
+        assert!(!spans_path_for(&segment(2)).exists());
+
+        // After a reopen, recovery reads every segment, and queries only the
+        // segments whose spans overlap them.
+        let cold = open();
+        let recovered = cold.recover_current_locations().unwrap();
+        assert_eq!(recovered["v::a"].timestamp, at(200));
+        assert_eq!(recovered["v::b"].timestamp, at(300));
+        assert_eq!(stamps(&cold, "b", 250, 1000), [300]);
+        assert_eq!(unloaded(&cold), 2);
+        assert_eq!(stamps(&cold, "a", 150, 250), [200]);
+        assert_eq!(unloaded(&cold), 1);
+        assert_eq!(stamps(&cold, "a", 0, 1000), [200, 100]);
+        assert_eq!(unloaded(&cold), 0);
+
+        // Spans saved for a segment of another length are ignored.
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(segment(1))
+            .unwrap()
+            .write_all(b"\n")
+            .unwrap();
+        assert!(read_spans(&segment(1), std::fs::metadata(segment(1)).unwrap().len()).is_none());
+
+        // Compaction removes a sealed segment it empties.
+        assert_eq!(cold.forget_object("v", "a").unwrap(), 2);
+        assert!(!segment(1).exists());
+        assert!(!spans_path_for(&segment(0)).exists());
+        assert!(stamps(&cold, "a", 0, 1000).is_empty());
+        assert_eq!(stamps(&cold, "b", 0, 1000), [300, 100]);
+        append(&cold, "b", 400);
+        drop(cold);
+
+        let cold = open();
+        let recovered = cold.recover_current_locations().unwrap();
+        assert!(!recovered.contains_key("v::a"));
+        assert_eq!(recovered["v::b"].timestamp, at(400));
+        assert_eq!(stamps(&cold, "b", 0, 1000), [400, 300, 100]);
+    }
+
+    #[test]
+    fn test_warm_up_indexes_recent_segments() {
+        let dir = tempdir().unwrap();
+        let log_path = dir.path().join("traj.log");
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        let open = || {
+            let persistence = PersistenceConfig {
+                buffer_size: 0,
+                segment_secs: Some(3600),
+                ..Default::default()
+            };
+            ColdState::new(&log_path, 0, persistence, SyncSettings::default()).unwrap()
+        };
+        {
+            let cold = open();
+            for secs in [100, 200, 300] {
+                cold.append_update(
+                    "v",
+                    "a",
+                    Point3d::new(0.0, 0.0, 0.0),
+                    serde_json::json!({}),
+                    at(secs),
+                )
+                .unwrap();
+                cold.trajectory_log.lock().start_segment().unwrap();
+            }
+            // Saves the spans of the sealed segments.
+            cold.query_trajectory("v", "a", at(0), at(1000), 10)
+                .unwrap();
+        }
+        let len =
+            |n| std::fs::metadata(segments::segment_path(&log_path, n)).map_or(0, |m| m.len());
+
+        // The segment being written is always read; of the sealed ones, only
+        // those holding updates since the cutoff.
+        let cold = open();
+        assert_eq!(
+            cold.warm_up(at(150)).unwrap(),
+            (3, len(1) + len(2) + len(3))
+        );
+        let log = cold.trajectory_log.lock();
+        assert_eq!(log.index.as_ref().unwrap().unloaded.len(), 1);
+        drop(log);
+        assert_eq!(cold.warm_up(at(150)).unwrap(), (0, 0));
+        assert_eq!(cold.warm_up(at(0)).unwrap(), (1, len(0)));
+    }
+
     #[test]
     fn test_trajectory_sees_buffered_but_evicted_records() {
         // Records evicted from the recent buffer but not yet OS-flushed must
@@ -1473,11 +5373,13 @@ mod tests {
             2, // tiny recent-buffer capacity
             PersistenceConfig {
                 buffer_size: 10_000,
+                ..Default::default()
             }, // large: no incidental OS flush
             SyncSettings {
                 policy: SyncPolicy::Never, // never fsyncs on its own
                 mode: SyncMode::All,
                 batch_size: 1,
+                ..SyncSettings::default()
             },
         )
         .unwrap();
@@ -1578,6 +5480,35 @@ mod tests {
         assert_eq!(super::crc32(b""), 0);
     }
 
+    #[test]
+    fn test_snapshot_checksums_and_v1_snapshots() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("traj.log.snap");
+        let update = LocationUpdate {
+            timestamp: UNIX_EPOCH + Duration::from_secs(1),
+            position: Point3d::new(1.0, 2.0, 0.0),
+            metadata: serde_json::json!({"k": "v"}),
+        };
+        let state = HashMap::from([("ns::a".to_string(), update.clone())]);
+        let checksum = write_snapshot(&path, &state, 100, 7).unwrap();
+        let snapshot = read_snapshot(&path).unwrap();
+        assert_eq!(snapshot.checksum, Some(checksum));
+        assert_eq!((snapshot.covered_len, snapshot.sequence), (100, 7));
+        assert_eq!(snapshot.objects["ns::a"].as_ref(), Some(&update));
+
+        // The checksum covers the header too.
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, content.replacen(" 100 ", " 10 ", 1)).unwrap();
+        assert!(read_snapshot(&path).is_none());
+
+        // Snapshots from before checksums are still read.
+        let record = content.lines().nth(1).unwrap();
+        std::fs::write(&path, format!("#spatio-snap v1 100 7\n{}\n", record)).unwrap();
+        let snapshot = read_snapshot(&path).unwrap();
+        assert_eq!(snapshot.checksum, None);
+        assert_eq!(snapshot.objects.len(), 1);
+    }
+
     /// A corrupted record body (simulating bit-rot or a torn write) must fail
     /// its CRC and be skipped on recovery, without taking out healthy records.
     #[test]
@@ -1645,6 +5576,62 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_replay_numbers_records_past_lost_lines() {
+        let dir = tempdir().unwrap();
+        for compression in [LogCompression::None, LogCompression::Lz4] {
+            let log_path = dir.path().join(format!("{compression:?}.log"));
+            let open = || {
+                let persistence = PersistenceConfig {
+                    compression,
+                    ..Default::default()
+                };
+                ColdState::new(&log_path, 10, persistence, SyncSettings::default()).unwrap()
+            };
+            {
+                let cold = open();
+                // Three lines of a compressed log: blocks of three, one and
+                // two records.
+                for batch in [&["a", "lost", "b"][..], &["c"], &["d", "e"]] {
+                    for id in batch {
+                        cold.append_update(
+                            "ns",
+                            id,
+                            Point3d::new(1.0, 2.0, 0.0),
+                            serde_json::json!({}),
+                            UNIX_EPOCH + Duration::from_secs(1),
+                        )
+                        .unwrap();
+                    }
+                    cold.flush().unwrap();
+                }
+                assert_eq!(cold.last_sequence(), 6);
+            }
+
+            // Lose the line holding "lost", whatever else it holds.
+            let contents = std::fs::read_to_string(&log_path).unwrap();
+            let lost = match compression {
+                LogCompression::None => contents.lines().position(|l| l.contains("|lost|")),
+                _ => contents.lines().position(|l| l.contains("|~")),
+            };
+            let corrupted: Vec<String> = contents
+                .lines()
+                .enumerate()
+                .map(|(i, line)| match Some(i) == lost {
+                    true => line.replacen('|', "|x", 1),
+                    false => line.to_string(),
+                })
+                .collect();
+            std::fs::write(&log_path, corrupted.join("\n") + "\n").unwrap();
+
+            let cold = open();
+            let recovered = cold.recover_current_locations().unwrap();
+            assert!(!recovered.contains_key("ns::lost"));
+            assert!(recovered.contains_key("ns::e"));
+            assert_eq!(cold.last_sequence(), 6, "{compression:?}");
+        }
+    }
+
     /// Legacy V1 logs (no header, no CRC) must still be recoverable.
     #[test]
     fn test_legacy_v1_log_is_readable() {
@@ -1671,4 +5658,55 @@ mod tests {
         assert_eq!(loc.position.x(), 1.0);
         assert_eq!(loc.position.y(), 2.0);
     }
+
+    /// Once the disk stops taking writes, appends go on in memory; only
+    /// writes that must be durable, and flushes, fail.
+    #[test]
+    fn test_degraded_log_keeps_writes_in_memory() {
+        let dir = tempdir().unwrap();
+        let log_path = dir.path().join("traj.log");
+        let open = |policy| {
+            ColdState::new(
+                &log_path,
+                10,
+                PersistenceConfig::default(),
+                SyncSettings {
+                    policy,
+                    batch_size: 1,
+                    ..SyncSettings::default()
+                },
+            )
+            .unwrap()
+        };
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        let point = Point3d::new(1.0, 2.0, 0.0);
+
+        let cold = open(SyncPolicy::Always);
+        cold.append_update("v", "o", point.clone(), serde_json::json!({}), at(1))
+            .unwrap();
+        assert_eq!(cold.degraded(), None);
+        let full = std::io::Error::from(std::io::ErrorKind::StorageFull);
+        degrade(&cold.degraded, &full);
+
+        let err = cold
+            .append_update("v", "o", point.clone(), serde_json::json!({}), at(2))
+            .unwrap_err();
+        assert!(matches!(err, SpatioError::DegradedPersistence(_)), "{err}");
+        assert!(matches!(
+            cold.flush(),
+            Err(SpatioError::DegradedPersistence(_))
+        ));
+        assert_eq!(cold.last_sequence(), 2);
+        assert!(!cold.wait_durable(2, Duration::from_secs(5)));
+        let history = cold.query_trajectory("v", "o", at(0), at(10), 10).unwrap();
+        assert_eq!(history.len(), 2, "the in-memory write is still queryable");
+        drop(cold);
+
+        let cold = open(SyncPolicy::EverySecond);
+        let recovered = cold.recover_current_locations().unwrap();
+        assert_eq!(recovered["v::o"].timestamp, at(1));
+        degrade(&cold.degraded, &full);
+        cold.append_tombstone("v", "o").unwrap();
+        assert!(cold.flush().is_err());
+    }
 }
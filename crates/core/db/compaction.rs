@@ -0,0 +1,348 @@
+//! When scheduled compactions rewrite the trajectory log.
+//!
+//! Expiring history rewrites the whole log, which blocks writers for a full
+//! pass over it. It runs on open and, with
+//! [`Config::with_active_expiration`](crate::Config::with_active_expiration),
+//! on every background pass. A [`CompactionPolicy`] set with
+//! [`DBBuilder::compaction_policy`](crate::DBBuilder::compaction_policy)
+//! decides which of those runs go ahead: once the log has grown enough
+//! ([`SizeBased`]), once enough time has passed ([`TimeBased`]), once the log
+//! holds enough writes per object ([`KeyCountBased`]), and never within
+//! given hours ([`OutsideHours`]).
+//!
+//! Explicit calls such as [`DB::expire_history`](super::DB::expire_history)
+//! always run, and so does the expiration started by the disk guard when
+//! free space runs low.
+
+use parking_lot::Mutex;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// What a [`CompactionPolicy`] decides on.
+///
+/// "Since the last compaction" counts from the last one the policy let run;
+/// before the first, from the start of the log.
+#[derive(Debug, Clone)]
+pub struct CompactionContext {
+    /// Bytes in the trajectory log (0 for an in-memory database).
+    pub log_bytes: u64,
+    /// Bytes appended to the log since the last compaction.
+    pub log_growth_bytes: u64,
+    /// Updates and deletions written since the last compaction.
+    pub writes: u64,
+    /// Objects with a current location.
+    pub objects: usize,
+    /// Time since the last compaction, or `None` before the first.
+    pub since_compaction: Option<Duration>,
+    /// Time of the check.
+    pub now: SystemTime,
+}
+
+/// Decides when scheduled compactions of the trajectory log run.
+///
+/// Implemented for closures:
+///
+/// ```
+/// use spatio::db::CompactionContext;
+/// use spatio::DBBuilder;
+///
+/// let db = DBBuilder::new()
+///     .compaction_policy(|context: &CompactionContext| context.writes >= 10_000)
+///     .build()
+///     .unwrap();
+/// ```
+pub trait CompactionPolicy: Send + Sync + 'static {
+    /// Whether a scheduled compaction should run now.
+    fn should_compact(&self, context: &CompactionContext) -> bool;
+
+    /// Expired records a compaction must find before it rewrites the log, or
+    /// `None` for the configured batch size (see
+    /// [`Config::with_active_expiration`](crate::Config::with_active_expiration)).
+    fn min_removed(&self, _context: &CompactionContext) -> Option<u64> {
+        None
+    }
+}
+
+impl<F> CompactionPolicy for F
+where
+    F: Fn(&CompactionContext) -> bool + Send + Sync + 'static,
+{
+    fn should_compact(&self, context: &CompactionContext) -> bool {
+        self(context)
+    }
+}
+
+/// Compacts once the log has grown by `min_growth_bytes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeBased {
+    pub min_growth_bytes: u64,
+}
+
+impl SizeBased {
+    pub fn new(min_growth_bytes: u64) -> Self {
+        Self { min_growth_bytes }
+    }
+}
+
+impl CompactionPolicy for SizeBased {
+    fn should_compact(&self, context: &CompactionContext) -> bool {
+        context.log_growth_bytes >= self.min_growth_bytes
+    }
+}
+
+/// Compacts at most once per `interval`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeBased {
+    pub interval: Duration,
+}
+
+impl TimeBased {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval }
+    }
+}
+
+impl CompactionPolicy for TimeBased {
+    fn should_compact(&self, context: &CompactionContext) -> bool {
+        context
+            .since_compaction
+            .is_none_or(|since| since >= self.interval)
+    }
+}
+
+/// Compacts once the writes since the last compaction reach
+/// `writes_per_object` for every object with a current location, that is
+/// once most of what was written is history rather than current state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyCountBased {
+    pub writes_per_object: u64,
+}
+
+impl KeyCountBased {
+    pub fn new(writes_per_object: u64) -> Self {
+        Self { writes_per_object }
+    }
+}
+
+impl CompactionPolicy for KeyCountBased {
+    fn should_compact(&self, context: &CompactionContext) -> bool {
+        let objects = (context.objects as u64).max(1);
+        context.writes >= self.writes_per_object.saturating_mul(objects)
+    }
+}
+
+/// Defers to `policy`, except from `start_hour` until `end_hour` (UTC plus
+/// `utc_offset_mins`), when it never compacts. A window that ends before it
+/// starts runs past midnight, so `OutsideHours::new(policy, 22, 6)` blocks
+/// the night.
+///
+/// ```
+/// use spatio::db::{OutsideHours, TimeBased};
+/// use std::time::Duration;
+///
+/// // Hourly, but not 9:00-17:00 Central European Time on weekdays.
+/// let policy = OutsideHours::new(TimeBased::new(Duration::from_secs(3600)), 9, 17)
+///     .with_utc_offset_mins(60)
+///     .weekdays_only();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutsideHours<P> {
+    pub policy: P,
+    pub start_hour: u8,
+    pub end_hour: u8,
+    pub utc_offset_mins: i32,
+    /// Block the hours only Monday to Friday.
+    pub weekdays_only: bool,
+}
+
+impl<P: CompactionPolicy> OutsideHours<P> {
+    /// # Panics
+    ///
+    /// If either hour is past 24.
+    pub fn new(policy: P, start_hour: u8, end_hour: u8) -> Self {
+        assert!(
+            start_hour <= 24 && end_hour <= 24,
+            "hours must be between 0 and 24"
+        );
+        Self {
+            policy,
+            start_hour,
+            end_hour,
+            utc_offset_mins: 0,
+            weekdays_only: false,
+        }
+    }
+
+    /// Read the hours in a time zone `mins` ahead of UTC.
+    pub fn with_utc_offset_mins(mut self, mins: i32) -> Self {
+        self.utc_offset_mins = mins;
+        self
+    }
+
+    /// Block the hours only Monday to Friday.
+    pub fn weekdays_only(mut self) -> Self {
+        self.weekdays_only = true;
+        self
+    }
+
+    /// Whether `now` falls within the blocked hours.
+    fn blocked(&self, now: SystemTime) -> bool {
+        let secs = now
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs() as i64)
+            + i64::from(self.utc_offset_mins) * 60;
+        let days = secs.div_euclid(86_400);
+        // The epoch was a Thursday; Monday is day 0.
+        if self.weekdays_only && (days + 3).rem_euclid(7) >= 5 {
+            return false;
+        }
+        let hour = secs.rem_euclid(86_400) / 3600;
+        let (start, end) = (i64::from(self.start_hour), i64::from(self.end_hour));
+        if start <= end {
+            (start..end).contains(&hour)
+        } else {
+            hour >= start || hour < end
+        }
+    }
+}
+
+impl<P: CompactionPolicy> CompactionPolicy for OutsideHours<P> {
+    fn should_compact(&self, context: &CompactionContext) -> bool {
+        !self.blocked(context.now) && self.policy.should_compact(context)
+    }
+
+    fn min_removed(&self, context: &CompactionContext) -> Option<u64> {
+        self.policy.min_removed(context)
+    }
+}
+
+/// Where the last compaction left the log.
+struct Mark {
+    at: Instant,
+    log_bytes: u64,
+    sequence: u64,
+}
+
+/// A database's compaction policy and when it last let a compaction run.
+#[derive(Default)]
+pub(crate) struct CompactionSchedule {
+    policy: Option<Arc<dyn CompactionPolicy>>,
+    last: Mutex<Option<Mark>>,
+}
+
+impl CompactionSchedule {
+    pub fn new(policy: Option<Arc<dyn CompactionPolicy>>) -> Self {
+        Self {
+            policy,
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Expired records a compaction must remove to rewrite the log, or
+    /// `None` if the policy holds it off. Without a policy every compaction
+    /// runs, with `batch_size`.
+    pub fn plan(
+        &self,
+        log_bytes: u64,
+        sequence: u64,
+        objects: usize,
+        batch_size: u64,
+    ) -> Option<u64> {
+        let Some(policy) = &self.policy else {
+            return Some(batch_size);
+        };
+        let last = self.last.lock();
+        let context = CompactionContext {
+            log_bytes,
+            log_growth_bytes: log_bytes
+                .saturating_sub(last.as_ref().map_or(0, |mark| mark.log_bytes)),
+            writes: sequence.saturating_sub(last.as_ref().map_or(0, |mark| mark.sequence)),
+            objects,
+            since_compaction: last.as_ref().map(|mark| mark.at.elapsed()),
+            now: SystemTime::now(),
+        };
+        drop(last);
+        policy
+            .should_compact(&context)
+            .then(|| policy.min_removed(&context).unwrap_or(batch_size))
+    }
+
+    /// Record a compaction that left `log_bytes` in the log, after the
+    /// record with `sequence`.
+    pub fn compacted(&self, log_bytes: u64, sequence: u64) {
+        *self.last.lock() = Some(Mark {
+            at: Instant::now(),
+            log_bytes,
+            sequence,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(now: SystemTime) -> CompactionContext {
+        CompactionContext {
+            log_bytes: 0,
+            log_growth_bytes: 0,
+            writes: 0,
+            objects: 0,
+            since_compaction: None,
+            now,
+        }
+    }
+
+    #[test]
+    fn test_outside_hours_blocks_the_window() {
+        // 2024-01-01 was a Monday.
+        let monday = UNIX_EPOCH + Duration::from_secs(1_704_067_200);
+        let at = |day: u64, hour: u64| monday + Duration::from_secs(day * 86_400 + hour * 3600);
+        let always = |_: &CompactionContext| true;
+
+        let office = OutsideHours::new(always, 9, 17);
+        assert!(office.should_compact(&context(at(0, 8))));
+        assert!(!office.should_compact(&context(at(0, 9))));
+        assert!(!office.should_compact(&context(at(0, 16))));
+        assert!(office.should_compact(&context(at(0, 17))));
+        assert!(!office.should_compact(&context(at(5, 12))));
+
+        // Weekends are free; offsets shift the window.
+        let office = office.weekdays_only();
+        assert!(office.should_compact(&context(at(5, 12))));
+        assert!(!office.should_compact(&context(at(4, 12))));
+        let office = office.with_utc_offset_mins(-120);
+        assert!(office.should_compact(&context(at(0, 10))));
+        assert!(!office.should_compact(&context(at(0, 11))));
+
+        // A window past midnight.
+        let night = OutsideHours::new(always, 22, 6);
+        assert!(!night.should_compact(&context(at(0, 23))));
+        assert!(!night.should_compact(&context(at(1, 5))));
+        assert!(night.should_compact(&context(at(1, 6))));
+    }
+
+    #[test]
+    fn test_schedule_counts_from_the_last_compaction() {
+        let schedule = CompactionSchedule::new(Some(Arc::new(SizeBased::new(100))));
+        assert_eq!(schedule.plan(99, 10, 1, 5), None);
+        assert_eq!(schedule.plan(150, 10, 1, 5), Some(5));
+        schedule.compacted(120, 10);
+        assert_eq!(schedule.plan(150, 20, 1, 5), None);
+        assert_eq!(schedule.plan(220, 20, 1, 5), Some(5));
+
+        let schedule = CompactionSchedule::new(Some(Arc::new(KeyCountBased::new(3))));
+        assert_eq!(schedule.plan(0, 5, 2, 1), None);
+        assert_eq!(schedule.plan(0, 6, 2, 1), Some(1));
+        schedule.compacted(0, 6);
+        assert_eq!(schedule.plan(0, 11, 2, 1), None);
+
+        let schedule =
+            CompactionSchedule::new(Some(Arc::new(TimeBased::new(Duration::from_secs(3600)))));
+        assert_eq!(schedule.plan(0, 0, 0, 1), Some(1));
+        schedule.compacted(0, 0);
+        assert_eq!(schedule.plan(0, 0, 0, 1), None);
+
+        assert_eq!(CompactionSchedule::default().plan(0, 0, 0, 7), Some(7));
+    }
+}
@@ -0,0 +1,190 @@
+//! Compression of trajectory log records.
+//!
+//! The log stays line-oriented text. A compressed log holds back appended
+//! records and writes them as one block line when it is flushed or the block
+//! fills up: their bodies are joined with newlines, compressed together, and
+//! stored as `~` followed by a codec letter and the base64 of the compressed
+//! bytes, under a CRC like any other record. Compressing records together is
+//! what pays off, since consecutive records repeat the same namespaces,
+//! object IDs and metadata keys. The codec a log writes is named in its
+//! header (see [`LogCompression::name`]), and every block names its own
+//! codec, so readers never need the configuration.
+//!
+//! An uncompressed log writes blocks only for batches, which must reach the
+//! log whole or not at all; their bodies are stored as is (letter `p`).
+
+use crate::config::LogCompression;
+use crate::error::{Result, SpatioError};
+use std::borrow::Cow;
+
+/// Marks a compressed block.
+const MARKER: char = '~';
+
+/// Fail if this build can't write logs with `codec`.
+pub(crate) fn check_supported(codec: LogCompression) -> Result<()> {
+    if codec == LogCompression::Zstd && !cfg!(feature = "zstd") {
+        return Err(SpatioError::InvalidInput(
+            "zstd log compression needs the `zstd` feature".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Codec named by a log header suffix.
+pub(crate) fn codec_named(name: &str) -> Option<LogCompression> {
+    [LogCompression::Lz4, LogCompression::Zstd]
+        .into_iter()
+        .find(|codec| codec.name() == Some(name))
+}
+
+/// `text` compressed with `codec` into a block (stored uncompressed for
+/// [`LogCompression::None`]).
+pub(crate) fn compress(text: &str, codec: LogCompression) -> std::io::Result<String> {
+    let (letter, compressed) = match codec {
+        LogCompression::None => ('p', text.as_bytes().to_vec()),
+        LogCompression::Lz4 => ('l', lz4_flex::compress_prepend_size(text.as_bytes())),
+        #[cfg(feature = "zstd")]
+        LogCompression::Zstd => ('z', zstd::encode_all(text.as_bytes(), 0)?),
+        #[cfg(not(feature = "zstd"))]
+        LogCompression::Zstd => return Err(std::io::Error::other("built without zstd")),
+    };
+    // Two characters of prefix, then four per three bytes.
+    let mut block = String::with_capacity(2 + compressed.len().div_ceil(3) * 4);
+    block.push(MARKER);
+    block.push(letter);
+    base64_encode(&compressed, &mut block);
+    Ok(block)
+}
+
+/// Record bodies stored in a log line, newline-separated: those of a
+/// block, or the line itself if it isn't one. `None` if the block can't be
+/// decompressed.
+pub(crate) fn decompress(stored: &str) -> Option<Cow<'_, str>> {
+    let Some(rest) = stored.strip_prefix(MARKER) else {
+        return Some(Cow::Borrowed(stored));
+    };
+    let mut chars = rest.chars();
+    let letter = chars.next()?;
+    let compressed = base64_decode(chars.as_str())?;
+    let plain = match letter {
+        'p' => compressed,
+        'l' => lz4_flex::decompress_size_prepended(&compressed).ok()?,
+        #[cfg(feature = "zstd")]
+        'z' => zstd::decode_all(compressed.as_slice()).ok()?,
+        #[cfg(not(feature = "zstd"))]
+        'z' => {
+            log::warn!("Skipping zstd-compressed log records; build with the `zstd` feature");
+            return None;
+        }
+        _ => return None,
+    };
+    String::from_utf8(plain).ok().map(Cow::Owned)
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard base64 with padding. Implemented inline to avoid adding a
+/// dependency.
+fn base64_encode(bytes: &[u8], out: &mut String) {
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.as_bytes();
+    if !text.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    for chunk in text.chunks(4) {
+        let padding = chunk.iter().rev().take_while(|&&c| c == b'=').count();
+        if padding > 2 {
+            return None;
+        }
+        let mut n = 0u32;
+        for &c in &chunk[..4 - padding] {
+            let value = match c {
+                b'A'..=b'Z' => c - b'A',
+                b'a'..=b'z' => c - b'a' + 26,
+                b'0'..=b'9' => c - b'0' + 52,
+                b'+' => 62,
+                b'/' => 63,
+                _ => return None,
+            };
+            n = n << 6 | value as u32;
+        }
+        n <<= 6 * padding;
+        out.extend_from_slice(&n.to_be_bytes()[1..4 - padding]);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_round_trips() {
+        for bytes in [&b""[..], b"f", b"fo", b"foo", b"foob", b"fooba", b"foobar"] {
+            let mut encoded = String::new();
+            base64_encode(bytes, &mut encoded);
+            assert_eq!(base64_decode(&encoded).unwrap(), bytes);
+        }
+        let mut encoded = String::new();
+        base64_encode(b"foobar", &mut encoded);
+        assert_eq!(encoded, "Zm9vYmFy");
+        assert!(base64_decode("Zm9=v").is_none());
+        assert!(base64_decode("Zm9*").is_none());
+    }
+
+    #[test]
+    fn test_blocks_round_trip_and_shrink() {
+        let block = (0..50)
+            .map(|i| {
+                let metadata = format!(r#"{{"driver":"alice","route":"north","stop":{}}}"#, i);
+                format!(
+                    "17000000{:08}|fleet|truck-1|40.7|-74.0|0.0|{}|{}",
+                    i,
+                    metadata.len(),
+                    metadata
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut codecs = vec![LogCompression::Lz4];
+        if cfg!(feature = "zstd") {
+            codecs.push(LogCompression::Zstd);
+        }
+        for codec in codecs {
+            let stored = compress(&block, codec).unwrap();
+            assert!(stored.starts_with(MARKER));
+            assert!(
+                stored.len() * 4 < block.len(),
+                "{codec:?}: {}",
+                stored.len()
+            );
+            assert!(!stored.contains(['\n', '\r', '|']));
+            assert_eq!(decompress(&stored).unwrap(), block);
+        }
+
+        let stored = compress(&block, LogCompression::None).unwrap();
+        assert!(stored.starts_with("~p"));
+        assert!(!stored.contains(['\n', '\r', '|']));
+        assert_eq!(decompress(&stored).unwrap(), block);
+
+        let tombstone = "TOMBSTONE|1700000000000000|fleet|truck-1";
+        assert_eq!(decompress(tombstone).unwrap(), tombstone);
+        assert!(decompress("~lnot base64").is_none());
+    }
+}
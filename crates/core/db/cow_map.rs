@@ -0,0 +1,130 @@
+//! A string-keyed map whose snapshots share memory with it.
+//!
+//! Entries are spread over [`SHARDS`] hash maps, each held by an `Arc` behind
+//! its own lock. [`CowMap::snapshot`] clones the `Arc`s, not the entries; a
+//! write to a shard that a snapshot still holds copies that shard first, so
+//! the cost of a snapshot is paid one shard at a time by the writers that
+//! touch it, and only once per snapshot.
+
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+use std::sync::Arc;
+
+/// Number of shards; a write after a snapshot copies about `1 / SHARDS` of
+/// the map.
+const SHARDS: usize = 256;
+
+type Shard<V> = RwLock<Arc<HashMap<String, V>>>;
+
+pub(crate) struct CowMap<V> {
+    shards: Box<[Shard<V>]>,
+    hasher: RandomState,
+}
+
+impl<V: Clone> CowMap<V> {
+    pub fn new() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| RwLock::default()).collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    fn shard(&self, key: &str) -> &Shard<V> {
+        &self.shards[self.hasher.hash_one(key) as usize % SHARDS]
+    }
+
+    /// A map holding the current entries, sharing them until either side
+    /// writes.
+    pub fn snapshot(&self) -> Self {
+        Self {
+            shards: self
+                .shards
+                .iter()
+                .map(|shard| RwLock::new(shard.read().clone()))
+                .collect(),
+            hasher: self.hasher.clone(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<V> {
+        self.shard(key).read().get(key).cloned()
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.shard(key).read().contains_key(key)
+    }
+
+    /// Run `f` on the shard holding `key`, write-locked and copied first if a
+    /// snapshot shares it.
+    pub fn with_shard<R>(&self, key: &str, f: impl FnOnce(&mut HashMap<String, V>) -> R) -> R {
+        f(Arc::make_mut(&mut self.shard(key).write()))
+    }
+
+    pub fn insert(&self, key: String, value: V) -> Option<V> {
+        let shard = self.shard(&key);
+        Arc::make_mut(&mut shard.write()).insert(key, value)
+    }
+
+    pub fn remove(&self, key: &str) -> Option<V> {
+        let mut shard = self.shard(key).write();
+        // Only copy a shared shard when there is something to remove.
+        if !shard.contains_key(key) {
+            return None;
+        }
+        Arc::make_mut(&mut shard).remove(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.read().len()).sum()
+    }
+
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            *shard.write() = Arc::default();
+        }
+    }
+
+    /// Visit every entry. Each shard is visited as it was when reached, and
+    /// without holding its lock.
+    pub fn for_each(&self, mut f: impl FnMut(&str, &V)) {
+        for shard in self.shards.iter() {
+            let entries = shard.read().clone();
+            for (key, value) in entries.iter() {
+                f(key, value);
+            }
+        }
+    }
+
+    /// Every value, in no particular order.
+    pub fn values(&self) -> Vec<V> {
+        let mut values = Vec::new();
+        self.for_each(|_, value| values.push(value.clone()));
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_is_unaffected_by_later_writes() {
+        let map = CowMap::new();
+        for i in 0..1000 {
+            map.insert(format!("k{i}"), i);
+        }
+        let snapshot = map.snapshot();
+        map.insert("k1".to_string(), -1);
+        map.remove("k2");
+        map.insert("new".to_string(), 7);
+
+        assert_eq!(snapshot.get("k1"), Some(1));
+        assert_eq!(snapshot.get("k2"), Some(2));
+        assert!(!snapshot.contains_key("new"));
+        assert_eq!(snapshot.len(), 1000);
+        assert_eq!(map.get("k1"), Some(-1));
+        assert_eq!(map.get("k2"), None);
+        assert_eq!(map.len(), 1000);
+    }
+}
@@ -0,0 +1,240 @@
+//! Free disk space watermarks.
+//!
+//! With [`Config::with_disk_watermarks`](crate::Config::with_disk_watermarks)
+//! a thread checks the file system holding the log periodically. Below the
+//! soft watermark it warns and removes expired history to reclaim space;
+//! below the hard watermark [`DiskGuard::check_write`] refuses writes that
+//! would append to the log, so a full disk can't cut a record short.
+
+use super::cold_state::ColdState;
+use super::namespace_settings::NamespaceSettings;
+use crate::config::DiskWatermarkConfig;
+use crate::error::{Result, SpatioError};
+use parking_lot::{Condvar, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+/// Free space is above the soft watermark.
+const OK: u8 = 0;
+/// Free space is below the soft watermark.
+const LOW: u8 = 1;
+/// Free space is below the hard watermark; writes are refused.
+const CRITICAL: u8 = 2;
+
+/// Latest free space reading, shared with the checking thread.
+struct DiskLevel {
+    level: AtomicU8,
+    available: AtomicU64,
+}
+
+/// Thread checking free space. Dropping it stops and joins the thread.
+pub(crate) struct DiskGuard {
+    state: Arc<DiskLevel>,
+    hard_bytes: u64,
+    /// Set to stop the thread.
+    shutdown: Arc<(Mutex<bool>, Condvar)>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl DiskGuard {
+    /// Check the file system holding `dir` now, then every
+    /// `config.check_interval_ms`. Expired history is removed from `cold`
+    /// per the retention in `settings` each time free space drops below the soft watermark,
+    /// adding the records removed to `expired`.
+    pub fn spawn(
+        dir: PathBuf,
+        cold: Arc<ColdState>,
+        settings: Arc<NamespaceSettings>,
+        config: &DiskWatermarkConfig,
+        expired: Arc<AtomicU64>,
+    ) -> std::io::Result<Self> {
+        // Fail here, rather than warn on every check, where free space can't
+        // be read at all.
+        available_space(&dir)?;
+        let state = Arc::new(DiskLevel {
+            level: AtomicU8::new(OK),
+            available: AtomicU64::new(u64::MAX),
+        });
+        let check = Checker {
+            dir,
+            state: state.clone(),
+            soft_bytes: config.soft_bytes,
+            hard_bytes: config.hard_bytes,
+            cold,
+            settings,
+            expired,
+        };
+        check.run();
+
+        let shutdown = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread_shutdown = shutdown.clone();
+        let interval = Duration::from_millis(config.check_interval_ms);
+        let handle = std::thread::Builder::new()
+            .name("spatio-disk".to_string())
+            .spawn(move || {
+                let (stopped, wake) = &*thread_shutdown;
+                loop {
+                    {
+                        let mut stopped = stopped.lock();
+                        if !*stopped {
+                            wake.wait_for(&mut stopped, interval);
+                        }
+                        if *stopped {
+                            return;
+                        }
+                    }
+                    check.run();
+                }
+            })?;
+        Ok(Self {
+            state,
+            hard_bytes: config.hard_bytes,
+            shutdown,
+            handle: Mutex::new(Some(handle)),
+        })
+    }
+
+    /// Refuse a write that appends to the log while free space is below the
+    /// hard watermark.
+    #[inline]
+    pub fn check_write(&self) -> Result<()> {
+        if self.state.level.load(Ordering::Acquire) == CRITICAL {
+            return Err(SpatioError::InsufficientDiskSpace {
+                available: self.state.available.load(Ordering::Relaxed),
+                required: self.hard_bytes,
+            });
+        }
+        Ok(())
+    }
+
+    /// Free bytes at the last check.
+    pub fn available(&self) -> u64 {
+        self.state.available.load(Ordering::Relaxed)
+    }
+
+    /// Stop the thread, waiting for a check in progress.
+    pub fn stop(&self) {
+        let (stopped, wake) = &*self.shutdown;
+        *stopped.lock() = true;
+        wake.notify_one();
+        if let Some(handle) = self.handle.lock().take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for DiskGuard {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// One free space check and what it triggers.
+struct Checker {
+    dir: PathBuf,
+    state: Arc<DiskLevel>,
+    soft_bytes: u64,
+    hard_bytes: u64,
+    cold: Arc<ColdState>,
+    settings: Arc<NamespaceSettings>,
+    expired: Arc<AtomicU64>,
+}
+
+impl Checker {
+    fn run(&self) {
+        let available = match available_space(&self.dir) {
+            Ok(available) => available,
+            Err(e) => {
+                log::warn!(
+                    "Failed to read free disk space of {}: {}",
+                    self.dir.display(),
+                    e
+                );
+                return;
+            }
+        };
+        self.state.available.store(available, Ordering::Relaxed);
+        let level = if available < self.hard_bytes {
+            CRITICAL
+        } else if available < self.soft_bytes {
+            LOW
+        } else {
+            OK
+        };
+        let previous = self.state.level.swap(level, Ordering::AcqRel);
+        if level == previous {
+            return;
+        }
+        match level {
+            CRITICAL => log::error!(
+                "Only {} bytes free on {} (hard watermark {}); refusing writes",
+                available,
+                self.dir.display(),
+                self.hard_bytes
+            ),
+            LOW => log::warn!(
+                "Only {} bytes free on {} (soft watermark {})",
+                available,
+                self.dir.display(),
+                self.soft_bytes
+            ),
+            _ => log::info!(
+                "{} bytes free on {}; disk space recovered",
+                available,
+                self.dir.display()
+            ),
+        }
+        if level > previous {
+            self.reclaim();
+        }
+    }
+
+    /// Remove expired history to make room.
+    fn reclaim(&self) {
+        let retention = self.settings.retention();
+        match self.cold.expire_history(&retention, SystemTime::now()) {
+            Ok(removed) => {
+                self.expired.fetch_add(removed, Ordering::Relaxed);
+            }
+            Err(e) => log::warn!("Failed to reclaim disk space: {}", e),
+        }
+    }
+}
+
+/// Bytes available to unprivileged users on the file system holding `path`.
+#[cfg(unix)]
+pub(crate) fn available_space(path: &Path) -> std::io::Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is NUL-terminated and `stat` is valid for writes.
+    if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: statvfs succeeded, so it filled `stat` in.
+    let stat = unsafe { stat.assume_init() };
+    // The field types differ between platforms.
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn available_space(_path: &Path) -> std::io::Result<u64> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_available_space_reads_the_file_system() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(available_space(dir.path()).unwrap() > 0);
+        assert!(available_space(&dir.path().join("missing")).is_err());
+    }
+}
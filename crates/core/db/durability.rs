@@ -0,0 +1,78 @@
+//! Durability watermark for the trajectory log.
+//!
+//! Every record appended to the log is assigned a sequence number. The
+//! watermark is the highest sequence known to be on stable storage; it is
+//! advanced after each `fsync`, whether the sync ran inline on a writer's
+//! thread or on the background sync thread.
+//!
+//! The cold state keeps a second one for the sequence writes have been
+//! applied up to, which reads holding a write's sequence wait on.
+
+use parking_lot::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+pub(crate) struct DurabilityWatermark {
+    durable: Mutex<u64>,
+    advanced: Condvar,
+}
+
+impl DurabilityWatermark {
+    /// Last durable sequence (0 until the first sync).
+    pub(crate) fn get(&self) -> u64 {
+        *self.durable.lock()
+    }
+
+    /// Raise the watermark to `sequence` (it never moves backwards) and wake
+    /// any waiters.
+    pub(crate) fn advance(&self, sequence: u64) {
+        let mut durable = self.durable.lock();
+        if sequence > *durable {
+            *durable = sequence;
+            self.advanced.notify_all();
+        }
+    }
+
+    /// Block until the watermark reaches `sequence` or `timeout` elapses.
+    /// Returns whether `sequence` is durable.
+    pub(crate) fn wait_for(&self, sequence: u64, timeout: Duration) -> bool {
+        let deadline = Instant::now().checked_add(timeout);
+        let mut durable = self.durable.lock();
+        while *durable < sequence {
+            match deadline {
+                Some(deadline) => {
+                    if self.advanced.wait_until(&mut durable, deadline).timed_out() {
+                        return *durable >= sequence;
+                    }
+                }
+                None => self.advanced.wait(&mut durable),
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_waiters_wake_when_watermark_passes_them() {
+        let watermark = Arc::new(DurabilityWatermark::default());
+        assert!(!watermark.wait_for(1, Duration::from_millis(5)));
+
+        let waiter = {
+            let watermark = watermark.clone();
+            std::thread::spawn(move || watermark.wait_for(3, Duration::from_secs(10)))
+        };
+        watermark.advance(2);
+        watermark.advance(5);
+        assert!(waiter.join().unwrap());
+
+        // The watermark never moves backwards.
+        watermark.advance(4);
+        assert_eq!(watermark.get(), 5);
+        assert!(watermark.wait_for(5, Duration::ZERO));
+    }
+}
@@ -0,0 +1,72 @@
+//! Background removal of expired trajectory history and objects.
+//!
+//! History past its namespace's retention is hidden from reads right away,
+//! but only leaves the log when it is rewritten, and objects past their
+//! namespace's TTL stay until deleted. With
+//! [`Config::with_active_expiration`](crate::Config::with_active_expiration)
+//! a thread does both periodically instead of waiting for the next open or a
+//! [`DB::expire_history`](super::DB::expire_history) or
+//! [`DB::expire_objects`](super::DB::expire_objects) call.
+
+use crate::config::ActiveExpirationConfig;
+use parking_lot::{Condvar, Mutex};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Thread running expiration passes. Dropping it stops and joins the thread,
+/// letting a pass in progress finish.
+pub(crate) struct ActiveExpiration {
+    /// Set to stop the thread.
+    shutdown: Arc<(Mutex<bool>, Condvar)>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl ActiveExpiration {
+    /// Run `pass` every `config.interval_ms`.
+    pub fn spawn(
+        config: &ActiveExpirationConfig,
+        mut pass: impl FnMut() + Send + 'static,
+    ) -> std::io::Result<Self> {
+        let shutdown = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread_shutdown = shutdown.clone();
+        let interval = Duration::from_millis(config.interval_ms);
+        let handle = std::thread::Builder::new()
+            .name("spatio-expire".to_string())
+            .spawn(move || {
+                let (stopped, wake) = &*thread_shutdown;
+                loop {
+                    {
+                        let mut stopped = stopped.lock();
+                        if !*stopped {
+                            wake.wait_for(&mut stopped, interval);
+                        }
+                        if *stopped {
+                            return;
+                        }
+                    }
+                    pass();
+                }
+            })?;
+        Ok(Self {
+            shutdown,
+            handle: Mutex::new(Some(handle)),
+        })
+    }
+
+    /// Stop the thread, waiting for a pass in progress.
+    pub fn stop(&self) {
+        let (stopped, wake) = &*self.shutdown;
+        *stopped.lock() = true;
+        wake.notify_one();
+        if let Some(handle) = self.handle.lock().take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for ActiveExpiration {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,306 @@
+//! Geofences: circles, boxes and polygons that report objects entering,
+//! leaving and dwelling inside them.
+//!
+//! Like [materialized views](super::views), each write re-evaluates only the
+//! written object, and changes are published to subscribers as
+//! [`FenceEvent`]s. Each namespace keeps its fences' bounding boxes in an
+//! R-tree, so a write is only tested against the fences whose box holds the
+//! object and those it was inside, however many fences the namespace has.
+//!
+//! Dwell time is measured with location timestamps rather than the wall
+//! clock: an object dwells once an update inside the fence is stamped at least
+//! the fence's dwell time after the update that brought it in. Evaluation only
+//! happens on writes, so an object that stops reporting never dwells.
+
+use crate::compute::query::{Predicate, envelope, matches};
+use crate::db::CurrentLocation;
+use parking_lot::{Mutex, RwLock};
+use rstar::RTree;
+use rstar::primitives::{GeomWithData, Rectangle};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, SystemTime};
+
+use super::subscribers::Subscribers;
+
+/// How a fence reports the objects inside it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FenceOptions {
+    /// Report objects that stay inside this long with a
+    /// [`FenceEvent::Dwell`]; no dwell events when `None`.
+    pub dwell: Option<Duration>,
+}
+
+impl FenceOptions {
+    pub fn with_dwell(mut self, dwell: Duration) -> Self {
+        self.dwell = Some(dwell);
+        self
+    }
+}
+
+/// An object crossing or staying inside a fence.
+#[derive(Debug, Clone)]
+pub enum FenceEvent {
+    /// The object moved into the fence (or was first written inside it).
+    Enter(Arc<CurrentLocation>),
+    /// The object has stayed inside for the fence's dwell time. Sent once per
+    /// stay.
+    Dwell(Arc<CurrentLocation>),
+    /// The object moved out of the fence, carrying its first location
+    /// outside; or it was deleted, carrying its last location inside.
+    Exit(Arc<CurrentLocation>),
+}
+
+/// Receiving end of a fence subscription, bounded as described in
+/// [`subscribers`](super::subscribers).
+pub type FenceSubscription = Receiver<FenceEvent>;
+
+/// An object inside a fence.
+struct Inside {
+    location: Arc<CurrentLocation>,
+    entered: SystemTime,
+    dwelled: bool,
+}
+
+pub(crate) struct Fence {
+    namespace: String,
+    predicate: Predicate,
+    dwell: Option<Duration>,
+    inside: Mutex<BTreeMap<String, Inside>>,
+    subscribers: Subscribers<FenceEvent>,
+}
+
+impl Fence {
+    fn publish(&self, event: FenceEvent) {
+        self.subscribers.publish(&event, |_| true);
+    }
+
+    /// Whether `inside` has stayed long enough to dwell as of `location`.
+    fn dwells(&self, inside: &Inside, location: &CurrentLocation) -> bool {
+        let Some(dwell) = self.dwell else {
+            return false;
+        };
+        !inside.dwelled
+            && location
+                .timestamp
+                .duration_since(inside.entered)
+                .is_ok_and(|stayed| stayed >= dwell)
+    }
+}
+
+/// A fence's bounding box in its namespace's R-tree, tagged with its name.
+type FenceBox = GeomWithData<Rectangle<[f64; 2]>, String>;
+
+/// The R-tree entry of fence `name`, `None` if `predicate` matches nothing.
+fn fence_box(name: &str, predicate: &Predicate) -> Option<FenceBox> {
+    let bounds = envelope(predicate);
+    if bounds.is_empty() {
+        return None;
+    }
+    let corners = Rectangle::from_corners(
+        [bounds.min[0], bounds.min[1]],
+        [bounds.max[0], bounds.max[1]],
+    );
+    Some(FenceBox::new(corners, name.to_string()))
+}
+
+/// The fences of one namespace.
+#[derive(Default)]
+struct NamespaceFences {
+    boxes: RTree<FenceBox>,
+    /// Names of the fences each object is inside, so a write moving it out
+    /// is tested against them.
+    occupied: HashMap<String, Vec<String>>,
+}
+
+/// All fences of a database, by name.
+///
+/// Locks are taken in the order namespace, `fences`, then a fence's `inside`.
+/// Writes to a namespace with fences are evaluated one at a time under its
+/// lock, which keeps `occupied` in step with the fences' members.
+#[derive(Default)]
+pub(crate) struct Fences {
+    fences: RwLock<HashMap<String, Arc<Fence>>>,
+    namespaces: RwLock<HashMap<String, Arc<Mutex<NamespaceFences>>>>,
+}
+
+impl Fences {
+    /// Register a fence named `name`, filling it with the objects `populate`
+    /// finds inside before any write can touch it. Those objects count as
+    /// having entered at their current timestamp, without an event. Returns
+    /// `false` if the name is taken.
+    pub fn create(
+        &self,
+        name: &str,
+        namespace: &str,
+        shape: spatio_types::fence::Fence,
+        options: FenceOptions,
+        populate: impl FnOnce(&Predicate) -> Vec<Arc<CurrentLocation>>,
+    ) -> bool {
+        let fence = Arc::new(Fence {
+            namespace: namespace.to_string(),
+            predicate: shape.into(),
+            dwell: options.dwell,
+            inside: Mutex::new(BTreeMap::new()),
+            subscribers: Subscribers::default(),
+        });
+        let shard = self
+            .namespaces
+            .write()
+            .entry(namespace.to_string())
+            .or_default()
+            .clone();
+        // As in `Views::create`, but with the namespace locked.
+        let mut shard = shard.lock();
+        let mut inside = fence.inside.lock();
+        {
+            let mut fences = self.fences.write();
+            if fences.contains_key(name) {
+                return false;
+            }
+            fences.insert(name.to_string(), fence.clone());
+        }
+        if let Some(fence_box) = fence_box(name, &fence.predicate) {
+            shard.boxes.insert(fence_box);
+        }
+        for loc in populate(&fence.predicate) {
+            shard
+                .occupied
+                .entry(loc.object_id.clone())
+                .or_default()
+                .push(name.to_string());
+            let entry = Inside {
+                entered: loc.timestamp,
+                location: loc.clone(),
+                dwelled: false,
+            };
+            inside.insert(loc.object_id.clone(), entry);
+        }
+        true
+    }
+
+    /// Remove a fence, disconnecting its subscribers.
+    pub fn drop_fence(&self, name: &str) -> bool {
+        let Some(fence) = self.get(name) else {
+            return false;
+        };
+        // Locked before the fence leaves `fences`, so no write sees it half
+        // removed.
+        let Some(shard) = self.namespaces.read().get(&fence.namespace).cloned() else {
+            return false;
+        };
+        let mut shard = shard.lock();
+        if self.fences.write().remove(name).is_none() {
+            return false;
+        }
+        if let Some(fence_box) = fence_box(name, &fence.predicate) {
+            shard.boxes.remove(&fence_box);
+        }
+        shard.occupied.retain(|_, names| {
+            names.retain(|n| n != name);
+            !names.is_empty()
+        });
+        true
+    }
+
+    fn get(&self, name: &str) -> Option<Arc<Fence>> {
+        self.fences.read().get(name).cloned()
+    }
+
+    /// Objects inside a fence in object ID order, or `None` if there is no
+    /// such fence.
+    pub fn members(&self, name: &str) -> Option<Vec<Arc<CurrentLocation>>> {
+        let fence = self.get(name)?;
+        let inside = fence.inside.lock();
+        Some(
+            inside
+                .values()
+                .map(|entry| entry.location.clone())
+                .collect(),
+        )
+    }
+
+    pub fn subscribe(&self, name: &str) -> Option<FenceSubscription> {
+        Some(self.get(name)?.subscribers.subscribe(()))
+    }
+
+    /// Re-evaluate `object_id` against the fences of `namespace` it is in or
+    /// may have moved into. `current` reads the object's latest location
+    /// (`None` once deleted).
+    pub fn refresh(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        current: impl Fn() -> Option<Arc<CurrentLocation>>,
+    ) {
+        let Some(shard) = self.namespaces.read().get(namespace).cloned() else {
+            return;
+        };
+        let mut shard = shard.lock();
+        let location = current();
+        let mut names = shard.occupied.remove(object_id).unwrap_or_default();
+        if let Some(loc) = &location {
+            for fence_box in shard
+                .boxes
+                .locate_all_at_point(&[loc.position.x(), loc.position.y()])
+            {
+                if !names.contains(&fence_box.data) {
+                    names.push(fence_box.data.clone());
+                }
+            }
+        }
+        let affected: Vec<(String, Arc<Fence>)> = {
+            let fences = self.fences.read();
+            names
+                .into_iter()
+                .filter_map(|name| {
+                    let fence = fences.get(&name)?.clone();
+                    Some((name, fence))
+                })
+                .collect()
+        };
+
+        let mut occupied = Vec::new();
+        for (name, fence) in affected {
+            let mut inside = fence.inside.lock();
+            let within = location
+                .as_ref()
+                .filter(|loc| matches(&fence.predicate, &loc.position, &loc.metadata));
+            // Published under the lock so subscribers see changes in order.
+            match (within, inside.get_mut(object_id)) {
+                (Some(loc), Some(entry)) => {
+                    occupied.push(name);
+                    entry.location = loc.clone();
+                    if fence.dwells(entry, loc) {
+                        entry.dwelled = true;
+                        fence.publish(FenceEvent::Dwell(loc.clone()));
+                    }
+                }
+                (Some(loc), None) => {
+                    let mut entry = Inside {
+                        location: loc.clone(),
+                        entered: loc.timestamp,
+                        dwelled: false,
+                    };
+                    fence.publish(FenceEvent::Enter(loc.clone()));
+                    if fence.dwells(&entry, loc) {
+                        entry.dwelled = true;
+                        fence.publish(FenceEvent::Dwell(loc.clone()));
+                    }
+                    inside.insert(object_id.to_string(), entry);
+                    occupied.push(name);
+                }
+                (None, Some(_)) => {
+                    if let Some(entry) = inside.remove(object_id) {
+                        fence.publish(FenceEvent::Exit(location.clone().unwrap_or(entry.location)));
+                    }
+                }
+                (None, None) => {}
+            }
+        }
+        if !occupied.is_empty() {
+            shard.occupied.insert(object_id.to_string(), occupied);
+        }
+    }
+}
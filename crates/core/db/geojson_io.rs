@@ -0,0 +1,525 @@
+//! GeoJSON interchange with GIS tools.
+//!
+//! [`DB::export_geojson`] writes a namespace as one `FeatureCollection`, the
+//! form QGIS and Leaflet load directly: current locations as `Point`
+//! features, trajectories as `LineString` features with a parallel
+//! `timestamps` property, and zones as `Polygon` features.
+//! [`DB::import_geojson`] reads the same shapes back.
+
+use super::DB;
+use crate::compute::spatial::ZoneGeometry;
+use crate::compute::validation;
+use crate::config::{SetOptions, TimeRange};
+use crate::error::{Result, SpatioError};
+use geojson::feature::Id;
+use geojson::{Feature, FeatureCollection, GeoJson, Geometry, Value};
+use serde_json::Map;
+use spatio_types::config::ScanDirection;
+use spatio_types::geo::Polygon;
+use spatio_types::point::Point3d;
+use spatio_types::stats::Operation;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How [`DB::import_geojson`] reads features.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoJsonImportOptions {
+    /// Property holding the object ID of `Point` and `LineString` features.
+    /// Features without it use their `id` member.
+    pub id_property: String,
+    /// Time given to `Point` features without a `timestamp` property; the
+    /// time of the import when `None`.
+    pub default_timestamp: Option<SystemTime>,
+}
+
+impl Default for GeoJsonImportOptions {
+    fn default() -> Self {
+        Self {
+            id_property: "object_id".to_string(),
+            default_timestamp: None,
+        }
+    }
+}
+
+/// What [`DB::import_geojson`] wrote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GeoJsonImportReport {
+    /// `Point` features written as current locations.
+    pub points: usize,
+    /// `LineString` vertices written as trajectory history.
+    pub trajectory_points: usize,
+    /// `Polygon` features stored as zones.
+    pub zones: usize,
+}
+
+/// A feature read for import.
+enum Item {
+    Point {
+        object_id: String,
+        timestamp: SystemTime,
+        position: Point3d,
+        metadata: serde_json::Value,
+    },
+    Trajectory {
+        object_id: String,
+        points: Vec<(SystemTime, Point3d)>,
+    },
+    Zone {
+        zone_id: String,
+        polygon: Polygon,
+        metadata: serde_json::Value,
+    },
+}
+
+fn seconds(t: SystemTime) -> f64 {
+    t.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+fn time_from_seconds(value: &serde_json::Value) -> Option<SystemTime> {
+    let since_epoch = Duration::try_from_secs_f64(value.as_f64()?).ok()?;
+    Some(UNIX_EPOCH + since_epoch)
+}
+
+fn position(coords: &[f64]) -> Result<Point3d> {
+    if !(2..=3).contains(&coords.len()) {
+        return Err(SpatioError::InvalidInput(
+            "Position must have 2 or 3 coordinates".to_string(),
+        ));
+    }
+    let position = Point3d::new(coords[0], coords[1], coords.get(2).copied().unwrap_or(0.0));
+    validation::validate_geographic_point_3d(&position)?;
+    Ok(position)
+}
+
+fn ring(coords: &[Vec<f64>]) -> Result<geo::LineString<f64>> {
+    coords
+        .iter()
+        .map(|c| position(c).map(|p| geo::Coord { x: p.x(), y: p.y() }))
+        .collect()
+}
+
+fn feature(geometry: Value, properties: Map<String, serde_json::Value>) -> Feature {
+    Feature {
+        bbox: None,
+        geometry: Some(Geometry::new(geometry)),
+        id: None,
+        properties: Some(properties),
+        foreign_members: None,
+    }
+}
+
+/// The feature's ID: the `property` if it is a string, else its `id` member.
+fn feature_id(
+    id: Option<Id>,
+    properties: &mut Map<String, serde_json::Value>,
+    property: &str,
+) -> Result<String> {
+    let id = match (properties.remove(property), id) {
+        (Some(serde_json::Value::String(id)), _) => id,
+        (_, Some(Id::String(id))) => id,
+        (_, Some(Id::Number(id))) => id.to_string(),
+        _ => {
+            return Err(SpatioError::InvalidInput(format!(
+                "Feature has no string {property} property or id"
+            )));
+        }
+    };
+    super::validate_identifier(property, &id)?;
+    Ok(id)
+}
+
+/// The `metadata` property if there is one, otherwise the remaining
+/// properties, as [`crate::compute::import`] reads them.
+fn metadata(mut properties: Map<String, serde_json::Value>) -> serde_json::Value {
+    match properties.remove("metadata") {
+        Some(metadata) => metadata,
+        None => serde_json::Value::Object(properties),
+    }
+}
+
+fn parse_item(feature: Feature, options: &GeoJsonImportOptions) -> Result<Item> {
+    let mut properties = feature.properties.unwrap_or_default();
+    let geometry = feature
+        .geometry
+        .ok_or_else(|| SpatioError::InvalidInput("Feature has no geometry".to_string()))?;
+    match geometry.value {
+        Value::Point(coords) => {
+            let object_id = feature_id(feature.id, &mut properties, &options.id_property)?;
+            let timestamp = match properties.remove("timestamp") {
+                Some(value) => time_from_seconds(&value).ok_or_else(|| {
+                    SpatioError::InvalidInput(
+                        "timestamp must be non-negative seconds since the Unix epoch".to_string(),
+                    )
+                })?,
+                None => options.default_timestamp.unwrap_or_else(SystemTime::now),
+            };
+            Ok(Item::Point {
+                object_id,
+                timestamp,
+                position: position(&coords)?,
+                metadata: metadata(properties),
+            })
+        }
+        Value::LineString(coords) => {
+            let object_id = feature_id(feature.id, &mut properties, &options.id_property)?;
+            let timestamps = match properties.remove("timestamps") {
+                Some(serde_json::Value::Array(timestamps)) if timestamps.len() == coords.len() => {
+                    timestamps
+                }
+                _ => {
+                    return Err(SpatioError::InvalidInput(
+                        "LineString needs a timestamps property with one time per vertex"
+                            .to_string(),
+                    ));
+                }
+            };
+            let points = timestamps
+                .iter()
+                .zip(&coords)
+                .map(|(timestamp, coords)| {
+                    let timestamp = time_from_seconds(timestamp).ok_or_else(|| {
+                        SpatioError::InvalidInput(
+                            "timestamps must be non-negative seconds since the Unix epoch"
+                                .to_string(),
+                        )
+                    })?;
+                    Ok((timestamp, position(coords)?))
+                })
+                .collect::<Result<_>>()?;
+            Ok(Item::Trajectory { object_id, points })
+        }
+        Value::Polygon(rings) => {
+            let zone_id = feature_id(feature.id, &mut properties, "zone_id")?;
+            let (exterior, interiors) = rings.split_first().ok_or_else(|| {
+                SpatioError::InvalidInput("Polygon must have at least one ring".to_string())
+            })?;
+            let polygon = Polygon::new(
+                ring(exterior)?,
+                interiors.iter().map(|r| ring(r)).collect::<Result<_>>()?,
+            );
+            validation::validate_polygon(&polygon)?;
+            Ok(Item::Zone {
+                zone_id,
+                polygon,
+                metadata: metadata(properties),
+            })
+        }
+        other => Err(SpatioError::InvalidInput(format!(
+            "Unsupported geometry type {}; expected Point, LineString or Polygon",
+            other.type_name()
+        ))),
+    }
+}
+
+impl DB {
+    /// Everything stored in `namespace` as a GeoJSON `FeatureCollection`.
+    ///
+    /// Current locations become `Point` features (`[lon, lat, alt]`) with
+    /// `object_id`, `timestamp` (seconds since the Unix epoch) and `metadata`
+    /// properties, in object ID order. Each object with at least two history
+    /// points within the namespace's retention also gets a `LineString`
+    /// feature with `object_id` and a `timestamps` array, one per vertex.
+    /// Zones become `Polygon` features with `zone_id` and `metadata`
+    /// properties, in zone ID order; bounding-box zones are written as
+    /// rectangles.
+    pub fn export_geojson(&self, namespace: &str) -> Result<FeatureCollection> {
+        db_span!("spatio.export_geojson", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::Export)?;
+        super::validate_identifier("namespace", namespace)?;
+
+        let mut features = Vec::new();
+        for loc in self
+            .hot
+            .range(namespace, .., usize::MAX, ScanDirection::Forward)
+        {
+            let mut properties = Map::new();
+            properties.insert("object_id".to_string(), loc.object_id.clone().into());
+            properties.insert("timestamp".to_string(), seconds(loc.timestamp).into());
+            properties.insert("metadata".to_string(), loc.metadata.clone());
+            let p = &loc.position;
+            features.push(feature(Value::Point(vec![p.x(), p.y(), p.z()]), properties));
+        }
+
+        let now = SystemTime::now();
+        let (start, end) = TimeRange::all().resolve(now);
+        let start = self.history_cutoff(namespace, now).unwrap_or(start);
+        let history = self.cold.scan_namespace(namespace, start, end)?;
+        for track in history.chunk_by(|(a, _), (b, _)| a == b) {
+            if track.len() < 2 {
+                continue;
+            }
+            let mut properties = Map::new();
+            properties.insert("object_id".to_string(), track[0].0.clone().into());
+            properties.insert(
+                "timestamps".to_string(),
+                track
+                    .iter()
+                    .map(|(_, u)| serde_json::Value::from(seconds(u.timestamp)))
+                    .collect(),
+            );
+            let line = track
+                .iter()
+                .map(|(_, u)| vec![u.position.x(), u.position.y(), u.position.z()])
+                .collect();
+            features.push(feature(Value::LineString(line), properties));
+        }
+
+        let mut zones = self.hot.zones(namespace);
+        zones.sort_by(|a, b| a.zone_id.cmp(&b.zone_id));
+        for zone in zones {
+            let rings = match &zone.geometry {
+                ZoneGeometry::BBox(b) => vec![vec![
+                    vec![b.min_x(), b.min_y()],
+                    vec![b.max_x(), b.min_y()],
+                    vec![b.max_x(), b.max_y()],
+                    vec![b.min_x(), b.max_y()],
+                    vec![b.min_x(), b.min_y()],
+                ]],
+                ZoneGeometry::Polygon(polygon) => std::iter::once(polygon.exterior())
+                    .chain(polygon.interiors())
+                    .map(|ring| ring.coords().map(|c| vec![c.x, c.y]).collect())
+                    .collect(),
+            };
+            let mut properties = Map::new();
+            properties.insert("zone_id".to_string(), zone.zone_id.clone().into());
+            properties.insert("metadata".to_string(), zone.metadata.clone());
+            features.push(feature(Value::Polygon(rings), properties));
+        }
+
+        Ok(FeatureCollection {
+            bbox: None,
+            features,
+            foreign_members: None,
+        })
+    }
+
+    /// Load a GeoJSON `FeatureCollection` (or a single `Feature`) into
+    /// `namespace`, reading the shapes [`DB::export_geojson`] writes.
+    ///
+    /// `Point` features set current locations. `LineString` features with a
+    /// `timestamps` property add their vertices to the object's history,
+    /// without metadata; a vertex stamped with the time of the object's
+    /// `Point` feature is left to that feature. `Polygon` features are stored
+    /// as zones. Metadata is the `metadata` property if there is one, and
+    /// otherwise the remaining properties, so files drawn in a GIS tool
+    /// import with their attributes.
+    ///
+    /// Every feature is checked before anything is written: an invalid one
+    /// fails the import and the error names it.
+    ///
+    /// ```
+    /// use spatio::{DB, GeoJsonImportOptions, Point3d};
+    ///
+    /// let source = DB::memory().unwrap();
+    /// source
+    ///     .upsert("fleet", "truck", Point3d::new(1.0, 2.0, 0.0), serde_json::json!({"v": 1}), None)
+    ///     .unwrap();
+    /// let geojson = source.export_geojson("fleet").unwrap().to_string();
+    ///
+    /// let target = DB::memory().unwrap();
+    /// let report = target
+    ///     .import_geojson("fleet", &geojson, &GeoJsonImportOptions::default())
+    ///     .unwrap();
+    /// assert_eq!(report.points, 1);
+    /// assert_eq!(target.get("fleet", "truck").unwrap().unwrap().metadata["v"], 1);
+    /// ```
+    pub fn import_geojson(
+        &self,
+        namespace: &str,
+        geojson: &str,
+        options: &GeoJsonImportOptions,
+    ) -> Result<GeoJsonImportReport> {
+        db_span!("spatio.import_geojson", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::InsertTrajectory)?;
+        super::validate_identifier("namespace", namespace)?;
+
+        let features = match geojson.parse::<GeoJson>() {
+            Ok(GeoJson::FeatureCollection(collection)) => collection.features,
+            Ok(GeoJson::Feature(feature)) => vec![feature],
+            Ok(GeoJson::Geometry(_)) => {
+                return Err(SpatioError::InvalidInput(
+                    "GeoJSON import needs features, not a bare geometry".to_string(),
+                ));
+            }
+            Err(e) => {
+                return Err(SpatioError::InvalidInput(format!("Invalid GeoJSON: {}", e)));
+            }
+        };
+        let items = features
+            .into_iter()
+            .enumerate()
+            .map(|(i, feature)| {
+                parse_item(feature, options)
+                    .map_err(|e| SpatioError::InvalidInput(format!("GeoJSON feature {i}: {e}")))
+            })
+            .collect::<Result<Vec<Item>>>()?;
+
+        // One group commit for the whole import, rather than one per point.
+        self.cold.group(|| {
+            let point_times: HashMap<&str, SystemTime> = items
+                .iter()
+                .filter_map(|item| match item {
+                    Item::Point {
+                        object_id,
+                        timestamp,
+                        ..
+                    } => Some((object_id.as_str(), *timestamp)),
+                    _ => None,
+                })
+                .collect();
+            let mut report = GeoJsonImportReport::default();
+            // History first, so the points' current locations win.
+            for item in &items {
+                if let Item::Trajectory { object_id, points } = item {
+                    let current = point_times.get(object_id.as_str());
+                    for (timestamp, position) in points {
+                        if current == Some(timestamp) {
+                            continue;
+                        }
+                        self.write_point(
+                            namespace,
+                            object_id,
+                            position.clone(),
+                            serde_json::json!({}),
+                            Some(SetOptions::with_timestamp(*timestamp)),
+                        )?;
+                        report.trajectory_points += 1;
+                    }
+                }
+            }
+            for item in items {
+                match item {
+                    Item::Point {
+                        object_id,
+                        timestamp,
+                        position,
+                        metadata,
+                    } => {
+                        self.write_point(
+                            namespace,
+                            &object_id,
+                            position,
+                            metadata,
+                            Some(SetOptions::with_timestamp(timestamp)),
+                        )?;
+                        report.points += 1;
+                    }
+                    Item::Zone {
+                        zone_id,
+                        polygon,
+                        metadata,
+                    } => {
+                        self.insert_zone(
+                            namespace,
+                            &zone_id,
+                            ZoneGeometry::Polygon(polygon),
+                            metadata,
+                        )?;
+                        report.zones += 1;
+                    }
+                    Item::Trajectory { .. } => {}
+                }
+            }
+            Ok(report)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spatio_types::bbox::BoundingBox2D;
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let source = DB::memory().unwrap();
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        for (secs, x) in [(100, 1.0), (200, 1.5), (300, 2.0)] {
+            source
+                .upsert(
+                    "fleet",
+                    "truck",
+                    Point3d::new(x, 2.0, 10.0),
+                    serde_json::json!({"speed": secs}),
+                    Some(SetOptions::with_timestamp(at(secs))),
+                )
+                .unwrap();
+        }
+        source
+            .insert_zone(
+                "fleet",
+                "depot",
+                ZoneGeometry::BBox(BoundingBox2D::new(0.0, 0.0, 1.0, 1.0)),
+                serde_json::json!({"name": "north"}),
+            )
+            .unwrap();
+
+        let collection = source.export_geojson("fleet").unwrap();
+        let kinds: Vec<_> = collection
+            .features
+            .iter()
+            .map(|f| f.geometry.as_ref().unwrap().value.type_name())
+            .collect();
+        assert_eq!(kinds, ["Point", "LineString", "Polygon"]);
+
+        let target = DB::memory().unwrap();
+        let report = target
+            .import_geojson(
+                "fleet",
+                &collection.to_string(),
+                &GeoJsonImportOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(
+            report,
+            GeoJsonImportReport {
+                points: 1,
+                trajectory_points: 2,
+                zones: 1,
+            }
+        );
+
+        let truck = target.get("fleet", "truck").unwrap().unwrap();
+        assert_eq!(truck.position, Point3d::new(2.0, 2.0, 10.0));
+        assert_eq!(truck.metadata["speed"], 300);
+        let history = target
+            .query_trajectory("fleet", "truck", ..=at(1_000), 10)
+            .unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[2].timestamp, at(100));
+        let depot = target.get_zone("fleet", "depot").unwrap().unwrap();
+        assert_eq!(depot.metadata["name"], "north");
+    }
+
+    #[test]
+    fn test_import_reads_gis_features_and_rejects_bad_ones() {
+        let db = DB::memory().unwrap();
+        let drawn = r#"{"type":"FeatureCollection","features":[
+            {"type":"Feature","id":"cafe","geometry":{"type":"Point","coordinates":[13.4,52.5]},"properties":{"name":"Kiez"}}
+        ]}"#;
+        let options = GeoJsonImportOptions {
+            default_timestamp: Some(UNIX_EPOCH + Duration::from_secs(5)),
+            ..Default::default()
+        };
+        db.import_geojson("places", drawn, &options).unwrap();
+        let cafe = db.get("places", "cafe").unwrap().unwrap();
+        assert_eq!(cafe.metadata, serde_json::json!({"name": "Kiez"}));
+
+        let bad = r#"{"type":"FeatureCollection","features":[
+            {"type":"Feature","geometry":{"type":"Point","coordinates":[1.0,1.0]},"properties":{"object_id":"ok"}},
+            {"type":"Feature","geometry":{"type":"Point","coordinates":[500.0,1.0]},"properties":{"object_id":"far"}}
+        ]}"#;
+        let err = db.import_geojson("places", bad, &options).unwrap_err();
+        assert!(err.to_string().contains("feature 1"), "{err}");
+        assert!(db.get("places", "ok").unwrap().is_none());
+    }
+}
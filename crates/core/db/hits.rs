@@ -0,0 +1,74 @@
+//! Named results of distance queries.
+//!
+//! Radius, cylinder and nearest-neighbor queries return [`NearbyHit`]s and
+//! nearest-zone queries [`ZoneHit`]s rather than tuples, so fields can be
+//! added later without breaking callers. Both convert to and from the
+//! `(item, distance)` tuples these queries used to return.
+
+use crate::db::{CurrentLocation, Zone};
+use std::sync::Arc;
+
+/// An object found by a distance query.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct NearbyHit {
+    pub location: Arc<CurrentLocation>,
+    /// Distance from the query's center or anchor, in meters (coordinate
+    /// units under the Euclidean metric).
+    pub distance: f64,
+}
+
+impl NearbyHit {
+    pub fn new(location: Arc<CurrentLocation>, distance: f64) -> Self {
+        Self { location, distance }
+    }
+
+    pub fn object_id(&self) -> &str {
+        &self.location.object_id
+    }
+}
+
+impl From<(Arc<CurrentLocation>, f64)> for NearbyHit {
+    fn from((location, distance): (Arc<CurrentLocation>, f64)) -> Self {
+        Self::new(location, distance)
+    }
+}
+
+impl From<NearbyHit> for (Arc<CurrentLocation>, f64) {
+    fn from(hit: NearbyHit) -> Self {
+        (hit.location, hit.distance)
+    }
+}
+
+/// A zone found by a nearest-zone query.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ZoneHit {
+    pub zone: Arc<Zone>,
+    /// Distance in meters from the query point to the zone's boundary;
+    /// `0.0` when the point is inside.
+    pub distance: f64,
+}
+
+impl ZoneHit {
+    pub fn new(zone: Arc<Zone>, distance: f64) -> Self {
+        Self { zone, distance }
+    }
+}
+
+impl From<(Arc<Zone>, f64)> for ZoneHit {
+    fn from((zone, distance): (Arc<Zone>, f64)) -> Self {
+        Self::new(zone, distance)
+    }
+}
+
+impl From<ZoneHit> for (Arc<Zone>, f64) {
+    fn from(hit: ZoneHit) -> Self {
+        (hit.zone, hit.distance)
+    }
+}
+
+/// Convert tuples from the hot state into named hits.
+pub(crate) fn named<T, H: From<(Arc<T>, f64)>>(results: Vec<(Arc<T>, f64)>) -> Vec<H> {
+    results.into_iter().map(H::from).collect()
+}
@@ -0,0 +1,491 @@
+//! Write hooks: callbacks run for every write committed to a namespace.
+//!
+//! Hooks are for side effects outside the database (forwarding writes to a
+//! webhook, invalidating a cache). Delivery is at least once: a hook that
+//! returns an error gets the same event again, after a backoff, until it
+//! succeeds, and later events wait behind it. Each event carries its log
+//! sequence, so a consumer that may see an event twice can drop the repeat.
+//!
+//! Each hook keeps a cursor, the sequence through which it has handled every
+//! write to its namespace, saved beside the log (`<log>.hooks`) about once a
+//! second and on close. Registering a hook under a name that has a cursor,
+//! in this process or after a restart, first replays the writes after it
+//! from the trajectory log; removing a hook forgets its cursor. Writes
+//! compacted out of the log before a hook handled them are not replayed.
+//!
+//! At most `QUEUE_CAPACITY` events wait in memory for a hook. A write that
+//! finds the queue full empties it, and the hook goes back to its cursor
+//! and reads the writes from the log instead: a stalled hook costs log scans
+//! and repeated events, never unbounded memory or blocked writers.
+//! [`DB::hook_backlog`](super::DB::hook_backlog) tells when a hook has caught
+//! up.
+
+use crate::db::LocationUpdate;
+use crate::db::cold_state::ColdState;
+use parking_lot::{Condvar, Mutex, RwLock, RwLockReadGuard};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// First wait before redelivering an event a hook failed on, doubled after
+/// each further failure.
+const RETRY_INITIAL_BACKOFF: Duration = Duration::from_millis(10);
+const RETRY_MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Events waiting for a hook before it falls back to reading the log.
+pub(crate) const QUEUE_CAPACITY: usize = 10_000;
+
+/// How often a hook that handled events saves its cursor.
+const CURSOR_SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A write committed to the trajectory log.
+#[derive(Debug, Clone)]
+pub struct HookEvent {
+    /// Log sequence of the write (see [`DB::last_sequence`](super::DB::last_sequence)).
+    pub sequence: u64,
+    pub namespace: String,
+    pub object_id: String,
+    /// The location written, or `None` for a deletion.
+    pub update: Option<LocationUpdate>,
+}
+
+/// A callback for committed writes.
+///
+/// Implemented for closures:
+///
+/// ```
+/// use spatio::db::HookEvent;
+/// use spatio::{HookMode, Spatio};
+///
+/// let db = Spatio::memory().unwrap();
+/// db.register_hook("audit", "fleet", HookMode::Async, |event: &HookEvent| {
+///     println!("{} changed at sequence {}", event.object_id, event.sequence);
+///     Ok(())
+/// })
+/// .unwrap();
+/// ```
+pub trait WriteHook: Send + Sync + 'static {
+    /// Handle one write. `Err` (with a reason, which is logged) asks for the
+    /// event again later.
+    fn on_commit(&self, event: &HookEvent) -> Result<(), String>;
+}
+
+impl<F> WriteHook for F
+where
+    F: Fn(&HookEvent) -> Result<(), String> + Send + Sync + 'static,
+{
+    fn on_commit(&self, event: &HookEvent) -> Result<(), String> {
+        self(event)
+    }
+}
+
+/// Where a hook runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookMode {
+    /// On the writing thread, before the write returns. Writers to the
+    /// namespace take turns running the hook. A failed event, and every
+    /// event after it until the backlog clears, is redelivered in the
+    /// background instead. A sync hook must not write to its own namespace.
+    Sync,
+    /// On a background thread; writers only queue the event.
+    Async,
+}
+
+struct HookState {
+    queue: VecDeque<HookEvent>,
+    /// Events read back from the log, handled before `queue`.
+    replay: VecDeque<HookEvent>,
+    /// Writes still to be read back from the log: after the first sequence
+    /// and through the second. `queue` only holds later ones.
+    pending: Option<(u64, u64)>,
+    /// Set when events were dropped: the hook must go back to `cursor`, and
+    /// drops new events until it has.
+    overflowed: bool,
+    /// Every write to the namespace through this sequence has been handled.
+    cursor: u64,
+    /// The cursor as last saved.
+    saved: u64,
+    stopped: bool,
+}
+
+impl HookState {
+    /// Whether every event the hook was handed is in `queue`.
+    fn is_live(&self) -> bool {
+        !self.overflowed && self.pending.is_none() && self.replay.is_empty()
+    }
+}
+
+/// What the hooks of a database share with their workers.
+struct Shared {
+    cold: Arc<ColdState>,
+    /// Held for reading by writers from logging a write to publishing it, so
+    /// with it held for writing, every write the log holds has reached the
+    /// hooks.
+    gate: RwLock<()>,
+    /// Last saved cursor of each hook, by name.
+    cursors: Mutex<HashMap<String, u64>>,
+}
+
+impl Shared {
+    /// Sequence through which every write has reached the hooks.
+    fn published(&self) -> u64 {
+        let _gate = self.gate.write();
+        self.cold.last_sequence()
+    }
+
+    /// Save the cursor of hook `name`, or forget it for `None`.
+    fn save_cursor(&self, name: &str, cursor: Option<u64>) {
+        let mut cursors = self.cursors.lock();
+        match cursor {
+            Some(cursor) => cursors.insert(name.to_string(), cursor),
+            None => cursors.remove(name),
+        };
+        if let Err(e) = self.cold.write_hook_cursors(&cursors) {
+            log::warn!("Failed to save write hook cursors: {}", e);
+        }
+    }
+}
+
+struct Hook {
+    name: String,
+    namespace: String,
+    mode: HookMode,
+    callback: Box<dyn WriteHook>,
+    shared: Arc<Shared>,
+    state: Mutex<HookState>,
+    wake: Condvar,
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Hook {
+    fn deliver(&self, event: HookEvent) {
+        let mut state = self.state.lock();
+        if state.overflowed {
+            // Logged, so read back once the hook goes back to its cursor.
+            return;
+        }
+        if self.mode == HookMode::Sync && state.queue.is_empty() && state.is_live() {
+            // Under the lock, so inline deliveries keep their order.
+            match self.callback.on_commit(&event) {
+                Ok(()) => return,
+                Err(e) => log::warn!("Write hook '{}' failed, will retry: {}", self.name, e),
+            }
+        }
+        if state.queue.len() >= QUEUE_CAPACITY {
+            log::warn!(
+                "Write hook '{}' is {} events behind, reading them back from the log",
+                self.name,
+                QUEUE_CAPACITY
+            );
+            state.overflowed = true;
+            state.queue.clear();
+            state.replay.clear();
+            state.pending = None;
+        } else {
+            state.queue.push_back(event);
+        }
+        self.wake.notify_one();
+    }
+
+    /// Deliver events until stopped.
+    fn run(&self) {
+        let mut backoff = RETRY_INITIAL_BACKOFF;
+        let mut next_save = Instant::now() + CURSOR_SAVE_INTERVAL;
+        let mut state = self.state.lock();
+        loop {
+            if state.stopped {
+                return;
+            }
+            if Instant::now() >= next_save {
+                drop(state);
+                self.save();
+                next_save = Instant::now() + CURSOR_SAVE_INTERVAL;
+                state = self.state.lock();
+                continue;
+            }
+            if state.overflowed {
+                drop(state);
+                self.rewind();
+                state = self.state.lock();
+                continue;
+            }
+            if state.replay.is_empty()
+                && let Some((after, through)) = state.pending
+            {
+                drop(state);
+                let read = self.shared.cold.namespace_records(
+                    &self.namespace,
+                    after,
+                    through,
+                    QUEUE_CAPACITY,
+                );
+                state = self.state.lock();
+                match read {
+                    // Unless an overflow reset the hook meanwhile.
+                    Ok(records) if state.pending == Some((after, through)) => {
+                        state.pending = match records.last() {
+                            Some((last, ..)) => Some((*last, through)),
+                            None => {
+                                state.cursor = through;
+                                None
+                            }
+                        };
+                        let namespace = &self.namespace;
+                        state.replay.extend(records.into_iter().map(
+                            |(sequence, object_id, update)| HookEvent {
+                                sequence,
+                                namespace: namespace.clone(),
+                                object_id,
+                                update,
+                            },
+                        ));
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        log::warn!("Write hook '{}' can't read the log: {}", self.name, e);
+                        self.wake.wait_for(&mut state, backoff);
+                        backoff = (backoff * 2).min(RETRY_MAX_BACKOFF);
+                    }
+                }
+                continue;
+            }
+            let Some(event) = state.replay.front().or(state.queue.front()).cloned() else {
+                self.wake.wait_until(&mut state, next_save);
+                continue;
+            };
+            // Sync writers queue behind the event while it is out, keeping
+            // the order.
+            drop(state);
+            let result = self.callback.on_commit(&event);
+            state = self.state.lock();
+            match result {
+                Ok(()) => {
+                    // An overflow may have emptied the queues meanwhile.
+                    let handled = |events: &VecDeque<HookEvent>| {
+                        events.front().is_some_and(|e| e.sequence == event.sequence)
+                    };
+                    if handled(&state.replay) {
+                        state.replay.pop_front();
+                        state.cursor = event.sequence;
+                    } else if handled(&state.queue) {
+                        state.queue.pop_front();
+                    }
+                    backoff = RETRY_INITIAL_BACKOFF;
+                }
+                Err(e) => {
+                    log::warn!("Write hook '{}' failed, will retry: {}", self.name, e);
+                    if !state.stopped {
+                        self.wake.wait_for(&mut state, backoff);
+                    }
+                    backoff = (backoff * 2).min(RETRY_MAX_BACKOFF);
+                }
+            }
+        }
+    }
+
+    /// Go back to the cursor after an overflow: read the writes logged so
+    /// far back from the log, and queue later ones again.
+    fn rewind(&self) {
+        let _gate = self.shared.gate.write();
+        let through = self.shared.cold.last_sequence();
+        let mut state = self.state.lock();
+        state.overflowed = false;
+        state.pending = (through > state.cursor).then_some((state.cursor, through));
+    }
+
+    /// Advance the cursor past the events handled so far and save it if it
+    /// moved.
+    fn save(&self) {
+        let published = self.shared.published();
+        let cursor = {
+            let mut state = self.state.lock();
+            if state.is_live() {
+                // Every write through `published` was handled or is queued.
+                let waiting = state.queue.iter().map(|e| e.sequence - 1).min();
+                state.cursor = state
+                    .cursor
+                    .max(waiting.unwrap_or(published).min(published));
+            }
+            if state.cursor == state.saved {
+                return;
+            }
+            state.saved = state.cursor;
+            state.cursor
+        };
+        self.shared.save_cursor(&self.name, Some(cursor));
+    }
+
+    fn stop(&self) {
+        self.state.lock().stopped = true;
+        self.wake.notify_one();
+        if let Some(worker) = self.worker.lock().take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// All write hooks of a database, by name, with the namespace each watches.
+pub(crate) struct Hooks {
+    hooks: RwLock<HashMap<String, (String, Arc<Hook>)>>,
+    /// Length of `hooks`, readable without the lock.
+    registered: AtomicUsize,
+    shared: Arc<Shared>,
+}
+
+impl Hooks {
+    /// Hooks of the database logging to `cold`, with the cursors saved
+    /// beside its log.
+    pub fn new(cold: Arc<ColdState>) -> Self {
+        Self {
+            hooks: RwLock::default(),
+            registered: AtomicUsize::new(0),
+            shared: Arc::new(Shared {
+                cursors: Mutex::new(cold.read_hook_cursors()),
+                cold,
+                gate: RwLock::new(()),
+            }),
+        }
+    }
+
+    /// Whether any hook is registered, i.e. whether writers should build
+    /// events.
+    pub fn is_active(&self) -> bool {
+        self.registered.load(Ordering::Acquire) > 0
+    }
+
+    /// Held by a writer from logging a write until it is published, and
+    /// taken before checking [`Self::is_active`] for it.
+    pub fn publishing(&self) -> RwLockReadGuard<'_, ()> {
+        // Recursive: a sync hook may write to another namespace.
+        self.shared.gate.read_recursive()
+    }
+
+    /// Register a hook on `namespace` under `name`, replaying the writes
+    /// after its saved cursor first. Returns `Ok(false)` if the name is
+    /// taken.
+    pub fn register(
+        &self,
+        name: &str,
+        namespace: &str,
+        mode: HookMode,
+        callback: impl WriteHook,
+    ) -> std::io::Result<bool> {
+        let cursor = {
+            // With writers shut out, each write is either logged by now or
+            // published to the new hook.
+            let _gate = self.shared.gate.write();
+            let mut hooks = self.hooks.write();
+            if hooks.contains_key(name) {
+                return Ok(false);
+            }
+            let through = self.shared.cold.last_sequence();
+            let saved = self.shared.cursors.lock().get(name).copied();
+            let cursor = saved.map_or(through, |saved| saved.min(through));
+            let hook = Arc::new(Hook {
+                name: name.to_string(),
+                namespace: namespace.to_string(),
+                mode,
+                callback: Box::new(callback),
+                shared: self.shared.clone(),
+                state: Mutex::new(HookState {
+                    queue: VecDeque::new(),
+                    replay: VecDeque::new(),
+                    pending: (cursor < through).then_some((cursor, through)),
+                    overflowed: false,
+                    cursor,
+                    saved: cursor,
+                    stopped: false,
+                }),
+                wake: Condvar::new(),
+                worker: Mutex::new(None),
+            });
+            let worker = {
+                let hook = hook.clone();
+                std::thread::Builder::new()
+                    .name(format!("spatio-hook-{name}"))
+                    .spawn(move || hook.run())?
+            };
+            *hook.worker.lock() = Some(worker);
+            hooks.insert(name.to_string(), (namespace.to_string(), hook));
+            self.registered.store(hooks.len(), Ordering::Release);
+            cursor
+        };
+        self.shared.save_cursor(name, Some(cursor));
+        Ok(true)
+    }
+
+    /// Remove a hook, discarding events it has not handled yet and its
+    /// cursor.
+    pub fn remove(&self, name: &str) -> bool {
+        let removed = {
+            let mut hooks = self.hooks.write();
+            let removed = hooks.remove(name);
+            self.registered.store(hooks.len(), Ordering::Release);
+            removed
+        };
+        match removed {
+            Some((_, hook)) => {
+                hook.stop();
+                self.shared.save_cursor(name, None);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Events waiting for a hook, plus one while it has writes to read back
+    /// from the log, or `None` if there is no such hook.
+    pub fn backlog(&self, name: &str) -> Option<usize> {
+        let hooks = self.hooks.read();
+        let (_, hook) = hooks.get(name)?;
+        let state = hook.state.lock();
+        let reading = state.overflowed || state.pending.is_some();
+        Some(state.queue.len() + state.replay.len() + usize::from(reading))
+    }
+
+    /// Hand a committed write to the hooks of its namespace. `event` is only
+    /// built if there are any. Call with [`Self::publishing`] held.
+    pub fn publish(&self, namespace: &str, event: impl FnOnce() -> HookEvent) {
+        let affected: Vec<Arc<Hook>> = {
+            if !self.is_active() {
+                return;
+            }
+            self.hooks
+                .read()
+                .values()
+                .filter(|(ns, _)| ns == namespace)
+                .map(|(_, hook)| hook.clone())
+                .collect()
+        };
+        let Some((last, rest)) = affected.split_last() else {
+            return;
+        };
+        let event = event();
+        for hook in rest {
+            hook.deliver(event.clone());
+        }
+        last.deliver(event);
+    }
+
+    /// Stop every hook and save its cursor.
+    pub fn close(&self) {
+        let hooks: Vec<Arc<Hook>> = self
+            .hooks
+            .read()
+            .values()
+            .map(|(_, hook)| hook.clone())
+            .collect();
+        for hook in hooks {
+            hook.stop();
+            hook.save();
+        }
+    }
+}
+
+impl Drop for Hooks {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
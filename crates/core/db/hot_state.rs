@@ -5,13 +5,23 @@
 //! position, which replaces the previous position on update.
 
 use dashmap::DashMap;
+use spatio_types::config::ScanDirection;
 use spatio_types::point::Point3d;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ops::{Bound, RangeBounds};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::SystemTime;
 
-use crate::compute::spatial::rtree::SpatialIndexManager;
-use crate::error::Result;
-use parking_lot::RwLock;
+use crate::compute::spatial::rtree::{PointImage, SpatialIndexManager, ZoneGeometry};
+use crate::compute::spatial::{DistanceMetric, LocalProjection};
+use crate::db::LocationUpdate;
+use crate::db::cow_map::CowMap;
+use crate::db::verify::Inconsistency;
+use crate::error::{Result, SpatioError};
+use parking_lot::{MappedRwLockWriteGuard, RwLock, RwLockWriteGuard};
+use serde::{Deserialize, Serialize};
 
 /// Current location of a tracked object
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -23,23 +33,172 @@ pub struct CurrentLocation {
     pub timestamp: SystemTime,
 }
 
+impl CurrentLocation {
+    /// Copy of this location with its position displaced by a deterministic
+    /// offset of up to `radius` meters (see [`crate::compute::privacy::jitter_point`]).
+    ///
+    /// The offset is derived from namespace, object ID and timestamp, so the
+    /// same location is always displaced the same way under a given secret
+    /// `key`. Altitude is kept. Fails for a negative or non-finite radius.
+    pub fn jittered(&self, radius: f64, key: u128) -> Result<CurrentLocation> {
+        let id = format!("{}::{}", self.namespace, self.object_id);
+        let origin = spatio_types::geo::Point::new(self.position.x(), self.position.y());
+        let moved =
+            crate::compute::privacy::jitter_point(&origin, &id, self.timestamp, radius, key)?;
+
+        Ok(CurrentLocation {
+            position: Point3d::new(moved.x(), moved.y(), self.position.z()),
+            ..self.clone()
+        })
+    }
+}
+
+/// Zone metadata key holding the zone's speed limit in meters per second.
+pub const SPEED_LIMIT_KEY: &str = "speed_limit";
+
+/// A stored region (bounding box or polygon) within a namespace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Zone {
+    pub zone_id: String,
+    pub namespace: String,
+    pub geometry: ZoneGeometry,
+    pub metadata: serde_json::Value,
+}
+
+impl Zone {
+    /// Speed limit in meters per second, from the [`SPEED_LIMIT_KEY`]
+    /// metadata attribute.
+    pub fn speed_limit(&self) -> Option<f64> {
+        self.metadata.get(SPEED_LIMIT_KEY)?.as_f64()
+    }
+}
+
+/// Per-namespace state a snapshot shares until the next write copies it.
+type Shared<T> = Arc<RwLock<Arc<T>>>;
+
+/// Write-lock `lock`, copying its value first if a snapshot shares it.
+fn cow_write<T: Clone>(lock: &RwLock<Arc<T>>) -> MappedRwLockWriteGuard<'_, T> {
+    RwLockWriteGuard::map(lock.write(), Arc::make_mut)
+}
+
 /// Hot state: current locations only.
 ///
 /// Optimized for frequent position updates and spatial queries on the current
-/// state. Current-location lookups are lock-free (`DashMap`); the spatial index
-/// is guarded by a single `RwLock`, so index writers are mutually exclusive
-/// with each other and with readers.
+/// state. Current locations and zones live in sharded copy-on-write maps. The
+/// spatial index is sharded per namespace, each shard behind its own `RwLock`:
+/// index writers in one namespace are mutually exclusive with each other and
+/// with that namespace's readers, but never block queries on other namespaces.
+///
+/// Each namespace also keeps its object IDs in sorted order for range scans.
 pub struct HotState {
-    current_locations: DashMap<String, Arc<CurrentLocation>>,
-    spatial_index: RwLock<SpatialIndexManager>,
+    current_locations: CowMap<Arc<CurrentLocation>>,
+    zones: CowMap<Arc<Zone>>,
+    spatial_index: DashMap<String, Shared<SpatialIndexManager>>,
+    ordered_ids: DashMap<String, Shared<BTreeSet<String>>>,
+    /// Bumped after every change to objects or zones, so snapshots can tell
+    /// whether they are out of date.
+    version: AtomicU64,
 }
 
 impl HotState {
     pub fn new() -> Self {
         Self {
-            current_locations: DashMap::new(),
-            spatial_index: RwLock::new(SpatialIndexManager::new()),
+            current_locations: CowMap::new(),
+            zones: CowMap::new(),
+            spatial_index: DashMap::new(),
+            ordered_ids: DashMap::new(),
+            version: AtomicU64::new(0),
+        }
+    }
+
+    /// Number of changes applied so far.
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    fn bump_version(&self) {
+        self.version.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// A copy of the current state that later writes to `self` don't touch.
+    ///
+    /// Nothing is copied up front: the snapshot shares every map shard and
+    /// namespace index with `self`, and the first write to one of them after
+    /// the snapshot copies just that part. Each part is taken under its own
+    /// lock, so like any reader the snapshot may catch a write to the
+    /// location map before the matching index update.
+    pub fn snapshot(&self) -> HotState {
+        let version = self.version();
+        let spatial_index = self
+            .spatial_index
+            .iter()
+            .map(|shard| {
+                let shared = shard.value().read().clone();
+                (shard.key().clone(), Arc::new(RwLock::new(shared)))
+            })
+            .collect();
+        let ordered_ids = self
+            .ordered_ids
+            .iter()
+            .map(|ids| {
+                let shared = ids.value().read().clone();
+                (ids.key().clone(), Arc::new(RwLock::new(shared)))
+            })
+            .collect();
+        HotState {
+            current_locations: self.current_locations.snapshot(),
+            zones: self.zones.snapshot(),
+            spatial_index,
+            ordered_ids,
+            version: AtomicU64::new(version),
+        }
+    }
+
+    /// Spatial index shard for `namespace`, if anything was ever indexed there.
+    ///
+    /// The `Arc` is cloned out so the `DashMap` shard guard is released before
+    /// the caller takes the namespace lock.
+    fn index(&self, namespace: &str) -> Option<Shared<SpatialIndexManager>> {
+        self.spatial_index
+            .get(namespace)
+            .map(|shard| shard.value().clone())
+    }
+
+    /// Run `f` against the read-locked index of `namespace`; namespaces that
+    /// were never indexed yield `R::default()` (no results).
+    fn read_index<R: Default>(
+        &self,
+        namespace: &str,
+        f: impl FnOnce(&SpatialIndexManager) -> R,
+    ) -> R {
+        db_span!("spatio.index_read");
+        self.index(namespace)
+            .map(|shard| f(&shard.read()))
+            .unwrap_or_default()
+    }
+
+    /// Spatial index shard for `namespace`, created on first use.
+    fn index_mut(&self, namespace: &str) -> Shared<SpatialIndexManager> {
+        if let Some(shard) = self.index(namespace) {
+            return shard;
+        }
+        self.spatial_index
+            .entry(namespace.to_string())
+            .or_default()
+            .value()
+            .clone()
+    }
+
+    /// Sorted object IDs of `namespace`, created on first use.
+    fn ids_mut(&self, namespace: &str) -> Shared<BTreeSet<String>> {
+        if let Some(ids) = self.ordered_ids.get(namespace) {
+            return ids.value().clone();
         }
+        self.ordered_ids
+            .entry(namespace.to_string())
+            .or_default()
+            .value()
+            .clone()
     }
 
     /// Create a composite key from namespace and object ID
@@ -86,20 +245,22 @@ impl HotState {
             Ignored,
         }
 
-        let action = match self.current_locations.entry(full_key.clone()) {
-            dashmap::mapref::entry::Entry::Occupied(mut entry) => {
-                if entry.get().timestamp <= timestamp {
-                    let old = entry.insert(new_location);
-                    UpdateAction::Updated(old)
-                } else {
-                    UpdateAction::Ignored
+        let action = self.current_locations.with_shard(&full_key, |locations| {
+            match locations.entry(full_key.clone()) {
+                Entry::Occupied(mut entry) => {
+                    if entry.get().timestamp <= timestamp {
+                        let old = entry.insert(new_location);
+                        UpdateAction::Updated(old)
+                    } else {
+                        UpdateAction::Ignored
+                    }
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(new_location);
+                    UpdateAction::Inserted
                 }
             }
-            dashmap::mapref::entry::Entry::Vacant(entry) => {
-                entry.insert(new_location);
-                UpdateAction::Inserted
-            }
-        };
+        });
 
         match action {
             UpdateAction::Updated(old_location) => {
@@ -113,19 +274,31 @@ impl HotState {
                 // state. Metadata/timestamp updates already landed in the
                 // DashMap above. (Common for stationary objects re-reporting.)
                 if old_x != pos_x || old_y != pos_y || old_z != pos_z {
-                    let mut spatial_idx = self.spatial_index.write();
+                    let shard = self.index_mut(namespace);
+                    let mut spatial_idx = cow_write(&shard);
                     // Remove old position
                     spatial_idx.remove_entry(namespace, &full_key, Some((old_x, old_y, old_z)));
                     // Insert new position
                     spatial_idx.insert_point(namespace, pos_x, pos_y, pos_z, full_key);
                 }
 
+                self.bump_version();
                 Ok(Some(old_location))
             }
             UpdateAction::Inserted => {
+                // A concurrent removal holds the ID lock while it clears the
+                // map entry, so re-checking the map here keeps the two in step.
+                {
+                    let ids = self.ids_mut(namespace);
+                    let mut ids = cow_write(&ids);
+                    if self.current_locations.contains_key(&full_key) {
+                        ids.insert(object_id.to_string());
+                    }
+                }
                 // Insert new position
-                let mut spatial_idx = self.spatial_index.write();
-                spatial_idx.insert_point(namespace, pos_x, pos_y, pos_z, full_key);
+                cow_write(&self.index_mut(namespace))
+                    .insert_point(namespace, pos_x, pos_y, pos_z, full_key);
+                self.bump_version();
                 Ok(None)
             }
             UpdateAction::Ignored => Ok(None),
@@ -139,7 +312,7 @@ impl HotState {
         object_id: &str,
     ) -> Option<Arc<CurrentLocation>> {
         let key = Self::make_key(namespace, object_id);
-        self.current_locations.get(&key).map(|v| v.value().clone())
+        self.current_locations.get(&key)
     }
 
     /// Query objects within radius, returning (location, distance)
@@ -149,17 +322,34 @@ impl HotState {
         center: &Point3d,
         radius: f64,
         limit: usize,
+        metric: DistanceMetric,
     ) -> Vec<(Arc<CurrentLocation>, f64)> {
-        let spatial_idx = self.spatial_index.read();
-        let results = spatial_idx.query_within_sphere(namespace, center, radius, limit);
+        let results = self.read_index(namespace, |idx| {
+            idx.query_within_sphere(namespace, center, radius, limit, metric)
+        });
 
         results
             .into_iter()
-            .filter_map(|(key, dist)| {
-                self.current_locations
-                    .get(&key)
-                    .map(|v| (v.value().clone(), dist))
-            })
+            .filter_map(|(key, dist)| self.current_locations.get(&key).map(|v| (v, dist)))
+            .collect()
+    }
+
+    /// Objects within `radius` meters of `center` by horizontal haversine
+    /// distance, returning (location, distance), nearest first.
+    pub fn query_within_radius_2d(
+        &self,
+        namespace: &str,
+        center: &spatio_types::geo::Point,
+        radius: f64,
+        limit: usize,
+    ) -> Vec<(Arc<CurrentLocation>, f64)> {
+        let results = self.read_index(namespace, |idx| {
+            idx.query_within_radius_2d(namespace, center, radius, limit)
+        });
+
+        results
+            .into_iter()
+            .filter_map(|(_x, _y, key, dist)| self.current_locations.get(&key).map(|v| (v, dist)))
             .collect()
     }
 
@@ -173,35 +363,88 @@ impl HotState {
         max_y: f64,
         limit: usize,
     ) -> Vec<Arc<CurrentLocation>> {
-        let spatial_idx = self.spatial_index.read();
-        let results =
-            spatial_idx.query_within_bbox_2d_points(namespace, min_x, min_y, max_x, max_y, limit);
+        let results = self.read_index(namespace, |idx| {
+            idx.query_within_bbox_2d_points(namespace, min_x, min_y, max_x, max_y, limit)
+        });
 
         results
             .into_iter()
-            .filter_map(|(_x, _y, key)| self.current_locations.get(&key).map(|v| v.value().clone()))
+            .filter_map(|(_x, _y, key)| self.current_locations.get(&key))
             .take(limit)
             .collect()
     }
 
+    /// Objects of `namespace` whose IDs fall in `range`, in ID order, up to
+    /// `limit`.
+    pub fn range(
+        &self,
+        namespace: &str,
+        range: impl RangeBounds<str>,
+        limit: usize,
+        direction: ScanDirection,
+    ) -> Vec<Arc<CurrentLocation>> {
+        // `BTreeSet::range` panics on these instead of returning nothing.
+        let empty = match (range.start_bound(), range.end_bound()) {
+            (Bound::Excluded(start), Bound::Excluded(end)) => start >= end,
+            (
+                Bound::Included(start) | Bound::Excluded(start),
+                Bound::Included(end) | Bound::Excluded(end),
+            ) => start > end,
+            _ => false,
+        };
+        if empty {
+            return Vec::new();
+        }
+        let Some(ids) = self
+            .ordered_ids
+            .get(namespace)
+            .map(|ids| ids.value().clone())
+        else {
+            return Vec::new();
+        };
+        let ids = ids.read();
+        let in_range = ids.range::<str, _>((range.start_bound(), range.end_bound()));
+        let locate = |id: &String| self.get_current_location(namespace, id);
+        match direction {
+            ScanDirection::Forward => in_range.filter_map(locate).take(limit).collect(),
+            ScanDirection::Reverse => in_range.rev().filter_map(locate).take(limit).collect(),
+        }
+    }
+
     /// Remove an object
     pub fn remove_object(&self, namespace: &str, object_id: &str) -> Option<Arc<CurrentLocation>> {
         let key = Self::make_key(namespace, object_id);
 
         // Remove from map
-        let removed = self.current_locations.remove(&key).map(|(_, v)| v);
+        let removed = match self
+            .ordered_ids
+            .get(namespace)
+            .map(|ids| ids.value().clone())
+        {
+            Some(ids) => {
+                let mut ids = cow_write(&ids);
+                ids.remove(object_id);
+                self.current_locations.remove(&key)
+            }
+            None => self.current_locations.remove(&key),
+        };
 
         // Remove from spatial index
-        if let Some(item) = &removed {
-            let mut spatial_idx = self.spatial_index.write();
+        if let Some(item) = &removed
+            && let Some(shard) = self.index(namespace)
+        {
             let pos = item.position.clone();
-            spatial_idx.remove_entry(namespace, &key, Some((pos.x(), pos.y(), pos.z())));
+            cow_write(&shard).remove_entry(namespace, &key, Some((pos.x(), pos.y(), pos.z())));
+        }
+        if removed.is_some() {
+            self.bump_version();
         }
 
         removed
     }
 
     /// Query objects within a cylindrical volume
+    #[allow(clippy::too_many_arguments)]
     pub fn query_within_cylinder(
         &self,
         namespace: &str,
@@ -210,19 +453,22 @@ impl HotState {
         max_z: f64,
         radius: f64,
         limit: usize,
+        metric: DistanceMetric,
     ) -> Vec<(Arc<CurrentLocation>, f64)> {
-        let spatial_idx = self.spatial_index.read();
         let query = crate::compute::spatial::rtree::CylinderQuery {
             center,
             min_z,
             max_z,
             radius,
+            metric,
         };
-        let results = spatial_idx.query_within_cylinder(namespace, query, limit);
+        let results = self.read_index(namespace, |idx| {
+            idx.query_within_cylinder(namespace, query, limit)
+        });
 
         results
             .into_iter()
-            .filter_map(|(key, dist)| self.current_locations.get(&key).map(|v| (v.clone(), dist)))
+            .filter_map(|(key, dist)| self.current_locations.get(&key).map(|v| (v, dist)))
             .collect()
     }
 
@@ -233,13 +479,26 @@ impl HotState {
         center: &Point3d,
         k: usize,
     ) -> Vec<(Arc<CurrentLocation>, f64)> {
-        let keys = self.spatial_index.read().knn_3d(namespace, center, k);
+        let keys = self.read_index(namespace, |idx| idx.knn_3d(namespace, center, k));
         keys.into_iter()
-            .filter_map(|(key, distance)| {
-                self.current_locations
-                    .get(&key)
-                    .map(|v| (v.clone(), distance))
-            })
+            .filter_map(|(key, distance)| self.current_locations.get(&key).map(|v| (v, distance)))
+            .collect()
+    }
+
+    /// Find k nearest neighbors by horizontal distance.
+    pub fn knn_2d(
+        &self,
+        namespace: &str,
+        center: &spatio_types::geo::Point,
+        k: usize,
+        max_distance: Option<f64>,
+        metric: crate::compute::spatial::DistanceMetric,
+    ) -> Vec<(Arc<CurrentLocation>, f64)> {
+        let keys = self.read_index(namespace, |idx| {
+            idx.knn_2d(namespace, center, k, max_distance, metric)
+        });
+        keys.into_iter()
+            .filter_map(|(key, distance)| self.current_locations.get(&key).map(|v| (v, distance)))
             .collect()
     }
 
@@ -256,7 +515,6 @@ impl HotState {
         max_z: f64,
         limit: usize,
     ) -> Vec<Arc<CurrentLocation>> {
-        let spatial_idx = self.spatial_index.read();
         let query = crate::compute::spatial::rtree::BBoxQuery {
             min_x,
             min_y,
@@ -265,11 +523,13 @@ impl HotState {
             max_y,
             max_z,
         };
-        let results = spatial_idx.query_within_bbox(namespace, query, limit);
+        let results = self.read_index(namespace, |idx| {
+            idx.query_within_bbox(namespace, query, limit)
+        });
 
         results
             .into_iter()
-            .filter_map(|(key,)| self.current_locations.get(&key).map(|v| v.value().clone()))
+            .filter_map(|(key,)| self.current_locations.get(&key))
             .collect()
     }
 
@@ -280,15 +540,15 @@ impl HotState {
         polygon: &spatio_types::geo::Polygon,
         limit: usize,
     ) -> Vec<Arc<CurrentLocation>> {
-        let spatial_idx = self.spatial_index.read();
-
         // Use optimized query that filters by polygon during iteration
         // This avoids the limit * 2 heuristic and unnecessary object lookups
-        let candidates = spatial_idx.query_within_polygon_2d(namespace, polygon, limit);
+        let candidates = self.read_index(namespace, |idx| {
+            idx.query_within_polygon_2d(namespace, polygon, limit)
+        });
 
         candidates
             .into_iter()
-            .filter_map(|(_, _, key)| self.current_locations.get(&key).map(|v| v.value().clone()))
+            .filter_map(|(_, _, key)| self.current_locations.get(&key))
             .collect()
     }
 
@@ -328,17 +588,27 @@ impl HotState {
     /// Compute convex hull of all objects in namespace
     pub fn convex_hull(&self, namespace: &str) -> Option<spatio_types::geo::Polygon> {
         // Use spatial index to get points efficiently (no DashMap scan)
-        let spatial_idx = self.spatial_index.read();
-        let points = spatial_idx.namespace_points(namespace);
+        let points = self.read_index(namespace, |idx| idx.namespace_points(namespace));
 
         crate::compute::spatial::convex_hull(&points)
     }
 
+    /// Aggregate namespace objects into grid cells, releasing only cells with at least k objects
+    pub fn k_anonymous_cells(
+        &self,
+        namespace: &str,
+        cell_size: f64,
+        k: usize,
+    ) -> Result<Vec<crate::compute::privacy::AggregateCell>> {
+        let points = self.read_index(namespace, |idx| idx.namespace_points(namespace));
+        crate::compute::privacy::k_anonymous_cells(&points, cell_size, k)
+    }
+
     /// Compute bounding box of all objects in namespace
     pub fn bounding_box(&self, namespace: &str) -> Option<geo::Rect> {
         // Use spatial index which tracks envelopes (O(1) or O(N_namespace) vs O(N_db))
-        let spatial_idx = self.spatial_index.read();
-        let (min_x, min_y, max_x, max_y) = spatial_idx.namespace_bbox_2d(namespace)?;
+        let (min_x, min_y, max_x, max_y) =
+            self.read_index(namespace, |idx| idx.namespace_bbox_2d(namespace))?;
 
         Some(geo::Rect::new(
             geo::coord! { x: min_x, y: min_y },
@@ -346,6 +616,152 @@ impl HotState {
         ))
     }
 
+    /// Store a zone, replacing any existing zone with the same ID.
+    pub fn upsert_zone(
+        &self,
+        namespace: &str,
+        zone_id: &str,
+        geometry: ZoneGeometry,
+        metadata: serde_json::Value,
+    ) -> Option<Arc<Zone>> {
+        let key = Self::make_key(namespace, zone_id);
+        let zone = Arc::new(Zone {
+            zone_id: zone_id.to_string(),
+            namespace: namespace.to_string(),
+            geometry: geometry.clone(),
+            metadata,
+        });
+
+        // Hold the index lock across the map update so the two stay in step.
+        let shard = self.index_mut(namespace);
+        let mut spatial_idx = cow_write(&shard);
+        spatial_idx.insert_zone(namespace, key.clone(), geometry);
+        let old = self.zones.insert(key, zone);
+        self.bump_version();
+        old
+    }
+
+    /// Get a stored zone
+    pub fn get_zone(&self, namespace: &str, zone_id: &str) -> Option<Arc<Zone>> {
+        let key = Self::make_key(namespace, zone_id);
+        self.zones.get(&key)
+    }
+
+    /// Every stored zone of a namespace, in no particular order.
+    pub fn zones(&self, namespace: &str) -> Vec<Arc<Zone>> {
+        let mut zones = Vec::new();
+        self.zones.for_each(|_, zone| {
+            if zone.namespace == namespace {
+                zones.push(zone.clone());
+            }
+        });
+        zones
+    }
+
+    /// Every stored zone, in all namespaces.
+    pub fn all_zones(&self) -> Vec<Arc<Zone>> {
+        self.zones.values()
+    }
+
+    /// Remove a stored zone
+    pub fn remove_zone(&self, namespace: &str, zone_id: &str) -> Option<Arc<Zone>> {
+        let key = Self::make_key(namespace, zone_id);
+        let removed = match self.index(namespace) {
+            Some(shard) => {
+                let mut spatial_idx = cow_write(&shard);
+                spatial_idx.remove_zone(namespace, &key);
+                self.zones.remove(&key)
+            }
+            None => self.zones.remove(&key),
+        };
+        if removed.is_some() {
+            self.bump_version();
+        }
+        removed
+    }
+
+    /// Calculate geodesic distance from a point to a stored zone's boundary
+    pub fn distance_to_zone(
+        &self,
+        namespace: &str,
+        zone_id: &str,
+        point: &spatio_types::geo::Point,
+    ) -> Option<f64> {
+        let zone = self.get_zone(namespace, zone_id)?;
+        let distance = match &zone.geometry {
+            ZoneGeometry::BBox(bbox) => {
+                crate::compute::spatial::distance_to_polygon(point, &bbox.rect.to_polygon().into())
+            }
+            ZoneGeometry::Polygon(polygon) => {
+                crate::compute::spatial::distance_to_polygon(point, polygon)
+            }
+        };
+        Some(distance)
+    }
+
+    /// Find the k zones nearest to a point by boundary distance, returning (zone, distance)
+    /// Zones of `namespace` containing `point`, boundary included, in zone ID
+    /// order.
+    pub fn zones_containing(
+        &self,
+        namespace: &str,
+        point: &spatio_types::geo::Point,
+        limit: usize,
+    ) -> Vec<Arc<Zone>> {
+        let keys = self.read_index(namespace, |idx| idx.zones_containing(namespace, point));
+        self.zones_by_key(keys, limit)
+    }
+
+    /// Zones of `namespace` sharing any point with `polygon`, in zone ID
+    /// order.
+    pub fn zones_intersecting(
+        &self,
+        namespace: &str,
+        polygon: &spatio_types::geo::Polygon,
+        limit: usize,
+    ) -> Vec<Arc<Zone>> {
+        let keys = self.read_index(namespace, |idx| idx.zones_intersecting(namespace, polygon));
+        self.zones_by_key(keys, limit)
+    }
+
+    fn zones_by_key(&self, mut keys: Vec<String>, limit: usize) -> Vec<Arc<Zone>> {
+        keys.sort();
+        keys.into_iter()
+            .filter_map(|key| self.zones.get(&key))
+            .take(limit)
+            .collect()
+    }
+
+    /// Zones of `namespace` whose boundary is within `radius` meters of
+    /// `point`, returning (zone, distance), nearest first.
+    pub fn zones_within_radius(
+        &self,
+        namespace: &str,
+        point: &spatio_types::geo::Point,
+        radius: f64,
+        limit: usize,
+    ) -> Vec<(Arc<Zone>, f64)> {
+        let keys = self.read_index(namespace, |idx| {
+            idx.zones_within_radius(namespace, point, radius)
+        });
+        keys.into_iter()
+            .filter_map(|(key, distance)| self.zones.get(&key).map(|v| (v, distance)))
+            .take(limit)
+            .collect()
+    }
+
+    pub fn nearest_zones(
+        &self,
+        namespace: &str,
+        point: &spatio_types::geo::Point,
+        k: usize,
+    ) -> Vec<(Arc<Zone>, f64)> {
+        let keys = self.read_index(namespace, |idx| idx.knn_zones(namespace, point, k));
+        keys.into_iter()
+            .filter_map(|(key, distance)| self.zones.get(&key).map(|v| (v, distance)))
+            .collect()
+    }
+
     /// Get total number of tracked objects
     pub fn object_count(&self) -> usize {
         self.current_locations.len()
@@ -354,10 +770,44 @@ impl HotState {
     /// Get number of objects in a specific namespace
     pub fn namespace_count(&self, namespace: &str) -> usize {
         let prefix = format!("{}::", namespace);
-        self.current_locations
+        let mut count = 0;
+        self.current_locations.for_each(|key, _| {
+            if key.starts_with(&prefix) {
+                count += 1;
+            }
+        });
+        count
+    }
+
+    /// Number of objects in each namespace holding any.
+    pub fn namespace_counts(&self) -> BTreeMap<String, usize> {
+        self.ordered_ids
+            .iter()
+            .map(|ids| (ids.key().clone(), ids.value().read().len()))
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    /// Names of the namespaces holding objects or zones, sorted.
+    pub fn namespaces(&self) -> Vec<String> {
+        let mut namespaces: BTreeSet<String> = self
+            .ordered_ids
             .iter()
-            .filter(|entry| entry.key().starts_with(&prefix))
-            .count()
+            .filter(|ids| !ids.value().read().is_empty())
+            .map(|ids| ids.key().clone())
+            .collect();
+        self.zones.for_each(|_, zone| {
+            namespaces.insert(zone.namespace.clone());
+        });
+        namespaces.into_iter().collect()
+    }
+
+    /// IDs of every object of `namespace`, in ID order.
+    pub fn object_ids(&self, namespace: &str) -> Vec<String> {
+        self.ordered_ids
+            .get(namespace)
+            .map(|ids| ids.value().read().iter().cloned().collect())
+            .unwrap_or_default()
     }
 
     /// Get detailed statistics including per-namespace breakdown
@@ -368,14 +818,316 @@ impl HotState {
         (total_objects, estimated_memory)
     }
 
+    /// Declare (or remove) the local projection used for distance filtering in
+    /// `namespace`; already indexed objects are reprojected.
+    pub fn set_projection(&self, namespace: &str, projection: Option<LocalProjection>) {
+        cow_write(&self.index_mut(namespace)).set_projection(namespace, projection);
+    }
+
+    /// Switch `namespace` to write-optimized index mode (`Some(capacity)`) or
+    /// back to direct R-tree inserts (`None`).
+    pub fn set_write_buffer(&self, namespace: &str, capacity: Option<usize>) {
+        cow_write(&self.index_mut(namespace)).set_write_buffer(namespace, capacity);
+    }
+
+    /// Merge all buffered index inserts into the R-trees.
+    pub fn merge_write_buffers(&self) {
+        let shards: Vec<_> = self
+            .spatial_index
+            .iter()
+            .map(|shard| shard.value().clone())
+            .collect();
+        for shard in shards {
+            cow_write(&shard).merge_write_buffers();
+        }
+    }
+
+    /// Write every namespace's point index as built, for
+    /// [`HotState::load_point_indexes`]. Each namespace is written under its
+    /// read lock.
+    pub(crate) fn save_point_indexes(&self, w: &mut dyn std::io::Write) -> Result<()> {
+        let shards: Vec<_> = self
+            .spatial_index
+            .iter()
+            .map(|shard| (shard.key().clone(), shard.value().clone()))
+            .collect();
+        bincode::serialize_into(&mut *w, &(shards.len() as u64)).map_err(encoding_error)?;
+        for (namespace, shard) in shards {
+            let index = shard.read();
+            bincode::serialize_into(&mut *w, &(&namespace, index.point_image(&namespace)))
+                .map_err(encoding_error)?;
+        }
+        Ok(())
+    }
+
+    /// Read point indexes written by [`HotState::save_point_indexes`].
+    pub(crate) fn load_point_indexes(
+        r: &mut dyn std::io::Read,
+    ) -> Result<Vec<(String, PointImage)>> {
+        let count: u64 = bincode::deserialize_from(&mut *r).map_err(encoding_error)?;
+        let mut images = Vec::new();
+        for _ in 0..count {
+            let (namespace, image): (String, Option<PointImage>) =
+                bincode::deserialize_from(&mut *r).map_err(encoding_error)?;
+            if let Some(image) = image {
+                images.push((namespace, image));
+            }
+        }
+        Ok(images)
+    }
+
+    /// Load recovered current locations (keyed `namespace::object_id`) into
+    /// an empty state, reusing saved point indexes where given.
+    ///
+    /// A saved index is installed as is and then checked against the
+    /// locations: entries that moved, are missing or no longer exist are
+    /// fixed one by one, so a stale index costs only its differences.
+    /// Namespaces without one are indexed point by point. Returns the number
+    /// of index entries fixed or inserted that way.
+    ///
+    /// `loaded` is called with each namespace and its object count once the
+    /// namespace is complete.
+    pub(crate) fn restore(
+        &self,
+        locations: HashMap<String, LocationUpdate>,
+        images: Vec<(String, PointImage)>,
+        loaded: &mut dyn FnMut(&str, usize),
+    ) -> usize {
+        let mut restored = HashSet::new();
+        for (namespace, image) in images {
+            if cow_write(&self.index_mut(&namespace)).restore_points(&namespace, image) {
+                restored.insert(namespace);
+            }
+        }
+
+        let mut by_namespace: HashMap<String, Vec<(String, LocationUpdate)>> = HashMap::new();
+        for (key, update) in locations {
+            let Some((namespace, _)) = key.split_once("::") else {
+                continue;
+            };
+            match by_namespace.get_mut(namespace) {
+                Some(entries) => entries.push((key, update)),
+                None => {
+                    by_namespace.insert(namespace.to_string(), vec![(key, update)]);
+                }
+            }
+        }
+        for namespace in &restored {
+            by_namespace.entry(namespace.clone()).or_default();
+        }
+
+        let mut fixed = 0;
+        for (namespace, entries) in by_namespace {
+            let shard = self.index_mut(&namespace);
+            let mut index = cow_write(&shard);
+            let mut object_ids = Vec::with_capacity(entries.len());
+            for (key, update) in entries {
+                let position = &update.position;
+                let (x, y, z) = (position.x(), position.y(), position.z());
+                let indexed = index.position_of(&namespace, &key);
+                if indexed != Some((x, y, z)) {
+                    if indexed.is_some() {
+                        index.remove_entry(&namespace, &key, indexed);
+                    }
+                    index.insert_point(&namespace, x, y, z, key.clone());
+                    fixed += 1;
+                }
+                let object_id = key[namespace.len() + 2..].to_string();
+                let location = Arc::new(CurrentLocation {
+                    object_id: object_id.clone(),
+                    namespace: namespace.clone(),
+                    position: update.position,
+                    metadata: update.metadata,
+                    timestamp: update.timestamp,
+                });
+                self.current_locations.insert(key, location);
+                object_ids.push(object_id);
+            }
+
+            // Every location is indexed now, so any extra entries belong to
+            // objects deleted since the index was saved.
+            if index.point_count(&namespace) > object_ids.len() {
+                for key in index.point_keys(&namespace) {
+                    if !self.current_locations.contains_key(key.as_ref()) {
+                        index.remove_entry(&namespace, &key, None);
+                        fixed += 1;
+                    }
+                }
+            }
+            let count = object_ids.len();
+            {
+                let ids = self.ids_mut(&namespace);
+                let mut ids = cow_write(&ids);
+                if ids.is_empty() {
+                    *ids = object_ids.into_iter().collect();
+                } else {
+                    ids.extend(object_ids);
+                }
+            }
+            drop(index);
+            self.bump_version();
+            loaded(&namespace, count);
+        }
+        fixed
+    }
+
+    /// Cross-check the location map against the spatial index and the ID
+    /// order, and the zone map against the zone index, appending every
+    /// disagreement to `issues`. Returns all current locations, for checks
+    /// against the log.
+    ///
+    /// Namespaces are checked one at a time under their own locks, so writes
+    /// racing with the check may show up as mismatches.
+    pub(crate) fn verify(&self, issues: &mut Vec<Inconsistency>) -> Vec<Arc<CurrentLocation>> {
+        let locations = self.current_locations.values();
+        let mut by_namespace: HashMap<&str, HashMap<&str, &CurrentLocation>> = HashMap::new();
+        for loc in &locations {
+            by_namespace
+                .entry(&loc.namespace)
+                .or_default()
+                .insert(&loc.object_id, loc);
+        }
+        let mut namespaces: BTreeSet<String> =
+            by_namespace.keys().map(|ns| ns.to_string()).collect();
+        namespaces.extend(self.spatial_index.iter().map(|shard| shard.key().clone()));
+        namespaces.extend(self.ordered_ids.iter().map(|ids| ids.key().clone()));
+        self.zones.for_each(|_, zone| {
+            namespaces.insert(zone.namespace.clone());
+        });
+
+        let empty = HashMap::new();
+        for namespace in &namespaces {
+            let objects = by_namespace.get(namespace.as_str()).unwrap_or(&empty);
+            let prefix = format!("{namespace}::");
+
+            let mut indexed = HashSet::new();
+            let entries = self.read_index(namespace, |idx| idx.namespace_entries(namespace));
+            for (key, [x, y, z]) in entries {
+                let object = key
+                    .as_deref()
+                    .and_then(|key| key.strip_prefix(&prefix))
+                    .filter(|id| {
+                        objects.get(id).is_some_and(|loc| {
+                            (loc.position.x(), loc.position.y(), loc.position.z()) == (x, y, z)
+                        })
+                    });
+                // A second entry for the same object is as wrong as a stray one.
+                if let Some(id) = object
+                    && indexed.insert(id.to_string())
+                {
+                    continue;
+                }
+                issues.push(Inconsistency::OrphanIndexEntry {
+                    namespace: namespace.clone(),
+                    key: key.map(|key| key.to_string()),
+                });
+            }
+
+            let ordered: Arc<BTreeSet<String>> = self
+                .ordered_ids
+                .get(namespace.as_str())
+                .map(|ids| ids.value().read().clone())
+                .unwrap_or_default();
+            for object_id in objects.keys() {
+                if !indexed.contains(*object_id) {
+                    issues.push(Inconsistency::UnindexedObject {
+                        namespace: namespace.clone(),
+                        object_id: object_id.to_string(),
+                    });
+                }
+                if !ordered.contains(*object_id) {
+                    issues.push(Inconsistency::UnorderedObject {
+                        namespace: namespace.clone(),
+                        object_id: object_id.to_string(),
+                    });
+                }
+            }
+            for object_id in ordered.iter() {
+                if !objects.contains_key(object_id.as_str()) {
+                    issues.push(Inconsistency::OrphanOrderedId {
+                        namespace: namespace.clone(),
+                        object_id: object_id.clone(),
+                    });
+                }
+            }
+
+            let zone_keys: HashSet<String> = self
+                .read_index(namespace, |idx| idx.namespace_zone_keys(namespace))
+                .into_iter()
+                .collect();
+            for zone in self.zones(namespace) {
+                if !zone_keys.contains(&Self::make_key(namespace, &zone.zone_id)) {
+                    issues.push(Inconsistency::UnindexedZone {
+                        namespace: namespace.clone(),
+                        zone_id: zone.zone_id.clone(),
+                    });
+                }
+            }
+            for key in zone_keys {
+                if !self.zones.contains_key(&key) {
+                    issues.push(Inconsistency::OrphanZoneEntry {
+                        namespace: namespace.clone(),
+                        key,
+                    });
+                }
+            }
+        }
+        locations
+    }
+
+    /// Rebuild the spatial index, zone index and ID order of `namespace` from
+    /// the location and zone maps, keeping its projection and write buffer
+    /// settings. Writes to the namespace racing with the rebuild may be left
+    /// out of, or doubled in, the index.
+    pub(crate) fn rebuild_index(&self, namespace: &str) {
+        let shard = self.index_mut(namespace);
+        let ids = self.ids_mut(namespace);
+        let mut index = cow_write(&shard);
+        let mut ids = cow_write(&ids);
+
+        let mut fresh = SpatialIndexManager::new();
+        fresh.set_projection(namespace, index.projection(namespace));
+        fresh.set_write_buffer(namespace, index.write_buffer(namespace));
+        ids.clear();
+        self.current_locations.for_each(|key, loc| {
+            if loc.namespace != namespace {
+                return;
+            }
+            let position = &loc.position;
+            fresh.insert_point(
+                namespace,
+                position.x(),
+                position.y(),
+                position.z(),
+                key.to_string(),
+            );
+            ids.insert(loc.object_id.clone());
+        });
+        for zone in self.zones(namespace) {
+            fresh.insert_zone(
+                namespace,
+                Self::make_key(namespace, &zone.zone_id),
+                zone.geometry.clone(),
+            );
+        }
+        *index = fresh;
+        self.bump_version();
+    }
+
     /// Clear all objects from hot state
     pub fn clear(&mut self) {
         self.current_locations.clear();
-        let mut spatial_idx = self.spatial_index.write();
-        spatial_idx.clear();
+        self.zones.clear();
+        self.spatial_index.clear();
+        self.ordered_ids.clear();
+        self.bump_version();
     }
 }
 
+fn encoding_error(err: bincode::Error) -> SpatioError {
+    SpatioError::SerializationErrorWithContext(format!("point index: {}", err))
+}
+
 impl Default for HotState {
     fn default() -> Self {
         Self::new()
@@ -463,7 +1215,8 @@ mod tests {
 
         // Still present in the spatial index exactly once.
         let center = Point3d::new(-74.0, 40.7, 100.0);
-        let results = hot.query_within_radius("drones", &center, 10.0, 10);
+        let results =
+            hot.query_within_radius("drones", &center, 10.0, 10, DistanceMetric::Haversine);
         assert_eq!(
             results.len(),
             1,
@@ -604,11 +1357,146 @@ mod tests {
 
         // Query within 1km radius
         let center = Point3d::new(-74.0, 40.7, 0.0);
-        let nearby = hot.query_within_radius("vehicles", &center, 1000.0, 10);
+        let nearby =
+            hot.query_within_radius("vehicles", &center, 1000.0, 10, DistanceMetric::Haversine);
 
         // Should find truck_001 and truck_002, not truck_003
         assert!(nearby.len() >= 2);
         assert!(nearby.iter().any(|(l, _)| l.object_id == "truck_001"));
         assert!(nearby.iter().any(|(l, _)| l.object_id == "truck_002"));
     }
+
+    #[test]
+    fn test_snapshot_shares_namespaces_until_written() {
+        let hot = HotState::new();
+        for ns in ["drones", "vehicles"] {
+            hot.update_location(
+                ns,
+                "obj",
+                Point3d::new(-74.0, 40.7, 0.0),
+                serde_json::json!({}),
+                SystemTime::now(),
+            )
+            .unwrap();
+        }
+        let snapshot = hot.snapshot();
+        let shared = |ns: &str| {
+            Arc::ptr_eq(
+                &hot.index(ns).unwrap().read(),
+                &snapshot.index(ns).unwrap().read(),
+            )
+        };
+        assert!(shared("drones") && shared("vehicles"));
+
+        hot.update_location(
+            "drones",
+            "obj",
+            Point3d::new(-73.0, 40.7, 0.0),
+            serde_json::json!({}),
+            SystemTime::now(),
+        )
+        .unwrap();
+        assert!(!shared("drones"));
+        assert!(shared("vehicles"));
+        let before = snapshot.get_current_location("drones", "obj").unwrap();
+        assert_eq!(before.position.x(), -74.0);
+        let center = Point3d::new(-74.0, 40.7, 0.0);
+        assert_eq!(
+            snapshot
+                .query_within_radius("drones", &center, 100.0, 10, DistanceMetric::Haversine)
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_index_lock_is_per_namespace() {
+        let hot = HotState::new();
+        for ns in ["drones", "vehicles"] {
+            hot.update_location(
+                ns,
+                "obj",
+                Point3d::new(-74.0, 40.7, 0.0),
+                serde_json::json!({}),
+                SystemTime::now(),
+            )
+            .unwrap();
+        }
+
+        // A writer holding one namespace's index must not block the other.
+        let shard = hot.index("drones").unwrap();
+        let _writer = shard.write();
+        let center = Point3d::new(-74.0, 40.7, 0.0);
+        assert_eq!(
+            hot.query_within_radius("vehicles", &center, 100.0, 10, DistanceMetric::Haversine)
+                .len(),
+            1
+        );
+        assert!(
+            hot.query_within_radius("unknown", &center, 100.0, 10, DistanceMetric::Haversine)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_verify_finds_index_and_order_drift() {
+        let hot = HotState::new();
+        for (id, x) in [("a", 1.0), ("b", 2.0), ("c", 3.0)] {
+            hot.update_location(
+                "fleet",
+                id,
+                Point3d::new(x, 0.0, 0.0),
+                serde_json::json!({}),
+                SystemTime::now(),
+            )
+            .unwrap();
+        }
+        let mut issues = Vec::new();
+        assert_eq!(hot.verify(&mut issues).len(), 3);
+        assert_eq!(issues, []);
+
+        // Lose "a" from the index, "b" from the map, and "c" from the ID order.
+        cow_write(&hot.index_mut("fleet")).remove_entry("fleet", "fleet::a", Some((1.0, 0.0, 0.0)));
+        hot.current_locations.remove("fleet::b");
+        cow_write(&hot.ids_mut("fleet")).remove("c");
+
+        let mut issues = Vec::new();
+        hot.verify(&mut issues);
+        issues.sort_by_key(|issue| issue.to_string());
+        let fleet = || "fleet".to_string();
+        assert_eq!(
+            issues,
+            [
+                Inconsistency::UnindexedObject {
+                    namespace: fleet(),
+                    object_id: "a".into(),
+                },
+                Inconsistency::OrphanOrderedId {
+                    namespace: fleet(),
+                    object_id: "b".into(),
+                },
+                Inconsistency::UnorderedObject {
+                    namespace: fleet(),
+                    object_id: "c".into(),
+                },
+                Inconsistency::OrphanIndexEntry {
+                    namespace: fleet(),
+                    key: Some("fleet::b".into()),
+                },
+            ]
+        );
+
+        hot.rebuild_index("fleet");
+        let mut issues = Vec::new();
+        assert_eq!(hot.verify(&mut issues).len(), 2);
+        assert_eq!(issues, []);
+        let found = hot.query_within_radius(
+            "fleet",
+            &Point3d::new(1.0, 0.0, 0.0),
+            1.0,
+            10,
+            DistanceMetric::Haversine,
+        );
+        assert_eq!(found.len(), 1);
+    }
 }
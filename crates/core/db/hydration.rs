@@ -0,0 +1,346 @@
+//! Opening a database in the background, and reporting open progress.
+//!
+//! [`DB::open_background`] returns as soon as the files are open and loads
+//! current locations on a separate thread. Until a namespace is loaded,
+//! operations on it wait for it; namespaces loaded already are served
+//! meanwhile. Writes, and operations spanning namespaces, wait for the whole
+//! load, since they append to the log recovery is reading.
+//!
+//! Progress of either kind of open is reported to the callback set with
+//! [`DBBuilder::on_progress`](crate::DBBuilder::on_progress).
+
+use super::{CompactionPolicy, DB};
+use crate::config::Config;
+use crate::error::Result;
+use parking_lot::{Condvar, Mutex};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// A step of opening a database, reported to
+/// [`DBBuilder::on_progress`](crate::DBBuilder::on_progress).
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum OpenProgress {
+    /// The checkpoint snapshot was read, with `objects` current locations.
+    SnapshotLoaded { objects: usize },
+    /// `read` of the `total` bytes of log written after the checkpoint have
+    /// been replayed. Reported every few megabytes and once at the end.
+    ReplayingLog { read: u64, total: u64 },
+    /// `namespace` is loaded and queryable, with `objects` objects: the
+    /// `loaded`th of `total` namespaces.
+    NamespaceLoaded {
+        namespace: String,
+        objects: usize,
+        loaded: usize,
+        total: usize,
+    },
+    /// Every namespace is loaded, `objects` objects in all, `elapsed` after
+    /// the open started.
+    Ready { objects: usize, elapsed: Duration },
+}
+
+/// Callback receiving [`OpenProgress`].
+pub(crate) type ProgressFn = Arc<dyn Fn(&OpenProgress) + Send + Sync>;
+
+/// Which namespaces are loaded yet.
+pub(crate) struct Hydration {
+    /// Set once everything is loaded; checked first so a loaded database
+    /// never takes the lock.
+    ready: AtomicBool,
+    /// Namespaces still to load; `None` until recovery has found them, when
+    /// every namespace waits.
+    pending: Mutex<Option<HashSet<String>>>,
+    changed: Condvar,
+}
+
+impl Hydration {
+    pub(crate) fn loaded() -> Self {
+        Self {
+            ready: AtomicBool::new(true),
+            pending: Mutex::new(Some(HashSet::new())),
+            changed: Condvar::new(),
+        }
+    }
+
+    pub(crate) fn loading() -> Self {
+        Self {
+            ready: AtomicBool::new(false),
+            pending: Mutex::new(None),
+            changed: Condvar::new(),
+        }
+    }
+
+    pub(crate) fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    /// Block until `namespace` is loaded.
+    pub(crate) fn wait_for(&self, namespace: &str) {
+        if self.is_ready() {
+            return;
+        }
+        let mut pending = self.pending.lock();
+        while !self.is_ready()
+            && pending
+                .as_ref()
+                .is_none_or(|pending| pending.contains(namespace))
+        {
+            self.changed.wait(&mut pending);
+        }
+    }
+
+    /// Block until every namespace is loaded.
+    pub(crate) fn wait_all(&self) {
+        if self.is_ready() {
+            return;
+        }
+        let mut pending = self.pending.lock();
+        while !self.is_ready() {
+            self.changed.wait(&mut pending);
+        }
+    }
+
+    /// Recovery found `namespaces`; namespaces not among them can be served.
+    fn set_pending(&self, namespaces: HashSet<String>) {
+        *self.pending.lock() = Some(namespaces);
+        self.changed.notify_all();
+    }
+
+    fn loaded_namespace(&self, namespace: &str) {
+        if let Some(pending) = self.pending.lock().as_mut() {
+            pending.remove(namespace);
+        }
+        self.changed.notify_all();
+    }
+
+    fn finish(&self) {
+        let _pending = self.pending.lock();
+        self.ready.store(true, Ordering::Release);
+        self.changed.notify_all();
+    }
+}
+
+/// Marks the load finished when dropped, so waiters are released even if
+/// loading panics.
+struct FinishOnDrop<'a>(&'a Hydration);
+
+impl Drop for FinishOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.finish();
+    }
+}
+
+impl DB {
+    /// Open or create a database at `path` without waiting for current
+    /// locations to load (see the [module docs](self)).
+    pub fn open_background<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_inner(path.as_ref(), Config::default(), None, None, true)
+    }
+
+    pub(crate) fn open_inner(
+        path: &Path,
+        config: Config,
+        progress: Option<ProgressFn>,
+        compaction: Option<Arc<dyn CompactionPolicy>>,
+        background: bool,
+    ) -> Result<Self> {
+        let started = std::time::Instant::now();
+        let in_memory = path.to_str() == Some(":memory:");
+        let background = background && !in_memory;
+        let db = Self::assemble(path, config, compaction, background)?;
+        if in_memory {
+            db.start_expiration()?;
+            if let Some(progress) = &progress {
+                progress(&OpenProgress::Ready {
+                    objects: 0,
+                    elapsed: started.elapsed(),
+                });
+            }
+            return Ok(db);
+        }
+
+        let report = move |step: &OpenProgress| {
+            if let Some(progress) = &progress {
+                progress(step);
+            }
+        };
+        if background {
+            let loader = db.clone();
+            std::thread::Builder::new()
+                .name("spatio-open".to_string())
+                .spawn(move || {
+                    if let Err(e) = loader.load(started, &report) {
+                        log::warn!("Failed to start background tasks: {}", e);
+                    }
+                })?;
+        } else {
+            db.load(started, &report)?;
+        }
+        Ok(db)
+    }
+
+    /// Recover current locations and rebuild the hot state, releasing each
+    /// namespace to waiting operations as it is loaded.
+    fn load(&self, started: std::time::Instant, report: &dyn Fn(&OpenProgress)) -> Result<()> {
+        let _finish = FinishOnDrop(&self.hydration);
+        let recovered = match self.cold.recover_current_locations_with_progress(report) {
+            Ok(recovered) => recovered,
+            Err(e) => {
+                log::warn!("Failed to recover current locations: {}", e);
+                // Continue anyway - partial recovery is acceptable
+                Default::default()
+            }
+        };
+        // Persist a fresh checkpoint covering everything recovered so
+        // the next startup replays only newly appended records. The
+        // full history log is left intact. Best-effort: a failure here
+        // only means the next recovery is slower, not incorrect.
+        if let Err(e) = self.cold.write_checkpoint(&recovered) {
+            log::warn!("Failed to write recovery checkpoint: {}", e);
+        }
+
+        let objects = recovered.len();
+        let namespaces: HashSet<String> = recovered
+            .keys()
+            .filter_map(|key| key.split_once("::"))
+            .map(|(namespace, _)| namespace.to_string())
+            .collect();
+        let total = namespaces.len();
+        // Zones are kept apart from the point indexes, so they are in place
+        // before any namespace is released.
+        for zone in self.cold.read_zones() {
+            self.hot
+                .upsert_zone(&zone.namespace, &zone.zone_id, zone.geometry, zone.metadata);
+        }
+        self.hydration.set_pending(namespaces);
+
+        // Load the indexes saved at the last open rather than
+        // rebuilding them point by point, then save them again
+        // if recovery had to fix them up.
+        let images = self
+            .cold
+            .read_index_snapshot(super::HotState::load_point_indexes);
+        let saved = images.is_some();
+        let mut loaded = 0;
+        let fixed = self.hot.restore(
+            recovered,
+            images.unwrap_or_default(),
+            &mut |namespace, count| {
+                loaded += 1;
+                self.hydration.loaded_namespace(namespace);
+                report(&OpenProgress::NamespaceLoaded {
+                    namespace: namespace.to_string(),
+                    objects: count,
+                    loaded,
+                    total,
+                });
+            },
+        );
+        // Recovered objects are already sorted into write buffers; start with them merged.
+        self.hot.merge_write_buffers();
+        if !saved || fixed > 0 {
+            self.save_indexes();
+        }
+
+        self.start_expiration()?;
+        self.start_disk_guard()?;
+        report(&OpenProgress::Ready {
+            objects,
+            elapsed: started.elapsed(),
+        });
+        Ok(())
+    }
+
+    /// Whether every namespace is loaded; always `true` unless the database
+    /// was opened with [`DB::open_background`].
+    pub fn is_loaded(&self) -> bool {
+        self.hydration.is_ready()
+    }
+
+    /// Block until every namespace is loaded.
+    pub fn wait_loaded(&self) {
+        self.hydration.wait_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spatio_types::point::Point3d;
+
+    #[test]
+    fn test_hydration_releases_namespaces_in_turn() {
+        let hydration = Arc::new(Hydration::loading());
+        let waiter = {
+            let hydration = hydration.clone();
+            std::thread::spawn(move || hydration.wait_for("b"))
+        };
+        hydration.set_pending(["a", "b"].map(String::from).into());
+        hydration.wait_for("other");
+        hydration.loaded_namespace("a");
+        hydration.wait_for("a");
+        assert!(!waiter.is_finished());
+        hydration.loaded_namespace("b");
+        waiter.join().unwrap();
+        assert!(!hydration.is_ready());
+        hydration.finish();
+        hydration.wait_all();
+    }
+
+    #[test]
+    fn test_open_background_serves_after_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db");
+        {
+            let db = DB::open(&path).unwrap();
+            for ns in ["cars", "boats"] {
+                for i in 0..50 {
+                    let point = Point3d::new(i as f64 * 0.001, 0.0, 0.0);
+                    db.upsert(ns, &format!("o{i}"), point, serde_json::json!({}), None)
+                        .unwrap();
+                }
+            }
+        }
+
+        let steps = Arc::new(Mutex::new(Vec::new()));
+        let db = {
+            let steps = steps.clone();
+            crate::DBBuilder::new()
+                .path(&path)
+                .on_progress(move |step| steps.lock().push(step.clone()))
+                .build_background()
+                .unwrap()
+        };
+        // Waits for its namespace rather than answering from a partial state.
+        let found = db
+            .query_radius("boats", &Point3d::new(0.0, 0.0, 0.0), 100_000.0, 100)
+            .unwrap();
+        assert_eq!(found.len(), 50);
+        db.upsert(
+            "cars",
+            "new",
+            Point3d::new(1.0, 1.0, 0.0),
+            serde_json::json!({}),
+            None,
+        )
+        .unwrap();
+        assert!(db.is_loaded());
+
+        let steps = steps.lock();
+        let namespaces: Vec<_> = steps
+            .iter()
+            .filter_map(|step| match step {
+                OpenProgress::NamespaceLoaded { objects, total, .. } => Some((*objects, *total)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(namespaces, vec![(50, 2), (50, 2)]);
+        assert!(matches!(
+            steps.last(),
+            Some(OpenProgress::Ready { objects: 100, .. })
+        ));
+    }
+}
@@ -0,0 +1,446 @@
+//! Resumable bulk import.
+//!
+//! [`DB::import`] loads newline-delimited GeoJSON (see
+//! [`crate::compute::import`]) in chunks and records each chunk in a manifest
+//! file: its size and CRC32 when it starts, and again when its points are
+//! durable. Rerunning an interrupted import with the same
+//! input and manifest skips the finished chunks, replays the one that was in
+//! progress without the points it already wrote, and carries on from there,
+//! so every point is imported exactly once.
+
+use super::DB;
+use super::access_log::micros;
+use super::cold_state::crc32;
+use super::fsync::sync_file;
+use crate::compute::import::{ImportRecord, parse_feature};
+use crate::compute::validation;
+use crate::config::{SetOptions, SyncMode};
+use crate::error::{Result, SpatioError};
+use spatio_types::stats::Operation;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+const MANIFEST_HEADER: &str = "#spatio-import v1";
+
+/// Default number of points per import chunk.
+pub const DEFAULT_IMPORT_CHUNK_SIZE: usize = 10_000;
+
+/// How [`DB::import`] splits its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportOptions {
+    /// Points per chunk. A chunk is the unit recorded in the manifest, so it
+    /// is also the most an interrupted import re-reads.
+    pub chunk_size: usize,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: DEFAULT_IMPORT_CHUNK_SIZE,
+        }
+    }
+}
+
+/// What [`DB::import`] did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ImportReport {
+    /// Chunks written by this run, including a resumed partial chunk.
+    pub chunks_imported: usize,
+    /// Chunks the manifest already listed as finished.
+    pub chunks_skipped: usize,
+    /// Points written by this run.
+    pub points_imported: usize,
+    /// Points of finished chunks, and points of a resumed chunk that were
+    /// already in the history.
+    pub points_skipped: usize,
+}
+
+/// A chunk as the manifest records it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ChunkEntry {
+    points: usize,
+    checksum: u32,
+    done: bool,
+}
+
+/// The manifest file: a header naming the namespace, then `begin` and `done`
+/// lines per chunk, each synced before the import moves on.
+struct Manifest {
+    file: File,
+    chunks: HashMap<usize, ChunkEntry>,
+}
+
+impl Manifest {
+    fn open(path: &Path, namespace: &str) -> Result<Manifest> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+
+        let header = format!("{MANIFEST_HEADER} {namespace}");
+        let mut chunks = HashMap::new();
+        let mut lines = contents.lines();
+        match lines.next() {
+            None => {
+                writeln!(file, "{header}")?;
+                sync_file(&file, SyncMode::Data)?;
+            }
+            Some(first) if first == header => {}
+            Some(first) => {
+                return Err(SpatioError::InvalidInput(format!(
+                    "Import manifest {} belongs to another import ({first:?})",
+                    path.display()
+                )));
+            }
+        }
+        // A line cut short by a crash is ignored: the step it recorded is
+        // redone.
+        for line in lines {
+            let fields: Vec<&str> = line.split(' ').collect();
+            match fields.as_slice() {
+                ["begin", index, points, checksum] => {
+                    if let (Ok(index), Ok(points), Ok(checksum)) = (
+                        index.parse(),
+                        points.parse(),
+                        u32::from_str_radix(checksum, 16),
+                    ) {
+                        chunks.insert(
+                            index,
+                            ChunkEntry {
+                                points,
+                                checksum,
+                                done: false,
+                            },
+                        );
+                    }
+                }
+                ["done", index] => {
+                    if let Some(entry) = index.parse().ok().and_then(|i| chunks.get_mut(&i)) {
+                        entry.done = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            writeln!(file)?;
+        }
+        file.seek(SeekFrom::End(0))?;
+        Ok(Manifest { file, chunks })
+    }
+
+    fn record(&mut self, line: std::fmt::Arguments) -> Result<()> {
+        writeln!(self.file, "{line}")?;
+        sync_file(&self.file, SyncMode::Data)?;
+        Ok(())
+    }
+
+    fn begin(&mut self, index: usize, points: usize, checksum: u32) -> Result<()> {
+        self.record(format_args!("begin {index} {points} {checksum:08x}"))?;
+        self.chunks.insert(
+            index,
+            ChunkEntry {
+                points,
+                checksum,
+                done: false,
+            },
+        );
+        Ok(())
+    }
+
+    fn finish(&mut self, index: usize) -> Result<()> {
+        self.record(format_args!("done {index}"))?;
+        if let Some(entry) = self.chunks.get_mut(&index) {
+            entry.done = true;
+        }
+        Ok(())
+    }
+}
+
+/// The next chunk of non-empty lines, with the 1-based line number of each,
+/// or an empty chunk at the end of the input.
+fn read_chunk(
+    input: &mut impl BufRead,
+    size: usize,
+    line_number: &mut usize,
+) -> Result<Vec<(usize, String)>> {
+    let mut chunk = Vec::new();
+    let mut line = String::new();
+    while chunk.len() < size {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+        *line_number += 1;
+        let trimmed = line.trim();
+        if !trimmed.is_empty() {
+            chunk.push((*line_number, trimmed.to_string()));
+        }
+    }
+    Ok(chunk)
+}
+
+fn checksum(chunk: &[(usize, String)]) -> u32 {
+    let mut bytes = Vec::new();
+    for (_, line) in chunk {
+        bytes.extend_from_slice(line.as_bytes());
+        bytes.push(b'\n');
+    }
+    crc32(&bytes)
+}
+
+impl DB {
+    /// Import points from newline-delimited GeoJSON (as written by
+    /// [`DB::export`] in [`ExportFormat::GeoJsonLines`]) into `namespace`,
+    /// tracking progress in the manifest file at `manifest`.
+    ///
+    /// Points are written in input order with their recorded timestamps, so
+    /// both current locations and trajectory history are restored. If the
+    /// import is interrupted, call it again with the same input, namespace
+    /// and manifest: finished chunks are skipped, and points of the chunk
+    /// that was in progress are only written if they are not in the history
+    /// yet. The input must not change between runs; a chunk that differs
+    /// from its manifest entry fails the import.
+    ///
+    /// A malformed line fails the import before any point of its chunk is
+    /// written, and the error names the line.
+    ///
+    /// ```
+    /// use spatio::{DB, ExportFormat, ExportSpec, ImportOptions, Point3d};
+    ///
+    /// let source = DB::memory().unwrap();
+    /// source
+    ///     .upsert("fleet", "truck", Point3d::new(1.0, 2.0, 0.0), serde_json::json!({}), None)
+    ///     .unwrap();
+    /// let mut dump = Vec::new();
+    /// source
+    ///     .export("fleet", &ExportSpec::new(ExportFormat::GeoJsonLines), &mut dump)
+    ///     .unwrap();
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let manifest = dir.path().join("fleet.manifest");
+    /// let target = DB::memory().unwrap();
+    /// let report = target
+    ///     .import("fleet", dump.as_slice(), &manifest, ImportOptions::default())
+    ///     .unwrap();
+    /// assert_eq!(report.points_imported, 1);
+    ///
+    /// // Running it again finds the work done.
+    /// let again = target
+    ///     .import("fleet", dump.as_slice(), &manifest, ImportOptions::default())
+    ///     .unwrap();
+    /// assert_eq!(again.points_imported, 0);
+    /// ```
+    ///
+    /// [`ExportFormat::GeoJsonLines`]: crate::ExportFormat::GeoJsonLines
+    pub fn import<R: BufRead, P: AsRef<Path>>(
+        &self,
+        namespace: &str,
+        mut input: R,
+        manifest: P,
+        options: ImportOptions,
+    ) -> Result<ImportReport> {
+        db_span!("spatio.import", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::InsertTrajectory)?;
+        super::validate_identifier("namespace", namespace)?;
+        if options.chunk_size == 0 {
+            return Err(SpatioError::InvalidInput(
+                "Import chunk size must be greater than zero".to_string(),
+            ));
+        }
+
+        let mut manifest = Manifest::open(manifest.as_ref(), namespace)?;
+        let mut report = ImportReport::default();
+        let mut line_number = 0;
+        for index in 0.. {
+            let chunk = read_chunk(&mut input, options.chunk_size, &mut line_number)?;
+            if chunk.is_empty() {
+                break;
+            }
+            let checksum = checksum(&chunk);
+            let resumed = match manifest.chunks.get(&index) {
+                Some(entry) if (entry.points, entry.checksum) != (chunk.len(), checksum) => {
+                    return Err(SpatioError::InvalidInput(format!(
+                        "Import input changed since the manifest was written: chunk {index} \
+                         (from line {}) differs",
+                        chunk[0].0
+                    )));
+                }
+                Some(entry) if entry.done => {
+                    report.chunks_skipped += 1;
+                    report.points_skipped += chunk.len();
+                    continue;
+                }
+                Some(_) => true,
+                None => false,
+            };
+
+            let records = chunk
+                .iter()
+                .map(|(line, text)| {
+                    parse_feature(text)
+                        .and_then(|record| {
+                            validation::validate_geographic_point_3d(&record.position)?;
+                            Ok(record)
+                        })
+                        .map_err(|e| SpatioError::InvalidInput(format!("Import line {line}: {e}")))
+                })
+                .collect::<Result<Vec<ImportRecord>>>()?;
+            if !resumed {
+                manifest.begin(index, chunk.len(), checksum)?;
+            }
+            self.cold.group(|| {
+                for record in records {
+                    if resumed && self.has_point(namespace, &record)? {
+                        report.points_skipped += 1;
+                        continue;
+                    }
+                    self.write_point(
+                        namespace,
+                        &record.object_id,
+                        record.position,
+                        record.metadata,
+                        Some(SetOptions::with_timestamp(record.timestamp)),
+                    )?;
+                    report.points_imported += 1;
+                }
+                Ok(())
+            })?;
+            self.cold.flush()?;
+            manifest.finish(index)?;
+            report.chunks_imported += 1;
+        }
+        Ok(report)
+    }
+
+    /// Whether the history of `namespace` already holds `record`.
+    fn has_point(&self, namespace: &str, record: &ImportRecord) -> Result<bool> {
+        let history = self.cold.query_trajectory(
+            namespace,
+            &record.object_id,
+            record.timestamp,
+            record.timestamp,
+            usize::MAX,
+        )?;
+        Ok(history.iter().any(|update| {
+            micros(update.timestamp) == micros(record.timestamp)
+                && update.position == record.position
+                && update.metadata == record.metadata
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spatio_types::point::Point3d;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn feature(id: &str, secs: u64, x: f64) -> String {
+        format!(
+            r#"{{"type":"Feature","geometry":{{"type":"Point","coordinates":[{x},0.0]}},"properties":{{"object_id":"{id}","timestamp":{secs},"metadata":{{}}}}}}"#
+        )
+    }
+
+    fn history_len(db: &DB, id: &str) -> usize {
+        db.query_trajectory("fleet", id, ..=UNIX_EPOCH + Duration::from_secs(100), 100)
+            .unwrap()
+            .len()
+    }
+
+    #[test]
+    fn test_interrupted_import_resumes_without_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("import.manifest");
+        let input: String = (0..10)
+            .map(|i| feature(if i % 2 == 0 { "a" } else { "b" }, i, i as f64) + "\n")
+            .collect();
+        let options = ImportOptions { chunk_size: 4 };
+
+        // Simulate a crash partway through the second chunk: the first chunk
+        // finished, and two points of the second were written.
+        let db = DB::memory().unwrap();
+        let write = |line: &str| {
+            let record = parse_feature(line).unwrap();
+            db.write_point(
+                "fleet",
+                &record.object_id,
+                record.position,
+                record.metadata,
+                Some(SetOptions::with_timestamp(record.timestamp)),
+            )
+            .unwrap();
+        };
+        {
+            let mut input = input.as_bytes();
+            let mut manifest = Manifest::open(&manifest, "fleet").unwrap();
+            let first = read_chunk(&mut input, 4, &mut 0).unwrap();
+            manifest.begin(0, 4, checksum(&first)).unwrap();
+            first.iter().for_each(|(_, line)| write(line));
+            manifest.finish(0).unwrap();
+            let second = read_chunk(&mut input, 4, &mut 0).unwrap();
+            manifest.begin(1, 4, checksum(&second)).unwrap();
+            second[..2].iter().for_each(|(_, line)| write(line));
+        }
+
+        let report = db
+            .import("fleet", input.as_bytes(), &manifest, options)
+            .unwrap();
+        assert_eq!(
+            report,
+            ImportReport {
+                chunks_imported: 2,
+                chunks_skipped: 1,
+                points_imported: 4,
+                points_skipped: 6,
+            }
+        );
+        assert_eq!(history_len(&db, "a"), 5);
+        assert_eq!(history_len(&db, "b"), 5);
+        let b = db.get("fleet", "b").unwrap().unwrap();
+        assert_eq!(b.position, Point3d::new(9.0, 0.0, 0.0));
+
+        let again = db
+            .import("fleet", input.as_bytes(), &manifest, options)
+            .unwrap();
+        assert_eq!(again.points_imported, 0);
+        assert_eq!(again.chunks_skipped, 3);
+    }
+
+    #[test]
+    fn test_import_rejects_changed_input_and_bad_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("import.manifest");
+        let db = DB::memory().unwrap();
+        let options = ImportOptions { chunk_size: 2 };
+        let input = format!("{}\n{}\n", feature("a", 1, 1.0), feature("a", 2, 2.0));
+        db.import("fleet", input.as_bytes(), &manifest, options)
+            .unwrap();
+
+        let changed = format!("{}\n{}\n", feature("a", 1, 1.0), feature("a", 2, 3.0));
+        assert!(matches!(
+            db.import("fleet", changed.as_bytes(), &manifest, options),
+            Err(SpatioError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            db.import("other", input.as_bytes(), &manifest, options),
+            Err(SpatioError::InvalidInput(_))
+        ));
+
+        let bad = format!("{}\n\nnot json\n", feature("c", 1, 1.0));
+        let err = db
+            .import("fleet", bad.as_bytes(), dir.path().join("bad"), options)
+            .unwrap_err();
+        assert!(err.to_string().contains("line 3"), "{err}");
+        assert!(db.get("fleet", "c").unwrap().is_none());
+    }
+}
@@ -0,0 +1,284 @@
+//! Buffered writer for the trajectory log.
+//!
+//! By default records go through a `BufWriter`, and a sync is a `write` of
+//! the buffer followed by a separate `fsync` (see [`fsync::sync_file`]). With
+//! the `uring` feature on Linux, the log is written through an io_uring
+//! instead: the buffer is larger, and a sync submits the write and the
+//! `fsync` linked together in a single system call, so under
+//! `SyncPolicy::Always` each durable write costs one round trip to the
+//! kernel rather than two. Where io_uring is unavailable (kernels before
+//! 5.6, or seccomp profiles that block it, as container runtimes often do)
+//! the log falls back to the `BufWriter`.
+
+use super::fsync;
+use spatio_types::config::SyncMode;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+pub(crate) enum LogWriter {
+    Buffered(BufWriter<File>),
+    #[cfg(all(feature = "uring", target_os = "linux"))]
+    Uring(Box<uring::UringWriter>),
+}
+
+impl LogWriter {
+    /// Writer appending to `file`, which must be opened for appending.
+    pub fn new(file: File) -> Self {
+        #[cfg(all(feature = "uring", target_os = "linux"))]
+        let file = match uring::UringWriter::new(file) {
+            Ok(writer) => return Self::Uring(Box::new(writer)),
+            Err((file, e)) => {
+                uring::warn_unavailable(&e);
+                file
+            }
+        };
+        Self::Buffered(BufWriter::new(file))
+    }
+
+    /// Push buffered bytes to the OS and sync them as `mode` asks.
+    pub fn sync(&mut self, mode: SyncMode) -> io::Result<()> {
+        match self {
+            Self::Buffered(writer) => {
+                writer.flush()?;
+                fsync::sync_file(writer.get_ref(), mode)
+            }
+            #[cfg(all(feature = "uring", target_os = "linux"))]
+            Self::Uring(writer) => writer.sync(mode),
+        }
+    }
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Buffered(writer) => writer.write(buf),
+            #[cfg(all(feature = "uring", target_os = "linux"))]
+            Self::Uring(writer) => writer.write(buf),
+        }
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            Self::Buffered(writer) => writer.write_all(buf),
+            #[cfg(all(feature = "uring", target_os = "linux"))]
+            Self::Uring(writer) => writer.write_all(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Buffered(writer) => writer.flush(),
+            #[cfg(all(feature = "uring", target_os = "linux"))]
+            Self::Uring(writer) => writer.flush(),
+        }
+    }
+}
+
+#[cfg(all(feature = "uring", target_os = "linux"))]
+mod uring {
+    use io_uring::{IoUring, opcode, squeue, types};
+    use spatio_types::config::SyncMode;
+    use std::fs::File;
+    use std::io::{self, Write};
+    use std::os::unix::io::AsRawFd;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Bytes buffered before they are submitted without a sync.
+    const BUFFER_CAPACITY: usize = 64 * 1024;
+    const WRITE: u64 = 1;
+    const FSYNC: u64 = 2;
+
+    static WARNED: AtomicBool = AtomicBool::new(false);
+
+    pub(super) fn warn_unavailable(error: &io::Error) {
+        if !WARNED.swap(true, Ordering::Relaxed) {
+            log::warn!(
+                "io_uring is unavailable ({}); writing the log with plain system calls",
+                error
+            );
+        }
+    }
+
+    pub(crate) struct UringWriter {
+        // Dropped first, so no operation outlives the buffers below.
+        ring: IoUring,
+        file: File,
+        buf: Vec<u8>,
+        /// File offset the buffer is written at.
+        offset: u64,
+        /// The buffer of a write whose completion was never reaped, because
+        /// waiting on the ring failed. The kernel may still read it, so it
+        /// lives as long as the ring, and the writer takes no more writes.
+        stranded: Option<Vec<u8>>,
+    }
+
+    impl UringWriter {
+        /// Set up a ring for `file`, handing the file back if that fails.
+        pub fn new(file: File) -> Result<Self, (File, io::Error)> {
+            let offset = match file.metadata() {
+                Ok(metadata) => metadata.len(),
+                Err(e) => return Err((file, e)),
+            };
+            match IoUring::new(4) {
+                Ok(ring) => Ok(Self {
+                    ring,
+                    file,
+                    buf: Vec::with_capacity(BUFFER_CAPACITY),
+                    offset,
+                    stranded: None,
+                }),
+                Err(e) => Err((file, e)),
+            }
+        }
+
+        pub fn sync(&mut self, mode: SyncMode) -> io::Result<()> {
+            let flags = match mode {
+                SyncMode::All => types::FsyncFlags::empty(),
+                // As `fsync::sync_file` does on Linux.
+                SyncMode::Data | SyncMode::Barrier => types::FsyncFlags::DATASYNC,
+            };
+            self.submit(Some(flags))
+        }
+
+        /// Write out the buffer, then `fsync` if `sync` is given, retrying
+        /// short writes until all of it is written.
+        fn submit(&mut self, sync: Option<types::FsyncFlags>) -> io::Result<()> {
+            if self.stranded.is_some() {
+                return Err(io::Error::other("io_uring log writer failed earlier"));
+            }
+            let fd = types::Fd(self.file.as_raw_fd());
+            let mut written = 0;
+            let result = loop {
+                let remaining = &self.buf[written..];
+                let mut entries = Vec::with_capacity(2);
+                if !remaining.is_empty() {
+                    let len = remaining.len().min(u32::MAX as usize) as u32;
+                    let write = opcode::Write::new(fd, remaining.as_ptr(), len)
+                        .offset(self.offset)
+                        .build()
+                        .user_data(WRITE);
+                    // Run the fsync only once the whole write has succeeded;
+                    // after a short one it completes with `ECANCELED`.
+                    entries.push(match sync {
+                        Some(_) => write.flags(squeue::Flags::IO_LINK),
+                        None => write,
+                    });
+                }
+                if let Some(flags) = sync {
+                    entries.push(opcode::Fsync::new(fd).flags(flags).build().user_data(FSYNC));
+                }
+                if entries.is_empty() {
+                    break Ok(());
+                }
+
+                // SAFETY: the buffer and the file outlive the entries, since
+                // their completions are reaped below before either changes,
+                // or the buffer is stranded until the ring is gone.
+                unsafe { self.ring.submission().push_multiple(&entries) }
+                    .map_err(|_| io::Error::other("io_uring submission queue is full"))?;
+                let (mut wrote, mut synced) = (None, None);
+                let mut pending = entries.len();
+                while pending > 0 {
+                    match self.ring.submit_and_wait(pending) {
+                        Ok(_) => {}
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(e) => {
+                            self.stranded = Some(std::mem::take(&mut self.buf));
+                            return Err(e);
+                        }
+                    }
+                    for entry in self.ring.completion() {
+                        pending -= 1;
+                        match entry.user_data() {
+                            WRITE => wrote = Some(entry.result()),
+                            _ => synced = Some(entry.result()),
+                        }
+                    }
+                }
+
+                match wrote {
+                    Some(n) if n < 0 => break Err(io::Error::from_raw_os_error(-n)),
+                    Some(0) => break Err(io::ErrorKind::WriteZero.into()),
+                    Some(n) => {
+                        written += n as usize;
+                        self.offset += n as u64;
+                    }
+                    None => {}
+                }
+                if written < self.buf.len() {
+                    continue;
+                }
+                break match synced {
+                    Some(n) if n < 0 => Err(io::Error::from_raw_os_error(-n)),
+                    _ => Ok(()),
+                };
+            };
+            self.buf.drain(..written);
+            result
+        }
+    }
+
+    impl Write for UringWriter {
+        fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+            if self.buf.len() + data.len() > BUFFER_CAPACITY && !self.buf.is_empty() {
+                self.submit(None)?;
+            }
+            self.buf.extend_from_slice(data);
+            Ok(data.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.submit(None)
+        }
+    }
+
+    impl Drop for UringWriter {
+        fn drop(&mut self) {
+            let _ = self.submit(None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+
+    #[test]
+    fn test_writes_reach_the_file_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log");
+        std::fs::write(&path, "header\n").unwrap();
+        let open = || OpenOptions::new().append(true).open(&path).unwrap();
+
+        let mut writer = LogWriter::new(open());
+        let mut expected = String::from("header\n");
+        for (i, mode) in [SyncMode::All, SyncMode::Data, SyncMode::Barrier]
+            .into_iter()
+            .enumerate()
+        {
+            let line = format!("record {i}\n");
+            writer.write_all(line.as_bytes()).unwrap();
+            expected.push_str(&line);
+            writer.sync(mode).unwrap();
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), expected);
+        }
+
+        // More than a buffer's worth, then whatever is left on drop.
+        let big = "x".repeat(100 * 1024) + "\n";
+        writer.write_all(big.as_bytes()).unwrap();
+        writer.flush().unwrap();
+        writer.write_all(b"tail\n").unwrap();
+        drop(writer);
+        expected.push_str(&big);
+        expected.push_str("tail\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), expected);
+
+        // A writer reopened on the file appends after what is there.
+        let mut writer = LogWriter::new(open());
+        writer.write_all(b"reopened\n").unwrap();
+        writer.sync(SyncMode::Data).unwrap();
+        expected.push_str("reopened\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), expected);
+    }
+}
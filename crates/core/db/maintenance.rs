@@ -0,0 +1,124 @@
+//! Rewrites of the trajectory log that hold up writes.
+//!
+//! Compacting the log and writing a checkpoint each hold the log for a full
+//! pass over it, so writes wait until they finish. [`DB::maintenance`]
+//! reports the one in progress and how long the last of its kind took, so a
+//! server can turn writers away with a hint of when to retry rather than let
+//! their latency grow unnoticed.
+//!
+//! [`DB::maintenance`]: super::DB::maintenance
+
+use parking_lot::Mutex;
+use std::time::{Duration, Instant};
+
+/// What a [`Maintenance`] is doing to the log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaintenanceKind {
+    /// Rewriting the log without expired, thinned or forgotten history.
+    Compaction,
+    /// Writing a recovery checkpoint.
+    Checkpoint,
+}
+
+impl std::fmt::Display for MaintenanceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            MaintenanceKind::Compaction => "compacting its log",
+            MaintenanceKind::Checkpoint => "writing a checkpoint",
+        })
+    }
+}
+
+/// A log rewrite in progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Maintenance {
+    pub kind: MaintenanceKind,
+    /// Time since it started.
+    pub elapsed: Duration,
+    /// How long the previous rewrite of the same kind took, if one ran since
+    /// open.
+    pub last_duration: Option<Duration>,
+}
+
+impl Maintenance {
+    /// Time left if it takes as long as the previous one, or `None` when
+    /// there was none or it has already run longer.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.last_duration?.checked_sub(self.elapsed)
+    }
+}
+
+/// The rewrite in progress and how long the last of each kind took.
+#[derive(Default)]
+pub(crate) struct MaintenanceTracker {
+    current: Mutex<Option<(MaintenanceKind, Instant)>>,
+    /// Indexed by kind.
+    last: Mutex<[Option<Duration>; 2]>,
+}
+
+/// Marks a rewrite as in progress until dropped.
+pub(crate) struct MaintenanceGuard<'a> {
+    tracker: &'a MaintenanceTracker,
+    kind: MaintenanceKind,
+    started: Instant,
+}
+
+impl MaintenanceTracker {
+    /// Mark a rewrite of `kind` as started. Call it with the log locked, so
+    /// rewrites never overlap.
+    pub fn begin(&self, kind: MaintenanceKind) -> MaintenanceGuard<'_> {
+        let started = Instant::now();
+        *self.current.lock() = Some((kind, started));
+        MaintenanceGuard {
+            tracker: self,
+            kind,
+            started,
+        }
+    }
+
+    pub fn current(&self) -> Option<Maintenance> {
+        let (kind, started) = (*self.current.lock())?;
+        Some(Maintenance {
+            kind,
+            elapsed: started.elapsed(),
+            last_duration: self.last.lock()[kind as usize],
+        })
+    }
+}
+
+impl Drop for MaintenanceGuard<'_> {
+    fn drop(&mut self) {
+        self.tracker.last.lock()[self.kind as usize] = Some(self.started.elapsed());
+        *self.tracker.current.lock() = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracks_the_rewrite_in_progress() {
+        let tracker = MaintenanceTracker::default();
+        assert_eq!(tracker.current(), None);
+
+        let compacting = tracker.begin(MaintenanceKind::Compaction);
+        let current = tracker.current().unwrap();
+        assert_eq!(current.kind, MaintenanceKind::Compaction);
+        assert_eq!(current.last_duration, None);
+        assert_eq!(current.remaining(), None);
+        std::thread::sleep(Duration::from_millis(20));
+        drop(compacting);
+        assert_eq!(tracker.current(), None);
+
+        // The next compaction is expected to take as long; checkpoints are
+        // timed apart.
+        let compacting = tracker.begin(MaintenanceKind::Compaction);
+        let current = tracker.current().unwrap();
+        assert!(current.last_duration.unwrap() >= Duration::from_millis(20));
+        assert!(current.remaining().is_some());
+        drop(compacting);
+        let _checkpointing = tracker.begin(MaintenanceKind::Checkpoint);
+        assert_eq!(tracker.current().unwrap().last_duration, None);
+    }
+}
@@ -0,0 +1,168 @@
+//! Metadata indexes: the objects of a namespace by the value of one metadata
+//! field.
+//!
+//! The spatial index cannot narrow a scan for `fuel == "diesel"`. With an
+//! index on `fuel`, [`DB::query`](super::DB::query) starts from the objects
+//! carrying that value instead and tests each one exactly. Indexes live in
+//! memory only, are filled from the current locations when created, and are
+//! updated on every write to their namespace, like views.
+
+use crate::compute::query::Predicate;
+use crate::db::CurrentLocation;
+use parking_lot::{Mutex, RwLock};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+/// Index key of a metadata value. `serde_json` keeps object keys sorted, so
+/// equal values serialize the same.
+fn value_key(value: &serde_json::Value) -> String {
+    value.to_string()
+}
+
+#[derive(Default)]
+struct Entries {
+    /// Object IDs by value key.
+    by_value: HashMap<String, BTreeSet<String>>,
+    /// Value key of each indexed object, for unindexing it.
+    by_object: HashMap<String, String>,
+}
+
+impl Entries {
+    /// Index `object_id` under `location`'s value of `field`, or nowhere if
+    /// it has none.
+    fn set(&mut self, object_id: &str, field: &str, location: Option<&CurrentLocation>) {
+        if let Some(old) = self.by_object.remove(object_id)
+            && let Some(ids) = self.by_value.get_mut(&old)
+        {
+            ids.remove(object_id);
+            if ids.is_empty() {
+                self.by_value.remove(&old);
+            }
+        }
+        if let Some(value) = location.and_then(|loc| loc.metadata.get(field)) {
+            let key = value_key(value);
+            self.by_value
+                .entry(key.clone())
+                .or_default()
+                .insert(object_id.to_string());
+            self.by_object.insert(object_id.to_string(), key);
+        }
+    }
+}
+
+type Index = Arc<Mutex<Entries>>;
+
+/// All metadata indexes of a database, by namespace and field.
+#[derive(Default)]
+pub(crate) struct MetadataIndexes {
+    indexes: RwLock<HashMap<(String, String), Index>>,
+}
+
+impl MetadataIndexes {
+    /// Index `field` in `namespace`, filling the index with `populate` before
+    /// any write can touch it. Returns `false` if the index exists.
+    pub fn create(
+        &self,
+        namespace: &str,
+        field: &str,
+        populate: impl FnOnce() -> Vec<Arc<CurrentLocation>>,
+    ) -> bool {
+        let index = Index::default();
+        // Filled under its lock, as in `Views::create`.
+        let mut entries = index.lock();
+        {
+            let mut indexes = self.indexes.write();
+            let key = (namespace.to_string(), field.to_string());
+            if indexes.contains_key(&key) {
+                return false;
+            }
+            indexes.insert(key, index.clone());
+        }
+        for loc in populate() {
+            entries.set(&loc.object_id, field, Some(&loc));
+        }
+        true
+    }
+
+    pub fn drop_index(&self, namespace: &str, field: &str) -> bool {
+        self.indexes
+            .write()
+            .remove(&(namespace.to_string(), field.to_string()))
+            .is_some()
+    }
+
+    /// Indexed fields of `namespace`, sorted.
+    pub fn fields(&self, namespace: &str) -> Vec<String> {
+        let mut fields: Vec<String> = self
+            .indexes
+            .read()
+            .keys()
+            .filter(|(ns, _)| ns == namespace)
+            .map(|(_, field)| field.clone())
+            .collect();
+        fields.sort();
+        fields
+    }
+
+    /// Re-index `object_id` in every index of `namespace`. `current` reads the
+    /// object's latest location (`None` once deleted).
+    pub fn refresh(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        current: impl Fn() -> Option<Arc<CurrentLocation>>,
+    ) {
+        let affected: Vec<(String, Index)> = {
+            let indexes = self.indexes.read();
+            if indexes.is_empty() {
+                return;
+            }
+            indexes
+                .iter()
+                .filter(|((ns, _), _)| ns == namespace)
+                .map(|((_, field), index)| (field.clone(), index.clone()))
+                .collect()
+        };
+        for (field, index) in affected {
+            let mut entries = index.lock();
+            // Read under the lock, so concurrent writers leave the newest
+            // location indexed.
+            entries.set(object_id, &field, current().as_deref());
+        }
+    }
+
+    /// The only objects of `namespace` that can match `predicate`, as far as
+    /// the indexes can tell, in ID order. `None` when the predicate has no
+    /// indexed conditions narrowing it.
+    ///
+    /// The set may hold objects that no longer match; callers test each
+    /// candidate exactly.
+    pub fn candidates(&self, namespace: &str, predicate: &Predicate) -> Option<BTreeSet<String>> {
+        match predicate {
+            Predicate::MetadataEquals { field, value } => {
+                let index = self
+                    .indexes
+                    .read()
+                    .get(&(namespace.to_string(), field.clone()))
+                    .cloned()?;
+                let entries = index.lock();
+                Some(
+                    entries
+                        .by_value
+                        .get(&value_key(value))
+                        .cloned()
+                        .unwrap_or_default(),
+                )
+            }
+            Predicate::All(all) => all
+                .iter()
+                .filter_map(|p| self.candidates(namespace, p))
+                .reduce(|acc, ids| acc.intersection(&ids).cloned().collect()),
+            Predicate::Any(any) => any.iter().try_fold(BTreeSet::new(), |mut acc, p| {
+                acc.extend(self.candidates(namespace, p)?);
+                Some(acc)
+            }),
+            _ => None,
+        }
+    }
+}
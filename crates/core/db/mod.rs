@@ -3,29 +3,114 @@
 //! This module defines the main `DB` type along with spatio-temporal helpers and
 //! persistence wiring that power the public `Spatio` API.
 
-use crate::compute::validation;
-use crate::config::{Config, DbStats, SetOptions, TemporalPoint};
+use crate::compute::export::{self, ExportRecord, ExportSpec};
+use crate::compute::passes::{self, TrajectoryMatch};
+use crate::compute::query::Predicate;
+use crate::compute::spatial::ZoneGeometry;
+use crate::compute::trips::{self, Trip};
+use crate::compute::validation::{self, ValidationError};
+use crate::compute::violations::{self, SpeedViolation};
+use crate::config::{
+    Config, DbStats, HistoryEntry, HistoryEventKind, NamespaceConfig, PersistenceStatus,
+    ScanDirection, SetOptions, TemporalPoint, TemporalPoint3D, TimeRange, Trajectory,
+    TrajectorySummary,
+};
 use crate::error::{Result, SpatioError};
+use pagination::PageKind;
+use spatio_types::fence::Fence;
+use spatio_types::stats::Operation;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::{Bound, RangeBounds};
 use std::path::Path;
 
 use std::time::SystemTime;
 
+/// Enter a debug-level `tracing` span for the rest of the enclosing block when
+/// the `tracing` feature is enabled; expands to nothing otherwise.
+macro_rules! db_span {
+    ($name:literal $(, $($fields:tt)*)?) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!($name $(, $($fields)*)?).entered();
+    };
+}
+
+mod access_log;
+mod changes;
 mod cold_state;
+mod compaction;
+mod compression;
+mod cow_map;
+mod disk_space;
+mod durability;
+mod expiration;
+mod fences;
+mod fsync;
+mod geojson_io;
+mod hits;
+mod hooks;
 mod hot_state;
+mod hydration;
+mod import;
+mod log_writer;
+mod maintenance;
+mod metadata_index;
 mod namespace;
+mod namespace_settings;
+mod objects;
+mod op_stats;
+mod pagination;
+mod query_builder;
+mod reader;
+mod rejection_log;
+mod segments;
+mod slow_queries;
+mod subscribers;
+mod verify;
+mod views;
+mod warm_up;
 
 #[cfg(feature = "sync")]
 mod sync;
 
+#[cfg(feature = "async")]
+mod async_db;
+
+pub use access_log::{AccessLogEntry, AccessQuery};
+pub use changes::{ChangeEvent, ChangeFeed};
 pub use cold_state::{ColdState, LocationUpdate};
-pub use hot_state::{CurrentLocation, HotState};
+pub use compaction::{
+    CompactionContext, CompactionPolicy, KeyCountBased, OutsideHours, SizeBased, TimeBased,
+};
+pub use fences::{FenceEvent, FenceOptions, FenceSubscription};
+pub use geojson_io::{GeoJsonImportOptions, GeoJsonImportReport};
+pub use hits::{NearbyHit, ZoneHit};
+pub use hooks::{HookEvent, HookMode, WriteHook};
+pub use hot_state::{CurrentLocation, HotState, SPEED_LIMIT_KEY, Zone};
+pub use hydration::OpenProgress;
+pub(crate) use hydration::ProgressFn;
+pub use import::{DEFAULT_IMPORT_CHUNK_SIZE, ImportOptions, ImportReport};
+pub use maintenance::{Maintenance, MaintenanceKind};
 pub use namespace::{Namespace, NamespaceManager};
+pub use objects::{ObjectHit, ObjectType, SpatialObject, StoredObject};
+pub use op_stats::STATS_WINDOW_MINUTES;
+pub use pagination::{BboxPage, Page};
+pub use query_builder::QueryBuilder;
+pub use reader::DBReader;
+pub use rejection_log::RejectionEntry;
+pub use slow_queries::{SLOW_QUERY_LOG_CAPACITY, SlowQuery};
+pub use subscribers::SUBSCRIBER_CAPACITY;
+pub use verify::{Inconsistency, RepairReport, VerifyReport};
+pub use views::{ViewEvent, ViewSubscription};
+pub use warm_up::WarmUpReport;
 
 #[cfg(feature = "sync")]
 pub use sync::SyncDB;
 
-use std::sync::Arc;
+#[cfg(feature = "async")]
+pub use async_db::{AsyncDB, DEFAULT_WRITE_QUEUE_CAPACITY};
+
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
 
 /// Reject namespace/object_id values that would corrupt the append-only log or
 /// alias the `namespace::object_id` composite key.
@@ -47,6 +132,69 @@ fn validate_identifier(kind: &str, value: &str) -> Result<()> {
     Ok(())
 }
 
+/// `history`, oldest first, as a [`Trajectory`].
+fn to_trajectory(history: &[LocationUpdate]) -> Trajectory {
+    Trajectory::new(
+        history
+            .iter()
+            .map(|u| {
+                TemporalPoint3D::new(
+                    spatio_types::geo::Point::new(u.position.x(), u.position.y()),
+                    u.position.z(),
+                    u.timestamp,
+                )
+            })
+            .collect(),
+    )
+}
+
+/// What [`DB::forget_object`] removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ForgetReport {
+    /// Whether the object had a current location.
+    pub current_location_removed: bool,
+    /// Trajectory log records removed, tombstones included.
+    pub log_records_removed: u64,
+    /// Access log entries naming the object (trajectory queries and queries
+    /// relative to it) removed.
+    pub access_log_entries_removed: u64,
+    /// Rejection log entries for the object removed.
+    pub rejection_log_entries_removed: u64,
+}
+
+/// How much trajectory history [`DB::compact_history`] keeps per object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryCompaction {
+    /// The newest `n` points of each object.
+    KeepLast(usize),
+    /// Points stamped at or after this time.
+    KeepSince(SystemTime),
+}
+
+/// Which checkpoint [`DB::checkpoint`] writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointKind {
+    /// Every current location, replacing the checkpoint snapshot.
+    Full,
+    /// Only the objects written or deleted since the last full checkpoint,
+    /// replacing the previous incremental one.
+    Incremental,
+}
+
+/// What [`DB::checkpoint`] wrote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckpointReport {
+    /// The checkpoint written. An incremental checkpoint is written as a
+    /// full one when there is no full one to base it on.
+    pub kind: CheckpointKind,
+    /// Objects recorded, deletions included.
+    pub objects: usize,
+    /// Sequence of the last write covered.
+    pub sequence: u64,
+    /// CRC32 of the checkpoint file, as recorded in its last line.
+    pub checksum: u32,
+}
+
 /// Embedded spatio-temporal database.
 ///
 /// Optimized for tracking moving objects with hot/cold data separation.
@@ -61,7 +209,30 @@ pub struct DB {
     pub(crate) cold: Arc<ColdState>,
     pub(crate) closed: Arc<AtomicBool>,
     pub(crate) ops_count: Arc<AtomicU64>,
-    #[allow(dead_code)] // retained for configuration introspection
+    /// Reads and queries since open.
+    pub(crate) queries_count: Arc<AtomicU64>,
+    pub(crate) access_log: Option<Arc<access_log::AccessLog>>,
+    pub(crate) rejection_log: Option<Arc<rejection_log::RejectionLog>>,
+    pub(crate) views: Arc<views::Views>,
+    pub(crate) fences: Arc<fences::Fences>,
+    pub(crate) metadata_indexes: Arc<metadata_index::MetadataIndexes>,
+    pub(crate) changes: Arc<changes::ChangeBus>,
+    pub(crate) hooks: Arc<hooks::Hooks>,
+    pub(crate) snapshots: Arc<reader::Snapshots>,
+    pub(crate) op_stats: Arc<op_stats::OpStats>,
+    pub(crate) slow_queries: Arc<slow_queries::SlowQueries>,
+    /// History records removed by expiration since open.
+    pub(crate) expired: Arc<AtomicU64>,
+    /// Started once current locations are loaded.
+    pub(crate) expiration: Arc<OnceLock<expiration::ActiveExpiration>>,
+    /// Started with expiration, if disk watermarks are configured.
+    pub(crate) disk_guard: Arc<OnceLock<disk_space::DiskGuard>>,
+    pub(crate) compaction: Arc<compaction::CompactionSchedule>,
+    pub(crate) hydration: Arc<hydration::Hydration>,
+    pub(crate) namespace_settings: Arc<namespace_settings::NamespaceSettings>,
+    /// Held while zones change and are saved, so the saved file follows the
+    /// order of the changes.
+    pub(crate) zone_writes: Arc<parking_lot::Mutex<()>>,
     pub(crate) config: Config,
 }
 
@@ -77,74 +248,275 @@ impl DB {
 
     /// Open or create a database with custom configuration.
     pub fn open_with_config<P: AsRef<Path>>(path: P, config: Config) -> Result<Self> {
-        let path_ref = path.as_ref();
+        Self::open_inner(path.as_ref(), config, None, None, false)
+    }
+
+    /// Open the files and set up an empty database; current locations are
+    /// loaded afterwards by `DB::load` (see [`hydration`]).
+    fn assemble(
+        path_ref: &Path,
+        config: Config,
+        compaction: Option<Arc<dyn CompactionPolicy>>,
+        background: bool,
+    ) -> Result<Self> {
         let hot = Arc::new(HotState::new());
+        // Before recovery, so recovered objects are indexed with projected coordinates.
+        for (namespace, projection) in &config.namespace_projections {
+            hot.set_projection(namespace, Some(*projection));
+        }
+        for (namespace, capacity) in &config.write_optimized_namespaces {
+            hot.set_write_buffer(namespace, Some(*capacity));
+        }
 
         let sync = cold_state::SyncSettings {
             policy: config.sync_policy,
             mode: config.sync_mode,
             batch_size: config.sync_batch_size,
+            interval: std::time::Duration::from_millis(config.sync_interval_ms),
+            group_commit: config.group_commit_us.map(std::time::Duration::from_micros),
         };
 
+        let buffering = cold_state::HistoryBuffering {
+            namespaces: config.history_buffer_namespaces.clone(),
+            max_points: config.history_buffer_max_points,
+        };
         let cold = if path_ref.to_str() == Some(":memory:") {
             // Pure in-memory: no temp dir, no file, no serialization on writes.
-            Arc::new(ColdState::new_memory(config.buffer_capacity))
+            Arc::new(
+                ColdState::new_memory(config.buffer_capacity).with_history_buffering(buffering),
+            )
         } else {
-            Arc::new(ColdState::new(
-                path_ref,
-                config.buffer_capacity,
-                config.persistence.clone(),
-                sync,
-            )?)
-        };
-
-        // Recover current locations from cold storage (skip for :memory: mode)
-        if path_ref.to_str() != Some(":memory:") {
-            match cold.recover_current_locations() {
-                Ok(recovered) => {
-                    // Persist a fresh checkpoint covering everything recovered so
-                    // the next startup replays only newly appended records. The
-                    // full history log is left intact. Best-effort: a failure here
-                    // only means the next recovery is slower, not incorrect.
-                    if let Err(e) = cold.write_checkpoint(&recovered) {
-                        log::warn!("Failed to write recovery checkpoint: {}", e);
-                    }
+            Arc::new(
+                ColdState::new(
+                    path_ref,
+                    config.buffer_capacity,
+                    config.persistence.clone(),
+                    sync,
+                )?
+                .with_history_buffering(buffering),
+            )
+        };
 
-                    for (key, update) in recovered {
-                        // Parse namespace and object_id from key "namespace::object_id"
-                        if let Some(separator_idx) = key.find("::") {
-                            let namespace = &key[..separator_idx];
-                            let object_id = &key[separator_idx + 2..];
-
-                            // Update hot state with recovered location
-                            if let Err(e) = hot.update_location(
-                                namespace,
-                                object_id,
-                                update.position,
-                                update.metadata,
-                                update.timestamp,
-                            ) {
-                                log::warn!("Failed to recover location for {}: {}", key, e);
-                            }
-                        }
-                    }
-                }
-                Err(e) => {
-                    log::warn!("Failed to recover current locations: {}", e);
-                    // Continue anyway - partial recovery is acceptable
-                }
-            }
-        }
+        let access_log = match &config.access_log {
+            Some(log_config) => Some(Arc::new(access_log::AccessLog::open(log_config)?)),
+            None => None,
+        };
+        let rejection_log = match &config.rejection_log {
+            Some(log_config) => Some(Arc::new(rejection_log::RejectionLog::open(log_config)?)),
+            None => None,
+        };
 
         Ok(Self {
             hot,
+            hooks: Arc::new(hooks::Hooks::new(cold.clone())),
             cold,
             closed: Arc::new(AtomicBool::new(false)),
             ops_count: Arc::new(AtomicU64::new(0)),
+            queries_count: Arc::new(AtomicU64::new(0)),
+            access_log,
+            rejection_log,
+            views: Arc::new(views::Views::default()),
+            fences: Arc::new(fences::Fences::default()),
+            metadata_indexes: Arc::new(metadata_index::MetadataIndexes::default()),
+            changes: Arc::new(changes::ChangeBus::default()),
+            snapshots: Arc::new(reader::Snapshots::default()),
+            op_stats: Arc::new(op_stats::OpStats::default()),
+            slow_queries: Arc::new(slow_queries::SlowQueries::new(
+                config
+                    .slow_query_threshold_ms
+                    .map(std::time::Duration::from_millis),
+            )),
+            expired: Arc::new(AtomicU64::new(0)),
+            expiration: Arc::new(OnceLock::new()),
+            disk_guard: Arc::new(OnceLock::new()),
+            compaction: Arc::new(compaction::CompactionSchedule::new(compaction)),
+            hydration: Arc::new(if background {
+                hydration::Hydration::loading()
+            } else {
+                hydration::Hydration::loaded()
+            }),
+            namespace_settings: Arc::new(namespace_settings::NamespaceSettings::from_config(
+                &config,
+            )),
+            zone_writes: Arc::new(parking_lot::Mutex::new(())),
             config,
         })
     }
 
+    /// Apply history retention and object TTLs left pending while the
+    /// database was closed, then start expiring both in the background if
+    /// configured.
+    fn start_expiration(&self) -> Result<()> {
+        // Expiring writes the log, which a read-only open leaves alone.
+        if self.cold.is_read_only() {
+            return Ok(());
+        }
+        // Reads filter expired points regardless, so a failure only delays
+        // their removal from the log.
+        if let Err(e) = self.scheduled_expire_history(1) {
+            log::warn!("Failed to expire trajectory history: {}", e);
+        }
+        if let Err(e) = self.remove_expired_objects() {
+            log::warn!("Failed to expire objects: {}", e);
+        }
+        if let Some(config) = &self.config.active_expiration
+            && !self.closed.load(Ordering::Acquire)
+        {
+            // Without the thread handles, so the thread keeps no reference
+            // to itself and stops once the last user handle is dropped.
+            let db = DB {
+                expiration: Arc::default(),
+                disk_guard: Arc::default(),
+                ..self.clone()
+            };
+            let batch_size = config.batch_size as u64;
+            let expiration = expiration::ActiveExpiration::spawn(config, move || {
+                if let Err(e) = db.scheduled_expire_history(batch_size) {
+                    log::warn!("Background history expiration failed: {}", e);
+                }
+                if let Err(e) = db.remove_expired_objects() {
+                    log::warn!("Background object expiration failed: {}", e);
+                }
+            })?;
+            let _ = self.expiration.set(expiration);
+        }
+        Ok(())
+    }
+
+    /// Remove expired history if the compaction policy lets it run now,
+    /// leaving the log alone unless at least `batch_size` records (or what
+    /// the policy asks for) have expired.
+    fn scheduled_expire_history(&self, batch_size: u64) -> Result<u64> {
+        let retention = self.namespace_settings.retention();
+        if retention.is_empty() {
+            return Ok(0);
+        }
+        let Some(min_removed) = self.compaction.plan(
+            self.cold.log_bytes(),
+            self.cold.last_sequence(),
+            self.hot.detailed_stats().0,
+            batch_size,
+        ) else {
+            return Ok(0);
+        };
+        let removed = self
+            .cold
+            .expire_history_batch(&retention, SystemTime::now(), min_removed)?;
+        // Below its batch size the log was left as it was.
+        if removed > 0 || min_removed <= 1 {
+            self.compaction
+                .compacted(self.cold.log_bytes(), self.cold.last_sequence());
+        }
+        self.expired.fetch_add(removed, Ordering::Relaxed);
+        Ok(removed)
+    }
+
+    /// Start checking free disk space against the configured watermarks.
+    fn start_disk_guard(&self) -> Result<()> {
+        let (Some(config), Some(dir)) = (&self.config.disk_watermarks, self.cold.log_dir()) else {
+            return Ok(());
+        };
+        if self.closed.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        match disk_space::DiskGuard::spawn(
+            dir.to_path_buf(),
+            self.cold.clone(),
+            self.namespace_settings.clone(),
+            config,
+            self.expired.clone(),
+        ) {
+            Ok(guard) => {
+                let _ = self.disk_guard.set(guard);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Unsupported => {
+                log::warn!("Disk watermarks are not supported on this platform");
+            }
+            Err(e) => return Err(e.into()),
+        }
+        Ok(())
+    }
+
+    /// Record a query in the sampled access log, if one is configured.
+    #[inline]
+    fn log_access(&self, query: impl FnOnce() -> AccessQuery) {
+        if let Some(log) = &self.access_log {
+            log.record(query);
+        }
+    }
+
+    /// This handle without the access log, for running a query relative to
+    /// an object, which is logged by the object's ID instead of its position.
+    fn unlogged(&self) -> DB {
+        DB {
+            access_log: None,
+            ..self.clone()
+        }
+    }
+
+    /// Time a spatial query for the slow-query log, if a threshold is
+    /// configured, until the returned timer drops.
+    #[inline]
+    fn time_query<'a>(
+        &'a self,
+        namespace: &'a str,
+        operation: Operation,
+    ) -> Option<slow_queries::QueryTimer<'a>> {
+        self.slow_queries.start(namespace, operation)
+    }
+
+    /// Record a write rejected by validation in the rejection log, if one is
+    /// configured.
+    fn log_rejection(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        error: &SpatioError,
+        position: &spatio_types::point::Point3d,
+        metadata: &serde_json::Value,
+    ) {
+        if let Some(log) = &self.rejection_log {
+            log.record(namespace, object_id, error, || {
+                serde_json::json!({
+                    "position": [position.x(), position.y(), position.z()],
+                    "metadata": metadata,
+                })
+            });
+        }
+    }
+
+    /// Start an operation on `namespace`: wait until it's loaded (or, for a
+    /// write, until everything is; see [`hydration`]), refuse it if it would
+    /// append to the log on a nearly full disk, and count it in the
+    /// per-minute statistics.
+    #[inline]
+    fn begin(&self, namespace: &str, operation: Operation) -> Result<()> {
+        if !operation.is_query() && self.cold.is_read_only() {
+            return Err(SpatioError::ReadOnly);
+        }
+        if !self.hydration.is_ready() {
+            match operation {
+                Operation::Upsert
+                | Operation::Delete
+                | Operation::InsertTrajectory
+                | Operation::ZoneWrite => self.hydration.wait_all(),
+                _ => self.hydration.wait_for(namespace),
+            }
+        }
+        if matches!(
+            operation,
+            Operation::Upsert | Operation::Delete | Operation::InsertTrajectory
+        ) && let Some(guard) = self.disk_guard.get()
+        {
+            guard.check_write()?;
+        }
+        self.op_stats.record(namespace, operation);
+        if operation.is_query() {
+            self.queries_count.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
     /// Create an in-memory database with default configuration.
     pub fn memory() -> Result<Self> {
         Self::open(":memory:")
@@ -155,7 +527,15 @@ impl DB {
         Self::open_with_config(":memory:", config)
     }
 
-    /// Upsert an object's location.
+    /// Upsert an object's location, returning the write's sequence number.
+    ///
+    /// Sequences are assigned to every logged mutation in log order and keep
+    /// increasing across restarts (see [`DB::last_sequence`]). An update that
+    /// snaps onto the current position with unchanged metadata (see
+    /// `coordinate_precision` and [`NamespaceConfig::geohash_precision`])
+    /// changes nothing: it is not logged, the object keeps its timestamp, and
+    /// the latest sequence, which covers the write that stored the position,
+    /// is returned.
     pub fn upsert(
         &self,
         namespace: &str,
@@ -163,91 +543,560 @@ impl DB {
         position: spatio_types::point::Point3d,
         metadata: serde_json::Value,
         opts: Option<SetOptions>,
-    ) -> Result<()> {
+    ) -> Result<u64> {
+        db_span!("spatio.upsert", namespace);
         if self.closed.load(Ordering::Acquire) {
             return Err(SpatioError::DatabaseClosed);
         }
+        self.begin(namespace, Operation::Upsert)?;
+        self.write_point(namespace, object_id, position, metadata, opts)
+    }
+
+    /// Upsert the locations of several objects of `namespace`, returning the
+    /// sequence of the last write (the latest sequence if `items` is empty).
+    ///
+    /// Every item is validated before any is written, so an invalid one
+    /// rejects the whole batch and the error names it. The batch is logged as
+    /// one record line, so recovery replays all of it or none, and is applied
+    /// in memory only once logged; readers may still see it partly applied
+    /// while that runs. Logs in the old V1 format have no batch lines and
+    /// take one line per item, so after a crash recovery may replay only the
+    /// first few. Items apply in order, all stamped with the same time; an ID
+    /// given twice ends at its last item. At most 65536 items fit in a batch.
+    pub fn upsert_batch(
+        &self,
+        namespace: &str,
+        items: Vec<(String, spatio_types::point::Point3d, serde_json::Value)>,
+    ) -> Result<u64> {
+        db_span!("spatio.upsert_batch", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::Upsert)?;
         validate_identifier("namespace", namespace)?;
-        validate_identifier("object_id", object_id)?;
-        // Reject NaN/Inf/out-of-range coordinates before they poison the index.
-        validation::validate_geographic_point_3d(&position)?;
+        for (i, (object_id, position, metadata)) in items.iter().enumerate() {
+            validate_identifier("object_id", object_id)
+                .and_then(|()| validation::validate_geographic_point_3d(position))
+                .inspect_err(|e| self.log_rejection(namespace, object_id, e, position, metadata))
+                .map_err(|e| match e {
+                    SpatioError::InvalidInput(msg) => {
+                        SpatioError::InvalidInput(format!("batch item {i}: {msg}"))
+                    }
+                    SpatioError::Validation(error) => ValidationError::Element {
+                        location: format!("batch item {i}"),
+                        error: Box::new(error),
+                    }
+                    .into(),
+                    e => e,
+                })?;
+        }
+        if items.len() > cold_state::MAX_BATCH_RECORDS {
+            return Err(SpatioError::InvalidInput(format!(
+                "batch of {} items exceeds the limit of {}",
+                items.len(),
+                cold_state::MAX_BATCH_RECORDS
+            )));
+        }
+        if items.is_empty() {
+            return Ok(self.cold.last_sequence());
+        }
+        let ts = SystemTime::now();
+        let items: Vec<_> = items
+            .into_iter()
+            .map(|(object_id, position, metadata)| {
+                (object_id, self.snap(namespace, position), metadata)
+            })
+            .collect();
+
+        // Log the batch first, so a failed append leaves nothing applied.
+        let count = items.len();
+        let _publishing = self.hooks.publishing();
+        let last = self.cold.append_batch(namespace, &items, ts)?;
+        let first = last + 1 - count as u64;
+        for ((object_id, position, metadata), sequence) in items.into_iter().zip(first..) {
+            let update = self.hooks.is_active().then(|| LocationUpdate {
+                timestamp: ts,
+                position: position.clone(),
+                metadata: metadata.clone(),
+            });
+            self.apply_point(namespace, &object_id, position, metadata, ts)?;
+            self.publish_hook(namespace, &object_id, sequence, update);
+        }
+        self.ops_count.fetch_add(count as u64, Ordering::Relaxed);
+        Ok(last)
+    }
+
+    fn write_point(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        position: spatio_types::point::Point3d,
+        metadata: serde_json::Value,
+        opts: Option<SetOptions>,
+    ) -> Result<u64> {
+        validate_identifier("namespace", namespace)?;
+        validate_identifier("object_id", object_id)
+            // Reject NaN/Inf/out-of-range coordinates before they poison the index.
+            .and_then(|()| validation::validate_geographic_point_3d(&position))
+            .inspect_err(|e| self.log_rejection(namespace, object_id, e, &position, &metadata))?;
 
         let ts = opts
             .as_ref()
             .and_then(|o| o.timestamp)
             .unwrap_or_else(SystemTime::now);
+        let track_history = opts.as_ref().is_some_and(|o| o.track_history);
+        let position = self.snap(namespace, position);
+
+        // A jittery re-report that snaps onto the current position is
+        // dropped, so the trajectory isn't padded with copies of one point.
+        if self.config.coordinate_precision.is_some()
+            || self
+                .namespace_settings
+                .geohash_precision(namespace)
+                .is_some()
+        {
+            let unchanged = self
+                .hot
+                .get_current_location(namespace, object_id)
+                .is_some_and(|cur| cur.position == position && cur.metadata == metadata);
+            if unchanged {
+                self.ops_count.fetch_add(1, Ordering::Relaxed);
+                return Ok(self.cold.last_sequence());
+            }
+        }
 
         // 1. Update hot state (replaces old position)
-        self.hot
-            .update_location(namespace, object_id, position.clone(), metadata.clone(), ts)?;
+        self.apply_point(namespace, object_id, position.clone(), metadata.clone(), ts)?;
 
         // 2. Append to cold state
-        self.cold
-            .append_update(namespace, object_id, position, metadata, ts)?;
+        let _publishing = self.hooks.publishing();
+        let update = self.hooks.is_active().then(|| LocationUpdate {
+            timestamp: ts,
+            position: position.clone(),
+            metadata: metadata.clone(),
+        });
+        let sequence = if track_history {
+            self.cold
+                .append_tracked_update(namespace, object_id, position, metadata, ts)?
+        } else {
+            self.cold
+                .append_update(namespace, object_id, position, metadata, ts)?
+        };
+        self.publish_hook(namespace, object_id, sequence, update);
 
         self.ops_count.fetch_add(1, Ordering::Relaxed);
 
+        Ok(sequence)
+    }
+
+    /// `position` snapped as configured for `namespace`.
+    fn snap(
+        &self,
+        namespace: &str,
+        mut position: spatio_types::point::Point3d,
+    ) -> spatio_types::point::Point3d {
+        if let Some(decimals) = self.config.coordinate_precision {
+            position = crate::compute::spatial::snap_to_precision(&position, decimals);
+        }
+        if let Some(precision) = self.namespace_settings.geohash_precision(namespace) {
+            position = crate::compute::spatial::snap_to_geohash_cell(&position, precision);
+        }
+        position
+    }
+
+    /// Apply a write to hot state and tell watchers and change subscribers.
+    fn apply_point(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        position: spatio_types::point::Point3d,
+        metadata: serde_json::Value,
+        ts: SystemTime,
+    ) -> Result<()> {
+        let previous = self
+            .changes
+            .is_watched()
+            .then(|| self.hot.get_current_location(namespace, object_id));
+        self.hot
+            .update_location(namespace, object_id, position, metadata, ts)?;
+        self.refresh_watchers(namespace, object_id);
+        if let Some(previous) = previous {
+            let current = self.hot.get_current_location(namespace, object_id);
+            self.changes.publish(previous, current);
+        }
         Ok(())
     }
 
+    /// Hand a logged write to the hooks, if any are registered.
+    fn publish_hook(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        sequence: u64,
+        update: Option<LocationUpdate>,
+    ) {
+        if let Some(update) = update {
+            self.hooks.publish(namespace, || HookEvent {
+                sequence,
+                namespace: namespace.to_string(),
+                object_id: object_id.to_string(),
+                update: Some(update),
+            });
+        }
+    }
+
     /// Get current location of an object.
     pub fn get(&self, namespace: &str, object_id: &str) -> Result<Option<Arc<CurrentLocation>>> {
         if self.closed.load(Ordering::Acquire) {
             return Err(SpatioError::DatabaseClosed);
         }
+        self.begin(namespace, Operation::Get)?;
         Ok(self.hot.get_current_location(namespace, object_id))
     }
 
-    /// Delete an object from the database.
-    pub fn delete(&self, namespace: &str, object_id: &str) -> Result<()> {
+    /// Current locations of objects whose IDs fall in `range`, in ID order.
+    ///
+    /// IDs compare bytewise, so objects keyed by a fixed-width time prefix
+    /// (say `2024-05-01T12:00:00Z/sensor-7`) can be read back as an ordered
+    /// log: `db.range("events", "2024-05-01".."2024-05-02", 100, ScanDirection::Forward)`.
+    /// To continue a scan, pass the last returned ID as an excluded bound.
+    pub fn range<'a>(
+        &self,
+        namespace: &str,
+        range: impl RangeBounds<&'a str>,
+        limit: usize,
+        direction: ScanDirection,
+    ) -> Result<Vec<Arc<CurrentLocation>>> {
+        db_span!("spatio.range", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::Range)?;
+        let bounds = (range.start_bound().cloned(), range.end_bound().cloned());
+        if let (
+            Bound::Included(start) | Bound::Excluded(start),
+            Bound::Included(end) | Bound::Excluded(end),
+        ) = bounds
+            && start > end
+        {
+            return Err(SpatioError::InvalidInput(format!(
+                "range start {start:?} is after range end {end:?}"
+            )));
+        }
+        Ok(self.hot.range(namespace, bounds, limit, direction))
+    }
+
+    /// Delete an object from the database, returning the deletion's sequence.
+    pub fn delete(&self, namespace: &str, object_id: &str) -> Result<u64> {
+        db_span!("spatio.delete", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::Delete)?;
+        validate_identifier("namespace", namespace)?;
+        validate_identifier("object_id", object_id)?;
+        self.remove_object(namespace, object_id)
+    }
+
+    fn remove_object(&self, namespace: &str, object_id: &str) -> Result<u64> {
+        let _publishing = self.hooks.publishing();
+        let sequence = self.cold.append_tombstone(namespace, object_id)?;
+        let removed = self.hot.remove_object(namespace, object_id);
+        self.refresh_watchers(namespace, object_id);
+        self.changes.publish(removed, None);
+        self.hooks.publish(namespace, || HookEvent {
+            sequence,
+            namespace: namespace.to_string(),
+            object_id: object_id.to_string(),
+            update: None,
+        });
+        Ok(sequence)
+    }
+
+    /// Erase every trace of an object, as required for data-subject deletion
+    /// requests: its current location, all of its trajectory history, and
+    /// access and rejection log entries naming it.
+    ///
+    /// Unlike [`DB::delete`], which appends a tombstone and leaves history in
+    /// place, this rewrites the trajectory log (and its checkpoint) without the
+    /// object's records before returning, so the data is gone from disk when
+    /// the report comes back. The rewrite blocks writers and costs a full pass
+    /// over the log. Stop writing the object first: a write racing with this
+    /// call may survive it.
+    pub fn forget_object(&self, namespace: &str, object_id: &str) -> Result<ForgetReport> {
+        db_span!("spatio.forget_object", namespace);
         if self.closed.load(Ordering::Acquire) {
             return Err(SpatioError::DatabaseClosed);
         }
+        self.begin(namespace, Operation::Delete)?;
         validate_identifier("namespace", namespace)?;
         validate_identifier("object_id", object_id)?;
-        self.cold.append_tombstone(namespace, object_id)?;
-        self.hot.remove_object(namespace, object_id);
+
+        let removed = self.hot.remove_object(namespace, object_id);
+        let current_location_removed = removed.is_some();
+        self.refresh_watchers(namespace, object_id);
+        self.changes.publish(removed, None);
+        let log_records_removed = self.cold.forget_object(namespace, object_id)?;
+        let access_log_entries_removed = match &self.access_log {
+            Some(log) => log.forget_object(namespace, object_id)?,
+            None => 0,
+        };
+        let rejection_log_entries_removed = match &self.rejection_log {
+            Some(log) => log.forget_object(namespace, object_id)?,
+            None => 0,
+        };
+        Ok(ForgetReport {
+            current_location_removed,
+            log_records_removed,
+            access_log_entries_removed,
+            rejection_log_entries_removed,
+        })
+    }
+
+    /// Names of the namespaces holding objects or zones, sorted. Once all of
+    /// a namespace's objects and zones are deleted it is no longer listed,
+    /// though its history can still be queried until it is dropped.
+    pub fn list_namespaces(&self) -> Result<Vec<String>> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        if !self.hydration.is_ready() {
+            self.hydration.wait_all();
+        }
+        Ok(self.hot.namespaces())
+    }
+
+    /// Delete every object and zone of `namespace`, returning how many were
+    /// removed. Objects are deleted as by [`DB::delete`], so their history
+    /// stays queryable; [`DB::drop_namespace`] erases it too.
+    pub fn clear_namespace(&self, namespace: &str) -> Result<u64> {
+        db_span!("spatio.clear_namespace", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::Delete)?;
+        validate_identifier("namespace", namespace)?;
+        self.remove_namespace_contents(namespace)
+    }
+
+    /// [`DB::clear_namespace`], then erase the namespace's trajectory history
+    /// as [`DB::forget_object`] does for one object, so nothing of it is left
+    /// on disk. Returns how many objects and zones were removed.
+    ///
+    /// Settings configured for the namespace, and views defined over it,
+    /// stay in place for objects written to it later.
+    pub fn drop_namespace(&self, namespace: &str) -> Result<u64> {
+        db_span!("spatio.drop_namespace", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::Delete)?;
+        validate_identifier("namespace", namespace)?;
+        let removed = self.remove_namespace_contents(namespace)?;
+        self.cold.forget_namespace(namespace)?;
+        Ok(removed)
+    }
+
+    /// Replace the settings of `namespace` (see [`NamespaceConfig`]),
+    /// overriding those given in [`Config::namespaces`] and
+    /// [`Config::history_retention_secs`].
+    ///
+    /// Settings are held in memory only; pass them in the config to keep them
+    /// across reopens. They apply from the next write, read or expiration
+    /// pass: positions already stored are not snapped to a new geohash
+    /// precision, and expired objects or history stay until active expiration
+    /// (or [`DB::expire_objects`] and [`DB::expire_history`]) removes them.
+    ///
+    /// ```
+    /// use spatio::{DB, NamespaceConfig};
+    /// use std::time::Duration;
+    ///
+    /// let db = DB::memory()?;
+    /// db.configure_namespace(
+    ///     "vehicles",
+    ///     NamespaceConfig::default()
+    ///         .with_default_ttl(Duration::from_secs(300))
+    ///         .with_retention(Duration::from_secs(86_400))
+    ///         .with_geohash_precision(8),
+    /// )?;
+    /// assert_eq!(db.namespace_config("vehicles").default_ttl_secs, Some(300));
+    /// // Static points of interest keep everything forever.
+    /// assert_eq!(db.namespace_config("pois").retention_secs, None);
+    /// # Ok::<(), spatio::SpatioError>(())
+    /// ```
+    pub fn configure_namespace(&self, namespace: &str, config: NamespaceConfig) -> Result<()> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        validate_identifier("namespace", namespace)?;
+        config.validate().map_err(SpatioError::InvalidInput)?;
+        self.namespace_settings.set(namespace, config);
         Ok(())
     }
 
-    /// Insert a trajectory (sequence of points)
+    /// Settings in effect for `namespace` (all unset if it has none).
+    pub fn namespace_config(&self, namespace: &str) -> NamespaceConfig {
+        self.namespace_settings.get(namespace)
+    }
+
+    /// Delete every object whose last update is older than its namespace's
+    /// TTL (see [`NamespaceConfig::default_ttl_secs`]), returning how many
+    /// were deleted.
+    ///
+    /// Objects are deleted as by [`DB::delete`], so their history stays
+    /// queryable within its retention. Until a pass removes them, expired
+    /// objects are still returned by queries. This also runs on open and,
+    /// with [`Config::with_active_expiration`], in the background. An object
+    /// updated while the pass runs may still be deleted.
+    pub fn expire_objects(&self) -> Result<u64> {
+        db_span!("spatio.expire_objects");
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.hydration.wait_all();
+        self.remove_expired_objects()
+    }
+
+    fn remove_expired_objects(&self) -> Result<u64> {
+        let now = SystemTime::now();
+        let mut removed = 0;
+        for (namespace, secs) in self.namespace_settings.ttls() {
+            let cutoff = now
+                .checked_sub(std::time::Duration::from_secs(secs))
+                .unwrap_or(std::time::UNIX_EPOCH);
+            let expired = |location: &CurrentLocation| location.timestamp < cutoff;
+            let object_ids: Vec<String> = self
+                .hot
+                .range(&namespace, .., usize::MAX, ScanDirection::Forward)
+                .into_iter()
+                .filter(|location| expired(location))
+                .map(|location| location.object_id.clone())
+                .collect();
+            if object_ids.is_empty() {
+                continue;
+            }
+            removed += self.cold.group(|| {
+                let mut removed = 0;
+                for object_id in &object_ids {
+                    // Skip objects updated since the scan.
+                    if self
+                        .hot
+                        .get_current_location(&namespace, object_id)
+                        .is_some_and(|location| expired(&location))
+                    {
+                        self.remove_object(&namespace, object_id)?;
+                        removed += 1;
+                    }
+                }
+                Ok(removed)
+            })?;
+        }
+        Ok(removed)
+    }
+
+    fn remove_namespace_contents(&self, namespace: &str) -> Result<u64> {
+        let object_ids = self.hot.object_ids(namespace);
+        self.cold.group(|| {
+            object_ids
+                .iter()
+                .try_for_each(|object_id| self.remove_object(namespace, object_id).map(drop))
+        })?;
+        let mut removed = object_ids.len() as u64;
+        let _zones = self.zone_writes.lock();
+        let zones = self.hot.zones(namespace);
+        for zone in &zones {
+            if self.hot.remove_zone(namespace, &zone.zone_id).is_some() {
+                removed += 1;
+            }
+        }
+        if !zones.is_empty() {
+            self.cold.write_zones(&self.hot.all_zones())?;
+        }
+        Ok(removed)
+    }
+
+    /// Bring the materialized views, fences and metadata indexes of
+    /// `namespace` up to date with a write to `object_id`.
+    fn refresh_watchers(&self, namespace: &str, object_id: &str) {
+        let current = || self.hot.get_current_location(namespace, object_id);
+        self.views.refresh(namespace, object_id, current);
+        self.fences.refresh(namespace, object_id, current);
+        self.metadata_indexes.refresh(namespace, object_id, current);
+    }
+
+    /// Insert a trajectory (sequence of points), returning the sequence of the
+    /// last point written (the latest sequence if `trajectory` is empty).
     pub fn insert_trajectory(
         &self,
         namespace: &str,
         object_id: &str,
         trajectory: &[TemporalPoint],
-    ) -> Result<()> {
-        for tp in trajectory {
-            let pos = spatio_types::point::Point3d::new(tp.point.x(), tp.point.y(), 0.0);
-            self.upsert(
-                namespace,
-                object_id,
-                pos,
-                serde_json::json!({}),
-                Some(SetOptions {
-                    timestamp: Some(tp.timestamp),
-                }),
-            )?;
+    ) -> Result<u64> {
+        db_span!("spatio.insert_trajectory", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
         }
-        Ok(())
+        self.begin(namespace, Operation::InsertTrajectory)?;
+        self.cold.group(|| {
+            let mut sequence = self.cold.last_sequence();
+            for tp in trajectory {
+                let pos = spatio_types::point::Point3d::new(tp.point.x(), tp.point.y(), 0.0);
+                sequence = self.write_point(
+                    namespace,
+                    object_id,
+                    pos,
+                    serde_json::json!({}),
+                    Some(SetOptions::with_timestamp(tp.timestamp)),
+                )?;
+            }
+            Ok(sequence)
+        })
     }
 
     /// Query objects within radius, always returning (Location, distance).
+    ///
+    /// Horizontal distance is measured in the namespace's configured metric
+    /// (see [`Config::with_namespace_distance_metric`]).
     pub fn query_radius(
         &self,
         namespace: &str,
         center: &spatio_types::point::Point3d,
         radius: f64,
         limit: usize,
-    ) -> Result<Vec<(Arc<CurrentLocation>, f64)>> {
+    ) -> Result<Vec<NearbyHit>> {
+        let metric = self.distance_metric(namespace);
+        self.query_radius_with_metric(namespace, center, radius, limit, metric)
+    }
+
+    /// Like [`DB::query_radius`], measuring horizontal distance with `metric`.
+    /// The radius and distances are in meters, or coordinate units for
+    /// [`DistanceMetric::Euclidean`](crate::compute::spatial::DistanceMetric::Euclidean).
+    pub fn query_radius_with_metric(
+        &self,
+        namespace: &str,
+        center: &spatio_types::point::Point3d,
+        radius: f64,
+        limit: usize,
+        metric: crate::compute::spatial::DistanceMetric,
+    ) -> Result<Vec<NearbyHit>> {
+        db_span!("spatio.query_radius", namespace);
         if self.closed.load(Ordering::Acquire) {
             return Err(SpatioError::DatabaseClosed);
         }
+        self.begin(namespace, Operation::QueryRadius)?;
+        let _timer = self.time_query(namespace, Operation::QueryRadius);
         validation::validate_geographic_point_3d(center)?;
         validation::validate_radius(radius)?;
-        Ok(self
-            .hot
-            .query_within_radius(namespace, center, radius, limit))
+        self.log_access(|| AccessQuery::Radius {
+            namespace: namespace.to_string(),
+            center: [center.x(), center.y(), center.z()],
+            radius,
+            limit,
+            metric,
+        });
+        Ok(hits::named(self.hot.query_within_radius(
+            namespace, center, radius, limit, metric,
+        )))
     }
 
     /// Query current locations within a 2D bounding box (HOT PATH)
@@ -260,51 +1109,339 @@ impl DB {
         max_y: f64,
         limit: usize,
     ) -> Result<Vec<Arc<CurrentLocation>>> {
+        db_span!("spatio.query_bbox", namespace);
         if self.closed.load(Ordering::Acquire) {
             return Err(SpatioError::DatabaseClosed);
         }
+        self.begin(namespace, Operation::QueryBbox)?;
+        let _timer = self.time_query(namespace, Operation::QueryBbox);
         validation::validate_bbox(min_x, min_y, max_x, max_y)?;
+        self.log_access(|| AccessQuery::Bbox {
+            namespace: namespace.to_string(),
+            min: [min_x, min_y],
+            max: [max_x, max_y],
+            limit,
+        });
         Ok(self
             .hot
             .query_within_bbox(namespace, min_x, min_y, max_x, max_y, limit))
     }
 
-    /// Query objects within a cylindrical volume (HOT PATH)
-    pub fn query_within_cylinder(
+    /// [`DB::query_bbox`], with each result's distance in meters from
+    /// `anchor`, or from the center of the box when `anchor` is `None`.
+    ///
+    /// Distances are horizontal, in the namespace's metric. Results keep the
+    /// order of the plain query, so sort them to get nearest first.
+    #[allow(clippy::too_many_arguments)]
+    pub fn query_bbox_with_distances(
         &self,
         namespace: &str,
-        center: spatio_types::geo::Point,
-        min_z: f64,
-        max_z: f64,
-        radius: f64,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        anchor: Option<&spatio_types::point::Point3d>,
         limit: usize,
-    ) -> Result<Vec<(Arc<CurrentLocation>, f64)>> {
-        if self.closed.load(Ordering::Acquire) {
-            return Err(SpatioError::DatabaseClosed);
-        }
-        validation::validate_geographic_point(&center)?;
-        validation::validate_radius(radius)?;
-        Ok(self
-            .hot
-            .query_within_cylinder(namespace, center, min_z, max_z, radius, limit))
+    ) -> Result<Vec<NearbyHit>> {
+        let results = self.query_bbox(namespace, min_x, min_y, max_x, max_y, limit)?;
+        let anchor = match anchor {
+            Some(anchor) => spatio_types::geo::Point::new(anchor.x(), anchor.y()),
+            None => spatio_types::geo::Point::new((min_x + max_x) / 2.0, (min_y + max_y) / 2.0),
+        };
+        Ok(self.with_distances(namespace, results, &anchor))
     }
 
-    /// Find k nearest neighbors in 3D (HOT PATH)
-    pub fn knn(
+    /// Scan a 2D bounding box one page at a time.
+    ///
+    /// Pass `None` for the first page and the returned `next_token` for each
+    /// following page, with the same namespace and box. Tokens are stateless
+    /// and name the log the scan started on ([`DB::log_id`]), so a scan can
+    /// resume on another database serving that log, such as a copy of this
+    /// one, once it has applied the sequence the scan started at; a lagging
+    /// one returns [`SpatioError::StaleReplica`], and a database with another
+    /// log refuses the token.
+    ///
+    /// A page never holds the same object twice, and an object that stays
+    /// put during the scan is returned exactly once across all pages, even
+    /// on the boundary between two scan cells. Objects written mid-scan may
+    /// be missed, or repeated on a later page if they moved ahead of the
+    /// scan.
+    #[allow(clippy::too_many_arguments)]
+    pub fn query_bbox_page(
         &self,
         namespace: &str,
-        center: &spatio_types::point::Point3d,
-        k: usize,
-    ) -> Result<Vec<(Arc<CurrentLocation>, f64)>> {
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        page_size: usize,
+        token: Option<&str>,
+    ) -> Result<Page<Arc<CurrentLocation>>> {
+        db_span!("spatio.query_bbox_page", namespace);
         if self.closed.load(Ordering::Acquire) {
             return Err(SpatioError::DatabaseClosed);
         }
-        validation::validate_geographic_point_3d(center)?;
-        Ok(self.hot.knn_3d(namespace, center, k))
-    }
+        self.begin(namespace, Operation::QueryBbox)?;
+        let _timer = self.time_query(namespace, Operation::QueryBbox);
+        validation::validate_bbox(min_x, min_y, max_x, max_y)?;
+        validate_page_size(page_size)?;
 
-    /// Query objects within a 3D bounding box (HOT PATH)
-    #[allow(clippy::too_many_arguments)]
+        let bounds = [min_x, min_y, max_x, max_y];
+        let start = pagination::PageToken::resume(
+            token,
+            PageKind::Bbox,
+            pagination::query_hash(namespace, &bounds),
+            self.cold.log_id(),
+            self.cold.last_sequence(),
+        )?;
+
+        self.log_access(|| AccessQuery::Bbox {
+            namespace: namespace.to_string(),
+            min: [min_x, min_y],
+            max: [max_x, max_y],
+            limit: page_size,
+        });
+
+        let mut items = Vec::with_capacity(page_size);
+        // An object moving between cells while the page is collected would
+        // otherwise be picked up in both.
+        let mut returned = HashSet::new();
+        let mut offset = start.offset as usize;
+        for cell in start.position as u32..pagination::PAGE_GRID * pagination::PAGE_GRID {
+            let ([x0, y0, x1, y1], last_col, last_row) = pagination::cell_bounds(bounds, cell);
+            // Cells are half-open except along the box's far edges, so each
+            // object belongs to exactly one cell.
+            let mut in_cell: Vec<_> = self
+                .hot
+                .query_within_bbox(namespace, x0, y0, x1, y1, usize::MAX)
+                .into_iter()
+                .filter(|loc| {
+                    let (x, y) = (loc.position.x(), loc.position.y());
+                    (x < x1 || last_col) && (y < y1 || last_row)
+                })
+                .collect();
+            in_cell.sort_unstable_by(|a, b| a.object_id.cmp(&b.object_id));
+
+            let remaining = in_cell.len().saturating_sub(offset);
+            let mut take = 0;
+            for loc in in_cell.into_iter().skip(offset) {
+                if items.len() == page_size {
+                    break;
+                }
+                take += 1;
+                if returned.insert(loc.object_id.clone()) {
+                    items.push(loc);
+                }
+            }
+
+            if items.len() == page_size {
+                let next = if take < remaining {
+                    Some((cell, offset + take))
+                } else if cell + 1 < pagination::PAGE_GRID * pagination::PAGE_GRID {
+                    Some((cell + 1, 0))
+                } else {
+                    None
+                };
+                let next_token =
+                    next.map(|(cell, offset)| start.at(u64::from(cell), offset as u64));
+                return Ok(Page { items, next_token });
+            }
+            offset = 0;
+        }
+
+        Ok(Page {
+            items,
+            next_token: None,
+        })
+    }
+
+    /// Query objects within a radius one page at a time, nearest first (ties
+    /// in object ID order), measuring horizontal distance in the namespace's
+    /// metric like [`DB::query_radius`].
+    ///
+    /// Tokens work as in [`DB::query_bbox_page`]. Each page re-reads every
+    /// object in the radius to find where the previous one stopped, so keep
+    /// pages large relative to the number of matches.
+    pub fn query_radius_page(
+        &self,
+        namespace: &str,
+        center: &spatio_types::point::Point3d,
+        radius: f64,
+        page_size: usize,
+        token: Option<&str>,
+    ) -> Result<Page<NearbyHit>> {
+        db_span!("spatio.query_radius_page", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::QueryRadius)?;
+        let _timer = self.time_query(namespace, Operation::QueryRadius);
+        validation::validate_geographic_point_3d(center)?;
+        validation::validate_radius(radius)?;
+        validate_page_size(page_size)?;
+        let start = pagination::PageToken::resume(
+            token,
+            PageKind::Radius,
+            pagination::query_hash(namespace, &[center.x(), center.y(), center.z(), radius]),
+            self.cold.log_id(),
+            self.cold.last_sequence(),
+        )?;
+        let metric = self.distance_metric(namespace);
+        self.log_access(|| AccessQuery::Radius {
+            namespace: namespace.to_string(),
+            center: [center.x(), center.y(), center.z()],
+            radius,
+            limit: page_size,
+            metric,
+        });
+
+        let mut hits: Vec<NearbyHit> = hits::named(self.hot.query_within_radius(
+            namespace,
+            center,
+            radius,
+            usize::MAX,
+            metric,
+        ));
+        hits.sort_unstable_by(|a, b| {
+            a.distance
+                .total_cmp(&b.distance)
+                .then_with(|| a.object_id().cmp(b.object_id()))
+        });
+        // Distances are non-negative, so their bits order like the values.
+        let key = |hit: &NearbyHit| hit.distance.to_bits();
+        let skip = hits.partition_point(|hit| key(hit) < start.position);
+        let mut offset = start.offset as usize;
+        let items: Vec<NearbyHit> = hits
+            .into_iter()
+            .skip(skip)
+            .skip_while(|hit| {
+                let resumed = key(hit) == start.position && offset > 0;
+                offset = offset.saturating_sub(usize::from(resumed));
+                resumed
+            })
+            .take(page_size + 1)
+            .collect();
+        Ok(next_page(items, page_size, start, key))
+    }
+
+    /// Query objects within a cylindrical volume (HOT PATH), measuring
+    /// horizontal distance in the namespace's configured metric.
+    pub fn query_within_cylinder(
+        &self,
+        namespace: &str,
+        center: spatio_types::geo::Point,
+        min_z: f64,
+        max_z: f64,
+        radius: f64,
+        limit: usize,
+    ) -> Result<Vec<NearbyHit>> {
+        let metric = self.distance_metric(namespace);
+        self.query_within_cylinder_with_metric(
+            namespace, center, min_z, max_z, radius, limit, metric,
+        )
+    }
+
+    /// Like [`DB::query_within_cylinder`], measuring horizontal distance with
+    /// `metric`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn query_within_cylinder_with_metric(
+        &self,
+        namespace: &str,
+        center: spatio_types::geo::Point,
+        min_z: f64,
+        max_z: f64,
+        radius: f64,
+        limit: usize,
+        metric: crate::compute::spatial::DistanceMetric,
+    ) -> Result<Vec<NearbyHit>> {
+        db_span!("spatio.query_within_cylinder", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::QueryCylinder)?;
+        let _timer = self.time_query(namespace, Operation::QueryCylinder);
+        validation::validate_geographic_point(&center)?;
+        validation::validate_z_range(min_z, max_z)?;
+        validation::validate_radius(radius)?;
+        self.log_access(|| AccessQuery::Cylinder {
+            namespace: namespace.to_string(),
+            center: [center.x(), center.y()],
+            min_z,
+            max_z,
+            radius,
+            limit,
+            metric,
+        });
+        Ok(hits::named(self.hot.query_within_cylinder(
+            namespace, center, min_z, max_z, radius, limit, metric,
+        )))
+    }
+
+    /// Find k nearest neighbors in 3D (HOT PATH)
+    pub fn knn(
+        &self,
+        namespace: &str,
+        center: &spatio_types::point::Point3d,
+        k: usize,
+    ) -> Result<Vec<NearbyHit>> {
+        db_span!("spatio.knn", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::Knn)?;
+        let _timer = self.time_query(namespace, Operation::Knn);
+        validation::validate_geographic_point_3d(center)?;
+        self.log_access(|| AccessQuery::Knn {
+            namespace: namespace.to_string(),
+            center: [center.x(), center.y(), center.z()],
+            k,
+        });
+        Ok(hits::named(self.hot.knn_3d(namespace, center, k)))
+    }
+
+    /// Find the k nearest neighbors of `center` by horizontal distance under
+    /// `metric`, ignoring altitude, nearest first (HOT PATH).
+    ///
+    /// With `max_distance`, only objects at most that far away are returned;
+    /// like the distances, it is in meters, or coordinate units for
+    /// [`DistanceMetric::Euclidean`](crate::compute::spatial::DistanceMetric::Euclidean).
+    /// Unlike [`DB::knn`], this checks every object of the namespace.
+    pub fn knn_2d(
+        &self,
+        namespace: &str,
+        center: &spatio_types::geo::Point,
+        k: usize,
+        max_distance: Option<f64>,
+        metric: crate::compute::spatial::DistanceMetric,
+    ) -> Result<Vec<NearbyHit>> {
+        db_span!("spatio.knn_2d", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::Knn)?;
+        let _timer = self.time_query(namespace, Operation::Knn);
+        validation::validate_geographic_point(center)?;
+        if let Some(max_distance) = max_distance {
+            validation::validate_positive("max_distance", max_distance)?;
+        }
+        self.log_access(|| AccessQuery::Knn2d {
+            namespace: namespace.to_string(),
+            center: [center.x(), center.y()],
+            k,
+            max_distance,
+            metric,
+        });
+        Ok(hits::named(self.hot.knn_2d(
+            namespace,
+            center,
+            k,
+            max_distance,
+            metric,
+        )))
+    }
+
+    /// Query objects within a 3D bounding box (HOT PATH)
+    #[allow(clippy::too_many_arguments)]
     pub fn query_within_bbox_3d(
         &self,
         namespace: &str,
@@ -316,10 +1453,19 @@ impl DB {
         max_z: f64,
         limit: usize,
     ) -> Result<Vec<Arc<CurrentLocation>>> {
+        db_span!("spatio.query_within_bbox_3d", namespace);
         if self.closed.load(Ordering::Acquire) {
             return Err(SpatioError::DatabaseClosed);
         }
+        self.begin(namespace, Operation::QueryBbox3d)?;
+        let _timer = self.time_query(namespace, Operation::QueryBbox3d);
         validation::validate_bbox_3d(min_x, min_y, min_z, max_x, max_y, max_z)?;
+        self.log_access(|| AccessQuery::Bbox3d {
+            namespace: namespace.to_string(),
+            min: [min_x, min_y, min_z],
+            max: [max_x, max_y, max_z],
+            limit,
+        });
         Ok(self
             .hot
             .query_within_bbox_3d(namespace, min_x, min_y, min_z, max_x, max_y, max_z, limit))
@@ -332,19 +1478,28 @@ impl DB {
         object_id: &str,
         radius: f64,
         limit: usize,
-    ) -> Result<Vec<(Arc<CurrentLocation>, f64)>> {
+    ) -> Result<Vec<NearbyHit>> {
         if self.closed.load(Ordering::Acquire) {
             return Err(SpatioError::DatabaseClosed);
         }
+        self.hydration.wait_for(namespace);
+        validation::validate_radius(radius)?;
 
         // 1. Get target object's current position
         let target = self
             .hot
             .get_current_location(namespace, object_id)
             .ok_or(SpatioError::ObjectNotFound)?;
+        self.log_access(|| AccessQuery::Near {
+            namespace: namespace.to_string(),
+            object_id: object_id.to_string(),
+            radius,
+            limit,
+        });
 
         // 2. Query around that position
-        self.query_radius(namespace, &target.position, radius, limit)
+        self.unlogged()
+            .query_radius(namespace, &target.position, radius, limit)
     }
 
     /// Query objects within a bounding box relative to another object
@@ -359,17 +1514,27 @@ impl DB {
         if self.closed.load(Ordering::Acquire) {
             return Err(SpatioError::DatabaseClosed);
         }
+        self.hydration.wait_for(namespace);
+        validation::validate_positive("width", width)?;
+        validation::validate_positive("height", height)?;
 
         let target = self
             .hot
             .get_current_location(namespace, object_id)
             .ok_or(SpatioError::ObjectNotFound)?;
+        self.log_access(|| AccessQuery::BboxNear {
+            namespace: namespace.to_string(),
+            object_id: object_id.to_string(),
+            width,
+            height,
+            limit,
+        });
 
         let half_width = width / 2.0;
         let half_height = height / 2.0;
         let center = &target.position;
 
-        self.query_bbox(
+        self.unlogged().query_bbox(
             namespace,
             center.x() - half_width,
             center.y() - half_height,
@@ -388,19 +1553,31 @@ impl DB {
         max_z: f64,
         radius: f64,
         limit: usize,
-    ) -> Result<Vec<(Arc<CurrentLocation>, f64)>> {
+    ) -> Result<Vec<NearbyHit>> {
         if self.closed.load(Ordering::Acquire) {
             return Err(SpatioError::DatabaseClosed);
         }
+        self.hydration.wait_for(namespace);
+        validation::validate_z_range(min_z, max_z)?;
+        validation::validate_radius(radius)?;
 
         let target = self
             .hot
             .get_current_location(namespace, object_id)
             .ok_or(SpatioError::ObjectNotFound)?;
+        self.log_access(|| AccessQuery::CylinderNear {
+            namespace: namespace.to_string(),
+            object_id: object_id.to_string(),
+            min_z,
+            max_z,
+            radius,
+            limit,
+        });
 
         let center = spatio_types::geo::Point::new(target.position.x(), target.position.y());
 
-        self.query_within_cylinder(namespace, center, min_z, max_z, radius, limit)
+        self.unlogged()
+            .query_within_cylinder(namespace, center, min_z, max_z, radius, limit)
     }
 
     /// Query objects within a 3D bounding box relative to another object
@@ -416,18 +1593,30 @@ impl DB {
         if self.closed.load(Ordering::Acquire) {
             return Err(SpatioError::DatabaseClosed);
         }
+        self.hydration.wait_for(namespace);
+        validation::validate_positive("width", width)?;
+        validation::validate_positive("height", height)?;
+        validation::validate_positive("depth", depth)?;
 
         let target = self
             .hot
             .get_current_location(namespace, object_id)
             .ok_or(SpatioError::ObjectNotFound)?;
+        self.log_access(|| AccessQuery::Bbox3dNear {
+            namespace: namespace.to_string(),
+            object_id: object_id.to_string(),
+            width,
+            height,
+            depth,
+            limit,
+        });
 
         let half_width = width / 2.0;
         let half_height = height / 2.0;
         let half_depth = depth / 2.0;
         let center = &target.position;
 
-        self.query_within_bbox_3d(
+        self.unlogged().query_within_bbox_3d(
             namespace,
             center.x() - half_width,
             center.y() - half_height,
@@ -445,678 +1634,5232 @@ impl DB {
         namespace: &str,
         object_id: &str,
         k: usize,
-    ) -> Result<Vec<(Arc<CurrentLocation>, f64)>> {
+    ) -> Result<Vec<NearbyHit>> {
         if self.closed.load(Ordering::Acquire) {
             return Err(SpatioError::DatabaseClosed);
         }
+        self.hydration.wait_for(namespace);
 
         let target = self
             .hot
             .get_current_location(namespace, object_id)
             .ok_or(SpatioError::ObjectNotFound)?;
+        self.log_access(|| AccessQuery::KnnNear {
+            namespace: namespace.to_string(),
+            object_id: object_id.to_string(),
+            k,
+        });
 
-        self.knn(namespace, &target.position, k)
+        self.unlogged().knn(namespace, &target.position, k)
     }
 
-    /// Query historical trajectory (COLD PATH)
-    pub fn query_trajectory(
-        &self,
-        namespace: &str,
-        object_id: &str,
-        start_time: SystemTime,
-        end_time: SystemTime,
-        limit: usize,
-    ) -> Result<Vec<LocationUpdate>> {
+    /// Define a materialized view named `name`: the objects of `namespace`
+    /// matching `predicate` (say, "drones under 120 m inside the city"),
+    /// maintained incrementally as objects are written.
+    ///
+    /// Views live in memory only and start from the current contents of the
+    /// namespace. Reading one with [`DB::view`] costs no index scan.
+    pub fn create_view(&self, name: &str, namespace: &str, predicate: Predicate) -> Result<()> {
+        db_span!("spatio.create_view", namespace);
         if self.closed.load(Ordering::Acquire) {
             return Err(SpatioError::DatabaseClosed);
         }
-        self.cold
-            .query_trajectory(namespace, object_id, start_time, end_time, limit)
+        validate_identifier("view name", name)?;
+        validate_identifier("namespace", namespace)?;
+        validation::validate_predicate(&predicate)?;
+        let created = self.views.create(name, namespace, predicate, |predicate| {
+            self.query(namespace, predicate, usize::MAX)
+                .unwrap_or_default()
+        });
+        if !created {
+            return Err(SpatioError::InvalidInput(format!(
+                "view {name:?} already exists"
+            )));
+        }
+        Ok(())
     }
 
-    /// Close the database, flushing and syncing any buffered writes to disk.
-    pub fn close(&self) -> Result<()> {
-        self.closed.store(true, Ordering::Release);
-        self.cold.flush()
+    /// Remove a view, returning whether it existed. Its subscriptions
+    /// disconnect.
+    pub fn drop_view(&self, name: &str) -> Result<bool> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        Ok(self.views.drop_view(name))
     }
 
-    /// Get database statistics
-    pub fn stats(&self) -> DbStats {
-        let (hot_objects, hot_memory) = self.hot.detailed_stats();
-        let (cold_trajectories, cold_buffer_bytes) = self.cold.stats();
+    /// Current members of a view, in object ID order.
+    pub fn view(&self, name: &str) -> Result<Vec<Arc<CurrentLocation>>> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.views
+            .members(name)
+            .ok_or_else(|| SpatioError::InvalidInput(format!("no view named {name:?}")))
+    }
 
-        DbStats {
-            expired_count: 0, // TTL/expiry is not implemented; always zero
-            operations_count: self.ops_count.load(Ordering::Relaxed),
-            size_bytes: hot_memory + cold_buffer_bytes,
-            hot_state_objects: hot_objects,
-            cold_state_trajectories: cold_trajectories,
-            cold_state_buffer_bytes: cold_buffer_bytes,
-            memory_usage_bytes: hot_memory + cold_buffer_bytes,
+    /// Subscribe to a view's membership changes.
+    ///
+    /// Every change after this call is delivered. Subscribe before reading
+    /// the starting state with [`DB::view`]; the first events may then repeat
+    /// changes the read already reflects.
+    pub fn subscribe_view(&self, name: &str) -> Result<ViewSubscription> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
         }
+        self.views
+            .subscribe(name)
+            .ok_or_else(|| SpatioError::InvalidInput(format!("no view named {name:?}")))
     }
-    /// Query objects within a polygon
-    pub fn query_polygon(
-        &self,
-        namespace: &str,
-        polygon: &spatio_types::geo::Polygon,
-        limit: usize,
-    ) -> Result<Vec<Arc<CurrentLocation>>> {
+
+    /// Index the objects of `namespace` by the value of the top-level
+    /// metadata field `field`, so [`DB::query`] (and the views built on it)
+    /// can answer [`Predicate::MetadataEquals`] conditions on that field
+    /// without scanning the namespace.
+    ///
+    /// ```
+    /// use spatio::{DB, Point3d, Predicate};
+    /// use serde_json::json;
+    ///
+    /// let db = DB::memory().unwrap();
+    /// db.create_metadata_index("fleet", "fuel").unwrap();
+    /// db.upsert("fleet", "truck", Point3d::new(1.0, 2.0, 0.0), json!({"fuel": "diesel"}), None)
+    ///     .unwrap();
+    /// db.upsert("fleet", "van", Point3d::new(1.0, 2.0, 0.0), json!({"fuel": "electric"}), None)
+    ///     .unwrap();
+    ///
+    /// let diesel_nearby = Predicate::WithinBbox { min_x: 0.0, min_y: 0.0, max_x: 2.0, max_y: 3.0 }
+    ///     .and(Predicate::MetadataEquals { field: "fuel".into(), value: json!("diesel") });
+    /// let found = db.query("fleet", &diesel_nearby, 10).unwrap();
+    /// assert_eq!(found.len(), 1);
+    /// assert_eq!(found[0].object_id, "truck");
+    /// ```
+    ///
+    /// Indexes live in memory only and start from the current contents of
+    /// the namespace. Snapshot readers ([`DB::reader`]) don't use them.
+    pub fn create_metadata_index(&self, namespace: &str, field: &str) -> Result<()> {
+        db_span!("spatio.create_metadata_index", namespace);
         if self.closed.load(Ordering::Acquire) {
             return Err(SpatioError::DatabaseClosed);
         }
-        validation::validate_polygon(polygon)?;
-        Ok(self.hot.query_polygon(namespace, polygon, limit))
+        self.hydration.wait_for(namespace);
+        validate_identifier("namespace", namespace)?;
+        let created = self.metadata_indexes.create(namespace, field, || {
+            self.hot
+                .range(namespace, .., usize::MAX, ScanDirection::Forward)
+        });
+        if !created {
+            return Err(SpatioError::InvalidInput(format!(
+                "metadata index on {field:?} in namespace {namespace:?} already exists"
+            )));
+        }
+        Ok(())
     }
 
-    /// Calculate distance between two objects
-    pub fn distance_between(
-        &self,
-        namespace: &str,
-        id1: &str,
-        id2: &str,
-        metric: crate::compute::spatial::DistanceMetric,
-    ) -> Result<Option<f64>> {
+    /// Remove a metadata index, returning whether it existed.
+    pub fn drop_metadata_index(&self, namespace: &str, field: &str) -> Result<bool> {
         if self.closed.load(Ordering::Acquire) {
             return Err(SpatioError::DatabaseClosed);
         }
-        Ok(self.hot.distance_between(namespace, id1, id2, metric))
+        Ok(self.metadata_indexes.drop_index(namespace, field))
     }
 
-    /// Calculate distance from object to point
-    pub fn distance_to(
-        &self,
-        namespace: &str,
-        id: &str,
-        point: &spatio_types::geo::Point,
-        metric: crate::compute::spatial::DistanceMetric,
-    ) -> Result<Option<f64>> {
+    /// Metadata fields indexed in `namespace`, sorted.
+    pub fn metadata_indexes(&self, namespace: &str) -> Result<Vec<String>> {
         if self.closed.load(Ordering::Acquire) {
             return Err(SpatioError::DatabaseClosed);
         }
-        Ok(self.hot.distance_to(namespace, id, point, metric))
+        Ok(self.metadata_indexes.fields(namespace))
     }
 
-    /// Compute convex hull of all objects in namespace
-    pub fn convex_hull(&self, namespace: &str) -> Result<Option<spatio_types::geo::Polygon>> {
+    /// Stream every change to current locations, in all namespaces.
+    ///
+    /// Inserts, updates and deletions made after this call are delivered as
+    /// [`ChangeEvent`]s. Rewinding a namespace with
+    /// [`DB::hydrate_from_history`] reports each object it changes. Objects
+    /// have no expiry, so there are no expiry events.
+    pub fn watch(&self) -> Result<ChangeFeed> {
         if self.closed.load(Ordering::Acquire) {
             return Err(SpatioError::DatabaseClosed);
         }
-        Ok(self.hot.convex_hull(namespace))
+        Ok(self.changes.subscribe(None, ""))
     }
 
-    /// Compute bounding box of all objects in namespace
-    pub fn bounding_box(&self, namespace: &str) -> Result<Option<geo::Rect>> {
+    /// Stream changes to the objects of `namespace` whose ID starts with
+    /// `prefix` (all of them when `prefix` is empty), as [`DB::watch`] does.
+    pub fn subscribe(&self, namespace: &str, prefix: &str) -> Result<ChangeFeed> {
         if self.closed.load(Ordering::Acquire) {
             return Err(SpatioError::DatabaseClosed);
         }
-        Ok(self.hot.bounding_box(namespace))
+        validate_identifier("namespace", namespace)?;
+        Ok(self.changes.subscribe(Some(namespace), prefix))
     }
-}
 
-pub use DB as Spatio;
+    /// Define a geofence named `name` over `namespace`: objects moving into
+    /// or out of `fence`, or staying inside it for
+    /// [`FenceOptions::dwell`], are reported to [`DB::subscribe_fence`]
+    /// subscribers as they are written.
+    ///
+    /// Fences live in memory only. Objects already inside when the fence is
+    /// created count as inside, without an event. A write is only tested
+    /// against fences whose bounding box holds the object, and those it was
+    /// inside; circle and box fences are cheaper to test than polygons.
+    pub fn create_fence(
+        &self,
+        name: &str,
+        namespace: &str,
+        fence: impl Into<Fence>,
+        options: FenceOptions,
+    ) -> Result<()> {
+        let fence = fence.into();
+        db_span!("spatio.create_fence", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        validate_identifier("fence name", name)?;
+        validate_identifier("namespace", namespace)?;
+        validation::validate_predicate(&fence.to_predicate())?;
+        let created = self
+            .fences
+            .create(name, namespace, fence, options, |predicate| {
+                self.query(namespace, predicate, usize::MAX)
+                    .unwrap_or_default()
+            });
+        if !created {
+            return Err(SpatioError::InvalidInput(format!(
+                "fence {name:?} already exists"
+            )));
+        }
+        Ok(())
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use spatio_types::point::Point3d;
-    use std::thread::sleep;
-    use std::time::Duration;
+    /// Remove a fence, returning whether it existed. Its subscriptions
+    /// disconnect.
+    pub fn drop_fence(&self, name: &str) -> Result<bool> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        Ok(self.fences.drop_fence(name))
+    }
 
-    #[test]
-    fn test_update_and_query_location() {
-        let db = DB::memory().unwrap();
-        let namespace = "vehicles";
-        let object_id = "car1";
-        let pos1 = Point3d::new(10.0, 20.0, 0.0);
-        let metadata1 = serde_json::json!({"engine": "on"});
+    /// Objects currently inside a fence, in object ID order.
+    pub fn fence(&self, name: &str) -> Result<Vec<Arc<CurrentLocation>>> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.fences
+            .members(name)
+            .ok_or_else(|| SpatioError::InvalidInput(format!("no fence named {name:?}")))
+    }
 
-        db.upsert(namespace, object_id, pos1.clone(), metadata1.clone(), None)
-            .unwrap();
+    /// Subscribe to a fence's enter, dwell and exit events.
+    ///
+    /// Every event after this call is delivered, as with
+    /// [`DB::subscribe_view`].
+    pub fn subscribe_fence(&self, name: &str) -> Result<FenceSubscription> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.fences
+            .subscribe(name)
+            .ok_or_else(|| SpatioError::InvalidInput(format!("no fence named {name:?}")))
+    }
 
-        let results = db.query_radius(namespace, &pos1, 1.0, 1).unwrap();
-        assert_eq!(results.len(), 1);
-        assert_eq!(results[0].0.object_id, object_id);
-        assert_eq!(results[0].0.position, pos1);
-        assert_eq!(results[0].0.metadata, metadata1);
+    /// Run `hook` for every write committed to `namespace` from now on.
+    ///
+    /// Delivery is at least once: a hook that fails gets the same event again
+    /// after a backoff, holding back the events behind it, and each
+    /// [`HookEvent`] carries its log sequence so repeats can be dropped.
+    ///
+    /// The hook itself lives in memory, but the sequence it has handled every
+    /// write through is saved beside the log. Registering a hook again under
+    /// `name`, after a restart or a slow hook's queue overflowed, first
+    /// replays the writes it missed from the trajectory log, as long as the
+    /// log still holds them.
+    ///
+    /// Upserts and deletions appended to the trajectory log are reported;
+    /// writes that change nothing on disk ([`DB::forget_object`],
+    /// [`DB::hydrate_from_history`]) are not.
+    pub fn register_hook(
+        &self,
+        name: &str,
+        namespace: &str,
+        mode: HookMode,
+        hook: impl WriteHook,
+    ) -> Result<()> {
+        db_span!("spatio.register_hook", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        validate_identifier("hook name", name)?;
+        validate_identifier("namespace", namespace)?;
+        if !self.hooks.register(name, namespace, mode, hook)? {
+            return Err(SpatioError::InvalidInput(format!(
+                "hook {name:?} already exists"
+            )));
+        }
+        Ok(())
+    }
 
-        let pos2 = Point3d::new(10.1, 20.1, 0.0);
-        let metadata2 = serde_json::json!({"engine": "off"});
-        db.upsert(namespace, object_id, pos2.clone(), metadata2.clone(), None)
-            .unwrap();
+    /// Remove a hook, returning whether it existed. Events it has not
+    /// handled yet are discarded, and so is its saved place in the log: a
+    /// hook registered under the same name later starts from then.
+    pub fn remove_hook(&self, name: &str) -> Result<bool> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        Ok(self.hooks.remove(name))
+    }
 
-        let results = db.query_radius(namespace, &pos2, 1.0, 1).unwrap();
-        assert_eq!(results.len(), 1);
-        assert_eq!(results[0].0.object_id, object_id);
-        assert_eq!(results[0].0.position, pos2);
-        assert_eq!(results[0].0.metadata, metadata2);
+    /// Number of events waiting for a hook, including one being retried, plus
+    /// one while it still has writes to read back from the log. Zero once it
+    /// has handled every write so far.
+    pub fn hook_backlog(&self, name: &str) -> Result<usize> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.hooks
+            .backlog(name)
+            .ok_or_else(|| SpatioError::InvalidInput(format!("no hook named {name:?}")))
     }
 
-    #[test]
-    fn test_query_near_object() {
-        let db = DB::memory().unwrap();
-        let namespace = "vehicles";
+    /// Store a zone (bounding box or polygon) under `zone_id`, replacing any
+    /// previous zone with the same ID.
+    ///
+    /// Zones live in their own keyspace, separate from tracked objects. They
+    /// are not written to the trajectory log: every change rewrites all zones
+    /// to a file beside it (`<log>.zones`), loaded again on open. If that
+    /// write fails the zone is left as it was and the error returned.
+    ///
+    /// A numeric [`SPEED_LIMIT_KEY`] entry in `metadata` sets the zone's speed
+    /// limit in meters per second (see [`DB::violations`]).
+    pub fn insert_zone(
+        &self,
+        namespace: &str,
+        zone_id: &str,
+        geometry: ZoneGeometry,
+        metadata: serde_json::Value,
+    ) -> Result<()> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::ZoneWrite)?;
+        validate_identifier("namespace", namespace)?;
+        validate_identifier("zone_id", zone_id)?;
+        match &geometry {
+            ZoneGeometry::BBox(bbox) => {
+                validation::validate_bbox(bbox.min_x(), bbox.min_y(), bbox.max_x(), bbox.max_y())?
+            }
+            ZoneGeometry::Polygon(polygon) => validation::validate_polygon(polygon)?,
+        }
+        if let Some(limit) = metadata.get(SPEED_LIMIT_KEY) {
+            validation::validate_positive(SPEED_LIMIT_KEY, limit.as_f64().unwrap_or(f64::NAN))?;
+        }
 
-        let car1_pos = Point3d::new(0.0, 0.0, 0.0);
-        db.upsert(namespace, "car1", car1_pos, serde_json::json!({}), None)
-            .unwrap();
+        let _zones = self.zone_writes.lock();
+        let previous = self.hot.upsert_zone(namespace, zone_id, geometry, metadata);
+        if let Err(e) = self.cold.write_zones(&self.hot.all_zones()) {
+            match previous {
+                Some(zone) => self.restore_zone(&zone),
+                None => drop(self.hot.remove_zone(namespace, zone_id)),
+            }
+            return Err(e);
+        }
+        self.ops_count.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
 
-        let car2_pos = Point3d::new(0.00001, 0.0, 0.0); // ~1 meter away
-        db.upsert(namespace, "car2", car2_pos, serde_json::json!({}), None)
-            .unwrap();
+    fn restore_zone(&self, zone: &Zone) {
+        self.hot.upsert_zone(
+            &zone.namespace,
+            &zone.zone_id,
+            zone.geometry.clone(),
+            zone.metadata.clone(),
+        );
+    }
 
-        let car3_pos = Point3d::new(10.0, 0.0, 0.0); // 10 units away
-        db.upsert(namespace, "car3", car3_pos, serde_json::json!({}), None)
-            .unwrap();
+    /// Get a stored zone.
+    pub fn get_zone(&self, namespace: &str, zone_id: &str) -> Result<Option<Arc<Zone>>> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::ZoneRead)?;
+        Ok(self.hot.get_zone(namespace, zone_id))
+    }
+
+    /// Delete a stored zone, returning it if it existed.
+    pub fn delete_zone(&self, namespace: &str, zone_id: &str) -> Result<Option<Arc<Zone>>> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::ZoneWrite)?;
+        let _zones = self.zone_writes.lock();
+        let removed = self.hot.remove_zone(namespace, zone_id);
+        if let Some(zone) = &removed
+            && let Err(e) = self.cold.write_zones(&self.hot.all_zones())
+        {
+            self.restore_zone(zone);
+            return Err(e);
+        }
+        Ok(removed)
+    }
+
+    /// Zones of `namespace` containing `point`, boundary included, in zone
+    /// ID order: "which service areas cover this address".
+    ///
+    /// The zone index narrows the search to zones whose envelope holds the
+    /// point; each is then tested against its exact geometry.
+    pub fn zones_containing(
+        &self,
+        namespace: &str,
+        point: &spatio_types::geo::Point,
+        limit: usize,
+    ) -> Result<Vec<Arc<Zone>>> {
+        db_span!("spatio.zones_containing", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::ZoneRead)?;
+        validation::validate_geographic_point(point)?;
+        Ok(self.hot.zones_containing(namespace, point, limit))
+    }
+
+    /// Zones of `namespace` sharing any point with `polygon` (overlapping
+    /// it, inside it, containing it or touching its boundary), in zone ID
+    /// order.
+    pub fn zones_intersecting(
+        &self,
+        namespace: &str,
+        polygon: &spatio_types::geo::Polygon,
+        limit: usize,
+    ) -> Result<Vec<Arc<Zone>>> {
+        db_span!("spatio.zones_intersecting", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::ZoneRead)?;
+        validation::validate_polygon(polygon)?;
+        Ok(self.hot.zones_intersecting(namespace, polygon, limit))
+    }
+
+    /// Find the k stored zones nearest to a point, returning (Zone, distance).
+    ///
+    /// Distance is measured in meters to the zone boundary, so a large zone
+    /// whose edge is close ranks ahead of a small zone whose center is closer.
+    /// Zones containing the point are at distance `0.0`.
+    pub fn nearest_zones(
+        &self,
+        namespace: &str,
+        point: &spatio_types::geo::Point,
+        k: usize,
+    ) -> Result<Vec<ZoneHit>> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::ZoneRead)?;
+        validation::validate_geographic_point(point)?;
+        Ok(hits::named(self.hot.nearest_zones(namespace, point, k)))
+    }
+
+    /// Calculate geodesic distance (meters) from a point to a stored zone's
+    /// boundary; `0.0` if the point is inside the zone.
+    pub fn distance_to_zone(
+        &self,
+        namespace: &str,
+        zone_id: &str,
+        point: &spatio_types::geo::Point,
+    ) -> Result<Option<f64>> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::ZoneRead)?;
+        validation::validate_geographic_point(point)?;
+        Ok(self.hot.distance_to_zone(namespace, zone_id, point))
+    }
+
+    /// Query historical trajectory (COLD PATH), newest first.
+    ///
+    /// `range` is any [`TimeRange`]: `start..=end`, `start..`, `..end`, `..`
+    /// or [`TimeRange::last`].
+    pub fn query_trajectory(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        range: impl Into<TimeRange>,
+        limit: usize,
+    ) -> Result<Vec<LocationUpdate>> {
+        db_span!("spatio.query_trajectory", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::QueryTrajectory)?;
+        let now = SystemTime::now();
+        let (start_time, end_time) = range.into().resolve(now);
+        self.log_access(|| AccessQuery::Trajectory {
+            namespace: namespace.to_string(),
+            object_id: object_id.to_string(),
+            start_micros: access_log::micros(start_time),
+            end_micros: access_log::micros(end_time),
+            limit,
+        });
+        let start_time = match self.history_cutoff(namespace, now) {
+            Some(cutoff) => start_time.max(cutoff),
+            None => start_time,
+        };
+        self.cold
+            .query_trajectory(namespace, object_id, start_time, end_time, limit)
+    }
+
+    /// Query historical trajectory one page at a time, newest first, like
+    /// [`DB::query_trajectory`].
+    ///
+    /// Tokens work as in [`DB::query_bbox_page`]; later pages resolve
+    /// `range` again, so a relative range such as [`TimeRange::last`] moves
+    /// its start forward as the scan goes on.
+    pub fn query_trajectory_page(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        range: impl Into<TimeRange>,
+        page_size: usize,
+        token: Option<&str>,
+    ) -> Result<Page<LocationUpdate>> {
+        db_span!("spatio.query_trajectory_page", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.hydration.wait_for(namespace);
+        validate_page_size(page_size)?;
+        let start = pagination::PageToken::resume(
+            token,
+            PageKind::Trajectory,
+            pagination::query_hash(&format!("{namespace}\0{object_id}"), &[]),
+            self.cold.log_id(),
+            self.cold.last_sequence(),
+        )?;
+        let nanos = |update: &LocationUpdate| {
+            update
+                .timestamp
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos() as u64
+        };
+        let range = range.into();
+        let range = match token {
+            Some(_) => {
+                let (start_time, _) = range.resolve(SystemTime::now());
+                let end_time =
+                    SystemTime::UNIX_EPOCH + std::time::Duration::from_nanos(start.position);
+                TimeRange::from(start_time..=end_time)
+            }
+            None => range,
+        };
+        let skip = if token.is_some() {
+            start.offset as usize
+        } else {
+            0
+        };
+        let items = self
+            .query_trajectory(
+                namespace,
+                object_id,
+                range,
+                skip.saturating_add(page_size).saturating_add(1),
+            )?
+            .into_iter()
+            .skip(skip)
+            .collect();
+        Ok(next_page(items, page_size, start, nanos))
+    }
+
+    /// Every recorded write and delete of an object, oldest first.
+    ///
+    /// Unlike [`DB::query_trajectory`], this includes the deletes, so an
+    /// audit view can show when an object disappeared and came back. Records
+    /// older than the namespace's history retention are left out.
+    pub fn history(&self, namespace: &str, object_id: &str) -> Result<Vec<HistoryEntry>> {
+        self.history_between(namespace, object_id, .., None)
+    }
+
+    /// The writes and deletes of an object within `range`, oldest first,
+    /// optionally only those of `kind`.
+    pub fn history_between(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        range: impl Into<TimeRange>,
+        kind: Option<HistoryEventKind>,
+    ) -> Result<Vec<HistoryEntry>> {
+        db_span!("spatio.history", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::QueryTrajectory)?;
+        validate_identifier("namespace", namespace)?;
+        validate_identifier("object_id", object_id)?;
+        let now = SystemTime::now();
+        let (start_time, end_time) = range.into().resolve(now);
+        let start_time = match self.history_cutoff(namespace, now) {
+            Some(cutoff) => start_time.max(cutoff),
+            None => start_time,
+        };
+        self.cold
+            .object_history(namespace, object_id, start_time, end_time, |k| {
+                kind.is_none_or(|kind| kind == k)
+            })
+    }
+
+    /// Split the history of an object within `range` into trips, oldest
+    /// first (see [`crate::compute::trips`]).
+    ///
+    /// A new trip starts after the object goes quiet for longer than
+    /// `gap_threshold` or reports its ignition off. Only points inside the
+    /// window are considered, so a trip crossing either bound is cut there.
+    pub fn trips(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        range: impl Into<TimeRange>,
+        gap_threshold: std::time::Duration,
+    ) -> Result<Vec<Trip>> {
+        let history = self.query_trajectory(namespace, object_id, range, usize::MAX)?;
+        Ok(trips::split_trips(&history, gap_threshold))
+    }
+
+    /// Distance, duration and speeds of an object's history within `range`
+    /// (see [`Trajectory::summary`]), computed here rather than by shipping
+    /// the points to the caller.
+    pub fn trajectory_summary(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        range: impl Into<TimeRange>,
+    ) -> Result<TrajectorySummary> {
+        let mut history = self.query_trajectory(namespace, object_id, range, usize::MAX)?;
+        history.reverse();
+        Ok(to_trajectory(&history).summary())
+    }
+
+    /// Total time each object of `namespace` spent inside the zone `zone_id`
+    /// within `range`, keyed by object ID, replayed from the trajectory log.
+    /// Objects that never entered are left out.
+    ///
+    /// An object counts as staying where it was last seen until its next
+    /// point or its deletion, so a stay that began before the window counts
+    /// from its start, and an open end counts up to now. Points are checked
+    /// against the zone's bounding box before its exact geometry. Fails if
+    /// there is no such zone.
+    pub fn dwell_report(
+        &self,
+        namespace: &str,
+        zone_id: &str,
+        range: impl Into<TimeRange>,
+    ) -> Result<BTreeMap<String, std::time::Duration>> {
+        db_span!("spatio.dwell_report", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::QueryTrajectory)?;
+        let zone = self.hot.get_zone(namespace, zone_id).ok_or_else(|| {
+            SpatioError::InvalidInput(format!("no zone {zone_id:?} in namespace {namespace:?}"))
+        })?;
+        let Some((min_x, min_y, max_x, max_y)) = zone.geometry.bounds() else {
+            return Ok(BTreeMap::new());
+        };
+        let now = SystemTime::now();
+        let (start_time, end_time) = range.into().resolve(now);
+        let end_time = end_time.min(now.max(start_time));
+        let start_time = match self.history_cutoff(namespace, now) {
+            Some(cutoff) => start_time.max(cutoff),
+            None => start_time,
+        };
+        self.cold
+            .dwell_times(namespace, start_time, end_time, |position| {
+                (min_x..=max_x).contains(&position.x())
+                    && (min_y..=max_y).contains(&position.y())
+                    && zone
+                        .geometry
+                        .contains(&spatio_types::geo::Point::new(position.x(), position.y()))
+            })
+    }
+
+    /// Times objects of `namespace` exceeded the speed limit of a zone of the
+    /// namespace within `range`, ordered by object ID and then start time
+    /// (see [`crate::compute::violations`]).
+    ///
+    /// Only zones with a speed limit attribute are checked.
+    pub fn violations(
+        &self,
+        namespace: &str,
+        range: impl Into<TimeRange>,
+    ) -> Result<Vec<SpeedViolation>> {
+        db_span!("spatio.violations", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::QueryTrajectory)?;
+        let zones = self.hot.zones(namespace);
+        if zones.iter().all(|zone| zone.speed_limit().is_none()) {
+            return Ok(Vec::new());
+        }
+        let now = SystemTime::now();
+        let (start_time, end_time) = range.into().resolve(now);
+        let start_time = match self.history_cutoff(namespace, now) {
+            Some(cutoff) => start_time.max(cutoff),
+            None => start_time,
+        };
+        let points = self.cold.scan_namespace(namespace, start_time, end_time)?;
+        Ok(violations::speed_violations(&points, &zones))
+    }
+
+    /// Objects of `namespace` whose trajectory within `range` passed through
+    /// `bbox`, with the stretches that did, ordered by object ID (see
+    /// [`crate::compute::passes`]).
+    pub fn query_trajectories_intersecting_bbox(
+        &self,
+        namespace: &str,
+        bbox: &spatio_types::bbox::BoundingBox2D,
+        range: impl Into<TimeRange>,
+    ) -> Result<Vec<TrajectoryMatch>> {
+        db_span!("spatio.query_trajectories_intersecting_bbox", namespace);
+        let points = self.scan_trajectories(namespace, range.into())?;
+        Ok(passes::passes(&points, |update| {
+            bbox.contains_point(&spatio_types::geo::Point::new(
+                update.position.x(),
+                update.position.y(),
+            ))
+        }))
+    }
+
+    /// Objects of `namespace` whose trajectory within `range` came within
+    /// `radius` meters (haversine) of `center`, with the stretches that did,
+    /// ordered by object ID (see [`crate::compute::passes`]).
+    pub fn query_trajectories_within_radius(
+        &self,
+        namespace: &str,
+        center: &spatio_types::point::Point3d,
+        radius: f64,
+        range: impl Into<TimeRange>,
+    ) -> Result<Vec<TrajectoryMatch>> {
+        db_span!("spatio.query_trajectories_within_radius", namespace);
+        if !radius.is_finite() || radius < 0.0 {
+            return Err(SpatioError::InvalidInput(format!(
+                "radius must be a non-negative number of meters, got {radius}"
+            )));
+        }
+        let points = self.scan_trajectories(namespace, range.into())?;
+        Ok(passes::passes(&points, |update| {
+            center.haversine_2d(&update.position) <= radius
+        }))
+    }
+
+    /// Every logged point of `namespace` within `range`, for the queries
+    /// that replay all trajectories of a namespace.
+    fn scan_trajectories(
+        &self,
+        namespace: &str,
+        range: TimeRange,
+    ) -> Result<Vec<(String, LocationUpdate)>> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::QueryTrajectory)?;
+        let now = SystemTime::now();
+        let (start_time, end_time) = range.resolve(now);
+        let start_time = match self.history_cutoff(namespace, now) {
+            Some(cutoff) => start_time.max(cutoff),
+            None => start_time,
+        };
+        self.cold.scan_namespace(namespace, start_time, end_time)
+    }
+
+    /// Metric radius and cylinder queries in `namespace` default to.
+    fn distance_metric(&self, namespace: &str) -> crate::compute::spatial::DistanceMetric {
+        self.config
+            .namespace_distance_metrics
+            .get(namespace)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Oldest trajectory point of `namespace` still within its history
+    /// retention as of `now`, if the namespace has one.
+    fn history_cutoff(&self, namespace: &str, now: SystemTime) -> Option<SystemTime> {
+        let secs = self.namespace_settings.retention_secs(namespace)?;
+        Some(
+            now.checked_sub(std::time::Duration::from_secs(secs))
+                .unwrap_or(std::time::UNIX_EPOCH),
+        )
+    }
+
+    /// Remove trajectory history past the per-namespace retention (see
+    /// [`Config::with_history_retention`] and [`DB::configure_namespace`])
+    /// from the log, returning the number of records removed.
+    ///
+    /// Points older than a namespace's retention are never returned by
+    /// trajectory queries; this reclaims their space and makes the removal
+    /// durable. Points beyond an object's newest
+    /// [`NamespaceConfig::retention_updates`] are still returned until it
+    /// runs. It also runs on open and, with
+    /// [`Config::with_active_expiration`], in the background, when the
+    /// [`CompactionPolicy`] allows. Each object's current location is kept
+    /// however old it is.
+    pub fn expire_history(&self) -> Result<u64> {
+        db_span!("spatio.expire_history");
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.hydration.wait_all();
+        let removed = self
+            .cold
+            .expire_history(&self.namespace_settings.retention(), SystemTime::now())?;
+        self.expired.fetch_add(removed, Ordering::Relaxed);
+        Ok(removed)
+    }
+
+    /// Remove trajectory history of `namespace` older than `older_than`,
+    /// along with whatever its configured retention expires, returning the
+    /// number of log records removed.
+    ///
+    /// A one-off [`DB::expire_history`] for one namespace with a tighter age
+    /// limit, for long-lived trackers that need to free disk space now. Each
+    /// object's current location is kept however old it is. Like
+    /// [`DB::expire_history`], this rewrites the log.
+    pub fn prune_trajectories(
+        &self,
+        namespace: &str,
+        older_than: std::time::Duration,
+    ) -> Result<u64> {
+        db_span!("spatio.prune_trajectories", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.hydration.wait_all();
+        validate_identifier("namespace", namespace)?;
+        let settings = self.namespace_settings.get(namespace);
+        let secs = older_than.as_secs() + u64::from(older_than.subsec_nanos() > 0);
+        let retention = cold_state::HistoryRetention {
+            max_age_secs: HashMap::from([(
+                namespace.to_string(),
+                settings
+                    .retention_secs
+                    .map_or(secs, |configured| configured.min(secs)),
+            )]),
+            max_updates: settings
+                .retention_updates
+                .map(|updates| HashMap::from([(namespace.to_string(), updates)]))
+                .unwrap_or_default(),
+        };
+        let removed = self.cold.expire_history(&retention, SystemTime::now())?;
+        self.expired.fetch_add(removed, Ordering::Relaxed);
+        Ok(removed)
+    }
+
+    /// Thin the trajectory history of `namespace` to what `policy` keeps,
+    /// returning the number of log records removed.
+    ///
+    /// Meant for objects that report every second but only need coarse
+    /// history long-term. Each object's current location is kept however old
+    /// it is. Like [`DB::expire_history`], this rewrites the log, blocking
+    /// writers for a full pass over it.
+    pub fn compact_history(&self, namespace: &str, policy: HistoryCompaction) -> Result<u64> {
+        db_span!("spatio.compact_history", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.hydration.wait_all();
+        validate_identifier("namespace", namespace)?;
+        self.cold.compact_history(namespace, policy)
+    }
+
+    /// Write a recovery checkpoint covering every write so far.
+    ///
+    /// The next open loads current locations from the checkpoint and replays
+    /// only the log after it. A full checkpoint (`<log>.snap`) holds every
+    /// current location. An incremental one (`<log>.snap.delta`) holds only
+    /// the objects changed since the last full checkpoint and names that
+    /// checkpoint's checksum, so frequent ones stay cheap to write and to
+    /// copy to a backup. Both files end with a CRC32 of their contents; one
+    /// that fails it is ignored on open, and the log replayed instead.
+    ///
+    /// A checkpoint is also written after every open. The spatial indexes are
+    /// saved with it (`<log>.idx`), and again on close, so the next open
+    /// loads them instead of rebuilding them. Writers wait while a checkpoint
+    /// is written. Fails for in-memory databases.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use spatio::{CheckpointKind, DB, Point3d};
+    /// use serde_json::json;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let db = DB::open(dir.path().join("fleet.db")).unwrap();
+    /// db.upsert("fleet", "truck", Point3d::new(1.0, 2.0, 0.0), json!({}), None).unwrap();
+    /// db.checkpoint(CheckpointKind::Full).unwrap();
+    ///
+    /// db.upsert("fleet", "van", Point3d::new(3.0, 4.0, 0.0), json!({}), None).unwrap();
+    /// let report = db.checkpoint(CheckpointKind::Incremental).unwrap();
+    /// assert_eq!(report.objects, 1);
+    /// ```
+    pub fn checkpoint(&self, kind: CheckpointKind) -> Result<CheckpointReport> {
+        db_span!("spatio.checkpoint");
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.hydration.wait_all();
+        let report = self.cold.checkpoint(kind)?;
+        self.save_indexes();
+        Ok(report)
+    }
+
+    /// Save the spatial indexes beside the log for the next open to load.
+    /// Best-effort: without them the next open rebuilds the indexes.
+    fn save_indexes(&self) {
+        if let Err(e) = self
+            .cold
+            .write_index_snapshot(|w| self.hot.save_point_indexes(w))
+        {
+            log::warn!("Failed to save spatial indexes: {}", e);
+        }
+    }
+
+    /// Thin the stored trajectory of an object with Douglas–Peucker (see
+    /// [`Trajectory::simplify`]), returning the number of log records removed.
+    ///
+    /// `epsilon` is in coordinate units (degrees for longitude/latitude).
+    /// Retained points keep their timestamps and metadata, and the object's
+    /// current location is kept. Points written while this runs are never
+    /// removed. Like [`DB::compact_history`], this rewrites the log.
+    pub fn simplify_trajectory(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        epsilon: f64,
+    ) -> Result<u64> {
+        db_span!("spatio.simplify_trajectory", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.hydration.wait_all();
+        validate_identifier("namespace", namespace)?;
+        validate_identifier("object_id", object_id)?;
+        validation::validate_positive("epsilon", epsilon)?;
+        let (start, end) = TimeRange::all().resolve(SystemTime::now());
+        let mut history =
+            self.cold
+                .query_trajectory(namespace, object_id, start, end, usize::MAX)?;
+        history.reverse();
+        let trajectory = to_trajectory(&history);
+        let kept: HashSet<SystemTime> = trajectory
+            .simplify(epsilon)
+            .points()
+            .iter()
+            .map(|p| p.timestamp)
+            .collect();
+        let dropped = history
+            .into_iter()
+            .map(|u| u.timestamp)
+            .filter(|t| !kept.contains(t))
+            .collect();
+        self.cold.drop_updates(namespace, object_id, dropped)
+    }
+
+    /// Replace the current locations of `namespace` with its state as of
+    /// `at_time`, replayed from the trajectory log, and return the number of
+    /// objects it now holds.
+    ///
+    /// Live queries then answer as of that moment: objects deleted since
+    /// reappear at their old positions, and objects first seen later are
+    /// dropped. Only the in-memory state changes; the log keeps the full
+    /// history, so reopening the database restores the latest state, and
+    /// newer writes apply on top as usual. Zones are untouched. Pause writers
+    /// to the namespace first: a write racing with the replay may be lost.
+    ///
+    /// Fails if `at_time` is before the namespace's history retention, since
+    /// the replayed state would be missing expired records.
+    pub fn hydrate_from_history(&self, namespace: &str, at_time: SystemTime) -> Result<usize> {
+        db_span!("spatio.hydrate_from_history", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.hydration.wait_for(namespace);
+        validate_identifier("namespace", namespace)?;
+        if let Some(cutoff) = self.history_cutoff(namespace, SystemTime::now())
+            && at_time < cutoff
+        {
+            return Err(SpatioError::InvalidInput(format!(
+                "History of namespace {namespace:?} is only retained for {}s",
+                self.namespace_settings
+                    .retention_secs(namespace)
+                    .unwrap_or_default()
+            )));
+        }
+
+        let state = self.cold.namespace_state_at(namespace, at_time)?;
+        let current = self
+            .hot
+            .range(namespace, .., usize::MAX, ScanDirection::Forward);
+        for loc in current {
+            if !state.contains_key(&loc.object_id) {
+                let removed = self.hot.remove_object(namespace, &loc.object_id);
+                self.refresh_watchers(namespace, &loc.object_id);
+                self.changes.publish(removed, None);
+            }
+        }
+        for (object_id, update) in &state {
+            // Removed first: the live location is newer and would win.
+            let previous = self.hot.remove_object(namespace, object_id);
+            self.hot.update_location(
+                namespace,
+                object_id,
+                update.position.clone(),
+                update.metadata.clone(),
+                update.timestamp,
+            )?;
+            self.refresh_watchers(namespace, object_id);
+            let current = self.hot.get_current_location(namespace, object_id);
+            self.changes.publish(previous, current);
+        }
+        Ok(state.len())
+    }
+
+    /// Write the records of `namespace` selected by `spec` to `out`, returning
+    /// the number of records written.
+    ///
+    /// Without a time range this exports current locations in object ID
+    /// order; with one, every trajectory point in the range (within the
+    /// namespace's history retention), ordered by object ID and then time.
+    pub fn export<W: std::io::Write>(
+        &self,
+        namespace: &str,
+        spec: &ExportSpec,
+        out: W,
+    ) -> Result<usize> {
+        db_span!("spatio.export", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::Export)?;
+        spec.validate()?;
+
+        let records: Vec<ExportRecord> = match spec.time_range {
+            None => self
+                .hot
+                .range(namespace, .., usize::MAX, ScanDirection::Forward)
+                .into_iter()
+                .map(|loc| ExportRecord {
+                    object_id: loc.object_id.clone(),
+                    timestamp: loc.timestamp,
+                    position: loc.position.clone(),
+                    metadata: loc.metadata.clone(),
+                })
+                .collect(),
+            Some((start, end)) => {
+                let start = match self.history_cutoff(namespace, SystemTime::now()) {
+                    Some(cutoff) => start.max(cutoff),
+                    None => start,
+                };
+                self.cold
+                    .scan_namespace(namespace, start, end)?
+                    .into_iter()
+                    .map(|(object_id, update)| ExportRecord {
+                        object_id,
+                        timestamp: update.timestamp,
+                        position: update.position,
+                        metadata: update.metadata,
+                    })
+                    .collect()
+            }
+        };
+        export::write_records(namespace, spec, records, out)
+    }
+
+    /// Close the database, flushing and syncing any buffered writes to disk
+    /// and saving the spatial indexes, and let other processes open it.
+    pub fn close(&self) -> Result<()> {
+        self.closed.store(true, Ordering::Release);
+        self.hydration.wait_all();
+        if let Some(expiration) = self.expiration.get() {
+            expiration.stop();
+        }
+        if let Some(guard) = self.disk_guard.get() {
+            guard.stop();
+        }
+        if let Some(log) = &self.access_log {
+            log.flush()?;
+        }
+        if let Some(log) = &self.rejection_log {
+            log.flush()?;
+        }
+        self.hooks.close();
+        let flushed = self.cold.flush();
+        self.save_indexes();
+        self.cold.unlock();
+        flushed
+    }
+
+    /// Sequence of the last write appended to the trajectory log (0 if none).
+    ///
+    /// Every logged mutation (upsert, delete) gets the next sequence in log
+    /// order. Numbering resumes from the log on open, so sequences increase
+    /// monotonically for the lifetime of the database files. Zones are saved
+    /// beside the log rather than in it, and are not sequenced.
+    pub fn last_sequence(&self) -> u64 {
+        self.cold.last_sequence()
+    }
+
+    /// Random ID of the trajectory log, created with it and kept beside it
+    /// (`<log>.id`), so it survives restarts. Sequences (see
+    /// [`DB::last_sequence`]) only identify a write together with it: tokens
+    /// carrying a sequence, such as page tokens, carry it too and are refused
+    /// by a database with another log. Memory databases get a new one each
+    /// time.
+    pub fn log_id(&self) -> u64 {
+        self.cold.log_id()
+    }
+
+    /// Last durable sequence: every write up to and including it has been
+    /// synced to stable storage.
+    ///
+    /// Advances with each `fsync` the configured [`SyncPolicy`] performs,
+    /// including the final one on `close`. `SyncPolicy::Never` only syncs on
+    /// `AsyncDB::sync`, so until then it stays at 0; in-memory databases
+    /// have nothing to sync and report every write durable.
+    ///
+    /// [`SyncPolicy`]: crate::config::SyncPolicy
+    pub fn flush_watermark(&self) -> u64 {
+        self.cold.durable_sequence()
+    }
+
+    /// Wait until write `sequence` is durable, for at most `timeout`.
+    ///
+    /// Lets writers under `SyncPolicy::Background` confirm durability without
+    /// syncing on their own thread; the background thread is asked to sync
+    /// immediately rather than at its next interval. Returns whether the
+    /// sequence is durable.
+    pub fn wait_durable(&self, sequence: u64, timeout: std::time::Duration) -> bool {
+        self.cold.wait_durable(sequence, timeout)
+    }
+
+    /// Wait until write `sequence` has been applied, for at most `timeout`.
+    ///
+    /// Sequences number the writes of this database's log only (see
+    /// [`DB::log_id`]): Spatio doesn't copy writes between databases, so
+    /// another database's sequence says nothing about this one. Returns
+    /// whether the write is visible to reads.
+    pub fn wait_applied(&self, sequence: u64, timeout: std::time::Duration) -> bool {
+        self.cold.wait_applied(sequence, timeout)
+    }
+
+    /// The compaction or checkpoint rewriting the trajectory log, if one is
+    /// running. Writes wait for it, so callers with a deadline may rather
+    /// back off for [`Maintenance::remaining`] than queue behind it.
+    pub fn maintenance(&self) -> Option<Maintenance> {
+        self.cold.maintenance()
+    }
+
+    /// Recent queries that took at least
+    /// [`Config::slow_query_threshold_ms`], oldest first, up to
+    /// [`SLOW_QUERY_LOG_CAPACITY`]. Empty unless the threshold is set.
+    pub fn slow_queries(&self) -> Vec<SlowQuery> {
+        self.slow_queries.recent()
+    }
+
+    /// Get database statistics
+    pub fn stats(&self) -> DbStats {
+        let (hot_objects, hot_memory) = self.hot.detailed_stats();
+        let (cold_trajectories, cold_buffer_bytes) = self.cold.stats();
+        let per_minute = self.op_stats.snapshot();
+        // The current minute is still under way.
+        let last_minute = (SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            / 60)
+            .saturating_sub(1);
+        let updates: u64 = per_minute
+            .iter()
+            .filter(|minute| minute.minute == last_minute)
+            .flat_map(|minute| {
+                Operation::ALL
+                    .into_iter()
+                    .filter(|op| op.is_update())
+                    .map(|op| minute.total(op))
+            })
+            .sum();
+
+        DbStats {
+            expired_count: self.expired.load(Ordering::Relaxed),
+            operations_count: self.ops_count.load(Ordering::Relaxed),
+            size_bytes: hot_memory + cold_buffer_bytes,
+            hot_state_objects: hot_objects,
+            namespace_objects: self.hot.namespace_counts(),
+            hot_state_memory_bytes: hot_memory,
+            cold_state_trajectories: cold_trajectories,
+            cold_state_buffer_bytes: cold_buffer_bytes,
+            memory_usage_bytes: hot_memory + cold_buffer_bytes,
+            cold_log_bytes: self.cold.log_bytes(),
+            queries_count: self.queries_count.load(Ordering::Relaxed),
+            updates_per_sec: updates as f64 / 60.0,
+            per_minute,
+            disk_available_bytes: self.disk_guard.get().map(|guard| guard.available()),
+            persistence: match self.cold.degraded() {
+                Some(reason) => PersistenceStatus::DegradedPersistence {
+                    reason: reason.to_string(),
+                },
+                None => PersistenceStatus::Healthy,
+            },
+            fsync_latency: self.cold.sync_latency(),
+        }
+    }
+    /// Query objects within a polygon
+    pub fn query_polygon(
+        &self,
+        namespace: &str,
+        polygon: &spatio_types::geo::Polygon,
+        limit: usize,
+    ) -> Result<Vec<Arc<CurrentLocation>>> {
+        db_span!("spatio.query_polygon", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::QueryPolygon)?;
+        let _timer = self.time_query(namespace, Operation::QueryPolygon);
+        validation::validate_polygon(polygon)?;
+        self.log_access(|| AccessQuery::Polygon {
+            namespace: namespace.to_string(),
+            exterior: polygon.exterior().coords().map(|c| [c.x, c.y]).collect(),
+            limit,
+        });
+        Ok(self.hot.query_polygon(namespace, polygon, limit))
+    }
+
+    /// [`DB::query_polygon`], with each result's distance in meters from
+    /// `anchor`, or from the centroid of the polygon when `anchor` is `None`.
+    ///
+    /// Distances are horizontal, in the namespace's metric. Results keep the
+    /// order of the plain query, so sort them to get nearest first.
+    pub fn query_polygon_with_distances(
+        &self,
+        namespace: &str,
+        polygon: &spatio_types::geo::Polygon,
+        anchor: Option<&spatio_types::point::Point3d>,
+        limit: usize,
+    ) -> Result<Vec<NearbyHit>> {
+        use geo::Centroid;
+
+        let results = self.query_polygon(namespace, polygon, limit)?;
+        let anchor = match anchor {
+            Some(anchor) => spatio_types::geo::Point::new(anchor.x(), anchor.y()),
+            // Only an empty polygon has no centroid, and it contains nothing.
+            None => match polygon.inner().centroid() {
+                Some(centroid) => spatio_types::geo::Point::from(centroid),
+                None => return Ok(Vec::new()),
+            },
+        };
+        Ok(self.with_distances(namespace, results, &anchor))
+    }
+
+    /// Pair query results with their horizontal distance from `anchor` in
+    /// the namespace's metric.
+    fn with_distances(
+        &self,
+        namespace: &str,
+        results: Vec<Arc<CurrentLocation>>,
+        anchor: &spatio_types::geo::Point,
+    ) -> Vec<NearbyHit> {
+        let metric = self.distance_metric(namespace);
+        results
+            .into_iter()
+            .map(|loc| {
+                let point = spatio_types::geo::Point::new(loc.position.x(), loc.position.y());
+                let distance = crate::compute::spatial::distance_between(anchor, &point, metric);
+                NearbyHit::new(loc, distance)
+            })
+            .collect()
+    }
+
+    /// Start building a query against `namespace`, as a readable alternative
+    /// to the positional query methods.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use spatio::{Point, Point3d, Spatio};
+    ///
+    /// let db = Spatio::memory().unwrap();
+    /// let depot = Point::new(-74.0060, 40.7128);
+    /// db.upsert("fleet", "van-1", Point3d::new(-74.0061, 40.7129, 0.0),
+    ///     serde_json::json!({"fuel": "diesel"}), None).unwrap();
+    ///
+    /// let vans = db
+    ///     .select("fleet")
+    ///     .within_radius(depot, 500.0)
+    ///     .filter_metadata("fuel", "diesel")
+    ///     .order_by_distance()
+    ///     .limit(50)
+    ///     .execute()
+    ///     .unwrap();
+    /// assert_eq!(vans.len(), 1);
+    /// ```
+    pub fn select(&self, namespace: &str) -> QueryBuilder<'_> {
+        QueryBuilder::new(self, namespace)
+    }
+
+    /// Query objects matching a composite predicate, such as "inside polygon
+    /// A, not inside polygon B, below 500 m", in a single index scan.
+    ///
+    /// The scan covers the box implied by the predicate's positive
+    /// constraints; every candidate in it is then tested exactly. When
+    /// metadata indexes (see [`DB::create_metadata_index`]) narrow the
+    /// predicate, the candidates are the objects they list instead, and
+    /// results come back in object ID order.
+    pub fn query(
+        &self,
+        namespace: &str,
+        predicate: &Predicate,
+        limit: usize,
+    ) -> Result<Vec<Arc<CurrentLocation>>> {
+        db_span!("spatio.query", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::Query)?;
+        let _timer = self.time_query(namespace, Operation::Query);
+        validation::validate_predicate(predicate)?;
+        self.log_access(|| AccessQuery::Composite {
+            namespace: namespace.to_string(),
+            predicate: predicate.clone(),
+            limit,
+        });
+
+        let envelope = crate::compute::query::envelope(predicate);
+        if envelope.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+        if let Some(candidates) = self.metadata_indexes.candidates(namespace, predicate) {
+            return Ok(candidates
+                .iter()
+                .filter_map(|id| self.hot.get_current_location(namespace, id))
+                .filter(|loc| {
+                    crate::compute::query::matches(predicate, &loc.position, &loc.metadata)
+                })
+                .take(limit)
+                .collect());
+        }
+        let [min_x, min_y, min_z] = envelope.min;
+        let [max_x, max_y, max_z] = envelope.max;
+        Ok(self
+            .hot
+            .query_within_bbox_3d(
+                namespace,
+                min_x,
+                min_y,
+                min_z,
+                max_x,
+                max_y,
+                max_z,
+                usize::MAX,
+            )
+            .into_iter()
+            .filter(|loc| crate::compute::query::matches(predicate, &loc.position, &loc.metadata))
+            .take(limit)
+            .collect())
+    }
+
+    /// Calculate distance between two objects
+    pub fn distance_between(
+        &self,
+        namespace: &str,
+        id1: &str,
+        id2: &str,
+        metric: crate::compute::spatial::DistanceMetric,
+    ) -> Result<Option<f64>> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::Distance)?;
+        Ok(self.hot.distance_between(namespace, id1, id2, metric))
+    }
+
+    /// Calculate distance from object to point
+    pub fn distance_to(
+        &self,
+        namespace: &str,
+        id: &str,
+        point: &spatio_types::geo::Point,
+        metric: crate::compute::spatial::DistanceMetric,
+    ) -> Result<Option<f64>> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::Distance)?;
+        validation::validate_geographic_point(point)?;
+        Ok(self.hot.distance_to(namespace, id, point, metric))
+    }
+
+    /// Compute convex hull of all objects in namespace
+    pub fn convex_hull(&self, namespace: &str) -> Result<Option<spatio_types::geo::Polygon>> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::Aggregate)?;
+        Ok(self.hot.convex_hull(namespace))
+    }
+
+    /// Aggregate current object positions into `cell_size`-degree grid cells,
+    /// releasing only cells that contain at least `k` objects.
+    ///
+    /// Intended for public dashboards: sparse cells that could single out an
+    /// individual are suppressed rather than reported with a small count.
+    pub fn k_anonymous_cells(
+        &self,
+        namespace: &str,
+        cell_size: f64,
+        k: usize,
+    ) -> Result<Vec<crate::compute::privacy::AggregateCell>> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::Aggregate)?;
+        self.hot.k_anonymous_cells(namespace, cell_size, k)
+    }
+
+    /// [`DB::query_radius`] for public outputs: objects are selected by their
+    /// true position, then each hit is moved by up to `jitter` meters under
+    /// the secret `key` (see [`CurrentLocation::jittered`]) and measured and
+    /// ordered by where it was moved, so neither the positions, distances nor
+    /// order returned give the true locations away.
+    pub fn query_radius_jittered(
+        &self,
+        namespace: &str,
+        center: &spatio_types::point::Point3d,
+        radius: f64,
+        limit: usize,
+        jitter: f64,
+        key: u128,
+    ) -> Result<Vec<NearbyHit>> {
+        if jitter != 0.0 {
+            validation::validate_radius(jitter)?;
+        }
+        let metric = self.distance_metric(namespace);
+        let origin = spatio_types::geo::Point::new(center.x(), center.y());
+        let mut hits = self
+            .query_radius_with_metric(namespace, center, radius, limit, metric)?
+            .into_iter()
+            .map(|hit| {
+                let moved = hit.location.jittered(jitter, key)?;
+                let at = spatio_types::geo::Point::new(moved.position.x(), moved.position.y());
+                let distance = crate::compute::spatial::distance_between(&origin, &at, metric);
+                Ok(NearbyHit::new(Arc::new(moved), distance))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        Ok(hits)
+    }
+
+    /// Compute bounding box of all objects in namespace
+    pub fn bounding_box(&self, namespace: &str) -> Result<Option<geo::Rect>> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::Aggregate)?;
+        Ok(self.hot.bounding_box(namespace))
+    }
+}
+
+pub use DB as Spatio;
+
+/// Reject empty pages, which could never make progress.
+fn validate_page_size(page_size: usize) -> Result<()> {
+    if page_size == 0 {
+        return Err(SpatioError::InvalidInput(
+            "page_size must be greater than 0".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Cut a page from `items`, the first `page_size + 1` results of a scan
+/// ordered by `position` resumed at `start`; the extra item only tells
+/// whether another page follows.
+fn next_page<T>(
+    mut items: Vec<T>,
+    page_size: usize,
+    start: pagination::PageToken,
+    position: impl Fn(&T) -> u64,
+) -> Page<T> {
+    if items.len() <= page_size {
+        return Page {
+            items,
+            next_token: None,
+        };
+    }
+    items.truncate(page_size);
+    let last = position(&items[page_size - 1]);
+    let mut offset = items
+        .iter()
+        .rev()
+        .take_while(|item| position(item) == last)
+        .count() as u64;
+    if last == start.position {
+        offset += start.offset;
+    }
+    Page {
+        items,
+        next_token: Some(start.at(last, offset)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spatio_types::point::Point3d;
+    use std::collections::{BTreeMap, HashMap, HashSet};
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    #[test]
+    fn test_update_and_query_location() {
+        let db = DB::memory().unwrap();
+        let namespace = "vehicles";
+        let object_id = "car1";
+        let pos1 = Point3d::new(10.0, 20.0, 0.0);
+        let metadata1 = serde_json::json!({"engine": "on"});
+
+        db.upsert(namespace, object_id, pos1.clone(), metadata1.clone(), None)
+            .unwrap();
+
+        let results = db.query_radius(namespace, &pos1, 1.0, 1).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].location.object_id, object_id);
+        assert_eq!(results[0].location.position, pos1);
+        assert_eq!(results[0].location.metadata, metadata1);
+
+        let pos2 = Point3d::new(10.1, 20.1, 0.0);
+        let metadata2 = serde_json::json!({"engine": "off"});
+        db.upsert(namespace, object_id, pos2.clone(), metadata2.clone(), None)
+            .unwrap();
+
+        let results = db.query_radius(namespace, &pos2, 1.0, 1).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].location.object_id, object_id);
+        assert_eq!(results[0].location.position, pos2);
+        assert_eq!(results[0].location.metadata, metadata2);
+    }
+
+    #[test]
+    fn test_query_near_object() {
+        let db = DB::memory().unwrap();
+        let namespace = "vehicles";
+
+        let car1_pos = Point3d::new(0.0, 0.0, 0.0);
+        db.upsert(namespace, "car1", car1_pos, serde_json::json!({}), None)
+            .unwrap();
+
+        let car2_pos = Point3d::new(0.00001, 0.0, 0.0); // ~1 meter away
+        db.upsert(namespace, "car2", car2_pos, serde_json::json!({}), None)
+            .unwrap();
+
+        let car3_pos = Point3d::new(10.0, 0.0, 0.0); // 10 units away
+        db.upsert(namespace, "car3", car3_pos, serde_json::json!({}), None)
+            .unwrap();
 
         let near_car1 = db.query_near(namespace, "car1", 1.5, 10).unwrap();
         assert_eq!(near_car1.len(), 2); // car1 and car2
-        assert!(near_car1.iter().any(|(loc, _)| loc.object_id == "car1"));
-        assert!(near_car1.iter().any(|(loc, _)| loc.object_id == "car2"));
-        assert!(!near_car1.iter().any(|(loc, _)| loc.object_id == "car3"));
+        assert!(near_car1.iter().any(|hit| hit.location.object_id == "car1"));
+        assert!(near_car1.iter().any(|hit| hit.location.object_id == "car2"));
+        assert!(!near_car1.iter().any(|hit| hit.location.object_id == "car3"));
+
+        let near_car1_limit_1 = db.query_near(namespace, "car1", 1.5, 1).unwrap();
+        assert_eq!(near_car1_limit_1.len(), 1);
+    }
+
+    #[test]
+    fn test_query_trajectory() {
+        let db = DB::memory().unwrap();
+        let namespace = "planes";
+        let object_id = "plane1";
+
+        let start_time = SystemTime::now();
+        sleep(Duration::from_millis(10));
+        db.upsert(
+            namespace,
+            object_id,
+            Point3d::new(0.0, 0.0, 0.0),
+            serde_json::json!({"status": "takeoff"}),
+            None,
+        )
+        .unwrap();
+        sleep(Duration::from_millis(10));
+        db.upsert(
+            namespace,
+            object_id,
+            Point3d::new(10.0, 10.0, 1000.0),
+            serde_json::json!({"status": "climb"}),
+            None,
+        )
+        .unwrap();
+        sleep(Duration::from_millis(10));
+        db.upsert(
+            namespace,
+            object_id,
+            Point3d::new(20.0, 20.0, 2000.0),
+            serde_json::json!({"status": "cruise"}),
+            None,
+        )
+        .unwrap();
+        sleep(Duration::from_millis(10));
+        let end_time = SystemTime::now();
+
+        let trajectory = db
+            .query_trajectory(namespace, object_id, start_time..=end_time, 10)
+            .unwrap();
+        assert_eq!(trajectory.len(), 3);
+        // Results are newest first
+        assert_eq!(trajectory[0].position, Point3d::new(20.0, 20.0, 2000.0));
+        assert_eq!(trajectory[1].position, Point3d::new(10.0, 10.0, 1000.0));
+        assert_eq!(trajectory[2].position, Point3d::new(0.0, 0.0, 0.0));
+
+        // Test limit
+        let limited_trajectory = db
+            .query_trajectory(namespace, object_id, start_time..=end_time, 2)
+            .unwrap();
+        assert_eq!(limited_trajectory.len(), 2);
+    }
+
+    #[test]
+    fn test_sequences_continue_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let position = Point3d::new(1.0, 2.0, 0.0);
+
+        {
+            let db = DB::open(&db_path).unwrap();
+            for (i, id) in ["a", "b", "c"].into_iter().enumerate() {
+                let sequence = db
+                    .upsert("ns", id, position.clone(), serde_json::json!({}), None)
+                    .unwrap();
+                assert_eq!(sequence, i as u64 + 1);
+            }
+            assert_eq!(db.delete("ns", "b").unwrap(), 4);
+            db.close().unwrap();
+        }
+
+        // Full replay on the first reopen, then from the checkpoint it writes.
+        for expected in [5, 6] {
+            let db = DB::open(&db_path).unwrap();
+            assert_eq!(db.last_sequence(), expected - 1);
+            assert_eq!(db.flush_watermark(), expected - 1);
+            let sequence = db
+                .upsert("ns", "d", position.clone(), serde_json::json!({}), None)
+                .unwrap();
+            assert_eq!(sequence, expected);
+            db.close().unwrap();
+        }
+
+        let db = DB::open(&db_path).unwrap();
+        let trajectory = [TemporalPoint::new(
+            spatio_types::geo::Point::new(3.0, 4.0),
+            SystemTime::now(),
+        )];
+        assert_eq!(db.insert_trajectory("ns", "e", &trajectory).unwrap(), 7);
+        assert_eq!(db.insert_trajectory("ns", "e", &[]).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_bbox_pages_resume_on_a_copy_of_the_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let (original, copy) = (dir.path().join("a"), dir.path().join("b"));
+        let write = |db: &DB, id: &str, position: Point3d| {
+            db.upsert("ns", id, position, serde_json::json!({}), None)
+                .unwrap();
+        };
+        {
+            let db = DB::open(original.join("db")).unwrap();
+            for i in 0..50 {
+                // Include points on the box edges and shared cell boundaries.
+                let position = Point3d::new((i % 10) as f64 * 0.5, (i / 10) as f64 * 1.25, 0.0);
+                write(&db, &format!("o{i}"), position);
+            }
+            db.close().unwrap();
+        }
+        std::fs::create_dir(&copy).unwrap();
+        for file in std::fs::read_dir(&original).unwrap() {
+            let file = file.unwrap().path();
+            std::fs::copy(&file, copy.join(file.file_name().unwrap())).unwrap();
+        }
+        let (primary, lagging) = (
+            DB::open(original.join("db")).unwrap(),
+            DB::open(copy.join("db")).unwrap(),
+        );
+        assert_eq!(primary.log_id(), lagging.log_id());
+        // Outside the box, so only the sequence moves on.
+        write(&primary, "far", Point3d::new(100.0, 0.0, 0.0));
+        let scan = |db: &DB, token: Option<&str>| {
+            db.query_bbox_page("ns", 0.0, 0.0, 4.5, 5.0, 7, token)
+                .unwrap()
+        };
+
+        // Alternate between the two databases, starting on the one behind.
+        let mut seen = HashSet::new();
+        let mut token = None;
+        for page_index in 0.. {
+            let db = if page_index % 2 == 0 {
+                &lagging
+            } else {
+                &primary
+            };
+            let page = scan(db, token.as_deref());
+            assert!(page.items.len() <= 7);
+            for loc in &page.items {
+                assert!(
+                    seen.insert(loc.object_id.clone()),
+                    "{} repeated",
+                    loc.object_id
+                );
+            }
+            match page.next_token {
+                Some(next) => token = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(seen.len(), 50);
+
+        // Tokens only resume their own query, on the same log once it has
+        // caught up.
+        let first = scan(&primary, None).next_token.unwrap();
+        assert!(matches!(
+            primary.query_bbox_page("ns", 0.0, 0.0, 4.0, 5.0, 7, Some(&first)),
+            Err(SpatioError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            lagging.query_bbox_page("ns", 0.0, 0.0, 4.5, 5.0, 7, Some(&first)),
+            Err(SpatioError::StaleReplica {
+                token_sequence: 51,
+                last_sequence: 50
+            })
+        ));
+        let other = DB::memory().unwrap();
+        assert_ne!(other.log_id(), primary.log_id());
+        assert!(matches!(
+            other.query_bbox_page("ns", 0.0, 0.0, 4.5, 5.0, 7, Some(&first)),
+            Err(SpatioError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_wait_applied_until_the_write_is_applied() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let db = Arc::new(DB::open(&path).unwrap());
+        let log_id = db.log_id();
+        let write = |db: &DB, id: &str| {
+            db.upsert(
+                "ns",
+                id,
+                Point3d::new(1.0, 2.0, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap()
+        };
+        let token = write(&db, "a") + 1;
+
+        assert!(!db.wait_applied(token, Duration::from_millis(5)));
+        let reader = {
+            let db = db.clone();
+            std::thread::spawn(move || {
+                db.wait_applied(token, Duration::from_secs(10))
+                    && db.get("ns", "b").unwrap().is_some()
+            })
+        };
+        assert_eq!(write(&db, "b"), token);
+        assert!(reader.join().unwrap());
+
+        // Writes recovered on open count as applied, and the log keeps its ID.
+        db.close().unwrap();
+        drop(db);
+        let reopened = DB::open(&path).unwrap();
+        assert_eq!(reopened.log_id(), log_id);
+        assert!(reopened.wait_applied(token, Duration::ZERO));
+        assert!(!reopened.wait_applied(token + 1, Duration::ZERO));
+    }
+
+    #[test]
+    fn test_radius_and_trajectory_pages() {
+        let db = DB::memory().unwrap();
+        // Two objects tie at each distance, so pages split ties.
+        for i in 0..10 {
+            let x = 0.001 * f64::from(i / 2 + 1);
+            db.upsert(
+                "fleet",
+                &format!("v{i}"),
+                Point3d::new(x, 0.0, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+        }
+        let center = Point3d::new(0.0, 0.0, 0.0);
+
+        let mut seen = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let page = db
+                .query_radius_page("fleet", &center, 10_000.0, 3, token.as_deref())
+                .unwrap();
+            seen.extend(page.items.iter().map(|hit| hit.object_id().to_string()));
+            match page.next_token {
+                Some(next) => token = Some(next),
+                None => break,
+            }
+        }
+        let expected: Vec<String> = (0..10).map(|i| format!("v{i}")).collect();
+        assert_eq!(seen, expected);
+        assert!(matches!(
+            db.query_radius_page("fleet", &center, 500.0, 3, token.as_deref()),
+            Err(SpatioError::InvalidInput(_))
+        ));
+
+        let base = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let trajectory: Vec<_> = (0..7u32)
+            .map(|i| {
+                TemporalPoint::new(
+                    spatio_types::geo::Point::new(f64::from(i), 0.0),
+                    base + Duration::from_secs(u64::from(i)),
+                )
+            })
+            .collect();
+        db.insert_trajectory("fleet", "truck", &trajectory).unwrap();
+
+        let mut xs = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let page = db
+                .query_trajectory_page("fleet", "truck", .., 2, token.as_deref())
+                .unwrap();
+            assert!(page.items.len() <= 2);
+            xs.extend(page.items.iter().map(|update| update.position.x()));
+            match page.next_token {
+                Some(next) => token = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(xs, [6.0, 5.0, 4.0, 3.0, 2.0, 1.0, 0.0]);
+        let radius_token = db
+            .query_radius_page("fleet", &center, 10_000.0, 3, None)
+            .unwrap()
+            .next_token;
+        assert!(matches!(
+            db.query_trajectory_page("fleet", "truck", .., 2, radius_token.as_deref()),
+            Err(SpatioError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_delete_does_not_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+
+        // First session: insert then delete.
+        {
+            let db = DB::open(&db_path).unwrap();
+            db.upsert(
+                "ns",
+                "obj",
+                Point3d::new(1.0, 2.0, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+            db.delete("ns", "obj").unwrap();
+            db.close().unwrap();
+        }
+
+        // Second session: object must not reappear.
+        {
+            let db = DB::open(&db_path).unwrap();
+            assert!(
+                db.get("ns", "obj").unwrap().is_none(),
+                "deleted object must not reappear after restart"
+            );
+        }
+    }
+
+    #[test]
+    fn test_delete_then_reinsert_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("test2.db");
+
+        let pos2 = Point3d::new(9.0, 8.0, 0.0);
+
+        {
+            let db = DB::open(&db_path).unwrap();
+            db.upsert(
+                "ns",
+                "obj",
+                Point3d::new(1.0, 2.0, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+            db.delete("ns", "obj").unwrap();
+            sleep(Duration::from_millis(1)); // ensure re-insert timestamp > tombstone
+            db.upsert("ns", "obj", pos2.clone(), serde_json::json!({"v": 2}), None)
+                .unwrap();
+            db.close().unwrap();
+        }
+
+        {
+            let db = DB::open(&db_path).unwrap();
+            let loc = db
+                .get("ns", "obj")
+                .unwrap()
+                .expect("re-inserted object must survive restart");
+            assert_eq!(loc.position, pos2);
+        }
+    }
+
+    #[test]
+    fn test_memory_db_serves_trajectory_history_in_memory() {
+        // A :memory: DB must not touch the filesystem yet still answer
+        // trajectory queries (history kept in the in-memory log) beyond the
+        // recent buffer window.
+        let db = DB::memory().unwrap();
+
+        let t0 = SystemTime::now();
+        for i in 0..5u64 {
+            db.upsert(
+                "ns",
+                "obj",
+                Point3d::new(i as f64, i as f64, 0.0),
+                serde_json::json!({ "i": i }),
+                Some(SetOptions::with_timestamp(t0 + Duration::from_millis(i))),
+            )
+            .unwrap();
+        }
+
+        // Current position reflects the latest update.
+        let current = db.get("ns", "obj").unwrap().unwrap();
+        assert_eq!(current.position.x(), 4.0);
+
+        // Full trajectory is queryable from the in-memory log. Use a window that
+        // safely brackets all records: stored timestamps are truncated to micros,
+        // so a raw-now() lower bound could exclude the boundary record.
+        let traj = db
+            .query_trajectory(
+                "ns",
+                "obj",
+                t0 - Duration::from_secs(1)..=t0 + Duration::from_secs(1),
+                10,
+            )
+            .unwrap();
+        assert_eq!(traj.len(), 5, "all in-memory history must be queryable");
+    }
+
+    #[test]
+    fn test_database_closed_operations() {
+        let db = DB::memory().unwrap();
+        db.close().unwrap();
+
+        let namespace = "test";
+        let object_id = "obj1";
+        let pos = Point3d::new(0.0, 0.0, 0.0);
+        let metadata = serde_json::json!({"data": "data"});
+
+        assert!(
+            db.upsert(namespace, object_id, pos.clone(), metadata, None)
+                .is_err()
+        );
+        assert!(db.query_radius(namespace, &pos, 1.0, 1).is_err());
+        assert!(db.query_near(namespace, object_id, 1.0, 1).is_err());
+        assert!(db.query_trajectory(namespace, object_id, .., 1).is_err());
+    }
+
+    #[test]
+    fn test_metadata_with_pipe_survives_reopen() {
+        // A '|' inside metadata must not corrupt the log record: the value has
+        // to survive a full close/reopen recovery cycle on a file-backed DB.
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("pipe.db");
+
+        {
+            let db = DB::open(&db_path).unwrap();
+            db.upsert(
+                "ns",
+                "obj",
+                Point3d::new(1.0, 2.0, 0.0),
+                serde_json::json!({"note": "a|b|c", "n": 1}),
+                None,
+            )
+            .unwrap();
+            db.close().unwrap();
+        }
+        {
+            let db = DB::open(&db_path).unwrap();
+            let loc = db
+                .get("ns", "obj")
+                .unwrap()
+                .expect("record with '|' in metadata must survive reopen");
+            assert_eq!(loc.metadata, serde_json::json!({"note": "a|b|c", "n": 1}));
+        }
+    }
+
+    #[test]
+    fn test_zones_survive_reopen() {
+        use crate::config::BoundingBox2D;
+        use spatio_types::geo::Polygon;
+
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("zones.db");
+        let depot = ZoneGeometry::Polygon(Polygon::from_coords(
+            &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.0, 0.0)],
+            vec![],
+        ));
+
+        {
+            let db = DB::open(&db_path).unwrap();
+            db.insert_zone(
+                "zones",
+                "depot",
+                depot.clone(),
+                serde_json::json!({"speed_limit": 5.0}),
+            )
+            .unwrap();
+            db.insert_zone(
+                "zones",
+                "yard",
+                ZoneGeometry::BBox(BoundingBox2D::new(2.0, 2.0, 3.0, 3.0)),
+                serde_json::json!({}),
+            )
+            .unwrap();
+            db.delete_zone("zones", "yard").unwrap();
+            db.close().unwrap();
+        }
+
+        let db = DB::open(&db_path).unwrap();
+        let zone = db.get_zone("zones", "depot").unwrap().expect("zone saved");
+        assert_eq!(zone.geometry, depot);
+        assert_eq!(zone.speed_limit(), Some(5.0));
+        assert!(db.get_zone("zones", "yard").unwrap().is_none());
+        let inside = spatio_types::geo::Point::new(0.5, 0.5);
+        assert_eq!(db.zones_containing("zones", &inside, 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_corrupt_index_snapshot_is_rebuilt() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("idx.db");
+        let idx_path = dir.path().join("idx.db.idx");
+
+        {
+            let db = DB::open(&db_path).unwrap();
+            for i in 0..50 {
+                let point = Point3d::new(i as f64 * 0.01, 0.0, 0.0);
+                db.upsert("ns", &format!("o{i}"), point, serde_json::json!({}), None)
+                    .unwrap();
+            }
+            db.close().unwrap();
+        }
+        // Reopening saves the indexes holding the recovered points.
+        DB::open(&db_path).unwrap().close().unwrap();
+        let mut saved = std::fs::read(&idx_path).unwrap();
+        let middle = saved.len() / 2;
+        saved[middle] ^= 0x40;
+        std::fs::write(&idx_path, &saved).unwrap();
+
+        let db = DB::open(&db_path).unwrap();
+        let found = db.query_bbox("ns", -1.0, -1.0, 1.0, 1.0, 100).unwrap();
+        assert_eq!(found.len(), 50);
+        assert!(
+            db.cold
+                .read_index_snapshot(HotState::load_point_indexes)
+                .is_some()
+        );
+        assert_ne!(std::fs::read(&idx_path).unwrap(), saved);
+    }
+
+    #[test]
+    fn test_indexes_are_saved_at_checkpoint_and_close() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = DB::open(dir.path().join("idx.db")).unwrap();
+        let place = |x: f64| {
+            for i in 0..20 {
+                let point = Point3d::new(x + i as f64 * 0.01, 0.0, 0.0);
+                db.upsert("ns", &format!("o{i}"), point, serde_json::json!({}), None)
+                    .unwrap();
+            }
+        };
+        // Entries the saved indexes get wrong for the current locations.
+        let stale_entries = |db: &DB| {
+            let images = db
+                .cold
+                .read_index_snapshot(HotState::load_point_indexes)
+                .unwrap();
+            let locations = (0..20)
+                .map(|i| {
+                    let loc = db.hot.get_current_location("ns", &format!("o{i}")).unwrap();
+                    let update = LocationUpdate {
+                        timestamp: loc.timestamp,
+                        position: loc.position.clone(),
+                        metadata: loc.metadata.clone(),
+                    };
+                    (format!("ns::o{i}"), update)
+                })
+                .collect();
+            HotState::new().restore(locations, images, &mut |_, _| {})
+        };
+
+        place(0.0);
+        db.checkpoint(CheckpointKind::Full).unwrap();
+        assert_eq!(stale_entries(&db), 0);
+        place(1.0);
+        assert_eq!(stale_entries(&db), 20);
+        db.close().unwrap();
+        assert_eq!(stale_entries(&db), 0);
+    }
+
+    #[test]
+    fn test_checkpoint_preserves_history_and_writes_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("traj.db");
+        let snap_path = dir.path().join("traj.db.snap");
+
+        let t1 = SystemTime::now();
+        let t2 = t1 + Duration::from_millis(5);
+
+        {
+            let db = DB::open(&db_path).unwrap();
+            db.upsert(
+                "ns",
+                "a",
+                Point3d::new(1.0, 1.0, 0.0),
+                serde_json::json!({"s": 1}),
+                Some(SetOptions::with_timestamp(t1)),
+            )
+            .unwrap();
+            db.upsert(
+                "ns",
+                "a",
+                Point3d::new(2.0, 2.0, 0.0),
+                serde_json::json!({"s": 2}),
+                Some(SetOptions::with_timestamp(t2)),
+            )
+            .unwrap();
+            db.upsert(
+                "ns",
+                "b",
+                Point3d::new(9.0, 9.0, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+            db.close().unwrap();
+        }
+        {
+            let db = DB::open(&db_path).unwrap();
+            // Current state recovered correctly.
+            assert_eq!(db.get("ns", "a").unwrap().unwrap().position.x(), 2.0);
+            assert!(db.get("ns", "b").unwrap().is_some());
+            // Trajectory history is NOT discarded by the checkpoint. Bracket the
+            // window generously: stored timestamps are micro-truncated, so a raw
+            // lower bound could exclude the first record.
+            let traj = db
+                .query_trajectory(
+                    "ns",
+                    "a",
+                    t1 - Duration::from_secs(1)..=t2 + Duration::from_secs(1),
+                    10,
+                )
+                .unwrap();
+            assert_eq!(
+                traj.len(),
+                2,
+                "checkpoint must preserve full trajectory history"
+            );
+        }
+        // A checkpoint snapshot was written beside the log.
+        assert!(snap_path.exists(), "checkpoint snapshot should exist");
+    }
+
+    #[test]
+    fn test_corrupt_snapshot_falls_back_to_full_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("traj.db");
+        let snap_path = dir.path().join("traj.db.snap");
+
+        {
+            let db = DB::open(&db_path).unwrap();
+            db.upsert(
+                "ns",
+                "a",
+                Point3d::new(1.0, 1.0, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+            db.upsert(
+                "ns",
+                "a",
+                Point3d::new(2.0, 2.0, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+            db.close().unwrap();
+        }
+        // Open once more so the snapshot covers the records, then corrupt it.
+        {
+            let db = DB::open(&db_path).unwrap();
+            db.close().unwrap();
+        }
+        // Valid header, but a record with a bad CRC -> snapshot must be rejected.
+        std::fs::write(&snap_path, "#spatio-snap v1 0\n00000000|garbage-record\n").unwrap();
+
+        let db = DB::open(&db_path).unwrap();
+        let loc = db
+            .get("ns", "a")
+            .unwrap()
+            .expect("state must still recover via full log replay");
+        assert_eq!(loc.position.x(), 2.0);
+    }
+
+    #[test]
+    fn test_incremental_checkpoint_recovers_changes_and_deletions() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("traj.db");
+        let snap_path = dir.path().join("traj.db.snap");
+        let delta_path = dir.path().join("traj.db.snap.delta");
+        let upsert = |db: &DB, id: &str, x: f64| {
+            db.upsert(
+                "ns",
+                id,
+                Point3d::new(x, 0.0, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+        };
+        let open = || {
+            let steps = Arc::new(parking_lot::Mutex::new(Vec::new()));
+            let db = {
+                let steps = steps.clone();
+                crate::DBBuilder::new()
+                    .path(&db_path)
+                    .on_progress(move |step| steps.lock().push(step.clone()))
+                    .build()
+                    .unwrap()
+            };
+            let steps = std::mem::take(&mut *steps.lock());
+            (db, steps)
+        };
+
+        let db = DB::open(&db_path).unwrap();
+        upsert(&db, "a", 1.0);
+        upsert(&db, "b", 2.0);
+        // No checksummed snapshot to base a delta on yet.
+        std::fs::remove_file(&snap_path).unwrap();
+        let report = db.checkpoint(CheckpointKind::Incremental).unwrap();
+        assert_eq!(report.kind, CheckpointKind::Full);
+        assert_eq!(report.objects, 2);
+        assert_eq!(report.sequence, 2);
+
+        upsert(&db, "a", 3.0);
+        upsert(&db, "c", 4.0);
+        db.delete("ns", "b").unwrap();
+        let report = db.checkpoint(CheckpointKind::Incremental).unwrap();
+        assert_eq!(report.kind, CheckpointKind::Incremental);
+        assert_eq!(report.objects, 3);
+        assert_eq!(report.sequence, 5);
+        assert!(delta_path.exists());
+        drop(db);
+
+        // Recovery loads the snapshot and delta and replays nothing.
+        let full = std::fs::read(&snap_path).unwrap();
+        let delta = std::fs::read(&delta_path).unwrap();
+        let (db, steps) = open();
+        assert!(steps.contains(&OpenProgress::SnapshotLoaded { objects: 2 }));
+        assert!(steps.contains(&OpenProgress::ReplayingLog { read: 0, total: 0 }));
+        assert_eq!(db.get("ns", "a").unwrap().unwrap().position.x(), 3.0);
+        assert!(db.get("ns", "b").unwrap().is_none());
+        assert!(db.get("ns", "c").unwrap().is_some());
+        assert_eq!(db.last_sequence(), 5);
+        // The snapshot written on open covers the delta.
+        assert!(!delta_path.exists());
+        drop(db);
+
+        // A delta that fails its checksum is ignored, and the log after the
+        // snapshot replayed instead.
+        let mut corrupt = delta.clone();
+        let at = corrupt.iter().rposition(|&b| b == b'|').unwrap() - 1;
+        corrupt[at] ^= 1;
+        std::fs::write(&snap_path, &full).unwrap();
+        std::fs::write(&delta_path, &corrupt).unwrap();
+        let (db, steps) = open();
+        assert!(steps.iter().any(|step| matches!(
+            step,
+            OpenProgress::ReplayingLog { total, .. } if total > &0
+        )));
+        assert!(db.get("ns", "b").unwrap().is_none());
+        db.close().unwrap();
+
+        // As is a snapshot missing a record.
+        let content = std::fs::read_to_string(&snap_path).unwrap();
+        let mut lines: Vec<&str> = content.lines().collect();
+        lines.remove(1);
+        std::fs::write(&snap_path, lines.join("\n") + "\n").unwrap();
+        let (db, steps) = open();
+        assert!(
+            !steps
+                .iter()
+                .any(|step| matches!(step, OpenProgress::SnapshotLoaded { .. }))
+        );
+        assert_eq!(db.get("ns", "a").unwrap().unwrap().position.x(), 3.0);
+
+        assert!(matches!(
+            DB::memory().unwrap().checkpoint(CheckpointKind::Full),
+            Err(SpatioError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_recovery_after_torn_final_write() {
+        // Simulate a crash mid-append: truncate the log inside the last record.
+        // Recovery must skip the torn record (CRC) and return the valid prefix
+        // without error.
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("torn.db");
+        let t0 = SystemTime::now();
+
+        {
+            let db = DB::open(&db_path).unwrap();
+            for i in 0..3u64 {
+                db.upsert(
+                    "ns",
+                    "a",
+                    Point3d::new(i as f64, 0.0, 0.0),
+                    serde_json::json!({ "i": i }),
+                    Some(SetOptions::with_timestamp(t0 + Duration::from_millis(i))),
+                )
+                .unwrap();
+            }
+            db.close().unwrap();
+        }
+
+        // Lop off the tail of the last record (leave earlier records intact).
+        let len = std::fs::metadata(&db_path).unwrap().len();
+        let f = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&db_path)
+            .unwrap();
+        f.set_len(len - 4).unwrap();
+        drop(f);
+
+        let db = DB::open(&db_path).unwrap();
+        let loc = db
+            .get("ns", "a")
+            .unwrap()
+            .expect("a complete earlier record must still recover");
+        // The torn last record (i=2) is dropped; the last intact one is i=1.
+        assert_eq!(loc.position.x(), 1.0);
+    }
+
+    #[test]
+    fn test_concurrent_writes_same_object_converge() {
+        use std::sync::Arc;
+        use std::thread;
+
+        // Many threads hammer the same object with increasing timestamps while
+        // readers query concurrently. No panic; final value is the latest write.
+        let db = Arc::new(DB::memory().unwrap());
+        let base = SystemTime::now();
+        let writers = 8u64;
+        let per = 200u64;
+
+        let mut handles = Vec::new();
+        for w in 0..writers {
+            let db = Arc::clone(&db);
+            handles.push(thread::spawn(move || {
+                for i in 0..per {
+                    let ms = w * per + i; // globally unique, increasing timestamp
+                    // Position stays a valid coordinate; ordering is by timestamp.
+                    let _ = db.upsert(
+                        "ns",
+                        "hot",
+                        Point3d::new(1.0, 2.0, 0.0),
+                        serde_json::json!({ "ms": ms }),
+                        Some(SetOptions::with_timestamp(base + Duration::from_millis(ms))),
+                    );
+                }
+            }));
+        }
+        // Concurrent readers — must never panic or deadlock.
+        for _ in 0..2 {
+            let db = Arc::clone(&db);
+            handles.push(thread::spawn(move || {
+                for _ in 0..per {
+                    let _ = db.get("ns", "hot");
+                    let _ = db.query_radius("ns", &Point3d::new(0.0, 0.0, 0.0), 1.0e6, 10);
+                }
+            }));
+        }
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        // Last-writer-wins by timestamp: the highest-timestamp write survives.
+        let max_ms = writers * per - 1;
+        let loc = db.get("ns", "hot").unwrap().unwrap();
+        assert_eq!(loc.timestamp, base + Duration::from_millis(max_ms));
+        assert_eq!(loc.metadata, serde_json::json!({ "ms": max_ms }));
+    }
+
+    #[test]
+    fn test_invalid_coordinates_are_rejected() {
+        let db = DB::memory().unwrap();
+        let meta = serde_json::json!({});
+
+        // NaN / Inf / out-of-range coordinates must never reach the index.
+        for bad in [
+            Point3d::new(f64::NAN, 0.0, 0.0),
+            Point3d::new(0.0, f64::INFINITY, 0.0),
+            Point3d::new(200.0, 0.0, 0.0), // lon > 180
+            Point3d::new(0.0, 95.0, 0.0),  // lat > 90
+            Point3d::new(0.0, 0.0, 1.0e9), // absurd altitude
+        ] {
+            assert!(
+                db.upsert("ns", "o", bad, meta.clone(), None).is_err(),
+                "invalid coordinate must be rejected on upsert"
+            );
+        }
+        // A valid point still works, and the bad ones left no trace.
+        assert!(
+            db.upsert("ns", "o", Point3d::new(1.0, 2.0, 0.0), meta, None)
+                .is_ok()
+        );
+        assert_eq!(db.stats().hot_state_objects, 1);
+    }
+
+    #[test]
+    fn test_upsert_batch_is_all_or_nothing() {
+        let db = DB::memory().unwrap();
+        let item = |id: &str, x: f64| {
+            (
+                id.to_string(),
+                Point3d::new(x, 0.0, 0.0),
+                serde_json::json!({}),
+            )
+        };
+
+        let err = db
+            .upsert_batch("fleet", vec![item("a", 1.0), item("b", 200.0)])
+            .unwrap_err();
+        assert!(err.to_string().contains("batch item 1"), "{err}");
+        assert!(
+            db.upsert_batch("fleet", vec![item("a", 1.0), item("", 2.0)])
+                .is_err()
+        );
+        assert_eq!(db.stats().hot_state_objects, 0);
+        assert_eq!(db.last_sequence(), 0);
+
+        let sequence = db
+            .upsert_batch(
+                "fleet",
+                vec![item("a", 1.0), item("b", 2.0), item("a", 3.0)],
+            )
+            .unwrap();
+        assert_eq!(sequence, 3);
+        assert_eq!(db.get("fleet", "a").unwrap().unwrap().position.x(), 3.0);
+        assert_eq!(db.get("fleet", "b").unwrap().unwrap().position.x(), 2.0);
+        assert_eq!(db.upsert_batch("fleet", Vec::new()).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_torn_batch_is_recovered_as_a_unit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fleet.log");
+        let item = |id: &str| {
+            (
+                id.to_string(),
+                Point3d::new(1.0, 2.0, 0.0),
+                serde_json::json!({}),
+            )
+        };
+        let db = DB::open(&path).unwrap();
+        db.upsert(
+            "fleet",
+            "z",
+            Point3d::new(0.0, 0.0, 0.0),
+            serde_json::json!({}),
+            None,
+        )
+        .unwrap();
+        let batch = vec![item("a"), item("b"), item("c")];
+        assert_eq!(db.upsert_batch("fleet", batch).unwrap(), 4);
+        db.close().unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let start = text.find("|~p").unwrap();
+        let end = start + text[start..].find('\n').unwrap();
+        let db = DB::open(&path).unwrap();
+        assert_eq!(db.stats().hot_state_objects, 4);
+        db.close().unwrap();
+
+        // A crash mid-append tears the batch's line: none of it comes back.
+        std::fs::remove_file(dir.path().join("fleet.log.snap")).unwrap();
+        std::fs::write(&path, &text[..(start + end) / 2]).unwrap();
+        let db = DB::open(&path).unwrap();
+        assert!(db.get("fleet", "z").unwrap().is_some());
+        assert_eq!(db.stats().hot_state_objects, 1);
+    }
+
+    #[test]
+    fn test_invalid_query_inputs_are_rejected() {
+        let db = DB::memory().unwrap();
+        let c = Point3d::new(0.0, 0.0, 0.0);
+
+        assert!(
+            db.query_radius("ns", &c, 0.0, 10).is_err(),
+            "radius 0 rejected"
+        );
+        assert!(
+            db.query_radius("ns", &c, -5.0, 10).is_err(),
+            "negative radius rejected"
+        );
+        assert!(
+            db.query_radius("ns", &Point3d::new(f64::NAN, 0.0, 0.0), 1.0, 10)
+                .is_err()
+        );
+        assert!(
+            db.query_bbox("ns", 10.0, 0.0, 5.0, 10.0, 10).is_err(),
+            "min>=max rejected"
+        );
+        assert!(
+            db.knn("ns", &Point3d::new(0.0, 200.0, 0.0), 5).is_err(),
+            "bad center rejected"
+        );
+    }
+
+    #[test]
+    fn test_coordinate_precision_snaps_and_dedups() {
+        let db = DB::memory_with_config(Config::default().with_coordinate_precision(4)).unwrap();
+        let t0 = SystemTime::now();
+        let meta = serde_json::json!({"s": 1});
+
+        for (i, (x, y)) in [
+            (10.000_01, 20.000_04),
+            (9.999_98, 19.999_97),
+            (10.000_03, 20.0),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            db.upsert(
+                "ns",
+                "gps",
+                Point3d::new(x, y, 3.0),
+                meta.clone(),
+                Some(SetOptions::with_timestamp(
+                    t0 + Duration::from_millis(i as u64),
+                )),
+            )
+            .unwrap();
+        }
+
+        let loc = db.get("ns", "gps").unwrap().unwrap();
+        assert_eq!(loc.position, Point3d::new(10.0, 20.0, 3.0));
+        // The dropped re-reports leave the stored (and logged) timestamp.
+        assert_eq!(loc.timestamp, t0);
+        assert_eq!(db.last_sequence(), 1);
+
+        // Jitter collapsed into a single trajectory point.
+        let window = (t0 - Duration::from_secs(1), t0 + Duration::from_secs(1));
+        let traj = db
+            .query_trajectory("ns", "gps", window.0..=window.1, 10)
+            .unwrap();
+        assert_eq!(traj.len(), 1);
+
+        // A metadata change is recorded.
+        db.upsert(
+            "ns",
+            "gps",
+            Point3d::new(10.0, 20.0, 3.0),
+            serde_json::json!({"s": 2}),
+            Some(SetOptions::with_timestamp(t0 + Duration::from_millis(3))),
+        )
+        .unwrap();
+
+        // A real move is recorded too.
+        db.upsert(
+            "ns",
+            "gps",
+            Point3d::new(10.001, 20.0, 3.0),
+            meta,
+            Some(SetOptions::with_timestamp(t0 + Duration::from_millis(4))),
+        )
+        .unwrap();
+        let traj = db
+            .query_trajectory("ns", "gps", window.0..=window.1, 10)
+            .unwrap();
+        assert_eq!(traj.len(), 3);
+    }
+
+    #[test]
+    fn test_anonymized_outputs() {
+        let db = DB::memory().unwrap();
+        for i in 0..4 {
+            db.upsert(
+                "riders",
+                &format!("r{i}"),
+                Point3d::new(2.31 + i as f64 * 0.01, 48.85, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+        }
+        db.upsert(
+            "riders",
+            "loner",
+            Point3d::new(-0.12, 51.5, 0.0),
+            serde_json::json!({}),
+            None,
+        )
+        .unwrap();
+
+        let cells = db.k_anonymous_cells("riders", 0.5, 3).unwrap();
+        assert_eq!(cells.len(), 1);
+        assert_eq!(cells[0].count, 4);
+
+        let loc = db.get("riders", "r0").unwrap().unwrap();
+        let j1 = loc.jittered(100.0, 1).unwrap();
+        let j2 = loc.jittered(100.0, 1).unwrap();
+        assert_eq!(j1.position, j2.position);
+        assert_ne!(j1.position, loc.position);
+        assert_eq!(j1.object_id, "r0");
+        assert!(loc.jittered(f64::NAN, 1).is_err());
+    }
+
+    #[test]
+    fn test_query_radius_jittered_hides_true_positions() {
+        let db = DB::memory().unwrap();
+        let center = Point3d::new(-0.1, 51.5, 0.0);
+        for i in 0..5 {
+            let position = Point3d::new(-0.1 + i as f64 * 0.001, 51.5, 0.0);
+            db.upsert(
+                "riders",
+                &format!("r{i}"),
+                position,
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+        }
+        let exact = db.query_radius("riders", &center, 1_000.0, 10).unwrap();
+        let jittered = db
+            .query_radius_jittered("riders", &center, 1_000.0, 10, 200.0, 7)
+            .unwrap();
+        assert_eq!(jittered.len(), exact.len());
+        assert!(jittered.windows(2).all(|w| w[0].distance <= w[1].distance));
+        for hit in &jittered {
+            let true_location = db.get("riders", &hit.location.object_id).unwrap().unwrap();
+            let expected = true_location.jittered(200.0, 7).unwrap();
+            assert_eq!(hit.location.position, expected.position);
+            assert_ne!(hit.location.position, true_location.position);
+        }
+        assert_eq!(
+            db.query_radius_jittered("riders", &center, 1_000.0, 10, 200.0, 7)
+                .unwrap()[0]
+                .location
+                .position,
+            jittered[0].location.position
+        );
+        assert!(
+            db.query_radius_jittered("riders", &center, 1_000.0, 10, -1.0, 7)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_access_log_records_sampled_queries() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("access.log");
+        let config = Config::default()
+            .with_access_log(crate::config::AccessLogConfig::new(&log_path).with_sample_rate(0.5));
+        let db = DB::memory_with_config(config).unwrap();
+
+        let center = Point3d::new(1.0, 2.0, 0.0);
+        for _ in 0..4 {
+            db.query_radius("ns", &center, 100.0, 10).unwrap();
+        }
+        db.knn("ns", &center, 3).unwrap();
+        db.knn("ns", &center, 3).unwrap();
+        // Rejected queries are not logged.
+        assert!(db.query_radius("ns", &center, -1.0, 10).is_err());
+        db.close().unwrap();
+
+        let content = std::fs::read_to_string(&log_path).unwrap();
+        let entries: Vec<AccessLogEntry> = content
+            .lines()
+            .map(|l| AccessLogEntry::parse(l).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries[0].query,
+            AccessQuery::Radius {
+                namespace: "ns".to_string(),
+                center: [1.0, 2.0, 0.0],
+                radius: 100.0,
+                limit: 10,
+                metric: crate::compute::spatial::DistanceMetric::Haversine,
+            }
+        );
+        assert!(matches!(entries[2].query, AccessQuery::Knn { k: 3, .. }));
+    }
+
+    #[test]
+    fn test_slow_queries_record_index_candidates() {
+        let config = Config::default().with_slow_query_threshold(std::time::Duration::ZERO);
+        let db = DB::memory_with_config(config).unwrap();
+        for i in 0..10 {
+            let pos = Point3d::new(i as f64 * 0.001, 0.0, 0.0);
+            db.upsert("ns", &format!("o{i}"), pos, serde_json::json!({}), None)
+                .unwrap();
+        }
+
+        db.query_bbox("ns", -1.0, -1.0, 1.0, 1.0, 100).unwrap();
+        db.knn("ns", &Point3d::new(0.0, 0.0, 0.0), 3).unwrap();
+        db.get("ns", "o1").unwrap();
+
+        let slow = db.slow_queries();
+        assert_eq!(slow.len(), 2);
+        assert_eq!(slow[0].operation, Operation::QueryBbox);
+        assert_eq!(slow[0].namespace, "ns");
+        assert_eq!(slow[0].candidates, 10);
+        assert_eq!(slow[1].operation, Operation::Knn);
+        assert!(slow[1].candidates >= 3);
+
+        // Untimed by default.
+        let db = DB::memory().unwrap();
+        db.query_bbox("ns", -1.0, -1.0, 1.0, 1.0, 100).unwrap();
+        assert!(db.slow_queries().is_empty());
+    }
+
+    #[test]
+    fn test_rejection_log_records_invalid_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("rejections.log");
+        let config = Config::default().with_rejection_log(
+            crate::config::RejectionLogConfig::new(&log_path).with_sample_bytes(16),
+        );
+        let db = DB::memory_with_config(config).unwrap();
+
+        db.upsert(
+            "fleet",
+            "ok",
+            Point3d::new(1.0, 2.0, 0.0),
+            serde_json::json!({}),
+            None,
+        )
+        .unwrap();
+        let bad = Point3d::new(1.0, 95.0, 0.0);
+        let meta = serde_json::json!({"fw": "1.2"});
+        assert!(
+            db.upsert("fleet", "tracker", bad.clone(), meta.clone(), None)
+                .is_err()
+        );
+        assert!(
+            db.upsert_batch("fleet", vec![("tracker".into(), bad, meta)])
+                .is_err()
+        );
+        db.close().unwrap();
+
+        let content = std::fs::read_to_string(&log_path).unwrap();
+        let entries: Vec<RejectionEntry> = content
+            .lines()
+            .map(|l| RejectionEntry::parse(l).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].namespace, "fleet");
+        assert_eq!(entries[0].object_id, "tracker");
+        assert_eq!(entries[0].reason, "out_of_range");
+        assert!(entries[0].detail.contains("latitude"));
+        assert_eq!(entries[0].sample.as_deref().map(str::len), Some(16));
+        // The same payload has the same digest.
+        assert_eq!(entries[0].digest, entries[1].digest);
+    }
+
+    #[test]
+    fn test_history_buffers_are_opt_in() {
+        let config = Config::default().with_history_buffer_namespace("fleet");
+        let db = DB::memory_with_config(config).unwrap();
+        let pos = Point3d::new(1.0, 2.0, 0.0);
+
+        db.upsert("fleet", "truck", pos.clone(), serde_json::json!({}), None)
+            .unwrap();
+        db.upsert("logs", "a", pos.clone(), serde_json::json!({}), None)
+            .unwrap();
+        assert_eq!(db.cold.stats().0, 1);
+
+        let opts = SetOptions::default().track_history();
+        db.upsert("logs", "b", pos, serde_json::json!({}), Some(opts))
+            .unwrap();
+        assert_eq!(db.cold.stats().0, 2);
+        let history = db.query_trajectory("logs", "a", .., 10).unwrap();
+        assert_eq!(history.len(), 1);
+    }
+
+    #[test]
+    fn test_write_optimized_namespace_queries_see_buffered_points() {
+        let config = Config::default().with_write_optimized_namespace("ingest", 8);
+        let db = DB::open_with_config(":memory:", config).unwrap();
+
+        for i in 0..20 {
+            let point = Point3d::new(-74.0 + i as f64 * 0.001, 40.7, 0.0);
+            db.upsert(
+                "ingest",
+                &format!("v{}", i),
+                point,
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+        }
+        // Move one object: its old entry may live in the tree or the buffer.
+        db.upsert(
+            "ingest",
+            "v0",
+            Point3d::new(-73.9, 40.7, 0.0),
+            serde_json::json!({}),
+            None,
+        )
+        .unwrap();
+
+        let center = Point3d::new(-74.0, 40.7, 0.0);
+        let nearby = db.query_radius("ingest", &center, 5_000.0, 100).unwrap();
+        assert_eq!(nearby.len(), 19);
+        let nearest = db.knn("ingest", &center, 3).unwrap();
+        assert_eq!(nearest[0].location.object_id, "v1");
+        let moved = db
+            .knn("ingest", &Point3d::new(-73.9, 40.7, 0.0), 1)
+            .unwrap();
+        assert_eq!(moved[0].location.object_id, "v0");
+    }
+
+    #[test]
+    fn test_knn_2d_ignores_altitude() {
+        use crate::compute::spatial::DistanceMetric;
+
+        let db = DB::memory().unwrap();
+        for (id, x, z) in [
+            ("drone", 0.001, 5_000.0),
+            ("truck", 0.002, 0.0),
+            ("far", 1.0, 0.0),
+        ] {
+            db.upsert(
+                "fleet",
+                id,
+                Point3d::new(x, 0.0, z),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+        }
+        let center = spatio_types::geo::Point::new(0.0, 0.0);
+        let ids = |found: Vec<NearbyHit>| -> Vec<String> {
+            found
+                .iter()
+                .map(|hit| hit.location.object_id.clone())
+                .collect()
+        };
+
+        let nearest = db
+            .knn_2d("fleet", &center, 2, None, DistanceMetric::Haversine)
+            .unwrap();
+        assert_eq!(ids(nearest.clone()), ["drone", "truck"]);
+        assert!((nearest[0].distance - 111.2).abs() < 1.0);
+
+        let within = db
+            .knn_2d(
+                "fleet",
+                &center,
+                10,
+                Some(1_000.0),
+                DistanceMetric::Geodesic,
+            )
+            .unwrap();
+        assert_eq!(ids(within), ["drone", "truck"]);
+        let planar = db
+            .knn_2d("fleet", &center, 10, Some(0.5), DistanceMetric::Euclidean)
+            .unwrap();
+        assert_eq!(planar.len(), 2);
+        assert!((planar[1].distance - 0.002).abs() < 1e-12);
+
+        assert!(
+            db.knn_2d("fleet", &center, 1, Some(-1.0), DistanceMetric::Haversine)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_bbox_and_polygon_results_with_distances() {
+        let db = DB::memory().unwrap();
+        for (id, x) in [("a", 1.0), ("b", 1.5)] {
+            db.upsert(
+                "fleet",
+                id,
+                Point3d::new(x, 1.0, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+        }
+
+        // Without an anchor, distances are from the center of the box.
+        let results = db
+            .query_bbox_with_distances("fleet", 0.0, 0.0, 2.0, 2.0, None, 10)
+            .unwrap();
+        let distance = |results: &[NearbyHit], id: &str| {
+            results
+                .iter()
+                .find(|hit| hit.location.object_id == id)
+                .map(|hit| hit.distance)
+                .unwrap()
+        };
+        assert!(distance(&results, "a") < 1.0);
+        assert!(distance(&results, "b") > 50_000.0);
+
+        let anchor = Point3d::new(1.5, 1.0, 0.0);
+        let polygon = spatio_types::geo::Polygon::from_coords(
+            &[(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0), (0.0, 0.0)],
+            vec![],
+        );
+        let results = db
+            .query_polygon_with_distances("fleet", &polygon, Some(&anchor), 10)
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(distance(&results, "b") < 1.0);
+        assert!(distance(&results, "a") > 50_000.0);
+    }
+
+    #[test]
+    fn test_radius_queries_follow_distance_metric() {
+        use crate::compute::spatial::{DistanceMetric, distance_between};
+
+        let config =
+            Config::default().with_namespace_distance_metric("grid", DistanceMetric::Euclidean);
+        let db = DB::memory_with_config(config).unwrap();
+        for ns in ["fleet", "grid"] {
+            for (id, x) in [("near", 0.001), ("edge", 0.009), ("far", 0.5)] {
+                db.upsert(
+                    ns,
+                    id,
+                    Point3d::new(0.0, x, 0.0),
+                    serde_json::json!({}),
+                    None,
+                )
+                .unwrap();
+            }
+        }
+        let center = Point3d::new(0.0, 0.0, 0.0);
+        let center_2d = spatio_types::geo::Point::new(0.0, 0.0);
+        let edge = spatio_types::geo::Point::new(0.0, 0.009);
+
+        // Along a meridian near the equator the ellipsoid runs shorter than
+        // the sphere, so a radius between the two distances to "edge" finds
+        // it only when measured geodesically.
+        let geodesic = distance_between(&center_2d, &edge, DistanceMetric::Geodesic);
+        let haversine = distance_between(&center_2d, &edge, DistanceMetric::Haversine);
+        assert!(geodesic < haversine);
+        let radius = (geodesic + haversine) / 2.0;
+        assert_eq!(
+            db.query_radius("fleet", &center, radius, 10).unwrap().len(),
+            1
+        );
+        let found = db
+            .query_radius_with_metric("fleet", &center, radius, 10, DistanceMetric::Geodesic)
+            .unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[1].distance, geodesic);
+
+        // Euclidean radii are in coordinate units, here by namespace default.
+        let found = db.query_radius("grid", &center, 0.01, 10).unwrap();
+        assert_eq!(found.len(), 2);
+        assert!((found[1].distance - 0.009).abs() < 1e-12);
+        let found = db
+            .query_within_cylinder("grid", center_2d, -1.0, 1.0, 0.005, 10)
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        let found = db
+            .query_within_cylinder_with_metric(
+                "grid",
+                center_2d,
+                -1.0,
+                1.0,
+                2_000.0,
+                10,
+                DistanceMetric::Haversine,
+            )
+            .unwrap();
+        assert_eq!(found.len(), 2);
+    }
+
+    #[test]
+    fn test_flush_watermark_tracks_synced_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::default().with_sync_policy(crate::config::SyncPolicy::Always);
+        let db = DB::open_with_config(dir.path().join("always.db"), config).unwrap();
+        assert_eq!(db.flush_watermark(), 0);
+
+        let position = Point3d::new(-74.0, 40.7, 0.0);
+        db.upsert("v", "a", position.clone(), serde_json::json!({}), None)
+            .unwrap();
+        db.delete("v", "a").unwrap();
+        assert_eq!(db.last_sequence(), 2);
+        assert_eq!(db.flush_watermark(), 2);
+
+        let config = Config::default()
+            .with_sync_policy(crate::config::SyncPolicy::Background)
+            .with_sync_interval(std::time::Duration::from_millis(5));
+        let db = DB::open_with_config(dir.path().join("background.db"), config).unwrap();
+        db.upsert("v", "a", position, serde_json::json!({}), None)
+            .unwrap();
+        assert!(db.wait_durable(db.last_sequence(), std::time::Duration::from_secs(10)));
+        assert_eq!(db.flush_watermark(), 1);
+    }
+
+    #[test]
+    fn test_nearest_zones() {
+        use crate::config::BoundingBox2D;
+        use spatio_types::geo::{Point, Polygon};
+
+        let db = DB::memory().unwrap();
+        db.insert_zone(
+            "zones",
+            "district",
+            ZoneGeometry::BBox(BoundingBox2D::new(-74.0, 40.0, -73.0, 41.0)),
+            serde_json::json!({"name": "district"}),
+        )
+        .unwrap();
+        db.insert_zone(
+            "zones",
+            "depot",
+            ZoneGeometry::Polygon(Polygon::from_coords(
+                &[
+                    (-74.2, 40.5),
+                    (-74.15, 40.5),
+                    (-74.15, 40.55),
+                    (-74.2, 40.55),
+                    (-74.2, 40.5),
+                ],
+                vec![],
+            )),
+            serde_json::json!({}),
+        )
+        .unwrap();
+
+        // The district's edge is ~850m away, the depot ~11km.
+        let hits = db
+            .nearest_zones("zones", &Point::new(-74.01, 40.5), 2)
+            .unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].zone.zone_id, "district");
+        assert_eq!(
+            hits[0].zone.metadata,
+            serde_json::json!({"name": "district"})
+        );
+        assert_eq!(hits[1].zone.zone_id, "depot");
+        assert!(hits[0].distance < hits[1].distance);
+
+        // Boundary distance, not centroid distance: the district's center is
+        // ~55km away but its western edge is under 1km.
+        let d = db
+            .distance_to_zone("zones", "district", &Point::new(-74.01, 40.5))
+            .unwrap()
+            .unwrap();
+        assert!(d > 800.0 && d < 900.0, "{d}");
+        assert!(
+            db.distance_to_zone("zones", "missing", &Point::new(-74.01, 40.5))
+                .unwrap()
+                .is_none()
+        );
+
+        // Zones are kept apart from tracked objects.
+        assert!(db.get("zones", "district").unwrap().is_none());
+
+        let covering = db
+            .zones_containing("zones", &Point::new(-74.17, 40.52), 10)
+            .unwrap();
+        assert_eq!(covering.len(), 1);
+        assert_eq!(covering[0].zone_id, "depot");
+        let area = Polygon::from_coords(
+            &[
+                (-74.3, 40.4),
+                (-73.9, 40.4),
+                (-73.9, 40.6),
+                (-74.3, 40.6),
+                (-74.3, 40.4),
+            ],
+            vec![],
+        );
+        let ids: Vec<String> = db
+            .zones_intersecting("zones", &area, 10)
+            .unwrap()
+            .iter()
+            .map(|zone| zone.zone_id.clone())
+            .collect();
+        assert_eq!(ids, ["depot", "district"]);
+        assert_eq!(db.zones_intersecting("zones", &area, 1).unwrap().len(), 1);
+
+        db.delete_zone("zones", "district").unwrap();
+        let hits = db
+            .nearest_zones("zones", &Point::new(-74.01, 40.5), 2)
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+
+        assert!(
+            db.insert_zone(
+                "zones",
+                "bad",
+                ZoneGeometry::BBox(BoundingBox2D::new(0.0, 0.0, 1.0, 95.0)),
+                serde_json::json!({}),
+            )
+            .is_err()
+        );
+        assert!(
+            db.nearest_zones("zones", &Point::new(0.0, 95.0), 1)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_unsafe_identifiers_are_rejected() {
+        let db = DB::memory().unwrap();
+        let pos = Point3d::new(0.0, 0.0, 0.0);
+        let meta = serde_json::json!({});
+
+        // Delimiter / ambiguity hazards must be rejected, not silently mangled.
+        for bad in ["a|b", "a\nb", "a\rb", "a::b", ""] {
+            assert!(
+                db.upsert(bad, "obj", pos.clone(), meta.clone(), None)
+                    .is_err(),
+                "namespace {bad:?} must be rejected"
+            );
+            assert!(
+                db.upsert("ns", bad, pos.clone(), meta.clone(), None)
+                    .is_err(),
+                "object_id {bad:?} must be rejected"
+            );
+            assert!(
+                db.delete("ns", bad).is_err(),
+                "delete {bad:?} must be rejected"
+            );
+        }
+
+        // A normal key still works.
+        assert!(db.upsert("ns", "ok", pos, meta, None).is_ok());
+    }
+
+    #[test]
+    fn test_non_finite_query_arguments_are_rejected() {
+        use crate::compute::spatial::DistanceMetric;
+        use crate::compute::validation::ValidationError;
+        use spatio_types::geo::Point;
+
+        let db = DB::memory().unwrap();
+        db.upsert(
+            "ns",
+            "a",
+            Point3d::new(1.0, 1.0, 10.0),
+            serde_json::json!({}),
+            None,
+        )
+        .unwrap();
+        fn invalid<T>(result: Result<T>) -> bool {
+            matches!(result, Err(SpatioError::Validation(_)))
+        }
+
+        for bad in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let center = Point3d::new(bad, 1.0, 0.0);
+            assert!(invalid(db.query_radius("ns", &center, 100.0, 10)));
+            assert!(invalid(db.knn("ns", &center, 1)));
+            assert!(invalid(db.query_radius(
+                "ns",
+                &Point3d::new(1.0, 1.0, 0.0),
+                bad,
+                10
+            )));
+            assert!(invalid(db.query_bbox("ns", 0.0, 0.0, bad, 2.0, 10)));
+            assert!(invalid(
+                db.query_within_bbox_3d("ns", 0.0, 0.0, 0.0, 2.0, 2.0, bad, 10)
+            ));
+            assert!(invalid(
+                db.query_bbox_page("ns", bad, 0.0, 2.0, 2.0, 10, None)
+            ));
+            assert!(invalid(db.query_within_cylinder(
+                "ns",
+                Point::new(1.0, 1.0),
+                bad,
+                100.0,
+                1000.0,
+                10
+            )));
+            assert!(invalid(db.distance_to(
+                "ns",
+                "a",
+                &Point::new(1.0, bad),
+                DistanceMetric::Haversine
+            )));
+            assert!(invalid(db.k_anonymous_cells("ns", bad, 1)));
+
+            // Relative queries reject their extents even for unknown objects.
+            assert!(invalid(db.query_near("ns", "missing", bad, 10)));
+            assert!(invalid(db.query_bbox_near_object("ns", "a", bad, 1.0, 10)));
+            assert!(invalid(
+                db.query_bbox_3d_near_object("ns", "a", 1.0, 1.0, bad, 10)
+            ));
+            assert!(invalid(
+                db.query_cylinder_near_object("ns", "a", 0.0, bad, 1000.0, 10)
+            ));
+        }
+
+        assert!(invalid(db.query_within_cylinder(
+            "ns",
+            Point::new(1.0, 1.0),
+            100.0,
+            0.0,
+            1000.0,
+            10
+        )));
+        assert!(invalid(db.query_bbox_near_object("ns", "a", -1.0, 1.0, 10)));
+        match db.query_near("ns", "a", f64::NAN, 10) {
+            Err(SpatioError::Validation(ValidationError::NonFinite { field, .. })) => {
+                assert_eq!(field, "radius")
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_range_scans_ids_in_order() {
+        let db = DB::memory().unwrap();
+        let pos = Point3d::new(0.0, 0.0, 0.0);
+        for id in ["t03", "t01", "t05", "t02", "t04"] {
+            db.upsert("log", id, pos.clone(), serde_json::json!({}), None)
+                .unwrap();
+        }
+        db.upsert("other", "t02", pos.clone(), serde_json::json!({}), None)
+            .unwrap();
+        db.delete("log", "t04").unwrap();
+
+        let ids = |locs: Vec<Arc<CurrentLocation>>| {
+            locs.iter()
+                .map(|loc| loc.object_id.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ids(db.range("log", .., 10, ScanDirection::Forward).unwrap()),
+            ["t01", "t02", "t03", "t05"]
+        );
+        assert_eq!(
+            ids(db
+                .range("log", "t02".."t05", 10, ScanDirection::Forward)
+                .unwrap()),
+            ["t02", "t03"]
+        );
+        assert_eq!(
+            ids(db.range("log", "t02".., 2, ScanDirection::Reverse).unwrap()),
+            ["t05", "t03"]
+        );
+        // Resume after the last ID returned.
+        let rest = (Bound::Excluded("t02"), Bound::Unbounded);
+        assert_eq!(
+            ids(db.range("log", rest, 10, ScanDirection::Forward).unwrap()),
+            ["t03", "t05"]
+        );
+        let empty = (Bound::Excluded("t03"), Bound::Excluded("t03"));
+        assert!(
+            db.range("log", empty, 10, ScanDirection::Forward)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(matches!(
+            db.range("log", "t05".."t01", 10, ScanDirection::Forward),
+            Err(SpatioError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_composite_query() {
+        use crate::compute::query::Predicate;
+        use spatio_types::geo::Polygon;
+
+        let square = |x: f64, y: f64, size: f64| {
+            Predicate::WithinPolygon(Polygon::from_coords(
+                &[
+                    (x, y),
+                    (x + size, y),
+                    (x + size, y + size),
+                    (x, y + size),
+                    (x, y),
+                ],
+                vec![],
+            ))
+        };
+        let db = DB::memory().unwrap();
+        for (id, x, y, z) in [
+            ("low", 3.0, 3.0, 100.0),
+            ("high", 3.0, 3.5, 900.0),
+            ("airport", 1.5, 1.5, 100.0),
+            ("outside", 6.0, 6.0, 100.0),
+        ] {
+            db.upsert(
+                "fleet",
+                id,
+                Point3d::new(x, y, z),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+        }
+
+        let low_over_city =
+            square(0.0, 0.0, 4.0)
+                .and(!square(1.0, 1.0, 1.0))
+                .and(Predicate::Altitude {
+                    min: None,
+                    max: Some(500.0),
+                });
+        let found = db.query("fleet", &low_over_city, 10).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].object_id, "low");
+
+        let mut either: Vec<_> = db
+            .query(
+                "fleet",
+                &square(1.0, 1.0, 1.0).or(square(5.0, 5.0, 2.0)),
+                10,
+            )
+            .unwrap()
+            .iter()
+            .map(|loc| loc.object_id.clone())
+            .collect();
+        either.sort();
+        assert_eq!(either, ["airport", "outside"]);
+
+        // Negation alone scans the whole namespace.
+        assert_eq!(
+            db.query("fleet", &!square(1.0, 1.0, 1.0), 10)
+                .unwrap()
+                .len(),
+            3
+        );
+        assert_eq!(
+            db.query("fleet", &!square(1.0, 1.0, 1.0), 2).unwrap().len(),
+            2
+        );
+
+        let inverted = Predicate::Altitude {
+            min: Some(500.0),
+            max: Some(100.0),
+        };
+        assert!(matches!(
+            db.query("fleet", &square(0.0, 0.0, 4.0).and(inverted), 10),
+            Err(SpatioError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_query_builder() {
+        use serde_json::json;
+        use spatio_types::geo::Point;
+
+        let db = DB::memory().unwrap();
+        for (id, x, fuel) in [
+            ("far", 0.003, "diesel"),
+            ("near", 0.001, "diesel"),
+            ("electric", 0.0005, "electric"),
+            ("outside", 1.0, "diesel"),
+        ] {
+            db.upsert(
+                "fleet",
+                id,
+                Point3d::new(x, 0.0, 0.0),
+                json!({ "fuel": fuel }),
+                None,
+            )
+            .unwrap();
+        }
+        let depot = Point::new(0.0, 0.0);
+        let ids = |found: Vec<Arc<CurrentLocation>>| -> Vec<String> {
+            found.iter().map(|loc| loc.object_id.clone()).collect()
+        };
+
+        let diesel = db
+            .select("fleet")
+            .within_radius(depot, 1_000.0)
+            .filter_metadata("fuel", "diesel")
+            .order_by_distance();
+        assert_eq!(ids(diesel.clone().execute().unwrap()), ["near", "far"]);
+        assert_eq!(ids(diesel.clone().limit(1).execute().unwrap()), ["near"]);
+        let hits = diesel.execute_with_distances().unwrap();
+        assert!((hits[0].distance - 111.2).abs() < 1.0);
+
+        let mut all = ids(db.select("fleet").execute().unwrap());
+        all.sort();
+        assert_eq!(all, ["electric", "far", "near", "outside"]);
+        assert_eq!(
+            ids(db
+                .select("fleet")
+                .within_bbox(0.0, -1.0, 2.0, 1.0)
+                .order_by_distance_from(Point::new(1.0, 0.0))
+                .limit(2)
+                .execute()
+                .unwrap()),
+            ["outside", "far"]
+        );
+
+        assert!(matches!(
+            db.select("fleet").order_by_distance().execute(),
+            Err(SpatioError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            db.select("fleet").within_radius(depot, -1.0).execute(),
+            Err(SpatioError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_metadata_index_follows_writes() {
+        use crate::compute::query::Predicate;
+        use serde_json::json;
+
+        let db = DB::memory().unwrap();
+        let upsert = |id: &str, x: f64, fuel: &str| {
+            db.upsert(
+                "fleet",
+                id,
+                Point3d::new(x, 0.0, 0.0),
+                json!({ "fuel": fuel }),
+                None,
+            )
+            .unwrap();
+        };
+        upsert("a", 1.0, "diesel");
+        db.create_metadata_index("fleet", "fuel").unwrap();
+        assert!(matches!(
+            db.create_metadata_index("fleet", "fuel"),
+            Err(SpatioError::InvalidInput(_))
+        ));
+        upsert("b", 2.0, "diesel");
+        upsert("c", 3.0, "electric");
+
+        let fuel = |value: &str| Predicate::MetadataEquals {
+            field: "fuel".into(),
+            value: json!(value),
+        };
+        let ids = |predicate: &Predicate| -> Vec<String> {
+            db.query("fleet", predicate, 10)
+                .unwrap()
+                .iter()
+                .map(|loc| loc.object_id.clone())
+                .collect()
+        };
+        assert_eq!(ids(&fuel("diesel")), ["a", "b"]);
+        let west = Predicate::WithinBbox {
+            min_x: 0.0,
+            min_y: -1.0,
+            max_x: 1.5,
+            max_y: 1.0,
+        };
+        assert_eq!(ids(&fuel("diesel").and(west)), ["a"]);
+        assert_eq!(ids(&fuel("diesel").or(fuel("electric"))), ["a", "b", "c"]);
+
+        // Rewritten and deleted objects move out of their old entries.
+        upsert("a", 1.0, "electric");
+        db.delete("fleet", "b").unwrap();
+        assert_eq!(ids(&fuel("diesel")), Vec::<String>::new());
+        assert_eq!(ids(&fuel("electric")), ["a", "c"]);
+
+        assert_eq!(db.metadata_indexes("fleet").unwrap(), ["fuel"]);
+        assert!(db.drop_metadata_index("fleet", "fuel").unwrap());
+        assert!(db.metadata_indexes("fleet").unwrap().is_empty());
+        // Without the index the same query scans the namespace.
+        let mut scanned = ids(&fuel("electric"));
+        scanned.sort();
+        assert_eq!(scanned, ["a", "c"]);
+    }
+
+    #[test]
+    fn test_materialized_view_tracks_writes() {
+        use crate::compute::query::Predicate;
+
+        let db = DB::memory().unwrap();
+        let upsert = |id: &str, x: f64, z: f64| {
+            db.upsert(
+                "drones",
+                id,
+                Point3d::new(x, 0.0, z),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+        };
+        upsert("d1", 0.5, 50.0);
+        upsert("d2", 5.0, 50.0);
+
+        let low_over_city = Predicate::WithinBbox {
+            min_x: 0.0,
+            min_y: -1.0,
+            max_x: 1.0,
+            max_y: 1.0,
+        }
+        .and(Predicate::Altitude {
+            min: None,
+            max: Some(120.0),
+        });
+        db.create_view("low", "drones", low_over_city.clone())
+            .unwrap();
+        assert!(matches!(
+            db.create_view("low", "drones", low_over_city),
+            Err(SpatioError::InvalidInput(_))
+        ));
+        let events = db.subscribe_view("low").unwrap();
+        let ids = |db: &DB| {
+            db.view("low")
+                .unwrap()
+                .iter()
+                .map(|loc| loc.object_id.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&db), ["d1"]);
+
+        upsert("d2", 0.2, 80.0);
+        upsert("d1", 0.6, 60.0);
+        upsert("d1", 0.6, 300.0);
+        db.delete("drones", "d2").unwrap();
+        db.upsert(
+            "other",
+            "d3",
+            Point3d::new(0.5, 0.0, 10.0),
+            serde_json::json!({}),
+            None,
+        )
+        .unwrap();
+        assert!(ids(&db).is_empty());
+
+        let seen: Vec<_> = events
+            .try_iter()
+            .map(|event| match event {
+                ViewEvent::Entered(loc) => format!("+{}", loc.object_id),
+                ViewEvent::Updated(loc) => format!("~{}", loc.object_id),
+                ViewEvent::Left(loc) => format!("-{}@{}", loc.object_id, loc.position.z()),
+            })
+            .collect();
+        assert_eq!(seen, ["+d2", "~d1", "-d1@60", "-d2@80"]);
+
+        assert!(db.drop_view("low").unwrap());
+        assert!(events.recv().is_err());
+        assert!(db.view("low").is_err());
+    }
+
+    #[test]
+    fn test_fence_reports_enter_dwell_and_exit() {
+        let db = DB::memory().unwrap();
+        let start = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_000);
+        let upsert = |id: &str, x: f64, secs: u64| {
+            db.upsert(
+                "fleet",
+                id,
+                Point3d::new(x, 0.5, 0.0),
+                serde_json::json!({}),
+                Some(SetOptions::with_timestamp(
+                    start + std::time::Duration::from_secs(secs),
+                )),
+            )
+            .unwrap();
+        };
+        upsert("parked", 0.5, 0);
+        upsert("truck", 5.0, 0);
+
+        let depot = spatio_types::geo::Polygon::from_coords(
+            &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.0, 0.0)],
+            vec![],
+        );
+        let options = FenceOptions::default().with_dwell(std::time::Duration::from_secs(60));
+        db.create_fence("depot", "fleet", depot.clone(), options.clone())
+            .unwrap();
+        assert!(matches!(
+            db.create_fence("depot", "fleet", depot, options),
+            Err(SpatioError::InvalidInput(_))
+        ));
+        let events = db.subscribe_fence("depot").unwrap();
+        let ids = |db: &DB| {
+            db.fence("depot")
+                .unwrap()
+                .iter()
+                .map(|loc| loc.object_id.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&db), ["parked"]);
+
+        upsert("truck", 0.2, 10);
+        upsert("truck", 0.3, 40);
+        upsert("truck", 0.4, 70);
+        upsert("truck", 0.5, 100);
+        upsert("parked", 0.5, 90);
+        upsert("truck", 2.0, 110);
+        db.delete("fleet", "parked").unwrap();
+        assert!(ids(&db).is_empty());
+
+        let seen: Vec<_> = events
+            .try_iter()
+            .map(|event| match event {
+                FenceEvent::Enter(loc) => format!("+{}", loc.object_id),
+                FenceEvent::Dwell(loc) => format!("={}@{}", loc.object_id, loc.position.x()),
+                FenceEvent::Exit(loc) => format!("-{}@{}", loc.object_id, loc.position.x()),
+            })
+            .collect();
+        assert_eq!(
+            seen,
+            [
+                "+truck",
+                "=truck@0.4",
+                "=parked@0.5",
+                "-truck@2",
+                "-parked@0.5"
+            ]
+        );
+
+        assert!(db.drop_fence("depot").unwrap());
+        assert!(events.recv().is_err());
+        assert!(db.subscribe_fence("depot").is_err());
+    }
+
+    #[test]
+    fn test_circle_and_bbox_fences() {
+        let db = DB::memory().unwrap();
+        let center = spatio_types::geo::Point::new(0.0, 0.0);
+        db.create_fence(
+            "yard",
+            "fleet",
+            Fence::circle(center, 1_000.0),
+            FenceOptions::default(),
+        )
+        .unwrap();
+        db.create_fence(
+            "lot",
+            "fleet",
+            spatio_types::bbox::BoundingBox2D::new(0.0, 0.0, 1.0, 1.0),
+            FenceOptions::default(),
+        )
+        .unwrap();
+        assert!(
+            db.create_fence(
+                "bad",
+                "fleet",
+                Fence::circle(center, -1.0),
+                FenceOptions::default()
+            )
+            .is_err()
+        );
+        let yard = db.subscribe_fence("yard").unwrap();
+        let lot = db.subscribe_fence("lot").unwrap();
+
+        // About 550 m from the center: inside both.
+        let position = Point3d::new(0.005, 0.002, 0.0);
+        db.upsert("fleet", "truck", position, serde_json::json!({}), None)
+            .unwrap();
+        // Still in the lot, but well outside the yard.
+        let position = Point3d::new(0.5, 0.5, 0.0);
+        db.upsert("fleet", "truck", position, serde_json::json!({}), None)
+            .unwrap();
+
+        let kinds = |events: &FenceSubscription| {
+            events
+                .try_iter()
+                .map(|event| match event {
+                    FenceEvent::Enter(_) => "enter",
+                    FenceEvent::Dwell(_) => "dwell",
+                    FenceEvent::Exit(_) => "exit",
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kinds(&yard), ["enter", "exit"]);
+        assert_eq!(kinds(&lot), ["enter"]);
+        assert_eq!(db.fence("lot").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_fences_are_found_through_their_boxes() {
+        let db = DB::memory().unwrap();
+        let bbox = spatio_types::bbox::BoundingBox2D::new;
+        // Objects already inside count when the fence is created.
+        let start = Point3d::new(0.5, 0.5, 0.0);
+        db.upsert("fleet", "truck", start, serde_json::json!({}), None)
+            .unwrap();
+        for i in 0..30 {
+            let x = f64::from(i) * 10.0 - 170.0;
+            db.create_fence(
+                &format!("depot{i}"),
+                "fleet",
+                bbox(x, 0.0, x + 1.0, 1.0),
+                FenceOptions::default(),
+            )
+            .unwrap();
+        }
+        db.create_fence(
+            "elsewhere",
+            "other",
+            bbox(-180.0, -90.0, 180.0, 90.0),
+            FenceOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(db.fence("depot17").unwrap().len(), 1);
+        let left = db.subscribe_fence("depot17").unwrap();
+        let entered = db.subscribe_fence("depot18").unwrap();
+
+        // Leaving a fence is noticed though the new position is outside its box.
+        db.upsert(
+            "fleet",
+            "truck",
+            Point3d::new(10.5, 0.5, 0.0),
+            serde_json::json!({}),
+            None,
+        )
+        .unwrap();
+        assert!(matches!(left.try_recv(), Ok(FenceEvent::Exit(_))));
+        assert!(matches!(entered.try_recv(), Ok(FenceEvent::Enter(_))));
+        assert!(db.fence("elsewhere").unwrap().is_empty());
+
+        assert!(db.drop_fence("depot18").unwrap());
+        assert!(entered.recv().is_err());
+        db.delete("fleet", "truck").unwrap();
+        assert!(left.try_recv().is_err());
+        assert!(db.fence("depot18").is_err());
+    }
+
+    #[test]
+    fn test_history_retention_hides_and_expires_old_points() {
+        let config =
+            Config::default().with_history_retention("fleet", std::time::Duration::from_secs(3600));
+        let db = DB::memory_with_config(config).unwrap();
+        let now = SystemTime::now();
+        let hours_ago = |h: u64| now - std::time::Duration::from_secs(h * 3600);
+        for (ns, h) in [("fleet", 3), ("fleet", 2), ("logs", 3)] {
+            db.upsert(
+                ns,
+                "truck",
+                Point3d::new(h as f64, 0.0, 0.0),
+                serde_json::json!({}),
+                Some(SetOptions::with_timestamp(hours_ago(h))),
+            )
+            .unwrap();
+        }
+        db.upsert(
+            "fleet",
+            "truck",
+            Point3d::new(0.0, 0.0, 0.0),
+            serde_json::json!({}),
+            None,
+        )
+        .unwrap();
+
+        let history = |ns: &str| {
+            db.query_trajectory(
+                ns,
+                "truck",
+                hours_ago(10)..=now + std::time::Duration::from_secs(60),
+                10,
+            )
+            .unwrap()
+            .len()
+        };
+        assert_eq!(history("fleet"), 1);
+        assert_eq!(history("logs"), 1);
+
+        assert_eq!(db.expire_history().unwrap(), 2);
+        assert_eq!(history("fleet"), 1);
+        assert_eq!(history("logs"), 1);
+        assert!(db.get("fleet", "truck").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_namespace_config_applies_ttl_retention_and_geohash() {
+        let config = Config::default()
+            .with_history_retention("pois", std::time::Duration::from_secs(3600))
+            .with_namespace(
+                "vehicles",
+                NamespaceConfig::default().with_default_ttl(std::time::Duration::from_secs(60)),
+            );
+        let db = DB::memory_with_config(config).unwrap();
+        assert_eq!(db.namespace_config("pois").retention_secs, Some(3600));
+        let now = SystemTime::now();
+        let minutes_ago = |m: u64| now - std::time::Duration::from_secs(m * 60);
+        for (ns, id, m) in [
+            ("vehicles", "stale", 5),
+            ("vehicles", "fresh", 0),
+            ("pois", "museum", 600),
+        ] {
+            db.upsert(
+                ns,
+                id,
+                Point3d::new(-74.006, 40.712, 0.0),
+                serde_json::json!({}),
+                Some(SetOptions::with_timestamp(minutes_ago(m))),
+            )
+            .unwrap();
+        }
+
+        assert_eq!(db.expire_objects().unwrap(), 1);
+        assert!(db.get("vehicles", "stale").unwrap().is_none());
+        assert!(db.get("vehicles", "fresh").unwrap().is_some());
+        assert!(db.get("pois", "museum").unwrap().is_some());
+
+        // Replacing the settings drops the retention the config gave.
+        db.configure_namespace("pois", NamespaceConfig::default().with_geohash_precision(5))
+            .unwrap();
+        assert_eq!(db.expire_history().unwrap(), 0);
+        let write = |x: f64| {
+            db.upsert(
+                "pois",
+                "museum",
+                Point3d::new(x, 40.712, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap()
+        };
+        let sequence = write(-74.006);
+        let snapped = db.get("pois", "museum").unwrap().unwrap().position.clone();
+        let (x, y) = spatio_types::geohash::cell_center(-74.006, 40.712, 5);
+        assert_eq!(snapped, Point3d::new(x, y, 0.0));
+        // A move within the cell is not logged.
+        assert_eq!(write(-74.005), sequence);
+        assert_eq!(db.get("pois", "museum").unwrap().unwrap().position, snapped);
+
+        assert!(matches!(
+            db.configure_namespace(
+                "pois",
+                NamespaceConfig {
+                    geohash_precision: Some(13),
+                    ..Default::default()
+                }
+            ),
+            Err(SpatioError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_retention_updates_and_prune_trajectories() {
+        let config = Config::default().with_namespace(
+            "fleet",
+            NamespaceConfig::default().with_retention_updates(3),
+        );
+        let db = DB::memory_with_config(config).unwrap();
+        let now = SystemTime::now();
+        let hours_ago = |h: u64| now - std::time::Duration::from_secs(h * 3600);
+        for ns in ["fleet", "logs"] {
+            for h in (0..6).rev() {
+                db.upsert(
+                    ns,
+                    "truck",
+                    Point3d::new(h as f64, 0.0, 0.0),
+                    serde_json::json!({}),
+                    Some(SetOptions::with_timestamp(hours_ago(h))),
+                )
+                .unwrap();
+            }
+        }
+        let history = |ns: &str| {
+            db.query_trajectory(ns, "truck", hours_ago(10)..=now, 10)
+                .unwrap()
+                .iter()
+                .map(|u| u.position.x())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(db.expire_history().unwrap(), 3);
+        assert_eq!(history("fleet"), [0.0, 1.0, 2.0]);
+        assert_eq!(history("logs").len(), 6);
+
+        let removed = db
+            .prune_trajectories("logs", std::time::Duration::from_secs(90 * 60))
+            .unwrap();
+        assert_eq!(removed, 4);
+        assert_eq!(history("logs"), [0.0, 1.0]);
+        // The current location is kept however old.
+        let removed = db
+            .prune_trajectories("fleet", std::time::Duration::ZERO)
+            .unwrap();
+        assert_eq!(removed, 2);
+        assert_eq!(history("fleet"), [0.0]);
+    }
+
+    #[test]
+    fn test_active_expiration_removes_expired_objects() {
+        let config =
+            Config::default().with_active_expiration(std::time::Duration::from_millis(10), 1);
+        let db = DB::memory_with_config(config).unwrap();
+        db.upsert(
+            "vehicles",
+            "truck",
+            Point3d::new(1.0, 2.0, 0.0),
+            serde_json::json!({}),
+            Some(SetOptions::with_timestamp(
+                SystemTime::now() - std::time::Duration::from_secs(120),
+            )),
+        )
+        .unwrap();
+        db.configure_namespace(
+            "vehicles",
+            NamespaceConfig::default().with_default_ttl(std::time::Duration::from_secs(60)),
+        )
+        .unwrap();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while db.get("vehicles", "truck").unwrap().is_some() && std::time::Instant::now() < deadline
+        {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(db.get("vehicles", "truck").unwrap().is_none());
+        db.close().unwrap();
+    }
+
+    #[test]
+    fn test_compaction_keeps_sequences_without_a_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fleet.log");
+        let upsert = |db: &DB, id: &str| {
+            db.upsert(
+                "fleet",
+                id,
+                Point3d::new(1.0, 2.0, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap()
+        };
+        let db = DB::open(&path).unwrap();
+        for id in ["a", "b", "a", "b", "a", "c"] {
+            upsert(&db, id);
+        }
+        assert_eq!(
+            db.compact_history("fleet", HistoryCompaction::KeepLast(1))
+                .unwrap(),
+            3
+        );
+        db.close().unwrap();
+
+        // A crash before the new checkpoint leaves a full replay of the
+        // compacted log.
+        std::fs::remove_file(dir.path().join("fleet.log.snap")).unwrap();
+        let db = DB::open(&path).unwrap();
+        assert_eq!(db.last_sequence(), 6);
+        assert_eq!(upsert(&db, "d"), 7);
+    }
+
+    #[test]
+    fn test_open_locks_the_database_and_read_only_writes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fleet.log");
+        let read_only = || crate::DBBuilder::new().path(&path).read_only().build();
+        let db = DB::open(&path).unwrap();
+        db.upsert(
+            "fleet",
+            "a",
+            Point3d::new(1.0, 2.0, 0.0),
+            serde_json::json!({}),
+            None,
+        )
+        .unwrap();
+        assert!(matches!(DB::open(&path), Err(SpatioError::Locked(_))));
+        assert!(matches!(read_only(), Err(SpatioError::Locked(_))));
+        db.close().unwrap();
+
+        let files = || {
+            let mut files: Vec<_> = std::fs::read_dir(dir.path())
+                .unwrap()
+                .map(|entry| {
+                    let entry = entry.unwrap();
+                    let metadata = entry.metadata().unwrap();
+                    (
+                        entry.file_name(),
+                        metadata.len(),
+                        metadata.modified().unwrap(),
+                    )
+                })
+                .collect();
+            files.sort();
+            files
+        };
+        let before = files();
+        let (first, second) = (read_only().unwrap(), read_only().unwrap());
+        assert!(matches!(DB::open(&path), Err(SpatioError::Locked(_))));
+        assert!(first.get("fleet", "a").unwrap().is_some());
+        assert!(matches!(
+            second.upsert(
+                "fleet",
+                "b",
+                Point3d::new(1.0, 2.0, 0.0),
+                serde_json::json!({}),
+                None
+            ),
+            Err(SpatioError::ReadOnly)
+        ));
+        assert!(matches!(
+            second.checkpoint(CheckpointKind::Full),
+            Err(SpatioError::ReadOnly)
+        ));
+        first.close().unwrap();
+        second.close().unwrap();
+        assert_eq!(files(), before);
+        DB::open(&path).unwrap();
+    }
+
+    #[test]
+    fn test_compressed_log_shrinks_and_reads_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &Path, config: Config| {
+            let db = DB::open_with_config(path, config).unwrap();
+            for i in 0..20 {
+                db.upsert(
+                    "fleet",
+                    "truck",
+                    Point3d::new(i as f64, 0.0, 0.0),
+                    serde_json::json!({
+                        "driver": "alice",
+                        "route": "north-east depot to harbour",
+                        "cargo": ["pallets", "pallets", "pallets", "crates"],
+                        "status": "en route",
+                    }),
+                    None,
+                )
+                .unwrap();
+            }
+            db.close().unwrap();
+            std::fs::metadata(path).unwrap().len()
+        };
+        let plain = write(&dir.path().join("plain.log"), Config::default());
+        let path = dir.path().join("lz4.log");
+        let compressed = write(
+            &path,
+            Config::default().with_log_compression(crate::config::LogCompression::Lz4),
+        );
+        assert!(compressed * 4 < plain, "{compressed} vs {plain} bytes");
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().next(), Some("#spatio-log v2 lz4"));
+
+        // The log keeps its compression whatever the config says.
+        let db = DB::open(&path).unwrap();
+        let history = db.query_trajectory("fleet", "truck", .., 100).unwrap();
+        assert_eq!(history.len(), 20);
+        assert_eq!(history[0].metadata["driver"], "alice");
+        assert_eq!(
+            db.compact_history("fleet", HistoryCompaction::KeepLast(5))
+                .unwrap(),
+            15
+        );
+        db.upsert(
+            "fleet",
+            "truck",
+            Point3d::new(99.0, 0.0, 0.0),
+            history[0].metadata.clone(),
+            None,
+        )
+        .unwrap();
+        db.close().unwrap();
+        // The kept records were rewritten as one block, numbered by the
+        // marker after it, then the new one appended as its own line.
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "#spatio-log v2 lz4");
+        assert!(lines[1].contains("|~l"));
+        assert!(lines[2].ends_with("|SEQ|20"));
+
+        let db = DB::open(&path).unwrap();
+        assert_eq!(
+            db.query_trajectory("fleet", "truck", .., 100)
+                .unwrap()
+                .len(),
+            6
+        );
+        assert_eq!(
+            db.get("fleet", "truck").unwrap().unwrap().position,
+            Point3d::new(99.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_forget_object_erases_it_from_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("db.log");
+        let config = Config::default().with_access_log(
+            crate::config::AccessLogConfig::new(dir.path().join("access.log"))
+                .with_sample_rate(1.0),
+        );
+        let db = DB::open_with_config(&db_path, config.clone()).unwrap();
+        for x in [1.0, 2.0, 3.0] {
+            db.upsert(
+                "people",
+                "alice",
+                Point3d::new(x, 0.0, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+        }
+        db.upsert(
+            "people",
+            "bob",
+            Point3d::new(4.0, 0.0, 0.0),
+            serde_json::json!({}),
+            None,
+        )
+        .unwrap();
+        db.query_trajectory("people", "alice", .., 10).unwrap();
+        db.query_trajectory("people", "bob", .., 10).unwrap();
+        let sequence = db.last_sequence();
+
+        let report = db.forget_object("people", "alice").unwrap();
+        assert_eq!(
+            report,
+            ForgetReport {
+                current_location_removed: true,
+                log_records_removed: 3,
+                access_log_entries_removed: 1,
+                rejection_log_entries_removed: 0,
+            }
+        );
+        assert!(db.get("people", "alice").unwrap().is_none());
+        db.close().unwrap();
+
+        for file in std::fs::read_dir(dir.path()).unwrap() {
+            let path = file.unwrap().path();
+            let content = std::fs::read(&path).unwrap();
+            assert!(
+                !String::from_utf8_lossy(&content).contains("alice"),
+                "{} still names alice",
+                path.display()
+            );
+        }
+
+        let db = DB::open_with_config(&db_path, config).unwrap();
+        assert!(db.get("people", "alice").unwrap().is_none());
+        assert!(db.get("people", "bob").unwrap().is_some());
+        assert_eq!(db.last_sequence(), sequence);
+        assert_eq!(
+            db.forget_object("people", "alice").unwrap(),
+            ForgetReport::default()
+        );
+        assert!(
+            db.query_trajectory("people", "alice", .., 10)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_forget_object_erases_queries_near_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let access_path = dir.path().join("access.log");
+        let rejection_path = dir.path().join("rejections.log");
+        let config = Config::default()
+            .with_access_log(
+                crate::config::AccessLogConfig::new(&access_path).with_sample_rate(1.0),
+            )
+            .with_rejection_log(crate::config::RejectionLogConfig::new(&rejection_path));
+        let db = DB::open_with_config(dir.path().join("db.log"), config).unwrap();
+        db.upsert(
+            "people",
+            "alice",
+            Point3d::new(12.3456, 45.6789, 0.0),
+            serde_json::json!({}),
+            None,
+        )
+        .unwrap();
+        assert!(
+            db.upsert(
+                "people",
+                "alice",
+                Point3d::new(12.3456, 99.0, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .is_err()
+        );
+        db.query_near("people", "alice", 100.0, 10).unwrap();
+        db.knn_near_object("people", "alice", 3).unwrap();
+        db.query_radius("people", &Point3d::new(1.0, 2.0, 0.0), 100.0, 10)
+            .unwrap();
+
+        let report = db.forget_object("people", "alice").unwrap();
+        assert_eq!(report.access_log_entries_removed, 2);
+        assert_eq!(report.rejection_log_entries_removed, 1);
+        db.close().unwrap();
+
+        let access = std::fs::read_to_string(&access_path).unwrap();
+        assert!(!access.contains("12.3456"), "{access}");
+        assert_eq!(access.lines().count(), 1);
+        let rejections = std::fs::read_to_string(&rejection_path).unwrap();
+        assert!(!rejections.contains("12.3456"), "{rejections}");
+    }
+
+    #[test]
+    fn test_list_clear_and_drop_namespaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("db.log");
+        let db = DB::open(&db_path).unwrap();
+        for (namespace, id) in [
+            ("cars", "c1"),
+            ("cars", "c2"),
+            ("bikes", "b1"),
+            ("people", "p1"),
+        ] {
+            db.upsert(
+                namespace,
+                id,
+                Point3d::new(1.0, 2.0, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+        }
+        db.insert_zone(
+            "zones",
+            "district",
+            ZoneGeometry::BBox(crate::config::BoundingBox2D::new(-74.0, 40.0, -73.0, 41.0)),
+            serde_json::json!({}),
+        )
+        .unwrap();
+        assert_eq!(
+            db.list_namespaces().unwrap(),
+            ["bikes", "cars", "people", "zones"]
+        );
+
+        assert_eq!(db.clear_namespace("cars").unwrap(), 2);
+        assert_eq!(db.clear_namespace("zones").unwrap(), 1);
+        assert_eq!(db.clear_namespace("missing").unwrap(), 0);
+        assert_eq!(db.list_namespaces().unwrap(), ["bikes", "people"]);
+        assert!(db.get("cars", "c1").unwrap().is_none());
+        assert_eq!(db.query_trajectory("cars", "c1", .., 10).unwrap().len(), 1);
+
+        assert_eq!(db.drop_namespace("bikes").unwrap(), 1);
+        assert!(
+            db.query_trajectory("bikes", "b1", .., 10)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(db.list_namespaces().unwrap(), ["people"]);
+        db.close().unwrap();
+        assert!(matches!(
+            db.list_namespaces(),
+            Err(SpatioError::DatabaseClosed)
+        ));
+
+        let db = DB::open(&db_path).unwrap();
+        assert_eq!(db.list_namespaces().unwrap(), ["people"]);
+        assert!(
+            !std::fs::read_to_string(&db_path).unwrap().contains("bikes"),
+            "the log still holds the dropped namespace"
+        );
+    }
+
+    #[test]
+    fn test_export_current_locations_and_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = DB::open(dir.path().join("db.log")).unwrap();
+        let at = |secs: u64| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        for (id, secs, x) in [("b", 100, 1.0), ("a", 200, 2.0), ("b", 300, 3.0)] {
+            db.upsert(
+                "fleet",
+                id,
+                Point3d::new(x, 0.0, 0.0),
+                serde_json::json!({"driver": id}),
+                Some(SetOptions::with_timestamp(at(secs))),
+            )
+            .unwrap();
+        }
+        db.upsert(
+            "other",
+            "c",
+            Point3d::new(0.0, 0.0, 0.0),
+            serde_json::json!({}),
+            None,
+        )
+        .unwrap();
+
+        let export = |spec: ExportSpec| {
+            let mut out = Vec::new();
+            let written = db.export("fleet", &spec, &mut out).unwrap();
+            (written, String::from_utf8(out).unwrap())
+        };
+        let (written, csv) =
+            export(ExportSpec::new(crate::ExportFormat::Csv).with_fields(["driver"]));
+        assert_eq!(written, 2);
+        assert_eq!(
+            csv,
+            "object_id,timestamp,longitude,latitude,altitude,driver\n\
+             a,200,2,0,0,a\n\
+             b,300,3,0,0,b\n"
+        );
+
+        let (written, csv) = export(
+            ExportSpec::new(crate::ExportFormat::Csv)
+                .with_time_range(at(0), at(250))
+                .with_fields(Vec::<String>::new()),
+        );
+        assert_eq!(written, 2);
+        assert_eq!(
+            csv,
+            "object_id,timestamp,longitude,latitude,altitude\n\
+             a,200,2,0,0\n\
+             b,100,1,0,0\n"
+        );
+
+        let (written, lines) = export(
+            ExportSpec::new(crate::ExportFormat::GeoJsonLines).with_time_range(at(0), at(1000)),
+        );
+        assert_eq!(written, 3);
+        assert!(
+            lines
+                .lines()
+                .all(|line| line.contains(r#""type":"Feature""#))
+        );
+
+        let backwards = ExportSpec::new(crate::ExportFormat::Csv).with_time_range(at(2), at(1));
+        assert!(db.export("fleet", &backwards, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_change_feed_reports_writes() {
+        let db = DB::memory().unwrap();
+        let at = |secs: u64| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        let upsert = |ns: &str, id: &str, secs: u64| {
+            db.upsert(
+                ns,
+                id,
+                Point3d::new(secs as f64, 0.0, 0.0),
+                serde_json::json!({}),
+                Some(SetOptions::with_timestamp(at(secs))),
+            )
+            .unwrap();
+        };
+        upsert("fleet", "truck-1", 1);
+
+        let all = db.watch().unwrap();
+        let trucks = db.subscribe("fleet", "truck-").unwrap();
+        upsert("fleet", "truck-1", 2);
+        upsert("fleet", "truck-1", 1); // older: ignored
+        upsert("fleet", "van-1", 3);
+        upsert("depot", "truck-2", 4);
+        db.delete("fleet", "truck-1").unwrap();
+        db.delete("fleet", "truck-1").unwrap(); // already gone
+        upsert("fleet", "truck-1", 5);
+        db.forget_object("fleet", "van-1").unwrap();
+
+        let describe = |feed: &ChangeFeed| -> Vec<String> {
+            feed.try_iter()
+                .map(|event| {
+                    let loc = event.location();
+                    let kind = match event {
+                        ChangeEvent::Inserted(_) => "+",
+                        ChangeEvent::Updated(_) => "~",
+                        ChangeEvent::Deleted(_) => "-",
+                    };
+                    format!(
+                        "{kind}{}/{}@{}",
+                        loc.namespace,
+                        loc.object_id,
+                        loc.position.x()
+                    )
+                })
+                .collect()
+        };
+        assert_eq!(
+            describe(&all),
+            [
+                "~fleet/truck-1@2",
+                "+fleet/van-1@3",
+                "+depot/truck-2@4",
+                "-fleet/truck-1@2",
+                "+fleet/truck-1@5",
+                "-fleet/van-1@3",
+            ]
+        );
+        assert_eq!(
+            describe(&trucks),
+            ["~fleet/truck-1@2", "-fleet/truck-1@2", "+fleet/truck-1@5"]
+        );
+
+        drop(all);
+        drop(trucks);
+        upsert("fleet", "truck-1", 6);
+        assert!(!db.changes.is_watched());
+    }
+
+    #[test]
+    fn test_compact_history_keeps_requested_points() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.log");
+        let at = |secs: u64| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        let history = |db: &DB, ns: &str, id: &str| -> Vec<u64> {
+            db.query_trajectory(ns, id, at(0)..=at(1_000), 100)
+                .unwrap()
+                .iter()
+                .map(|u| u.timestamp.duration_since(at(0)).unwrap().as_secs())
+                .rev()
+                .collect()
+        };
+        {
+            let db = DB::open(&path).unwrap();
+            for (ns, id, secs) in [
+                ("fleet", "a", 1),
+                ("fleet", "a", 2),
+                ("fleet", "a", 3),
+                ("fleet", "a", 4),
+                ("fleet", "b", 1),
+                ("fleet", "c", 1),
+                ("fleet", "c", 2),
+                ("logs", "a", 1),
+                ("logs", "a", 2),
+            ] {
+                db.upsert(
+                    ns,
+                    id,
+                    Point3d::new(secs as f64, 0.0, 0.0),
+                    serde_json::json!({}),
+                    Some(SetOptions::with_timestamp(at(secs))),
+                )
+                .unwrap();
+            }
+            db.delete("fleet", "c").unwrap();
+
+            // Only "a" has more than two points.
+            let removed = db
+                .compact_history("fleet", HistoryCompaction::KeepLast(2))
+                .unwrap();
+            assert_eq!(removed, 2);
+            assert_eq!(history(&db, "fleet", "a"), [3, 4]);
+            assert_eq!(history(&db, "fleet", "b"), [1]);
+            assert_eq!(history(&db, "logs", "a"), [1, 2]);
+
+            // "b" is older but is its current location; deleted "c" goes
+            // entirely, tombstone included.
+            let removed = db
+                .compact_history("fleet", HistoryCompaction::KeepSince(at(4)))
+                .unwrap();
+            assert_eq!(removed, 4);
+            assert_eq!(history(&db, "fleet", "a"), [4]);
+            assert_eq!(history(&db, "fleet", "b"), [1]);
+            assert!(history(&db, "fleet", "c").is_empty());
+        }
+
+        let db = DB::open(&path).unwrap();
+        assert_eq!(history(&db, "fleet", "a"), [4]);
+        assert_eq!(history(&db, "logs", "a"), [1, 2]);
+        assert!(db.get("fleet", "b").unwrap().is_some());
+        assert!(db.get("fleet", "c").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_simplify_trajectory_drops_collinear_points() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.log");
+        let at = |secs: u64| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        let history = |db: &DB, id: &str| -> Vec<u64> {
+            db.query_trajectory("fleet", id, at(0)..=at(1_000), 100)
+                .unwrap()
+                .iter()
+                .map(|u| u.timestamp.duration_since(at(0)).unwrap().as_secs())
+                .rev()
+                .collect()
+        };
+        {
+            let db = DB::open(&path).unwrap();
+            // East along the equator, then north.
+            let route = [(0.0, 0.0), (0.1, 0.0), (0.2, 0.0), (0.2, 0.1), (0.2, 0.2)];
+            for (secs, (x, y)) in (1..).zip(route) {
+                for id in ["a", "b"] {
+                    db.upsert(
+                        "fleet",
+                        id,
+                        Point3d::new(x, y, 0.0),
+                        serde_json::json!({"leg": secs}),
+                        Some(SetOptions::with_timestamp(at(secs))),
+                    )
+                    .unwrap();
+                }
+            }
+
+            assert_eq!(db.simplify_trajectory("fleet", "a", 0.01).unwrap(), 2);
+            assert_eq!(history(&db, "a"), [1, 3, 5]);
+            assert_eq!(history(&db, "b"), [1, 2, 3, 4, 5]);
+            let corner = db.query_trajectory("fleet", "a", at(3)..=at(3), 1).unwrap();
+            assert_eq!(corner[0].metadata, serde_json::json!({"leg": 3}));
+            assert!(db.simplify_trajectory("fleet", "a", 0.0).is_err());
+        }
+
+        let db = DB::open(&path).unwrap();
+        assert_eq!(history(&db, "a"), [1, 3, 5]);
+        assert_eq!(
+            db.get("fleet", "a").unwrap().unwrap().position,
+            Point3d::new(0.2, 0.2, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_hydrate_from_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.log");
+        let now = SystemTime::now();
+        let ago = |secs: u64| now - std::time::Duration::from_secs(secs);
+        let ids = |db: &DB| -> Vec<String> {
+            db.range("fleet", .., 10, ScanDirection::Forward)
+                .unwrap()
+                .iter()
+                .map(|loc| loc.object_id.clone())
+                .collect()
+        };
+
+        for db in [DB::memory().unwrap(), DB::open(&path).unwrap()] {
+            for (id, secs, x) in [("a", 300, 1.0), ("b", 200, 2.0), ("a", 100, 3.0)] {
+                db.upsert(
+                    "fleet",
+                    id,
+                    Point3d::new(x, 0.0, 0.0),
+                    serde_json::json!({}),
+                    Some(SetOptions::with_timestamp(ago(secs))),
+                )
+                .unwrap();
+            }
+            db.delete("fleet", "b").unwrap();
+            let origin = Point3d::new(0.0, 0.0, 0.0);
+            db.upsert("fleet", "c", origin.clone(), serde_json::json!({}), None)
+                .unwrap();
+            assert_eq!(ids(&db), ["a", "c"]);
+
+            // Before the deletion and before "c" existed.
+            assert_eq!(db.hydrate_from_history("fleet", ago(150)).unwrap(), 2);
+            assert_eq!(ids(&db), ["a", "b"]);
+            let a = db.get("fleet", "a").unwrap().unwrap();
+            assert_eq!(a.position.x(), 1.0);
+            assert_eq!(
+                db.query_radius("fleet", &origin, 250_000.0, 10)
+                    .unwrap()
+                    .len(),
+                2
+            );
 
-        let near_car1_limit_1 = db.query_near(namespace, "car1", 1.5, 1).unwrap();
-        assert_eq!(near_car1_limit_1.len(), 1);
-    }
+            assert_eq!(db.hydrate_from_history("fleet", ago(250)).unwrap(), 1);
+            assert_eq!(ids(&db), ["a"]);
 
-    #[test]
-    fn test_query_trajectory() {
-        let db = DB::memory().unwrap();
-        let namespace = "planes";
-        let object_id = "plane1";
+            // Back to the present.
+            db.hydrate_from_history("fleet", SystemTime::now()).unwrap();
+            assert_eq!(ids(&db), ["a", "c"]);
+            assert_eq!(db.get("fleet", "a").unwrap().unwrap().position.x(), 3.0);
 
-        let start_time = SystemTime::now();
-        sleep(Duration::from_millis(10));
-        db.upsert(
-            namespace,
-            object_id,
-            Point3d::new(0.0, 0.0, 0.0),
-            serde_json::json!({"status": "takeoff"}),
-            None,
-        )
-        .unwrap();
-        sleep(Duration::from_millis(10));
-        db.upsert(
-            namespace,
-            object_id,
-            Point3d::new(10.0, 10.0, 1000.0),
-            serde_json::json!({"status": "climb"}),
-            None,
-        )
-        .unwrap();
-        sleep(Duration::from_millis(10));
-        db.upsert(
-            namespace,
-            object_id,
-            Point3d::new(20.0, 20.0, 2000.0),
-            serde_json::json!({"status": "cruise"}),
-            None,
+            db.hydrate_from_history("fleet", ago(150)).unwrap();
+        }
+
+        // The log is untouched: reopening recovers the latest state.
+        let db = DB::open(&path).unwrap();
+        assert_eq!(ids(&db), ["a", "c"]);
+
+        let db = DB::memory_with_config(
+            Config::default().with_history_retention("fleet", std::time::Duration::from_secs(60)),
         )
         .unwrap();
-        sleep(Duration::from_millis(10));
-        let end_time = SystemTime::now();
+        assert!(db.hydrate_from_history("fleet", ago(120)).is_err());
+        assert_eq!(db.hydrate_from_history("fleet", ago(30)).unwrap(), 0);
+    }
 
-        let trajectory = db
-            .query_trajectory(namespace, object_id, start_time, end_time, 10)
+    #[test]
+    fn test_stats_count_operations_per_minute() {
+        let db = DB::memory().unwrap();
+        let origin = Point3d::new(0.0, 0.0, 0.0);
+        db.upsert("fleet", "a", origin.clone(), serde_json::json!({}), None)
             .unwrap();
-        assert_eq!(trajectory.len(), 3);
-        // Results are newest first
-        assert_eq!(trajectory[0].position, Point3d::new(20.0, 20.0, 2000.0));
-        assert_eq!(trajectory[1].position, Point3d::new(10.0, 10.0, 1000.0));
-        assert_eq!(trajectory[2].position, Point3d::new(0.0, 0.0, 0.0));
+        db.insert_trajectory(
+            "fleet",
+            "b",
+            &[TemporalPoint::new(
+                spatio_types::geo::Point::new(1.0, 1.0),
+                SystemTime::now(),
+            )],
+        )
+        .unwrap();
+        db.query_near("fleet", "a", 10.0, 10).unwrap();
+        db.get("zones", "z").unwrap();
 
-        // Test limit
-        let limited_trajectory = db
-            .query_trajectory(namespace, object_id, start_time, end_time, 2)
-            .unwrap();
-        assert_eq!(limited_trajectory.len(), 2);
+        let per_minute = db.stats().per_minute;
+        let ops: HashMap<(String, Operation), u64> = per_minute
+            .iter()
+            .flat_map(|minute| &minute.counts)
+            .flat_map(|(ns, ops)| ops.iter().map(move |(op, n)| ((ns.clone(), *op), *n)))
+            .fold(HashMap::new(), |mut acc, (key, n)| {
+                *acc.entry(key).or_default() += n;
+                acc
+            });
+        let count = |ns: &str, op| ops.get(&(ns.to_string(), op)).copied().unwrap_or(0);
+        assert_eq!(count("fleet", Operation::Upsert), 1);
+        assert_eq!(count("fleet", Operation::InsertTrajectory), 1);
+        assert_eq!(count("fleet", Operation::QueryRadius), 1);
+        assert_eq!(count("zones", Operation::Get), 1);
+        assert_eq!(ops.len(), 4);
     }
 
     #[test]
-    fn test_delete_does_not_survive_restart() {
+    fn test_stats_report_namespaces_queries_and_log_size() {
         let dir = tempfile::tempdir().unwrap();
-        let db_path = dir.path().join("test.db");
+        let db = DB::open(dir.path().join("db")).unwrap();
+        let stats = db.stats();
+        assert!(stats.namespace_objects.is_empty());
+        assert_eq!(stats.queries_count, 0);
+        let empty_log = stats.cold_log_bytes;
 
-        // First session: insert then delete.
-        {
-            let db = DB::open(&db_path).unwrap();
+        for (namespace, id) in [("fleet", "a"), ("fleet", "b"), ("drones", "c")] {
             db.upsert(
-                "ns",
-                "obj",
-                Point3d::new(1.0, 2.0, 0.0),
+                namespace,
+                id,
+                Point3d::new(0.0, 0.0, 0.0),
                 serde_json::json!({}),
                 None,
             )
             .unwrap();
-            db.delete("ns", "obj").unwrap();
-            db.close().unwrap();
         }
+        db.delete("fleet", "b").unwrap();
+        db.get("fleet", "a").unwrap();
+        db.query_near("fleet", "a", 10.0, 10).unwrap();
 
-        // Second session: object must not reappear.
-        {
-            let db = DB::open(&db_path).unwrap();
-            assert!(
-                db.get("ns", "obj").unwrap().is_none(),
-                "deleted object must not reappear after restart"
-            );
-        }
+        let stats = db.stats();
+        assert_eq!(
+            stats.namespace_objects,
+            BTreeMap::from([("drones".to_string(), 1), ("fleet".to_string(), 1)])
+        );
+        assert_eq!(stats.hot_state_objects, 2);
+        assert!(stats.hot_state_memory_bytes > 0);
+        assert_eq!(stats.queries_count, 2);
+        assert!(stats.cold_log_bytes > empty_log);
+        assert_eq!(DB::memory().unwrap().stats().cold_log_bytes, 0);
+
+        let config = Config::default().with_sync_policy(crate::config::SyncPolicy::Always);
+        let synced = DB::open_with_config(dir.path().join("synced"), config).unwrap();
+        synced
+            .upsert(
+                "fleet",
+                "a",
+                Point3d::new(0.0, 0.0, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+        assert!(synced.stats().fsync_latency.count() > 0);
     }
 
     #[test]
-    fn test_delete_then_reinsert_survives_restart() {
-        let dir = tempfile::tempdir().unwrap();
-        let db_path = dir.path().join("test2.db");
-
-        let pos2 = Point3d::new(9.0, 8.0, 0.0);
-
-        {
-            let db = DB::open(&db_path).unwrap();
+    fn test_trips_split_on_gaps() {
+        let db = DB::memory().unwrap();
+        let at = |secs: u64| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        for secs in [100, 160, 220, 4000, 4060] {
             db.upsert(
-                "ns",
-                "obj",
-                Point3d::new(1.0, 2.0, 0.0),
+                "fleet",
+                "truck",
+                Point3d::new(secs as f64 / 10_000.0, 0.0, 0.0),
                 serde_json::json!({}),
-                None,
+                Some(SetOptions::with_timestamp(at(secs))),
             )
             .unwrap();
-            db.delete("ns", "obj").unwrap();
-            sleep(Duration::from_millis(1)); // ensure re-insert timestamp > tombstone
-            db.upsert("ns", "obj", pos2.clone(), serde_json::json!({"v": 2}), None)
-                .unwrap();
-            db.close().unwrap();
         }
 
-        {
-            let db = DB::open(&db_path).unwrap();
-            let loc = db
-                .get("ns", "obj")
+        let gap = std::time::Duration::from_secs(300);
+        let trips = db.trips("fleet", "truck", at(0)..=at(5000), gap).unwrap();
+        assert_eq!(trips.len(), 2);
+        assert_eq!(trips[0].points, 3);
+        assert_eq!(trips[0].start.timestamp, at(100));
+        assert_eq!(trips[0].duration(), std::time::Duration::from_secs(120));
+        assert!(trips[0].distance > 0.0);
+        assert_eq!(trips[1].end.timestamp, at(4060));
+
+        // The window cuts the first trip short.
+        let trips = db.trips("fleet", "truck", at(150)..=at(5000), gap).unwrap();
+        assert_eq!(trips[0].start.timestamp, at(160));
+        assert!(
+            db.trips("fleet", "nobody", at(0)..=at(5000), gap)
                 .unwrap()
-                .expect("re-inserted object must survive restart");
-            assert_eq!(loc.position, pos2);
-        }
+                .is_empty()
+        );
     }
 
     #[test]
-    fn test_memory_db_serves_trajectory_history_in_memory() {
-        // A :memory: DB must not touch the filesystem yet still answer
-        // trajectory queries (history kept in the in-memory log) beyond the
-        // recent buffer window.
+    fn test_trajectory_summary() {
         let db = DB::memory().unwrap();
-
-        let t0 = SystemTime::now();
-        for i in 0..5u64 {
+        let at = |secs: u64| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        for secs in [100, 160, 220] {
             db.upsert(
-                "ns",
-                "obj",
-                Point3d::new(i as f64, i as f64, 0.0),
-                serde_json::json!({ "i": i }),
-                Some(SetOptions {
-                    timestamp: Some(t0 + Duration::from_millis(i)),
-                }),
+                "fleet",
+                "truck",
+                Point3d::new(secs as f64 / 10_000.0, 0.0, 0.0),
+                serde_json::json!({}),
+                Some(SetOptions::with_timestamp(at(secs))),
             )
             .unwrap();
         }
 
-        // Current position reflects the latest update.
-        let current = db.get("ns", "obj").unwrap().unwrap();
-        assert_eq!(current.position.x(), 4.0);
+        let summary = db
+            .trajectory_summary("fleet", "truck", at(0)..=at(5000))
+            .unwrap();
+        assert_eq!(summary.points, 3);
+        assert_eq!(summary.duration, std::time::Duration::from_secs(120));
+        assert!(summary.total_distance > 0.0);
+        let avg = summary.average_speed.unwrap();
+        assert!((avg - summary.total_distance / 120.0).abs() < 1e-9);
 
-        // Full trajectory is queryable from the in-memory log. Use a window that
-        // safely brackets all records: stored timestamps are truncated to micros,
-        // so a raw-now() lower bound could exclude the boundary record.
-        let traj = db
-            .query_trajectory(
-                "ns",
-                "obj",
-                t0 - Duration::from_secs(1),
-                t0 + Duration::from_secs(1),
-                10,
-            )
+        let empty = db
+            .trajectory_summary("fleet", "nobody", at(0)..=at(5000))
             .unwrap();
-        assert_eq!(traj.len(), 5, "all in-memory history must be queryable");
+        assert_eq!(empty.points, 0);
+        assert_eq!(empty.average_speed, None);
     }
 
     #[test]
-    fn test_database_closed_operations() {
+    fn test_trajectories_through_region() {
         let db = DB::memory().unwrap();
-        db.close().unwrap();
+        let at = |secs: u64| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        // The bus passes through the box twice, leaving it in between.
+        for (secs, x) in [(100, 0.5), (110, 1.5), (120, 1.6), (130, 3.0), (140, 1.5)] {
+            db.upsert(
+                "fleet",
+                "bus",
+                Point3d::new(x, 1.5, 0.0),
+                serde_json::json!({}),
+                Some(SetOptions::with_timestamp(at(secs))),
+            )
+            .unwrap();
+        }
+        db.upsert(
+            "fleet",
+            "truck",
+            Point3d::new(10.0, 10.0, 0.0),
+            serde_json::json!({}),
+            Some(SetOptions::with_timestamp(at(100))),
+        )
+        .unwrap();
 
-        let namespace = "test";
-        let object_id = "obj1";
-        let pos = Point3d::new(0.0, 0.0, 0.0);
-        let metadata = serde_json::json!({"data": "data"});
+        let bbox = spatio_types::bbox::BoundingBox2D::new(1.0, 1.0, 2.0, 2.0);
+        let matches = db
+            .query_trajectories_intersecting_bbox("fleet", &bbox, at(0)..=at(5000))
+            .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].object_id, "bus");
+        let stamps: Vec<Vec<SystemTime>> = matches[0]
+            .segments
+            .iter()
+            .map(|segment| segment.iter().map(|u| u.timestamp).collect())
+            .collect();
+        assert_eq!(stamps, vec![vec![at(110), at(120)], vec![at(140)]]);
+
+        // The window cuts the second pass off.
+        let matches = db
+            .query_trajectories_intersecting_bbox("fleet", &bbox, at(0)..=at(135))
+            .unwrap();
+        assert_eq!(matches[0].segments.len(), 1);
 
+        let center = Point3d::new(10.0, 10.0, 0.0);
+        let matches = db
+            .query_trajectories_within_radius("fleet", &center, 1_000.0, TimeRange::all())
+            .unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].object_id, "truck");
         assert!(
-            db.upsert(namespace, object_id, pos.clone(), metadata, None)
+            db.query_trajectories_within_radius("fleet", &center, -1.0, TimeRange::all())
                 .is_err()
         );
-        assert!(db.query_radius(namespace, &pos, 1.0, 1).is_err());
-        assert!(db.query_near(namespace, object_id, 1.0, 1).is_err());
-        assert!(
-            db.query_trajectory(
-                namespace,
-                object_id,
-                SystemTime::UNIX_EPOCH,
-                SystemTime::now(),
-                1
-            )
-            .is_err()
-        );
     }
 
     #[test]
-    fn test_metadata_with_pipe_survives_reopen() {
-        // A '|' inside metadata must not corrupt the log record: the value has
-        // to survive a full close/reopen recovery cycle on a file-backed DB.
-        let dir = tempfile::tempdir().unwrap();
-        let db_path = dir.path().join("pipe.db");
-
-        {
-            let db = DB::open(&db_path).unwrap();
+    fn test_open_and_relative_time_ranges() {
+        let db = DB::memory().unwrap();
+        let now = SystemTime::now();
+        let ago = |secs: u64| now - std::time::Duration::from_secs(secs);
+        // The last point is stamped ahead of the clock, which a range ending
+        // at "now" would miss.
+        for at in [ago(7200), ago(60), now + std::time::Duration::from_secs(60)] {
             db.upsert(
-                "ns",
-                "obj",
+                "fleet",
+                "truck",
                 Point3d::new(1.0, 2.0, 0.0),
-                serde_json::json!({"note": "a|b|c", "n": 1}),
-                None,
-            )
-            .unwrap();
-            db.close().unwrap();
-        }
-        {
-            let db = DB::open(&db_path).unwrap();
-            let loc = db
-                .get("ns", "obj")
-                .unwrap()
-                .expect("record with '|' in metadata must survive reopen");
-            assert_eq!(loc.metadata, serde_json::json!({"note": "a|b|c", "n": 1}));
-        }
-    }
-
-    #[test]
-    fn test_checkpoint_preserves_history_and_writes_snapshot() {
-        let dir = tempfile::tempdir().unwrap();
-        let db_path = dir.path().join("traj.db");
-        let snap_path = dir.path().join("traj.db.snap");
-
-        let t1 = SystemTime::now();
-        let t2 = t1 + Duration::from_millis(5);
-
-        {
-            let db = DB::open(&db_path).unwrap();
-            db.upsert(
-                "ns",
-                "a",
-                Point3d::new(1.0, 1.0, 0.0),
-                serde_json::json!({"s": 1}),
-                Some(SetOptions {
-                    timestamp: Some(t1),
-                }),
-            )
-            .unwrap();
-            db.upsert(
-                "ns",
-                "a",
-                Point3d::new(2.0, 2.0, 0.0),
-                serde_json::json!({"s": 2}),
-                Some(SetOptions {
-                    timestamp: Some(t2),
-                }),
-            )
-            .unwrap();
-            db.upsert(
-                "ns",
-                "b",
-                Point3d::new(9.0, 9.0, 0.0),
                 serde_json::json!({}),
-                None,
+                Some(SetOptions::with_timestamp(at)),
             )
             .unwrap();
-            db.close().unwrap();
-        }
-        {
-            let db = DB::open(&db_path).unwrap();
-            // Current state recovered correctly.
-            assert_eq!(db.get("ns", "a").unwrap().unwrap().position.x(), 2.0);
-            assert!(db.get("ns", "b").unwrap().is_some());
-            // Trajectory history is NOT discarded by the checkpoint. Bracket the
-            // window generously: stored timestamps are micro-truncated, so a raw
-            // lower bound could exclude the first record.
-            let traj = db
-                .query_trajectory(
-                    "ns",
-                    "a",
-                    t1 - Duration::from_secs(1),
-                    t2 + Duration::from_secs(1),
-                    10,
-                )
-                .unwrap();
-            assert_eq!(
-                traj.len(),
-                2,
-                "checkpoint must preserve full trajectory history"
-            );
         }
-        // A checkpoint snapshot was written beside the log.
-        assert!(snap_path.exists(), "checkpoint snapshot should exist");
+
+        let count = |range: TimeRange| {
+            db.query_trajectory("fleet", "truck", range, 10)
+                .unwrap()
+                .len()
+        };
+        assert_eq!(count((..).into()), 3);
+        assert_eq!(count((ago(120)..).into()), 2);
+        assert_eq!(count((..ago(120)).into()), 1);
+        assert_eq!(count((..=now).into()), 2);
+        assert_eq!(
+            count(TimeRange::last(std::time::Duration::from_secs(600))),
+            2
+        );
+        assert_eq!(db.history("fleet", "truck").unwrap().len(), 3);
     }
 
     #[test]
-    fn test_corrupt_snapshot_falls_back_to_full_replay() {
-        let dir = tempfile::tempdir().unwrap();
-        let db_path = dir.path().join("traj.db");
-        let snap_path = dir.path().join("traj.db.snap");
-
-        {
-            let db = DB::open(&db_path).unwrap();
+    fn test_dwell_report_sums_time_inside_zone() {
+        use spatio_types::geo::Polygon;
+
+        let db = DB::memory().unwrap();
+        let at = |secs: u64| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        db.insert_zone(
+            "fleet",
+            "depot",
+            ZoneGeometry::Polygon(Polygon::from_coords(
+                &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.0, 0.0)],
+                vec![],
+            )),
+            serde_json::json!({}),
+        )
+        .unwrap();
+        let write = |id: &str, secs: u64, x: f64| {
             db.upsert(
-                "ns",
-                "a",
-                Point3d::new(1.0, 1.0, 0.0),
+                "fleet",
+                id,
+                Point3d::new(x, 0.5, 0.0),
                 serde_json::json!({}),
-                None,
+                Some(SetOptions::with_timestamp(at(secs))),
             )
             .unwrap();
+        };
+        // "a" arrives before the window, leaves, comes back and is deleted.
+        write("a", 50, 0.5);
+        write("a", 150, 2.0);
+        write("a", 300, 0.5);
+        // "b" is inside from 180 until its first point after the window.
+        write("b", 180, 0.5);
+        write("b", 600, 2.0);
+        // "c" never enters.
+        write("c", 120, 2.0);
+        write("c", 200, 3.0);
+        db.delete("fleet", "a").unwrap();
+
+        let secs = std::time::Duration::from_secs;
+        let report = db
+            .dwell_report("fleet", "depot", at(100)..=at(500))
+            .unwrap();
+        assert_eq!(report.get("b"), Some(&secs(320)));
+        assert!(!report.contains_key("c"));
+        // 100..150, then 300 until deleted (stamped with the wall clock).
+        assert_eq!(report.get("a"), Some(&secs(250)));
+
+        assert!(matches!(
+            db.dwell_report("fleet", "nowhere", at(0)..=at(500)),
+            Err(SpatioError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_active_expiration_removes_history_in_batches() {
+        let config = Config::default()
+            .with_history_retention("fleet", std::time::Duration::from_secs(3600))
+            .with_active_expiration(std::time::Duration::from_millis(10), 3);
+        let db = DB::memory_with_config(config).unwrap();
+        let now = SystemTime::now();
+        let hours_ago = |h: u64| now - std::time::Duration::from_secs(h * 3600);
+        let write = |h: u64| {
             db.upsert(
-                "ns",
-                "a",
-                Point3d::new(2.0, 2.0, 0.0),
+                "fleet",
+                "truck",
+                Point3d::new(h as f64, 0.0, 0.0),
                 serde_json::json!({}),
-                None,
+                Some(SetOptions::with_timestamp(hours_ago(h))),
             )
             .unwrap();
-            db.close().unwrap();
-        }
-        // Open once more so the snapshot covers the records, then corrupt it.
-        {
-            let db = DB::open(&db_path).unwrap();
-            db.close().unwrap();
-        }
-        // Valid header, but a record with a bad CRC -> snapshot must be rejected.
-        std::fs::write(&snap_path, "#spatio-snap v1 0\n00000000|garbage-record\n").unwrap();
+        };
+        write(3);
+        write(2);
+        write(0);
 
-        let db = DB::open(&db_path).unwrap();
-        let loc = db
-            .get("ns", "a")
-            .unwrap()
-            .expect("state must still recover via full log replay");
-        assert_eq!(loc.position.x(), 2.0);
+        // Two expired records are below the batch size.
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert_eq!(db.stats().expired_count, 0);
+
+        write(4);
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while db.stats().expired_count == 0 && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(db.stats().expired_count, 3);
+        db.close().unwrap();
     }
 
     #[test]
-    fn test_recovery_after_torn_final_write() {
-        // Simulate a crash mid-append: truncate the log inside the last record.
-        // Recovery must skip the torn record (CRC) and return the valid prefix
-        // without error.
+    fn test_compaction_policy_holds_off_scheduled_expiration() {
+        let allowed = Arc::new(AtomicBool::new(false));
+        let policy = {
+            let allowed = allowed.clone();
+            move |_: &CompactionContext| allowed.load(Ordering::Relaxed)
+        };
         let dir = tempfile::tempdir().unwrap();
-        let db_path = dir.path().join("torn.db");
-        let t0 = SystemTime::now();
-
+        let path = dir.path().join("db");
+        let config =
+            Config::default().with_history_retention("fleet", std::time::Duration::from_secs(3600));
         {
-            let db = DB::open(&db_path).unwrap();
-            for i in 0..3u64 {
+            let db = DB::open_with_config(&path, config.clone()).unwrap();
+            let old = SystemTime::now() - std::time::Duration::from_secs(7200);
+            for x in 0..3 {
                 db.upsert(
-                    "ns",
-                    "a",
-                    Point3d::new(i as f64, 0.0, 0.0),
-                    serde_json::json!({ "i": i }),
-                    Some(SetOptions {
-                        timestamp: Some(t0 + Duration::from_millis(i)),
-                    }),
+                    "fleet",
+                    "truck",
+                    Point3d::new(x as f64, 0.0, 0.0),
+                    serde_json::json!({}),
+                    Some(SetOptions::with_timestamp(old)),
                 )
                 .unwrap();
             }
-            db.close().unwrap();
         }
 
-        // Lop off the tail of the last record (leave earlier records intact).
-        let len = std::fs::metadata(&db_path).unwrap().len();
-        let f = std::fs::OpenOptions::new()
-            .write(true)
-            .open(&db_path)
+        // Neither the open nor the background passes compact while the
+        // policy says no.
+        let config = config.with_active_expiration(std::time::Duration::from_millis(10), 1);
+        let db = DB::builder()
+            .path(&path)
+            .config(config)
+            .compaction_policy(policy)
+            .build()
             .unwrap();
-        f.set_len(len - 4).unwrap();
-        drop(f);
-
-        let db = DB::open(&db_path).unwrap();
-        let loc = db
-            .get("ns", "a")
-            .unwrap()
-            .expect("a complete earlier record must still recover");
-        // The torn last record (i=2) is dropped; the last intact one is i=1.
-        assert_eq!(loc.position.x(), 1.0);
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert_eq!(db.stats().expired_count, 0);
+        allowed.store(true, Ordering::Relaxed);
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while db.stats().expired_count == 0 && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(db.stats().expired_count, 2);
+        db.close().unwrap();
     }
 
+    #[cfg(unix)]
     #[test]
-    fn test_concurrent_writes_same_object_converge() {
-        use std::sync::Arc;
-        use std::thread;
-
-        // Many threads hammer the same object with increasing timestamps while
-        // readers query concurrently. No panic; final value is the latest write.
-        let db = Arc::new(DB::memory().unwrap());
-        let base = SystemTime::now();
-        let writers = 8u64;
-        let per = 200u64;
-
-        let mut handles = Vec::new();
-        for w in 0..writers {
-            let db = Arc::clone(&db);
-            handles.push(thread::spawn(move || {
-                for i in 0..per {
-                    let ms = w * per + i; // globally unique, increasing timestamp
-                    // Position stays a valid coordinate; ordering is by timestamp.
-                    let _ = db.upsert(
-                        "ns",
-                        "hot",
-                        Point3d::new(1.0, 2.0, 0.0),
-                        serde_json::json!({ "ms": ms }),
-                        Some(SetOptions {
-                            timestamp: Some(base + Duration::from_millis(ms)),
-                        }),
-                    );
-                }
-            }));
-        }
-        // Concurrent readers — must never panic or deadlock.
-        for _ in 0..2 {
-            let db = Arc::clone(&db);
-            handles.push(thread::spawn(move || {
-                for _ in 0..per {
-                    let _ = db.get("ns", "hot");
-                    let _ = db.query_radius("ns", &Point3d::new(0.0, 0.0, 0.0), 1.0e6, 10);
-                }
-            }));
-        }
-        for h in handles {
-            h.join().unwrap();
+    fn test_hard_disk_watermark_refuses_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db");
+        let point = Point3d::new(1.0, 2.0, 0.0);
+        {
+            let low = Config::default().with_disk_watermarks(u64::MAX, 0);
+            let db = DB::open_with_config(&path, low).unwrap();
+            db.upsert("fleet", "truck", point.clone(), serde_json::json!({}), None)
+                .unwrap();
+            assert!(db.stats().disk_available_bytes.unwrap() > 0);
         }
 
-        // Last-writer-wins by timestamp: the highest-timestamp write survives.
-        let max_ms = writers * per - 1;
-        let loc = db.get("ns", "hot").unwrap().unwrap();
-        assert_eq!(loc.timestamp, base + Duration::from_millis(max_ms));
-        assert_eq!(loc.metadata, serde_json::json!({ "ms": max_ms }));
+        let full = Config::default().with_disk_watermarks(u64::MAX, u64::MAX);
+        let db = DB::open_with_config(&path, full).unwrap();
+        let err = db
+            .upsert("fleet", "van", point.clone(), serde_json::json!({}), None)
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                SpatioError::InsufficientDiskSpace {
+                    required: u64::MAX,
+                    ..
+                }
+            ),
+            "{err}"
+        );
+        assert!(db.delete("fleet", "truck").is_err());
+        // Reads, and writes kept in memory, go on.
+        assert!(db.get("fleet", "truck").unwrap().is_some());
+        db.insert_zone(
+            "fleet",
+            "depot",
+            ZoneGeometry::BBox(crate::config::BoundingBox2D::new(0.0, 0.0, 1.0, 1.0)),
+            serde_json::json!({}),
+        )
+        .unwrap();
+        db.close().unwrap();
     }
 
     #[test]
-    fn test_invalid_coordinates_are_rejected() {
-        let db = DB::memory().unwrap();
-        let meta = serde_json::json!({});
+    fn test_violations_against_zone_speed_limits() {
+        use crate::config::BoundingBox2D;
 
-        // NaN / Inf / out-of-range coordinates must never reach the index.
-        for bad in [
-            Point3d::new(f64::NAN, 0.0, 0.0),
-            Point3d::new(0.0, f64::INFINITY, 0.0),
-            Point3d::new(200.0, 0.0, 0.0), // lon > 180
-            Point3d::new(0.0, 95.0, 0.0),  // lat > 90
-            Point3d::new(0.0, 0.0, 1.0e9), // absurd altitude
-        ] {
-            assert!(
-                db.upsert("ns", "o", bad, meta.clone(), None).is_err(),
-                "invalid coordinate must be rejected on upsert"
-            );
-        }
-        // A valid point still works, and the bad ones left no trace.
+        let db = DB::memory().unwrap();
+        let at = |secs: u64| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        db.insert_zone(
+            "fleet",
+            "school",
+            ZoneGeometry::BBox(BoundingBox2D::new(0.0, 0.0, 1.0, 1.0)),
+            serde_json::json!({"speed_limit": 10.0}),
+        )
+        .unwrap();
+        db.insert_zone(
+            "fleet",
+            "unlimited",
+            ZoneGeometry::BBox(BoundingBox2D::new(0.0, 0.0, 5.0, 5.0)),
+            serde_json::json!({}),
+        )
+        .unwrap();
         assert!(
-            db.upsert("ns", "o", Point3d::new(1.0, 2.0, 0.0), meta, None)
-                .is_ok()
+            db.insert_zone(
+                "fleet",
+                "broken",
+                ZoneGeometry::BBox(BoundingBox2D::new(0.0, 0.0, 1.0, 1.0)),
+                serde_json::json!({"speed_limit": -5}),
+            )
+            .is_err()
         );
-        assert_eq!(db.stats().hot_state_objects, 1);
+
+        // Roughly 111 m per 0.001 degree of longitude at the equator.
+        let track = [
+            ("car", 0, 0.100),
+            ("car", 1, 0.101),
+            ("car", 2, 0.102),
+            ("car", 12, 0.1021),
+            ("car", 13, 0.1031),
+            // Fast, but outside the limited zone.
+            ("jet", 0, 2.0),
+            ("jet", 1, 2.1),
+        ];
+        for (id, secs, x) in track {
+            db.upsert(
+                "fleet",
+                id,
+                Point3d::new(x, 0.0005, 0.0),
+                serde_json::json!({}),
+                Some(SetOptions::with_timestamp(at(secs))),
+            )
+            .unwrap();
+        }
+
+        let violations = db.violations("fleet", at(0)..=at(100)).unwrap();
+        assert_eq!(violations.len(), 2);
+        assert!(violations.iter().all(|v| v.object_id == "car"));
+        assert!(violations.iter().all(|v| v.zone_id == "school"));
+        assert_eq!((violations[0].start, violations[0].end), (at(0), at(2)));
+        assert!(violations[0].max_speed > 100.0);
+        assert_eq!(violations[0].speed_limit, 10.0);
+        assert_eq!((violations[1].start, violations[1].end), (at(12), at(13)));
+
+        assert!(db.violations("fleet", at(3)..=at(12)).unwrap().is_empty());
     }
 
     #[test]
-    fn test_invalid_query_inputs_are_rejected() {
-        let db = DB::memory().unwrap();
-        let c = Point3d::new(0.0, 0.0, 0.0);
+    fn test_write_hooks_deliver_committed_writes() {
+        use std::sync::Mutex;
+        use std::sync::atomic::AtomicUsize;
 
+        let db = DB::memory().unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        db.register_hook(
+            "mirror",
+            "fleet",
+            HookMode::Sync,
+            move |event: &HookEvent| {
+                log.lock()
+                    .unwrap()
+                    .push((event.sequence, event.update.is_some()));
+                Ok(())
+            },
+        )
+        .unwrap();
+        // Fails twice, then catches up on everything it was sent.
+        let failures = Arc::new(AtomicUsize::new(0));
+        let delivered = Arc::new(Mutex::new(Vec::new()));
+        let (fails, out) = (failures.clone(), delivered.clone());
+        db.register_hook(
+            "webhook",
+            "fleet",
+            HookMode::Async,
+            move |event: &HookEvent| {
+                if fails.fetch_add(1, Ordering::SeqCst) < 2 {
+                    return Err("endpoint unavailable".to_string());
+                }
+                out.lock().unwrap().push(event.sequence);
+                Ok(())
+            },
+        )
+        .unwrap();
         assert!(
-            db.query_radius("ns", &c, 0.0, 10).is_err(),
-            "radius 0 rejected"
-        );
-        assert!(
-            db.query_radius("ns", &c, -5.0, 10).is_err(),
-            "negative radius rejected"
-        );
-        assert!(
-            db.query_radius("ns", &Point3d::new(f64::NAN, 0.0, 0.0), 1.0, 10)
+            db.register_hook("mirror", "fleet", HookMode::Async, |_: &HookEvent| Ok(()))
                 .is_err()
         );
-        assert!(
-            db.query_bbox("ns", 10.0, 0.0, 5.0, 10.0, 10).is_err(),
-            "min>=max rejected"
-        );
-        assert!(
-            db.knn("ns", &Point3d::new(0.0, 200.0, 0.0), 5).is_err(),
-            "bad center rejected"
-        );
+
+        let point = Point3d::new(1.0, 2.0, 0.0);
+        let first = db
+            .upsert("fleet", "truck", point.clone(), serde_json::json!({}), None)
+            .unwrap();
+        db.upsert("other", "truck", point.clone(), serde_json::json!({}), None)
+            .unwrap();
+        let deleted = db.delete("fleet", "truck").unwrap();
+
+        // Sync hooks have run by the time the write returns.
+        assert_eq!(*seen.lock().unwrap(), vec![(first, true), (deleted, false)]);
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while db.hook_backlog("webhook").unwrap() > 0 && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert_eq!(*delivered.lock().unwrap(), vec![first, deleted]);
+        assert_eq!(failures.load(Ordering::SeqCst), 4);
+
+        assert!(db.remove_hook("webhook").unwrap());
+        assert!(db.hook_backlog("webhook").is_err());
     }
 
     #[test]
-    fn test_unsafe_identifiers_are_rejected() {
-        let db = DB::memory().unwrap();
-        let pos = Point3d::new(0.0, 0.0, 0.0);
-        let meta = serde_json::json!({});
+    fn test_write_hooks_replay_missed_writes_from_the_log() {
+        use std::collections::BTreeSet;
+        use std::sync::Mutex;
+        use std::sync::atomic::AtomicBool;
 
-        // Delimiter / ambiguity hazards must be rejected, not silently mangled.
-        for bad in ["a|b", "a\nb", "a\rb", "a::b", ""] {
-            assert!(
-                db.upsert(bad, "obj", pos.clone(), meta.clone(), None)
-                    .is_err(),
-                "namespace {bad:?} must be rejected"
-            );
-            assert!(
-                db.upsert("ns", bad, pos.clone(), meta.clone(), None)
-                    .is_err(),
-                "object_id {bad:?} must be rejected"
-            );
-            assert!(
-                db.delete("ns", bad).is_err(),
-                "delete {bad:?} must be rejected"
-            );
+        let wait_for_backlog = |db: &DB, name: &str| {
+            let deadline = std::time::Instant::now() + std::time::Duration::from_secs(30);
+            while db.hook_backlog(name).unwrap() > 0 && std::time::Instant::now() < deadline {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+            assert_eq!(db.hook_backlog(name).unwrap(), 0);
+        };
+        let recorder = |seen: &Arc<Mutex<BTreeSet<u64>>>| {
+            let seen = seen.clone();
+            move |event: &HookEvent| {
+                seen.lock().unwrap().insert(event.sequence);
+                Ok(())
+            }
+        };
+        let point = Point3d::new(1.0, 2.0, 0.0);
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+
+        let seen = Arc::new(Mutex::new(BTreeSet::new()));
+        let first = {
+            let db = DB::open(&db_path).unwrap();
+            db.register_hook("audit", "fleet", HookMode::Async, recorder(&seen))
+                .unwrap();
+            let first = db
+                .upsert("fleet", "truck", point.clone(), serde_json::json!({}), None)
+                .unwrap();
+            wait_for_backlog(&db, "audit");
+            db.close().unwrap();
+            first
+        };
+        assert_eq!(*seen.lock().unwrap(), BTreeSet::from([first]));
+
+        // Written while no hook was registered, then replayed from the log.
+        let db = DB::open(&db_path).unwrap();
+        let missed = db
+            .upsert("fleet", "van", point.clone(), serde_json::json!({}), None)
+            .unwrap();
+        db.upsert("other", "van", point.clone(), serde_json::json!({}), None)
+            .unwrap();
+        let deleted = db.delete("fleet", "truck").unwrap();
+        let seen = Arc::new(Mutex::new(BTreeSet::new()));
+        db.register_hook("audit", "fleet", HookMode::Sync, recorder(&seen))
+            .unwrap();
+        wait_for_backlog(&db, "audit");
+        assert_eq!(*seen.lock().unwrap(), BTreeSet::from([missed, deleted]));
+
+        // A removed hook starts over from the present.
+        assert!(db.remove_hook("audit").unwrap());
+        db.upsert("fleet", "bike", point.clone(), serde_json::json!({}), None)
+            .unwrap();
+        let fresh = Arc::new(Mutex::new(BTreeSet::new()));
+        db.register_hook("audit", "fleet", HookMode::Async, recorder(&fresh))
+            .unwrap();
+        wait_for_backlog(&db, "audit");
+        assert!(fresh.lock().unwrap().is_empty());
+        db.remove_hook("audit").unwrap();
+
+        // A stalled hook's queue overflows to the log and nothing is lost.
+        let stalled = Arc::new(AtomicBool::new(true));
+        let seen = Arc::new(Mutex::new(BTreeSet::new()));
+        let (hold, out) = (stalled.clone(), seen.clone());
+        db.register_hook(
+            "slow",
+            "fleet",
+            HookMode::Async,
+            move |event: &HookEvent| {
+                while hold.load(Ordering::SeqCst) {
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                }
+                out.lock().unwrap().insert(event.sequence);
+                Ok(())
+            },
+        )
+        .unwrap();
+        let written: BTreeSet<u64> = (0..hooks::QUEUE_CAPACITY + 100)
+            .map(|i| {
+                db.upsert(
+                    "fleet",
+                    &format!("obj{i}"),
+                    point.clone(),
+                    serde_json::json!({}),
+                    None,
+                )
+                .unwrap()
+            })
+            .collect();
+        assert!(db.hook_backlog("slow").unwrap() <= hooks::QUEUE_CAPACITY + 1);
+        stalled.store(false, Ordering::SeqCst);
+        wait_for_backlog(&db, "slow");
+        assert!(seen.lock().unwrap().is_superset(&written));
+    }
+
+    #[test]
+    fn test_history_filters_by_time_and_kind() {
+        let db = DB::memory().unwrap();
+        let at = |secs| std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        for (secs, x) in [(100, 1.0), (200, 2.0)] {
+            db.upsert(
+                "fleet",
+                "truck",
+                Point3d::new(x, 0.0, 0.0),
+                serde_json::json!({}),
+                Some(SetOptions::with_timestamp(at(secs))),
+            )
+            .unwrap();
         }
+        db.delete("fleet", "truck").unwrap();
 
-        // A normal key still works.
-        assert!(db.upsert("ns", "ok", pos, meta, None).is_ok());
+        let kinds: Vec<_> = db
+            .history("fleet", "truck")
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                HistoryEventKind::Set,
+                HistoryEventKind::Set,
+                HistoryEventKind::Delete
+            ]
+        );
+
+        let sets = db
+            .history_between("fleet", "truck", at(150).., Some(HistoryEventKind::Set))
+            .unwrap();
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].timestamp, at(200));
+        assert_eq!(sets[0].update.as_ref().unwrap().position.x(), 2.0);
+
+        let deletes = db
+            .history_between(
+                "fleet",
+                "truck",
+                at(0)..=at(1_000),
+                Some(HistoryEventKind::Delete),
+            )
+            .unwrap();
+        assert!(deletes.is_empty());
     }
 }
@@ -0,0 +1,94 @@
+//! Per-namespace settings.
+//!
+//! Seeded on open from `Config::namespaces` and
+//! `Config::history_retention_secs`, then changed at runtime by
+//! [`DB::configure_namespace`](super::DB::configure_namespace). Reads,
+//! writes and the background threads look namespaces up here rather than in
+//! the config, so a change applies to them all at once.
+
+use super::cold_state::HistoryRetention;
+use crate::config::{Config, NamespaceConfig};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+
+pub(crate) struct NamespaceSettings {
+    namespaces: RwLock<HashMap<String, NamespaceConfig>>,
+}
+
+impl NamespaceSettings {
+    pub fn from_config(config: &Config) -> Self {
+        let mut namespaces: HashMap<String, NamespaceConfig> = HashMap::new();
+        for (namespace, secs) in &config.history_retention_secs {
+            namespaces
+                .entry(namespace.clone())
+                .or_default()
+                .retention_secs = Some(*secs);
+        }
+        for (namespace, settings) in &config.namespaces {
+            let entry = namespaces.entry(namespace.clone()).or_default();
+            let retention_secs = settings.retention_secs.or(entry.retention_secs);
+            *entry = NamespaceConfig {
+                retention_secs,
+                ..settings.clone()
+            };
+        }
+        Self {
+            namespaces: RwLock::new(namespaces),
+        }
+    }
+
+    /// Settings of `namespace` (all unset if it has none).
+    pub fn get(&self, namespace: &str) -> NamespaceConfig {
+        self.namespaces
+            .read()
+            .get(namespace)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Replace the settings of `namespace`.
+    pub fn set(&self, namespace: &str, settings: NamespaceConfig) {
+        let mut namespaces = self.namespaces.write();
+        if settings == NamespaceConfig::default() {
+            namespaces.remove(namespace);
+        } else {
+            namespaces.insert(namespace.to_string(), settings);
+        }
+    }
+
+    /// History retention of `namespace` in seconds, if it has one.
+    pub fn retention_secs(&self, namespace: &str) -> Option<u64> {
+        self.namespaces.read().get(namespace)?.retention_secs
+    }
+
+    /// Geohash precision positions of `namespace` are snapped to, if any.
+    #[inline]
+    pub fn geohash_precision(&self, namespace: &str) -> Option<usize> {
+        let namespaces = self.namespaces.read();
+        if namespaces.is_empty() {
+            return None;
+        }
+        namespaces.get(namespace)?.geohash_precision
+    }
+
+    /// History retention of every namespace that has one.
+    pub fn retention(&self) -> HistoryRetention {
+        HistoryRetention {
+            max_age_secs: self.collect(|settings| settings.retention_secs),
+            max_updates: self.collect(|settings| settings.retention_updates),
+        }
+    }
+
+    /// `namespace -> seconds` for every namespace with an object TTL.
+    pub fn ttls(&self) -> HashMap<String, u64> {
+        self.collect(|settings| settings.default_ttl_secs)
+    }
+
+    fn collect<T>(&self, field: impl Fn(&NamespaceConfig) -> Option<T>) -> HashMap<String, T> {
+        self.namespaces
+            .read()
+            .iter()
+            .filter_map(|(namespace, settings)| Some((namespace.clone(), field(settings)?)))
+            .collect()
+    }
+}
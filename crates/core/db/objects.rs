@@ -0,0 +1,355 @@
+//! Points and shapes through one API.
+//!
+//! Tracked objects (points with a trajectory) and zones (boxes and polygons)
+//! live in separate keyspaces. [`DB::insert_object`] writes either kind under
+//! one ID, and [`DB::objects_within_radius`] and [`DB::objects_within_bbox`]
+//! search both, tagging each result with its [`ObjectType`].
+
+use super::{CurrentLocation, DB, Zone};
+use crate::compute::spatial::ZoneGeometry;
+use crate::compute::validation;
+use crate::config::BoundingBox2D;
+use crate::error::{Result, SpatioError};
+use spatio_types::geo::{Point, Polygon};
+use spatio_types::point::Point3d;
+use spatio_types::stats::Operation;
+use std::cmp::Ordering as CmpOrdering;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+/// The kind of a stored object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObjectType {
+    /// A tracked object's current location.
+    Point,
+    /// A zone stored as a bounding box.
+    BBox,
+    /// A zone stored as a polygon.
+    Polygon,
+}
+
+/// The geometry of an object to store with [`DB::insert_object`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpatialObject {
+    Point(Point3d),
+    BBox(BoundingBox2D),
+    Polygon(Polygon),
+}
+
+impl SpatialObject {
+    pub fn object_type(&self) -> ObjectType {
+        match self {
+            SpatialObject::Point(_) => ObjectType::Point,
+            SpatialObject::BBox(_) => ObjectType::BBox,
+            SpatialObject::Polygon(_) => ObjectType::Polygon,
+        }
+    }
+}
+
+impl From<Point3d> for SpatialObject {
+    fn from(point: Point3d) -> Self {
+        SpatialObject::Point(point)
+    }
+}
+
+impl From<Point> for SpatialObject {
+    fn from(point: Point) -> Self {
+        SpatialObject::Point(Point3d::new(point.x(), point.y(), 0.0))
+    }
+}
+
+impl From<BoundingBox2D> for SpatialObject {
+    fn from(bbox: BoundingBox2D) -> Self {
+        SpatialObject::BBox(bbox)
+    }
+}
+
+impl From<Polygon> for SpatialObject {
+    fn from(polygon: Polygon) -> Self {
+        SpatialObject::Polygon(polygon)
+    }
+}
+
+/// A stored object of either kind.
+#[derive(Debug, Clone)]
+pub enum StoredObject {
+    Point(Arc<CurrentLocation>),
+    Zone(Arc<Zone>),
+}
+
+impl StoredObject {
+    pub fn object_type(&self) -> ObjectType {
+        match self {
+            StoredObject::Point(_) => ObjectType::Point,
+            StoredObject::Zone(zone) => match zone.geometry {
+                ZoneGeometry::BBox(_) => ObjectType::BBox,
+                ZoneGeometry::Polygon(_) => ObjectType::Polygon,
+            },
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        match self {
+            StoredObject::Point(location) => &location.object_id,
+            StoredObject::Zone(zone) => &zone.zone_id,
+        }
+    }
+
+    pub fn metadata(&self) -> &serde_json::Value {
+        match self {
+            StoredObject::Point(location) => &location.metadata,
+            StoredObject::Zone(zone) => &zone.metadata,
+        }
+    }
+}
+
+/// An object found by [`DB::objects_within_radius`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ObjectHit {
+    pub object: StoredObject,
+    /// Horizontal haversine distance in meters from the query's center: to
+    /// the point, or to the zone's boundary (`0.0` when inside the zone).
+    pub distance: f64,
+}
+
+impl ObjectHit {
+    pub fn new(object: StoredObject, distance: f64) -> Self {
+        Self { object, distance }
+    }
+
+    pub fn object_type(&self) -> ObjectType {
+        self.object.object_type()
+    }
+
+    pub fn id(&self) -> &str {
+        self.object.id()
+    }
+}
+
+impl DB {
+    /// Store a point or a shape under `object_id`. Points are upserted as
+    /// tracked objects (see [`DB::upsert`]) and shapes as zones (see
+    /// [`DB::insert_zone`]); an object of the other kind with the same ID is
+    /// deleted, so the ID names one object afterwards.
+    pub fn insert_object(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        object: impl Into<SpatialObject>,
+        metadata: serde_json::Value,
+    ) -> Result<()> {
+        let geometry = match object.into() {
+            SpatialObject::Point(point) => {
+                self.upsert(namespace, object_id, point, metadata, None)?;
+                self.delete_zone(namespace, object_id)?;
+                return Ok(());
+            }
+            SpatialObject::BBox(bbox) => ZoneGeometry::BBox(bbox),
+            SpatialObject::Polygon(polygon) => ZoneGeometry::Polygon(polygon),
+        };
+        self.insert_zone(namespace, object_id, geometry, metadata)?;
+        if self
+            .hot
+            .get_current_location(namespace, object_id)
+            .is_some()
+        {
+            self.delete(namespace, object_id)?;
+        }
+        Ok(())
+    }
+
+    /// The object stored under `object_id`, of either kind. A tracked object
+    /// wins if a zone was also stored under the ID through [`DB::insert_zone`].
+    pub fn get_object(&self, namespace: &str, object_id: &str) -> Result<Option<StoredObject>> {
+        if let Some(location) = self.get(namespace, object_id)? {
+            return Ok(Some(StoredObject::Point(location)));
+        }
+        Ok(self.get_zone(namespace, object_id)?.map(StoredObject::Zone))
+    }
+
+    /// Delete the object stored under `object_id`, of either kind, returning
+    /// it if it existed.
+    pub fn delete_object(&self, namespace: &str, object_id: &str) -> Result<Option<StoredObject>> {
+        if let Some(location) = self.get(namespace, object_id)? {
+            self.delete(namespace, object_id)?;
+            return Ok(Some(StoredObject::Point(location)));
+        }
+        Ok(self
+            .delete_zone(namespace, object_id)?
+            .map(StoredObject::Zone))
+    }
+
+    /// Points and zones of `namespace` within `radius` meters of `center`,
+    /// nearest first, up to `limit`. Zones are measured to their boundary,
+    /// so a zone containing `center` comes first at distance `0.0`.
+    pub fn objects_within_radius(
+        &self,
+        namespace: &str,
+        center: &Point,
+        radius: f64,
+        limit: usize,
+    ) -> Result<Vec<ObjectHit>> {
+        db_span!("spatio.objects_within_radius", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::QueryRadius)?;
+        let _timer = self.time_query(namespace, Operation::QueryRadius);
+        validation::validate_geographic_point(center)?;
+        validation::validate_radius(radius)?;
+
+        let points = self
+            .hot
+            .query_within_radius_2d(namespace, center, radius, limit)
+            .into_iter()
+            .map(|(location, distance)| ObjectHit::new(StoredObject::Point(location), distance));
+        let zones = self
+            .hot
+            .zones_within_radius(namespace, center, radius, limit)
+            .into_iter()
+            .map(|(zone, distance)| ObjectHit::new(StoredObject::Zone(zone), distance));
+        let mut hits: Vec<ObjectHit> = points.chain(zones).collect();
+        hits.sort_by(|a, b| {
+            a.distance
+                .partial_cmp(&b.distance)
+                .unwrap_or(CmpOrdering::Equal)
+                .then_with(|| a.id().cmp(b.id()))
+        });
+        hits.truncate(limit);
+        Ok(hits)
+    }
+
+    /// Points inside the box and zones sharing any point with it, edges
+    /// included, up to `limit`: points first, then zones in zone ID order.
+    pub fn objects_within_bbox(
+        &self,
+        namespace: &str,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        limit: usize,
+    ) -> Result<Vec<StoredObject>> {
+        db_span!("spatio.objects_within_bbox", namespace);
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.begin(namespace, Operation::QueryBbox)?;
+        let _timer = self.time_query(namespace, Operation::QueryBbox);
+        validation::validate_bbox(min_x, min_y, max_x, max_y)?;
+
+        let mut objects: Vec<StoredObject> = self
+            .hot
+            .query_within_bbox(namespace, min_x, min_y, max_x, max_y, limit)
+            .into_iter()
+            .map(StoredObject::Point)
+            .collect();
+        let remaining = limit - objects.len();
+        if remaining > 0 {
+            let area: Polygon = BoundingBox2D::new(min_x, min_y, max_x, max_y)
+                .rect
+                .to_polygon()
+                .into();
+            objects.extend(
+                self.hot
+                    .zones_intersecting(namespace, &area, remaining)
+                    .into_iter()
+                    .map(StoredObject::Zone),
+            );
+        }
+        Ok(objects)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn square(min: f64, max: f64) -> Polygon {
+        Polygon::new(
+            geo::LineString::from(vec![
+                (min, min),
+                (max, min),
+                (max, max),
+                (min, max),
+                (min, min),
+            ]),
+            vec![],
+        )
+    }
+
+    #[test]
+    fn test_mixed_objects_share_queries() {
+        let db = DB::memory().unwrap();
+        db.insert_object("city", "bus", Point::new(0.0, 0.0), json!({}))
+            .unwrap();
+        db.insert_object(
+            "city",
+            "depot",
+            BoundingBox2D::new(0.01, -0.01, 0.02, 0.01),
+            json!({"kind": "depot"}),
+        )
+        .unwrap();
+        db.insert_object("city", "park", square(-0.001, 0.001), json!({}))
+            .unwrap();
+        db.insert_object("city", "far", Point3d::new(1.0, 1.0, 0.0), json!({}))
+            .unwrap();
+
+        let hits = db
+            .objects_within_radius("city", &Point::new(0.0, 0.0), 2_000.0, 10)
+            .unwrap();
+        let tagged: Vec<_> = hits.iter().map(|h| (h.id(), h.object_type())).collect();
+        assert_eq!(
+            tagged,
+            vec![
+                ("bus", ObjectType::Point),
+                ("park", ObjectType::Polygon),
+                ("depot", ObjectType::BBox),
+            ]
+        );
+        assert_eq!(hits[1].distance, 0.0);
+        assert!(hits[2].distance > 1_000.0);
+
+        let found = db
+            .objects_within_bbox("city", 0.015, -1.0, 2.0, 2.0, 10)
+            .unwrap();
+        let tagged: Vec<_> = found.iter().map(|o| (o.id(), o.object_type())).collect();
+        assert_eq!(
+            tagged,
+            vec![("far", ObjectType::Point), ("depot", ObjectType::BBox)]
+        );
+        assert_eq!(found[1].metadata()["kind"], "depot");
+
+        let limited = db
+            .objects_within_radius("city", &Point::new(0.0, 0.0), 2_000.0, 1)
+            .unwrap();
+        assert_eq!(limited.len(), 1);
+    }
+
+    #[test]
+    fn test_insert_object_replaces_other_kind() {
+        let db = DB::memory().unwrap();
+        db.insert_object("city", "site", Point::new(0.0, 0.0), json!({}))
+            .unwrap();
+        db.insert_object("city", "site", square(-1.0, 1.0), json!({}))
+            .unwrap();
+        assert!(db.get("city", "site").unwrap().is_none());
+        let stored = db.get_object("city", "site").unwrap().unwrap();
+        assert_eq!(stored.object_type(), ObjectType::Polygon);
+
+        db.insert_object("city", "site", Point::new(0.5, 0.5), json!({}))
+            .unwrap();
+        assert!(db.get_zone("city", "site").unwrap().is_none());
+        let removed = db.delete_object("city", "site").unwrap().unwrap();
+        assert_eq!(removed.object_type(), ObjectType::Point);
+        assert!(db.get_object("city", "site").unwrap().is_none());
+
+        let out_of_range = BoundingBox2D::new(0.0, 0.0, 200.0, 1.0);
+        assert!(
+            db.insert_object("city", "bad", out_of_range, json!({}))
+                .is_err()
+        );
+    }
+}
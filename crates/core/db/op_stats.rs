@@ -0,0 +1,134 @@
+//! Rolling per-minute operation counters, by namespace and operation kind.
+//!
+//! Each namespace keeps one row of counters per recent minute behind its own
+//! lock, so operations on different namespaces don't contend. Minutes older
+//! than [`STATS_WINDOW_MINUTES`] are dropped as new ones start.
+
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use spatio_types::stats::{MinuteStats, Operation};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Minutes of history kept, including the current one.
+pub const STATS_WINDOW_MINUTES: u64 = 60;
+
+type Row = (u64, [u64; Operation::ALL.len()]);
+
+#[derive(Default)]
+pub(crate) struct OpStats {
+    namespaces: DashMap<String, Arc<Mutex<VecDeque<Row>>>>,
+}
+
+fn minute_of(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / 60
+}
+
+/// Drop rows that fell out of the window ending at `minute`.
+fn expire(rows: &mut VecDeque<Row>, minute: u64) {
+    while rows
+        .front()
+        .is_some_and(|(start, _)| start + STATS_WINDOW_MINUTES <= minute)
+    {
+        rows.pop_front();
+    }
+}
+
+impl OpStats {
+    pub fn record(&self, namespace: &str, operation: Operation) {
+        self.record_at(namespace, operation, SystemTime::now());
+    }
+
+    fn record_at(&self, namespace: &str, operation: Operation, now: SystemTime) {
+        let minute = minute_of(now);
+        let rows = match self.namespaces.get(namespace) {
+            Some(rows) => rows.value().clone(),
+            None => self
+                .namespaces
+                .entry(namespace.to_string())
+                .or_default()
+                .value()
+                .clone(),
+        };
+        let mut rows = rows.lock();
+        // Clocks can step back; count late operations in the newest minute.
+        if rows.back().is_none_or(|(start, _)| *start < minute) {
+            expire(&mut rows, minute);
+            rows.push_back((minute, [0; Operation::ALL.len()]));
+        }
+        if let Some((_, counts)) = rows.back_mut() {
+            counts[operation as usize] += 1;
+        }
+    }
+
+    /// Counts for the window ending at the current minute, oldest first.
+    pub fn snapshot(&self) -> Vec<MinuteStats> {
+        self.snapshot_at(SystemTime::now())
+    }
+
+    fn snapshot_at(&self, now: SystemTime) -> Vec<MinuteStats> {
+        let minute = minute_of(now);
+        let mut minutes: BTreeMap<u64, MinuteStats> = BTreeMap::new();
+        self.namespaces.retain(|namespace, rows| {
+            let mut rows = rows.lock();
+            expire(&mut rows, minute);
+            for (start, counts) in rows.iter() {
+                let ops: BTreeMap<Operation, u64> = Operation::ALL
+                    .iter()
+                    .zip(counts)
+                    .filter(|(_, count)| **count > 0)
+                    .map(|(op, count)| (*op, *count))
+                    .collect();
+                minutes
+                    .entry(*start)
+                    .or_insert_with(|| MinuteStats {
+                        minute: *start,
+                        ..MinuteStats::default()
+                    })
+                    .counts
+                    .insert(namespace.clone(), ops);
+            }
+            !rows.is_empty()
+        });
+        minutes.into_values().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_counts_roll_over_by_minute() {
+        let stats = OpStats::default();
+        let at = |secs: u64| UNIX_EPOCH + Duration::from_secs(secs);
+
+        stats.record_at("fleet", Operation::Upsert, at(0));
+        stats.record_at("fleet", Operation::Upsert, at(59));
+        stats.record_at("fleet", Operation::Get, at(60));
+        stats.record_at("zones", Operation::Get, at(61));
+
+        let minutes = stats.snapshot_at(at(61));
+        assert_eq!(minutes.len(), 2);
+        assert_eq!(minutes[0].minute, 0);
+        assert_eq!(minutes[0].counts["fleet"][&Operation::Upsert], 2);
+        assert!(!minutes[0].counts.contains_key("zones"));
+        assert_eq!(minutes[1].total(Operation::Get), 2);
+        assert_eq!(minutes[1].total(Operation::Upsert), 0);
+
+        // An hour later only the second minute is left, then nothing.
+        let later = at(STATS_WINDOW_MINUTES * 60);
+        assert_eq!(stats.snapshot_at(later).len(), 1);
+        assert!(
+            stats
+                .snapshot_at(at((STATS_WINDOW_MINUTES + 1) * 60))
+                .is_empty()
+        );
+        assert!(stats.namespaces.is_empty());
+    }
+}
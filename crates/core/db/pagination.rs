@@ -0,0 +1,263 @@
+//! Stateless pagination for bounding-box, radius and trajectory scans.
+//!
+//! A bbox scan walks the query box as a fixed `PAGE_GRID x PAGE_GRID` grid of
+//! cells in row-major order, and each cell's objects in `object_id` order. A
+//! radius scan returns objects nearest first (ties in `object_id` order), and
+//! a trajectory scan returns points newest first. The continuation token
+//! records everything needed to resume: the position reached (cell, distance
+//! or timestamp), how many objects at that position were already returned,
+//! and the log and sequence the scan started at. No cursor state is kept on
+//! the server, so a scan can resume on any database serving the same log
+//! (such as the same one after a restart) that has applied at least that
+//! sequence; any other database rejects the token.
+//!
+//! Cells are half-open except along the box's far edges, so an object on a
+//! shared cell edge belongs to exactly one cell, and a page never repeats an
+//! object. Pages are not a point-in-time snapshot, though: objects written
+//! after the scan started may or may not appear, and an object moving ahead
+//! of the scan into a later cell (or farther from a radius scan's center)
+//! can be returned again on a later page.
+
+use crate::error::{Result, SpatioError};
+use std::fmt;
+
+/// Cells per side of the scan grid.
+pub(crate) const PAGE_GRID: u32 = 8;
+
+/// The kind of query a token pages through. Each kind has its own token
+/// prefix, so a token can only resume the kind of query that issued it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PageKind {
+    Bbox,
+    Radius,
+    Trajectory,
+}
+
+impl PageKind {
+    fn prefix(self) -> &'static str {
+        match self {
+            PageKind::Bbox => "v1",
+            PageKind::Radius => "r1",
+            PageKind::Trajectory => "t1",
+        }
+    }
+}
+
+/// Decoded continuation token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PageToken {
+    pub kind: PageKind,
+    /// Where to resume: the row-major index of a bbox scan's grid cell, the
+    /// bits of the last distance a radius scan returned, or the last
+    /// timestamp (nanoseconds since the epoch) a trajectory scan returned.
+    pub position: u64,
+    /// Objects at `position` already returned.
+    pub offset: u64,
+    /// ID of the log the scan started on (see [`crate::DB::log_id`]).
+    pub log: u64,
+    /// Last log sequence when the scan started.
+    pub sequence: u64,
+    /// Hash of the scanned namespace and parameters; a token only resumes
+    /// its own query.
+    pub query: u64,
+}
+
+impl PageToken {
+    pub(crate) fn parse(token: &str, kind: PageKind) -> Result<Self> {
+        let invalid = || SpatioError::InvalidInput(format!("malformed page token: {token:?}"));
+        let mut parts = token.split('.');
+        if parts.next() != Some(kind.prefix()) {
+            return Err(invalid());
+        }
+        let mut field = || {
+            parts
+                .next()
+                .and_then(|part| u64::from_str_radix(part, 16).ok())
+                .ok_or_else(invalid)
+        };
+        let position = field()?;
+        let offset = field()?;
+        let log = field()?;
+        let sequence = field()?;
+        let query = field()?;
+        if parts.next().is_some()
+            || (kind == PageKind::Bbox && position >= u64::from(PAGE_GRID * PAGE_GRID))
+        {
+            return Err(invalid());
+        }
+        Ok(Self {
+            kind,
+            position,
+            offset,
+            log,
+            sequence,
+            query,
+        })
+    }
+
+    /// Where a scan of `kind` with query hash `query` starts: at the
+    /// beginning for `None`, or where `token` left off.
+    ///
+    /// Fails when the token is malformed or belongs to another query or
+    /// another log than `log`, and with [`SpatioError::StaleReplica`] when it
+    /// was issued at a later sequence than `last_sequence`.
+    pub(crate) fn resume(
+        token: Option<&str>,
+        kind: PageKind,
+        query: u64,
+        log: u64,
+        last_sequence: u64,
+    ) -> Result<Self> {
+        let Some(token) = token else {
+            return Ok(Self {
+                kind,
+                position: 0,
+                offset: 0,
+                log,
+                sequence: last_sequence,
+                query,
+            });
+        };
+        let token = Self::parse(token, kind)?;
+        if token.query != query {
+            return Err(SpatioError::InvalidInput(
+                "page token belongs to a different query".into(),
+            ));
+        }
+        if token.log != log {
+            return Err(SpatioError::InvalidInput(
+                "page token is from another database's log".into(),
+            ));
+        }
+        if token.sequence > last_sequence {
+            return Err(SpatioError::StaleReplica {
+                token_sequence: token.sequence,
+                last_sequence,
+            });
+        }
+        Ok(token)
+    }
+
+    /// Token resuming the same scan at `position`, after `offset` objects
+    /// there.
+    pub(crate) fn at(self, position: u64, offset: u64) -> String {
+        Self {
+            position,
+            offset,
+            ..self
+        }
+        .to_string()
+    }
+}
+
+impl fmt::Display for PageToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{:x}.{:x}.{:x}.{:x}.{:x}",
+            self.kind.prefix(),
+            self.position,
+            self.offset,
+            self.log,
+            self.sequence,
+            self.query
+        )
+    }
+}
+
+/// Stable hash of a scan's namespace (or key) and parameters (FNV-1a; must
+/// not change between builds, since tokens outlive the process that issued
+/// them).
+pub(crate) fn query_hash(namespace: &str, params: &[f64]) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    let bytes = namespace
+        .bytes()
+        .chain(params.iter().flat_map(|b| b.to_bits().to_le_bytes()));
+    for b in bytes {
+        h ^= u64::from(b);
+        h = h.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    h
+}
+
+/// Bounds of grid cell `cell` within `[min_x, min_y, max_x, max_y]`, and
+/// whether the cell is the last column/row (whose upper edges are inclusive).
+pub(crate) fn cell_bounds(bounds: [f64; 4], cell: u32) -> ([f64; 4], bool, bool) {
+    let [min_x, min_y, max_x, max_y] = bounds;
+    let (col, row) = (cell % PAGE_GRID, cell / PAGE_GRID);
+    let width = (max_x - min_x) / f64::from(PAGE_GRID);
+    let height = (max_y - min_y) / f64::from(PAGE_GRID);
+    let last_col = col + 1 == PAGE_GRID;
+    let last_row = row + 1 == PAGE_GRID;
+    let x0 = min_x + f64::from(col) * width;
+    let y0 = min_y + f64::from(row) * height;
+    // Computed like the neighbour's lower edge so adjacent cells share it exactly.
+    let x1 = if last_col {
+        max_x
+    } else {
+        min_x + f64::from(col + 1) * width
+    };
+    let y1 = if last_row {
+        max_y
+    } else {
+        min_y + f64::from(row + 1) * height
+    };
+    ([x0, y0, x1, y1], last_col, last_row)
+}
+
+/// One page of a paged scan.
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Token for the next page, or `None` when the scan is complete.
+    pub next_token: Option<String>,
+}
+
+/// One page of a bounding-box scan.
+pub type BboxPage<T> = Page<T>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_round_trip_and_rejection() {
+        let token = PageToken {
+            kind: PageKind::Bbox,
+            position: 63,
+            offset: 12,
+            log: 0xfeed,
+            sequence: 4096,
+            query: query_hash("fleet", &[0.0, 0.0, 1.0, 1.0]),
+        };
+        assert_eq!(
+            PageToken::parse(&token.to_string(), PageKind::Bbox).unwrap(),
+            token
+        );
+        assert!(PageToken::parse(&token.to_string(), PageKind::Radius).is_err());
+        let radius = PageToken {
+            kind: PageKind::Radius,
+            position: 250.5f64.to_bits(),
+            ..token
+        };
+        assert_eq!(
+            PageToken::parse(&radius.to_string(), PageKind::Radius).unwrap(),
+            radius
+        );
+
+        for bad in [
+            "",
+            "v1.0.0.0.0",
+            "v2.0.0.0.0.0",
+            "v1.40.0.0.0.0",
+            "v1.0.0.0.0.0.0",
+            "v1.x.0.0.0.0",
+        ] {
+            assert!(PageToken::parse(bad, PageKind::Bbox).is_err(), "{bad:?}");
+        }
+        assert_ne!(
+            query_hash("fleet", &[0.0, 0.0, 1.0, 1.0]),
+            query_hash("fleet", &[0.0, 0.0, 1.0, 2.0])
+        );
+    }
+}
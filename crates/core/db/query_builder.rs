@@ -0,0 +1,158 @@
+//! Fluent construction of composite queries.
+//!
+//! [`DB::select`] starts a [`QueryBuilder`] for a namespace; each filter adds
+//! a [`Predicate`] that must hold, and [`QueryBuilder::execute`] runs them as
+//! one [`DB::query`]. Ordering by distance reads every match before applying
+//! the limit, so prefer [`DB::knn`] for plain nearest-neighbor lookups.
+
+use crate::compute::query::Predicate;
+use crate::compute::validation;
+use crate::db::{CurrentLocation, DB, NearbyHit};
+use crate::error::{Result, SpatioError};
+use spatio_types::geo::{Point, Polygon};
+use std::sync::Arc;
+
+/// A query under construction. See [`DB::select`].
+#[derive(Clone)]
+#[must_use = "a query does nothing until executed"]
+pub struct QueryBuilder<'a> {
+    db: &'a DB,
+    namespace: String,
+    predicates: Vec<Predicate>,
+    anchor: Option<Point>,
+    ordered: bool,
+    limit: usize,
+}
+
+impl<'a> QueryBuilder<'a> {
+    pub(crate) fn new(db: &'a DB, namespace: &str) -> Self {
+        Self {
+            db,
+            namespace: namespace.to_string(),
+            predicates: Vec::new(),
+            anchor: None,
+            ordered: false,
+            limit: usize::MAX,
+        }
+    }
+
+    /// Keep objects within `radius` meters (horizontal haversine distance)
+    /// of `center`. Unless [`order_by_distance_from`](Self::order_by_distance_from)
+    /// sets one, the first such center is the anchor distances are measured
+    /// from.
+    pub fn within_radius(mut self, center: Point, radius: f64) -> Self {
+        self.anchor.get_or_insert(center);
+        self.filter(Predicate::WithinRadius { center, radius })
+    }
+
+    /// Keep objects inside a longitude/latitude box, edges included.
+    pub fn within_bbox(self, min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Self {
+        self.filter(Predicate::WithinBbox {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        })
+    }
+
+    /// Keep objects inside `polygon`.
+    pub fn within_polygon(self, polygon: Polygon) -> Self {
+        self.filter(Predicate::WithinPolygon(polygon))
+    }
+
+    /// Keep objects with altitude in `[min, max]`; `None` leaves that side
+    /// open.
+    pub fn altitude(self, min: Option<f64>, max: Option<f64>) -> Self {
+        self.filter(Predicate::Altitude { min, max })
+    }
+
+    /// Keep objects whose metadata has a top-level `field` equal to `value`.
+    pub fn filter_metadata(
+        self,
+        field: impl Into<String>,
+        value: impl Into<serde_json::Value>,
+    ) -> Self {
+        self.filter(Predicate::MetadataEquals {
+            field: field.into(),
+            value: value.into(),
+        })
+    }
+
+    /// Keep objects matching an arbitrary predicate.
+    pub fn filter(mut self, predicate: Predicate) -> Self {
+        self.predicates.push(predicate);
+        self
+    }
+
+    /// Return results nearest the anchor first.
+    pub fn order_by_distance(mut self) -> Self {
+        self.ordered = true;
+        self
+    }
+
+    /// Return results nearest `anchor` first.
+    pub fn order_by_distance_from(mut self, anchor: Point) -> Self {
+        self.anchor = Some(anchor);
+        self.order_by_distance()
+    }
+
+    /// Return at most `limit` results (unlimited by default).
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Run the query.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`DB::query`], or with [`SpatioError::InvalidInput`] when
+    /// ordering by distance without an anchor.
+    pub fn execute(self) -> Result<Vec<Arc<CurrentLocation>>> {
+        if !self.ordered {
+            return self
+                .db
+                .query(&self.namespace, &self.predicate(), self.limit);
+        }
+        Ok(self
+            .execute_with_distances()?
+            .into_iter()
+            .map(|hit| hit.location)
+            .collect())
+    }
+
+    /// Run the query, pairing each result with its distance from the anchor
+    /// in the namespace's metric.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`execute`](Self::execute), or with
+    /// [`SpatioError::InvalidInput`] when there is no anchor.
+    pub fn execute_with_distances(self) -> Result<Vec<NearbyHit>> {
+        let anchor = self.anchor.ok_or_else(|| {
+            SpatioError::InvalidInput(
+                "distances need within_radius or order_by_distance_from".to_string(),
+            )
+        })?;
+        validation::validate_geographic_point(&anchor)?;
+        let limit = if self.ordered { usize::MAX } else { self.limit };
+        let results = self.db.query(&self.namespace, &self.predicate(), limit)?;
+        let mut hits = self.db.with_distances(&self.namespace, results, &anchor);
+        if self.ordered {
+            hits.sort_by(|a, b| {
+                a.distance
+                    .total_cmp(&b.distance)
+                    .then_with(|| a.object_id().cmp(b.object_id()))
+            });
+            hits.truncate(self.limit);
+        }
+        Ok(hits)
+    }
+
+    fn predicate(&self) -> Predicate {
+        match self.predicates.as_slice() {
+            [single] => single.clone(),
+            all => Predicate::All(all.to_vec()),
+        }
+    }
+}
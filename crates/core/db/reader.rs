@@ -0,0 +1,328 @@
+//! Snapshot readers: read-only handles over a recent copy of the hot state.
+//!
+//! A [`DBReader`] queries an immutable snapshot that writers never touch, so
+//! readers don't wait on index locks held by an ingesting writer, and a
+//! reader sees the same state for its whole lifetime.
+//!
+//! Snapshots are published through an atomic pointer: handing one out is a
+//! pointer load and a refcount bump. When the published snapshot is older
+//! than the configured refresh interval and writes have happened since, the
+//! next [`crate::DB::reader`] call copies the hot state and publishes the
+//! copy; concurrent callers keep using the previous snapshot meanwhile. A
+//! snapshot is freed when the last reader holding it is dropped.
+
+use super::{CurrentLocation, DB, LocationUpdate, NearbyHit, Zone, ZoneHit};
+use crate::compute::query::Predicate;
+use crate::config::{ScanDirection, TimeRange};
+use crate::db::hot_state::HotState;
+use crate::error::Result;
+use arc_swap::ArcSwapOption;
+use parking_lot::Mutex;
+use spatio_types::geo::{Point, Polygon};
+use spatio_types::point::Point3d;
+use std::ops::RangeBounds;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A copy of the hot state and when it was taken.
+struct Snapshot {
+    hot: Arc<HotState>,
+    taken: Instant,
+}
+
+/// The snapshot currently handed out to readers.
+#[derive(Default)]
+pub(crate) struct Snapshots {
+    current: ArcSwapOption<Snapshot>,
+    /// Held while a new snapshot is copied, so only one caller pays for it.
+    refreshing: Mutex<()>,
+}
+
+impl Snapshots {
+    /// The published snapshot of `hot`, replaced first if it is older than
+    /// `max_age` and out of date.
+    fn get(&self, hot: &HotState, max_age: Duration) -> Arc<Snapshot> {
+        if let Some(snapshot) = self.current.load_full() {
+            let fresh = snapshot.hot.version() == hot.version();
+            if fresh || snapshot.taken.elapsed() < max_age {
+                return snapshot;
+            }
+            // Someone else is refreshing; the current snapshot will do.
+            let Some(_refreshing) = self.refreshing.try_lock() else {
+                return snapshot;
+            };
+            return self.refresh(hot);
+        }
+        let _refreshing = self.refreshing.lock();
+        match self.current.load_full() {
+            Some(snapshot) => snapshot,
+            None => self.refresh(hot),
+        }
+    }
+
+    fn refresh(&self, hot: &HotState) -> Arc<Snapshot> {
+        let snapshot = Arc::new(Snapshot {
+            hot: Arc::new(hot.snapshot()),
+            taken: Instant::now(),
+        });
+        self.current.store(Some(snapshot.clone()));
+        snapshot
+    }
+}
+
+impl DB {
+    /// A read-only handle over a recent snapshot of current locations and
+    /// zones.
+    ///
+    /// Cheap to create and to clone, so a server can take one per request.
+    /// The snapshot lags writes by at most [`crate::Config::reader_refresh_ms`]
+    /// (as of when the reader was created) and stays fixed for the reader's
+    /// lifetime. Trajectory queries read the live history.
+    ///
+    /// ```
+    /// use spatio::{DB, Point3d};
+    ///
+    /// let db = DB::memory().unwrap();
+    /// db.upsert("fleet", "truck", Point3d::new(1.0, 2.0, 0.0), serde_json::json!({}), None)
+    ///     .unwrap();
+    /// let reader = db.reader();
+    /// db.delete("fleet", "truck").unwrap();
+    ///
+    /// assert!(reader.get("fleet", "truck").unwrap().is_some());
+    /// ```
+    pub fn reader(&self) -> DBReader {
+        self.hydration.wait_all();
+        let max_age = Duration::from_millis(self.config.reader_refresh_ms);
+        let snapshot = self.snapshots.get(&self.hot, max_age);
+        DBReader {
+            db: DB {
+                hot: snapshot.hot.clone(),
+                // The indexes follow the live state, not the snapshot.
+                metadata_indexes: Arc::default(),
+                ..self.clone()
+            },
+            taken: snapshot.taken,
+        }
+    }
+}
+
+/// Read-only view of a database snapshot (see [`DB::reader`]).
+#[derive(Clone)]
+pub struct DBReader {
+    /// The database with its hot state replaced by the snapshot. Only read
+    /// methods are exposed, so the snapshot is never written.
+    db: DB,
+    taken: Instant,
+}
+
+impl DBReader {
+    /// How long ago the snapshot was taken.
+    pub fn age(&self) -> Duration {
+        self.taken.elapsed()
+    }
+
+    pub fn get(&self, namespace: &str, object_id: &str) -> Result<Option<Arc<CurrentLocation>>> {
+        self.db.get(namespace, object_id)
+    }
+
+    pub fn range<'a>(
+        &self,
+        namespace: &str,
+        range: impl RangeBounds<&'a str>,
+        limit: usize,
+        direction: ScanDirection,
+    ) -> Result<Vec<Arc<CurrentLocation>>> {
+        self.db.range(namespace, range, limit, direction)
+    }
+
+    pub fn query_radius(
+        &self,
+        namespace: &str,
+        center: &Point3d,
+        radius: f64,
+        limit: usize,
+    ) -> Result<Vec<NearbyHit>> {
+        self.db.query_radius(namespace, center, radius, limit)
+    }
+
+    pub fn query_bbox(
+        &self,
+        namespace: &str,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        limit: usize,
+    ) -> Result<Vec<Arc<CurrentLocation>>> {
+        self.db
+            .query_bbox(namespace, min_x, min_y, max_x, max_y, limit)
+    }
+
+    pub fn query_within_cylinder(
+        &self,
+        namespace: &str,
+        center: Point,
+        min_z: f64,
+        max_z: f64,
+        radius: f64,
+        limit: usize,
+    ) -> Result<Vec<NearbyHit>> {
+        self.db
+            .query_within_cylinder(namespace, center, min_z, max_z, radius, limit)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn query_within_bbox_3d(
+        &self,
+        namespace: &str,
+        min_x: f64,
+        min_y: f64,
+        min_z: f64,
+        max_x: f64,
+        max_y: f64,
+        max_z: f64,
+        limit: usize,
+    ) -> Result<Vec<Arc<CurrentLocation>>> {
+        self.db
+            .query_within_bbox_3d(namespace, min_x, min_y, min_z, max_x, max_y, max_z, limit)
+    }
+
+    pub fn knn(&self, namespace: &str, center: &Point3d, k: usize) -> Result<Vec<NearbyHit>> {
+        self.db.knn(namespace, center, k)
+    }
+
+    pub fn query_near(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        radius: f64,
+        limit: usize,
+    ) -> Result<Vec<NearbyHit>> {
+        self.db.query_near(namespace, object_id, radius, limit)
+    }
+
+    pub fn knn_near_object(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        k: usize,
+    ) -> Result<Vec<NearbyHit>> {
+        self.db.knn_near_object(namespace, object_id, k)
+    }
+
+    pub fn query_polygon(
+        &self,
+        namespace: &str,
+        polygon: &Polygon,
+        limit: usize,
+    ) -> Result<Vec<Arc<CurrentLocation>>> {
+        self.db.query_polygon(namespace, polygon, limit)
+    }
+
+    pub fn query(
+        &self,
+        namespace: &str,
+        predicate: &Predicate,
+        limit: usize,
+    ) -> Result<Vec<Arc<CurrentLocation>>> {
+        self.db.query(namespace, predicate, limit)
+    }
+
+    pub fn distance_between(
+        &self,
+        namespace: &str,
+        id1: &str,
+        id2: &str,
+        metric: crate::compute::spatial::DistanceMetric,
+    ) -> Result<Option<f64>> {
+        self.db.distance_between(namespace, id1, id2, metric)
+    }
+
+    pub fn get_zone(&self, namespace: &str, zone_id: &str) -> Result<Option<Arc<Zone>>> {
+        self.db.get_zone(namespace, zone_id)
+    }
+
+    pub fn nearest_zones(&self, namespace: &str, point: &Point, k: usize) -> Result<Vec<ZoneHit>> {
+        self.db.nearest_zones(namespace, point, k)
+    }
+
+    /// Trajectory history, read live rather than from the snapshot.
+    pub fn query_trajectory(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        range: impl Into<TimeRange>,
+        limit: usize,
+    ) -> Result<Vec<LocationUpdate>> {
+        self.db.query_trajectory(namespace, object_id, range, limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn upsert(db: &DB, id: &str, x: f64) {
+        db.upsert(
+            "fleet",
+            id,
+            Point3d::new(x, 0.0, 0.0),
+            serde_json::json!({}),
+            None,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_reader_sees_a_fixed_snapshot() {
+        let db =
+            DB::memory_with_config(Config::default().with_reader_refresh_interval(Duration::ZERO))
+                .unwrap();
+        upsert(&db, "a", 1.0);
+        let before = db.reader();
+
+        upsert(&db, "a", 2.0);
+        upsert(&db, "b", 3.0);
+        let after = db.reader();
+
+        let x = |reader: &DBReader, id: &str| {
+            reader.get("fleet", id).unwrap().map(|loc| loc.position.x())
+        };
+        assert_eq!(x(&before, "a"), Some(1.0));
+        assert_eq!(x(&before, "b"), None);
+        assert_eq!(x(&after, "a"), Some(2.0));
+        assert_eq!(
+            after
+                .query_radius("fleet", &Point3d::new(3.0, 0.0, 0.0), 1.0, 10)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(
+            before
+                .query_radius("fleet", &Point3d::new(3.0, 0.0, 0.0), 1.0, 10)
+                .unwrap()
+                .is_empty()
+        );
+
+        // Unchanged state reuses the published snapshot.
+        assert!(Arc::ptr_eq(&after.db.hot, &db.reader().db.hot));
+    }
+
+    #[test]
+    fn test_reader_lags_by_at_most_the_refresh_interval() {
+        let db = DB::memory_with_config(
+            Config::default().with_reader_refresh_interval(Duration::from_secs(3600)),
+        )
+        .unwrap();
+        upsert(&db, "a", 1.0);
+        let first = db.reader();
+        upsert(&db, "b", 2.0);
+
+        let second = db.reader();
+        assert!(Arc::ptr_eq(&first.db.hot, &second.db.hot));
+        assert!(second.get("fleet", "b").unwrap().is_none());
+        assert!(db.get("fleet", "b").unwrap().is_some());
+    }
+}
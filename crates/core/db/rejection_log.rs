@@ -0,0 +1,219 @@
+//! Rate-limited log of rejected writes.
+//!
+//! Each write that fails validation is recorded as one NDJSON
+//! [`RejectionEntry`] line naming the reason, the namespace and object, and a
+//! digest of the payload, so operators can see why device updates are being
+//! dropped without the log growing with the traffic. At most
+//! `max_per_second` entries are written per second; the rest are counted and
+//! reported in the next entry's `suppressed` field.
+
+use crate::compute::validation::ValidationError;
+use crate::config::RejectionLogConfig;
+use crate::db::access_log::{micros, rewrite_without};
+use crate::db::cold_state::crc32;
+use crate::error::{Result, SpatioError};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// One line of the rejection log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RejectionEntry {
+    /// Wall-clock time of the rejection (microseconds since the Unix epoch).
+    pub ts_micros: u64,
+    pub namespace: String,
+    pub object_id: String,
+    /// Short machine-readable cause, e.g. `"out_of_range"` or `"non_finite"`.
+    pub reason: String,
+    /// The full error message.
+    pub detail: String,
+    /// CRC-32 of the rejected payload's JSON, as 8 hex digits, for spotting
+    /// the same bad report arriving repeatedly.
+    pub digest: String,
+    /// The start of the payload's JSON, cut at `sample_bytes`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample: Option<String>,
+    /// Rejections not logged since the previous entry because of the rate
+    /// limit.
+    #[serde(default)]
+    pub suppressed: u64,
+}
+
+impl RejectionEntry {
+    /// Parse one NDJSON line of a rejection log.
+    pub fn parse(line: &str) -> Result<Self> {
+        serde_json::from_str(line).map_err(|e| {
+            SpatioError::SerializationErrorWithContext(format!(
+                "Invalid rejection log entry: {}",
+                e
+            ))
+        })
+    }
+}
+
+/// The reason code of a rejected write's error.
+fn reason(error: &SpatioError) -> &'static str {
+    fn validation_reason(error: &ValidationError) -> &'static str {
+        match error {
+            ValidationError::NonFinite { .. } => "non_finite",
+            ValidationError::OutOfRange { .. } => "out_of_range",
+            ValidationError::NotPositive { .. } => "not_positive",
+            ValidationError::EmptyRange { .. } => "empty_range",
+            ValidationError::Element { error, .. } => validation_reason(error),
+        }
+    }
+    match error {
+        SpatioError::Validation(error) => validation_reason(error),
+        SpatioError::InvalidInput(_) => "invalid_input",
+        _ => "other",
+    }
+}
+
+struct Window {
+    /// Whole seconds since the epoch the current window covers.
+    second: u64,
+    emitted: u32,
+    suppressed: u64,
+}
+
+/// Append-only rate-limited writer for [`RejectionEntry`] lines.
+pub(crate) struct RejectionLog {
+    path: PathBuf,
+    writer: Mutex<BufWriter<File>>,
+    window: Mutex<Window>,
+    max_per_second: u32,
+    sample_bytes: usize,
+}
+
+impl RejectionLog {
+    pub(crate) fn open(config: &RejectionLogConfig) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)?;
+        Ok(Self {
+            path: config.path.clone(),
+            writer: Mutex::new(BufWriter::new(file)),
+            window: Mutex::new(Window {
+                second: 0,
+                emitted: 0,
+                suppressed: 0,
+            }),
+            max_per_second: config.max_per_second,
+            sample_bytes: config.sample_bytes,
+        })
+    }
+
+    /// Take a slot in the current second's budget, returning how many
+    /// rejections were suppressed since the last one logged, or `None` if
+    /// this one is suppressed too.
+    fn admit(&self, now_micros: u64) -> Option<u64> {
+        let second = now_micros / 1_000_000;
+        let mut window = self.window.lock();
+        if window.second != second {
+            window.second = second;
+            window.emitted = 0;
+        }
+        if window.emitted >= self.max_per_second {
+            window.suppressed += 1;
+            return None;
+        }
+        window.emitted += 1;
+        Some(std::mem::take(&mut window.suppressed))
+    }
+
+    /// Record a rejected write. `payload` is only built for logged rejections.
+    pub(crate) fn record(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        error: &SpatioError,
+        payload: impl FnOnce() -> serde_json::Value,
+    ) {
+        let ts_micros = micros(SystemTime::now());
+        let Some(suppressed) = self.admit(ts_micros) else {
+            return;
+        };
+        let payload = payload().to_string();
+        let sample = (self.sample_bytes > 0).then(|| {
+            let mut end = self.sample_bytes.min(payload.len());
+            while !payload.is_char_boundary(end) {
+                end -= 1;
+            }
+            payload[..end].to_string()
+        });
+        let entry = RejectionEntry {
+            ts_micros,
+            namespace: namespace.to_string(),
+            object_id: object_id.to_string(),
+            reason: reason(error).to_string(),
+            detail: error.to_string(),
+            digest: format!("{:08x}", crc32(payload.as_bytes())),
+            sample,
+            suppressed,
+        };
+        let Ok(line) = serde_json::to_string(&entry) else {
+            return;
+        };
+
+        // Best-effort: diagnostics must never change the write's outcome.
+        let mut writer = self.writer.lock();
+        if let Err(e) = writeln!(writer, "{}", line) {
+            log::warn!("Failed to write rejection log entry: {}", e);
+        }
+    }
+
+    pub(crate) fn flush(&self) -> Result<()> {
+        self.writer.lock().flush()?;
+        Ok(())
+    }
+
+    /// Rewrite the log without entries for `object_id` in `namespace`,
+    /// returning how many were removed. Unparseable lines are kept.
+    pub(crate) fn forget_object(&self, namespace: &str, object_id: &str) -> Result<u64> {
+        rewrite_without(&self.path, &mut self.writer.lock(), |line| {
+            RejectionEntry::parse(line)
+                .is_ok_and(|entry| entry.namespace == namespace && entry.object_id == object_id)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limit_reports_suppressed_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = RejectionLog::open(
+            &RejectionLogConfig::new(dir.path().join("rejections.log")).with_max_per_second(2),
+        )
+        .unwrap();
+
+        assert_eq!(log.admit(1_000_000), Some(0));
+        assert_eq!(log.admit(1_100_000), Some(0));
+        assert_eq!(log.admit(1_200_000), None);
+        assert_eq!(log.admit(1_300_000), None);
+        assert_eq!(log.admit(2_000_000), Some(2));
+        assert_eq!(log.admit(2_500_000), Some(0));
+    }
+
+    #[test]
+    fn test_reason_looks_through_elements() {
+        let error = SpatioError::Validation(ValidationError::Element {
+            location: "batch item 3".to_string(),
+            error: Box::new(ValidationError::NonFinite {
+                field: "latitude",
+                value: f64::NAN,
+            }),
+        });
+        assert_eq!(reason(&error), "non_finite");
+        assert_eq!(
+            reason(&SpatioError::InvalidInput("bad id".into())),
+            "invalid_input"
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! Segment files of the trajectory log.
+//!
+//! With [`PersistenceConfig::segment_secs`] set, the log is split by write
+//! time: the log path holds the first segment, and a write in a later time
+//! bucket than the current segment starts the next one, `<log>.seg-1`,
+//! `<log>.seg-2` and so on. Only the last segment is appended to. Offsets
+//! into the log are offsets into its segments read back to back (see
+//! [`SegmentReader`]), so a log that was never split is simply a log of one
+//! segment, and everything that records a log offset works the same on both.
+//!
+//! [`PersistenceConfig::segment_secs`]: crate::config::PersistenceConfig::segment_secs
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+const SEGMENT_INFIX: &str = ".seg-";
+
+/// Path of segment `number` of the log at `log_path`; segment 0 is the log
+/// path itself.
+pub(crate) fn segment_path(log_path: &Path, number: u64) -> PathBuf {
+    if number == 0 {
+        return log_path.to_path_buf();
+    }
+    let mut s = log_path.as_os_str().to_os_string();
+    s.push(format!("{}{}", SEGMENT_INFIX, number));
+    PathBuf::from(s)
+}
+
+/// Numbers of the segments of the log at `log_path`, oldest first. Segment 0
+/// is always listed, whether or not it exists yet.
+pub(crate) fn list(log_path: &Path) -> io::Result<Vec<u64>> {
+    let mut numbers = vec![0];
+    let Some(name) = log_path.file_name().and_then(|name| name.to_str()) else {
+        return Ok(numbers);
+    };
+    let prefix = format!("{}{}", name, SEGMENT_INFIX);
+    let dir = match log_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(numbers),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        if let Some(number) = entry
+            .file_name()
+            .to_str()
+            .and_then(|file| file.strip_prefix(&prefix))
+            .and_then(|number| number.parse::<u64>().ok())
+            .filter(|&number| number > 0)
+        {
+            numbers.push(number);
+        }
+    }
+    numbers.sort_unstable();
+    Ok(numbers)
+}
+
+/// Paths of segments `numbers` of the log at `log_path`.
+pub(crate) fn paths(log_path: &Path, numbers: &[u64]) -> Vec<PathBuf> {
+    numbers
+        .iter()
+        .map(|&number| segment_path(log_path, number))
+        .collect()
+}
+
+/// Bytes in the files at `paths` (missing ones count as empty).
+pub(crate) fn total_len(paths: &[PathBuf]) -> u64 {
+    paths
+        .iter()
+        .map(|path| std::fs::metadata(path).map_or(0, |m| m.len()))
+        .sum()
+}
+
+/// Reads segment files back to back as one log. Each file's length is
+/// taken when it is opened, so bytes appended afterwards are not read; a
+/// missing file reads as empty.
+pub(crate) struct SegmentReader {
+    /// Each segment's file, offset in the log and length.
+    files: Vec<(Option<File>, u64, u64)>,
+    /// Segment being read.
+    current: usize,
+    /// Offset in the log of the next byte read.
+    offset: u64,
+}
+
+impl SegmentReader {
+    pub fn open(paths: &[PathBuf]) -> io::Result<Self> {
+        let mut files = Vec::with_capacity(paths.len());
+        let mut start = 0;
+        for path in paths {
+            let (file, len) = match File::open(path) {
+                Ok(file) => {
+                    let len = file.metadata()?.len();
+                    (Some(file), len)
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => (None, 0),
+                Err(e) => return Err(e),
+            };
+            files.push((file, start, len));
+            start += len;
+        }
+        Ok(Self {
+            files,
+            current: 0,
+            offset: 0,
+        })
+    }
+
+    /// Bytes in the log as opened.
+    pub fn len(&self) -> u64 {
+        self.files.last().map_or(0, |(_, start, len)| start + len)
+    }
+}
+
+impl Read for SegmentReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while let Some((file, start, len)) = self.files.get_mut(self.current) {
+            let end = *start + *len;
+            let remaining = end.saturating_sub(self.offset);
+            if remaining > 0
+                && let Some(file) = file
+            {
+                let want = buf
+                    .len()
+                    .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+                let read = file.read(&mut buf[..want])?;
+                if read > 0 {
+                    self.offset += read as u64;
+                    return Ok(read);
+                }
+                // Shorter than when opened: read on from the next segment.
+            }
+            self.current += 1;
+            self.offset = end;
+            if let Some((Some(next), _, _)) = self.files.get_mut(self.current) {
+                next.seek(SeekFrom::Start(0))?;
+            }
+        }
+        Ok(0)
+    }
+}
+
+impl Seek for SegmentReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => self.offset.checked_add_signed(delta),
+            SeekFrom::End(delta) => self.len().checked_add_signed(delta),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before the log"))?;
+        // The segment holding `target`, or the last one past the end.
+        let index = self
+            .files
+            .partition_point(|(_, start, len)| start + len <= target)
+            .min(self.files.len().saturating_sub(1));
+        if let Some((Some(file), start, _)) = self.files.get_mut(index) {
+            file.seek(SeekFrom::Start(target.saturating_sub(*start)))?;
+        }
+        self.current = index;
+        self.offset = target;
+        Ok(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+
+    #[test]
+    fn test_segments_read_as_one_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("traj.log");
+        assert_eq!(list(&log).unwrap(), [0]);
+        std::fs::write(segment_path(&log, 0), "a\nb\n").unwrap();
+        std::fs::write(segment_path(&log, 2), "").unwrap();
+        std::fs::write(segment_path(&log, 10), "c\nd\n").unwrap();
+        std::fs::write(dir.path().join("traj.log.seg-x"), "").unwrap();
+        std::fs::write(dir.path().join("other.log.seg-3"), "").unwrap();
+        let numbers = list(&log).unwrap();
+        assert_eq!(numbers, [0, 2, 10]);
+
+        let paths = paths(&log, &numbers);
+        assert_eq!(total_len(&paths), 8);
+        let mut reader = SegmentReader::open(&paths).unwrap();
+        let mut all = String::new();
+        reader.read_to_string(&mut all).unwrap();
+        assert_eq!(all, "a\nb\nc\nd\n");
+
+        // Lines are found by offset in the whole log, across segments.
+        for (offset, line) in [(6, "d\n"), (2, "b\n"), (4, "c\n")] {
+            reader.seek(SeekFrom::Start(offset)).unwrap();
+            let mut read = String::new();
+            std::io::BufReader::new(&mut reader)
+                .read_line(&mut read)
+                .unwrap();
+            assert_eq!(read, line);
+        }
+
+        // Bytes appended after opening aren't read.
+        std::fs::write(segment_path(&log, 10), "c\nd\ne\n").unwrap();
+        reader.seek(SeekFrom::Start(0)).unwrap();
+        let mut all = String::new();
+        reader.read_to_string(&mut all).unwrap();
+        assert_eq!(all, "a\nb\nc\nd\n");
+    }
+}
@@ -0,0 +1,142 @@
+//! Slow-query log.
+//!
+//! With [`Config::slow_query_threshold_ms`](crate::config::Config::slow_query_threshold_ms)
+//! set, spatial queries are timed, and those reaching the threshold are
+//! logged at warn level and kept, most recent last, for
+//! [`DB::slow_queries`](crate::DB::slow_queries). Each records the index
+//! candidates left after envelope pruning, so a query that's slow because it
+//! checks too many points (a coarse geohash precision, a hot cell) can be
+//! told apart from one that's slow for other reasons.
+
+use crate::compute::spatial::point_index;
+use parking_lot::Mutex;
+use spatio_types::stats::Operation;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Slow queries kept for [`DB::slow_queries`](crate::DB::slow_queries).
+pub const SLOW_QUERY_LOG_CAPACITY: usize = 256;
+
+/// A query that took at least the configured threshold.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct SlowQuery {
+    pub namespace: String,
+    pub operation: Operation,
+    pub started_at: SystemTime,
+    pub duration: Duration,
+    /// Points handed out by the spatial index before exact distance or
+    /// containment checks.
+    pub candidates: u64,
+}
+
+pub(crate) struct SlowQueries {
+    threshold: Option<Duration>,
+    recent: Mutex<VecDeque<SlowQuery>>,
+}
+
+impl SlowQueries {
+    pub fn new(threshold: Option<Duration>) -> Self {
+        Self {
+            threshold,
+            recent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Start timing a query, if a threshold is configured. The query is
+    /// checked against it when the returned timer drops.
+    #[inline]
+    pub fn start<'a>(&'a self, namespace: &'a str, operation: Operation) -> Option<QueryTimer<'a>> {
+        let threshold = self.threshold?;
+        Some(QueryTimer {
+            log: self,
+            threshold,
+            namespace,
+            operation,
+            started_at: SystemTime::now(),
+            start: Instant::now(),
+            candidates: point_index::candidates_examined(),
+        })
+    }
+
+    /// Slow queries still kept, oldest first.
+    pub fn recent(&self) -> Vec<SlowQuery> {
+        self.recent.lock().iter().cloned().collect()
+    }
+
+    fn record(&self, query: SlowQuery) {
+        log::warn!(
+            "Slow {:?} query on namespace '{}': {:?}, {} candidates",
+            query.operation,
+            query.namespace,
+            query.duration,
+            query.candidates
+        );
+        let mut recent = self.recent.lock();
+        if recent.len() == SLOW_QUERY_LOG_CAPACITY {
+            recent.pop_front();
+        }
+        recent.push_back(query);
+    }
+}
+
+/// Times one query; see [`SlowQueries::start`].
+pub(crate) struct QueryTimer<'a> {
+    log: &'a SlowQueries,
+    threshold: Duration,
+    namespace: &'a str,
+    operation: Operation,
+    started_at: SystemTime,
+    start: Instant,
+    candidates: u64,
+}
+
+impl Drop for QueryTimer<'_> {
+    fn drop(&mut self) {
+        let duration = self.start.elapsed();
+        // Searches run on the calling thread, so the difference is this query's.
+        let candidates = point_index::candidates_examined().wrapping_sub(self.candidates);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            elapsed_us = duration.as_micros() as u64,
+            candidates,
+            "spatio.query_done"
+        );
+        if duration >= self.threshold {
+            self.log.record(SlowQuery {
+                namespace: self.namespace.to_string(),
+                operation: self.operation,
+                started_at: self.started_at,
+                duration,
+                candidates,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_only_queries_over_threshold() {
+        let log = SlowQueries::new(Some(Duration::from_millis(20)));
+        drop(log.start("fleet", Operation::Knn));
+        assert!(log.recent().is_empty());
+
+        let timer = log.start("fleet", Operation::QueryRadius);
+        std::thread::sleep(Duration::from_millis(25));
+        drop(timer);
+        let recent = log.recent();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].namespace, "fleet");
+        assert_eq!(recent[0].operation, Operation::QueryRadius);
+        assert!(recent[0].duration >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_untimed_without_threshold() {
+        let log = SlowQueries::new(None);
+        assert!(log.start("fleet", Operation::Knn).is_none());
+    }
+}
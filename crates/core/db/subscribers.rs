@@ -0,0 +1,58 @@
+//! Bounded event channels for views, fences and the change feed.
+//!
+//! Each subscriber gets a channel of [`SUBSCRIBER_CAPACITY`] events. One that
+//! falls that far behind is dropped so writers never block on it: its channel
+//! disconnects, and it should re-read the state it mirrors and subscribe
+//! again. Subscribers that hung up are noticed on their next event.
+
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError, sync_channel};
+
+/// Events a subscriber may fall behind by before it is disconnected.
+pub const SUBSCRIBER_CAPACITY: usize = 1024;
+
+/// Senders of one event stream, each with a `filter` choosing the events it
+/// wants.
+pub(crate) struct Subscribers<E, F = ()> {
+    senders: Mutex<Vec<(F, SyncSender<E>)>>,
+    /// Length of `senders`, readable without the lock.
+    len: AtomicUsize,
+}
+
+impl<E, F> Default for Subscribers<E, F> {
+    fn default() -> Self {
+        Self {
+            senders: Mutex::new(Vec::new()),
+            len: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<E: Clone, F> Subscribers<E, F> {
+    pub fn subscribe(&self, filter: F) -> Receiver<E> {
+        let (tx, rx) = sync_channel(SUBSCRIBER_CAPACITY);
+        let mut senders = self.senders.lock();
+        senders.push((filter, tx));
+        self.len.store(senders.len(), Ordering::Release);
+        rx
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len.load(Ordering::Acquire) == 0
+    }
+
+    /// Send `event` to every subscriber whose filter `wants` it, dropping
+    /// the ones that are full or gone.
+    pub fn publish(&self, event: &E, wants: impl Fn(&F) -> bool) {
+        let mut senders = self.senders.lock();
+        senders.retain(|(filter, tx)| {
+            !wants(filter)
+                || match tx.try_send(event.clone()) {
+                    Ok(()) => true,
+                    Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => false,
+                }
+        });
+        self.len.store(senders.len(), Ordering::Release);
+    }
+}
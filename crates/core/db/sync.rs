@@ -1,16 +1,22 @@
 //! Thread-safe wrapper for concurrent database access.
 //!
-//! This module provides `SyncDB`, a thread-safe wrapper around `DB`.
-//! Since `DB` is now inherently thread-safe (using DashMap and internal locking),
-//! `SyncDB` is just a lightweight wrapper for API compatibility.
+//! This module provides `SyncDB`, a thread-safe wrapper around `DB`. `DB` is
+//! itself safe to share between threads, so `SyncDB` adds no lock of its own
+//! and is kept for API compatibility.
+//!
+//! Concurrency comes from the hot state (see [`HotState`](super::HotState)):
+//! current locations sit in a concurrent map, and each namespace's spatial
+//! index is a separate shard behind its own `RwLock`. Queries take only read
+//! locks, so any number of them run in parallel; a write that moves an
+//! object holds its namespace's write lock just for the index update, and
+//! never blocks queries on other namespaces.
 
-use crate::config::{Config, DbStats, SetOptions};
-use crate::db::{CurrentLocation, DB, LocationUpdate};
+use crate::config::{Config, DbStats, SetOptions, TimeRange};
+use crate::db::{CurrentLocation, DB, LocationUpdate, NearbyHit};
 use crate::error::Result;
 use std::path::Path;
-use std::time::SystemTime;
 
-/// Thread-safe wrapper around `DB`.
+/// Thread-safe wrapper around `DB`. Clones share the same database.
 #[derive(Clone)]
 pub struct SyncDB {
     inner: DB,
@@ -50,7 +56,7 @@ impl SyncDB {
         self.inner.stats()
     }
 
-    /// Upsert an object's location.
+    /// Upsert an object's location, returning the write's sequence number.
     pub fn upsert(
         &self,
         namespace: &str,
@@ -58,7 +64,7 @@ impl SyncDB {
         position: spatio_types::point::Point3d,
         metadata: serde_json::Value,
         opts: Option<SetOptions>,
-    ) -> Result<()> {
+    ) -> Result<u64> {
         self.inner
             .upsert(namespace, object_id, position, metadata, opts)
     }
@@ -72,8 +78,8 @@ impl SyncDB {
         self.inner.get(namespace, object_id)
     }
 
-    /// Delete an object from the database.
-    pub fn delete(&self, namespace: &str, object_id: &str) -> Result<()> {
+    /// Delete an object from the database, returning the deletion's sequence.
+    pub fn delete(&self, namespace: &str, object_id: &str) -> Result<u64> {
         self.inner.delete(namespace, object_id)
     }
 
@@ -84,7 +90,7 @@ impl SyncDB {
         center: &spatio_types::point::Point3d,
         radius: f64,
         limit: usize,
-    ) -> Result<Vec<(std::sync::Arc<CurrentLocation>, f64)>> {
+    ) -> Result<Vec<NearbyHit>> {
         self.inner.query_radius(namespace, center, radius, limit)
     }
 
@@ -95,7 +101,7 @@ impl SyncDB {
         object_id: &str,
         radius: f64,
         limit: usize,
-    ) -> Result<Vec<(std::sync::Arc<CurrentLocation>, f64)>> {
+    ) -> Result<Vec<NearbyHit>> {
         self.inner.query_near(namespace, object_id, radius, limit)
     }
 
@@ -104,12 +110,11 @@ impl SyncDB {
         &self,
         namespace: &str,
         object_id: &str,
-        start_time: SystemTime,
-        end_time: SystemTime,
+        range: impl Into<TimeRange>,
         limit: usize,
     ) -> Result<Vec<LocationUpdate>> {
         self.inner
-            .query_trajectory(namespace, object_id, start_time, end_time, limit)
+            .query_trajectory(namespace, object_id, range, limit)
     }
 
     /// Close the database.
@@ -117,3 +122,55 @@ impl SyncDB {
         self.inner.close()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spatio_types::point::Point3d;
+
+    #[test]
+    fn test_concurrent_readers_and_writers() {
+        let db = SyncDB::memory().unwrap();
+        for i in 0..100 {
+            let position = Point3d::new(f64::from(i) * 0.001, 0.0, 0.0);
+            db.upsert(
+                "read",
+                &format!("o{i}"),
+                position,
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+        }
+
+        let center = Point3d::new(0.05, 0.0, 0.0);
+        std::thread::scope(|scope| {
+            for writer in 0..2 {
+                let db = db.clone();
+                scope.spawn(move || {
+                    for i in 0..500 {
+                        let position = Point3d::new(f64::from(i % 50) * 0.001, 0.0, 0.0);
+                        let namespace = if writer == 0 { "write" } else { "read" };
+                        // The second writer moves objects the readers are
+                        // querying, back and forth within the query circle.
+                        let id = format!("o{}", i % 100);
+                        db.upsert(namespace, &id, position, serde_json::json!({}), None)
+                            .unwrap();
+                    }
+                });
+            }
+            for _ in 0..4 {
+                let db = db.clone();
+                let center = center.clone();
+                scope.spawn(move || {
+                    for _ in 0..200 {
+                        let found = db.query_radius("read", &center, 100_000.0, 1000).unwrap();
+                        assert_eq!(found.len(), 100);
+                    }
+                });
+            }
+        });
+
+        assert_eq!(db.stats().hot_state_objects, 200);
+    }
+}
@@ -0,0 +1,453 @@
+//! Consistency checking and repair across the structures that hold the same
+//! data.
+//!
+//! Current locations live in a map, are indexed again in the per-namespace
+//! spatial index and ID order, and are derived from the trajectory log (via
+//! the recovery checkpoint on restart). A bug that lets these drift apart
+//! shows up only as queries quietly missing or inventing objects;
+//! [`DB::verify`] compares them directly and lists every disagreement, and
+//! [`DB::repair`] rebuilds what disagrees from the log and the location map.
+
+use super::{DB, LocationUpdate};
+use crate::error::{Result, SpatioError};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::Ordering;
+
+/// A disagreement found by [`DB::verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inconsistency {
+    /// A current location has no spatial index entry at its position, so
+    /// spatial queries can't find it.
+    UnindexedObject {
+        namespace: String,
+        object_id: String,
+    },
+    /// A spatial index entry for no current location, for one at another
+    /// position, or repeating another entry. `key` is `None` when the entry's
+    /// key can't be resolved.
+    OrphanIndexEntry {
+        namespace: String,
+        key: Option<String>,
+    },
+    /// A current location missing from its namespace's ID order, so range
+    /// scans skip it.
+    UnorderedObject {
+        namespace: String,
+        object_id: String,
+    },
+    /// An ID in a namespace's ID order with no current location.
+    OrphanOrderedId {
+        namespace: String,
+        object_id: String,
+    },
+    /// A zone missing from the zone index.
+    UnindexedZone { namespace: String, zone_id: String },
+    /// A zone index entry for no stored zone.
+    OrphanZoneEntry { namespace: String, key: String },
+    /// Trajectory log records that fail their checksum or don't parse; they
+    /// are skipped on recovery.
+    CorruptLogRecords { count: u64 },
+    /// A current location that is not the newest one in the log: its
+    /// position or metadata differ, or the log has the object deleted or not
+    /// at all.
+    StaleObject {
+        namespace: String,
+        object_id: String,
+    },
+    /// An object the log has a location for, with no current location.
+    MissingObject {
+        namespace: String,
+        object_id: String,
+    },
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Inconsistency::UnindexedObject {
+                namespace,
+                object_id,
+            } => write!(f, "{namespace}/{object_id}: not in the spatial index"),
+            Inconsistency::OrphanIndexEntry {
+                namespace,
+                key: Some(key),
+            } => write!(
+                f,
+                "{namespace}: spatial index entry {key:?} matches no object"
+            ),
+            Inconsistency::OrphanIndexEntry {
+                namespace,
+                key: None,
+            } => {
+                write!(
+                    f,
+                    "{namespace}: spatial index entry with an unresolvable key"
+                )
+            }
+            Inconsistency::UnorderedObject {
+                namespace,
+                object_id,
+            } => write!(f, "{namespace}/{object_id}: missing from the ID order"),
+            Inconsistency::OrphanOrderedId {
+                namespace,
+                object_id,
+            } => write!(f, "{namespace}/{object_id}: in the ID order but not stored"),
+            Inconsistency::UnindexedZone { namespace, zone_id } => {
+                write!(f, "{namespace}/{zone_id}: zone not in the zone index")
+            }
+            Inconsistency::OrphanZoneEntry { namespace, key } => {
+                write!(f, "{namespace}: zone index entry {key:?} matches no zone")
+            }
+            Inconsistency::CorruptLogRecords { count } => {
+                write!(f, "{count} corrupt trajectory log record(s)")
+            }
+            Inconsistency::StaleObject {
+                namespace,
+                object_id,
+            } => write!(
+                f,
+                "{namespace}/{object_id}: current location differs from the log"
+            ),
+            Inconsistency::MissingObject {
+                namespace,
+                object_id,
+            } => write!(
+                f,
+                "{namespace}/{object_id}: in the log but has no current location"
+            ),
+        }
+    }
+}
+
+/// What [`DB::verify`] checked and found.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct VerifyReport {
+    /// Current locations checked.
+    pub objects: usize,
+    /// Trajectory log records replayed.
+    pub log_records: u64,
+    pub issues: Vec<Inconsistency>,
+}
+
+impl VerifyReport {
+    pub fn is_consistent(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// What [`DB::repair`] found and did.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RepairReport {
+    /// Whether this was a dry run, which changes nothing.
+    pub dry_run: bool,
+    /// Issues fixed, or in a dry run the ones that would be.
+    pub repaired: Vec<Inconsistency>,
+    /// Issues repair cannot fix: corrupt log records stay skipped.
+    pub unrepairable: Vec<Inconsistency>,
+}
+
+impl Inconsistency {
+    fn namespace(&self) -> Option<&str> {
+        match self {
+            Inconsistency::UnindexedObject { namespace, .. }
+            | Inconsistency::OrphanIndexEntry { namespace, .. }
+            | Inconsistency::UnorderedObject { namespace, .. }
+            | Inconsistency::OrphanOrderedId { namespace, .. }
+            | Inconsistency::UnindexedZone { namespace, .. }
+            | Inconsistency::OrphanZoneEntry { namespace, .. }
+            | Inconsistency::StaleObject { namespace, .. }
+            | Inconsistency::MissingObject { namespace, .. } => Some(namespace),
+            Inconsistency::CorruptLogRecords { .. } => None,
+        }
+    }
+}
+
+impl DB {
+    /// Cross-check current locations against the spatial index, the ID
+    /// order and a full replay of the trajectory log, and zones against the
+    /// zone index, reporting every disagreement.
+    ///
+    /// Meant for a quiet database, such as one just opened for checking:
+    /// writes during the check can show up as mismatches, and writers wait
+    /// while the log is replayed. [`DB::hydrate_from_history`] deliberately
+    /// leaves current locations behind the log, so after it objects report
+    /// as stale until they are written again.
+    ///
+    /// ```
+    /// use spatio::{DB, Point3d};
+    ///
+    /// let db = DB::memory().unwrap();
+    /// db.upsert("fleet", "truck", Point3d::new(1.0, 2.0, 0.0), serde_json::json!({}), None)
+    ///     .unwrap();
+    /// let report = db.verify().unwrap();
+    /// assert!(report.is_consistent());
+    /// assert_eq!(report.objects, 1);
+    /// ```
+    pub fn verify(&self) -> Result<VerifyReport> {
+        db_span!("spatio.verify");
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.hydration.wait_all();
+        Ok(self.check()?.0)
+    }
+
+    /// Fix what [`DB::verify`] finds, or with `dry_run` only report what would
+    /// be fixed.
+    ///
+    /// The log is taken as the truth for current locations, as on recovery:
+    /// stale objects are reset to their newest logged location, or removed
+    /// if the log has them deleted, and missing ones are restored. Then the
+    /// spatial index, zone index and ID order of every affected namespace
+    /// are rebuilt from the location and zone maps, which adds missing
+    /// entries and drops orphaned ones. Views, fences, metadata indexes and
+    /// change subscribers see restored objects as ordinary writes. Corrupt
+    /// log records are reported but left as they are.
+    ///
+    /// Like [`DB::verify`], meant for a quiet database: a write racing with
+    /// the repair may be lost from the index until the next repair.
+    pub fn repair(&self, dry_run: bool) -> Result<RepairReport> {
+        db_span!("spatio.repair");
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        self.hydration.wait_all();
+        let (report, mut latest) = self.check()?;
+        let (unrepairable, repaired): (Vec<_>, Vec<_>) = report
+            .issues
+            .into_iter()
+            .partition(|issue| matches!(issue, Inconsistency::CorruptLogRecords { .. }));
+        if !dry_run {
+            for issue in &repaired {
+                if let Inconsistency::StaleObject {
+                    namespace,
+                    object_id,
+                }
+                | Inconsistency::MissingObject {
+                    namespace,
+                    object_id,
+                } = issue
+                {
+                    let logged = latest.remove(&format!("{namespace}::{object_id}"));
+                    self.restore_from_log(namespace, object_id, logged)?;
+                }
+            }
+            let namespaces: BTreeSet<&str> = repaired
+                .iter()
+                .filter_map(Inconsistency::namespace)
+                .collect();
+            for namespace in namespaces {
+                self.hot.rebuild_index(namespace);
+            }
+        }
+        Ok(RepairReport {
+            dry_run,
+            repaired,
+            unrepairable,
+        })
+    }
+
+    /// Set the current location of an object to its newest logged one, or
+    /// remove it if there is none.
+    fn restore_from_log(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        logged: Option<LocationUpdate>,
+    ) -> Result<()> {
+        // Removed first: the live location may be newer and would win.
+        let previous = self.hot.remove_object(namespace, object_id);
+        if let Some(update) = logged {
+            self.hot.update_location(
+                namespace,
+                object_id,
+                update.position,
+                update.metadata,
+                update.timestamp,
+            )?;
+        }
+        self.refresh_watchers(namespace, object_id);
+        let current = self.hot.get_current_location(namespace, object_id);
+        self.changes.publish(previous, current);
+        Ok(())
+    }
+
+    /// Run every check, returning the report and the newest logged location
+    /// of each object the log has one for, by `namespace::object_id`.
+    fn check(&self) -> Result<(VerifyReport, HashMap<String, LocationUpdate>)> {
+        let mut issues = Vec::new();
+        let locations = self.hot.verify(&mut issues);
+        let log = self.cold.verify_log()?;
+        if log.corrupt > 0 {
+            issues.push(Inconsistency::CorruptLogRecords { count: log.corrupt });
+        }
+        let mut seen = HashSet::new();
+        for loc in &locations {
+            let key = format!("{}::{}", loc.namespace, loc.object_id);
+            // Timestamps may differ: a write that snapped onto the current
+            // position only refreshed it (see `Config::coordinate_precision`).
+            let current = log.latest.get(&key).is_some_and(|update| {
+                update.position == loc.position && update.metadata == loc.metadata
+            });
+            if !current {
+                issues.push(Inconsistency::StaleObject {
+                    namespace: loc.namespace.clone(),
+                    object_id: loc.object_id.clone(),
+                });
+            }
+            seen.insert(key);
+        }
+        let mut missing: Vec<&String> = log
+            .latest
+            .keys()
+            .filter(|key| !seen.contains(*key))
+            .collect();
+        missing.sort();
+        for key in missing {
+            if let Some((namespace, object_id)) = key.split_once("::") {
+                issues.push(Inconsistency::MissingObject {
+                    namespace: namespace.to_string(),
+                    object_id: object_id.to_string(),
+                });
+            }
+        }
+        let report = VerifyReport {
+            objects: locations.len(),
+            log_records: log.records,
+            issues,
+        };
+        Ok((report, log.latest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spatio_types::point::Point3d;
+
+    fn upsert(db: &DB, id: &str, x: f64) {
+        db.upsert(
+            "fleet",
+            id,
+            Point3d::new(x, 0.0, 0.0),
+            serde_json::json!({}),
+            None,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_current_locations_are_checked_against_the_log() {
+        let db = DB::memory().unwrap();
+        upsert(&db, "a", 1.0);
+        upsert(&db, "b", 2.0);
+        db.delete("fleet", "b").unwrap();
+        let report = db.verify().unwrap();
+        assert_eq!(report.issues, []);
+        assert_eq!((report.objects, report.log_records), (1, 3));
+
+        // A write that bypasses the log.
+        db.hot
+            .update_location(
+                "fleet",
+                "a",
+                Point3d::new(5.0, 0.0, 0.0),
+                serde_json::json!({}),
+                std::time::SystemTime::now(),
+            )
+            .unwrap();
+        assert_eq!(
+            db.verify().unwrap().issues,
+            [Inconsistency::StaleObject {
+                namespace: "fleet".into(),
+                object_id: "a".into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_corrupt_log_records_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.log");
+        {
+            let db = DB::open(&path).unwrap();
+            upsert(&db, "a", 1.0);
+            upsert(&db, "b", 2.0);
+            db.close().unwrap();
+        }
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let corrupted = contents.replacen("|fleet|b|", "|fleet|b|9", 1);
+        std::fs::write(&path, corrupted).unwrap();
+
+        let db = DB::open(&path).unwrap();
+        let report = db.verify().unwrap();
+        assert_eq!(
+            report.issues,
+            [Inconsistency::CorruptLogRecords { count: 1 }]
+        );
+        assert_eq!(report.objects, 1);
+    }
+
+    #[test]
+    fn test_repair_restores_current_locations_from_the_log() {
+        let db = DB::memory().unwrap();
+        upsert(&db, "a", 1.0);
+        upsert(&db, "b", 2.0);
+        // Writes that bypass the log.
+        db.hot
+            .update_location(
+                "fleet",
+                "a",
+                Point3d::new(5.0, 0.0, 0.0),
+                serde_json::json!({}),
+                std::time::SystemTime::now(),
+            )
+            .unwrap();
+        db.hot.remove_object("fleet", "b");
+        db.hot
+            .update_location(
+                "fleet",
+                "ghost",
+                Point3d::new(3.0, 0.0, 0.0),
+                serde_json::json!({}),
+                std::time::SystemTime::now(),
+            )
+            .unwrap();
+        let stale = |id: &str| Inconsistency::StaleObject {
+            namespace: "fleet".into(),
+            object_id: id.into(),
+        };
+        let sorted = |mut issues: Vec<Inconsistency>| {
+            issues.sort_by_key(|issue| issue.to_string());
+            issues
+        };
+        let expected = sorted(vec![
+            stale("a"),
+            stale("ghost"),
+            Inconsistency::MissingObject {
+                namespace: "fleet".into(),
+                object_id: "b".into(),
+            },
+        ]);
+
+        let dry = db.repair(true).unwrap();
+        assert!(dry.dry_run);
+        assert_eq!(sorted(dry.repaired), expected);
+        assert_eq!(db.verify().unwrap().issues.len(), 3);
+
+        let report = db.repair(false).unwrap();
+        assert_eq!(sorted(report.repaired), expected);
+        assert_eq!(report.unrepairable, []);
+        assert!(db.verify().unwrap().is_consistent());
+        let position = |id| db.get("fleet", id).unwrap().map(|loc| loc.position.x());
+        assert_eq!(
+            (position("a"), position("b"), position("ghost")),
+            (Some(1.0), Some(2.0), None)
+        );
+        let near = db
+            .query_radius("fleet", &Point3d::new(5.0, 0.0, 0.0), 1.0, 10)
+            .unwrap();
+        assert!(near.is_empty());
+    }
+}
@@ -0,0 +1,143 @@
+//! Materialized views: the objects of a namespace matching a [`Predicate`],
+//! kept up to date as objects are written.
+//!
+//! Each write re-evaluates only the written object against the views of its
+//! namespace, so reading a view never scans the index. Membership changes
+//! are published to subscribers as [`ViewEvent`]s.
+//!
+//! A view re-reads the object's current location under its own lock rather
+//! than trusting the value the writer saw, so concurrent writes to one object
+//! cannot leave the view holding a stale position.
+
+use crate::compute::query::{Predicate, matches};
+use crate::db::CurrentLocation;
+use parking_lot::{Mutex, RwLock};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::sync::mpsc::Receiver;
+
+use super::subscribers::Subscribers;
+
+/// A change in a view's membership.
+#[derive(Debug, Clone)]
+pub enum ViewEvent {
+    /// The object now matches the view.
+    Entered(Arc<CurrentLocation>),
+    /// A member was written and still matches.
+    Updated(Arc<CurrentLocation>),
+    /// The object no longer matches, or was deleted. Carries its last
+    /// location inside the view.
+    Left(Arc<CurrentLocation>),
+}
+
+/// Receiving end of a view subscription, bounded as described in
+/// [`subscribers`](super::subscribers).
+pub type ViewSubscription = Receiver<ViewEvent>;
+
+pub(crate) struct View {
+    namespace: String,
+    predicate: Predicate,
+    members: Mutex<BTreeMap<String, Arc<CurrentLocation>>>,
+    subscribers: Subscribers<ViewEvent>,
+}
+
+/// All views of a database, by name.
+#[derive(Default)]
+pub(crate) struct Views {
+    views: RwLock<HashMap<String, Arc<View>>>,
+}
+
+impl Views {
+    /// Register a view named `name`, filling it with `populate` before any
+    /// write can touch it. Returns `false` if the name is taken.
+    pub fn create(
+        &self,
+        name: &str,
+        namespace: &str,
+        predicate: Predicate,
+        populate: impl FnOnce(&Predicate) -> Vec<Arc<CurrentLocation>>,
+    ) -> bool {
+        let view = Arc::new(View {
+            namespace: namespace.to_string(),
+            predicate,
+            members: Mutex::new(BTreeMap::new()),
+            subscribers: Subscribers::default(),
+        });
+        // Writes that see the view wait on its lock until it is filled; writes
+        // that don't are already visible to `populate`.
+        let mut members = view.members.lock();
+        {
+            let mut views = self.views.write();
+            if views.contains_key(name) {
+                return false;
+            }
+            views.insert(name.to_string(), view.clone());
+        }
+        members.extend(
+            populate(&view.predicate)
+                .into_iter()
+                .map(|loc| (loc.object_id.clone(), loc)),
+        );
+        true
+    }
+
+    /// Remove a view, disconnecting its subscribers.
+    pub fn drop_view(&self, name: &str) -> bool {
+        self.views.write().remove(name).is_some()
+    }
+
+    fn get(&self, name: &str) -> Option<Arc<View>> {
+        self.views.read().get(name).cloned()
+    }
+
+    /// Current members of a view in object ID order, or `None` if there is no
+    /// such view.
+    pub fn members(&self, name: &str) -> Option<Vec<Arc<CurrentLocation>>> {
+        let view = self.get(name)?;
+        let members = view.members.lock();
+        Some(members.values().cloned().collect())
+    }
+
+    pub fn subscribe(&self, name: &str) -> Option<ViewSubscription> {
+        Some(self.get(name)?.subscribers.subscribe(()))
+    }
+
+    /// Re-evaluate `object_id` against every view of `namespace`. `current`
+    /// reads the object's latest location (`None` once deleted).
+    pub fn refresh(
+        &self,
+        namespace: &str,
+        object_id: &str,
+        current: impl Fn() -> Option<Arc<CurrentLocation>>,
+    ) {
+        let affected: Vec<Arc<View>> = {
+            let views = self.views.read();
+            if views.is_empty() {
+                return;
+            }
+            views
+                .values()
+                .filter(|view| view.namespace == namespace)
+                .cloned()
+                .collect()
+        };
+
+        for view in affected {
+            let mut members = view.members.lock();
+            let location =
+                current().filter(|loc| matches(&view.predicate, &loc.position, &loc.metadata));
+            let event = match location {
+                Some(loc) => match members.insert(object_id.to_string(), loc.clone()) {
+                    Some(_) => ViewEvent::Updated(loc),
+                    None => ViewEvent::Entered(loc),
+                },
+                None => match members.remove(object_id) {
+                    Some(last) => ViewEvent::Left(last),
+                    None => continue,
+                },
+            };
+            // Published under the lock so subscribers see changes in order.
+            view.subscribers.publish(&event, |_| true);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Warming up a standby before it takes over.
+//!
+//! A database that has just opened answers its first trajectory queries
+//! slowly: the first to reach the log indexes it, under the log lock, and
+//! reads come from disk rather than the page cache. A standby that fails
+//! over serves all of that at once to the traffic it takes over.
+//! [`DB::warm_up`] does the work up front, so it can be run on a standby
+//! after it opens and again after compactions, which drop the index.
+
+use super::DB;
+use crate::error::{Result, SpatioError};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// What [`DB::warm_up`] loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WarmUpReport {
+    /// Objects with a current location, all in their spatial indexes.
+    pub objects: usize,
+    /// Log segments read into the trajectory index.
+    pub segments_read: usize,
+    /// Bytes of log read.
+    pub bytes_read: u64,
+    pub elapsed: Duration,
+}
+
+impl DB {
+    /// Load what the first queries after a failover would otherwise load on
+    /// demand: wait until every namespace is loaded, merge buffered inserts
+    /// into the spatial indexes, and index the trajectory log, reading every
+    /// segment holding updates from the last `recent` (and with them, the
+    /// page cache).
+    ///
+    /// Writes wait while the log is read. Running it again reads only what
+    /// hasn't been read since, or since the last compaction.
+    ///
+    /// ```
+    /// use spatio::{DB, Point3d};
+    /// use serde_json::json;
+    /// use std::time::Duration;
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let path = dir.path().join("fleet.db");
+    /// DB::open(&path)
+    ///     .unwrap()
+    ///     .upsert("fleet", "truck", Point3d::new(1.0, 2.0, 0.0), json!({}), None)
+    ///     .unwrap();
+    ///
+    /// let standby = DB::open(&path).unwrap();
+    /// let report = standby.warm_up(Duration::from_secs(3600)).unwrap();
+    /// assert_eq!(report.objects, 1);
+    /// assert!(report.bytes_read > 0);
+    /// ```
+    pub fn warm_up(&self, recent: Duration) -> Result<WarmUpReport> {
+        db_span!("spatio.warm_up");
+        if self.closed.load(Ordering::Acquire) {
+            return Err(SpatioError::DatabaseClosed);
+        }
+        let started = Instant::now();
+        self.hydration.wait_all();
+        self.hot.merge_write_buffers();
+        let since = SystemTime::now().checked_sub(recent).unwrap_or(UNIX_EPOCH);
+        let (segments_read, bytes_read) = self.cold.warm_up(since)?;
+        Ok(WarmUpReport {
+            objects: self.hot.detailed_stats().0,
+            segments_read,
+            bytes_read,
+            elapsed: started.elapsed(),
+        })
+    }
+}
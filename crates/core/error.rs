@@ -1,5 +1,6 @@
 //! Error types and result aliases for Spatio operations.
 
+use crate::compute::validation::ValidationError;
 use std::fmt;
 
 /// Simplified error types for Spatio
@@ -16,8 +17,27 @@ pub enum SpatioError {
     InvalidTimestamp,
     /// Invalid input parameter
     InvalidInput(String),
+    /// Non-finite or out-of-range coordinate, distance, or extent
+    Validation(ValidationError),
     /// Object not found
     ObjectNotFound,
+    /// A page token was issued at a later log sequence than this database has
+    /// applied (e.g. resuming a scan on a lagging copy of its log)
+    StaleReplica {
+        token_sequence: u64,
+        last_sequence: u64,
+    },
+    /// The log can't be written (read-only or full disk), so writes are kept
+    /// in memory only; returned where a write must be durable
+    DegradedPersistence(String),
+    /// Free disk space fell below the configured hard watermark, so writes
+    /// that append to the log are refused
+    InsufficientDiskSpace { available: u64, required: u64 },
+    /// Another process has the database open: any other open while a
+    /// writer holds it, or a writer while readers do
+    Locked(String),
+    /// The database was opened read-only, so it can't be written
+    ReadOnly,
     /// I/O error from persistence layer
     Io(std::io::Error),
     /// Generic error with message
@@ -34,7 +54,33 @@ impl fmt::Display for SpatioError {
             }
             SpatioError::InvalidTimestamp => write!(f, "Invalid timestamp value"),
             SpatioError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
+            SpatioError::Validation(err) => write!(f, "Invalid input: {}", err),
             SpatioError::ObjectNotFound => write!(f, "Object not found"),
+            SpatioError::StaleReplica {
+                token_sequence,
+                last_sequence,
+            } => write!(
+                f,
+                "Page token requires sequence {}, but this database is at {}",
+                token_sequence, last_sequence
+            ),
+            SpatioError::DegradedPersistence(reason) => write!(
+                f,
+                "Persistence degraded, writes are kept in memory only: {}",
+                reason
+            ),
+            SpatioError::InsufficientDiskSpace {
+                available,
+                required,
+            } => write!(
+                f,
+                "Insufficient disk space: {} bytes free, writes need {}",
+                available, required
+            ),
+            SpatioError::Locked(path) => {
+                write!(f, "Database {} is in use by another process", path)
+            }
+            SpatioError::ReadOnly => write!(f, "Database is open read-only"),
             SpatioError::Io(err) => write!(f, "I/O error: {}", err),
             SpatioError::Other(msg) => write!(f, "{}", msg),
         }
@@ -45,6 +91,7 @@ impl std::error::Error for SpatioError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             SpatioError::Io(err) => Some(err),
+            SpatioError::Validation(err) => Some(err),
             _ => None,
         }
     }
@@ -56,5 +103,11 @@ impl From<std::io::Error> for SpatioError {
     }
 }
 
+impl From<ValidationError> for SpatioError {
+    fn from(err: ValidationError) -> Self {
+        SpatioError::Validation(err)
+    }
+}
+
 /// Result type alias for Spatio operations
 pub type Result<T> = std::result::Result<T, SpatioError>;
@@ -3,7 +3,7 @@
 //! This example demonstrates Spatio's 3D spatial indexing capabilities for
 //! altitude-aware applications like drone tracking, aviation, and multi-floor navigation.
 
-use spatio::{Point3d, Spatio};
+use spatio::{NearbyHit, Point3d, Spatio};
 use std::error::Error;
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -69,7 +69,12 @@ fn main() -> Result<(), Box<dyn Error>> {
     println!("   Search radius: {}m (3D)\n", search_radius);
     println!("   Found {} drones within range:", nearby_drones.len());
 
-    for (loc, distance) in &nearby_drones {
+    for NearbyHit {
+        location: loc,
+        distance,
+        ..
+    } in &nearby_drones
+    {
         let description = loc.metadata.to_string();
         println!(
             "   - {} at ({:.4}, {:.4}, {}m) - distance: {:.1}m",
@@ -107,7 +112,12 @@ fn main() -> Result<(), Box<dyn Error>> {
     println!("   Horizontal radius: {}m\n", horizontal_radius);
     println!("   Found {} drones in corridor:", corridor_drones.len());
 
-    for (loc, h_dist) in &corridor_drones {
+    for NearbyHit {
+        location: loc,
+        distance: h_dist,
+        ..
+    } in &corridor_drones
+    {
         let description = loc.metadata.to_string();
         println!(
             "   - {} at altitude {}m (horizontal: {:.1}m)",
@@ -168,7 +178,15 @@ fn main() -> Result<(), Box<dyn Error>> {
     );
     println!("   Finding {} nearest drones:\n", k);
 
-    for (i, (loc, distance)) in nearest_drones.iter().enumerate() {
+    for (
+        i,
+        NearbyHit {
+            location: loc,
+            distance,
+            ..
+        },
+    ) in nearest_drones.iter().enumerate()
+    {
         let description = loc.metadata.to_string();
         println!("   {}. {} - {:.1}m away", i + 1, description, distance);
         println!(
@@ -216,7 +234,12 @@ fn main() -> Result<(), Box<dyn Error>> {
         10,
     )?;
 
-    for (loc, h_dist) in &traffic {
+    for NearbyHit {
+        location: loc,
+        distance: h_dist,
+        ..
+    } in &traffic
+    {
         let info = loc.metadata.to_string();
         println!(
             "   - {} at FL{:.0} ({}km away)",
@@ -258,7 +281,12 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     // Print native DB results
     println!("   Results near point_a:");
-    for (loc, dist) in &results {
+    for NearbyHit {
+        location: loc,
+        distance: dist,
+        ..
+    } in &results
+    {
         println!("   - Distance to {}: {:.2}m", loc.object_id, dist);
     }
 
@@ -309,7 +337,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     )?;
 
     println!("   Building sensors on floors 3-7:");
-    for (loc, _) in &mid_floor_sensors {
+    for NearbyHit { location: loc, .. } in &mid_floor_sensors {
         let info = loc.metadata.to_string();
         let floor = (loc.position.z() / 3.0).round() as i32;
         println!("   - Floor {}: {}", floor, info);
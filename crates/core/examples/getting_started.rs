@@ -1,4 +1,4 @@
-use spatio::{Point3d, Spatio, TemporalPoint};
+use spatio::{NearbyHit, Point3d, Spatio, TemporalPoint, TimeRange};
 use spatio_types::geo::Point;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
@@ -53,7 +53,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // using query_radius which now returns distance
     let nearby = db.query_radius("cities", &london, 500_000.0, 10)?;
     println!("   Found {} cities within 500km of London:", nearby.len());
-    for (loc, dist) in &nearby {
+    for NearbyHit {
+        location: loc,
+        distance: dist,
+        ..
+    } in &nearby
+    {
         println!(
             "     - {} ({:.1}m away): {:?}",
             loc.object_id, dist, loc.metadata
@@ -90,8 +95,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let path_segment = db.query_trajectory(
         "logistics",
         "vehicle:truck001",
-        UNIX_EPOCH + Duration::from_secs(1640995200),
-        UNIX_EPOCH + Duration::from_secs(1640995320),
+        TimeRange::between(
+            UNIX_EPOCH + Duration::from_secs(1640995200),
+            UNIX_EPOCH + Duration::from_secs(1640995320),
+        ),
         100,
     )?;
     println!(
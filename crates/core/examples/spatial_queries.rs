@@ -65,8 +65,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Find cities within 500km of London
     let nearby_500km = db.query_radius("world_cities", &london, 500_000.0, 10)?;
     println!("   Cities within 500km of London: {}", nearby_500km.len());
-    for (loc, dist) in &nearby_500km {
-        println!("     - {} ({:.1}m)", loc.metadata, dist);
+    for hit in &nearby_500km {
+        println!("     - {} ({:.1}m)", hit.location.metadata, hit.distance);
     }
 
     // Find cities within 2000km of London
@@ -75,15 +75,20 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         "\n   Cities within 2000km of London: {}",
         nearby_2000km.len()
     );
-    for (loc, dist) in &nearby_2000km {
-        println!("     - {} ({:.1}m)", loc.metadata, dist);
+    for hit in &nearby_2000km {
+        println!("     - {} ({:.1}m)", hit.location.metadata, hit.distance);
     }
 
     // Use limit to get only closest N cities
     let closest_3 = db.query_radius("world_cities", &london, f64::INFINITY, 3)?;
     println!("\n   Closest 3 cities to London:");
-    for (i, (loc, dist)) in closest_3.iter().enumerate() {
-        println!("     {}. {} ({:.1}m)", i + 1, loc.metadata, dist);
+    for (i, hit) in closest_3.iter().enumerate() {
+        println!(
+            "     {}. {} ({:.1}m)",
+            i + 1,
+            hit.location.metadata,
+            hit.distance
+        );
     }
     println!();
 
@@ -206,8 +211,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let results = db.query_radius("nyc_grid", &center, radius, 100)?;
     println!("   Found {} points within radius:", results.len());
 
-    if let Some((loc, dist)) = results.first() {
-        println!("     - Sample: {} at {:.1}m", loc.object_id, dist);
+    if let Some(hit) = results.first() {
+        println!("     - Sample: {} at {:.1}m", hit.object_id(), hit.distance);
     }
 
     // Performance check
@@ -229,8 +234,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     println!("\n   Landmarks within 2km:");
     let close_landmarks = db.query_radius("landmarks", &center_london, 2_000.0, 10)?;
-    for (loc, dist) in &close_landmarks {
-        println!("     - {} ({:.1}m)", loc.metadata, dist);
+    for hit in &close_landmarks {
+        println!("     - {} ({:.1}m)", hit.location.metadata, hit.distance);
     }
     println!();
 
@@ -1,4 +1,4 @@
-use spatio::{Point, Spatio, TemporalPoint};
+use spatio::{Point, Spatio, TemporalPoint, TimeRange};
 use std::time::{Duration, UNIX_EPOCH};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -44,8 +44,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let full_path = db.query_trajectory(
         "logistics",
         "vehicle:truck001",
-        UNIX_EPOCH + Duration::from_secs(1640995200),
-        UNIX_EPOCH + Duration::from_secs(1640995400),
+        TimeRange::between(
+            UNIX_EPOCH + Duration::from_secs(1640995200),
+            UNIX_EPOCH + Duration::from_secs(1640995400),
+        ),
         100,
     )?;
     println!("   Retrieved {} waypoints:", full_path.len());
@@ -68,8 +70,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let partial_path = db.query_trajectory(
         "logistics",
         "vehicle:truck001",
-        UNIX_EPOCH + Duration::from_secs(1640995200),
-        UNIX_EPOCH + Duration::from_secs(1640995320),
+        TimeRange::between(
+            UNIX_EPOCH + Duration::from_secs(1640995200),
+            UNIX_EPOCH + Duration::from_secs(1640995320),
+        ),
         100,
     )?;
     println!("   First 2 minutes: {} waypoints", partial_path.len());
@@ -78,8 +82,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let middle_segment = db.query_trajectory(
         "logistics",
         "vehicle:truck001",
-        UNIX_EPOCH + Duration::from_secs(1640995260),
-        UNIX_EPOCH + Duration::from_secs(1640995320),
+        TimeRange::between(
+            UNIX_EPOCH + Duration::from_secs(1640995260),
+            UNIX_EPOCH + Duration::from_secs(1640995320),
+        ),
         100,
     )?;
     println!("   Middle segment: {} waypoints\n", middle_segment.len());
@@ -147,22 +153,28 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let truck_path = db.query_trajectory(
         "logistics",
         "vehicle:truck001",
-        UNIX_EPOCH + Duration::from_secs(1640995200),
-        UNIX_EPOCH + Duration::from_secs(1640995400),
+        TimeRange::between(
+            UNIX_EPOCH + Duration::from_secs(1640995200),
+            UNIX_EPOCH + Duration::from_secs(1640995400),
+        ),
         100,
     )?;
     let taxi_path = db.query_trajectory(
         "transport",
         "vehicle:taxi042",
-        UNIX_EPOCH + Duration::from_secs(1640995200),
-        UNIX_EPOCH + Duration::from_secs(1640995400),
+        TimeRange::between(
+            UNIX_EPOCH + Duration::from_secs(1640995200),
+            UNIX_EPOCH + Duration::from_secs(1640995400),
+        ),
         100,
     )?;
     let bus_path = db.query_trajectory(
         "transport",
         "vehicle:bus123",
-        UNIX_EPOCH + Duration::from_secs(1640995200),
-        UNIX_EPOCH + Duration::from_secs(1640995700),
+        TimeRange::between(
+            UNIX_EPOCH + Duration::from_secs(1640995200),
+            UNIX_EPOCH + Duration::from_secs(1640995700),
+        ),
         100,
     )?;
 
@@ -199,8 +211,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let window = db.query_trajectory(
         "logistics",
         "drone:delivery001",
-        UNIX_EPOCH + Duration::from_secs(start_time),
-        UNIX_EPOCH + Duration::from_secs(start_time + 10),
+        TimeRange::between(
+            UNIX_EPOCH + Duration::from_secs(start_time),
+            UNIX_EPOCH + Duration::from_secs(start_time + 10),
+        ),
         100,
     )?;
     println!(
@@ -245,8 +259,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let updated_path = db.query_trajectory(
         "logistics",
         "vehicle:truck001",
-        UNIX_EPOCH + Duration::from_secs(1640995200),
-        UNIX_EPOCH + Duration::from_secs(1640995600),
+        TimeRange::between(
+            UNIX_EPOCH + Duration::from_secs(1640995200),
+            UNIX_EPOCH + Duration::from_secs(1640995600),
+        ),
         100,
     )?;
     println!(
@@ -32,23 +32,46 @@ pub use error::{Result, SpatioError};
 #[cfg(feature = "sync")]
 pub use db::SyncDB;
 
+#[cfg(feature = "async")]
+pub use db::AsyncDB;
+
 #[doc(inline)]
 pub use db::DB as Spatio;
 
 pub use geo::Rect;
+pub use spatio_types::fence::Fence;
 pub use spatio_types::geo::{Point, Polygon};
 
 pub use config::{
-    BoundingBox2D, BoundingBox3D, Config, DbStats, Point3d, Polygon3D, PolygonDynamic,
-    PolygonDynamic3D, SetOptions, SyncMode, SyncPolicy, TemporalBoundingBox2D,
-    TemporalBoundingBox3D, TemporalPoint, TemporalPoint3D,
+    AccessLogConfig, ActiveExpirationConfig, BoundingBox2D, BoundingBox3D, Config, DbStats,
+    DiskWatermarkConfig, MinuteStats, NamespaceConfig, Operation, PersistenceStatus, Point3d,
+    Polygon3D, PolygonDynamic, PolygonDynamic3D, RejectionLogConfig, ScanDirection, SetOptions,
+    SyncMode, SyncPolicy, TemporalBoundingBox2D, TemporalBoundingBox3D, TemporalPoint,
+    TemporalPoint3D, TimeBound, TimeRange, Trajectory, TrajectorySummary,
 };
 
+pub use compute::export::{Anonymization, ExportFormat, ExportSpec};
+pub use compute::passes::TrajectoryMatch;
+pub use compute::query::Predicate;
 pub use compute::spatial::DistanceMetric;
-#[cfg(feature = "time-index")]
+pub use compute::trips::Trip;
+pub use compute::violations::SpeedViolation;
 pub use config::{HistoryEntry, HistoryEventKind};
 
+pub use db::OpenProgress;
+pub use db::{ChangeEvent, ChangeFeed, FenceEvent, FenceOptions, FenceSubscription};
+pub use db::{CheckpointKind, CheckpointReport};
+pub use db::{CompactionContext, CompactionPolicy};
+pub use db::{DBReader, ForgetReport, HistoryCompaction, ViewEvent, ViewSubscription};
+pub use db::{GeoJsonImportOptions, GeoJsonImportReport};
+pub use db::{HookEvent, HookMode, WriteHook};
+pub use db::{ImportOptions, ImportReport, RejectionEntry};
+pub use db::{Inconsistency, RepairReport, VerifyReport};
+pub use db::{Maintenance, MaintenanceKind};
 pub use db::{Namespace, NamespaceManager};
+pub use db::{NearbyHit, QueryBuilder, ZoneHit};
+pub use db::{ObjectHit, ObjectType, SpatialObject, StoredObject};
+pub use db::{SlowQuery, WarmUpReport};
 
 pub use compute::validation;
 
@@ -62,7 +85,10 @@ pub mod prelude {
     #[cfg(feature = "sync")]
     pub use crate::SyncDB;
 
-    pub use crate::{Point, Polygon};
+    #[cfg(feature = "async")]
+    pub use crate::AsyncDB;
+
+    pub use crate::{Point, Polygon, Predicate};
     pub use geo::Rect;
 
     pub use crate::{Config, SetOptions, SyncPolicy};
@@ -0,0 +1,376 @@
+//! Property-based tests for the spatial query paths.
+//!
+//! Generated inputs lean on the awkward parts of the sphere: longitudes near
+//! the antimeridian, latitudes near the poles, points on a coarse grid so they
+//! land exactly on bounding-box edges, and zero-area polygons. Each query is
+//! checked against a brute-force scan of the same points, and no query path
+//! may return an object (or zone) twice, whichever index cells or search
+//! envelopes it falls into.
+
+use geo::{Contains, Distance, Haversine};
+use proptest::prelude::*;
+use proptest::test_runner::FileFailurePersistence;
+use spatio::compute::spatial::algorithms::bboxes_intersect;
+use spatio::compute::spatial::{DistanceMetric, ZoneGeometry, bounding_box, distance_between};
+use spatio::{BoundingBox2D, NearbyHit, Point, Point3d, Polygon, Predicate, Spatio};
+use std::collections::BTreeSet;
+
+/// Relative slack for points sitting on a radius boundary, where the index
+/// and the brute-force scan may round the distance differently.
+const BOUNDARY_TOLERANCE: f64 = 1e-9;
+
+fn lon() -> impl Strategy<Value = f64> {
+    prop_oneof![
+        3 => -180.0..=180.0f64,
+        1 => 179.0..=180.0f64,
+        1 => -180.0..=-179.0f64,
+        1 => prop_oneof![Just(-180.0), Just(180.0)],
+    ]
+}
+
+fn lat() -> impl Strategy<Value = f64> {
+    prop_oneof![
+        4 => -89.0..=89.0f64,
+        1 => 89.0..=90.0f64,
+        1 => -90.0..=-89.0f64,
+        1 => prop_oneof![Just(-90.0), Just(90.0)],
+    ]
+}
+
+fn point3d() -> impl Strategy<Value = Point3d> {
+    (lon(), lat(), 0.0..=1000.0f64).prop_map(|(x, y, z)| Point3d::new(x, y, z))
+}
+
+/// Half-degree grid coordinate; boxes and points drawn from it share edges.
+fn grid(min: f64, max: f64) -> impl Strategy<Value = f64> {
+    let steps = ((max - min) * 2.0) as i32;
+    (0..=steps).prop_map(move |i| min + f64::from(i) * 0.5)
+}
+
+fn grid_point() -> impl Strategy<Value = Point3d> {
+    (grid(-180.0, 180.0), grid(-90.0, 90.0)).prop_map(|(x, y)| Point3d::new(x, y, 0.0))
+}
+
+/// `(min_x, min_y, max_x, max_y)` with `min < max` on both axes, on the grid
+/// (often one cell wide) or anywhere.
+fn bbox() -> impl Strategy<Value = (f64, f64, f64, f64)> {
+    let ordered = |a: f64, b: f64| (a.min(b), a.max(b));
+    prop_oneof![
+        (grid(-180.0, 180.0), grid(-90.0, 90.0), 1..4i32, 1..4i32).prop_map(|(x, y, w, h)| {
+            let (x, y) = (x.min(178.5), y.min(88.5));
+            (x, y, x + f64::from(w) * 0.5, y + f64::from(h) * 0.5)
+        }),
+        (lon(), lon(), lat(), lat()).prop_map(move |(x0, x1, y0, y1)| {
+            let (min_x, max_x) = ordered(x0, x1);
+            let (min_y, max_y) = ordered(y0, y1);
+            (min_x, min_y, max_x, max_y)
+        }),
+    ]
+    .prop_filter("empty box", |(min_x, min_y, max_x, max_y)| {
+        min_x < max_x && min_y < max_y
+    })
+}
+
+/// Star-shaped (hence simple) polygon around a center, or a degenerate one
+/// whose vertices are collinear.
+fn polygon() -> impl Strategy<Value = Polygon> {
+    let star = (
+        -170.0..=170.0f64,
+        -80.0..=80.0f64,
+        prop::collection::vec((0.0..1.0f64, 0.5..=5.0f64), 3..12),
+    )
+        .prop_map(|(cx, cy, mut spokes)| {
+            spokes.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let mut ring: Vec<(f64, f64)> = spokes
+                .iter()
+                .map(|(turn, r)| {
+                    let angle = turn * std::f64::consts::TAU;
+                    (cx + r * angle.cos(), cy + r * angle.sin())
+                })
+                .collect();
+            ring.push(ring[0]);
+            Polygon::from_coords(&ring, vec![])
+        });
+    let collinear = (-170.0..=170.0f64, -80.0..=80.0f64, 0.5..=5.0f64).prop_map(|(x, y, d)| {
+        Polygon::from_coords(
+            &[(x, y), (x + d, y + d), (x + 2.0 * d, y + 2.0 * d), (x, y)],
+            vec![],
+        )
+    });
+    prop_oneof![4 => star, 1 => collinear]
+}
+
+fn load(points: &[Point3d]) -> Spatio {
+    let db = Spatio::memory().unwrap();
+    for (i, point) in points.iter().enumerate() {
+        db.upsert(
+            "p",
+            &format!("o{i}"),
+            point.clone(),
+            serde_json::json!({}),
+            None,
+        )
+        .unwrap();
+    }
+    db
+}
+
+fn id(i: usize) -> String {
+    format!("o{i}")
+}
+
+fn ids<'a>(ids: impl IntoIterator<Item = &'a str>) -> BTreeSet<String> {
+    ids.into_iter().map(str::to_string).collect()
+}
+
+/// The hybrid distance the radius queries use: haversine plus altitude.
+fn distance_3d(a: &Point3d, b: &Point3d) -> f64 {
+    let horizontal =
+        Haversine.distance(geo::Point::new(a.x(), a.y()), geo::Point::new(b.x(), b.y()));
+    horizontal.hypot(b.z() - a.z())
+}
+
+fn in_box(p: &Point3d, (min_x, min_y, max_x, max_y): (f64, f64, f64, f64)) -> bool {
+    (min_x..=max_x).contains(&p.x()) && (min_y..=max_y).contains(&p.y())
+}
+
+proptest! {
+    // Proptest looks for failures beside the crate root's parent, which is
+    // `crates/` here as the crate has no `src/`; keep them in the crate.
+    #![proptest_config(ProptestConfig {
+        failure_persistence: Some(Box::new(FileFailurePersistence::Direct(
+            "proptest-regressions/geometry_properties.txt",
+        ))),
+        ..ProptestConfig::with_cases(128)
+    })]
+
+    #[test]
+    fn radius_query_matches_brute_force(
+        points in prop::collection::vec(point3d(), 1..40),
+        center in point3d(),
+        radius in prop_oneof![1.0..=5_000.0f64, 5_000.0..=2_000_000.0f64],
+    ) {
+        let db = load(&points);
+        let found = db.query_radius("p", &center, radius, points.len()).unwrap();
+
+        for pair in found.windows(2) {
+            prop_assert!(pair[0].distance <= pair[1].distance, "results not sorted by distance");
+        }
+        let found_ids = ids(found.iter().map(|hit| hit.object_id()));
+        for (i, point) in points.iter().enumerate() {
+            let distance = distance_3d(&center, point);
+            let slack = radius * BOUNDARY_TOLERANCE;
+            if distance < radius - slack {
+                prop_assert!(found_ids.contains(&id(i)), "missed {point:?} at {distance} m");
+            } else if distance > radius + slack {
+                prop_assert!(!found_ids.contains(&id(i)), "{point:?} is {distance} m away");
+            }
+        }
+    }
+
+    #[test]
+    fn radius_query_contains_knn_within_radius(
+        points in prop::collection::vec(point3d(), 1..40),
+        center in point3d(),
+        radius in 1.0..=2_000_000.0f64,
+        k in 1..40usize,
+    ) {
+        let db = load(&points);
+        let nearest = db.knn("p", &center, k).unwrap();
+        prop_assert_eq!(nearest.len(), k.min(points.len()));
+
+        let within = ids(
+            db.query_radius("p", &center, radius, points.len())
+                .unwrap()
+                .iter()
+                .map(|hit| hit.object_id()),
+        );
+        for hit in &nearest {
+            let (loc, distance) = (&hit.location, hit.distance);
+            if distance < radius * (1.0 - BOUNDARY_TOLERANCE) {
+                prop_assert!(
+                    within.contains(&loc.object_id),
+                    "knn found {} at {} m, radius query did not",
+                    loc.object_id,
+                    distance
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bbox_query_matches_brute_force(
+        points in prop::collection::vec(prop_oneof![grid_point(), point3d()], 1..60),
+        bounds in bbox(),
+    ) {
+        let (min_x, min_y, max_x, max_y) = bounds;
+        let db = load(&points);
+        let found = db.query_bbox("p", min_x, min_y, max_x, max_y, points.len()).unwrap();
+        let expected: BTreeSet<String> = (0..points.len())
+            .filter(|&i| in_box(&points[i], bounds))
+            .map(id)
+            .collect();
+        prop_assert_eq!(ids(found.iter().map(|loc| loc.object_id.as_str())), expected.clone());
+
+        // A paged scan covers the same objects exactly once.
+        let mut paged = Vec::new();
+        let mut token = None;
+        loop {
+            let page = db
+                .query_bbox_page("p", min_x, min_y, max_x, max_y, 7, token.as_deref())
+                .unwrap();
+            paged.extend(page.items.iter().map(|loc| loc.object_id.clone()));
+            match page.next_token {
+                Some(next) => token = Some(next),
+                None => break,
+            }
+        }
+        let unique: BTreeSet<String> = paged.iter().cloned().collect();
+        prop_assert_eq!(unique.len(), paged.len(), "paged scan returned duplicates");
+        prop_assert_eq!(unique, expected);
+    }
+
+    #[test]
+    fn bbox_query_is_mirror_symmetric(
+        points in prop::collection::vec(prop_oneof![grid_point(), point3d()], 1..60),
+        bounds in bbox(),
+    ) {
+        let (min_x, min_y, max_x, max_y) = bounds;
+        let mirrored: Vec<Point3d> =
+            points.iter().map(|p| Point3d::new(-p.x(), -p.y(), p.z())).collect();
+
+        let original = load(&points)
+            .query_bbox("p", min_x, min_y, max_x, max_y, points.len())
+            .unwrap();
+        let reflected = load(&mirrored)
+            .query_bbox("p", -max_x, -max_y, -min_x, -min_y, points.len())
+            .unwrap();
+        prop_assert_eq!(
+            ids(original.iter().map(|loc| loc.object_id.as_str())),
+            ids(reflected.iter().map(|loc| loc.object_id.as_str()))
+        );
+    }
+
+    #[test]
+    fn bbox_intersection_is_symmetric(a in bbox(), b in bbox()) {
+        let a = bounding_box(a.0, a.1, a.2, a.3).unwrap();
+        let b = bounding_box(b.0, b.1, b.2, b.3).unwrap();
+        prop_assert_eq!(bboxes_intersect(&a, &b), bboxes_intersect(&b, &a));
+        prop_assert!(bboxes_intersect(&a, &a));
+    }
+
+    #[test]
+    fn polygon_query_matches_brute_force(
+        polygon in polygon(),
+        points in prop::collection::vec(
+            (-175.0..=175.0f64, -85.0..=85.0f64).prop_map(|(x, y)| Point3d::new(x, y, 0.0)),
+            1..60,
+        ),
+    ) {
+        let db = load(&points);
+        let found = db.query_polygon("p", &polygon, points.len()).unwrap();
+        let expected: BTreeSet<String> = (0..points.len())
+            .filter(|&i| polygon.inner().contains(&geo::Point::new(points[i].x(), points[i].y())))
+            .map(id)
+            .collect();
+        prop_assert_eq!(ids(found.iter().map(|loc| loc.object_id.as_str())), expected);
+    }
+
+    #[test]
+    fn queries_return_each_object_once(
+        points in prop::collection::vec(prop_oneof![grid_point(), point3d()], 1..60),
+        center in point3d(),
+        radius in prop_oneof![1.0..=5_000.0f64, 5_000.0..=2_000_000.0f64],
+        bounds in bbox(),
+    ) {
+        let db = load(&points);
+        let n = points.len();
+        let unique = |found: Vec<String>| {
+            let set: BTreeSet<String> = found.iter().cloned().collect();
+            set.len() == found.len()
+        };
+        let keyed = |found: Vec<NearbyHit>| {
+            found.iter().map(|hit| hit.object_id().to_string()).collect::<Vec<_>>()
+        };
+
+        for metric in [
+            DistanceMetric::Haversine,
+            DistanceMetric::Geodesic,
+            DistanceMetric::Rhumb,
+        ] {
+            let found = db.query_radius_with_metric("p", &center, radius, n, metric).unwrap();
+            prop_assert!(unique(keyed(found)), "{:?} radius query repeated an object", metric);
+        }
+        let center_2d = Point::new(center.x(), center.y());
+        let found = db.query_within_cylinder("p", center_2d, 0.0, 1000.0, radius, n).unwrap();
+        prop_assert!(unique(keyed(found)), "cylinder query repeated an object");
+        let found = db.knn_2d("p", &center_2d, n, Some(radius), DistanceMetric::Haversine).unwrap();
+        prop_assert!(unique(keyed(found)), "knn_2d repeated an object");
+        prop_assert!(unique(keyed(db.knn("p", &center, n).unwrap())), "knn repeated an object");
+
+        let (min_x, min_y, max_x, max_y) = bounds;
+        let found = db.query_bbox("p", min_x, min_y, max_x, max_y, n).unwrap();
+        prop_assert!(
+            unique(found.iter().map(|loc| loc.object_id.clone()).collect()),
+            "bbox query repeated an object"
+        );
+        // Overlapping areas: an object inside both still counts once.
+        let either = Predicate::WithinBbox { min_x, min_y, max_x, max_y }.or(
+            Predicate::WithinRadius { center: center_2d, radius },
+        );
+        let found = db.query("p", &either, n).unwrap();
+        prop_assert!(
+            unique(found.iter().map(|loc| loc.object_id.clone()).collect()),
+            "composite query repeated an object"
+        );
+    }
+
+    #[test]
+    fn nearest_zones_are_unique(
+        zones in prop::collection::vec(bbox(), 1..12),
+        center in (lon(), lat()).prop_map(|(x, y)| Point::new(x, y)),
+        k in 1..12usize,
+    ) {
+        let db = Spatio::memory().unwrap();
+        for (i, (min_x, min_y, max_x, max_y)) in zones.iter().enumerate() {
+            db.insert_zone(
+                "p",
+                &format!("z{i}"),
+                ZoneGeometry::BBox(BoundingBox2D::new(*min_x, *min_y, *max_x, *max_y)),
+                serde_json::json!({}),
+            )
+            .unwrap();
+        }
+        let found: Vec<String> = db
+            .nearest_zones("p", &center, k)
+            .unwrap()
+            .iter()
+            .map(|hit| hit.zone.zone_id.clone())
+            .collect();
+        let set: BTreeSet<&String> = found.iter().collect();
+        prop_assert_eq!(set.len(), found.len(), "a zone was returned twice");
+        prop_assert_eq!(found.len(), k.min(zones.len()));
+    }
+
+    #[test]
+    fn distances_are_symmetric_and_non_negative(
+        a in (lon(), lat()).prop_map(|(x, y)| Point::new(x, y)),
+        b in (lon(), lat()).prop_map(|(x, y)| Point::new(x, y)),
+    ) {
+        for metric in [
+            DistanceMetric::Haversine,
+            DistanceMetric::Geodesic,
+            DistanceMetric::Rhumb,
+            DistanceMetric::Euclidean,
+        ] {
+            let ab = distance_between(&a, &b, metric);
+            let ba = distance_between(&b, &a, metric);
+            prop_assert!(ab >= 0.0, "{metric:?} distance is negative: {ab}");
+            prop_assert!(
+                (ab - ba).abs() <= 1e-6 * ab.max(1.0),
+                "{metric:?} is asymmetric: {ab} vs {ba}"
+            );
+        }
+        prop_assert_eq!(distance_between(&a, &a, DistanceMetric::Haversine), 0.0);
+    }
+}
@@ -63,12 +63,96 @@ mod tests {
             assert_eq!(aircraft.len(), 1, "Should recover 1 aircraft");
 
             // Verify specific object
-            let (truck, _) = vehicles
+            let truck = &vehicles
                 .iter()
-                .find(|(v, _)| v.object_id == "truck_001")
-                .unwrap();
+                .find(|hit| hit.object_id() == "truck_001")
+                .unwrap()
+                .location;
             assert_eq!(truck.position.x(), -74.0);
             assert_eq!(truck.position.y(), 40.0);
         }
     }
+
+    /// The `.idx` file the database saves its spatial indexes to.
+    fn index_snapshot(dir: &std::path::Path) -> std::path::PathBuf {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.extension().is_some_and(|ext| ext == "idx"))
+            .expect("index snapshot saved")
+    }
+
+    #[test]
+    fn test_restart_loads_saved_indexes() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        let center = Point3d::new(-74.0, 40.0, 0.0);
+        let count_near = |db: &DB| db.query_radius("fleet", &center, 50_000.0, 1_000).unwrap();
+
+        {
+            let db = DB::open(&db_path).unwrap();
+            for i in 0..200 {
+                let point = Point3d::new(-74.0 + i as f64 * 0.001, 40.0, 0.0);
+                db.upsert(
+                    "fleet",
+                    &format!("v{i}"),
+                    point,
+                    serde_json::json!({}),
+                    None,
+                )
+                .unwrap();
+            }
+        }
+        // Indexed point by point, then saved.
+        {
+            let db = DB::open(&db_path).unwrap();
+            assert_eq!(count_near(&db).len(), 200);
+            db.upsert(
+                "fleet",
+                "v0",
+                Point3d::new(10.0, 10.0, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+            db.delete("fleet", "v1").unwrap();
+        }
+        // Loaded, then fixed up for the move and the delete.
+        {
+            let db = DB::open(&db_path).unwrap();
+            assert!(db.verify().unwrap().is_consistent());
+            let hits = count_near(&db);
+            assert_eq!(hits.len(), 198);
+            assert!(
+                hits.iter()
+                    .all(|hit| hit.object_id() != "v0" && hit.object_id() != "v1")
+            );
+            let moved = db
+                .query_radius("fleet", &Point3d::new(10.0, 10.0, 0.0), 100.0, 10)
+                .unwrap();
+            assert_eq!(moved.len(), 1);
+        }
+
+        // A damaged snapshot is ignored and rebuilt.
+        let snapshot = index_snapshot(dir.path());
+        let mut bytes = std::fs::read(&snapshot).unwrap();
+        bytes.truncate(bytes.len() / 2);
+        std::fs::write(&snapshot, bytes).unwrap();
+        {
+            let db = DB::open(&db_path).unwrap();
+            assert!(db.verify().unwrap().is_consistent());
+            assert_eq!(count_near(&db).len(), 198);
+            db.upsert(
+                "fleet",
+                "v2",
+                Point3d::new(-74.5, 40.0, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+        }
+        let db = DB::open(&db_path).unwrap();
+        assert!(db.verify().unwrap().is_consistent());
+        assert_eq!(count_near(&db).len(), 198);
+    }
 }
@@ -125,7 +125,8 @@ fn test_3d_cylinder_query_altitude_pruning() {
     }
 
     // All results should be within altitude bounds
-    for (loc, _) in &narrow_results {
+    for hit in &narrow_results {
+        let loc = &hit.location;
         assert!(
             loc.position.z() >= 2000.0 && loc.position.z() <= 3000.0,
             "Point altitude {} outside range [2000, 3000]",
@@ -0,0 +1,21 @@
+//! Spatio Router
+//!
+//! Client-side sharding over independent Spatio servers. Objects are placed by
+//! namespace and geohash cell on a consistent hash ring, and queries are
+//! scatter-gathered across the shards whose cells they overlap.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use spatio_router::ShardRouter;
+//!
+//! let router = ShardRouter::connect(&addrs).await?;
+//! router.upsert("ns", "id", point, metadata).await?;
+//! let nearby = router.query_radius("ns", center, 500.0, 10, None).await?;
+//! ```
+
+pub mod ring;
+mod router;
+
+pub use router::{DEFAULT_GEOHASH_PRECISION, Result, RouterError, ShardRouter, ShardWrite};
+pub use spatio_types::geohash;
@@ -0,0 +1,112 @@
+//! Consistent hash ring.
+//!
+//! Each shard is placed on the ring at many pseudo-random points (virtual
+//! nodes) derived from its name; a key belongs to the first shard point at or
+//! after the key's hash. Adding or removing a shard only moves the keys in the
+//! arcs it gains or loses, roughly `1 / shards` of the total.
+
+use std::collections::BTreeMap;
+
+/// Virtual nodes per shard; enough to keep shard loads within a few percent.
+pub const DEFAULT_VIRTUAL_NODES: usize = 128;
+
+#[derive(Debug, Clone)]
+pub struct HashRing {
+    points: BTreeMap<u64, usize>,
+    shards: usize,
+}
+
+impl HashRing {
+    /// Ring over shards identified by `names` (typically server addresses).
+    ///
+    /// Shard `i` is the `i`-th name. Names must be unique and stable: a key's
+    /// placement depends only on the names, not on their order.
+    pub fn new<I, S>(names: I, virtual_nodes: usize) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let virtual_nodes = virtual_nodes.max(1);
+        let mut points = BTreeMap::new();
+        let mut shards = 0;
+        for (shard, name) in names.into_iter().enumerate() {
+            for vnode in 0..virtual_nodes {
+                let point = hash(format!("{}#{}", name.as_ref(), vnode).as_bytes());
+                points.insert(point, shard);
+            }
+            shards += 1;
+        }
+        Self { points, shards }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards
+    }
+
+    /// Shard owning `key`, or `None` for an empty ring.
+    pub fn shard_for(&self, key: &str) -> Option<usize> {
+        let h = hash(key.as_bytes());
+        self.points
+            .range(h..)
+            .next()
+            .or_else(|| self.points.iter().next())
+            .map(|(_, &shard)| shard)
+    }
+}
+
+/// Stable 64-bit hash: FNV-1a followed by a SplitMix64 finalizer, so that
+/// similar keys (adjacent geohashes, `addr#0`/`addr#1`) spread over the ring.
+/// Unlike `std`'s hasher, the output never changes between builds.
+fn hash(bytes: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in bytes {
+        h ^= u64::from(b);
+        h = h.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    h ^= h >> 30;
+    h = h.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h ^= h >> 27;
+    h = h.wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^ (h >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> impl Iterator<Item = String> {
+        (0..20_000).map(|i| format!("ns/{:06x}", i * 7919))
+    }
+
+    #[test]
+    fn test_keys_spread_evenly() {
+        let ring = HashRing::new(["a:1", "b:1", "c:1", "d:1"], DEFAULT_VIRTUAL_NODES);
+        let mut counts = [0usize; 4];
+        for key in keys() {
+            counts[ring.shard_for(&key).unwrap()] += 1;
+        }
+        for count in counts {
+            // 5000 expected per shard.
+            assert!((4000..6000).contains(&count), "{:?}", counts);
+        }
+        assert_eq!(HashRing::new(Vec::<&str>::new(), 8).shard_for("x"), None);
+    }
+
+    #[test]
+    fn test_adding_a_shard_moves_few_keys() {
+        let before = HashRing::new(["a:1", "b:1", "c:1"], DEFAULT_VIRTUAL_NODES);
+        let after = HashRing::new(["a:1", "b:1", "c:1", "d:1"], DEFAULT_VIRTUAL_NODES);
+
+        let mut moved = 0;
+        for key in keys() {
+            let (old, new) = (before.shard_for(&key), after.shard_for(&key));
+            if old != new {
+                // Keys only ever move to the new shard.
+                assert_eq!(new, Some(3));
+                moved += 1;
+            }
+        }
+        // About a quarter of the keys belong to the new shard.
+        assert!((4000..6000).contains(&moved), "moved {}", moved);
+    }
+}
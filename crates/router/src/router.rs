@@ -0,0 +1,506 @@
+//! Shard router over independent Spatio servers.
+
+use crate::geohash;
+use crate::ring::{DEFAULT_VIRTUAL_NODES, HashRing};
+use futures::future::try_join_all;
+use spatio_client::{ClientError, CurrentLocation, QueryHit, SpatioClient};
+use spatio_types::geo::{DistanceMetric, Point};
+use spatio_types::point::Point3d;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use thiserror::Error;
+
+/// Default geohash length of shard keys: cells of roughly 39 x 20 km, so a
+/// metro area spans a handful of shards and most radius queries touch one or
+/// two.
+pub const DEFAULT_GEOHASH_PRECISION: usize = 4;
+
+/// Queries whose area covers more cells than this go to every shard instead
+/// of enumerating cells.
+const MAX_COVER_CELLS: usize = 256;
+
+/// Meters per degree of latitude (and of longitude at the equator).
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+#[derive(Error, Debug)]
+pub enum RouterError {
+    #[error("Router has no shards")]
+    NoShards,
+    #[error("Geohash precision must be between 1 and {max}, got: {0}", max = geohash::MAX_PRECISION)]
+    InvalidPrecision(usize),
+    #[error("Shard {shard}: {source}")]
+    Shard {
+        shard: usize,
+        #[source]
+        source: ClientError,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, RouterError>;
+
+/// Where a routed write landed: the shard index and the sequence number that
+/// shard assigned (sequences are per shard, not global).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShardWrite {
+    pub shard: usize,
+    pub sequence: u64,
+}
+
+/// Routes objects to one of N independent servers by namespace and geohash
+/// cell, and scatter-gathers queries across the shards they may touch.
+///
+/// Placement uses a consistent hash ring over the shard names, so adding a
+/// server moves only the cells it takes over. An object moving into a cell
+/// owned by another shard is deleted from its previous shard, which the
+/// router remembers for objects it has written. Objects written by another
+/// router (or before a restart) are found by asking every shard.
+///
+/// Every query returns each object at most once, even when stale copies of
+/// it sit on several shards (a move whose delete from the old shard failed,
+/// or was made by another router) or a query's cells map to the same shard
+/// more than once: queries and [`ShardRouter::get`] keep the copy with the
+/// latest timestamp.
+///
+/// Boxes whose `min_x` is greater than their `max_x` cross the antimeridian
+/// and are split in two.
+pub struct ShardRouter {
+    ring: HashRing,
+    shards: Vec<SpatioClient>,
+    precision: usize,
+    /// Last known shard of each object written through this router ("ns::id").
+    placements: Mutex<HashMap<String, usize>>,
+}
+
+impl ShardRouter {
+    /// Connect to every server in `addrs`; shards are named by address.
+    pub async fn connect(addrs: &[SocketAddr]) -> Result<Self> {
+        let mut shards = Vec::with_capacity(addrs.len());
+        for (shard, addr) in addrs.iter().enumerate() {
+            let client = SpatioClient::connect(*addr)
+                .await
+                .map_err(|source| RouterError::Shard { shard, source })?;
+            shards.push((addr.to_string(), client));
+        }
+        Self::new(shards)
+    }
+
+    /// Router over already connected clients, each with a unique, stable name.
+    pub fn new(shards: Vec<(String, SpatioClient)>) -> Result<Self> {
+        if shards.is_empty() {
+            return Err(RouterError::NoShards);
+        }
+        let (names, clients): (Vec<String>, Vec<SpatioClient>) = shards.into_iter().unzip();
+        Ok(Self {
+            ring: HashRing::new(&names, DEFAULT_VIRTUAL_NODES),
+            shards: clients,
+            precision: DEFAULT_GEOHASH_PRECISION,
+            placements: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Set the geohash length of shard keys. Longer hashes spread a dense
+    /// area over more shards at the cost of more fan-out per query.
+    pub fn with_geohash_precision(mut self, precision: usize) -> Result<Self> {
+        if !(1..=geohash::MAX_PRECISION).contains(&precision) {
+            return Err(RouterError::InvalidPrecision(precision));
+        }
+        self.precision = precision;
+        Ok(self)
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Shard owning the cell containing `(lon, lat)` in `namespace`.
+    pub fn shard_for(&self, namespace: &str, lon: f64, lat: f64) -> usize {
+        self.shard_for_cell(namespace, &geohash::encode(lon, lat, self.precision))
+    }
+
+    fn shard_for_cell(&self, namespace: &str, cell: &str) -> usize {
+        let key = format!("{}/{}", namespace, cell);
+        // The ring is never empty: `new` rejects an empty shard list.
+        self.ring.shard_for(&key).unwrap_or(0)
+    }
+
+    /// Shards owning any cell overlapping the box, or every shard when the box
+    /// is too large to enumerate. `min_x` must not exceed `max_x`.
+    fn shards_for_bbox(
+        &self,
+        namespace: &str,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+    ) -> Vec<usize> {
+        let Some(cells) =
+            geohash::cover(min_x, min_y, max_x, max_y, self.precision, MAX_COVER_CELLS)
+        else {
+            return (0..self.shards.len()).collect();
+        };
+        let mut shards: Vec<usize> = cells
+            .iter()
+            .map(|cell| self.shard_for_cell(namespace, cell))
+            .collect();
+        shards.sort_unstable();
+        shards.dedup();
+        shards
+    }
+
+    /// Shards that may hold objects within `radius` meters of `center`.
+    fn shards_for_radius(&self, namespace: &str, center: &Point3d, radius: f64) -> Vec<usize> {
+        let lat_delta = radius / METERS_PER_DEGREE;
+        let cos_lat = center.y().to_radians().cos();
+        let (min_y, max_y) = (center.y() - lat_delta, center.y() + lat_delta);
+        if min_y < -90.0 || max_y > 90.0 || cos_lat < 1e-6 {
+            // The circle wraps a pole.
+            return (0..self.shards.len()).collect();
+        }
+        let lon_delta = lat_delta / cos_lat;
+        if lon_delta >= 180.0 {
+            return (0..self.shards.len()).collect();
+        }
+        let wrap = |x: f64| match x {
+            x if x < -180.0 => x + 360.0,
+            x if x > 180.0 => x - 360.0,
+            x => x,
+        };
+        let (min_x, max_x) = (wrap(center.x() - lon_delta), wrap(center.x() + lon_delta));
+        let mut shards: Vec<usize> = split_antimeridian(min_x, max_x)
+            .into_iter()
+            .flat_map(|(min_x, max_x)| self.shards_for_bbox(namespace, min_x, min_y, max_x, max_y))
+            .collect();
+        shards.sort_unstable();
+        shards.dedup();
+        shards
+    }
+
+    fn client(&self, shard: usize) -> &SpatioClient {
+        &self.shards[shard]
+    }
+
+    fn placement(&self, key: &str) -> Option<usize> {
+        self.placements
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(key)
+            .copied()
+    }
+
+    fn set_placement(&self, key: String, shard: Option<usize>) {
+        let mut placements = self.placements.lock().unwrap_or_else(|e| e.into_inner());
+        match shard {
+            Some(shard) => placements.insert(key, shard),
+            None => placements.remove(&key),
+        };
+    }
+
+    /// Run `f` on each shard in `shards` concurrently, failing on the first error.
+    async fn scatter<'a, T, F, Fut>(&'a self, shards: &[usize], f: F) -> Result<Vec<T>>
+    where
+        F: Fn(&'a SpatioClient) -> Fut,
+        Fut: Future<Output = spatio_client::Result<T>>,
+    {
+        try_join_all(shards.iter().map(|&shard| {
+            let request = f(self.client(shard));
+            async move {
+                request
+                    .await
+                    .map_err(|source| RouterError::Shard { shard, source })
+            }
+        }))
+        .await
+    }
+
+    /// Upsert an object on the shard owning its cell.
+    pub async fn upsert(
+        &self,
+        namespace: &str,
+        id: &str,
+        point: Point3d,
+        metadata: serde_json::Value,
+    ) -> Result<ShardWrite> {
+        let shard = self.shard_for(namespace, point.x(), point.y());
+        let sequence = self
+            .client(shard)
+            .upsert(namespace, id, point, metadata)
+            .await
+            .map_err(|source| RouterError::Shard { shard, source })?;
+
+        let key = format!("{}::{}", namespace, id);
+        if let Some(previous) = self.placement(&key)
+            && previous != shard
+        {
+            // The object crossed into another shard's cell.
+            self.client(previous)
+                .delete(namespace, id)
+                .await
+                .map_err(|source| RouterError::Shard {
+                    shard: previous,
+                    source,
+                })?;
+        }
+        self.set_placement(key, Some(shard));
+
+        Ok(ShardWrite { shard, sequence })
+    }
+
+    /// Current location of an object, from its known shard or else the
+    /// latest copy on any shard.
+    pub async fn get(&self, namespace: &str, id: &str) -> Result<Option<CurrentLocation>> {
+        let key = format!("{}::{}", namespace, id);
+        if let Some(shard) = self.placement(&key) {
+            return self
+                .client(shard)
+                .get(namespace, id)
+                .await
+                .map_err(|source| RouterError::Shard { shard, source });
+        }
+
+        let all: Vec<usize> = (0..self.shards.len()).collect();
+        let found = self
+            .scatter(&all, |client| client.get(namespace, id))
+            .await?;
+        Ok(found.into_iter().flatten().reduce(|kept, copy| {
+            if copy.timestamp > kept.timestamp {
+                copy
+            } else {
+                kept
+            }
+        }))
+    }
+
+    /// Delete an object from its known shard, or from every shard.
+    pub async fn delete(&self, namespace: &str, id: &str) -> Result<()> {
+        let key = format!("{}::{}", namespace, id);
+        let shards = match self.placement(&key) {
+            Some(shard) => vec![shard],
+            None => (0..self.shards.len()).collect(),
+        };
+        self.scatter(&shards, |client| client.delete(namespace, id))
+            .await?;
+        self.set_placement(key, None);
+        Ok(())
+    }
+
+    /// Objects within `radius` of `center`, nearest first, gathered from the
+    /// shards whose cells the circle overlaps.
+    ///
+    /// Shards are picked assuming a radius in meters; a Euclidean `metric`,
+    /// whose radius is in coordinate units, asks every shard.
+    pub async fn query_radius(
+        &self,
+        namespace: &str,
+        center: Point3d,
+        radius: f64,
+        limit: usize,
+        metric: Option<DistanceMetric>,
+    ) -> Result<Vec<QueryHit>> {
+        let shards = match metric {
+            Some(DistanceMetric::Euclidean) => (0..self.shards.len()).collect(),
+            // Geodesic and rhumb distances run up to a percent past haversine.
+            Some(DistanceMetric::Geodesic | DistanceMetric::Rhumb) => {
+                self.shards_for_radius(namespace, &center, radius * 1.01)
+            }
+            Some(DistanceMetric::Haversine) | None => {
+                self.shards_for_radius(namespace, &center, radius)
+            }
+        };
+        let results = self
+            .scatter(&shards, |client| {
+                client.query_radius(namespace, center.clone(), radius, limit, metric)
+            })
+            .await?;
+        Ok(merge_nearest(results, limit))
+    }
+
+    /// The `k` objects nearest to `center` across all shards.
+    pub async fn knn(&self, namespace: &str, center: Point3d, k: usize) -> Result<Vec<QueryHit>> {
+        let all: Vec<usize> = (0..self.shards.len()).collect();
+        let results = self
+            .scatter(&all, |client| client.knn(namespace, center.clone(), k))
+            .await?;
+        Ok(merge_nearest(results, k))
+    }
+
+    /// The `k` objects nearest to `center` by horizontal distance across all
+    /// shards, optionally only those within `max_distance`.
+    pub async fn knn_2d(
+        &self,
+        namespace: &str,
+        center: Point,
+        k: usize,
+        max_distance: Option<f64>,
+        metric: Option<DistanceMetric>,
+    ) -> Result<Vec<QueryHit>> {
+        let all: Vec<usize> = (0..self.shards.len()).collect();
+        let results = self
+            .scatter(&all, |client| {
+                client.knn_2d(namespace, center, k, max_distance, metric)
+            })
+            .await?;
+        Ok(merge_nearest(results, k))
+    }
+
+    /// Objects inside a 2D bounding box, gathered from the shards whose cells
+    /// the box overlaps. A box with `min_x > max_x` wraps across the
+    /// antimeridian.
+    pub async fn query_bbox(
+        &self,
+        namespace: &str,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        limit: usize,
+    ) -> Result<Vec<CurrentLocation>> {
+        let mut results = Vec::new();
+        for (min_x, max_x) in split_antimeridian(min_x, max_x) {
+            let shards = self.shards_for_bbox(namespace, min_x, min_y, max_x, max_y);
+            results.extend(
+                self.scatter(&shards, |client| {
+                    client.query_bbox(namespace, min_x, min_y, max_x, max_y, limit)
+                })
+                .await?,
+            );
+        }
+        Ok(merge_unique(results, limit))
+    }
+}
+
+/// The longitude ranges of a box from `min_x` to `max_x`, in two pieces if
+/// it crosses the antimeridian (`min_x > max_x`).
+fn split_antimeridian(min_x: f64, max_x: f64) -> Vec<(f64, f64)> {
+    if min_x > max_x {
+        vec![(min_x, 180.0), (-180.0, max_x)]
+    } else {
+        vec![(min_x, max_x)]
+    }
+}
+
+/// Keep the latest copy of each object, in the order objects first appear;
+/// of copies with the same timestamp, the first.
+fn latest_copies<T>(
+    copies: impl IntoIterator<Item = T>,
+    id: impl Fn(&T) -> &str,
+    timestamp: impl Fn(&T) -> f64,
+) -> Vec<T> {
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut kept: Vec<T> = Vec::new();
+    for copy in copies {
+        match positions.get(id(&copy)) {
+            Some(&i) => {
+                if timestamp(&copy) > timestamp(&kept[i]) {
+                    kept[i] = copy;
+                }
+            }
+            None => {
+                positions.insert(id(&copy).to_string(), kept.len());
+                kept.push(copy);
+            }
+        }
+    }
+    kept
+}
+
+/// Concatenate per-shard results in shard order, keeping the latest copy of
+/// each object, up to `limit`.
+fn merge_unique(results: Vec<Vec<CurrentLocation>>, limit: usize) -> Vec<CurrentLocation> {
+    let mut merged = latest_copies(
+        results.into_iter().flatten(),
+        |location| &location.object_id,
+        |location| location.timestamp,
+    );
+    merged.truncate(limit);
+    merged
+}
+
+/// Merge per-shard distance-sorted results into the overall nearest `limit`,
+/// keeping the latest copy of each object.
+fn merge_nearest(results: Vec<Vec<QueryHit>>, limit: usize) -> Vec<QueryHit> {
+    let mut merged = latest_copies(
+        results.into_iter().flatten(),
+        |hit| &hit.object_id,
+        |hit| hit.timestamp,
+    );
+    merged.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+    merged.truncate(limit);
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(id: &str) -> CurrentLocation {
+        CurrentLocation {
+            object_id: id.to_string(),
+            position: Point3d::new(0.0, 0.0, 0.0),
+            metadata: Vec::new(),
+            timestamp: 10.0,
+        }
+    }
+
+    fn hit(id: &str, distance: f64) -> QueryHit {
+        QueryHit::new(location(id), distance)
+    }
+
+    #[test]
+    fn test_merge_nearest_orders_dedups_and_limits() {
+        let merged = merge_nearest(
+            vec![
+                vec![hit("a", 1.0), hit("c", 5.0)],
+                vec![hit("b", 2.0), hit("a", 3.0)],
+                vec![],
+            ],
+            2,
+        );
+        let ids: Vec<_> = merged
+            .iter()
+            .map(|h| (h.object_id.as_str(), h.distance))
+            .collect();
+        assert_eq!(ids, vec![("a", 1.0), ("b", 2.0)]);
+    }
+
+    #[test]
+    fn test_merge_unique_keeps_latest_copy() {
+        // A stale copy of "b" on the first shard, left behind when it moved
+        // across a cell boundary.
+        let mut stale = location("b");
+        stale.position = Point3d::new(1.0, 1.0, 0.0);
+        stale.timestamp = 5.0;
+        let merged = merge_unique(
+            vec![
+                vec![location("a"), stale],
+                vec![location("b"), location("c")],
+            ],
+            10,
+        );
+        let ids: Vec<_> = merged.iter().map(|l| l.object_id.as_str()).collect();
+        assert_eq!(ids, ["a", "b", "c"]);
+        assert_eq!(merged[1].position.x(), 0.0);
+        assert_eq!(merge_unique(vec![vec![location("a")]; 3], 10).len(), 1);
+    }
+
+    #[test]
+    fn test_merge_nearest_keeps_latest_copy() {
+        let mut stale = hit("a", 1.0);
+        stale.timestamp = 5.0;
+        let merged = merge_nearest(vec![vec![stale, hit("b", 2.0)], vec![hit("a", 3.0)]], 10);
+        let ids: Vec<_> = merged
+            .iter()
+            .map(|h| (h.object_id.as_str(), h.distance))
+            .collect();
+        assert_eq!(ids, vec![("b", 2.0), ("a", 3.0)]);
+    }
+
+    #[test]
+    fn test_boxes_across_the_antimeridian_are_split() {
+        assert_eq!(split_antimeridian(-10.0, 10.0), vec![(-10.0, 10.0)]);
+        assert_eq!(
+            split_antimeridian(170.0, -170.0),
+            vec![(170.0, 180.0), (-180.0, -170.0)]
+        );
+    }
+}
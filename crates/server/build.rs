@@ -0,0 +1,12 @@
+//! Generates the gRPC service from `proto/spatio.proto` when the `grpc`
+//! feature is on.
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/spatio.proto");
+        let descriptors = protox::compile(["spatio.proto"], ["proto"])?;
+        tonic_build::configure().compile_fds(descriptors)?;
+    }
+    Ok(())
+}
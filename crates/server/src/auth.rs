@@ -0,0 +1,72 @@
+//! Connection authentication.
+//!
+//! An [`Authenticator`] decides whether a freshly accepted connection may
+//! issue requests, once per connection and before any request is read. With
+//! TLS enabled it runs after the handshake, so it can check the client's
+//! certificate chain; without TLS it only has the peer address to go on.
+//!
+//! An admitted client may also be named by a principal, which scopes its
+//! idempotency keys (see [`crate::idempotency`]) independently of the address
+//! it connects from.
+//!
+//! Per-request policy (which namespaces a client may touch, say) belongs in
+//! [`crate::middleware`] instead.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::net::SocketAddr;
+
+/// What is known about a connecting client.
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    pub peer: SocketAddr,
+    /// DER-encoded certificates the client presented, leaf first. Empty
+    /// without TLS or when the client sent none.
+    pub client_certificates: Vec<Vec<u8>>,
+}
+
+/// Admits or refuses connections.
+///
+/// Implemented for closures, so an allow-list is a one-liner:
+///
+/// ```
+/// use spatio_server::auth::{Authenticator, ConnectionInfo};
+///
+/// fn loopback_only() -> impl Authenticator {
+///     |conn: &ConnectionInfo| {
+///         if conn.peer.ip().is_loopback() {
+///             Ok(())
+///         } else {
+///             Err(format!("{} is not a local client", conn.peer))
+///         }
+///     }
+/// }
+/// ```
+pub trait Authenticator: Send + Sync + 'static {
+    /// `Ok` to accept the connection; `Err` with the reason, which is logged,
+    /// to close it.
+    fn authenticate(&self, conn: &ConnectionInfo) -> Result<(), String>;
+
+    /// Who an admitted connection belongs to, the same wherever the client
+    /// connects from. By default the client's leaf certificate, so mutual
+    /// TLS names clients without further setup; `None` when there is none.
+    fn principal(&self, conn: &ConnectionInfo) -> Option<String> {
+        let leaf = conn.client_certificates.first()?;
+        let mut hasher = DefaultHasher::new();
+        leaf.hash(&mut hasher);
+        Some(format!("cert:{:016x}", hasher.finish()))
+    }
+}
+
+/// The principal of an authenticated request, as a request extension.
+#[cfg(any(feature = "http", feature = "grpc"))]
+#[derive(Debug, Clone)]
+pub(crate) struct Principal(pub std::sync::Arc<str>);
+
+impl<F> Authenticator for F
+where
+    F: Fn(&ConnectionInfo) -> Result<(), String> + Send + Sync + 'static,
+{
+    fn authenticate(&self, conn: &ConnectionInfo) -> Result<(), String> {
+        self(conn)
+    }
+}
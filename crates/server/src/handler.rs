@@ -1,39 +1,181 @@
 //! Handler implementation for Spatio RPC service
 
-use crate::protocol::{CurrentLocation, LocationUpdate, SpatioService, Stats};
+use crate::idempotency::{IdempotencyCache, IdempotencyConfig, IdempotencyKey};
+use crate::protocol::{
+    AggregateCell, BboxPage, CurrentLocation, HistoryEntry, KnnQuery, LocationUpdate, Page,
+    QueryHit, RadiusQuery, RegionEvent, SpatioService, Stats, Topology, TrajectoryMatch,
+    TrajectorySlice, WarmUpReport, busy_error, foreign_log_error, not_primary_error,
+    stale_replica_error,
+};
 use crate::reader::Reader;
+use crate::saved_queries::{QueryArgs, QueryTemplate, SavedQueries};
+use crate::scheduler::{QueryScheduler, SchedulerConfig};
+use crate::subscriptions::Subscriptions;
+use crate::topology::ServerTopology;
 use crate::writer::WriteOp;
 use spatio::Spatio;
+use spatio_types::config::{HistoryEventKind, ScanDirection};
 use spatio_types::geo::{DistanceMetric, Point, Polygon};
 use spatio_types::point::Point3d;
+use spatio_types::query::Predicate;
+use spatio_types::time::TimeRange;
+use spatio_types::trajectory::TrajectorySummary;
+use std::ops::Bound;
 use std::sync::Arc;
+use std::time::Duration;
 use tarpc::context;
 use tokio::sync::{mpsc, oneshot};
+use tracing::Instrument;
 
 /// Upper bound on result/neighbour counts accepted from the wire, so a single
 /// request can't drive an unbounded allocation.
 const MAX_QUERY_LIMIT: usize = 100_000;
 
+/// How long a compaction or checkpoint may hold the trajectory log before
+/// new writes are turned away as busy rather than queued behind it.
+const WRITE_STALL_GRACE: Duration = Duration::from_millis(20);
+
+/// Retry hint given while a log rewrite has no previous run to go by, or has
+/// already outrun it.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_millis(100);
+
+/// How long [`SpatioService::wait_applied`] waits for a write unless
+/// configured otherwise.
+pub const DEFAULT_APPLIED_WAIT: Duration = Duration::from_secs(1);
+
 #[derive(Clone)]
 pub struct Handler {
+    db: Arc<Spatio>,
     write_tx: mpsc::Sender<WriteOp>,
     reader: Reader,
+    saved_queries: SavedQueries,
+    scheduler: QueryScheduler,
+    idempotency: IdempotencyCache,
+    /// Principal the requests come from, to scope their idempotency keys;
+    /// empty when unauthenticated.
+    client: Arc<str>,
+    subscriptions: Subscriptions,
+    topology: ServerTopology,
+    applied_wait: Duration,
+    /// Secret key of jittered queries; they are refused without one.
+    jitter_key: Option<u128>,
 }
 
 impl Handler {
     pub fn new(db: Arc<Spatio>, write_tx: mpsc::Sender<WriteOp>) -> Self {
-        let reader = Reader::new(db);
-        Self { write_tx, reader }
+        let reader = Reader::new(db.clone());
+        Self {
+            db: db.clone(),
+            write_tx,
+            reader,
+            saved_queries: SavedQueries::default(),
+            scheduler: QueryScheduler::default(),
+            idempotency: IdempotencyCache::default(),
+            client: Arc::from(""),
+            subscriptions: Subscriptions::new(db),
+            topology: ServerTopology::default(),
+            applied_wait: DEFAULT_APPLIED_WAIT,
+            jitter_key: None,
+        }
+    }
+
+    /// Remember idempotency keys according to `config` instead of the
+    /// defaults.
+    pub fn with_idempotency(mut self, config: IdempotencyConfig) -> Self {
+        self.idempotency = IdempotencyCache::new(config);
+        self
+    }
+
+    /// Serve requests sent by the principal `client`, whose idempotency keys
+    /// then don't match those of other principals.
+    pub fn for_client(mut self, client: impl Into<Arc<str>>) -> Self {
+        self.client = client.into();
+        self
+    }
+
+    /// Admit namespace queries according to `config` instead of the default
+    /// limits.
+    pub fn with_scheduler(mut self, config: SchedulerConfig) -> Self {
+        self.scheduler = QueryScheduler::new(config);
+        self
+    }
+
+    /// Admit namespace queries through `scheduler`, whose limits it shares
+    /// with the other handlers given it.
+    pub fn with_query_scheduler(mut self, scheduler: QueryScheduler) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+
+    /// Take writes only while `topology` says this server is the primary.
+    pub fn with_topology(mut self, topology: ServerTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// Let [`SpatioService::wait_applied`] wait up to `wait` for a write.
+    pub fn with_applied_wait(mut self, wait: Duration) -> Self {
+        self.applied_wait = wait;
+        self
+    }
+
+    /// Answer [`SpatioService::query_radius_jittered`] by moving hits under
+    /// the secret `key`. Clients never see it, so they can't undo the moves.
+    pub fn with_jitter_key(mut self, key: u128) -> Self {
+        self.jitter_key = Some(key);
+        self
+    }
+
+    /// [`Self::submit_write`], applied at most once per idempotency key when
+    /// one is given.
+    async fn submit_keyed_write(
+        &self,
+        idempotency_key: Option<IdempotencyKey>,
+        make_op: impl FnOnce(oneshot::Sender<Result<u64, String>>, tracing::Span) -> WriteOp
+        + Send
+        + 'static,
+    ) -> Result<u64, String> {
+        let Some(key) = idempotency_key else {
+            return self.submit_write(make_op).await;
+        };
+        let handler = self.clone();
+        let write = async move { handler.submit_write(make_op).await };
+        self.idempotency
+            .run(
+                self.client.clone(),
+                key,
+                write.instrument(tracing::Span::current()),
+            )
+            .await
     }
 
     /// Enqueue a write and await its actual completion on the writer thread.
+    ///
+    /// The op carries the request span so the write is traced under it.
+    /// A replica refuses it with a [`not_primary_error`] naming its primary.
+    /// While a compaction or checkpoint has held the log for longer than
+    /// [`WRITE_STALL_GRACE`], the write is refused with a [`busy_error`]
+    /// instead, so clients back off rather than pile up behind it.
+    ///
+    /// [`busy_error`]: crate::protocol::busy_error
+    /// [`not_primary_error`]: crate::protocol::not_primary_error
     async fn submit_write(
         &self,
-        make_op: impl FnOnce(oneshot::Sender<Result<(), String>>) -> WriteOp,
-    ) -> Result<(), String> {
+        make_op: impl FnOnce(oneshot::Sender<Result<u64, String>>, tracing::Span) -> WriteOp,
+    ) -> Result<u64, String> {
+        let topology = self.topology.current();
+        if topology.role != crate::protocol::Role::Primary {
+            return Err(not_primary_error(topology.primary));
+        }
+        if let Some(maintenance) = self.db.maintenance()
+            && maintenance.elapsed >= WRITE_STALL_GRACE
+        {
+            let retry_after = maintenance.remaining().unwrap_or(DEFAULT_RETRY_AFTER);
+            return Err(busy_error(maintenance.kind, retry_after));
+        }
         let (ack_tx, ack_rx) = oneshot::channel();
         self.write_tx
-            .send(make_op(ack_tx))
+            .send(make_op(ack_tx, tracing::Span::current()))
             .await
             .map_err(|_| "Server storage is overwhelmed or shutting down".to_string())?;
         ack_rx
@@ -43,15 +185,22 @@ impl Handler {
 }
 
 /// Run a blocking reader call on the blocking pool so it can't stall the async
-/// runtime, mapping a join failure to an error string.
+/// runtime, mapping a join failure to an error string. The call runs inside
+/// the request span, so database spans nest under the RPC.
+///
+/// A panic in `f` is resumed on the calling task, where the connection's panic
+/// guard logs it and closes the connection.
 async fn blocking<T, F>(f: F) -> Result<T, String>
 where
     F: FnOnce() -> Result<T, String> + Send + 'static,
     T: Send + 'static,
 {
-    tokio::task::spawn_blocking(f)
-        .await
-        .map_err(|e| format!("Internal error: {e}"))?
+    let span = tracing::Span::current();
+    match tokio::task::spawn_blocking(move || span.in_scope(f)).await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(format!("Internal error: {e}")),
+    }
 }
 
 impl SpatioService for Handler {
@@ -62,13 +211,35 @@ impl SpatioService for Handler {
         id: String,
         point: Point3d,
         metadata: serde_json::Value,
-    ) -> Result<(), String> {
-        self.submit_write(|ack| WriteOp::Upsert {
+        idempotency_key: Option<String>,
+    ) -> Result<u64, String> {
+        let idempotency_key = idempotency_key
+            .map(|key| IdempotencyKey::new(&namespace, key, &(&id, &point, &metadata)));
+        self.submit_keyed_write(idempotency_key, |ack, span| WriteOp::Upsert {
             namespace,
             id,
             point,
             metadata,
             ack,
+            span,
+        })
+        .await
+    }
+
+    async fn upsert_batch(
+        self,
+        _: context::Context,
+        namespace: String,
+        items: Vec<(String, Point3d, serde_json::Value)>,
+        idempotency_key: Option<String>,
+    ) -> Result<u64, String> {
+        let idempotency_key =
+            idempotency_key.map(|key| IdempotencyKey::new(&namespace, key, &items));
+        self.submit_keyed_write(idempotency_key, |ack, span| WriteOp::UpsertBatch {
+            namespace,
+            items,
+            ack,
+            span,
         })
         .await
     }
@@ -79,43 +250,125 @@ impl SpatioService for Handler {
         namespace: String,
         id: String,
     ) -> Result<Option<CurrentLocation>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
         let reader = self.reader;
         blocking(move || reader.get(&namespace, &id)).await
     }
 
+    async fn range(
+        self,
+        _: context::Context,
+        namespace: String,
+        start: Bound<String>,
+        end: Bound<String>,
+        limit: usize,
+        direction: ScanDirection,
+    ) -> Result<Vec<CurrentLocation>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
+        let reader = self.reader;
+        let limit = limit.min(MAX_QUERY_LIMIT);
+        blocking(move || {
+            reader.range(
+                &namespace,
+                start.as_ref().map(String::as_str),
+                end.as_ref().map(String::as_str),
+                limit,
+                direction,
+            )
+        })
+        .await
+    }
+
     async fn delete(
         self,
         _: context::Context,
         namespace: String,
         id: String,
-    ) -> Result<(), String> {
-        self.submit_write(|ack| WriteOp::Delete { namespace, id, ack })
-            .await
+    ) -> Result<u64, String> {
+        self.submit_write(|ack, span| WriteOp::Delete {
+            namespace,
+            id,
+            ack,
+            span,
+        })
+        .await
+    }
+
+    async fn list_namespaces(self, _: context::Context) -> Result<Vec<String>, String> {
+        let reader = self.reader;
+        blocking(move || reader.list_namespaces()).await
+    }
+
+    async fn clear_namespace(self, _: context::Context, namespace: String) -> Result<u64, String> {
+        self.submit_write(|ack, span| WriteOp::ClearNamespace {
+            namespace,
+            ack,
+            span,
+        })
+        .await
+    }
+
+    async fn drop_namespace(self, _: context::Context, namespace: String) -> Result<u64, String> {
+        self.submit_write(|ack, span| WriteOp::DropNamespace {
+            namespace,
+            ack,
+            span,
+        })
+        .await
     }
 
     async fn query_radius(
+        self,
+        _: context::Context,
+        mut query: RadiusQuery,
+    ) -> Result<Vec<QueryHit>, String> {
+        let _permit = self.scheduler.acquire(&query.namespace).await?;
+        let reader = self.reader;
+        query.limit = query.limit.min(MAX_QUERY_LIMIT);
+        blocking(move || reader.query_radius(&query)).await
+    }
+
+    async fn query_radius_jittered(
         self,
         _: context::Context,
         namespace: String,
         center: Point3d,
         radius: f64,
         limit: usize,
-    ) -> Result<Vec<(CurrentLocation, f64)>, String> {
+        jitter: f64,
+    ) -> Result<Vec<QueryHit>, String> {
+        let key = self
+            .jitter_key
+            .ok_or("Jittered queries are disabled: this server has no jitter key")?;
+        let _permit = self.scheduler.acquire(&namespace).await?;
         let reader = self.reader;
         let limit = limit.min(MAX_QUERY_LIMIT);
-        blocking(move || reader.query_radius(&namespace, &center, radius, limit)).await
+        blocking(move || {
+            reader.query_radius_jittered(&namespace, &center, radius, limit, jitter, key)
+        })
+        .await
     }
 
-    async fn knn(
+    async fn knn(self, _: context::Context, mut query: KnnQuery) -> Result<Vec<QueryHit>, String> {
+        let _permit = self.scheduler.acquire(&query.namespace).await?;
+        let reader = self.reader;
+        query.k = query.k.min(MAX_QUERY_LIMIT);
+        blocking(move || reader.knn(&query)).await
+    }
+
+    async fn knn_2d(
         self,
         _: context::Context,
         namespace: String,
-        center: Point3d,
+        center: Point,
         k: usize,
-    ) -> Result<Vec<(CurrentLocation, f64)>, String> {
+        max_distance: Option<f64>,
+        metric: Option<DistanceMetric>,
+    ) -> Result<Vec<QueryHit>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
         let reader = self.reader;
         let k = k.min(MAX_QUERY_LIMIT);
-        blocking(move || reader.knn(&namespace, &center, k)).await
+        blocking(move || reader.knn_2d(&namespace, &center, k, max_distance, metric)).await
     }
 
     async fn query_bbox(
@@ -128,11 +381,87 @@ impl SpatioService for Handler {
         max_y: f64,
         limit: usize,
     ) -> Result<Vec<CurrentLocation>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
         let reader = self.reader;
         let limit = limit.min(MAX_QUERY_LIMIT);
         blocking(move || reader.query_bbox(&namespace, min_x, min_y, max_x, max_y, limit)).await
     }
 
+    #[allow(clippy::too_many_arguments)]
+    async fn query_bbox_with_distances(
+        self,
+        _: context::Context,
+        namespace: String,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        anchor: Option<Point3d>,
+        limit: usize,
+    ) -> Result<Vec<QueryHit>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
+        let reader = self.reader;
+        let limit = limit.min(MAX_QUERY_LIMIT);
+        blocking(move || {
+            reader.query_bbox_with_distances(
+                &namespace,
+                min_x,
+                min_y,
+                max_x,
+                max_y,
+                anchor.as_ref(),
+                limit,
+            )
+        })
+        .await
+    }
+
+    async fn query_bbox_page(
+        self,
+        _: context::Context,
+        namespace: String,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        page_size: usize,
+        token: Option<String>,
+    ) -> Result<BboxPage, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
+        let reader = self.reader;
+        let page_size = page_size.min(MAX_QUERY_LIMIT);
+        blocking(move || {
+            reader.query_bbox_page(
+                &namespace,
+                min_x,
+                min_y,
+                max_x,
+                max_y,
+                page_size,
+                token.as_deref(),
+            )
+        })
+        .await
+    }
+
+    async fn query_radius_page(
+        self,
+        _: context::Context,
+        namespace: String,
+        center: Point3d,
+        radius: f64,
+        page_size: usize,
+        token: Option<String>,
+    ) -> Result<Page<QueryHit>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
+        let reader = self.reader;
+        let page_size = page_size.min(MAX_QUERY_LIMIT);
+        blocking(move || {
+            reader.query_radius_page(&namespace, &center, radius, page_size, token.as_deref())
+        })
+        .await
+    }
+
     async fn query_cylinder(
         self,
         _: context::Context,
@@ -142,26 +471,112 @@ impl SpatioService for Handler {
         max_z: f64,
         radius: f64,
         limit: usize,
-    ) -> Result<Vec<(CurrentLocation, f64)>, String> {
+        metric: Option<DistanceMetric>,
+    ) -> Result<Vec<QueryHit>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
         let reader = self.reader;
         let limit = limit.min(MAX_QUERY_LIMIT);
-        blocking(move || reader.query_cylinder(&namespace, center, min_z, max_z, radius, limit))
-            .await
+        blocking(move || {
+            reader.query_cylinder(&namespace, center, min_z, max_z, radius, limit, metric)
+        })
+        .await
     }
 
     async fn query_trajectory(
+        self,
+        _: context::Context,
+        mut query: TrajectorySlice,
+    ) -> Result<Vec<LocationUpdate>, String> {
+        let _permit = self.scheduler.acquire(&query.namespace).await?;
+        let reader = self.reader;
+        query.limit = query.limit.min(MAX_QUERY_LIMIT);
+        blocking(move || reader.query_trajectory(&query)).await
+    }
+
+    async fn query_trajectory_page(
+        self,
+        _: context::Context,
+        namespace: String,
+        id: String,
+        range: TimeRange,
+        page_size: usize,
+        token: Option<String>,
+    ) -> Result<Page<LocationUpdate>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
+        let reader = self.reader;
+        let page_size = page_size.min(MAX_QUERY_LIMIT);
+        blocking(move || {
+            reader.query_trajectory_page(&namespace, &id, range, page_size, token.as_deref())
+        })
+        .await
+    }
+
+    async fn history(
         self,
         _: context::Context,
         namespace: String,
         id: String,
-        start_time: Option<f64>,
-        end_time: Option<f64>,
+        range: TimeRange,
+        kind: Option<HistoryEventKind>,
         limit: usize,
-    ) -> Result<Vec<LocationUpdate>, String> {
+    ) -> Result<Vec<HistoryEntry>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
         let reader = self.reader;
         let limit = limit.min(MAX_QUERY_LIMIT);
-        blocking(move || reader.query_trajectory(&namespace, &id, start_time, end_time, limit))
-            .await
+        blocking(move || reader.history(&namespace, &id, range, kind, limit)).await
+    }
+
+    async fn trajectory_summary(
+        self,
+        _: context::Context,
+        namespace: String,
+        id: String,
+        range: TimeRange,
+    ) -> Result<TrajectorySummary, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
+        let reader = self.reader;
+        blocking(move || reader.trajectory_summary(&namespace, &id, range)).await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn query_trajectories_intersecting_bbox(
+        self,
+        _: context::Context,
+        namespace: String,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        range: TimeRange,
+        limit: usize,
+    ) -> Result<Vec<TrajectoryMatch>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
+        let reader = self.reader;
+        let limit = limit.min(MAX_QUERY_LIMIT);
+        blocking(move || {
+            reader.query_trajectories_intersecting_bbox(
+                &namespace, min_x, min_y, max_x, max_y, range, limit,
+            )
+        })
+        .await
+    }
+
+    async fn query_trajectories_within_radius(
+        self,
+        _: context::Context,
+        namespace: String,
+        center: Point3d,
+        radius: f64,
+        range: TimeRange,
+        limit: usize,
+    ) -> Result<Vec<TrajectoryMatch>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
+        let reader = self.reader;
+        let limit = limit.min(MAX_QUERY_LIMIT);
+        blocking(move || {
+            reader.query_trajectories_within_radius(&namespace, &center, radius, range, limit)
+        })
+        .await
     }
 
     async fn insert_trajectory(
@@ -170,12 +585,16 @@ impl SpatioService for Handler {
         namespace: String,
         id: String,
         trajectory: Vec<(f64, Point3d, serde_json::Value)>,
-    ) -> Result<(), String> {
-        self.submit_write(|ack| WriteOp::InsertTrajectory {
+        idempotency_key: Option<String>,
+    ) -> Result<u64, String> {
+        let idempotency_key =
+            idempotency_key.map(|key| IdempotencyKey::new(&namespace, key, &(&id, &trajectory)));
+        self.submit_keyed_write(idempotency_key, |ack, span| WriteOp::InsertTrajectory {
             namespace,
             id,
             trajectory,
             ack,
+            span,
         })
         .await
     }
@@ -192,6 +611,7 @@ impl SpatioService for Handler {
         max_z: f64,
         limit: usize,
     ) -> Result<Vec<CurrentLocation>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
         let reader = self.reader;
         let limit = limit.min(MAX_QUERY_LIMIT);
         blocking(move || {
@@ -207,7 +627,8 @@ impl SpatioService for Handler {
         id: String,
         radius: f64,
         limit: usize,
-    ) -> Result<Vec<(CurrentLocation, f64)>, String> {
+    ) -> Result<Vec<QueryHit>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
         let reader = self.reader;
         let limit = limit.min(MAX_QUERY_LIMIT);
         blocking(move || reader.query_near(&namespace, &id, radius, limit)).await
@@ -220,11 +641,98 @@ impl SpatioService for Handler {
         polygon: Polygon,
         limit: usize,
     ) -> Result<Vec<CurrentLocation>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
         let reader = self.reader;
         let limit = limit.min(MAX_QUERY_LIMIT);
         blocking(move || reader.contains(&namespace, &polygon, limit)).await
     }
 
+    async fn contains_with_distances(
+        self,
+        _: context::Context,
+        namespace: String,
+        polygon: Polygon,
+        anchor: Option<Point3d>,
+        limit: usize,
+    ) -> Result<Vec<QueryHit>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
+        let reader = self.reader;
+        let limit = limit.min(MAX_QUERY_LIMIT);
+        blocking(move || {
+            reader.contains_with_distances(&namespace, &polygon, anchor.as_ref(), limit)
+        })
+        .await
+    }
+
+    async fn query(
+        self,
+        _: context::Context,
+        namespace: String,
+        predicate: Predicate,
+        limit: usize,
+    ) -> Result<Vec<CurrentLocation>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
+        let reader = self.reader;
+        let limit = limit.min(MAX_QUERY_LIMIT);
+        blocking(move || reader.query(&namespace, &predicate, limit)).await
+    }
+
+    async fn create_view(
+        self,
+        _: context::Context,
+        name: String,
+        namespace: String,
+        predicate: Predicate,
+    ) -> Result<(), String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
+        let reader = self.reader;
+        blocking(move || reader.create_view(&name, &namespace, predicate)).await
+    }
+
+    async fn drop_view(self, _: context::Context, name: String) -> Result<bool, String> {
+        let reader = self.reader;
+        blocking(move || reader.drop_view(&name)).await
+    }
+
+    async fn view(
+        self,
+        _: context::Context,
+        name: String,
+        limit: usize,
+    ) -> Result<Vec<CurrentLocation>, String> {
+        let reader = self.reader;
+        let limit = limit.min(MAX_QUERY_LIMIT);
+        blocking(move || reader.view(&name, limit)).await
+    }
+
+    async fn register_query(
+        self,
+        _: context::Context,
+        name: String,
+        template: QueryTemplate,
+    ) -> Result<(), String> {
+        self.saved_queries.register(name, template)
+    }
+
+    async fn unregister_query(self, _: context::Context, name: String) -> Result<bool, String> {
+        Ok(self.saved_queries.unregister(&name))
+    }
+
+    async fn run_query(
+        self,
+        _: context::Context,
+        namespace: String,
+        name: String,
+        args: QueryArgs,
+        limit: usize,
+    ) -> Result<Vec<CurrentLocation>, String> {
+        let predicate = self.saved_queries.bind(&name, &args)?;
+        let _permit = self.scheduler.acquire(&namespace).await?;
+        let reader = self.reader;
+        let limit = limit.min(MAX_QUERY_LIMIT);
+        blocking(move || reader.query(&namespace, &predicate, limit)).await
+    }
+
     async fn distance(
         self,
         _: context::Context,
@@ -233,6 +741,7 @@ impl SpatioService for Handler {
         id2: String,
         metric: Option<DistanceMetric>,
     ) -> Result<Option<f64>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
         let reader = self.reader;
         blocking(move || reader.distance(&namespace, &id1, &id2, metric)).await
     }
@@ -245,6 +754,7 @@ impl SpatioService for Handler {
         point: Point,
         metric: Option<DistanceMetric>,
     ) -> Result<Option<f64>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
         let reader = self.reader;
         blocking(move || reader.distance_to(&namespace, &id, &point, metric)).await
     }
@@ -254,6 +764,7 @@ impl SpatioService for Handler {
         _: context::Context,
         namespace: String,
     ) -> Result<Option<Polygon>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
         let reader = self.reader;
         blocking(move || reader.convex_hull(&namespace)).await
     }
@@ -263,11 +774,86 @@ impl SpatioService for Handler {
         _: context::Context,
         namespace: String,
     ) -> Result<Option<spatio_types::bbox::BoundingBox2D>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
         let reader = self.reader;
         blocking(move || reader.bounding_box(&namespace)).await
     }
 
+    async fn k_anonymous_cells(
+        self,
+        _: context::Context,
+        namespace: String,
+        cell_size: f64,
+        k: usize,
+    ) -> Result<Vec<AggregateCell>, String> {
+        let _permit = self.scheduler.acquire(&namespace).await?;
+        let reader = self.reader;
+        blocking(move || reader.k_anonymous_cells(&namespace, cell_size, k)).await
+    }
+
+    async fn subscribe(
+        self,
+        _: context::Context,
+        namespace: String,
+        region: spatio_types::bbox::BoundingBox2D,
+    ) -> Result<u64, String> {
+        self.subscriptions.subscribe(&namespace, region)
+    }
+
+    async fn poll_subscription(
+        self,
+        _: context::Context,
+        id: u64,
+        max_events: usize,
+        timeout_ms: u64,
+    ) -> Result<Vec<RegionEvent>, String> {
+        let max_events = max_events.min(MAX_QUERY_LIMIT);
+        let subscriptions = self.subscriptions.clone();
+        blocking(move || {
+            subscriptions.poll(id, max_events, std::time::Duration::from_millis(timeout_ms))
+        })
+        .await
+    }
+
+    async fn unsubscribe(self, _: context::Context, id: u64) -> bool {
+        self.subscriptions.unsubscribe(id)
+    }
+
     async fn stats(self, _: context::Context) -> Stats {
         self.reader.stats()
     }
+
+    async fn warm_up(self, _: context::Context, recent_secs: u64) -> Result<WarmUpReport, String> {
+        let reader = self.reader;
+        blocking(move || reader.warm_up(recent_secs)).await
+    }
+
+    async fn topology(self, _: context::Context) -> Topology {
+        self.topology.current()
+    }
+
+    async fn log_id(self, _: context::Context) -> u64 {
+        self.db.log_id()
+    }
+
+    async fn wait_applied(
+        self,
+        _: context::Context,
+        log_id: u64,
+        sequence: u64,
+    ) -> Result<u64, String> {
+        let db = self.db;
+        if log_id != db.log_id() {
+            return Err(foreign_log_error(log_id, db.log_id()));
+        }
+        let wait = self.applied_wait;
+        blocking(move || {
+            if db.wait_applied(sequence, wait) {
+                Ok(db.last_sequence())
+            } else {
+                Err(stale_replica_error(sequence, db.last_sequence()))
+            }
+        })
+        .await
+    }
 }
@@ -0,0 +1,283 @@
+//! Duplicate suppression for retried writes.
+//!
+//! A client that times out waiting for a write cannot tell whether it was
+//! applied, so it retries. Writes sent with an idempotency key are applied at
+//! most once per key: a retry arriving while the first attempt is still in
+//! flight waits for it, and one arriving later gets the remembered result.
+//!
+//! Keys are scoped to the namespace written and, when the server's
+//! [`crate::auth::Authenticator`] names one, to the client's principal, so
+//! two authenticated clients picking the same key don't see each other's
+//! results. Scoping never depends on the peer address: a client retrying
+//! after changing networks still finds its first attempt. A key reused for a
+//! request with a different payload is refused rather than answered with the
+//! first request's result.
+//!
+//! Once a keyed write is accepted it runs to completion even if the request
+//! that sent it is cancelled, so a retry never races an orphaned first
+//! attempt. Failed writes are not remembered, so they can be retried with the
+//! same key. Keys are remembered for a limited time and number; a retry
+//! arriving after its key was forgotten is applied again.
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// Longest accepted idempotency key, in bytes.
+pub const MAX_KEY_LEN: usize = 256;
+
+/// How many keys are remembered, and for how long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdempotencyConfig {
+    /// Keys remembered at most; the oldest are forgotten first.
+    pub capacity: usize,
+    /// How long a key is remembered after it was first seen.
+    pub ttl: Duration,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 100_000,
+            ttl: Duration::from_secs(600),
+        }
+    }
+}
+
+/// An idempotency key sent with a write, with what it applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdempotencyKey {
+    pub namespace: String,
+    pub key: String,
+    /// Hash of the write's payload, so a reused key can be told apart from a
+    /// retry.
+    pub request_hash: u64,
+}
+
+impl IdempotencyKey {
+    /// `key` for a write to `namespace` carrying `request`.
+    pub fn new(namespace: &str, key: String, request: &impl Serialize) -> Self {
+        let mut hasher = DefaultHasher::new();
+        // Serializing plain data to JSON only fails for non-string map keys,
+        // which writes don't carry; such a payload hashes as empty.
+        serde_json::to_vec(request)
+            .unwrap_or_default()
+            .hash(&mut hasher);
+        Self {
+            namespace: namespace.to_string(),
+            key,
+            request_hash: hasher.finish(),
+        }
+    }
+}
+
+type Outcome = Option<Result<u64, String>>;
+
+/// Principal (empty without one), namespace and key.
+type ScopedKey = (Arc<str>, String, String);
+
+struct Entry {
+    result: watch::Receiver<Outcome>,
+    request_hash: u64,
+    /// Distinguishes this entry from a later one under the same key.
+    generation: u64,
+}
+
+#[derive(Default)]
+struct State {
+    entries: HashMap<ScopedKey, Entry>,
+    /// Keys in insertion order, for expiry and eviction.
+    order: VecDeque<(Instant, ScopedKey, u64)>,
+    next_generation: u64,
+}
+
+impl State {
+    fn prune(&mut self, config: &IdempotencyConfig, now: Instant) {
+        while let Some((inserted, _, _)) = self.order.front() {
+            let expired = now.duration_since(*inserted) > config.ttl;
+            if !expired && self.order.len() <= config.capacity {
+                break;
+            }
+            let Some((_, key, generation)) = self.order.pop_front() else {
+                break;
+            };
+            self.remove(&key, generation);
+        }
+    }
+
+    fn remove(&mut self, key: &ScopedKey, generation: u64) {
+        if self
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.generation == generation)
+        {
+            self.entries.remove(key);
+        }
+    }
+}
+
+/// Remembered write results by idempotency key, shared by all connections.
+#[derive(Clone, Default)]
+pub struct IdempotencyCache {
+    config: IdempotencyConfig,
+    state: Arc<Mutex<State>>,
+}
+
+impl IdempotencyCache {
+    pub fn new(config: IdempotencyConfig) -> Self {
+        Self {
+            config,
+            state: Arc::default(),
+        }
+    }
+
+    /// Run `write` unless a write from `client` with `key` already ran or is
+    /// running, and return its result either way. Fails without running
+    /// `write` if that earlier write carried a different payload.
+    pub async fn run<F>(
+        &self,
+        client: Arc<str>,
+        key: IdempotencyKey,
+        write: F,
+    ) -> Result<u64, String>
+    where
+        F: Future<Output = Result<u64, String>> + Send + 'static,
+    {
+        if key.key.is_empty() || key.key.len() > MAX_KEY_LEN {
+            return Err(format!(
+                "Idempotency key must be 1 to {MAX_KEY_LEN} bytes long"
+            ));
+        }
+        let request_hash = key.request_hash;
+        let key: ScopedKey = (client, key.namespace, key.key);
+        let mut result = {
+            let mut state = self.state.lock();
+            let now = Instant::now();
+            state.prune(&self.config, now);
+            match state.entries.get(&key) {
+                Some(entry) if entry.request_hash != request_hash => {
+                    return Err(format!(
+                        "Idempotency key {} was already used for a different request",
+                        key.2
+                    ));
+                }
+                Some(entry) => entry.result.clone(),
+                None => {
+                    let (tx, rx) = watch::channel(None);
+                    let generation = state.next_generation;
+                    state.next_generation += 1;
+                    state.entries.insert(
+                        key.clone(),
+                        Entry {
+                            result: rx.clone(),
+                            request_hash,
+                            generation,
+                        },
+                    );
+                    state.order.push_back((now, key.clone(), generation));
+                    state.prune(&self.config, now);
+                    // Detached, so the write finishes and is remembered even
+                    // if this request is cancelled.
+                    let cache = self.state.clone();
+                    tokio::spawn(async move {
+                        let outcome = write.await;
+                        if outcome.is_err() {
+                            cache.lock().remove(&key, generation);
+                        }
+                        let _ = tx.send(Some(outcome));
+                    });
+                    rx
+                }
+            }
+        };
+        let outcome = result
+            .wait_for(Option::is_some)
+            .await
+            .map_err(|_| "Write was dropped before completion".to_string())?;
+        outcome
+            .clone()
+            .unwrap_or_else(|| Err("Write was dropped before completion".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    fn key(key: &str) -> IdempotencyKey {
+        IdempotencyKey::new("fleet", key.to_string(), &"payload")
+    }
+
+    fn client() -> Arc<str> {
+        Arc::from("cert:0001")
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_keys_apply_once() {
+        let cache = IdempotencyCache::default();
+        let applied = Arc::new(AtomicU64::new(0));
+        let write = || {
+            let applied = applied.clone();
+            async move { Ok(applied.fetch_add(1, Ordering::SeqCst) + 1) }
+        };
+
+        assert_eq!(cache.run(client(), key("a"), write()).await, Ok(1));
+        assert_eq!(cache.run(client(), key("a"), write()).await, Ok(1));
+        assert_eq!(cache.run(client(), key("b"), write()).await, Ok(2));
+        assert_eq!(applied.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_keys_are_scoped_and_bound_to_their_payload() {
+        let cache = IdempotencyCache::default();
+        assert_eq!(cache.run(client(), key("a"), async { Ok(1) }).await, Ok(1));
+
+        // Another client, or another namespace, has its own "a".
+        assert_eq!(
+            cache
+                .run(Arc::from("cert:0002"), key("a"), async { Ok(2) })
+                .await,
+            Ok(2)
+        );
+        let other_namespace = IdempotencyKey::new("drones", "a".into(), &"payload");
+        assert_eq!(
+            cache.run(client(), other_namespace, async { Ok(3) }).await,
+            Ok(3)
+        );
+
+        // The same client reusing "a" for something else is refused.
+        let reused = IdempotencyKey::new("fleet", "a".into(), &"other payload");
+        let error = cache
+            .run(client(), reused, async { Ok(4) })
+            .await
+            .unwrap_err();
+        assert!(error.contains("different request"), "{error}");
+        assert_eq!(cache.run(client(), key("a"), async { Ok(5) }).await, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn test_failed_and_evicted_keys_are_forgotten() {
+        let cache = IdempotencyCache::new(IdempotencyConfig {
+            capacity: 1,
+            ttl: Duration::from_secs(60),
+        });
+        assert!(
+            cache
+                .run(client(), key("a"), async { Err("busy".into()) })
+                .await
+                .is_err()
+        );
+        assert_eq!(cache.run(client(), key("a"), async { Ok(7) }).await, Ok(7));
+
+        // "b" evicts "a".
+        assert_eq!(cache.run(client(), key("b"), async { Ok(8) }).await, Ok(8));
+        assert_eq!(cache.run(client(), key("a"), async { Ok(9) }).await, Ok(9));
+        assert!(cache.run(client(), key(""), async { Ok(0) }).await.is_err());
+    }
+}
@@ -5,7 +5,11 @@
 //! # Transports
 //!
 //! - **RPC** (default): High-performance tarpc-based transport
-//! - **HTTP** (optional): REST API, enable with `http` feature
+//! - **HTTP** (optional): REST API answering in GeoJSON, enable with `http`
+//!   feature (see [`transport::http`])
+//!
+//! With the `metrics` feature, [`metrics::run_metrics_server`] exposes
+//! Prometheus metrics at `/metrics`.
 //!
 //! # Example
 //!
@@ -14,15 +18,60 @@
 //!
 //! run_server(listener, db, shutdown).await?;
 //! ```
+//!
+//! Applications embedding the server can use [`SpatioServer::builder`]
+//! instead, which also configures TLS (with the `tls` feature),
+//! authentication and middleware.
 
+pub mod auth;
 pub mod handler;
+pub mod idempotency;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod middleware;
 pub mod protocol;
 pub mod reader;
+pub mod saved_queries;
+pub mod scheduler;
+pub mod server;
+pub mod subscriptions;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod topology;
+pub mod trace_context;
 pub mod transport;
 pub mod writer;
 
 // Re-export protocol types for client usage
-pub use protocol::{CurrentLocation, LocationUpdate, SpatioService, SpatioServiceClient, Stats};
+pub use auth::{Authenticator, ConnectionInfo};
+pub use idempotency::IdempotencyConfig;
+pub use middleware::{Middleware, MiddlewareChain, RequestInfo};
+pub use protocol::{
+    AggregateCell, BboxPage, CurrentLocation, FOREIGN_LOG_ERROR_PREFIX, HistoryEntry, KnnQuery,
+    LocationUpdate, Page, QueryHit, RadiusQuery, RegionEvent, Role, SpatioService,
+    SpatioServiceClient, Stats, Topology, TrajectoryMatch, TrajectorySlice, WarmUpReport,
+    busy_error, foreign_log_error, not_primary_error, primary_hint, retry_after,
+    stale_replica_error, stale_sequences,
+};
+pub use saved_queries::{QueryArgs, QueryTemplate};
+pub use scheduler::{NamespaceLimits, SchedulerConfig};
+pub use server::{SpatioServer, SpatioServerBuilder};
+pub use topology::ServerTopology;
+
+#[cfg(feature = "tls")]
+pub use tls::TlsConfig;
+/// The rustls version [`SpatioServerBuilder::tls`] takes its config from.
+#[cfg(feature = "tls")]
+pub use tokio_rustls::rustls;
 
 // Re-export default transport for convenience
-pub use transport::rpc::run_server;
+pub use transport::rpc::{ServerOptions, run_server, run_server_with_options};
+
+#[cfg(feature = "http")]
+pub use transport::http::run_http_server;
+
+#[cfg(feature = "grpc")]
+pub use transport::grpc::run_grpc_server;
+
+#[cfg(feature = "metrics")]
+pub use metrics::{Metrics, run_metrics_server};
@@ -1,8 +1,8 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use spatio::Spatio;
-use spatio_server::run_server;
+use spatio_server::handler::DEFAULT_APPLIED_WAIT;
+use spatio_server::{NamespaceLimits, SchedulerConfig, ServerTopology, SpatioServer};
 use std::net::SocketAddr;
-use std::sync::Arc;
 use tracing::info;
 
 #[derive(Parser, Debug)]
@@ -16,6 +16,101 @@ struct Args {
 
     #[arg(short, long)]
     data_dir: Option<String>,
+
+    /// Also serve the HTTP/GeoJSON API on this port
+    #[cfg(feature = "http")]
+    #[arg(long)]
+    http_port: Option<u16>,
+
+    /// Also serve the gRPC API on this port
+    #[cfg(feature = "grpc")]
+    #[arg(long)]
+    grpc_port: Option<u16>,
+
+    /// Serve Prometheus metrics at /metrics on this port
+    #[cfg(feature = "metrics")]
+    #[arg(long)]
+    metrics_port: Option<u16>,
+
+    /// Serve the RPC API over TLS with this PEM certificate chain. The HTTP,
+    /// gRPC and metrics listeners don't speak TLS and can't be combined
+    /// with it
+    #[cfg(feature = "tls")]
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<std::path::PathBuf>,
+
+    /// PEM private key for --tls-cert
+    #[cfg(feature = "tls")]
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<std::path::PathBuf>,
+
+    /// Before serving, load the spatial indexes and the log segments with
+    /// updates from the last SECS seconds, as a standby should before
+    /// traffic fails over to it
+    #[arg(long, value_name = "SECS")]
+    warm_up: Option<u64>,
+
+    /// Log queries taking at least MS milliseconds as slow, with the index
+    /// candidates they examined
+    #[arg(long, value_name = "MS")]
+    slow_query_ms: Option<u64>,
+
+    /// Serve as a replica of the primary at ADDR, refusing writes and
+    /// pointing writers there
+    #[arg(long, value_name = "ADDR")]
+    replica_of: Option<SocketAddr>,
+
+    /// Longest a read naming a write waits for this server to apply it
+    #[arg(long, value_name = "MS", default_value_t = DEFAULT_APPLIED_WAIT.as_millis() as u64)]
+    applied_wait_ms: u64,
+
+    /// Secret key, in hex, that jittered queries move positions under; they
+    /// are refused without one
+    #[arg(long, value_name = "HEX", value_parser = parse_jitter_key)]
+    jitter_key: Option<u128>,
+
+    /// Queries run concurrently per namespace
+    #[arg(long, default_value_t = NamespaceLimits::default().max_concurrent)]
+    max_concurrent_queries: usize,
+
+    /// Queries queued per namespace before new ones are rejected
+    #[arg(long, default_value_t = NamespaceLimits::default().max_queued)]
+    max_queued_queries: usize,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check a database for inconsistencies and exit, without serving
+    Check { data_dir: String },
+}
+
+fn parse_jitter_key(hex: &str) -> Result<u128, String> {
+    u128::from_str_radix(hex, 16).map_err(|e| format!("not a 128-bit hex key: {e}"))
+}
+
+/// Open the database at `path`, report what `DB::verify` finds, and fail if
+/// it finds anything.
+fn check(path: &str) -> anyhow::Result<()> {
+    anyhow::ensure!(std::path::Path::new(path).exists(), "no database at {path}");
+    let db = Spatio::builder().path(path).build()?;
+    let report = db.verify()?;
+    println!(
+        "checked {} objects and {} log records",
+        report.objects, report.log_records
+    );
+    for issue in &report.issues {
+        println!("{issue}");
+    }
+    anyhow::ensure!(
+        report.is_consistent(),
+        "found {} inconsistencies",
+        report.issues.len()
+    );
+    println!("ok");
+    Ok(())
 }
 
 #[tokio::main]
@@ -28,25 +123,128 @@ async fn main() -> anyhow::Result<()> {
         .init();
 
     let args = Args::parse();
+    if let Some(Command::Check { data_dir }) = &args.command {
+        return check(data_dir);
+    }
+    // HTTP and gRPC are refused beside TLS when the server binds.
+    #[cfg(all(feature = "tls", feature = "metrics"))]
+    anyhow::ensure!(
+        args.tls_cert.is_none() || args.metrics_port.is_none(),
+        "--metrics-port serves plain text and can't be combined with --tls-cert"
+    );
+    anyhow::ensure!(
+        args.max_concurrent_queries > 0,
+        "--max-concurrent-queries must be greater than zero"
+    );
+    let scheduler = SchedulerConfig::default().with_default_limits(NamespaceLimits {
+        max_concurrent: args.max_concurrent_queries,
+        max_queued: args.max_queued_queries,
+    });
 
+    let mut config = spatio::Config::default();
+    if let Some(ms) = args.slow_query_ms {
+        config = config.with_slow_query_threshold(std::time::Duration::from_millis(ms));
+    }
     let db = if let Some(path) = args.data_dir {
         info!("Opening database at {}", path);
-        Spatio::builder().path(path).build()?
+        Spatio::builder().path(path).config(config).build()?
     } else {
         info!("Opening in-memory database");
-        Spatio::builder().build()?
+        Spatio::builder().config(config).build()?
+    };
+
+    if let Some(secs) = args.warm_up {
+        let report = db.warm_up(std::time::Duration::from_secs(secs))?;
+        info!(
+            "Warmed up {} objects and {} bytes of log in {:?}",
+            report.objects, report.bytes_read, report.elapsed
+        );
+    }
+
+    let db = std::sync::Arc::new(db);
+    let topology = match args.replica_of {
+        Some(primary) => {
+            info!("Serving as a replica of {}", primary);
+            ServerTopology::replica_of(primary, 0)
+        }
+        None => ServerTopology::default(),
+    };
+    let applied_wait = std::time::Duration::from_millis(args.applied_wait_ms);
+
+    #[cfg(feature = "metrics")]
+    let (metrics, metrics_server) = match args.metrics_port {
+        Some(port) => {
+            let listener = tokio::net::TcpListener::bind((args.host.as_str(), port)).await?;
+            let metrics = spatio_server::Metrics::new();
+            let server = tokio::spawn(spatio_server::run_metrics_server(
+                listener,
+                db.clone(),
+                metrics.clone(),
+                ctrl_c(),
+            ));
+            (Some(metrics), Some(server))
+        }
+        None => (None, None),
     };
 
     let addr: SocketAddr = format!("{}:{}", args.host, args.port).parse()?;
-    let listener = tokio::net::TcpListener::bind(addr).await?;
+    let builder = SpatioServer::builder()
+        .db(db)
+        .addr(addr)
+        .scheduler(scheduler)
+        .topology(topology)
+        .applied_wait(applied_wait)
+        .shutdown(ctrl_c());
+    let builder = match args.jitter_key {
+        Some(key) => builder.jitter_key(key),
+        None => builder,
+    };
 
-    let shutdown = async {
-        tokio::signal::ctrl_c()
-            .await
-            .expect("Failed to listen for ctrl_c signal");
+    // Every transport shares the builder's options: one query scheduler,
+    // one authenticator.
+    #[cfg(feature = "http")]
+    let builder = match args.http_port {
+        Some(port) => {
+            builder.http_listener(tokio::net::TcpListener::bind((args.host.as_str(), port)).await?)
+        }
+        None => builder,
     };
 
-    run_server(listener, Arc::new(db), Box::pin(shutdown)).await?;
+    #[cfg(feature = "grpc")]
+    let builder = match args.grpc_port {
+        Some(port) => {
+            builder.grpc_listener(tokio::net::TcpListener::bind((args.host.as_str(), port)).await?)
+        }
+        None => builder,
+    };
+
+    #[cfg(feature = "metrics")]
+    let builder = match metrics {
+        Some(metrics) => builder.metrics(metrics),
+        None => builder,
+    };
+
+    #[cfg(feature = "tls")]
+    let builder = match (args.tls_cert, args.tls_key) {
+        (Some(cert), Some(key)) => {
+            info!("Serving RPC over TLS with {}", cert.display());
+            builder.tls(spatio_server::TlsConfig::new(cert, key).load()?)
+        }
+        _ => builder,
+    };
+
+    builder.serve().await?;
+
+    #[cfg(feature = "metrics")]
+    if let Some(metrics_server) = metrics_server {
+        metrics_server.await??;
+    }
 
     Ok(())
 }
+
+async fn ctrl_c() {
+    tokio::signal::ctrl_c()
+        .await
+        .expect("Failed to listen for ctrl_c signal");
+}
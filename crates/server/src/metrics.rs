@@ -0,0 +1,370 @@
+//! Prometheus metrics, with the `metrics` feature.
+//!
+//! [`Metrics`] times every RPC request by method and counts connections; give
+//! it to the server with [`SpatioServerBuilder::metrics`]. Everything else is
+//! read from the database when scraped: objects per namespace (the size of
+//! each spatial index), trajectory log size, `fsync` latency, memory use and
+//! query and expiration counts. [`run_metrics_server`] serves it all at
+//! `GET /metrics` in the Prometheus text format. Like [`crate::middleware`],
+//! request timing covers the RPC transport only.
+//!
+//! [`SpatioServerBuilder::metrics`]: crate::SpatioServerBuilder::metrics
+
+use crate::middleware::{Middleware, RequestInfo};
+use crate::protocol::SpatioServiceResponse;
+use axum::Router;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use parking_lot::Mutex;
+use spatio::Spatio;
+use spatio_types::stats::LatencyHistogram;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tarpc::ServerError;
+use tracing::info;
+
+/// Content type of the Prometheus text format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Request and connection metrics of one server.
+#[derive(Default)]
+pub struct Metrics {
+    /// Latencies by method name.
+    requests: Mutex<BTreeMap<&'static str, LatencyHistogram>>,
+    connections_open: AtomicU64,
+    connections_total: AtomicU64,
+}
+
+/// Counts a connection as open until dropped.
+pub(crate) struct OpenConnection(Arc<Metrics>);
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        self.0.connections_open.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Arc<Self> {
+        Arc::default()
+    }
+
+    /// Count a request to `method` that took `elapsed`.
+    pub fn observe(&self, method: &'static str, elapsed: Duration) {
+        self.requests
+            .lock()
+            .entry(method)
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub(crate) fn connection_opened(self: &Arc<Self>) -> OpenConnection {
+        self.connections_open.fetch_add(1, Ordering::Relaxed);
+        self.connections_total.fetch_add(1, Ordering::Relaxed);
+        OpenConnection(self.clone())
+    }
+
+    /// These metrics and those of `db`, in the Prometheus text format.
+    pub fn render(&self, db: &Spatio) -> String {
+        let mut out = String::new();
+        let requests = self.requests.lock().clone();
+        family(
+            &mut out,
+            "spatio_request_duration_seconds",
+            "histogram",
+            "Time to serve an RPC request, by method.",
+        );
+        for (method, latencies) in &requests {
+            histogram(
+                &mut out,
+                "spatio_request_duration_seconds",
+                &[("method", method)],
+                latencies,
+            );
+        }
+        family(
+            &mut out,
+            "spatio_connections_open",
+            "gauge",
+            "RPC connections currently open.",
+        );
+        sample(
+            &mut out,
+            "spatio_connections_open",
+            &[],
+            self.connections_open.load(Ordering::Relaxed),
+        );
+        family(
+            &mut out,
+            "spatio_connections_total",
+            "counter",
+            "RPC connections accepted.",
+        );
+        sample(
+            &mut out,
+            "spatio_connections_total",
+            &[],
+            self.connections_total.load(Ordering::Relaxed),
+        );
+
+        let stats = db.stats();
+        family(
+            &mut out,
+            "spatio_index_objects",
+            "gauge",
+            "Objects in a namespace's spatial index.",
+        );
+        for (namespace, objects) in &stats.namespace_objects {
+            sample(
+                &mut out,
+                "spatio_index_objects",
+                &[("namespace", namespace)],
+                objects,
+            );
+        }
+        family(
+            &mut out,
+            "spatio_fsync_duration_seconds",
+            "histogram",
+            "Time taken by each fsync of the trajectory log.",
+        );
+        histogram(
+            &mut out,
+            "spatio_fsync_duration_seconds",
+            &[],
+            &stats.fsync_latency,
+        );
+        let scalars: [(&str, &str, &str, u64); 5] = [
+            (
+                "spatio_log_bytes",
+                "gauge",
+                "Bytes in the trajectory log.",
+                stats.cold_log_bytes,
+            ),
+            (
+                "spatio_memory_bytes",
+                "gauge",
+                "Approximate memory used by current locations and history buffers.",
+                stats.memory_usage_bytes as u64,
+            ),
+            (
+                "spatio_objects",
+                "gauge",
+                "Objects with a current location.",
+                stats.hot_state_objects as u64,
+            ),
+            (
+                "spatio_queries_total",
+                "counter",
+                "Reads and queries served since the database was opened.",
+                stats.queries_count,
+            ),
+            (
+                "spatio_expired_records_total",
+                "counter",
+                "Trajectory history records removed by expiration.",
+                stats.expired_count,
+            ),
+        ];
+        for (name, kind, help, value) in scalars {
+            family(&mut out, name, kind, help);
+            sample(&mut out, name, &[], value);
+        }
+        family(
+            &mut out,
+            "spatio_persistence_degraded",
+            "gauge",
+            "1 while writes can't reach the trajectory log.",
+        );
+        sample(
+            &mut out,
+            "spatio_persistence_degraded",
+            &[],
+            u8::from(stats.persistence != Default::default()),
+        );
+        out
+    }
+}
+
+/// Times every request for its [`Metrics`]; added around every other layer
+/// when the server has metrics.
+pub(crate) struct MetricsLayer(pub Arc<Metrics>);
+
+impl Middleware for MetricsLayer {
+    fn on_response(
+        &self,
+        info: &RequestInfo,
+        elapsed: Duration,
+        _response: &mut Result<SpatioServiceResponse, ServerError>,
+    ) {
+        self.0.observe(info.method, elapsed);
+    }
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        out.push('{');
+        for (i, (label, value)) in labels.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{label}=\"");
+            for c in value.chars() {
+                match c {
+                    '\\' => out.push_str("\\\\"),
+                    '"' => out.push_str("\\\""),
+                    '\n' => out.push_str("\\n"),
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+        }
+        out.push('}');
+    }
+    let _ = writeln!(out, " {value}");
+}
+
+fn histogram(out: &mut String, name: &str, labels: &[(&str, &str)], latencies: &LatencyHistogram) {
+    let bucket = format!("{name}_bucket");
+    for (bound, count) in latencies.cumulative() {
+        let le = if bound.is_infinite() {
+            "+Inf".to_string()
+        } else {
+            bound.to_string()
+        };
+        let mut bucket_labels = labels.to_vec();
+        bucket_labels.push(("le", &le));
+        sample(out, &bucket, &bucket_labels, count);
+    }
+    sample(out, &format!("{name}_sum"), labels, latencies.sum_secs);
+    sample(out, &format!("{name}_count"), labels, latencies.count());
+}
+
+/// The `/metrics` route, for mounting into an existing axum application.
+pub fn router(db: Arc<Spatio>, metrics: Arc<Metrics>) -> Router {
+    Router::new()
+        .route("/metrics", get(scrape))
+        .with_state((db, metrics))
+}
+
+async fn scrape(State((db, metrics)): State<(Arc<Spatio>, Arc<Metrics>)>) -> impl IntoResponse {
+    let body = tokio::task::spawn_blocking(move || metrics.render(&db))
+        .await
+        .unwrap_or_default();
+    ([(header::CONTENT_TYPE, CONTENT_TYPE)], body)
+}
+
+/// Serve `GET /metrics` until `shutdown` resolves.
+pub async fn run_metrics_server(
+    listener: tokio::net::TcpListener,
+    db: Arc<Spatio>,
+    metrics: Arc<Metrics>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    info!("Spatio metrics listening on {}", listener.local_addr()?);
+    axum::serve(listener, router(db, metrics))
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SpatioServer;
+    use crate::protocol::SpatioServiceClient;
+    use spatio_types::point::Point3d;
+    use tarpc::tokio_serde::formats::Json;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+    #[tokio::test]
+    async fn test_serves_request_and_database_metrics() {
+        let db = Arc::new(Spatio::builder().build().unwrap());
+        db.upsert(
+            "fleet",
+            "truck",
+            Point3d::new(1.0, 2.0, 0.0),
+            serde_json::json!({}),
+            None,
+        )
+        .unwrap();
+        let metrics = Metrics::new();
+        let server = SpatioServer::builder()
+            .db(db.clone())
+            .listener(TcpListener::bind("127.0.0.1:0").await.unwrap())
+            .metrics(metrics.clone())
+            .bind()
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.serve());
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let transport = tarpc::serde_transport::new(
+            Framed::new(stream, LengthDelimitedCodec::new()),
+            Json::default(),
+        );
+        let client = SpatioServiceClient::new(tarpc::client::Config::default(), transport).spawn();
+        client
+            .get(
+                tarpc::context::current(),
+                "fleet".to_string(),
+                "truck".to_string(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let metrics_addr = listener.local_addr().unwrap();
+        tokio::spawn(run_metrics_server(
+            listener,
+            db,
+            metrics,
+            std::future::pending(),
+        ));
+        let mut stream = TcpStream::connect(metrics_addr).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains(CONTENT_TYPE));
+        for line in [
+            "spatio_request_duration_seconds_count{method=\"SpatioService.get\"} 1",
+            "spatio_request_duration_seconds_bucket{method=\"SpatioService.get\",le=\"+Inf\"} 1",
+            "spatio_connections_open 1",
+            "spatio_connections_total 1",
+            "spatio_index_objects{namespace=\"fleet\"} 1",
+            "spatio_fsync_duration_seconds_count 0",
+            "spatio_log_bytes 0",
+            "spatio_persistence_degraded 0",
+        ] {
+            assert!(response.contains(line), "missing {line:?} in:\n{response}");
+        }
+    }
+
+    #[test]
+    fn test_label_values_are_escaped() {
+        let mut out = String::new();
+        sample(&mut out, "m", &[("a", "x\"y\\z\n")], 1);
+        assert_eq!(out, "m{a=\"x\\\"y\\\\z\\n\"} 1\n");
+    }
+}
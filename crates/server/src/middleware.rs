@@ -0,0 +1,308 @@
+//! Hooks run around every RPC request.
+//!
+//! Applications embedding the server add [`Middleware`] layers for concerns
+//! the handler doesn't cover — authentication, request logging, quotas,
+//! rewriting requests (say, pinning a tenant's namespace) — without forking
+//! the handler.
+//!
+//! Layers see a request in the order they were added and its response in
+//! reverse, like an onion. A layer that rejects a request stops it there: the
+//! handler and later layers never see it, while the layers before it still
+//! see the rejection as the response.
+//!
+//! ```
+//! use spatio_server::middleware::{Middleware, RequestInfo};
+//! use spatio_server::protocol::SpatioServiceRequest;
+//! use tarpc::ServerError;
+//!
+//! /// Refuse writes.
+//! struct ReadOnly;
+//!
+//! impl Middleware for ReadOnly {
+//!     fn on_request(
+//!         &self,
+//!         info: &RequestInfo,
+//!         _request: &mut SpatioServiceRequest,
+//!     ) -> Result<(), ServerError> {
+//!         match info.method {
+//!             "SpatioService.upsert" | "SpatioService.delete" => Err(ServerError::new(
+//!                 std::io::ErrorKind::PermissionDenied,
+//!                 "This server is read-only".to_string(),
+//!             )),
+//!             _ => Ok(()),
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::protocol::{SpatioServiceRequest, SpatioServiceResponse};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tarpc::ServerError;
+use tarpc::context;
+use tarpc::server::Serve;
+
+/// What a layer knows about the request besides its arguments.
+#[derive(Debug, Clone)]
+pub struct RequestInfo {
+    /// Address of the client, when known.
+    pub peer: Option<SocketAddr>,
+    /// Method name, such as `"SpatioService.get"`.
+    pub method: &'static str,
+    /// The request's deadline and trace context.
+    pub context: context::Context,
+}
+
+/// A layer around the request handler. Both hooks default to doing nothing.
+pub trait Middleware: Send + Sync + 'static {
+    /// Inspect or rewrite `request` before it is handled. Returning an error
+    /// rejects the request with that error.
+    fn on_request(
+        &self,
+        info: &RequestInfo,
+        request: &mut SpatioServiceRequest,
+    ) -> Result<(), ServerError> {
+        let _ = (info, request);
+        Ok(())
+    }
+
+    /// Inspect or rewrite the response, `elapsed` after the request reached
+    /// this layer.
+    fn on_response(
+        &self,
+        info: &RequestInfo,
+        elapsed: Duration,
+        response: &mut Result<SpatioServiceResponse, ServerError>,
+    ) {
+        let _ = (info, elapsed, response);
+    }
+}
+
+/// An ordered list of [`Middleware`] layers.
+#[derive(Clone, Default)]
+pub struct MiddlewareChain {
+    layers: Vec<Arc<dyn Middleware>>,
+}
+
+impl MiddlewareChain {
+    /// Add `layer` inside the layers already added.
+    pub fn with(mut self, layer: impl Middleware) -> Self {
+        self.layers.push(Arc::new(layer));
+        self
+    }
+
+    /// Add `layer` outside the layers already added.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn around(mut self, layer: impl Middleware) -> Self {
+        self.layers.insert(0, Arc::new(layer));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+}
+
+/// Runs a connection's requests through a [`MiddlewareChain`].
+#[derive(Clone)]
+pub(crate) struct WithMiddleware<S> {
+    inner: S,
+    chain: MiddlewareChain,
+    peer: Option<SocketAddr>,
+}
+
+impl<S> WithMiddleware<S> {
+    pub(crate) fn new(inner: S, chain: MiddlewareChain, peer: Option<SocketAddr>) -> Self {
+        Self { inner, chain, peer }
+    }
+}
+
+impl<S> Serve for WithMiddleware<S>
+where
+    S: Serve<Req = SpatioServiceRequest, Resp = SpatioServiceResponse>,
+{
+    type Req = SpatioServiceRequest;
+    type Resp = SpatioServiceResponse;
+
+    fn method(&self, request: &Self::Req) -> Option<&'static str> {
+        self.inner.method(request)
+    }
+
+    async fn serve(
+        self,
+        ctx: context::Context,
+        mut req: Self::Req,
+    ) -> Result<Self::Resp, ServerError> {
+        if self.chain.is_empty() {
+            return self.inner.serve(ctx, req).await;
+        }
+        let info = RequestInfo {
+            peer: self.peer,
+            method: self.inner.method(&req).unwrap_or("unknown"),
+            context: ctx,
+        };
+        let started = Instant::now();
+
+        let mut entered = 0;
+        let mut response = None;
+        for layer in &self.chain.layers {
+            entered += 1;
+            if let Err(e) = layer.on_request(&info, &mut req) {
+                response = Some(Err(e));
+                break;
+            }
+        }
+        let mut response = match response {
+            Some(rejected) => rejected,
+            None => self.inner.serve(ctx, req).await,
+        };
+        for layer in self.chain.layers[..entered].iter().rev() {
+            layer.on_response(&info, started.elapsed(), &mut response);
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::Handler;
+    use crate::protocol::SpatioService;
+    use parking_lot::Mutex;
+    use spatio::Spatio;
+
+    /// Records the hooks it sees.
+    struct Trace {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+        reject: bool,
+    }
+
+    impl Middleware for Trace {
+        fn on_request(
+            &self,
+            info: &RequestInfo,
+            _request: &mut SpatioServiceRequest,
+        ) -> Result<(), ServerError> {
+            self.log
+                .lock()
+                .push(format!("{} request {}", self.name, info.method));
+            if self.reject {
+                return Err(ServerError::new(
+                    std::io::ErrorKind::PermissionDenied,
+                    "denied".to_string(),
+                ));
+            }
+            Ok(())
+        }
+
+        fn on_response(
+            &self,
+            _info: &RequestInfo,
+            _elapsed: Duration,
+            response: &mut Result<SpatioServiceResponse, ServerError>,
+        ) {
+            self.log
+                .lock()
+                .push(format!("{} response ok={}", self.name, response.is_ok()));
+        }
+    }
+
+    /// Serves every request against namespace `tenant`.
+    struct PinNamespace;
+
+    impl Middleware for PinNamespace {
+        fn on_request(
+            &self,
+            _info: &RequestInfo,
+            request: &mut SpatioServiceRequest,
+        ) -> Result<(), ServerError> {
+            if let SpatioServiceRequest::Get { namespace, .. } = request {
+                *namespace = "tenant".to_string();
+            }
+            Ok(())
+        }
+    }
+
+    fn handler() -> Handler {
+        let db = Arc::new(Spatio::builder().build().unwrap());
+        db.upsert(
+            "tenant",
+            "a",
+            spatio_types::point::Point3d::new(1.0, 2.0, 0.0),
+            serde_json::json!({}),
+            None,
+        )
+        .unwrap();
+        let (write_tx, _writer) = crate::writer::spawn_background_writer(db.clone(), 16);
+        Handler::new(db, write_tx)
+    }
+
+    fn get(namespace: &str) -> SpatioServiceRequest {
+        SpatioServiceRequest::Get {
+            namespace: namespace.to_string(),
+            id: "a".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_layers_wrap_the_handler_in_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let layer = |name, reject| Trace {
+            name,
+            log: log.clone(),
+            reject,
+        };
+        let chain = MiddlewareChain::default()
+            .with(layer("outer", false))
+            .with(PinNamespace)
+            .with(layer("inner", false));
+        let service = WithMiddleware::new(handler().serve(), chain, None);
+
+        let response = service.serve(context::current(), get("other")).await;
+        let Ok(SpatioServiceResponse::Get(Ok(Some(found)))) = response else {
+            panic!("rewritten request should find the object: {response:?}");
+        };
+        assert_eq!(found.object_id, "a");
+        assert_eq!(
+            *log.lock(),
+            [
+                "outer request SpatioService.get",
+                "inner request SpatioService.get",
+                "inner response ok=true",
+                "outer response ok=true",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejection_skips_the_handler_and_inner_layers() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let layer = |name, reject| Trace {
+            name,
+            log: log.clone(),
+            reject,
+        };
+        let chain = MiddlewareChain::default()
+            .with(layer("outer", false))
+            .with(layer("auth", true))
+            .with(layer("inner", false));
+        let service = WithMiddleware::new(handler().serve(), chain, None);
+
+        let err = service
+            .serve(context::current(), get("tenant"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.detail, "denied");
+        assert_eq!(
+            *log.lock(),
+            [
+                "outer request SpatioService.get",
+                "auth request SpatioService.get",
+                "auth response ok=false",
+                "outer response ok=false",
+            ]
+        );
+    }
+}
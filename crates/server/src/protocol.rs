@@ -4,14 +4,40 @@
 
 #![allow(clippy::too_many_arguments)]
 
+use crate::saved_queries::{QueryArgs, QueryTemplate};
 use serde::{Deserialize, Serialize};
+use spatio_types::config::{HistoryEventKind, ScanDirection};
 use spatio_types::geo::{DistanceMetric, Point, Polygon};
 use spatio_types::point::Point3d;
+use spatio_types::query::Predicate;
+use spatio_types::stats::MinuteStats;
+use spatio_types::time::TimeRange;
+use spatio_types::trajectory::TrajectorySummary;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::ops::Bound;
+use std::time::Duration;
 
+pub use spatio_types::wire::{
+    CurrentLocation, KnnQuery, LocationUpdate, QueryHit, RadiusQuery, TrajectorySlice,
+};
+
+/// An object whose trajectory passed through a region, with the points of
+/// each pass, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrajectoryMatch {
+    pub object_id: String,
+    pub segments: Vec<Vec<LocationUpdate>>,
+}
+
+/// One recorded write or delete of an object.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LocationUpdate {
+pub struct HistoryEntry {
     pub timestamp: f64,
-    pub position: Point3d,
+    pub kind: HistoryEventKind,
+    /// The location written; `None` for deletes.
+    pub position: Option<Point3d>,
+    /// The metadata written; empty for deletes.
     pub metadata: Vec<u8>,
 }
 
@@ -19,41 +45,233 @@ pub struct LocationUpdate {
 pub struct Stats {
     pub object_count: usize,
     pub memory_usage_bytes: usize,
+    /// Objects by namespace
+    #[serde(default)]
+    pub namespace_objects: BTreeMap<String, usize>,
+    /// Bytes in the trajectory log on disk
+    #[serde(default)]
+    pub cold_log_bytes: u64,
+    /// Reads and queries served since the database was opened
+    #[serde(default)]
+    pub queries_count: u64,
+    /// Object writes per second during the last complete minute
+    #[serde(default)]
+    pub updates_per_sec: f64,
+    /// Operation counts for each recent minute that had any, oldest first
+    #[serde(default)]
+    pub per_minute: Vec<MinuteStats>,
 }
 
+/// What a [`SpatioService::warm_up`] loaded.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CurrentLocation {
-    pub object_id: String,
-    pub position: Point3d,
-    pub metadata: Vec<u8>,
+pub struct WarmUpReport {
+    /// Objects with a current location, all in their spatial indexes
+    pub objects: usize,
+    /// Log segments read into the trajectory index
+    pub segments_read: usize,
+    /// Bytes of log read
+    pub bytes_read: u64,
+    pub elapsed_secs: f64,
+}
+
+/// A write seen by a region subscription.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RegionEvent {
+    /// The object was written inside the region.
+    Updated(CurrentLocation),
+    /// The object moved out of the region or was deleted; carries the
+    /// location it left for.
+    Left(CurrentLocation),
+}
+
+/// A grid cell released by [`SpatioService::k_anonymous_cells`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AggregateCell {
+    /// Cell extent in degrees.
+    pub bounds: spatio_types::bbox::BoundingBox2D,
+    /// Objects in the cell; never fewer than the `k` asked for.
+    pub count: usize,
+}
+
+/// One page of a paged scan; pass `next_token` back to continue.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_token: Option<String>,
+}
+
+/// One page of a bounding-box scan.
+pub type BboxPage = Page<CurrentLocation>;
+
+/// Start of the error a write gets while the database is rewriting its log.
+pub const BUSY_ERROR_PREFIX: &str = "Server is busy";
+
+/// The error a write gets while the database is `doing` a log rewrite,
+/// asking the client to retry after `retry_after`.
+pub fn busy_error(doing: impl std::fmt::Display, retry_after: Duration) -> String {
+    format!(
+        "{BUSY_ERROR_PREFIX} {doing}; retry after {}ms",
+        retry_after.as_millis().max(1)
+    )
+}
+
+/// How long a [`busy_error`] asks the client to wait, or `None` for any
+/// other error.
+pub fn retry_after(message: &str) -> Option<Duration> {
+    let rest = message.strip_prefix(BUSY_ERROR_PREFIX)?;
+    let (_, millis) = rest.rsplit_once("; retry after ")?;
+    Some(Duration::from_millis(
+        millis.strip_suffix("ms")?.parse().ok()?,
+    ))
+}
+
+/// A server's part in a primary/replica deployment (see
+/// [`crate::topology`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Role {
+    /// Takes writes.
+    Primary,
+    /// Serves reads and refuses writes.
+    Replica,
+}
+
+/// A server's role, as reported by [`SpatioService::topology`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Topology {
+    pub role: Role,
+    /// The primary a replica follows, if it was told; `None` on the primary.
+    pub primary: Option<SocketAddr>,
+    /// Failover term the role was assigned in; of two servers claiming to be
+    /// primary, the one with the higher term is.
+    pub term: u64,
+}
+
+/// Start of the error a write gets from a replica.
+pub const NOT_PRIMARY_ERROR_PREFIX: &str = "Not the primary";
+
+/// The error a replica refuses writes with, naming the primary it follows
+/// if it knows one.
+pub fn not_primary_error(primary: Option<SocketAddr>) -> String {
+    match primary {
+        Some(primary) => format!("{NOT_PRIMARY_ERROR_PREFIX}; primary is {primary}"),
+        None => format!("{NOT_PRIMARY_ERROR_PREFIX}; primary unknown"),
+    }
+}
+
+/// The primary a [`not_primary_error`] names: `Some(None)` when it names
+/// none, `None` for any other error.
+pub fn primary_hint(message: &str) -> Option<Option<SocketAddr>> {
+    let rest = message.strip_prefix(NOT_PRIMARY_ERROR_PREFIX)?;
+    Some(
+        rest.strip_prefix("; primary is ")
+            .and_then(|addr| addr.parse().ok()),
+    )
+}
+
+/// Start of the error a read gets when the server hasn't applied the write
+/// it was asked to wait for.
+pub const STALE_REPLICA_ERROR_PREFIX: &str = "Replica is behind";
+
+/// The error for a read that waited for write `required` on a server still
+/// at `applied`.
+pub fn stale_replica_error(required: u64, applied: u64) -> String {
+    format!("{STALE_REPLICA_ERROR_PREFIX}; needs sequence {required}, applied {applied}")
+}
+
+/// The `(required, applied)` sequences of a [`stale_replica_error`], or
+/// `None` for any other error.
+pub fn stale_sequences(message: &str) -> Option<(u64, u64)> {
+    let rest = message.strip_prefix(STALE_REPLICA_ERROR_PREFIX)?;
+    let (required, applied) = rest
+        .strip_prefix("; needs sequence ")?
+        .split_once(", applied ")?;
+    Some((required.parse().ok()?, applied.parse().ok()?))
+}
+
+/// Start of the error for a sequence from another server's log.
+pub const FOREIGN_LOG_ERROR_PREFIX: &str = "Sequence is from another log";
+
+/// The error for waiting on a sequence of log `log_id` on a server whose log
+/// is `own`. Spatio doesn't copy writes between servers, so no wait helps.
+pub fn foreign_log_error(log_id: u64, own: u64) -> String {
+    format!("{FOREIGN_LOG_ERROR_PREFIX}; got {log_id:016x}, this server's is {own:016x}")
 }
 
 #[allow(clippy::too_many_arguments)]
 #[tarpc::service]
 pub trait SpatioService {
+    /// Upsert an object's location. A write sent again with the same
+    /// `idempotency_key` is applied only once (see [`crate::idempotency`]).
     async fn upsert(
         namespace: String,
         id: String,
         point: Point3d,
         metadata: serde_json::Value,
-    ) -> Result<(), String>;
+        idempotency_key: Option<String>,
+    ) -> Result<u64, String>;
+
+    /// Upsert the locations of many objects in one round trip. The writer
+    /// applies the batch in one step: every item is validated first, so an
+    /// invalid one rejects them all, and no other write interleaves.
+    /// `idempotency_key` works as for `upsert`.
+    async fn upsert_batch(
+        namespace: String,
+        items: Vec<(String, Point3d, serde_json::Value)>,
+        idempotency_key: Option<String>,
+    ) -> Result<u64, String>;
 
     async fn get(namespace: String, id: String) -> Result<Option<CurrentLocation>, String>;
 
-    async fn delete(namespace: String, id: String) -> Result<(), String>;
+    /// Objects whose IDs fall between `start` and `end`, in ID order.
+    async fn range(
+        namespace: String,
+        start: Bound<String>,
+        end: Bound<String>,
+        limit: usize,
+        direction: ScanDirection,
+    ) -> Result<Vec<CurrentLocation>, String>;
+
+    async fn delete(namespace: String, id: String) -> Result<u64, String>;
 
-    async fn query_radius(
+    /// Names of the namespaces holding objects or zones, sorted.
+    async fn list_namespaces() -> Result<Vec<String>, String>;
+
+    /// Delete every object and zone of `namespace`, keeping their history,
+    /// and return how many were removed.
+    async fn clear_namespace(namespace: String) -> Result<u64, String>;
+
+    /// `clear_namespace`, also erasing the namespace's history from disk.
+    async fn drop_namespace(namespace: String) -> Result<u64, String>;
+
+    /// Objects within the query's radius, nearest first. The metric
+    /// defaults to the namespace's configured metric.
+    async fn query_radius(query: RadiusQuery) -> Result<Vec<QueryHit>, String>;
+
+    /// `query_radius` for public outputs: each hit is moved by up to
+    /// `jitter` meters under the server's jitter key, then measured and
+    /// ordered by where it was moved (see
+    /// `spatio::compute::privacy::jitter_point`). Refused when the server
+    /// has no jitter key.
+    async fn query_radius_jittered(
         namespace: String,
         center: Point3d,
         radius: f64,
         limit: usize,
-    ) -> Result<Vec<(CurrentLocation, f64)>, String>;
+        jitter: f64,
+    ) -> Result<Vec<QueryHit>, String>;
 
-    async fn knn(
+    async fn knn(query: KnnQuery) -> Result<Vec<QueryHit>, String>;
+
+    /// The `k` objects nearest to `center` by horizontal distance, ignoring
+    /// altitude, optionally only those within `max_distance`. `metric`
+    /// defaults to haversine.
+    async fn knn_2d(
         namespace: String,
-        center: Point3d,
+        center: Point,
         k: usize,
-    ) -> Result<Vec<(CurrentLocation, f64)>, String>;
+        max_distance: Option<f64>,
+        metric: Option<DistanceMetric>,
+    ) -> Result<Vec<QueryHit>, String>;
 
     async fn query_bbox(
         namespace: String,
@@ -64,6 +282,43 @@ pub trait SpatioService {
         limit: usize,
     ) -> Result<Vec<CurrentLocation>, String>;
 
+    /// `query_bbox`, with each result's distance in meters from `anchor`, or
+    /// from the center of the box when `anchor` is `None`.
+    #[allow(clippy::too_many_arguments)]
+    async fn query_bbox_with_distances(
+        namespace: String,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        anchor: Option<Point3d>,
+        limit: usize,
+    ) -> Result<Vec<QueryHit>, String>;
+
+    /// Page through a bounding box. Tokens are stateless and can be resumed
+    /// against any server that has applied the scan's starting sequence.
+    async fn query_bbox_page(
+        namespace: String,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        page_size: usize,
+        token: Option<String>,
+    ) -> Result<BboxPage, String>;
+
+    /// Page through the objects within a radius, nearest first. Tokens work
+    /// as for `query_bbox_page`.
+    async fn query_radius_page(
+        namespace: String,
+        center: Point3d,
+        radius: f64,
+        page_size: usize,
+        token: Option<String>,
+    ) -> Result<Page<QueryHit>, String>;
+
+    /// Objects within `radius` of `center` horizontally and between `min_z`
+    /// and `max_z`, nearest first. `metric` works as for `query_radius`.
     async fn query_cylinder(
         namespace: String,
         center: Point,
@@ -71,21 +326,69 @@ pub trait SpatioService {
         max_z: f64,
         radius: f64,
         limit: usize,
-    ) -> Result<Vec<(CurrentLocation, f64)>, String>;
+        metric: Option<DistanceMetric>,
+    ) -> Result<Vec<QueryHit>, String>;
+
+    async fn query_trajectory(query: TrajectorySlice) -> Result<Vec<LocationUpdate>, String>;
 
-    async fn query_trajectory(
+    /// Page through an object's trajectory, newest first. Tokens work as for
+    /// `query_bbox_page`.
+    async fn query_trajectory_page(
         namespace: String,
         id: String,
-        start_time: Option<f64>,
-        end_time: Option<f64>,
+        range: TimeRange,
+        page_size: usize,
+        token: Option<String>,
+    ) -> Result<Page<LocationUpdate>, String>;
+
+    /// Writes and deletes of an object within the time range, oldest first,
+    /// optionally only those of `kind`.
+    async fn history(
+        namespace: String,
+        id: String,
+        range: TimeRange,
+        kind: Option<HistoryEventKind>,
         limit: usize,
-    ) -> Result<Vec<LocationUpdate>, String>;
+    ) -> Result<Vec<HistoryEntry>, String>;
 
+    /// Distance, duration and speeds of an object's history within the time
+    /// range.
+    async fn trajectory_summary(
+        namespace: String,
+        id: String,
+        range: TimeRange,
+    ) -> Result<TrajectorySummary, String>;
+
+    /// Objects whose trajectory within the time range passed through the
+    /// bounding box, with the stretches that did, ordered by object ID.
+    #[allow(clippy::too_many_arguments)]
+    async fn query_trajectories_intersecting_bbox(
+        namespace: String,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        range: TimeRange,
+        limit: usize,
+    ) -> Result<Vec<TrajectoryMatch>, String>;
+
+    /// Objects whose trajectory within the time range came within `radius`
+    /// meters of `center`, with the stretches that did, ordered by object ID.
+    async fn query_trajectories_within_radius(
+        namespace: String,
+        center: Point3d,
+        radius: f64,
+        range: TimeRange,
+        limit: usize,
+    ) -> Result<Vec<TrajectoryMatch>, String>;
+
+    /// Insert a batch of points; `idempotency_key` works as for `upsert`.
     async fn insert_trajectory(
         namespace: String,
         id: String,
         trajectory: Vec<(f64, Point3d, serde_json::Value)>,
-    ) -> Result<(), String>;
+        idempotency_key: Option<String>,
+    ) -> Result<u64, String>;
 
     async fn query_bbox_3d(
         namespace: String,
@@ -103,7 +406,7 @@ pub trait SpatioService {
         id: String,
         radius: f64,
         limit: usize,
-    ) -> Result<Vec<(CurrentLocation, f64)>, String>;
+    ) -> Result<Vec<QueryHit>, String>;
 
     async fn contains(
         namespace: String,
@@ -111,6 +414,50 @@ pub trait SpatioService {
         limit: usize,
     ) -> Result<Vec<CurrentLocation>, String>;
 
+    /// `contains`, with each result's distance in meters from `anchor`, or
+    /// from the centroid of the polygon when `anchor` is `None`.
+    async fn contains_with_distances(
+        namespace: String,
+        polygon: Polygon,
+        anchor: Option<Point3d>,
+        limit: usize,
+    ) -> Result<Vec<QueryHit>, String>;
+
+    /// Objects matching a composite predicate, evaluated in one index scan.
+    async fn query(
+        namespace: String,
+        predicate: Predicate,
+        limit: usize,
+    ) -> Result<Vec<CurrentLocation>, String>;
+
+    /// Define a materialized view of the objects in `namespace` matching
+    /// `predicate`, maintained by the server as objects are written.
+    async fn create_view(
+        name: String,
+        namespace: String,
+        predicate: Predicate,
+    ) -> Result<(), String>;
+
+    /// Remove a view, returning whether it existed.
+    async fn drop_view(name: String) -> Result<bool, String>;
+
+    /// Current members of a view, in object ID order.
+    async fn view(name: String, limit: usize) -> Result<Vec<CurrentLocation>, String>;
+
+    /// Register `template` under `name`, replacing any query of that name.
+    async fn register_query(name: String, template: QueryTemplate) -> Result<(), String>;
+
+    /// Remove a saved query, returning whether it existed.
+    async fn unregister_query(name: String) -> Result<bool, String>;
+
+    /// Run the saved query `name` with `args` bound to its parameters.
+    async fn run_query(
+        namespace: String,
+        name: String,
+        args: QueryArgs,
+        limit: usize,
+    ) -> Result<Vec<CurrentLocation>, String>;
+
     async fn distance(
         namespace: String,
         id1: String,
@@ -131,5 +478,52 @@ pub trait SpatioService {
         namespace: String,
     ) -> Result<Option<spatio_types::bbox::BoundingBox2D>, String>;
 
+    /// Current objects of `namespace` counted in `cell_size`-degree grid
+    /// cells, releasing only cells holding at least `k` of them, south to
+    /// north and west to east.
+    async fn k_anonymous_cells(
+        namespace: String,
+        cell_size: f64,
+        k: usize,
+    ) -> Result<Vec<AggregateCell>, String>;
+
+    /// Subscribe to writes of objects of `namespace` inside `region`,
+    /// returning a subscription ID to poll (see [`crate::subscriptions`]).
+    async fn subscribe(
+        namespace: String,
+        region: spatio_types::bbox::BoundingBox2D,
+    ) -> Result<u64, String>;
+
+    /// Up to `max_events` events of a subscription, waiting up to
+    /// `timeout_ms` for the first. Fails once the subscription has fallen too
+    /// far behind and been closed.
+    async fn poll_subscription(
+        id: u64,
+        max_events: usize,
+        timeout_ms: u64,
+    ) -> Result<Vec<RegionEvent>, String>;
+
+    /// Close a subscription, returning whether it was open.
+    async fn unsubscribe(id: u64) -> bool;
+
     async fn stats() -> Stats;
+
+    /// Load what the first queries would otherwise load on demand, reading
+    /// the log segments with updates from the last `recent_secs` (see
+    /// `DB::warm_up`). Meant for a standby, before traffic fails over to it.
+    async fn warm_up(recent_secs: u64) -> Result<WarmUpReport, String>;
+
+    /// This server's role, and the primary it follows if it's a replica.
+    async fn topology() -> Topology;
+
+    /// The ID of this server's log, which the sequences its writes return
+    /// belong to (see `DB::log_id`).
+    async fn log_id() -> u64;
+
+    /// Wait until this server has applied write `sequence` of log `log_id`,
+    /// so reads sent after see it. Returns the server's last applied
+    /// sequence. A server that doesn't catch up within its configured wait
+    /// fails with a [`stale_replica_error`], and one with another log at
+    /// once with a [`foreign_log_error`].
+    async fn wait_applied(log_id: u64, sequence: u64) -> Result<u64, String>;
 }
@@ -1,9 +1,18 @@
-use crate::protocol::{CurrentLocation, LocationUpdate, Stats};
+use crate::protocol::{
+    AggregateCell, BboxPage, CurrentLocation, HistoryEntry, KnnQuery, LocationUpdate, Page,
+    QueryHit, RadiusQuery, Stats, TrajectoryMatch, TrajectorySlice, WarmUpReport,
+};
 use spatio::Spatio;
+use spatio::error::SpatioError;
+use spatio_types::config::{HistoryEventKind, ScanDirection};
 use spatio_types::geo::{DistanceMetric, Point, Polygon};
 use spatio_types::point::Point3d;
-use spatio_types::time::system_time_from_secs;
+use spatio_types::query::Predicate;
+use spatio_types::time::TimeRange;
+use spatio_types::trajectory::TrajectorySummary;
+use std::ops::Bound;
 use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct Reader {
@@ -16,18 +25,68 @@ fn encode_metadata(metadata: &serde_json::Value) -> Result<Vec<u8>, String> {
     serde_json::to_vec(metadata).map_err(|e| format!("Failed to serialize metadata: {e}"))
 }
 
+/// Seconds since the Unix epoch, as the wire carries timestamps.
+fn epoch_secs(timestamp: std::time::SystemTime) -> f64 {
+    timestamp
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
 /// Convert a core current-location into its wire representation.
-fn to_wire(loc: &spatio::db::CurrentLocation) -> Result<CurrentLocation, String> {
+pub(crate) fn to_wire(loc: &spatio::db::CurrentLocation) -> Result<CurrentLocation, String> {
     Ok(CurrentLocation {
         object_id: loc.object_id.clone(),
         position: loc.position.clone(),
         metadata: encode_metadata(&loc.metadata)?,
+        timestamp: epoch_secs(loc.timestamp),
+    })
+}
+
+/// Convert a core distance-query result into its wire representation.
+fn hit_to_wire(hit: &spatio::NearbyHit) -> Result<QueryHit, String> {
+    Ok(QueryHit::new(to_wire(&hit.location)?, hit.distance))
+}
+
+/// Convert a core trajectory point into its wire representation.
+fn update_to_wire(upd: &spatio::db::LocationUpdate) -> Result<LocationUpdate, String> {
+    Ok(LocationUpdate {
+        timestamp: epoch_secs(upd.timestamp),
+        position: upd.position.clone(),
+        metadata: encode_metadata(&upd.metadata)?,
     })
 }
 
-/// Map a DB error into the wire error string.
-fn internal_err(e: impl std::fmt::Display) -> String {
-    format!("Internal error: {e}")
+/// Convert trajectory matches into their wire representation, keeping the
+/// first `limit` objects.
+fn matches_to_wire(
+    matches: Vec<spatio::TrajectoryMatch>,
+    limit: usize,
+) -> Result<Vec<TrajectoryMatch>, String> {
+    matches
+        .into_iter()
+        .take(limit)
+        .map(|m| {
+            let segments = m
+                .segments
+                .iter()
+                .map(|segment| segment.iter().map(update_to_wire).collect())
+                .collect::<Result<_, String>>()?;
+            Ok(TrajectoryMatch {
+                object_id: m.object_id,
+                segments,
+            })
+        })
+        .collect()
+}
+
+/// Map a DB error into the wire error string. Rejected input is the caller's
+/// to fix and is reported as-is; anything else is an internal error.
+fn db_err(e: SpatioError) -> String {
+    match e {
+        SpatioError::Validation(_) | SpatioError::InvalidInput(_) => e.to_string(),
+        _ => format!("Internal error: {e}"),
+    }
 }
 
 impl Reader {
@@ -42,34 +101,82 @@ impl Reader {
         }
     }
 
-    pub fn query_radius(
+    pub fn range(
+        &self,
+        namespace: &str,
+        start: Bound<&str>,
+        end: Bound<&str>,
+        limit: usize,
+        direction: ScanDirection,
+    ) -> Result<Vec<CurrentLocation>, String> {
+        let results = self
+            .db
+            .range(namespace, (start, end), limit, direction)
+            .map_err(db_err)?;
+        results.iter().map(|loc| to_wire(loc)).collect()
+    }
+
+    pub fn query_radius(&self, query: &RadiusQuery) -> Result<Vec<QueryHit>, String> {
+        let RadiusQuery {
+            namespace,
+            center,
+            radius,
+            limit,
+            metric,
+        } = query;
+        let results = match metric {
+            Some(metric) => self
+                .db
+                .query_radius_with_metric(namespace, center, *radius, *limit, *metric),
+            None => self.db.query_radius(namespace, center, *radius, *limit),
+        }
+        .map_err(db_err)?;
+        results.iter().map(hit_to_wire).collect()
+    }
+
+    pub fn query_radius_jittered(
         &self,
         namespace: &str,
         center: &Point3d,
         radius: f64,
         limit: usize,
-    ) -> Result<Vec<(CurrentLocation, f64)>, String> {
+        jitter: f64,
+        key: u128,
+    ) -> Result<Vec<QueryHit>, String> {
         let results = self
             .db
-            .query_radius(namespace, center, radius, limit)
-            .map_err(internal_err)?;
-        results
-            .into_iter()
-            .map(|(loc, dist)| Ok((to_wire(&loc)?, dist)))
-            .collect()
+            .query_radius_jittered(namespace, center, radius, limit, jitter, key)
+            .map_err(db_err)?;
+        results.iter().map(hit_to_wire).collect()
+    }
+
+    pub fn knn(&self, query: &KnnQuery) -> Result<Vec<QueryHit>, String> {
+        let results = self
+            .db
+            .knn(&query.namespace, &query.center, query.k)
+            .map_err(db_err)?;
+        results.iter().map(hit_to_wire).collect()
     }
 
-    pub fn knn(
+    pub fn knn_2d(
         &self,
         namespace: &str,
-        center: &Point3d,
+        center: &Point,
         k: usize,
-    ) -> Result<Vec<(CurrentLocation, f64)>, String> {
-        let results = self.db.knn(namespace, center, k).map_err(internal_err)?;
-        results
-            .into_iter()
-            .map(|(loc, dist)| Ok((to_wire(&loc)?, dist)))
-            .collect()
+        max_distance: Option<f64>,
+        metric: Option<DistanceMetric>,
+    ) -> Result<Vec<QueryHit>, String> {
+        let results = self
+            .db
+            .knn_2d(
+                namespace,
+                center,
+                k,
+                max_distance,
+                metric.unwrap_or_default(),
+            )
+            .map_err(db_err)?;
+        results.iter().map(hit_to_wire).collect()
     }
 
     pub fn stats(&self) -> Stats {
@@ -77,9 +184,27 @@ impl Reader {
         Stats {
             object_count: s.hot_state_objects,
             memory_usage_bytes: s.memory_usage_bytes,
+            namespace_objects: s.namespace_objects,
+            cold_log_bytes: s.cold_log_bytes,
+            queries_count: s.queries_count,
+            updates_per_sec: s.updates_per_sec,
+            per_minute: s.per_minute,
         }
     }
 
+    pub fn warm_up(&self, recent_secs: u64) -> Result<WarmUpReport, String> {
+        let report = self
+            .db
+            .warm_up(Duration::from_secs(recent_secs))
+            .map_err(db_err)?;
+        Ok(WarmUpReport {
+            objects: report.objects,
+            segments_read: report.segments_read,
+            bytes_read: report.bytes_read,
+            elapsed_secs: report.elapsed.as_secs_f64(),
+        })
+    }
+
     pub fn query_bbox(
         &self,
         namespace: &str,
@@ -92,10 +217,78 @@ impl Reader {
         let results = self
             .db
             .query_bbox(namespace, min_x, min_y, max_x, max_y, limit)
-            .map_err(internal_err)?;
+            .map_err(db_err)?;
         results.into_iter().map(|loc| to_wire(&loc)).collect()
     }
 
+    #[allow(clippy::too_many_arguments)]
+    pub fn query_bbox_with_distances(
+        &self,
+        namespace: &str,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        anchor: Option<&Point3d>,
+        limit: usize,
+    ) -> Result<Vec<QueryHit>, String> {
+        let results = self
+            .db
+            .query_bbox_with_distances(namespace, min_x, min_y, max_x, max_y, anchor, limit)
+            .map_err(db_err)?;
+        results.iter().map(hit_to_wire).collect()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn query_bbox_page(
+        &self,
+        namespace: &str,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        page_size: usize,
+        token: Option<&str>,
+    ) -> Result<BboxPage, String> {
+        // Token and replica-lag errors are the caller's to act on, so pass
+        // them through verbatim.
+        let page = self
+            .db
+            .query_bbox_page(namespace, min_x, min_y, max_x, max_y, page_size, token)
+            .map_err(|e| e.to_string())?;
+        Ok(BboxPage {
+            items: page
+                .items
+                .iter()
+                .map(|loc| to_wire(loc))
+                .collect::<Result<_, _>>()?,
+            next_token: page.next_token,
+        })
+    }
+
+    pub fn query_radius_page(
+        &self,
+        namespace: &str,
+        center: &Point3d,
+        radius: f64,
+        page_size: usize,
+        token: Option<&str>,
+    ) -> Result<Page<QueryHit>, String> {
+        let page = self
+            .db
+            .query_radius_page(namespace, center, radius, page_size, token)
+            .map_err(|e| e.to_string())?;
+        Ok(Page {
+            items: page
+                .items
+                .iter()
+                .map(hit_to_wire)
+                .collect::<Result<_, String>>()?,
+            next_token: page.next_token,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn query_cylinder(
         &self,
         namespace: &str,
@@ -104,55 +297,130 @@ impl Reader {
         max_z: f64,
         radius: f64,
         limit: usize,
-    ) -> Result<Vec<(CurrentLocation, f64)>, String> {
+        metric: Option<DistanceMetric>,
+    ) -> Result<Vec<QueryHit>, String> {
+        let results = match metric {
+            Some(metric) => self.db.query_within_cylinder_with_metric(
+                namespace, center, min_z, max_z, radius, limit, metric,
+            ),
+            None => self
+                .db
+                .query_within_cylinder(namespace, center, min_z, max_z, radius, limit),
+        }
+        .map_err(db_err)?;
+        results.iter().map(hit_to_wire).collect()
+    }
+
+    pub fn query_trajectory(&self, query: &TrajectorySlice) -> Result<Vec<LocationUpdate>, String> {
         let results = self
             .db
-            .query_within_cylinder(namespace, center, min_z, max_z, radius, limit)
-            .map_err(internal_err)?;
-        results
-            .into_iter()
-            .map(|(loc, dist)| Ok((to_wire(&loc)?, dist)))
-            .collect()
+            .query_trajectory(&query.namespace, &query.object_id, query.range, query.limit)
+            .map_err(|e| e.to_string())?;
+        results.iter().map(update_to_wire).collect()
     }
 
-    pub fn query_trajectory(
+    pub fn query_trajectory_page(
         &self,
         namespace: &str,
         id: &str,
-        start_time: Option<f64>,
-        end_time: Option<f64>,
-        limit: usize,
-    ) -> Result<Vec<LocationUpdate>, String> {
-        let start = match start_time {
-            Some(t) => system_time_from_secs(t)?,
-            None => std::time::UNIX_EPOCH,
-        };
-        let end = match end_time {
-            Some(t) => system_time_from_secs(t)?,
-            None => std::time::SystemTime::now(),
-        };
+        range: TimeRange,
+        page_size: usize,
+        token: Option<&str>,
+    ) -> Result<Page<LocationUpdate>, String> {
+        let page = self
+            .db
+            .query_trajectory_page(namespace, id, range, page_size, token)
+            .map_err(|e| e.to_string())?;
+        Ok(Page {
+            items: page
+                .items
+                .iter()
+                .map(update_to_wire)
+                .collect::<Result<_, _>>()?,
+            next_token: page.next_token,
+        })
+    }
 
+    pub fn history(
+        &self,
+        namespace: &str,
+        id: &str,
+        range: TimeRange,
+        kind: Option<HistoryEventKind>,
+        limit: usize,
+    ) -> Result<Vec<HistoryEntry>, String> {
         let results = self
             .db
-            .query_trajectory(namespace, id, start, end, limit)
-            .map_err(|e| e.to_string())?;
+            .history_between(namespace, id, range, kind)
+            .map_err(db_err)?;
         results
             .into_iter()
-            .map(|upd| {
-                let timestamp = upd
+            .take(limit)
+            .map(|entry| {
+                let timestamp = entry
                     .timestamp
                     .duration_since(std::time::UNIX_EPOCH)
                     .unwrap_or_default()
                     .as_secs_f64();
-                Ok(LocationUpdate {
+                let (position, metadata) = match entry.update {
+                    Some(upd) => (Some(upd.position), encode_metadata(&upd.metadata)?),
+                    None => (None, Vec::new()),
+                };
+                Ok(HistoryEntry {
                     timestamp,
-                    position: upd.position,
-                    metadata: encode_metadata(&upd.metadata)?,
+                    kind: entry.kind,
+                    position,
+                    metadata,
                 })
             })
             .collect()
     }
 
+    pub fn trajectory_summary(
+        &self,
+        namespace: &str,
+        id: &str,
+        range: TimeRange,
+    ) -> Result<TrajectorySummary, String> {
+        self.db
+            .trajectory_summary(namespace, id, range)
+            .map_err(db_err)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn query_trajectories_intersecting_bbox(
+        &self,
+        namespace: &str,
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+        range: TimeRange,
+        limit: usize,
+    ) -> Result<Vec<TrajectoryMatch>, String> {
+        let bbox = spatio_types::bbox::BoundingBox2D::new(min_x, min_y, max_x, max_y);
+        let matches = self
+            .db
+            .query_trajectories_intersecting_bbox(namespace, &bbox, range)
+            .map_err(db_err)?;
+        matches_to_wire(matches, limit)
+    }
+
+    pub fn query_trajectories_within_radius(
+        &self,
+        namespace: &str,
+        center: &Point3d,
+        radius: f64,
+        range: TimeRange,
+        limit: usize,
+    ) -> Result<Vec<TrajectoryMatch>, String> {
+        let matches = self
+            .db
+            .query_trajectories_within_radius(namespace, center, radius, range)
+            .map_err(db_err)?;
+        matches_to_wire(matches, limit)
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn query_bbox_3d(
         &self,
@@ -168,7 +436,7 @@ impl Reader {
         let results = self
             .db
             .query_within_bbox_3d(namespace, min_x, min_y, min_z, max_x, max_y, max_z, limit)
-            .map_err(internal_err)?;
+            .map_err(db_err)?;
         results.into_iter().map(|loc| to_wire(&loc)).collect()
     }
 
@@ -178,15 +446,12 @@ impl Reader {
         id: &str,
         radius: f64,
         limit: usize,
-    ) -> Result<Vec<(CurrentLocation, f64)>, String> {
+    ) -> Result<Vec<QueryHit>, String> {
         let results = self
             .db
             .query_near(namespace, id, radius, limit)
-            .map_err(internal_err)?;
-        results
-            .into_iter()
-            .map(|(loc, dist)| Ok((to_wire(&loc)?, dist)))
-            .collect()
+            .map_err(db_err)?;
+        results.iter().map(hit_to_wire).collect()
     }
 
     pub fn contains(
@@ -198,10 +463,58 @@ impl Reader {
         let results = self
             .db
             .query_polygon(namespace, polygon, limit)
-            .map_err(internal_err)?;
+            .map_err(db_err)?;
         results.into_iter().map(|loc| to_wire(&loc)).collect()
     }
 
+    pub fn contains_with_distances(
+        &self,
+        namespace: &str,
+        polygon: &Polygon,
+        anchor: Option<&Point3d>,
+        limit: usize,
+    ) -> Result<Vec<QueryHit>, String> {
+        let results = self
+            .db
+            .query_polygon_with_distances(namespace, polygon, anchor, limit)
+            .map_err(db_err)?;
+        results.iter().map(hit_to_wire).collect()
+    }
+
+    pub fn query(
+        &self,
+        namespace: &str,
+        predicate: &Predicate,
+        limit: usize,
+    ) -> Result<Vec<CurrentLocation>, String> {
+        let results = self.db.query(namespace, predicate, limit).map_err(db_err)?;
+        results.into_iter().map(|loc| to_wire(&loc)).collect()
+    }
+
+    pub fn create_view(
+        &self,
+        name: &str,
+        namespace: &str,
+        predicate: Predicate,
+    ) -> Result<(), String> {
+        self.db
+            .create_view(name, namespace, predicate)
+            .map_err(db_err)
+    }
+
+    pub fn list_namespaces(&self) -> Result<Vec<String>, String> {
+        self.db.list_namespaces().map_err(db_err)
+    }
+
+    pub fn drop_view(&self, name: &str) -> Result<bool, String> {
+        self.db.drop_view(name).map_err(db_err)
+    }
+
+    pub fn view(&self, name: &str, limit: usize) -> Result<Vec<CurrentLocation>, String> {
+        let results = self.db.view(name).map_err(db_err)?;
+        results.iter().take(limit).map(|loc| to_wire(loc)).collect()
+    }
+
     pub fn distance(
         &self,
         namespace: &str,
@@ -211,7 +524,7 @@ impl Reader {
     ) -> Result<Option<f64>, String> {
         self.db
             .distance_between(namespace, id1, id2, metric.unwrap_or_default())
-            .map_err(|e| format!("Internal error: {e}"))
+            .map_err(db_err)
     }
 
     pub fn distance_to(
@@ -223,13 +536,11 @@ impl Reader {
     ) -> Result<Option<f64>, String> {
         self.db
             .distance_to(namespace, id, point, metric.unwrap_or_default())
-            .map_err(|e| format!("Internal error: {e}"))
+            .map_err(db_err)
     }
 
     pub fn convex_hull(&self, namespace: &str) -> Result<Option<Polygon>, String> {
-        self.db
-            .convex_hull(namespace)
-            .map_err(|e| format!("Internal error: {e}"))
+        self.db.convex_hull(namespace).map_err(db_err)
     }
 
     pub fn bounding_box(
@@ -239,6 +550,25 @@ impl Reader {
         self.db
             .bounding_box(namespace)
             .map(|opt| opt.map(spatio_types::bbox::BoundingBox2D::from_rect))
-            .map_err(|e| format!("Internal error: {e}"))
+            .map_err(db_err)
+    }
+
+    pub fn k_anonymous_cells(
+        &self,
+        namespace: &str,
+        cell_size: f64,
+        k: usize,
+    ) -> Result<Vec<AggregateCell>, String> {
+        let cells = self
+            .db
+            .k_anonymous_cells(namespace, cell_size, k)
+            .map_err(db_err)?;
+        Ok(cells
+            .into_iter()
+            .map(|cell| AggregateCell {
+                bounds: spatio_types::bbox::BoundingBox2D::from_rect(cell.bounds),
+                count: cell.count,
+            })
+            .collect())
     }
 }
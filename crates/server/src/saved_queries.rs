@@ -0,0 +1,245 @@
+//! Named, parameterized queries registered on the server.
+//!
+//! A client registers a [`QueryTemplate`] once under a name, and later runs it
+//! by sending just the name and its arguments. Definitions live in one place
+//! and repeated invocations of large predicates (polygons especially) cost a
+//! few bytes on the wire.
+//!
+//! The registry is held in memory and shared by all connections; it does not
+//! survive a server restart.
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use spatio_types::query::Predicate;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Most templates a server keeps, so clients can't grow the registry without
+/// bound.
+pub const MAX_SAVED_QUERIES: usize = 1024;
+
+/// Longest accepted query or parameter name, in bytes.
+const MAX_NAME_BYTES: usize = 128;
+
+/// Arguments of a saved query invocation, by parameter name.
+pub type QueryArgs = BTreeMap<String, serde_json::Value>;
+
+/// A [`Predicate`] some of whose values are supplied when the query is run.
+///
+/// Each parameter names a JSON pointer into the serialized predicate. The
+/// value found there is the parameter's default; an argument replaces it.
+///
+/// # Examples
+///
+/// ```
+/// use spatio_server::saved_queries::{QueryArgs, QueryTemplate};
+/// use spatio_types::geo::Point;
+/// use spatio_types::query::Predicate;
+///
+/// let busy_zone = QueryTemplate::new(Predicate::WithinRadius {
+///     center: Point::new(0.0, 0.0),
+///     radius: 500.0,
+/// })
+/// .param("center", "/within_radius/center")
+/// .param("radius", "/within_radius/radius");
+///
+/// let args = QueryArgs::from([("radius".to_string(), 250.0.into())]);
+/// let bound = busy_zone.bind(&args).unwrap();
+/// assert!(matches!(bound, Predicate::WithinRadius { radius: 250.0, .. }));
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryTemplate {
+    predicate: Predicate,
+    params: BTreeMap<String, String>,
+}
+
+impl QueryTemplate {
+    /// A template with no parameters yet.
+    pub fn new(predicate: Predicate) -> Self {
+        Self {
+            predicate,
+            params: BTreeMap::new(),
+        }
+    }
+
+    /// Expose the value at JSON pointer `pointer` as parameter `name`.
+    pub fn param(mut self, name: impl Into<String>, pointer: impl Into<String>) -> Self {
+        self.params.insert(name.into(), pointer.into());
+        self
+    }
+
+    /// Parameter names, in order.
+    pub fn params(&self) -> impl Iterator<Item = &str> {
+        self.params.keys().map(String::as_str)
+    }
+
+    /// The predicate with its default values.
+    pub fn predicate(&self) -> &Predicate {
+        &self.predicate
+    }
+
+    /// Substitute `args` into the predicate. Omitted parameters keep their
+    /// defaults; unknown parameters and ill-typed values are rejected.
+    pub fn bind(&self, args: &QueryArgs) -> Result<Predicate, String> {
+        if let Some(unknown) = args.keys().find(|name| !self.params.contains_key(*name)) {
+            return Err(format!("Unknown query parameter: {unknown}"));
+        }
+        if args.is_empty() {
+            return Ok(self.predicate.clone());
+        }
+        let mut tree = self.tree()?;
+        for (name, value) in args {
+            let slot = tree
+                .pointer_mut(&self.params[name])
+                .ok_or_else(|| format!("Query parameter {name} has no target"))?;
+            *slot = value.clone();
+        }
+        serde_json::from_value(tree).map_err(|e| format!("Invalid query arguments: {e}"))
+    }
+
+    /// Check that parameter names are sane and every pointer resolves.
+    fn check(&self) -> Result<(), String> {
+        let tree = self.tree()?;
+        for (name, pointer) in &self.params {
+            check_name("Query parameter", name)?;
+            if tree.pointer(pointer).is_none() {
+                return Err(format!(
+                    "Query parameter {name} points at nothing: {pointer:?}"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn tree(&self) -> Result<serde_json::Value, String> {
+        serde_json::to_value(&self.predicate).map_err(|e| format!("Internal error: {e}"))
+    }
+}
+
+fn check_name(kind: &str, name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_NAME_BYTES {
+        return Err(format!(
+            "{kind} name must be 1 to {MAX_NAME_BYTES} bytes long"
+        ));
+    }
+    Ok(())
+}
+
+/// The server's registry of saved queries.
+#[derive(Clone, Default)]
+pub struct SavedQueries {
+    queries: Arc<RwLock<HashMap<String, Arc<QueryTemplate>>>>,
+}
+
+impl SavedQueries {
+    /// Register `template` as `name`, replacing any query of that name.
+    ///
+    /// The template's defaults must form a valid predicate, so a query that
+    /// registers can always run without arguments.
+    pub fn register(&self, name: String, template: QueryTemplate) -> Result<(), String> {
+        check_name("Query", &name)?;
+        template.check()?;
+        spatio::validation::validate_predicate(template.predicate()).map_err(|e| e.to_string())?;
+
+        let mut queries = self.queries.write();
+        if queries.len() >= MAX_SAVED_QUERIES && !queries.contains_key(&name) {
+            return Err(format!(
+                "Too many saved queries (limit {MAX_SAVED_QUERIES})"
+            ));
+        }
+        queries.insert(name, Arc::new(template));
+        Ok(())
+    }
+
+    /// Remove `name`, returning whether it was registered.
+    pub fn unregister(&self, name: &str) -> bool {
+        self.queries.write().remove(name).is_some()
+    }
+
+    /// Bind `args` to the query registered as `name`.
+    pub fn bind(&self, name: &str, args: &QueryArgs) -> Result<Predicate, String> {
+        let template = self
+            .queries
+            .read()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("No saved query named {name:?}"))?;
+        template.bind(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spatio_types::geo::Point;
+
+    fn busy_zone() -> QueryTemplate {
+        QueryTemplate::new(
+            Predicate::WithinRadius {
+                center: Point::new(0.0, 0.0),
+                radius: 500.0,
+            }
+            .and(Predicate::Altitude {
+                min: None,
+                max: Some(100.0),
+            }),
+        )
+        .param("center", "/all/0/within_radius/center")
+        .param("radius", "/all/0/within_radius/radius")
+    }
+
+    #[test]
+    fn test_register_and_bind() {
+        let saved = SavedQueries::default();
+        saved.register("busy_zone".into(), busy_zone()).unwrap();
+
+        let args = QueryArgs::from([
+            (
+                "center".to_string(),
+                serde_json::to_value(Point::new(2.0, 3.0)).unwrap(),
+            ),
+            ("radius".to_string(), 50.0.into()),
+        ]);
+        let expected = Predicate::WithinRadius {
+            center: Point::new(2.0, 3.0),
+            radius: 50.0,
+        }
+        .and(Predicate::Altitude {
+            min: None,
+            max: Some(100.0),
+        });
+        assert_eq!(saved.bind("busy_zone", &args).unwrap(), expected);
+        assert_eq!(
+            saved.bind("busy_zone", &QueryArgs::new()).unwrap(),
+            *busy_zone().predicate()
+        );
+
+        let unknown = QueryArgs::from([("depth".to_string(), 1.0.into())]);
+        assert!(saved.bind("busy_zone", &unknown).is_err());
+        let ill_typed = QueryArgs::from([("radius".to_string(), "far".into())]);
+        assert!(saved.bind("busy_zone", &ill_typed).is_err());
+        assert!(saved.bind("quiet_zone", &QueryArgs::new()).is_err());
+
+        assert!(saved.unregister("busy_zone"));
+        assert!(!saved.unregister("busy_zone"));
+    }
+
+    #[test]
+    fn test_register_rejects_bad_templates() {
+        let saved = SavedQueries::default();
+        let dangling = busy_zone().param("depth", "/all/2");
+        assert!(saved.register("q".into(), dangling).is_err());
+        let invalid = QueryTemplate::new(Predicate::WithinRadius {
+            center: Point::new(0.0, 0.0),
+            radius: -1.0,
+        });
+        assert!(saved.register("q".into(), invalid).is_err());
+        assert!(saved.register(String::new(), busy_zone()).is_err());
+
+        for i in 0..MAX_SAVED_QUERIES {
+            saved.register(format!("q{i}"), busy_zone()).unwrap();
+        }
+        assert!(saved.register("one_more".into(), busy_zone()).is_err());
+        saved.register("q0".into(), busy_zone()).unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+//! Per-namespace admission control for read queries.
+//!
+//! Each namespace gets its own concurrency limit and a bounded queue of
+//! waiting queries, admitted in arrival order. A namespace flooded with
+//! expensive queries (large polygon scans, say) fills only its own slots, so
+//! cheap lookups in other namespaces still run at once. Once a namespace's
+//! queue is full, further queries to it are rejected instead of piling up.
+
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Idle namespace queues kept before they are pruned, so clients naming many
+/// namespaces can't grow the scheduler without bound.
+const MAX_IDLE_QUEUES: usize = 4096;
+
+/// How many queries of one namespace run, and wait, at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NamespaceLimits {
+    /// Queries executing concurrently.
+    pub max_concurrent: usize,
+    /// Queries waiting for a slot; more are rejected.
+    pub max_queued: usize,
+}
+
+impl Default for NamespaceLimits {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 32,
+            max_queued: 1024,
+        }
+    }
+}
+
+/// Limits for every namespace, with optional per-namespace overrides.
+#[derive(Debug, Clone, Default)]
+pub struct SchedulerConfig {
+    pub default_limits: NamespaceLimits,
+    pub namespaces: HashMap<String, NamespaceLimits>,
+}
+
+impl SchedulerConfig {
+    pub fn with_default_limits(mut self, limits: NamespaceLimits) -> Self {
+        assert!(
+            limits.max_concurrent > 0,
+            "Query concurrency must be greater than zero"
+        );
+        self.default_limits = limits;
+        self
+    }
+
+    pub fn with_namespace_limits(
+        mut self,
+        namespace: impl Into<String>,
+        limits: NamespaceLimits,
+    ) -> Self {
+        assert!(
+            limits.max_concurrent > 0,
+            "Query concurrency must be greater than zero"
+        );
+        self.namespaces.insert(namespace.into(), limits);
+        self
+    }
+}
+
+struct NamespaceQueue {
+    permits: Arc<Semaphore>,
+    waiting: AtomicUsize,
+    max_concurrent: usize,
+    max_queued: usize,
+}
+
+impl NamespaceQueue {
+    /// Whether no query holds or waits for a slot and no caller is about to.
+    /// Running queries hold only the semaphore, so the free permits tell.
+    fn is_idle(self: &Arc<Self>) -> bool {
+        Arc::strong_count(self) == 1
+            && self.permits.available_permits() == self.max_concurrent
+            && self.waiting.load(Ordering::Acquire) == 0
+    }
+}
+
+/// Admission control shared by all connections.
+#[derive(Clone, Default)]
+pub struct QueryScheduler {
+    config: Arc<SchedulerConfig>,
+    queues: Arc<Mutex<HashMap<String, Arc<NamespaceQueue>>>>,
+}
+
+/// A query's slot in its namespace, released on drop.
+pub struct QueryPermit {
+    _permit: OwnedSemaphorePermit,
+}
+
+impl QueryScheduler {
+    pub fn new(config: SchedulerConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            queues: Arc::default(),
+        }
+    }
+
+    /// Wait for a slot to run a query against `namespace`, or fail at once if
+    /// the namespace's queue is full.
+    pub async fn acquire(&self, namespace: &str) -> Result<QueryPermit, String> {
+        let queue = self.queue(namespace);
+        if let Ok(permit) = queue.permits.clone().try_acquire_owned() {
+            return Ok(QueryPermit { _permit: permit });
+        }
+
+        if queue.waiting.fetch_add(1, Ordering::AcqRel) >= queue.max_queued {
+            queue.waiting.fetch_sub(1, Ordering::AcqRel);
+            return Err(format!(
+                "Namespace {namespace:?} is overloaded; retry later"
+            ));
+        }
+        // Leaves the queue even if the request is cancelled while waiting.
+        struct Waiting<'a>(&'a AtomicUsize);
+        impl Drop for Waiting<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_sub(1, Ordering::AcqRel);
+            }
+        }
+        let _waiting = Waiting(&queue.waiting);
+
+        let permit = queue
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| "Query scheduler is shutting down".to_string())?;
+        Ok(QueryPermit { _permit: permit })
+    }
+
+    fn queue(&self, namespace: &str) -> Arc<NamespaceQueue> {
+        let mut queues = self.queues.lock();
+        if let Some(queue) = queues.get(namespace) {
+            return queue.clone();
+        }
+        if queues.len() >= MAX_IDLE_QUEUES {
+            queues.retain(|_, queue| !queue.is_idle());
+        }
+        let limits = self
+            .config
+            .namespaces
+            .get(namespace)
+            .copied()
+            .unwrap_or(self.config.default_limits);
+        let queue = Arc::new(NamespaceQueue {
+            permits: Arc::new(Semaphore::new(limits.max_concurrent)),
+            waiting: AtomicUsize::new(0),
+            max_concurrent: limits.max_concurrent,
+            max_queued: limits.max_queued,
+        });
+        queues.insert(namespace.to_string(), queue.clone());
+        queue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn scheduler() -> QueryScheduler {
+        QueryScheduler::new(SchedulerConfig::default().with_namespace_limits(
+            "busy",
+            NamespaceLimits {
+                max_concurrent: 1,
+                max_queued: 1,
+            },
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_busy_namespace_does_not_block_others() {
+        let scheduler = scheduler();
+        let running = scheduler.acquire("busy").await.unwrap();
+
+        let queued = {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move { scheduler.acquire("busy").await.map(|_| ()) })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!queued.is_finished());
+
+        // The queue is full, but other namespaces are unaffected.
+        assert!(scheduler.acquire("busy").await.is_err());
+        let _other = scheduler.acquire("quiet").await.unwrap();
+
+        drop(running);
+        queued.await.unwrap().unwrap();
+        let _again = scheduler.acquire("busy").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_leaves_the_queue() {
+        let scheduler = scheduler();
+        let _running = scheduler.acquire("busy").await.unwrap();
+
+        let waiting = tokio::time::timeout(Duration::from_millis(10), scheduler.acquire("busy"));
+        assert!(waiting.await.is_err());
+
+        let queued = {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move { scheduler.acquire("busy").await.map(|_| ()) })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!queued.is_finished());
+        queued.abort();
+    }
+
+    #[tokio::test]
+    async fn test_pruning_keeps_queues_with_running_queries() {
+        let scheduler = scheduler();
+        let _running = scheduler.acquire("busy").await.unwrap();
+        for i in 0..MAX_IDLE_QUEUES {
+            drop(scheduler.acquire(&format!("idle-{i}")).await.unwrap());
+        }
+
+        // The running query still holds the namespace's only slot.
+        let waiting = tokio::time::timeout(Duration::from_millis(10), scheduler.acquire("busy"));
+        assert!(waiting.await.is_err());
+        assert!(scheduler.queues.lock().len() < MAX_IDLE_QUEUES);
+    }
+}
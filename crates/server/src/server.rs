@@ -0,0 +1,402 @@
+//! Programmatic server setup for applications embedding Spatio.
+//!
+//! One server can answer on several transports: RPC always, and HTTP and gRPC
+//! on listeners of their own when those features are on. Every transport
+//! gets the same options, so they admit queries through one scheduler and
+//! check clients with one authenticator.
+//!
+//! ```no_run
+//! use spatio::Spatio;
+//! use spatio_server::SpatioServer;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let db = Spatio::builder().path("/var/lib/spatio").build()?;
+//! SpatioServer::builder()
+//!     .db(db)
+//!     .addr(([0, 0, 0, 0], 3000).into())
+//!     .auth(|conn: &spatio_server::auth::ConnectionInfo| {
+//!         if conn.peer.ip().is_loopback() {
+//!             Ok(())
+//!         } else {
+//!             Err("remote clients are not allowed".to_string())
+//!         }
+//!     })
+//!     .shutdown(async {
+//!         tokio::signal::ctrl_c().await.ok();
+//!     })
+//!     .serve()
+//!     .await
+//! # }
+//! ```
+
+use crate::auth::Authenticator;
+use crate::idempotency::IdempotencyConfig;
+use crate::middleware::Middleware;
+use crate::scheduler::SchedulerConfig;
+use crate::topology::ServerTopology;
+use crate::transport::rpc::{ServerOptions, run_server_with_options};
+use spatio::Spatio;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+
+/// Address served when neither [`SpatioServerBuilder::addr`] nor
+/// [`SpatioServerBuilder::listener`] is given; matches the CLI defaults.
+const DEFAULT_ADDR: ([u8; 4], u16) = ([127, 0, 0, 1], 3000);
+
+type Shutdown = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// A server bound to its address and ready to serve.
+///
+/// Built with [`SpatioServer::builder`]; binding separately from serving
+/// lets callers learn the address when they asked for port 0.
+pub struct SpatioServer {
+    listener: TcpListener,
+    #[cfg(feature = "http")]
+    http_listener: Option<TcpListener>,
+    #[cfg(feature = "grpc")]
+    grpc_listener: Option<TcpListener>,
+    db: Arc<Spatio>,
+    options: ServerOptions,
+    shutdown: Shutdown,
+}
+
+impl SpatioServer {
+    pub fn builder() -> SpatioServerBuilder {
+        SpatioServerBuilder::default()
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serve every transport until the shutdown future resolves, then flush
+    /// pending writes. If one transport fails, the others are stopped.
+    pub async fn serve(self) -> anyhow::Result<()> {
+        let stop = CancellationToken::new();
+        let shutdown = self.shutdown;
+        let signal = tokio::spawn({
+            let stop = stop.clone();
+            async move {
+                shutdown.await;
+                stop.cancel();
+            }
+        });
+        let stopped = || Box::pin(stop.clone().cancelled_owned());
+
+        #[cfg(feature = "http")]
+        let http = self.http_listener.map(|listener| {
+            tokio::spawn(crate::run_http_server(
+                listener,
+                self.db.clone(),
+                self.options.clone(),
+                stopped(),
+            ))
+        });
+        #[cfg(feature = "grpc")]
+        let grpc = self.grpc_listener.map(|listener| {
+            tokio::spawn(crate::run_grpc_server(
+                listener,
+                self.db.clone(),
+                self.options.clone(),
+                stopped(),
+            ))
+        });
+
+        let result = run_server_with_options(self.listener, self.db, self.options, stopped()).await;
+        stop.cancel();
+        signal.abort();
+        #[cfg(feature = "http")]
+        let result = match http {
+            Some(http) => result.and(http.await?),
+            None => result,
+        };
+        #[cfg(feature = "grpc")]
+        let result = match grpc {
+            Some(grpc) => result.and(grpc.await?),
+            None => result,
+        };
+        result
+    }
+}
+
+/// Configures a [`SpatioServer`]. Only the database is required.
+#[derive(Default)]
+pub struct SpatioServerBuilder {
+    db: Option<Arc<Spatio>>,
+    addr: Option<SocketAddr>,
+    listener: Option<TcpListener>,
+    #[cfg(feature = "http")]
+    http_listener: Option<TcpListener>,
+    #[cfg(feature = "grpc")]
+    grpc_listener: Option<TcpListener>,
+    options: ServerOptions,
+    shutdown: Option<Shutdown>,
+}
+
+impl SpatioServerBuilder {
+    /// The database to serve.
+    pub fn db(mut self, db: impl Into<Arc<Spatio>>) -> Self {
+        self.db = Some(db.into());
+        self
+    }
+
+    /// Address to bind; defaults to `127.0.0.1:3000`.
+    pub fn addr(mut self, addr: SocketAddr) -> Self {
+        self.addr = Some(addr);
+        self
+    }
+
+    /// Serve on an already bound listener instead of binding
+    /// [`Self::addr`].
+    pub fn listener(mut self, listener: TcpListener) -> Self {
+        self.listener = Some(listener);
+        self
+    }
+
+    /// Also serve the HTTP API (see [`crate::transport::http`]) on
+    /// `listener`.
+    #[cfg(feature = "http")]
+    pub fn http_listener(mut self, listener: TcpListener) -> Self {
+        self.http_listener = Some(listener);
+        self
+    }
+
+    /// Also serve the gRPC API (see [`crate::transport::grpc`]) on
+    /// `listener`.
+    #[cfg(feature = "grpc")]
+    pub fn grpc_listener(mut self, listener: TcpListener) -> Self {
+        self.grpc_listener = Some(listener);
+        self
+    }
+
+    /// Serve TLS with `config` instead of plain TCP. Only the RPC transport
+    /// speaks TLS, so [`Self::bind`] then refuses HTTP and gRPC listeners
+    /// rather than serve them in plain text.
+    #[cfg(feature = "tls")]
+    pub fn tls(mut self, config: Arc<tokio_rustls::rustls::ServerConfig>) -> Self {
+        self.options = self.options.with_tls(config);
+        self
+    }
+
+    /// Time requests and count connections in `metrics` (see
+    /// [`crate::metrics`]).
+    #[cfg(feature = "metrics")]
+    pub fn metrics(mut self, metrics: Arc<crate::metrics::Metrics>) -> Self {
+        self.options = self.options.with_metrics(metrics);
+        self
+    }
+
+    /// Check every new connection with `auth`, on every transport.
+    pub fn auth(mut self, auth: impl Authenticator) -> Self {
+        self.options = self.options.with_auth(auth);
+        self
+    }
+
+    /// Add a request layer inside those already added (see
+    /// [`crate::middleware`]).
+    pub fn middleware(mut self, layer: impl Middleware) -> Self {
+        self.options.middleware = self.options.middleware.with(layer);
+        self
+    }
+
+    /// Remember idempotency keys of retried writes according to `config`.
+    pub fn idempotency(mut self, config: IdempotencyConfig) -> Self {
+        self.options.idempotency = config;
+        self
+    }
+
+    /// Take writes or refuse them as a replica according to `topology`;
+    /// keep a clone of it to change the role on failover (see
+    /// [`crate::topology`]). Servers are primaries by default.
+    pub fn topology(mut self, topology: ServerTopology) -> Self {
+        self.options.topology = topology;
+        self
+    }
+
+    /// Let a read wait up to `wait` for this server to apply the write a
+    /// client names ([`SpatioService::wait_applied`]), instead of
+    /// [`DEFAULT_APPLIED_WAIT`].
+    ///
+    /// [`SpatioService::wait_applied`]: crate::protocol::SpatioService::wait_applied
+    /// [`DEFAULT_APPLIED_WAIT`]: crate::handler::DEFAULT_APPLIED_WAIT
+    pub fn applied_wait(mut self, wait: std::time::Duration) -> Self {
+        self.options.applied_wait = Some(wait);
+        self
+    }
+
+    /// Move the hits of jittered queries under the secret `key` (see
+    /// [`SpatioService::query_radius_jittered`]); without one they are
+    /// refused. Keep the key the same across restarts, or an object's many
+    /// moved positions average out towards its true one.
+    ///
+    /// [`SpatioService::query_radius_jittered`]: crate::protocol::SpatioService::query_radius_jittered
+    pub fn jitter_key(mut self, key: u128) -> Self {
+        self.options = self.options.with_jitter_key(key);
+        self
+    }
+
+    /// Admit namespace queries from every transport according to `config`.
+    pub fn scheduler(mut self, config: SchedulerConfig) -> Self {
+        self.options = self.options.with_scheduler(config);
+        self
+    }
+
+    /// Stop serving once `signal` resolves; by default the server runs until
+    /// its task is dropped.
+    pub fn shutdown(mut self, signal: impl Future<Output = ()> + Send + 'static) -> Self {
+        self.shutdown = Some(Box::pin(signal));
+        self
+    }
+
+    /// Bind the listening socket. Fails if TLS is configured beside a
+    /// transport that would serve plain text.
+    pub async fn bind(self) -> anyhow::Result<SpatioServer> {
+        #[cfg(all(feature = "tls", feature = "http"))]
+        anyhow::ensure!(
+            self.options.tls.is_none() || self.http_listener.is_none(),
+            "TLS covers only the RPC transport; refusing to serve HTTP in plain text beside it"
+        );
+        #[cfg(all(feature = "tls", feature = "grpc"))]
+        anyhow::ensure!(
+            self.options.tls.is_none() || self.grpc_listener.is_none(),
+            "TLS covers only the RPC transport; refusing to serve gRPC in plain text beside it"
+        );
+        let db = self
+            .db
+            .ok_or_else(|| anyhow::anyhow!("SpatioServer needs a database; call .db()"))?;
+        let listener = match self.listener {
+            Some(listener) => listener,
+            None => TcpListener::bind(self.addr.unwrap_or_else(|| DEFAULT_ADDR.into())).await?,
+        };
+        Ok(SpatioServer {
+            listener,
+            #[cfg(feature = "http")]
+            http_listener: self.http_listener,
+            #[cfg(feature = "grpc")]
+            grpc_listener: self.grpc_listener,
+            db,
+            options: self.options,
+            shutdown: self
+                .shutdown
+                .unwrap_or_else(|| Box::pin(std::future::pending())),
+        })
+    }
+
+    /// Bind and serve until shutdown.
+    pub async fn serve(self) -> anyhow::Result<()> {
+        self.bind().await?.serve().await
+    }
+}
+
+#[cfg(all(test, any(feature = "http", feature = "tls")))]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "http")]
+    #[tokio::test]
+    async fn test_http_listener_shares_the_authenticator() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let http_addr = http.local_addr().unwrap();
+        let server = SpatioServer::builder()
+            .db(Spatio::builder().build().unwrap())
+            .listener(TcpListener::bind("127.0.0.1:0").await.unwrap())
+            .http_listener(http)
+            .auth(|_: &crate::auth::ConnectionInfo| Err("nobody gets in".to_string()))
+            .bind()
+            .await
+            .unwrap();
+        tokio::spawn(server.serve());
+
+        let mut stream = tokio::net::TcpStream::connect(http_addr).await.unwrap();
+        stream
+            .write_all(b"GET /namespaces/fleet/objects HTTP/1.1\r\nHost: spatio\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 403"), "{response}");
+    }
+
+    #[cfg(all(feature = "tls", feature = "http"))]
+    #[tokio::test]
+    async fn test_tls_refuses_a_plain_text_http_listener() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata");
+        let tls = crate::tls::TlsConfig::new(
+            format!("{dir}/localhost.crt"),
+            format!("{dir}/localhost.key"),
+        );
+        let bound = SpatioServer::builder()
+            .db(Spatio::builder().build().unwrap())
+            .listener(TcpListener::bind("127.0.0.1:0").await.unwrap())
+            .http_listener(TcpListener::bind("127.0.0.1:0").await.unwrap())
+            .tls(tls.load().unwrap())
+            .bind()
+            .await;
+        assert!(bound.is_err());
+    }
+
+    #[cfg(feature = "tls")]
+    #[tokio::test]
+    async fn test_serves_tls() {
+        use crate::protocol::SpatioServiceClient;
+        use tarpc::tokio_serde::formats::Json;
+        use tokio::net::TcpStream;
+        use tokio_rustls::TlsConnector;
+        use tokio_rustls::rustls::pki_types::pem::PemObject;
+        use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+        use tokio_rustls::rustls::{self, ClientConfig, RootCertStore, ServerConfig};
+        use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+        const CERT: &[u8] = include_bytes!("../testdata/localhost.crt");
+        const KEY: &[u8] = include_bytes!("../testdata/localhost.key");
+
+        let cert = CertificateDer::from_pem_slice(CERT).unwrap();
+        let key = PrivateKeyDer::from_pem_slice(KEY).unwrap();
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let config = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![cert.clone()], key)
+            .unwrap();
+
+        let server = SpatioServer::builder()
+            .db(Spatio::builder().build().unwrap())
+            .listener(TcpListener::bind("127.0.0.1:0").await.unwrap())
+            .tls(Arc::new(config))
+            .bind()
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.serve());
+
+        let mut roots = RootCertStore::empty();
+        roots.add(cert).unwrap();
+        let client_config = ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let stream = TlsConnector::from(Arc::new(client_config))
+            .connect(
+                ServerName::try_from("localhost").unwrap(),
+                TcpStream::connect(addr).await.unwrap(),
+            )
+            .await
+            .unwrap();
+        let transport = tarpc::serde_transport::new(
+            Framed::new(stream, LengthDelimitedCodec::new()),
+            Json::default(),
+        );
+        let client = SpatioServiceClient::new(tarpc::client::Config::default(), transport).spawn();
+
+        let stats = client.stats(tarpc::context::current()).await.unwrap();
+        assert_eq!(stats.object_count, 0);
+    }
+}
@@ -0,0 +1,237 @@
+//! Live location streams over RPC.
+//!
+//! A client subscribes to the objects of a namespace inside a region and then
+//! long-polls for [`RegionEvent`]s: every write that leaves an object in the
+//! region, and the write that takes it out. Each subscription is backed by a
+//! database change feed filtered to its namespace, so writers never wait for
+//! subscribers: one that falls [`spatio::db::SUBSCRIBER_CAPACITY`]
+//! events behind is closed, and its next poll fails so the client knows to
+//! re-read the region and subscribe again.
+//!
+//! Subscriptions live in memory, shared by all connections. One that is not
+//! polled for [`IDLE_TIMEOUT`] (its client went away, say) is removed.
+
+use crate::protocol::RegionEvent;
+use crate::reader::to_wire;
+use parking_lot::Mutex;
+use spatio::Spatio;
+use spatio::db::{ChangeEvent, ChangeFeed};
+use spatio_types::bbox::BoundingBox2D;
+use spatio_types::geo::Point;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant};
+
+/// Most open subscriptions a server keeps.
+pub const MAX_SUBSCRIPTIONS: usize = 1024;
+
+/// How long a subscription may go unpolled before it is removed.
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Longest a poll waits for the first event, so it returns well within the
+/// client's request deadline.
+pub const MAX_POLL_WAIT: Duration = Duration::from_secs(10);
+
+struct Subscription {
+    region: BoundingBox2D,
+    feed: Mutex<ChangeFeed>,
+    /// Objects last seen inside the region, to report when they leave.
+    inside: Mutex<HashSet<String>>,
+    last_poll: Mutex<Instant>,
+}
+
+impl Subscription {
+    fn contains(&self, location: &spatio::db::CurrentLocation) -> bool {
+        self.region
+            .contains_point(&Point::new(location.position.x(), location.position.y()))
+    }
+
+    /// The region event for a change, if it concerns the region.
+    fn filter(&self, event: ChangeEvent) -> Option<Result<RegionEvent, String>> {
+        let mut inside = self.inside.lock();
+        let location = event.location();
+        let id = &location.object_id;
+        let is_inside = !matches!(event, ChangeEvent::Deleted(_)) && self.contains(location);
+        let event = if is_inside {
+            inside.insert(id.clone());
+            RegionEvent::Updated
+        } else if inside.remove(id) {
+            RegionEvent::Left
+        } else {
+            return None;
+        };
+        Some(to_wire(location).map(event))
+    }
+}
+
+/// The server's open subscriptions.
+#[derive(Clone)]
+pub struct Subscriptions {
+    db: Arc<Spatio>,
+    open: Arc<Mutex<HashMap<u64, Arc<Subscription>>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl Subscriptions {
+    pub fn new(db: Arc<Spatio>) -> Self {
+        Self {
+            db,
+            open: Arc::default(),
+            next_id: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    /// Open a subscription to the objects of `namespace` inside `region`,
+    /// returning its ID. Events start with the next write.
+    pub fn subscribe(&self, namespace: &str, region: BoundingBox2D) -> Result<u64, String> {
+        spatio::compute::validation::validate_bbox(
+            region.min_x(),
+            region.min_y(),
+            region.max_x(),
+            region.max_y(),
+        )
+        .map_err(|e| e.to_string())?;
+        let mut open = self.open.lock();
+        open.retain(|_, subscription| subscription.last_poll.lock().elapsed() < IDLE_TIMEOUT);
+        if open.len() >= MAX_SUBSCRIPTIONS {
+            return Err(format!(
+                "Too many open subscriptions (limit {MAX_SUBSCRIPTIONS})"
+            ));
+        }
+        let feed = self
+            .db
+            .subscribe(namespace, "")
+            .map_err(|e| e.to_string())?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        open.insert(
+            id,
+            Arc::new(Subscription {
+                region,
+                feed: Mutex::new(feed),
+                inside: Mutex::new(HashSet::new()),
+                last_poll: Mutex::new(Instant::now()),
+            }),
+        );
+        Ok(id)
+    }
+
+    /// Close a subscription, returning whether it was open.
+    pub fn unsubscribe(&self, id: u64) -> bool {
+        self.open.lock().remove(&id).is_some()
+    }
+
+    /// Up to `max_events` events of subscription `id`, waiting up to `wait`
+    /// (at most [`MAX_POLL_WAIT`]) for the first. Blocks the calling thread.
+    ///
+    /// Fails if the subscription is unknown, or if it fell too far behind and
+    /// was closed; the client should then re-read the region and subscribe
+    /// again.
+    pub fn poll(
+        &self,
+        id: u64,
+        max_events: usize,
+        wait: Duration,
+    ) -> Result<Vec<RegionEvent>, String> {
+        let subscription = self
+            .open
+            .lock()
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| format!("Unknown subscription: {id}"))?;
+        *subscription.last_poll.lock() = Instant::now();
+
+        let deadline = Instant::now() + wait.min(MAX_POLL_WAIT);
+        let feed = subscription.feed.lock();
+        let mut events = Vec::new();
+        while events.len() < max_events {
+            // Wait only until the first event; then drain what is queued.
+            let timeout = if events.is_empty() {
+                deadline.saturating_duration_since(Instant::now())
+            } else {
+                Duration::ZERO
+            };
+            match feed.recv_timeout(timeout) {
+                Ok(change) => {
+                    if let Some(event) = subscription.filter(change) {
+                        events.push(event?);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                // Report the closure once the queued events are delivered.
+                Err(RecvTimeoutError::Disconnected) if !events.is_empty() => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    drop(feed);
+                    self.unsubscribe(id);
+                    return Err(
+                        "Subscription fell behind and was closed; subscribe again".to_string()
+                    );
+                }
+            }
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spatio_types::point::Point3d;
+
+    #[test]
+    fn test_reports_objects_entering_and_leaving_the_region() {
+        let db = Arc::new(Spatio::builder().build().unwrap());
+        let subscriptions = Subscriptions::new(db.clone());
+        let id = subscriptions
+            .subscribe("fleet", BoundingBox2D::new(0.0, 0.0, 1.0, 1.0))
+            .unwrap();
+        let upsert = |ns: &str, x: f64| {
+            db.upsert(
+                ns,
+                "truck",
+                Point3d::new(x, 0.5, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+        };
+
+        upsert("fleet", 0.5);
+        upsert("fleet", 5.0);
+        upsert("fleet", 6.0);
+        upsert("depot", 0.5);
+        let events = subscriptions.poll(id, 10, Duration::ZERO).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(&events[0], RegionEvent::Updated(loc) if loc.position.x() == 0.5));
+        assert!(matches!(&events[1], RegionEvent::Left(loc) if loc.position.x() == 5.0));
+
+        assert!(subscriptions.unsubscribe(id));
+        assert!(subscriptions.poll(id, 10, Duration::ZERO).is_err());
+    }
+
+    #[test]
+    fn test_slow_subscriber_is_closed() {
+        let db = Arc::new(Spatio::builder().build().unwrap());
+        let subscriptions = Subscriptions::new(db.clone());
+        let id = subscriptions
+            .subscribe("fleet", BoundingBox2D::new(0.0, 0.0, 1.0, 1.0))
+            .unwrap();
+        for i in 0..=spatio::db::SUBSCRIBER_CAPACITY {
+            let x = 0.5 + i as f64 * 1e-6;
+            db.upsert(
+                "fleet",
+                "truck",
+                Point3d::new(x, 0.5, 0.0),
+                serde_json::json!({}),
+                None,
+            )
+            .unwrap();
+        }
+
+        let events = subscriptions.poll(id, usize::MAX, Duration::ZERO).unwrap();
+        assert_eq!(events.len(), spatio::db::SUBSCRIBER_CAPACITY);
+        let err = subscriptions.poll(id, 10, Duration::ZERO).unwrap_err();
+        assert!(err.contains("subscribe again"), "{err}");
+    }
+}
@@ -0,0 +1,76 @@
+//! TLS certificates loaded from PEM files.
+//!
+//! [`SpatioServerBuilder::tls`](crate::SpatioServerBuilder::tls) takes a
+//! ready rustls config; [`TlsConfig`] builds one from the certificate and
+//! key files a deployment already has, which is what the CLI's `--tls-cert`
+//! and `--tls-key` options use.
+
+use anyhow::Context;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::{self, ServerConfig};
+
+/// Where to find the server's certificate chain and private key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsConfig {
+    /// PEM file with the server certificate first, then any intermediates.
+    pub cert_path: PathBuf,
+    /// PEM file with the certificate's private key (PKCS#8, PKCS#1 or SEC1).
+    pub key_path: PathBuf,
+}
+
+impl TlsConfig {
+    pub fn new(cert_path: impl Into<PathBuf>, key_path: impl Into<PathBuf>) -> Self {
+        Self {
+            cert_path: cert_path.into(),
+            key_path: key_path.into(),
+        }
+    }
+
+    /// Read the files into a rustls server config, without client
+    /// certificate authentication.
+    pub fn load(&self) -> anyhow::Result<Arc<ServerConfig>> {
+        let certs = CertificateDer::pem_file_iter(&self.cert_path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .with_context(|| format!("reading certificates from {}", self.cert_path.display()))?;
+        anyhow::ensure!(
+            !certs.is_empty(),
+            "no certificates in {}",
+            self.cert_path.display()
+        );
+        let key = PrivateKeyDer::from_pem_file(&self.key_path)
+            .with_context(|| format!("reading private key from {}", self.key_path.display()))?;
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let config = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .context("certificate and private key do not match")?;
+        Ok(Arc::new(config))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TESTDATA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata");
+
+    #[test]
+    fn test_loads_pem_files() {
+        let config = TlsConfig::new(
+            format!("{TESTDATA}/localhost.crt"),
+            format!("{TESTDATA}/localhost.key"),
+        );
+        config.load().unwrap();
+
+        let swapped = TlsConfig::new(&config.key_path, &config.cert_path);
+        assert!(swapped.load().is_err());
+        let missing = TlsConfig::new(format!("{TESTDATA}/missing.crt"), &config.key_path);
+        let err = missing.load().unwrap_err();
+        assert!(format!("{err:#}").contains("missing.crt"), "{err:#}");
+    }
+}
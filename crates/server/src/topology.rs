@@ -0,0 +1,146 @@
+//! A server's role in a primary/replica deployment.
+//!
+//! Spatio doesn't replicate by itself: whatever ships the primary's log to
+//! its replicas also decides when a replica takes over. It tells each server
+//! its role through a [`ServerTopology`], which the server reports to clients
+//! ([`SpatioService::topology`]) and checks writes against. A replica refuses
+//! writes with a [`not_primary_error`] naming the primary it follows, so
+//! clients can redirect them.
+//!
+//! Replicas serve reads, but only of what their own database holds: a
+//! replica numbers the writes it is fed in its own log, under its own
+//! [`SpatioService::log_id`]. So a sequence is only ever waited for
+//! ([`SpatioService::wait_applied`]) on a server with the log that issued
+//! it; any other server refuses it rather than compare unrelated numbers.
+//!
+//! Roles are assigned in a failover term, raised by each failover. A server
+//! ignores assignments from an older term than its own, and a client that
+//! hears two servers claim to be primary, such as a deposed one that hasn't
+//! been told yet, believes the one with the higher term.
+//!
+//! [`SpatioService::topology`]: crate::protocol::SpatioService::topology
+//! [`SpatioService::log_id`]: crate::protocol::SpatioService::log_id
+//! [`SpatioService::wait_applied`]: crate::protocol::SpatioService::wait_applied
+//! [`not_primary_error`]: crate::protocol::not_primary_error
+
+use crate::protocol::{Role, Topology};
+use parking_lot::RwLock;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// A server's current role. Cloning shares it, so the code that handles
+/// failover can keep a clone and change the role while the server runs.
+///
+/// Defaults to primary in term 0, as for a server on its own.
+#[derive(Clone)]
+pub struct ServerTopology {
+    current: Arc<RwLock<Topology>>,
+}
+
+impl Default for ServerTopology {
+    fn default() -> Self {
+        Self::primary(0)
+    }
+}
+
+impl ServerTopology {
+    /// A primary in `term`.
+    pub fn primary(term: u64) -> Self {
+        Self::new(Topology {
+            role: Role::Primary,
+            primary: None,
+            term,
+        })
+    }
+
+    /// A replica following `primary` in `term`.
+    pub fn replica_of(primary: SocketAddr, term: u64) -> Self {
+        Self::new(Topology {
+            role: Role::Replica,
+            primary: Some(primary),
+            term,
+        })
+    }
+
+    fn new(topology: Topology) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(topology)),
+        }
+    }
+
+    pub fn current(&self) -> Topology {
+        *self.current.read()
+    }
+
+    pub fn is_primary(&self) -> bool {
+        self.current.read().role == Role::Primary
+    }
+
+    /// Make this server the primary in `term`. Returns `false`, changing
+    /// nothing, if it already holds a role from a later term.
+    pub fn promote(&self, term: u64) -> bool {
+        self.assign(Topology {
+            role: Role::Primary,
+            primary: None,
+            term,
+        })
+    }
+
+    /// Make this server a replica of `primary` in `term`, refusing writes
+    /// from then on. Returns `false`, changing nothing, if it already holds
+    /// a role from a later term.
+    pub fn follow(&self, primary: SocketAddr, term: u64) -> bool {
+        self.assign(Topology {
+            role: Role::Replica,
+            primary: Some(primary),
+            term,
+        })
+    }
+
+    fn assign(&self, topology: Topology) -> bool {
+        let mut current = self.current.write();
+        if topology.term < current.term {
+            return false;
+        }
+        *current = topology;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{not_primary_error, primary_hint, stale_replica_error, stale_sequences};
+
+    #[test]
+    fn test_ignores_roles_from_older_terms() {
+        let primary: SocketAddr = "10.0.0.1:3000".parse().unwrap();
+        let topology = ServerTopology::replica_of(primary, 1);
+        assert!(!topology.is_primary());
+
+        assert!(topology.promote(2));
+        assert_eq!(topology.current().primary, None);
+        assert!(topology.is_primary());
+        // A late message from the failover before.
+        assert!(!topology.follow(primary, 1));
+        assert!(topology.is_primary());
+        assert_eq!(topology.current().term, 2);
+    }
+
+    #[test]
+    fn test_not_primary_error_names_the_primary() {
+        let primary: SocketAddr = "10.0.0.1:3000".parse().unwrap();
+        assert_eq!(
+            primary_hint(&not_primary_error(Some(primary))),
+            Some(Some(primary))
+        );
+        assert_eq!(primary_hint(&not_primary_error(None)), Some(None));
+        assert_eq!(primary_hint("Internal error: disk full"), None);
+    }
+
+    #[test]
+    fn test_stale_replica_error_names_both_sequences() {
+        assert_eq!(stale_sequences(&stale_replica_error(12, 9)), Some((12, 9)));
+        assert_eq!(stale_sequences(&not_primary_error(None)), None);
+    }
+}
@@ -0,0 +1,115 @@
+//! W3C trace context interop.
+//!
+//! Every RPC frame carries a tarpc [`trace::Context`] (trace id, parent span
+//! id, sampling flag). The server opens its request span as a child of that
+//! context, so with a `tracing_opentelemetry` layer installed, server spans
+//! (down to the database's query spans) join the caller's trace. These helpers
+//! convert to and from the `traceparent` header used by HTTP services, so an
+//! inbound web request can be followed into Spatio and back out.
+
+use opentelemetry::trace::TraceContextExt;
+use tarpc::trace::{self, SamplingDecision, SpanId, TraceId};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Parse a W3C `traceparent` header (`00-<trace id>-<parent id>-<flags>`).
+///
+/// Returns `None` for malformed headers or all-zero ids, which the spec says
+/// must be ignored (start a new trace instead). Headers of later versions are
+/// accepted as long as they begin with the version 00 fields.
+pub fn parse_traceparent(header: &str) -> Option<trace::Context> {
+    let header = header.trim();
+    let mut fields = header.splitn(5, '-');
+    let version = fields.next().filter(|v| v.len() == 2)?;
+    let trace_id = fields.next().filter(|v| v.len() == 32)?;
+    let span_id = fields.next().filter(|v| v.len() == 16)?;
+    let flags = fields.next().filter(|v| v.len() == 2)?;
+    if version == "ff" || (version == "00" && fields.next().is_some()) {
+        return None;
+    }
+    let is_hex = |s: &str| {
+        s.bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    };
+    if ![version, trace_id, span_id, flags].into_iter().all(is_hex) {
+        return None;
+    }
+
+    let trace_id = u128::from_str_radix(trace_id, 16).ok()?;
+    let span_id = u64::from_str_radix(span_id, 16).ok()?;
+    let flags = u8::from_str_radix(flags, 16).ok()?;
+    if trace_id == 0 || span_id == 0 {
+        return None;
+    }
+    Some(trace::Context {
+        trace_id: TraceId::from(trace_id),
+        span_id: SpanId::from(span_id),
+        sampling_decision: if flags & 0x01 != 0 {
+            SamplingDecision::Sampled
+        } else {
+            SamplingDecision::Unsampled
+        },
+    })
+}
+
+/// Format a trace context as a version 00 `traceparent` header.
+pub fn format_traceparent(context: &trace::Context) -> String {
+    let flags = match context.sampling_decision {
+        SamplingDecision::Sampled => 1,
+        SamplingDecision::Unsampled => 0,
+    };
+    format!(
+        "00-{:032x}-{:016x}-{:02x}",
+        u128::from(context.trace_id),
+        u64::from(context.span_id),
+        flags
+    )
+}
+
+/// Make `span` a child of a remote span, for OpenTelemetry export. Without a
+/// `tracing_opentelemetry` layer installed this does nothing.
+pub fn set_remote_parent(span: &tracing::Span, parent: &trace::Context) {
+    span.set_parent(opentelemetry::Context::new().with_remote_span_context(
+        opentelemetry::trace::SpanContext::new(
+            parent.trace_id.into(),
+            parent.span_id.into(),
+            parent.sampling_decision.into(),
+            true,
+            opentelemetry::trace::TraceState::default(),
+        ),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traceparent_round_trip() {
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let context = parse_traceparent(header).unwrap();
+        assert_eq!(
+            u128::from(context.trace_id),
+            0x4bf92f3577b34da6a3ce929d0e0e4736
+        );
+        assert_eq!(u64::from(context.span_id), 0x00f067aa0ba902b7);
+        assert_eq!(context.sampling_decision, SamplingDecision::Sampled);
+        assert_eq!(format_traceparent(&context), header);
+
+        // Future versions may append fields.
+        assert!(
+            parse_traceparent("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra")
+                .is_some()
+        );
+        for bad in [
+            "",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+        ] {
+            assert!(parse_traceparent(bad).is_none(), "{bad:?}");
+        }
+    }
+}
@@ -0,0 +1,1762 @@
+//! gRPC transport for the Spatio server, generated from `proto/spatio.proto`.
+//!
+//! For clients in languages without a tarpc stack. The service has one method
+//! per [`protocol::SpatioService`] method; a test below fails when the two
+//! drift apart. Predicates, query templates and their arguments travel as
+//! JSON text, serialized as over RPC. Requests go through the same
+//! [`Handler`] as RPC requests, so query admission, limits and idempotency
+//! behave the same; [`crate::middleware`] layers are RPC-only.
+//!
+//! Handler errors map to `INVALID_ARGUMENT` for rejected input,
+//! `UNAVAILABLE` when the server is overloaded or busy rewriting its log
+//! (the message then names when to retry), and `INTERNAL` otherwise.
+//! Clients the [`Authenticator`] refuses get `PERMISSION_DENIED`.
+
+// The generated service fixes `tonic::Status` as the error type.
+#![allow(clippy::result_large_err)]
+
+use serde::de::DeserializeOwned;
+use spatio::Spatio;
+use spatio_types::bbox::BoundingBox2D;
+use spatio_types::config::{HistoryEventKind, ScanDirection};
+use spatio_types::geo::{DistanceMetric, Point, Polygon};
+use spatio_types::point::Point3d;
+use spatio_types::stats::MinuteStats;
+use spatio_types::time::{TimeBound, TimeRange, UNIX_EPOCH};
+use spatio_types::trajectory::TrajectorySummary;
+use std::future::Future;
+use std::ops::Bound;
+use std::sync::Arc;
+use std::time::Duration;
+use tarpc::context;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{Request, Response, Status};
+use tracing::{info, warn};
+
+use crate::auth::{Authenticator, ConnectionInfo, Principal};
+use crate::handler::Handler;
+use crate::protocol::{self, SpatioService as _};
+use crate::saved_queries::QueryArgs;
+use crate::transport::rpc::ServerOptions;
+use crate::transport::{ErrorKind, classify_error, join_writer};
+
+/// Types and service stubs generated from `proto/spatio.proto`.
+pub mod proto {
+    tonic::include_proto!("spatio.v1");
+}
+
+pub use proto::spatio_service_server::SpatioServiceServer;
+
+/// Run the gRPC server until `shutdown` resolves, configured by `options`.
+///
+/// Like [`crate::run_http_server`], the server has its own writer and shares
+/// the query scheduler and authenticator of `options`.
+pub async fn run_grpc_server(
+    listener: tokio::net::TcpListener,
+    db: Arc<Spatio>,
+    options: ServerOptions,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    let (write_tx, writer_handle) = crate::writer::spawn_background_writer(db.clone(), 10_000);
+    let handler = options.handler(db, write_tx);
+    let auth = options.auth;
+    let service =
+        SpatioServiceServer::with_interceptor(GrpcService::new(handler), move |request| {
+            authenticate(auth.as_deref(), request)
+        });
+
+    info!("Spatio gRPC Server listening on {}", listener.local_addr()?);
+    tonic::transport::Server::builder()
+        .add_service(service)
+        .serve_with_incoming_shutdown(TcpListenerStream::new(listener), shutdown)
+        .await?;
+
+    join_writer(writer_handle).await;
+    Ok(())
+}
+
+/// Refuse requests from clients `auth` rejects, and tag the others with
+/// their principal. Like HTTP, this runs per request, with the peer address
+/// only.
+fn authenticate(
+    auth: Option<&dyn Authenticator>,
+    mut request: Request<()>,
+) -> Result<Request<()>, Status> {
+    let Some(auth) = auth else {
+        return Ok(request);
+    };
+    let Some(peer) = request.remote_addr() else {
+        return Err(Status::permission_denied("unknown peer address"));
+    };
+    let conn = ConnectionInfo {
+        peer,
+        client_certificates: Vec::new(),
+    };
+    match auth.authenticate(&conn) {
+        Ok(()) => {
+            if let Some(principal) = auth.principal(&conn) {
+                request.extensions_mut().insert(Principal(principal.into()));
+            }
+            Ok(request)
+        }
+        Err(reason) => {
+            warn!("Refused gRPC request from {peer}: {reason}");
+            Err(Status::permission_denied(reason))
+        }
+    }
+}
+
+/// The generated gRPC service, answering through a [`Handler`].
+#[derive(Clone)]
+pub struct GrpcService {
+    handler: Handler,
+}
+
+impl GrpcService {
+    pub fn new(handler: Handler) -> Self {
+        Self { handler }
+    }
+
+    /// The service wrapped for [`tonic::transport::Server::add_service`],
+    /// without authentication.
+    pub fn into_server(self) -> SpatioServiceServer<Self> {
+        SpatioServiceServer::new(self)
+    }
+
+    /// The handler serving the principal that sent `request`.
+    fn handler_for<T>(&self, request: &Request<T>) -> Handler {
+        match request.extensions().get::<Principal>() {
+            Some(Principal(principal)) => self.handler.clone().for_client(principal.clone()),
+            None => self.handler.clone(),
+        }
+    }
+}
+
+fn status(message: String) -> Status {
+    match classify_error(&message) {
+        ErrorKind::Rejected => Status::invalid_argument(message),
+        ErrorKind::Unavailable => Status::unavailable(message),
+        ErrorKind::Internal => Status::internal(message),
+    }
+}
+
+fn limit(value: u64) -> usize {
+    usize::try_from(value).unwrap_or(usize::MAX)
+}
+
+fn position(point: Option<proto::Point3d>, field: &str) -> Result<Point3d, Status> {
+    point
+        .map(Point3d::from)
+        .ok_or_else(|| Status::invalid_argument(format!("{field} is required")))
+}
+
+fn point(point: Option<proto::Point2d>, field: &str) -> Result<Point, Status> {
+    point
+        .map(|p| Point::new(p.x, p.y))
+        .ok_or_else(|| Status::invalid_argument(format!("{field} is required")))
+}
+
+fn polygon(polygon: Option<proto::Polygon>) -> Result<Polygon, Status> {
+    let polygon =
+        polygon.ok_or_else(|| Status::invalid_argument("polygon is required".to_string()))?;
+    let ring = |ring: proto::Ring| ring.points.iter().map(|p| (p.x, p.y)).collect::<Vec<_>>();
+    let exterior = ring(polygon.exterior.unwrap_or_default());
+    let interiors = polygon.interiors.into_iter().map(ring).collect();
+    Ok(Polygon::from_coords(&exterior, interiors))
+}
+
+fn bbox(bbox: Option<proto::BoundingBox>, field: &str) -> Result<BoundingBox2D, Status> {
+    bbox.map(|b| BoundingBox2D::new(b.min_x, b.min_y, b.max_x, b.max_y))
+        .ok_or_else(|| Status::invalid_argument(format!("{field} is required")))
+}
+
+/// Predicates, templates and arguments arrive as JSON text.
+fn parse_json<T: DeserializeOwned>(json: &str, field: &str) -> Result<T, Status> {
+    serde_json::from_str(json)
+        .map_err(|e| Status::invalid_argument(format!("invalid {field}: {e}")))
+}
+
+/// Metadata arrives as JSON text; empty means none.
+fn parse_metadata(json: &str) -> Result<serde_json::Value, Status> {
+    if json.is_empty() {
+        return Ok(serde_json::json!({}));
+    }
+    serde_json::from_str(json)
+        .map_err(|e| Status::invalid_argument(format!("invalid metadata JSON: {e}")))
+}
+
+fn metadata_json(metadata: Vec<u8>) -> String {
+    String::from_utf8(metadata).unwrap_or_default()
+}
+
+fn time_bound(bound: Option<proto::TimeBound>) -> Result<TimeBound, Status> {
+    let duration = |secs: f64| {
+        Duration::try_from_secs_f64(secs)
+            .map_err(|e| Status::invalid_argument(format!("invalid time {secs}: {e}")))
+    };
+    Ok(match bound.and_then(|b| b.bound) {
+        None => TimeBound::Open,
+        Some(proto::time_bound::Bound::At(secs)) => TimeBound::At(
+            UNIX_EPOCH
+                .checked_add(duration(secs)?)
+                .ok_or_else(|| Status::invalid_argument(format!("time out of range: {secs}")))?,
+        ),
+        Some(proto::time_bound::Bound::Ago(secs)) => TimeBound::Ago(duration(secs)?),
+    })
+}
+
+fn time_range(
+    start: Option<proto::TimeBound>,
+    end: Option<proto::TimeBound>,
+) -> Result<TimeRange, Status> {
+    Ok(TimeRange {
+        start: time_bound(start)?,
+        end: time_bound(end)?,
+    })
+}
+
+/// An unset bound is open.
+fn id_bound(bound: Option<proto::IdBound>) -> Bound<String> {
+    match bound.and_then(|b| b.bound) {
+        None => Bound::Unbounded,
+        Some(proto::id_bound::Bound::Included(id)) => Bound::Included(id),
+        Some(proto::id_bound::Bound::Excluded(id)) => Bound::Excluded(id),
+    }
+}
+
+fn scan_direction(direction: proto::ScanDirection) -> ScanDirection {
+    match direction {
+        proto::ScanDirection::Forward => ScanDirection::Forward,
+        proto::ScanDirection::Reverse => ScanDirection::Reverse,
+    }
+}
+
+/// `None` for either kind.
+fn history_kind(kind: proto::HistoryEventKind) -> Option<HistoryEventKind> {
+    match kind {
+        proto::HistoryEventKind::Unspecified => None,
+        proto::HistoryEventKind::Set => Some(HistoryEventKind::Set),
+        proto::HistoryEventKind::Delete => Some(HistoryEventKind::Delete),
+    }
+}
+
+impl From<proto::Point3d> for Point3d {
+    fn from(point: proto::Point3d) -> Self {
+        Point3d::new(point.x, point.y, point.z)
+    }
+}
+
+impl From<&Point3d> for proto::Point3d {
+    fn from(point: &Point3d) -> Self {
+        Self {
+            x: point.x(),
+            y: point.y(),
+            z: point.z(),
+        }
+    }
+}
+
+/// `None` leaves the choice to the namespace.
+fn metric(metric: proto::DistanceMetric) -> Option<DistanceMetric> {
+    match metric {
+        proto::DistanceMetric::Unspecified => None,
+        proto::DistanceMetric::Haversine => Some(DistanceMetric::Haversine),
+        proto::DistanceMetric::Geodesic => Some(DistanceMetric::Geodesic),
+        proto::DistanceMetric::Rhumb => Some(DistanceMetric::Rhumb),
+        proto::DistanceMetric::Euclidean => Some(DistanceMetric::Euclidean),
+    }
+}
+
+impl From<protocol::CurrentLocation> for proto::CurrentLocation {
+    fn from(location: protocol::CurrentLocation) -> Self {
+        Self {
+            position: Some((&location.position).into()),
+            object_id: location.object_id,
+            metadata_json: metadata_json(location.metadata),
+            timestamp: location.timestamp,
+        }
+    }
+}
+
+impl From<protocol::QueryHit> for proto::QueryHit {
+    fn from(hit: protocol::QueryHit) -> Self {
+        Self {
+            position: Some((&hit.position).into()),
+            object_id: hit.object_id,
+            metadata_json: metadata_json(hit.metadata),
+            distance: hit.distance,
+            timestamp: hit.timestamp,
+        }
+    }
+}
+
+impl From<protocol::LocationUpdate> for proto::LocationUpdate {
+    fn from(update: protocol::LocationUpdate) -> Self {
+        Self {
+            timestamp: update.timestamp,
+            position: Some((&update.position).into()),
+            metadata_json: metadata_json(update.metadata),
+        }
+    }
+}
+
+// The other direction, for clients.
+
+impl From<proto::CurrentLocation> for protocol::CurrentLocation {
+    fn from(location: proto::CurrentLocation) -> Self {
+        Self {
+            object_id: location.object_id,
+            position: location.position.unwrap_or_default().into(),
+            metadata: location.metadata_json.into_bytes(),
+            timestamp: location.timestamp,
+        }
+    }
+}
+
+impl From<proto::QueryHit> for protocol::QueryHit {
+    fn from(hit: proto::QueryHit) -> Self {
+        Self {
+            object_id: hit.object_id,
+            position: hit.position.unwrap_or_default().into(),
+            metadata: hit.metadata_json.into_bytes(),
+            timestamp: hit.timestamp,
+            distance: hit.distance,
+        }
+    }
+}
+
+impl From<proto::LocationUpdate> for protocol::LocationUpdate {
+    fn from(update: proto::LocationUpdate) -> Self {
+        Self {
+            timestamp: update.timestamp,
+            position: update.position.unwrap_or_default().into(),
+            metadata: update.metadata_json.into_bytes(),
+        }
+    }
+}
+
+impl From<DistanceMetric> for proto::DistanceMetric {
+    fn from(metric: DistanceMetric) -> Self {
+        match metric {
+            DistanceMetric::Haversine => Self::Haversine,
+            DistanceMetric::Geodesic => Self::Geodesic,
+            DistanceMetric::Rhumb => Self::Rhumb,
+            DistanceMetric::Euclidean => Self::Euclidean,
+        }
+    }
+}
+
+impl From<TimeBound> for proto::TimeBound {
+    fn from(bound: TimeBound) -> Self {
+        let bound = match bound {
+            TimeBound::Open => None,
+            TimeBound::At(t) => Some(proto::time_bound::Bound::At(
+                t.duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64(),
+            )),
+            TimeBound::Ago(d) => Some(proto::time_bound::Bound::Ago(d.as_secs_f64())),
+        };
+        Self { bound }
+    }
+}
+
+impl From<protocol::RadiusQuery> for proto::RadiusQuery {
+    fn from(query: protocol::RadiusQuery) -> Self {
+        let metric = query
+            .metric
+            .map_or(proto::DistanceMetric::Unspecified, Into::into);
+        Self {
+            namespace: query.namespace,
+            center: Some((&query.center).into()),
+            radius: query.radius,
+            limit: query.limit as u64,
+            metric: metric.into(),
+        }
+    }
+}
+
+impl From<protocol::KnnQuery> for proto::KnnQuery {
+    fn from(query: protocol::KnnQuery) -> Self {
+        Self {
+            namespace: query.namespace,
+            center: Some((&query.center).into()),
+            k: query.k as u64,
+        }
+    }
+}
+
+impl From<protocol::TrajectorySlice> for proto::TrajectorySlice {
+    fn from(slice: protocol::TrajectorySlice) -> Self {
+        Self {
+            namespace: slice.namespace,
+            object_id: slice.object_id,
+            start: Some(slice.range.start.into()),
+            end: Some(slice.range.end.into()),
+            limit: slice.limit as u64,
+        }
+    }
+}
+
+impl From<&Polygon> for proto::Polygon {
+    fn from(polygon: &Polygon) -> Self {
+        let exterior = polygon.exterior().coords();
+        Self {
+            exterior: Some(proto::Ring {
+                points: exterior
+                    .map(|c| proto::Point2d { x: c.x, y: c.y })
+                    .collect(),
+            }),
+            interiors: polygon
+                .interiors()
+                .iter()
+                .map(|ring| proto::Ring {
+                    points: ring
+                        .coords()
+                        .map(|c| proto::Point2d { x: c.x, y: c.y })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&BoundingBox2D> for proto::BoundingBox {
+    fn from(bbox: &BoundingBox2D) -> Self {
+        Self {
+            min_x: bbox.min_x(),
+            min_y: bbox.min_y(),
+            max_x: bbox.max_x(),
+            max_y: bbox.max_y(),
+        }
+    }
+}
+
+impl From<HistoryEventKind> for proto::HistoryEventKind {
+    fn from(kind: HistoryEventKind) -> Self {
+        match kind {
+            HistoryEventKind::Set => Self::Set,
+            HistoryEventKind::Delete => Self::Delete,
+        }
+    }
+}
+
+impl From<protocol::HistoryEntry> for proto::HistoryEntry {
+    fn from(entry: protocol::HistoryEntry) -> Self {
+        Self {
+            timestamp: entry.timestamp,
+            kind: proto::HistoryEventKind::from(entry.kind).into(),
+            position: entry.position.as_ref().map(Into::into),
+            metadata_json: metadata_json(entry.metadata),
+        }
+    }
+}
+
+impl From<TrajectorySummary> for proto::TrajectorySummary {
+    fn from(summary: TrajectorySummary) -> Self {
+        Self {
+            points: summary.points as u64,
+            total_distance: summary.total_distance,
+            duration_secs: summary.duration.as_secs_f64(),
+            average_speed: summary.average_speed,
+            max_speed: summary.max_speed,
+        }
+    }
+}
+
+impl From<protocol::TrajectoryMatch> for proto::TrajectoryMatch {
+    fn from(matched: protocol::TrajectoryMatch) -> Self {
+        Self {
+            object_id: matched.object_id,
+            segments: matched
+                .segments
+                .into_iter()
+                .map(|updates| proto::TrajectorySegment {
+                    updates: updates.into_iter().map(Into::into).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<protocol::RegionEvent> for proto::RegionEvent {
+    fn from(event: protocol::RegionEvent) -> Self {
+        let event = match event {
+            protocol::RegionEvent::Updated(location) => {
+                proto::region_event::Event::Updated(location.into())
+            }
+            protocol::RegionEvent::Left(location) => {
+                proto::region_event::Event::Left(location.into())
+            }
+        };
+        Self { event: Some(event) }
+    }
+}
+
+impl From<MinuteStats> for proto::MinuteStats {
+    fn from(stats: MinuteStats) -> Self {
+        let namespaces = stats
+            .counts
+            .into_iter()
+            .map(|(namespace, ops)| {
+                let counts = ops
+                    .into_iter()
+                    .filter_map(|(op, count)| {
+                        let name = serde_json::to_value(op).ok()?.as_str()?.to_string();
+                        Some((name, count))
+                    })
+                    .collect();
+                (namespace, proto::OperationCounts { counts })
+            })
+            .collect();
+        Self {
+            minute: stats.minute,
+            namespaces,
+        }
+    }
+}
+
+impl From<protocol::WarmUpReport> for proto::WarmUpReport {
+    fn from(report: protocol::WarmUpReport) -> Self {
+        Self {
+            objects: report.objects as u64,
+            segments_read: report.segments_read as u64,
+            bytes_read: report.bytes_read,
+            elapsed_secs: report.elapsed_secs,
+        }
+    }
+}
+
+impl From<protocol::Topology> for proto::Topology {
+    fn from(topology: protocol::Topology) -> Self {
+        let role = match topology.role {
+            protocol::Role::Primary => proto::Role::Primary,
+            protocol::Role::Replica => proto::Role::Replica,
+        };
+        Self {
+            role: role.into(),
+            primary: topology.primary.map(|addr| addr.to_string()),
+            term: topology.term,
+        }
+    }
+}
+
+fn hits_reply(hits: Vec<protocol::QueryHit>) -> Response<proto::HitsReply> {
+    Response::new(proto::HitsReply {
+        hits: hits.into_iter().map(Into::into).collect(),
+    })
+}
+
+fn locations_reply(locations: Vec<protocol::CurrentLocation>) -> Response<proto::LocationsReply> {
+    Response::new(proto::LocationsReply {
+        locations: locations.into_iter().map(Into::into).collect(),
+    })
+}
+
+fn matches_reply(
+    matches: Vec<protocol::TrajectoryMatch>,
+) -> Response<proto::TrajectoryMatchesReply> {
+    Response::new(proto::TrajectoryMatchesReply {
+        matches: matches.into_iter().map(Into::into).collect(),
+    })
+}
+
+#[tonic::async_trait]
+impl proto::spatio_service_server::SpatioService for GrpcService {
+    async fn upsert(
+        &self,
+        request: Request<proto::UpsertRequest>,
+    ) -> Result<Response<proto::WriteReply>, Status> {
+        let handler = self.handler_for(&request);
+        let req = request.into_inner();
+        let point = position(req.position, "position")?;
+        let metadata = parse_metadata(&req.metadata_json)?;
+        let sequence = handler
+            .upsert(
+                context::current(),
+                req.namespace,
+                req.object_id,
+                point,
+                metadata,
+                req.idempotency_key,
+            )
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::WriteReply { sequence }))
+    }
+
+    async fn upsert_batch(
+        &self,
+        request: Request<proto::UpsertBatchRequest>,
+    ) -> Result<Response<proto::WriteReply>, Status> {
+        let handler = self.handler_for(&request);
+        let req = request.into_inner();
+        let items = req
+            .items
+            .into_iter()
+            .map(|item| {
+                let point = position(item.position, "position")?;
+                Ok((item.object_id, point, parse_metadata(&item.metadata_json)?))
+            })
+            .collect::<Result<Vec<_>, Status>>()?;
+        let sequence = handler
+            .upsert_batch(
+                context::current(),
+                req.namespace,
+                items,
+                req.idempotency_key,
+            )
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::WriteReply { sequence }))
+    }
+
+    async fn get(
+        &self,
+        request: Request<proto::GetRequest>,
+    ) -> Result<Response<proto::GetReply>, Status> {
+        let req = request.into_inner();
+        let location = self
+            .handler
+            .clone()
+            .get(context::current(), req.namespace, req.object_id)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::GetReply {
+            location: location.map(Into::into),
+        }))
+    }
+
+    async fn delete(
+        &self,
+        request: Request<proto::DeleteRequest>,
+    ) -> Result<Response<proto::WriteReply>, Status> {
+        let req = request.into_inner();
+        let sequence = self
+            .handler
+            .clone()
+            .delete(context::current(), req.namespace, req.object_id)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::WriteReply { sequence }))
+    }
+
+    async fn range(
+        &self,
+        request: Request<proto::RangeRequest>,
+    ) -> Result<Response<proto::LocationsReply>, Status> {
+        let req = request.into_inner();
+        let direction = scan_direction(req.direction());
+        let locations = self
+            .handler
+            .clone()
+            .range(
+                context::current(),
+                req.namespace,
+                id_bound(req.start),
+                id_bound(req.end),
+                limit(req.limit),
+                direction,
+            )
+            .await
+            .map_err(status)?;
+        Ok(locations_reply(locations))
+    }
+
+    async fn list_namespaces(
+        &self,
+        _request: Request<proto::ListNamespacesRequest>,
+    ) -> Result<Response<proto::NamespacesReply>, Status> {
+        let namespaces = self
+            .handler
+            .clone()
+            .list_namespaces(context::current())
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::NamespacesReply { namespaces }))
+    }
+
+    async fn clear_namespace(
+        &self,
+        request: Request<proto::NamespaceRequest>,
+    ) -> Result<Response<proto::CountReply>, Status> {
+        let count = self
+            .handler
+            .clone()
+            .clear_namespace(context::current(), request.into_inner().namespace)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::CountReply { count }))
+    }
+
+    async fn drop_namespace(
+        &self,
+        request: Request<proto::NamespaceRequest>,
+    ) -> Result<Response<proto::CountReply>, Status> {
+        let count = self
+            .handler
+            .clone()
+            .drop_namespace(context::current(), request.into_inner().namespace)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::CountReply { count }))
+    }
+
+    async fn query_radius(
+        &self,
+        request: Request<proto::RadiusQuery>,
+    ) -> Result<Response<proto::HitsReply>, Status> {
+        let req = request.into_inner();
+        let metric = metric(req.metric());
+        let mut query = protocol::RadiusQuery::new(
+            req.namespace,
+            position(req.center, "center")?,
+            req.radius,
+            limit(req.limit),
+        );
+        query.metric = metric;
+        let hits = self
+            .handler
+            .clone()
+            .query_radius(context::current(), query)
+            .await
+            .map_err(status)?;
+        Ok(hits_reply(hits))
+    }
+
+    async fn query_radius_jittered(
+        &self,
+        request: Request<proto::JitteredRadiusQuery>,
+    ) -> Result<Response<proto::HitsReply>, Status> {
+        let req = request.into_inner();
+        let hits = self
+            .handler
+            .clone()
+            .query_radius_jittered(
+                context::current(),
+                req.namespace,
+                position(req.center, "center")?,
+                req.radius,
+                limit(req.limit),
+                req.jitter,
+            )
+            .await
+            .map_err(status)?;
+        Ok(hits_reply(hits))
+    }
+
+    async fn knn(
+        &self,
+        request: Request<proto::KnnQuery>,
+    ) -> Result<Response<proto::HitsReply>, Status> {
+        let req = request.into_inner();
+        let query =
+            protocol::KnnQuery::new(req.namespace, position(req.center, "center")?, limit(req.k));
+        let hits = self
+            .handler
+            .clone()
+            .knn(context::current(), query)
+            .await
+            .map_err(status)?;
+        Ok(hits_reply(hits))
+    }
+
+    async fn knn2d(
+        &self,
+        request: Request<proto::Knn2dQuery>,
+    ) -> Result<Response<proto::HitsReply>, Status> {
+        let req = request.into_inner();
+        let metric = metric(req.metric());
+        let hits = self
+            .handler
+            .clone()
+            .knn_2d(
+                context::current(),
+                req.namespace,
+                point(req.center, "center")?,
+                limit(req.k),
+                req.max_distance,
+                metric,
+            )
+            .await
+            .map_err(status)?;
+        Ok(hits_reply(hits))
+    }
+
+    async fn query_bbox(
+        &self,
+        request: Request<proto::BboxQuery>,
+    ) -> Result<Response<proto::LocationsReply>, Status> {
+        let req = request.into_inner();
+        let locations = self
+            .handler
+            .clone()
+            .query_bbox(
+                context::current(),
+                req.namespace,
+                req.min_x,
+                req.min_y,
+                req.max_x,
+                req.max_y,
+                limit(req.limit),
+            )
+            .await
+            .map_err(status)?;
+        Ok(locations_reply(locations))
+    }
+
+    async fn query_bbox_with_distances(
+        &self,
+        request: Request<proto::BboxDistancesQuery>,
+    ) -> Result<Response<proto::HitsReply>, Status> {
+        let req = request.into_inner();
+        let hits = self
+            .handler
+            .clone()
+            .query_bbox_with_distances(
+                context::current(),
+                req.namespace,
+                req.min_x,
+                req.min_y,
+                req.max_x,
+                req.max_y,
+                req.anchor.map(Into::into),
+                limit(req.limit),
+            )
+            .await
+            .map_err(status)?;
+        Ok(hits_reply(hits))
+    }
+
+    async fn query_bbox_page(
+        &self,
+        request: Request<proto::BboxPageQuery>,
+    ) -> Result<Response<proto::LocationsPage>, Status> {
+        let req = request.into_inner();
+        let page = self
+            .handler
+            .clone()
+            .query_bbox_page(
+                context::current(),
+                req.namespace,
+                req.min_x,
+                req.min_y,
+                req.max_x,
+                req.max_y,
+                limit(req.page_size),
+                req.token,
+            )
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::LocationsPage {
+            items: page.items.into_iter().map(Into::into).collect(),
+            next_token: page.next_token,
+        }))
+    }
+
+    async fn query_radius_page(
+        &self,
+        request: Request<proto::RadiusPageQuery>,
+    ) -> Result<Response<proto::HitsPage>, Status> {
+        let req = request.into_inner();
+        let page = self
+            .handler
+            .clone()
+            .query_radius_page(
+                context::current(),
+                req.namespace,
+                position(req.center, "center")?,
+                req.radius,
+                limit(req.page_size),
+                req.token,
+            )
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::HitsPage {
+            items: page.items.into_iter().map(Into::into).collect(),
+            next_token: page.next_token,
+        }))
+    }
+
+    async fn query_cylinder(
+        &self,
+        request: Request<proto::CylinderQuery>,
+    ) -> Result<Response<proto::HitsReply>, Status> {
+        let req = request.into_inner();
+        let metric = metric(req.metric());
+        let hits = self
+            .handler
+            .clone()
+            .query_cylinder(
+                context::current(),
+                req.namespace,
+                point(req.center, "center")?,
+                req.min_z,
+                req.max_z,
+                req.radius,
+                limit(req.limit),
+                metric,
+            )
+            .await
+            .map_err(status)?;
+        Ok(hits_reply(hits))
+    }
+
+    async fn query_trajectory(
+        &self,
+        request: Request<proto::TrajectorySlice>,
+    ) -> Result<Response<proto::TrajectoryReply>, Status> {
+        let req = request.into_inner();
+        let range = time_range(req.start, req.end)?;
+        let slice =
+            protocol::TrajectorySlice::new(req.namespace, req.object_id, range, limit(req.limit));
+        let updates = self
+            .handler
+            .clone()
+            .query_trajectory(context::current(), slice)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::TrajectoryReply {
+            updates: updates.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn query_trajectory_page(
+        &self,
+        request: Request<proto::TrajectoryPageQuery>,
+    ) -> Result<Response<proto::TrajectoryPage>, Status> {
+        let req = request.into_inner();
+        let page = self
+            .handler
+            .clone()
+            .query_trajectory_page(
+                context::current(),
+                req.namespace,
+                req.object_id,
+                time_range(req.start, req.end)?,
+                limit(req.page_size),
+                req.token,
+            )
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::TrajectoryPage {
+            items: page.items.into_iter().map(Into::into).collect(),
+            next_token: page.next_token,
+        }))
+    }
+
+    async fn history(
+        &self,
+        request: Request<proto::HistoryQuery>,
+    ) -> Result<Response<proto::HistoryReply>, Status> {
+        let req = request.into_inner();
+        let kind = history_kind(req.kind());
+        let entries = self
+            .handler
+            .clone()
+            .history(
+                context::current(),
+                req.namespace,
+                req.object_id,
+                time_range(req.start, req.end)?,
+                kind,
+                limit(req.limit),
+            )
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::HistoryReply {
+            entries: entries.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn trajectory_summary(
+        &self,
+        request: Request<proto::TrajectorySummaryQuery>,
+    ) -> Result<Response<proto::TrajectorySummary>, Status> {
+        let req = request.into_inner();
+        let summary = self
+            .handler
+            .clone()
+            .trajectory_summary(
+                context::current(),
+                req.namespace,
+                req.object_id,
+                time_range(req.start, req.end)?,
+            )
+            .await
+            .map_err(status)?;
+        Ok(Response::new(summary.into()))
+    }
+
+    async fn query_trajectories_intersecting_bbox(
+        &self,
+        request: Request<proto::TrajectoriesInBboxQuery>,
+    ) -> Result<Response<proto::TrajectoryMatchesReply>, Status> {
+        let req = request.into_inner();
+        let matches = self
+            .handler
+            .clone()
+            .query_trajectories_intersecting_bbox(
+                context::current(),
+                req.namespace,
+                req.min_x,
+                req.min_y,
+                req.max_x,
+                req.max_y,
+                time_range(req.start, req.end)?,
+                limit(req.limit),
+            )
+            .await
+            .map_err(status)?;
+        Ok(matches_reply(matches))
+    }
+
+    async fn query_trajectories_within_radius(
+        &self,
+        request: Request<proto::TrajectoriesInRadiusQuery>,
+    ) -> Result<Response<proto::TrajectoryMatchesReply>, Status> {
+        let req = request.into_inner();
+        let matches = self
+            .handler
+            .clone()
+            .query_trajectories_within_radius(
+                context::current(),
+                req.namespace,
+                position(req.center, "center")?,
+                req.radius,
+                time_range(req.start, req.end)?,
+                limit(req.limit),
+            )
+            .await
+            .map_err(status)?;
+        Ok(matches_reply(matches))
+    }
+
+    async fn insert_trajectory(
+        &self,
+        request: Request<proto::InsertTrajectoryRequest>,
+    ) -> Result<Response<proto::WriteReply>, Status> {
+        let handler = self.handler_for(&request);
+        let req = request.into_inner();
+        let trajectory = req
+            .points
+            .into_iter()
+            .map(|update| {
+                let point = position(update.position, "position")?;
+                let metadata = parse_metadata(&update.metadata_json)?;
+                Ok((update.timestamp, point, metadata))
+            })
+            .collect::<Result<Vec<_>, Status>>()?;
+        let sequence = handler
+            .insert_trajectory(
+                context::current(),
+                req.namespace,
+                req.object_id,
+                trajectory,
+                req.idempotency_key,
+            )
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::WriteReply { sequence }))
+    }
+
+    async fn query_bbox3d(
+        &self,
+        request: Request<proto::Bbox3dQuery>,
+    ) -> Result<Response<proto::LocationsReply>, Status> {
+        let req = request.into_inner();
+        let locations = self
+            .handler
+            .clone()
+            .query_bbox_3d(
+                context::current(),
+                req.namespace,
+                req.min_x,
+                req.min_y,
+                req.min_z,
+                req.max_x,
+                req.max_y,
+                req.max_z,
+                limit(req.limit),
+            )
+            .await
+            .map_err(status)?;
+        Ok(locations_reply(locations))
+    }
+
+    async fn query_near(
+        &self,
+        request: Request<proto::NearQuery>,
+    ) -> Result<Response<proto::HitsReply>, Status> {
+        let req = request.into_inner();
+        let hits = self
+            .handler
+            .clone()
+            .query_near(
+                context::current(),
+                req.namespace,
+                req.object_id,
+                req.radius,
+                limit(req.limit),
+            )
+            .await
+            .map_err(status)?;
+        Ok(hits_reply(hits))
+    }
+
+    async fn contains(
+        &self,
+        request: Request<proto::ContainsQuery>,
+    ) -> Result<Response<proto::LocationsReply>, Status> {
+        let req = request.into_inner();
+        let locations = self
+            .handler
+            .clone()
+            .contains(
+                context::current(),
+                req.namespace,
+                polygon(req.polygon)?,
+                limit(req.limit),
+            )
+            .await
+            .map_err(status)?;
+        Ok(locations_reply(locations))
+    }
+
+    async fn contains_with_distances(
+        &self,
+        request: Request<proto::ContainsQuery>,
+    ) -> Result<Response<proto::HitsReply>, Status> {
+        let req = request.into_inner();
+        let hits = self
+            .handler
+            .clone()
+            .contains_with_distances(
+                context::current(),
+                req.namespace,
+                polygon(req.polygon)?,
+                req.anchor.map(Into::into),
+                limit(req.limit),
+            )
+            .await
+            .map_err(status)?;
+        Ok(hits_reply(hits))
+    }
+
+    async fn query(
+        &self,
+        request: Request<proto::PredicateQuery>,
+    ) -> Result<Response<proto::LocationsReply>, Status> {
+        let req = request.into_inner();
+        let predicate = parse_json(&req.predicate_json, "predicate")?;
+        let locations = self
+            .handler
+            .clone()
+            .query(
+                context::current(),
+                req.namespace,
+                predicate,
+                limit(req.limit),
+            )
+            .await
+            .map_err(status)?;
+        Ok(locations_reply(locations))
+    }
+
+    async fn create_view(
+        &self,
+        request: Request<proto::CreateViewRequest>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        let req = request.into_inner();
+        let predicate = parse_json(&req.predicate_json, "predicate")?;
+        self.handler
+            .clone()
+            .create_view(context::current(), req.name, req.namespace, predicate)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn drop_view(
+        &self,
+        request: Request<proto::NameRequest>,
+    ) -> Result<Response<proto::ExistedReply>, Status> {
+        let existed = self
+            .handler
+            .clone()
+            .drop_view(context::current(), request.into_inner().name)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::ExistedReply { existed }))
+    }
+
+    async fn view(
+        &self,
+        request: Request<proto::ViewQuery>,
+    ) -> Result<Response<proto::LocationsReply>, Status> {
+        let req = request.into_inner();
+        let locations = self
+            .handler
+            .clone()
+            .view(context::current(), req.name, limit(req.limit))
+            .await
+            .map_err(status)?;
+        Ok(locations_reply(locations))
+    }
+
+    async fn register_query(
+        &self,
+        request: Request<proto::RegisterQueryRequest>,
+    ) -> Result<Response<proto::Empty>, Status> {
+        let req = request.into_inner();
+        let template = parse_json(&req.template_json, "query template")?;
+        self.handler
+            .clone()
+            .register_query(context::current(), req.name, template)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::Empty {}))
+    }
+
+    async fn unregister_query(
+        &self,
+        request: Request<proto::NameRequest>,
+    ) -> Result<Response<proto::ExistedReply>, Status> {
+        let existed = self
+            .handler
+            .clone()
+            .unregister_query(context::current(), request.into_inner().name)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::ExistedReply { existed }))
+    }
+
+    async fn run_query(
+        &self,
+        request: Request<proto::RunQueryRequest>,
+    ) -> Result<Response<proto::LocationsReply>, Status> {
+        let req = request.into_inner();
+        let args = if req.args_json.is_empty() {
+            QueryArgs::new()
+        } else {
+            parse_json(&req.args_json, "query arguments")?
+        };
+        let locations = self
+            .handler
+            .clone()
+            .run_query(
+                context::current(),
+                req.namespace,
+                req.name,
+                args,
+                limit(req.limit),
+            )
+            .await
+            .map_err(status)?;
+        Ok(locations_reply(locations))
+    }
+
+    async fn distance(
+        &self,
+        request: Request<proto::DistanceQuery>,
+    ) -> Result<Response<proto::DistanceReply>, Status> {
+        let req = request.into_inner();
+        let metric = metric(req.metric());
+        let distance = self
+            .handler
+            .clone()
+            .distance(
+                context::current(),
+                req.namespace,
+                req.object_id,
+                req.other_id,
+                metric,
+            )
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::DistanceReply { distance }))
+    }
+
+    async fn distance_to(
+        &self,
+        request: Request<proto::DistanceToQuery>,
+    ) -> Result<Response<proto::DistanceReply>, Status> {
+        let req = request.into_inner();
+        let metric = metric(req.metric());
+        let distance = self
+            .handler
+            .clone()
+            .distance_to(
+                context::current(),
+                req.namespace,
+                req.object_id,
+                point(req.point, "point")?,
+                metric,
+            )
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::DistanceReply { distance }))
+    }
+
+    async fn convex_hull(
+        &self,
+        request: Request<proto::NamespaceRequest>,
+    ) -> Result<Response<proto::PolygonReply>, Status> {
+        let hull = self
+            .handler
+            .clone()
+            .convex_hull(context::current(), request.into_inner().namespace)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::PolygonReply {
+            polygon: hull.as_ref().map(Into::into),
+        }))
+    }
+
+    async fn bounding_box(
+        &self,
+        request: Request<proto::NamespaceRequest>,
+    ) -> Result<Response<proto::BoundingBoxReply>, Status> {
+        let bbox = self
+            .handler
+            .clone()
+            .bounding_box(context::current(), request.into_inner().namespace)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::BoundingBoxReply {
+            bbox: bbox.as_ref().map(Into::into),
+        }))
+    }
+
+    async fn k_anonymous_cells(
+        &self,
+        request: Request<proto::KAnonymousCellsQuery>,
+    ) -> Result<Response<proto::AggregateCellsReply>, Status> {
+        let req = request.into_inner();
+        let cells = self
+            .handler
+            .clone()
+            .k_anonymous_cells(
+                context::current(),
+                req.namespace,
+                req.cell_size,
+                limit(req.k),
+            )
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::AggregateCellsReply {
+            cells: cells
+                .iter()
+                .map(|cell| proto::AggregateCell {
+                    bounds: Some((&cell.bounds).into()),
+                    count: cell.count as u64,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn subscribe(
+        &self,
+        request: Request<proto::SubscribeRequest>,
+    ) -> Result<Response<proto::SubscriptionReply>, Status> {
+        let req = request.into_inner();
+        let id = self
+            .handler
+            .clone()
+            .subscribe(
+                context::current(),
+                req.namespace,
+                bbox(req.region, "region")?,
+            )
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::SubscriptionReply { id }))
+    }
+
+    async fn poll_subscription(
+        &self,
+        request: Request<proto::PollSubscriptionRequest>,
+    ) -> Result<Response<proto::RegionEventsReply>, Status> {
+        let req = request.into_inner();
+        let events = self
+            .handler
+            .clone()
+            .poll_subscription(
+                context::current(),
+                req.id,
+                limit(req.max_events),
+                req.timeout_ms,
+            )
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::RegionEventsReply {
+            events: events.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn unsubscribe(
+        &self,
+        request: Request<proto::SubscriptionReply>,
+    ) -> Result<Response<proto::ExistedReply>, Status> {
+        let existed = self
+            .handler
+            .clone()
+            .unsubscribe(context::current(), request.into_inner().id)
+            .await;
+        Ok(Response::new(proto::ExistedReply { existed }))
+    }
+
+    async fn stats(
+        &self,
+        _request: Request<proto::StatsRequest>,
+    ) -> Result<Response<proto::StatsReply>, Status> {
+        let stats = self.handler.clone().stats(context::current()).await;
+        Ok(Response::new(proto::StatsReply {
+            object_count: stats.object_count as u64,
+            memory_usage_bytes: stats.memory_usage_bytes as u64,
+            namespace_objects: stats
+                .namespace_objects
+                .into_iter()
+                .map(|(namespace, count)| (namespace, count as u64))
+                .collect(),
+            cold_log_bytes: stats.cold_log_bytes,
+            queries_count: stats.queries_count,
+            updates_per_sec: stats.updates_per_sec,
+            per_minute: stats.per_minute.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn warm_up(
+        &self,
+        request: Request<proto::WarmUpRequest>,
+    ) -> Result<Response<proto::WarmUpReport>, Status> {
+        let req = request.into_inner();
+        let report = self
+            .handler
+            .clone()
+            .warm_up(context::current(), req.recent_secs)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(report.into()))
+    }
+
+    async fn topology(
+        &self,
+        _request: Request<proto::Empty>,
+    ) -> Result<Response<proto::Topology>, Status> {
+        let topology = self.handler.clone().topology(context::current()).await;
+        Ok(Response::new(topology.into()))
+    }
+
+    async fn log_id(
+        &self,
+        _request: Request<proto::Empty>,
+    ) -> Result<Response<proto::LogIdReply>, Status> {
+        let log_id = self.handler.clone().log_id(context::current()).await;
+        Ok(Response::new(proto::LogIdReply { log_id }))
+    }
+
+    async fn wait_applied(
+        &self,
+        request: Request<proto::WaitAppliedRequest>,
+    ) -> Result<Response<proto::WaitAppliedReply>, Status> {
+        let req = request.into_inner();
+        let applied = self
+            .handler
+            .clone()
+            .wait_applied(context::current(), req.log_id, req.sequence)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(proto::WaitAppliedReply { applied }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::proto::spatio_service_server::SpatioService;
+    use super::*;
+
+    fn service() -> GrpcService {
+        let db = Arc::new(Spatio::builder().build().unwrap());
+        let (write_tx, _writer) = crate::writer::spawn_background_writer(db.clone(), 16);
+        GrpcService::new(Handler::new(db, write_tx))
+    }
+
+    fn point(x: f64, y: f64) -> Option<proto::Point3d> {
+        Some(proto::Point3d { x, y, z: 0.0 })
+    }
+
+    #[tokio::test]
+    async fn test_grpc_service() {
+        let service = service();
+        for (id, lon) in [("near", 0.001), ("far", 0.1)] {
+            let upsert = proto::UpsertRequest {
+                namespace: "fleet".into(),
+                object_id: id.into(),
+                position: point(lon, 0.0),
+                metadata_json: format!(r#"{{"kind":"{id}"}}"#),
+                idempotency_key: None,
+            };
+            service.upsert(Request::new(upsert)).await.unwrap();
+        }
+
+        let get = proto::GetRequest {
+            namespace: "fleet".into(),
+            object_id: "near".into(),
+        };
+        let location = service
+            .get(Request::new(get))
+            .await
+            .unwrap()
+            .into_inner()
+            .location
+            .unwrap();
+        assert_eq!(location.metadata_json, r#"{"kind":"near"}"#);
+
+        let radius = proto::RadiusQuery {
+            namespace: "fleet".into(),
+            center: point(0.0, 0.0),
+            radius: 1000.0,
+            limit: 10,
+            metric: proto::DistanceMetric::Haversine.into(),
+        };
+        let hits = service
+            .query_radius(Request::new(radius))
+            .await
+            .unwrap()
+            .into_inner()
+            .hits;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].object_id, "near");
+
+        let knn = proto::KnnQuery {
+            namespace: "fleet".into(),
+            center: point(0.2, 0.0),
+            k: 1,
+        };
+        let hits = service
+            .knn(Request::new(knn))
+            .await
+            .unwrap()
+            .into_inner()
+            .hits;
+        assert_eq!(hits[0].object_id, "far");
+
+        let bbox = proto::BboxQuery {
+            namespace: "fleet".into(),
+            min_x: 0.05,
+            min_y: -1.0,
+            max_x: 1.0,
+            max_y: 1.0,
+            limit: 10,
+        };
+        let locations = service
+            .query_bbox(Request::new(bbox))
+            .await
+            .unwrap()
+            .into_inner()
+            .locations;
+        assert_eq!(locations.len(), 1);
+
+        let slice = proto::TrajectorySlice {
+            namespace: "fleet".into(),
+            object_id: "near".into(),
+            start: Some(proto::TimeBound {
+                bound: Some(proto::time_bound::Bound::Ago(3600.0)),
+            }),
+            end: None,
+            limit: 10,
+        };
+        let updates = service
+            .query_trajectory(Request::new(slice))
+            .await
+            .unwrap()
+            .into_inner()
+            .updates;
+        assert_eq!(updates.len(), 1);
+
+        let stats = service
+            .stats(Request::new(proto::StatsRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(stats.object_count, 2);
+        assert_eq!(stats.namespace_objects.get("fleet"), Some(&2));
+        assert!(stats.queries_count > 0);
+
+        let bad = proto::RadiusQuery {
+            namespace: "fleet".into(),
+            center: point(0.0, 100.0),
+            radius: 1000.0,
+            limit: 10,
+            metric: 0,
+        };
+        let error = service.query_radius(Request::new(bad)).await.unwrap_err();
+        assert_eq!(error.code(), tonic::Code::InvalidArgument);
+
+        let missing = proto::UpsertRequest {
+            namespace: "fleet".into(),
+            object_id: "x".into(),
+            position: None,
+            metadata_json: String::new(),
+            idempotency_key: None,
+        };
+        let error = service.upsert(Request::new(missing)).await.unwrap_err();
+        assert_eq!(error.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_grpc_batch_range_and_predicates() {
+        let service = service();
+        let items = ["a", "b", "c"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, id)| proto::BatchItem {
+                object_id: id.into(),
+                position: Some(proto::Point3d {
+                    x: i as f64,
+                    y: 0.0,
+                    z: i as f64 * 100.0,
+                }),
+                metadata_json: String::new(),
+            })
+            .collect();
+        let batch = proto::UpsertBatchRequest {
+            namespace: "fleet".into(),
+            items,
+            idempotency_key: None,
+        };
+        let sequence = service
+            .upsert_batch(Request::new(batch))
+            .await
+            .unwrap()
+            .into_inner()
+            .sequence;
+
+        let range = proto::RangeRequest {
+            namespace: "fleet".into(),
+            start: Some(proto::IdBound {
+                bound: Some(proto::id_bound::Bound::Excluded("a".into())),
+            }),
+            end: None,
+            limit: 10,
+            direction: proto::ScanDirection::Reverse.into(),
+        };
+        let ids: Vec<_> = service
+            .range(Request::new(range))
+            .await
+            .unwrap()
+            .into_inner()
+            .locations
+            .into_iter()
+            .map(|location| location.object_id)
+            .collect();
+        assert_eq!(ids, ["c", "b"]);
+
+        let query = proto::PredicateQuery {
+            namespace: "fleet".into(),
+            predicate_json: r#"{"altitude":{"min":null,"max":150.0}}"#.into(),
+            limit: 10,
+        };
+        let locations = service
+            .query(Request::new(query))
+            .await
+            .unwrap()
+            .into_inner()
+            .locations;
+        assert_eq!(locations.len(), 2);
+        let bad = proto::PredicateQuery {
+            namespace: "fleet".into(),
+            predicate_json: "{".into(),
+            limit: 10,
+        };
+        let error = service.query(Request::new(bad)).await.unwrap_err();
+        assert_eq!(error.code(), tonic::Code::InvalidArgument);
+
+        let log_id = service
+            .log_id(Request::new(proto::Empty {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .log_id;
+        let wait = proto::WaitAppliedRequest { log_id, sequence };
+        let applied = service
+            .wait_applied(Request::new(wait))
+            .await
+            .unwrap()
+            .into_inner()
+            .applied;
+        assert!(applied >= sequence);
+    }
+
+    /// Method names of a service definition in `source`, found on lines
+    /// starting with `keyword`, lowercased and without underscores so proto
+    /// and Rust spellings compare equal.
+    fn method_names(source: &str, keyword: &str) -> std::collections::BTreeSet<String> {
+        source
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix(keyword))
+            .filter_map(|rest| rest.split_once('('))
+            .map(|(name, _)| name.replace('_', "").to_lowercase())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_grpc_privacy_queries() {
+        let service = service();
+        for (id, lon) in [("a", 0.1), ("b", 0.2)] {
+            let upsert = proto::UpsertRequest {
+                namespace: "fleet".into(),
+                object_id: id.into(),
+                position: point(lon, 0.5),
+                metadata_json: "{}".into(),
+                idempotency_key: None,
+            };
+            service.upsert(Request::new(upsert)).await.unwrap();
+        }
+
+        let cells = proto::KAnonymousCellsQuery {
+            namespace: "fleet".into(),
+            cell_size: 1.0,
+            k: 2,
+        };
+        let cells = service
+            .k_anonymous_cells(Request::new(cells))
+            .await
+            .unwrap()
+            .into_inner()
+            .cells;
+        assert_eq!(cells.len(), 1);
+        assert_eq!(cells[0].count, 2);
+        assert_eq!(cells[0].bounds.as_ref().unwrap().max_x, 1.0);
+
+        // The service has no jitter key.
+        let jittered = proto::JitteredRadiusQuery {
+            namespace: "fleet".into(),
+            center: point(0.1, 0.5),
+            radius: 50_000.0,
+            limit: 10,
+            jitter: 100.0,
+        };
+        let error = service
+            .query_radius_jittered(Request::new(jittered))
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn test_proto_matches_the_rpc_service() {
+        let protocol = include_str!("../protocol.rs");
+        let service = &protocol[protocol.find("pub trait SpatioService").unwrap()..];
+        let service = &service[..service.find("\n}").unwrap()];
+        let rpc = method_names(service, "async fn ");
+        let grpc = method_names(include_str!("../../proto/spatio.proto"), "rpc ");
+        assert!(rpc.len() > 40);
+        assert_eq!(
+            rpc.symmetric_difference(&grpc).collect::<Vec<_>>(),
+            Vec::<&String>::new(),
+            "proto/spatio.proto and the tarpc SpatioService differ"
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! Per-connection panic isolation.
+//!
+//! A panic while serving a request (say, a query path tripping over an
+//! unexpected NaN) must not take the process down with it. [`PanicGuard`]
+//! catches the unwind, logs it with the offending command, answers the request
+//! with an error, and poisons the connection so it is closed: state touched by
+//! the panicking request is suspect, but other connections carry on.
+
+use futures::FutureExt;
+use std::fmt::{self, Write};
+use std::net::SocketAddr;
+use std::panic::AssertUnwindSafe;
+use tarpc::ServerError;
+use tarpc::context;
+use tarpc::server::Serve;
+use tokio_util::sync::CancellationToken;
+use tracing::error;
+
+/// Longest command description included in a panic log line; large
+/// trajectories would otherwise flood the log.
+const MAX_COMMAND_LOG_BYTES: usize = 512;
+
+/// Wraps a connection's service, converting panics into errors and
+/// cancelling `poisoned` so the connection loop can shut the connection.
+#[derive(Clone)]
+pub(crate) struct PanicGuard<S> {
+    inner: S,
+    peer: Option<SocketAddr>,
+    poisoned: CancellationToken,
+}
+
+impl<S> PanicGuard<S> {
+    pub(crate) fn new(inner: S, peer: Option<SocketAddr>, poisoned: CancellationToken) -> Self {
+        Self {
+            inner,
+            peer,
+            poisoned,
+        }
+    }
+}
+
+impl<S> Serve for PanicGuard<S>
+where
+    S: Serve,
+    S::Req: fmt::Debug,
+{
+    type Req = S::Req;
+    type Resp = S::Resp;
+
+    fn method(&self, request: &Self::Req) -> Option<&'static str> {
+        self.inner.method(request)
+    }
+
+    async fn serve(self, ctx: context::Context, req: Self::Req) -> Result<Self::Resp, ServerError> {
+        // Described up front: the request is moved into the handler.
+        let command = describe(&req);
+        match AssertUnwindSafe(self.inner.serve(ctx, req))
+            .catch_unwind()
+            .await
+        {
+            Ok(response) => response,
+            Err(panic) => {
+                let message = panic_message(&*panic);
+                error!(
+                    peer = ?self.peer,
+                    command = %command,
+                    "Request panicked, closing connection: {message}"
+                );
+                self.poisoned.cancel();
+                Err(ServerError::new(
+                    std::io::ErrorKind::Other,
+                    "Internal error: request handler panicked".to_string(),
+                ))
+            }
+        }
+    }
+}
+
+/// Debug rendering of `value`, cut off at [`MAX_COMMAND_LOG_BYTES`] without
+/// formatting the rest.
+fn describe(value: &impl fmt::Debug) -> String {
+    struct Bounded(String);
+
+    impl Write for Bounded {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let room = MAX_COMMAND_LOG_BYTES - self.0.len();
+            if s.len() <= room {
+                self.0.push_str(s);
+                return Ok(());
+            }
+            let mut end = room;
+            while !s.is_char_boundary(end) {
+                end -= 1;
+            }
+            self.0.push_str(&s[..end]);
+            // Stop the formatter; the output is complete enough.
+            Err(fmt::Error)
+        }
+    }
+
+    let mut out = Bounded(String::new());
+    if write!(out, "{value:?}").is_err() {
+        out.0.push_str("...");
+    }
+    out.0
+}
+
+/// Message carried by a panic payload.
+pub(crate) fn panic_message(panic: &(dyn std::any::Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_panic_is_answered_and_poisons_connection() {
+        let poisoned = CancellationToken::new();
+        let guard = PanicGuard::new(
+            tarpc::server::serve(|_, divisor: u32| async move { Ok(100 / divisor) }),
+            None,
+            poisoned.clone(),
+        );
+
+        let ok = guard.clone().serve(context::current(), 4).await;
+        assert_eq!(ok.unwrap(), 25);
+        assert!(!poisoned.is_cancelled());
+
+        let err = guard.serve(context::current(), 0).await.unwrap_err();
+        assert!(err.detail.contains("panicked"));
+        assert!(poisoned.is_cancelled());
+    }
+
+    #[test]
+    fn test_describe_truncates_large_commands() {
+        assert_eq!(describe(&("ns", 1)), r#"("ns", 1)"#);
+
+        let huge = vec![1.5f64; 10_000];
+        let described = describe(&huge);
+        assert!(described.len() <= MAX_COMMAND_LOG_BYTES + 3);
+        assert!(described.starts_with("[1.5, 1.5"));
+        assert!(described.ends_with("..."));
+    }
+}
@@ -0,0 +1,764 @@
+//! HTTP/REST transport for the Spatio server, answering in GeoJSON.
+//!
+//! Meant for browser dashboards and other clients without a tarpc stack.
+//! Requests go through the same [`Handler`] as RPC requests, so query
+//! admission, limits and idempotency behave the same; [`crate::middleware`]
+//! layers are RPC-only. Routes:
+//!
+//! | Method   | Path                                      | Response                  |
+//! |----------|-------------------------------------------|---------------------------|
+//! | `GET`    | `/namespaces/{ns}/objects`                | `FeatureCollection`       |
+//! | `GET`    | `/namespaces/{ns}/objects/{id}`           | `Feature`, or 404         |
+//! | `PUT`    | `/namespaces/{ns}/objects/{id}`           | `{"sequence", "log_id"}`  |
+//! | `DELETE` | `/namespaces/{ns}/objects/{id}`           | `{"sequence", "log_id"}`  |
+//! | `GET`    | `/namespaces/{ns}/query/radius`           | `FeatureCollection`       |
+//! | `GET`    | `/namespaces/{ns}/query/bbox`             | `FeatureCollection`       |
+//! | `GET`    | `/namespaces/{ns}/trajectory/{id}`        | `LineString` `Feature`    |
+//! | `GET`    | `/namespaces/{ns}/cells`                  | `FeatureCollection`       |
+//!
+//! Objects are `Point` features whose `id` is the object ID and whose
+//! coordinates are `[lon, lat, altitude]`. Their properties hold the object's
+//! `metadata`, and for radius queries also its `distance` from the center.
+//! `PUT` takes such a feature (or a bare `Point` geometry) and honors an
+//! `Idempotency-Key` header.
+//!
+//! For public dashboards, a radius query with `jitter` moves each hit by up
+//! to that many meters under the server's jitter key, and `cells` counts the
+//! objects in a `cell_size`-degree grid, returning as `Polygon` features only
+//! the cells holding at least `k` of them.
+//!
+//! A write's `sequence` numbers it in the server's log, whose ID (hex) is
+//! `log_id`. Any request may carry a `Spatio-Min-Sequence: {log_id}:{sequence}`
+//! header naming an earlier write; the server then answers only once it has
+//! applied that write. Spatio doesn't copy writes between servers, so a
+//! server with another log refuses the header with a 400.
+//!
+//! Errors come back as `{"error": "..."}`: 400 for rejected input, 403 when
+//! the [`Authenticator`] refuses the client, 503 when the server is
+//! overloaded or hasn't caught up with a `Spatio-Min-Sequence`, and 500
+//! otherwise. Writes refused while the database rewrites its log get a 503
+//! with a `Retry-After` header.
+
+use axum::extract::{ConnectInfo, Path, Query, Request, State};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use spatio::Spatio;
+use spatio_types::config::ScanDirection;
+use spatio_types::geo::DistanceMetric;
+use spatio_types::point::Point3d;
+use spatio_types::time::TimeRange;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::ops::Bound;
+use std::sync::Arc;
+use tarpc::context;
+use tracing::{info, warn};
+
+use crate::auth::{Authenticator, ConnectionInfo, Principal};
+use crate::handler::Handler;
+use crate::protocol::{
+    CurrentLocation, QueryHit, RadiusQuery, SpatioService, TrajectorySlice, retry_after,
+};
+use crate::transport::rpc::ServerOptions;
+use crate::transport::{ErrorKind, classify_error, join_writer};
+
+/// Results returned when a request doesn't set `limit`.
+const DEFAULT_LIMIT: usize = 1_000;
+
+/// Request header naming a write, as `{log_id}:{sequence}` from its
+/// response, that the server must have applied before answering.
+pub const MIN_SEQUENCE_HEADER: &str = "spatio-min-sequence";
+
+const GEOJSON: HeaderValue = HeaderValue::from_static("application/geo+json");
+
+/// Run the HTTP server until `shutdown` resolves, configured by `options`.
+///
+/// The server has its own writer. Its query scheduler and authenticator are
+/// those of `options`, so servers started with clones of the same options
+/// (as [`crate::SpatioServer`] does) admit queries against one set of limits
+/// and let in the same clients.
+pub async fn run_http_server(
+    listener: tokio::net::TcpListener,
+    db: Arc<Spatio>,
+    options: ServerOptions,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> anyhow::Result<()> {
+    let (write_tx, writer_handle) = crate::writer::spawn_background_writer(db.clone(), 10_000);
+    let handler = options.handler(db, write_tx);
+    let mut app = router(handler);
+    if let Some(auth) = options.auth {
+        app = app.layer(middleware::from_fn_with_state(auth, authenticate));
+    }
+
+    info!("Spatio HTTP Server listening on {}", listener.local_addr()?);
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown)
+    .await?;
+
+    join_writer(writer_handle).await;
+    Ok(())
+}
+
+/// The HTTP routes, for mounting into an existing axum application.
+pub fn router(handler: Handler) -> Router {
+    Router::new()
+        .route("/namespaces/:ns/objects", get(list_objects))
+        .route(
+            "/namespaces/:ns/objects/:id",
+            get(get_object).put(put_object).delete(delete_object),
+        )
+        .route("/namespaces/:ns/query/radius", get(query_radius))
+        .route("/namespaces/:ns/query/bbox", get(query_bbox))
+        .route("/namespaces/:ns/trajectory/:id", get(trajectory))
+        .route("/namespaces/:ns/cells", get(k_anonymous_cells))
+        .route_layer(middleware::from_fn_with_state(
+            handler.clone(),
+            wait_for_sequence,
+        ))
+        .with_state(handler)
+}
+
+/// Hold a request carrying a [`MIN_SEQUENCE_HEADER`] until this server has
+/// applied that write, refusing it with a 503 if it doesn't in time.
+async fn wait_for_sequence(
+    State(handler): State<Handler>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(value) = request.headers().get(MIN_SEQUENCE_HEADER) else {
+        return next.run(request).await;
+    };
+    let parsed = value.to_str().ok().and_then(|v| {
+        let (log_id, sequence) = v.trim().split_once(':')?;
+        Some((
+            u64::from_str_radix(log_id, 16).ok()?,
+            sequence.parse().ok()?,
+        ))
+    });
+    let Some((log_id, sequence)) = parsed else {
+        return ApiError::bad_request(format!(
+            "{MIN_SEQUENCE_HEADER} must be a log ID and a sequence, as `log_id:sequence`"
+        ))
+        .into_response();
+    };
+    match handler
+        .wait_applied(context::current(), log_id, sequence)
+        .await
+    {
+        Ok(_) => next.run(request).await,
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+/// Refuse requests from clients `auth` rejects, and tag the others with
+/// their principal. HTTP has no connection hook, so this runs per request,
+/// with the peer address only.
+async fn authenticate(
+    State(auth): State<Arc<dyn Authenticator>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let conn = ConnectionInfo {
+        peer,
+        client_certificates: Vec::new(),
+    };
+    match auth.authenticate(&conn) {
+        Ok(()) => {
+            if let Some(principal) = auth.principal(&conn) {
+                request.extensions_mut().insert(Principal(principal.into()));
+            }
+            next.run(request).await
+        }
+        Err(reason) => {
+            warn!("Refused HTTP request from {peer}: {reason}");
+            ApiError::new(StatusCode::FORBIDDEN, reason).into_response()
+        }
+    }
+}
+
+/// A handler error with the status it maps to.
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+}
+
+impl From<String> for ApiError {
+    fn from(message: String) -> Self {
+        let status = match classify_error(&message) {
+            ErrorKind::Rejected => StatusCode::BAD_REQUEST,
+            ErrorKind::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorKind::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self::new(status, message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let retry_after = retry_after(&self.message);
+        let mut response = (self.status, Json(json!({ "error": self.message }))).into_response();
+        if let Some(retry_after) = retry_after {
+            // Whole seconds, rounded up.
+            let secs = retry_after.as_millis().div_ceil(1000);
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs as u64));
+        }
+        response
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+/// A GeoJSON body, served as `application/geo+json`.
+struct GeoJson(Value);
+
+impl IntoResponse for GeoJson {
+    fn into_response(self) -> Response {
+        let mut response = Json(self.0).into_response();
+        response.headers_mut().insert(header::CONTENT_TYPE, GEOJSON);
+        response
+    }
+}
+
+fn decode_metadata(metadata: &[u8]) -> Value {
+    serde_json::from_slice(metadata).unwrap_or(Value::Null)
+}
+
+fn coordinates(position: &Point3d) -> Value {
+    json!([position.x(), position.y(), position.z()])
+}
+
+fn point_feature(object_id: &str, position: &Point3d, properties: Value) -> Value {
+    json!({
+        "type": "Feature",
+        "id": object_id,
+        "geometry": { "type": "Point", "coordinates": coordinates(position) },
+        "properties": properties,
+    })
+}
+
+fn location_feature(location: &CurrentLocation) -> Value {
+    point_feature(
+        &location.object_id,
+        &location.position,
+        json!({ "metadata": decode_metadata(&location.metadata) }),
+    )
+}
+
+fn hit_feature(hit: &QueryHit) -> Value {
+    point_feature(
+        &hit.object_id,
+        &hit.position,
+        json!({ "metadata": decode_metadata(&hit.metadata), "distance": hit.distance }),
+    )
+}
+
+fn feature_collection(features: Vec<Value>) -> GeoJson {
+    GeoJson(json!({ "type": "FeatureCollection", "features": features }))
+}
+
+#[derive(Deserialize)]
+struct ListParams {
+    /// Only objects whose IDs sort after this one, for paging by the last ID
+    /// seen.
+    after: Option<String>,
+    limit: Option<usize>,
+}
+
+async fn list_objects(
+    State(handler): State<Handler>,
+    Path(ns): Path<String>,
+    Query(params): Query<ListParams>,
+) -> ApiResult<GeoJson> {
+    let start = params.after.map_or(Bound::Unbounded, Bound::Excluded);
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+    let locations = handler
+        .range(
+            context::current(),
+            ns,
+            start,
+            Bound::Unbounded,
+            limit,
+            ScanDirection::Forward,
+        )
+        .await?;
+    Ok(feature_collection(
+        locations.iter().map(location_feature).collect(),
+    ))
+}
+
+async fn get_object(
+    State(handler): State<Handler>,
+    Path((ns, id)): Path<(String, String)>,
+) -> ApiResult<GeoJson> {
+    match handler.get(context::current(), ns, id.clone()).await? {
+        Some(location) => Ok(GeoJson(location_feature(&location))),
+        None => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            format!("No object {id:?}"),
+        )),
+    }
+}
+
+/// Parse a `PUT` body: a `Point` feature, whose properties become the
+/// metadata, or a bare `Point` geometry.
+fn parse_point_feature(body: &Value) -> ApiResult<(Point3d, Value)> {
+    let (geometry, metadata) = match body["type"].as_str() {
+        Some("Feature") => (&body["geometry"], body["properties"].clone()),
+        Some("Point") => (body, Value::Null),
+        _ => return Err(ApiError::bad_request("expected a Feature or Point")),
+    };
+    if geometry["type"] != "Point" {
+        return Err(ApiError::bad_request("geometry must be a Point"));
+    }
+    let coords: Vec<f64> = serde_json::from_value(geometry["coordinates"].clone())
+        .map_err(|e| ApiError::bad_request(format!("invalid coordinates: {e}")))?;
+    let point = match coords.as_slice() {
+        [x, y] => Point3d::new(*x, *y, 0.0),
+        [x, y, z] => Point3d::new(*x, *y, *z),
+        _ => return Err(ApiError::bad_request("a Point needs 2 or 3 coordinates")),
+    };
+    let metadata = if metadata.is_null() {
+        json!({})
+    } else {
+        metadata
+    };
+    Ok((point, metadata))
+}
+
+async fn put_object(
+    State(handler): State<Handler>,
+    principal: Option<Extension<Principal>>,
+    Path((ns, id)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> ApiResult<Json<Value>> {
+    let (point, metadata) = parse_point_feature(&body)?;
+    let idempotency_key = match headers.get("idempotency-key") {
+        Some(key) => Some(
+            key.to_str()
+                .map_err(|_| ApiError::bad_request("Idempotency-Key must be ASCII"))?
+                .to_string(),
+        ),
+        None => None,
+    };
+    let handler = match principal {
+        Some(Extension(Principal(principal))) => handler.for_client(principal),
+        None => handler,
+    };
+    let log_id = handler.clone().log_id(context::current()).await;
+    let sequence = handler
+        .upsert(context::current(), ns, id, point, metadata, idempotency_key)
+        .await?;
+    Ok(Json(written(log_id, sequence)))
+}
+
+async fn delete_object(
+    State(handler): State<Handler>,
+    Path((ns, id)): Path<(String, String)>,
+) -> ApiResult<Json<Value>> {
+    let log_id = handler.clone().log_id(context::current()).await;
+    let sequence = handler.delete(context::current(), ns, id).await?;
+    Ok(Json(written(log_id, sequence)))
+}
+
+/// Response to a write: its sequence, and the log it numbers (in hex, as
+/// JSON numbers can't hold every `u64`).
+fn written(log_id: u64, sequence: u64) -> Value {
+    json!({ "sequence": sequence, "log_id": format!("{log_id:016x}") })
+}
+
+#[derive(Deserialize)]
+struct RadiusParams {
+    lon: f64,
+    lat: f64,
+    #[serde(default)]
+    alt: f64,
+    radius: f64,
+    limit: Option<usize>,
+    metric: Option<DistanceMetric>,
+    /// Move each hit by up to this many meters; measured in the namespace's
+    /// metric, so `metric` can't be given with it.
+    jitter: Option<f64>,
+}
+
+async fn query_radius(
+    State(handler): State<Handler>,
+    Path(ns): Path<String>,
+    Query(params): Query<RadiusParams>,
+) -> ApiResult<GeoJson> {
+    let center = Point3d::new(params.lon, params.lat, params.alt);
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+    let hits = match (params.jitter, params.metric) {
+        (Some(_), Some(_)) => {
+            return Err(ApiError::bad_request(
+                "metric can't be combined with jitter",
+            ));
+        }
+        (Some(jitter), None) => {
+            handler
+                .query_radius_jittered(context::current(), ns, center, params.radius, limit, jitter)
+                .await?
+        }
+        (None, metric) => {
+            let mut query = RadiusQuery::new(ns, center, params.radius, limit);
+            query.metric = metric;
+            handler.query_radius(context::current(), query).await?
+        }
+    };
+    Ok(feature_collection(hits.iter().map(hit_feature).collect()))
+}
+
+#[derive(Deserialize)]
+struct CellsParams {
+    cell_size: f64,
+    k: usize,
+}
+
+async fn k_anonymous_cells(
+    State(handler): State<Handler>,
+    Path(ns): Path<String>,
+    Query(params): Query<CellsParams>,
+) -> ApiResult<GeoJson> {
+    let cells = handler
+        .k_anonymous_cells(context::current(), ns, params.cell_size, params.k)
+        .await?;
+    Ok(feature_collection(
+        cells
+            .iter()
+            .map(|cell| {
+                let b = &cell.bounds;
+                let ring = [
+                    [b.min_x(), b.min_y()],
+                    [b.max_x(), b.min_y()],
+                    [b.max_x(), b.max_y()],
+                    [b.min_x(), b.max_y()],
+                    [b.min_x(), b.min_y()],
+                ];
+                json!({
+                    "type": "Feature",
+                    "geometry": { "type": "Polygon", "coordinates": [ring] },
+                    "properties": { "count": cell.count },
+                })
+            })
+            .collect(),
+    ))
+}
+
+#[derive(Deserialize)]
+struct BboxParams {
+    min_x: f64,
+    min_y: f64,
+    max_x: f64,
+    max_y: f64,
+    limit: Option<usize>,
+}
+
+async fn query_bbox(
+    State(handler): State<Handler>,
+    Path(ns): Path<String>,
+    Query(params): Query<BboxParams>,
+) -> ApiResult<GeoJson> {
+    let locations = handler
+        .query_bbox(
+            context::current(),
+            ns,
+            params.min_x,
+            params.min_y,
+            params.max_x,
+            params.max_y,
+            params.limit.unwrap_or(DEFAULT_LIMIT),
+        )
+        .await?;
+    Ok(feature_collection(
+        locations.iter().map(location_feature).collect(),
+    ))
+}
+
+#[derive(Deserialize)]
+struct TrajectoryParams {
+    /// Seconds since the Unix epoch.
+    start: Option<f64>,
+    end: Option<f64>,
+    /// Seconds before now, instead of `start`.
+    last: Option<f64>,
+    limit: Option<usize>,
+}
+
+/// The newest `limit` points of the trajectory as one `LineString` feature,
+/// oldest first, with each point's timestamp (seconds since the Unix epoch)
+/// and metadata in the `timestamps` and `metadata` properties.
+async fn trajectory(
+    State(handler): State<Handler>,
+    Path((ns, id)): Path<(String, String)>,
+    Query(params): Query<TrajectoryParams>,
+) -> ApiResult<GeoJson> {
+    let range =
+        TimeRange::from_secs(params.start, params.end, params.last).map_err(ApiError::from)?;
+    let slice = TrajectorySlice::new(ns, id.clone(), range, params.limit.unwrap_or(DEFAULT_LIMIT));
+    let mut updates = handler.query_trajectory(context::current(), slice).await?;
+    updates.reverse();
+    Ok(GeoJson(json!({
+        "type": "Feature",
+        "id": id,
+        "geometry": {
+            "type": "LineString",
+            "coordinates": updates.iter().map(|u| coordinates(&u.position)).collect::<Vec<_>>(),
+        },
+        "properties": {
+            "timestamps": updates.iter().map(|u| u.timestamp).collect::<Vec<_>>(),
+            "metadata": updates.iter().map(|u| decode_metadata(&u.metadata)).collect::<Vec<_>>(),
+        },
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        let db = Arc::new(Spatio::builder().build().unwrap());
+        let (write_tx, _writer) = crate::writer::spawn_background_writer(db.clone(), 16);
+        router(Handler::new(db, write_tx))
+    }
+
+    async fn send(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let request = axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json");
+        let body = body.map_or_else(Body::empty, |b| Body::from(b.to_string()));
+        let response = app
+            .clone()
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_geojson_routes() {
+        let app = app();
+        for (id, lon) in [("near", 0.001), ("far", 0.1)] {
+            let feature = json!({
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": [lon, 0.0] },
+                "properties": { "kind": id },
+            });
+            let (status, body) = send(
+                &app,
+                "PUT",
+                &format!("/namespaces/fleet/objects/{id}"),
+                Some(feature),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK, "{body}");
+        }
+
+        let (status, feature) = send(&app, "GET", "/namespaces/fleet/objects/near", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(feature["geometry"]["coordinates"], json!([0.001, 0.0, 0.0]));
+        assert_eq!(feature["properties"]["metadata"]["kind"], "near");
+
+        let (_, all) = send(&app, "GET", "/namespaces/fleet/objects", None).await;
+        assert_eq!(all["type"], "FeatureCollection");
+        assert_eq!(all["features"].as_array().unwrap().len(), 2);
+
+        let uri = "/namespaces/fleet/query/radius?lon=0&lat=0&radius=1000";
+        let (_, nearby) = send(&app, "GET", uri, None).await;
+        let features = nearby["features"].as_array().unwrap();
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0]["id"], "near");
+        assert!(features[0]["properties"]["distance"].as_f64().unwrap() < 200.0);
+
+        let uri = "/namespaces/fleet/query/bbox?min_x=0.05&min_y=-1&max_x=1&max_y=1";
+        let (_, boxed) = send(&app, "GET", uri, None).await;
+        assert_eq!(boxed["features"][0]["id"], "far");
+
+        let (_, track) = send(&app, "GET", "/namespaces/fleet/trajectory/near", None).await;
+        assert_eq!(track["geometry"]["type"], "LineString");
+        assert_eq!(
+            track["properties"]["timestamps"].as_array().unwrap().len(),
+            1
+        );
+
+        let (status, _) = send(&app, "DELETE", "/namespaces/fleet/objects/near", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&app, "GET", "/namespaces/fleet/objects/near", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let line = json!({ "type": "LineString", "coordinates": [[0.0, 0.0], [1.0, 1.0]] });
+        let (status, error) = send(&app, "PUT", "/namespaces/fleet/objects/x", Some(line)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(error["error"].is_string());
+
+        let uri = "/namespaces/fleet/query/radius?lon=0&lat=100&radius=1000";
+        let (status, _) = send(&app, "GET", uri, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_reads_wait_for_min_sequence() {
+        let db = Arc::new(Spatio::builder().build().unwrap());
+        let (write_tx, _writer) = crate::writer::spawn_background_writer(db.clone(), 16);
+        let app = router(Handler::new(db, write_tx).with_applied_wait(Duration::from_millis(10)));
+        let point = json!({ "type": "Point", "coordinates": [1.0, 2.0] });
+        let (_, written) = send(&app, "PUT", "/namespaces/fleet/objects/van", Some(point)).await;
+        let sequence = written["sequence"].as_u64().unwrap();
+        let log_id = written["log_id"].as_str().unwrap().to_string();
+
+        let read = |min_sequence: String| {
+            let request = axum::http::Request::get("/namespaces/fleet/objects/van")
+                .header(MIN_SEQUENCE_HEADER, min_sequence)
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+        assert_eq!(
+            read(format!("{log_id}:{sequence}")).await.unwrap().status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            read(format!("{log_id}:{}", sequence + 1))
+                .await
+                .unwrap()
+                .status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        // Another log's sequence, or none, can't be waited for.
+        for header in [format!("{:016x}:{sequence}", !0u64), sequence.to_string()] {
+            assert_eq!(
+                read(header).await.unwrap().status(),
+                StatusCode::BAD_REQUEST
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_privacy_routes() {
+        let db = Arc::new(Spatio::builder().build().unwrap());
+        let (write_tx, _writer) = crate::writer::spawn_background_writer(db.clone(), 16);
+        let keyless = router(Handler::new(db.clone(), write_tx.clone()));
+        let app = router(Handler::new(db, write_tx).with_jitter_key(7));
+        for (id, lon) in [("a", 0.1), ("b", 0.2), ("c", 0.3), ("loner", 5.5)] {
+            let point = json!({ "type": "Point", "coordinates": [lon, 0.5] });
+            let uri = format!("/namespaces/fleet/objects/{id}");
+            send(&app, "PUT", &uri, Some(point)).await;
+        }
+
+        let uri = "/namespaces/fleet/query/radius?lon=0.2&lat=0.5&radius=50000&jitter=300";
+        let (status, _) = send(&keyless, "GET", uri, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, jittered) = send(&app, "GET", uri, None).await;
+        assert_eq!(status, StatusCode::OK, "{jittered}");
+        let features = jittered["features"].as_array().unwrap();
+        assert_eq!(features.len(), 3);
+        for feature in features {
+            assert_ne!(feature["geometry"]["coordinates"][1], 0.5);
+        }
+        let (status, _) = send(&app, "GET", &format!("{uri}&metric=euclidean"), None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, cells) =
+            send(&app, "GET", "/namespaces/fleet/cells?cell_size=1&k=3", None).await;
+        assert_eq!(status, StatusCode::OK, "{cells}");
+        let cells = cells["features"].as_array().unwrap();
+        assert_eq!(cells.len(), 1);
+        assert_eq!(cells[0]["properties"]["count"], 3);
+        assert_eq!(cells[0]["geometry"]["coordinates"][0][0], json!([0.0, 0.0]));
+        let (status, _) = send(&app, "GET", "/namespaces/fleet/cells?cell_size=1&k=0", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_retries_from_another_address_are_deduplicated() {
+        struct Tracker;
+        impl Authenticator for Tracker {
+            fn authenticate(&self, _: &ConnectionInfo) -> Result<(), String> {
+                Ok(())
+            }
+            fn principal(&self, _: &ConnectionInfo) -> Option<String> {
+                Some("tracker".into())
+            }
+        }
+        let auth: Arc<dyn Authenticator> = Arc::new(Tracker);
+        let authenticated = app().layer(middleware::from_fn_with_state(auth, authenticate));
+
+        for app in [app(), authenticated] {
+            let put = |peer: &str| {
+                let point = json!({ "type": "Point", "coordinates": [1.0, 2.0] });
+                let mut request = axum::http::Request::put("/namespaces/fleet/objects/van")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .header("idempotency-key", "move-1")
+                    .body(Body::from(point.to_string()))
+                    .unwrap();
+                let peer: SocketAddr = peer.parse().unwrap();
+                request.extensions_mut().insert(ConnectInfo(peer));
+                app.clone().oneshot(request)
+            };
+            let first = put("10.0.0.1:4000").await.unwrap();
+            // The device moved to another network before retrying.
+            let retry = put("192.168.1.7:5000").await.unwrap();
+            assert_eq!(first.status(), StatusCode::OK);
+            assert_eq!(retry.status(), StatusCode::OK);
+            let sequence = |response: Response| async {
+                let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                serde_json::from_slice::<Value>(&bytes).unwrap()["sequence"].clone()
+            };
+            assert_eq!(sequence(first).await, sequence(retry).await);
+        }
+    }
+
+    #[test]
+    fn test_busy_errors_ask_to_retry() {
+        let busy = crate::protocol::busy_error("compacting its log", Duration::from_millis(1500));
+        assert_eq!(retry_after(&busy), Some(Duration::from_millis(1500)));
+        let response = ApiError::from(busy).into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "2");
+
+        let rejected = ApiError::from("latitude out of range".to_string()).into_response();
+        assert_eq!(rejected.status(), StatusCode::BAD_REQUEST);
+        assert!(!rejected.headers().contains_key(header::RETRY_AFTER));
+    }
+}
@@ -1 +1,55 @@
+#[cfg(feature = "grpc")]
+pub mod grpc;
+mod guard;
+#[cfg(feature = "http")]
+pub mod http;
 pub mod rpc;
+
+pub(crate) use guard::panic_message;
+
+/// Whose fault a handler error is, for transports that report it as a status.
+#[cfg(any(feature = "http", feature = "grpc"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ErrorKind {
+    /// The request was rejected; sending it again won't help.
+    Rejected,
+    /// The server is overloaded, busy or shutting down; worth retrying
+    /// later.
+    Unavailable,
+    Internal,
+}
+
+/// Handler errors are strings; the ones that aren't the caller's fault are
+/// recognizable by their wording.
+#[cfg(any(feature = "http", feature = "grpc"))]
+pub(crate) fn classify_error(message: &str) -> ErrorKind {
+    if message.starts_with("Internal error") {
+        ErrorKind::Internal
+    } else if message.starts_with(crate::protocol::BUSY_ERROR_PREFIX)
+        || message.starts_with(crate::protocol::STALE_REPLICA_ERROR_PREFIX)
+        || message.contains("overloaded")
+        || message.contains("overwhelmed")
+        || message.contains("shutting down")
+    {
+        ErrorKind::Unavailable
+    } else {
+        ErrorKind::Rejected
+    }
+}
+
+/// Wait for the background writer to drain its queue after its channel has
+/// closed, so durability is preserved on shutdown.
+pub(crate) async fn join_writer(handle: std::thread::JoinHandle<()>) {
+    match tokio::task::spawn_blocking(move || handle.join()).await {
+        Ok(Ok(())) => {}
+        // The writer thread panicked: buffered writes may have been lost, so
+        // surface it rather than letting shutdown look clean.
+        Ok(Err(panic)) => {
+            tracing::error!(
+                "Background writer thread panicked: {}",
+                panic_message(&*panic)
+            );
+        }
+        Err(e) => tracing::error!("Failed to join background writer task: {e}"),
+    }
+}
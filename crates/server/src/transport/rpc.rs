@@ -3,15 +3,26 @@
 use futures::prelude::*;
 use spatio::Spatio;
 
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 use tarpc::server::{self, Channel};
 use tarpc::tokio_serde::formats::Json;
-use tokio::sync::Semaphore;
-use tracing::{error, info};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{Semaphore, mpsc};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
 
-use crate::handler::Handler;
+use crate::auth::{Authenticator, ConnectionInfo};
+use crate::handler::{DEFAULT_APPLIED_WAIT, Handler};
+use crate::idempotency::IdempotencyConfig;
+use crate::middleware::{MiddlewareChain, WithMiddleware};
 use crate::protocol::SpatioService;
+use crate::scheduler::{QueryScheduler, SchedulerConfig};
+use crate::topology::ServerTopology;
+use crate::transport::guard::PanicGuard;
+use crate::transport::join_writer;
+use crate::writer::WriteOp;
 
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 
@@ -22,16 +33,239 @@ const MAX_FRAME_BYTES: usize = 8 * 1024 * 1024;
 const MAX_CONNECTIONS: usize = 1024;
 /// Maximum in-flight requests handled concurrently on a single connection.
 const MAX_REQUESTS_PER_CONNECTION: usize = 256;
+/// How long a client gets to complete the TLS handshake.
+#[cfg(feature = "tls")]
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Settings for [`run_server_with_options`].
+#[derive(Clone, Default)]
+pub struct ServerOptions {
+    /// Admission control for namespace queries. Clones of the options share
+    /// it, so transports serving a database with them admit queries
+    /// against one set of limits.
+    pub scheduler: QueryScheduler,
+    /// Layers every request passes through before reaching the handler.
+    pub middleware: MiddlewareChain,
+    /// Checked for every new connection; all connections are accepted when
+    /// unset.
+    pub auth: Option<Arc<dyn Authenticator>>,
+    /// How long idempotency keys of retried writes are remembered.
+    pub idempotency: IdempotencyConfig,
+    /// Whether writes are taken or refused as by a replica.
+    pub topology: ServerTopology,
+    /// Longest a read waits for this server to apply a client's write
+    /// ([`SpatioService::wait_applied`]); [`DEFAULT_APPLIED_WAIT`] when
+    /// unset.
+    pub applied_wait: Option<Duration>,
+    /// Secret key of jittered queries, which are refused when unset.
+    pub jitter_key: Option<u128>,
+    /// Serve TLS instead of plain TCP.
+    #[cfg(feature = "tls")]
+    pub tls: Option<Arc<tokio_rustls::rustls::ServerConfig>>,
+    /// Time requests and count connections here.
+    #[cfg(feature = "metrics")]
+    pub metrics: Option<Arc<crate::metrics::Metrics>>,
+}
+
+impl ServerOptions {
+    /// Admit namespace queries according to `config`, in a scheduler shared
+    /// by clones of the options made after.
+    pub fn with_scheduler(mut self, config: SchedulerConfig) -> Self {
+        self.scheduler = QueryScheduler::new(config);
+        self
+    }
+
+    pub fn with_middleware(mut self, middleware: MiddlewareChain) -> Self {
+        self.middleware = middleware;
+        self
+    }
+
+    pub fn with_idempotency(mut self, idempotency: IdempotencyConfig) -> Self {
+        self.idempotency = idempotency;
+        self
+    }
+
+    pub fn with_topology(mut self, topology: ServerTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn with_applied_wait(mut self, wait: Duration) -> Self {
+        self.applied_wait = Some(wait);
+        self
+    }
+
+    pub fn with_jitter_key(mut self, key: u128) -> Self {
+        self.jitter_key = Some(key);
+        self
+    }
+
+    pub fn with_auth(mut self, auth: impl Authenticator) -> Self {
+        self.auth = Some(Arc::new(auth));
+        self
+    }
+
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, config: Arc<tokio_rustls::rustls::ServerConfig>) -> Self {
+        self.tls = Some(config);
+        self
+    }
+
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: Arc<crate::metrics::Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// A handler for `db` configured by the options, sending writes to
+    /// `write_tx`.
+    pub(crate) fn handler(&self, db: Arc<Spatio>, write_tx: mpsc::Sender<WriteOp>) -> Handler {
+        let handler = Handler::new(db, write_tx)
+            .with_query_scheduler(self.scheduler.clone())
+            .with_idempotency(self.idempotency)
+            .with_topology(self.topology.clone())
+            .with_applied_wait(self.applied_wait.unwrap_or(DEFAULT_APPLIED_WAIT));
+        match self.jitter_key {
+            Some(key) => handler.with_jitter_key(key),
+            None => handler,
+        }
+    }
+}
+
+/// What every connection task needs, shared across connections.
+#[derive(Clone)]
+struct ConnectionContext {
+    handler: Handler,
+    middleware: MiddlewareChain,
+    auth: Option<Arc<dyn Authenticator>>,
+    #[cfg(feature = "tls")]
+    tls: Option<tokio_rustls::TlsAcceptor>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<crate::metrics::Metrics>>,
+}
+
+impl ConnectionContext {
+    /// Serve one accepted socket until the client leaves or the connection is
+    /// poisoned.
+    async fn run(self, socket: tokio::net::TcpStream, peer: SocketAddr) {
+        #[cfg(feature = "tls")]
+        if let Some(acceptor) = self.tls.clone() {
+            let stream =
+                match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(socket)).await {
+                    Ok(Ok(stream)) => stream,
+                    Ok(Err(e)) => {
+                        warn!("TLS handshake with {peer} failed: {e}");
+                        return;
+                    }
+                    Err(_) => {
+                        warn!("TLS handshake with {peer} timed out");
+                        return;
+                    }
+                };
+            let client_certificates = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .map(|certs| certs.iter().map(|cert| cert.to_vec()).collect())
+                .unwrap_or_default();
+            let conn = ConnectionInfo {
+                peer,
+                client_certificates,
+            };
+            return self.serve(stream, conn).await;
+        }
+        let conn = ConnectionInfo {
+            peer,
+            client_certificates: Vec::new(),
+        };
+        self.serve(socket, conn).await
+    }
+
+    async fn serve<S>(self, stream: S, conn: ConnectionInfo)
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let peer = conn.peer;
+        let mut handler = self.handler;
+        if let Some(auth) = &self.auth {
+            if let Err(reason) = auth.authenticate(&conn) {
+                warn!("Refused connection from {peer}: {reason}");
+                return;
+            }
+            if let Some(principal) = auth.principal(&conn) {
+                handler = handler.for_client(principal);
+            }
+        }
+        #[cfg(feature = "metrics")]
+        let _open = self
+            .metrics
+            .as_ref()
+            .map(|metrics| metrics.connection_opened());
+
+        let codec = LengthDelimitedCodec::builder()
+            .max_frame_length(MAX_FRAME_BYTES)
+            .new_codec();
+        let framed = Framed::new(stream, codec);
+        let transport = tarpc::serde_transport::new(framed, Json::default());
+
+        // A panicking request poisons only its own connection.
+        let poisoned = CancellationToken::new();
+        let service = WithMiddleware::new(handler.serve(), self.middleware, Some(peer));
+        let service = PanicGuard::new(service, Some(peer), poisoned.clone());
+        let connection = server::BaseChannel::with_defaults(transport)
+            .execute(service)
+            // Bound concurrent in-flight requests per connection
+            // rather than spawning an unbounded task per response.
+            .for_each_concurrent(MAX_REQUESTS_PER_CONNECTION, |response| async move {
+                response.await;
+            });
+        tokio::select! {
+            _ = connection => {}
+            _ = poisoned.cancelled() => {
+                warn!("Closing connection from {peer} after a panicked request");
+            }
+        }
+    }
+}
 
 /// Run the tarpc RPC server until `shutdown` resolves.
 pub async fn run_server(
     listener: tokio::net::TcpListener,
     db: Arc<Spatio>,
+    shutdown: impl Future<Output = ()> + Unpin + Send + 'static,
+) -> anyhow::Result<()> {
+    run_server_with_options(listener, db, ServerOptions::default(), shutdown).await
+}
+
+/// Run the tarpc RPC server until `shutdown` resolves, configured by
+/// `options`.
+pub async fn run_server_with_options(
+    listener: tokio::net::TcpListener,
+    db: Arc<Spatio>,
+    options: ServerOptions,
     mut shutdown: impl Future<Output = ()> + Unpin + Send + 'static,
 ) -> anyhow::Result<()> {
     let (write_tx, writer_handle) = crate::writer::spawn_background_writer(db.clone(), 10_000);
+    let handler = options.handler(db, write_tx);
 
-    let handler = Handler::new(db, write_tx);
+    #[cfg(feature = "metrics")]
+    let middleware = match &options.metrics {
+        Some(metrics) => options
+            .middleware
+            .around(crate::metrics::MetricsLayer(metrics.clone())),
+        None => options.middleware,
+    };
+    #[cfg(not(feature = "metrics"))]
+    let middleware = options.middleware;
+    let context = ConnectionContext {
+        handler,
+        middleware,
+        auth: options.auth,
+        #[cfg(feature = "tls")]
+        tls: options.tls.map(tokio_rustls::TlsAcceptor::from),
+        #[cfg(feature = "metrics")]
+        metrics: options.metrics,
+    };
     let connections = Arc::new(Semaphore::new(MAX_CONNECTIONS));
     let mut conns = tokio::task::JoinSet::new();
 
@@ -41,7 +275,7 @@ pub async fn run_server(
         tokio::select! {
             accept_result = listener.accept() => {
                 match accept_result {
-                    Ok((socket, _)) => {
+                    Ok((socket, peer)) => {
                         // Bound live connections; if at capacity, drop the freshly
                         // accepted socket rather than pile on.
                         let Ok(permit) = connections.clone().try_acquire_owned() else {
@@ -50,23 +284,10 @@ pub async fn run_server(
                             continue;
                         };
 
-                        let server = handler.clone();
+                        let context = context.clone();
                         conns.spawn(async move {
                             let _permit = permit; // held for the connection's lifetime
-                            let codec = LengthDelimitedCodec::builder()
-                                .max_frame_length(MAX_FRAME_BYTES)
-                                .new_codec();
-                            let framed = Framed::new(socket, codec);
-                            let transport = tarpc::serde_transport::new(framed, Json::default());
-
-                            server::BaseChannel::with_defaults(transport)
-                                .execute(server.serve())
-                                // Bound concurrent in-flight requests per connection
-                                // rather than spawning an unbounded task per response.
-                                .for_each_concurrent(MAX_REQUESTS_PER_CONNECTION, |response| async move {
-                                    response.await;
-                                })
-                                .await;
+                            context.run(socket, peer).await;
                         });
                     }
                     Err(e) => {
@@ -89,21 +310,8 @@ pub async fn run_server(
     // Abort in-flight connections, then close the writer's channel and wait for
     // it to drain its queue so durability is preserved on shutdown.
     conns.shutdown().await;
-    drop(handler);
-    match tokio::task::spawn_blocking(move || writer_handle.join()).await {
-        Ok(Ok(())) => {}
-        // The writer thread panicked: buffered writes may have been lost, so
-        // surface it rather than letting shutdown look clean.
-        Ok(Err(panic)) => {
-            let msg = panic
-                .downcast_ref::<&str>()
-                .copied()
-                .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
-                .unwrap_or("unknown panic");
-            error!("Background writer thread panicked: {msg}");
-        }
-        Err(e) => error!("Failed to join background writer task: {e}"),
-    }
+    drop(context);
+    join_writer(writer_handle).await;
 
     Ok(())
 }
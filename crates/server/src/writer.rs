@@ -1,16 +1,21 @@
+use crate::transport::panic_message;
 use spatio::Spatio;
 use spatio_types::point::Point3d;
 use spatio_types::time::system_time_from_secs;
+use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
 use tokio::sync::{mpsc, oneshot};
 
-/// Acknowledgement channel a write operation uses to report its result.
-type Ack = oneshot::Sender<Result<(), String>>;
+/// Acknowledgement channel a write operation uses to report its result (the
+/// write's sequence number on success, or for namespace-wide operations the
+/// number of objects and zones removed).
+type Ack = oneshot::Sender<Result<u64, String>>;
 
 /// Write operation to be executed by the background writer thread.
 ///
 /// Each variant carries an `Ack` so the handler can await the *actual* write
-/// result rather than reporting success the moment the op is enqueued.
+/// result rather than reporting success the moment the op is enqueued, and the
+/// submitting request's span so the write is traced as part of that request.
 #[derive(Debug)]
 pub enum WriteOp {
     Upsert {
@@ -19,20 +24,79 @@ pub enum WriteOp {
         point: Point3d,
         metadata: serde_json::Value,
         ack: Ack,
+        span: tracing::Span,
+    },
+    UpsertBatch {
+        namespace: String,
+        items: Vec<(String, Point3d, serde_json::Value)>,
+        ack: Ack,
+        span: tracing::Span,
     },
     Delete {
         namespace: String,
         id: String,
         ack: Ack,
+        span: tracing::Span,
     },
     InsertTrajectory {
         namespace: String,
         id: String,
         trajectory: Vec<(f64, Point3d, serde_json::Value)>,
         ack: Ack,
+        span: tracing::Span,
+    },
+    ClearNamespace {
+        namespace: String,
+        ack: Ack,
+        span: tracing::Span,
+    },
+    DropNamespace {
+        namespace: String,
+        ack: Ack,
+        span: tracing::Span,
     },
 }
 
+impl WriteOp {
+    fn span(&self) -> &tracing::Span {
+        match self {
+            WriteOp::Upsert { span, .. }
+            | WriteOp::UpsertBatch { span, .. }
+            | WriteOp::Delete { span, .. }
+            | WriteOp::InsertTrajectory { span, .. }
+            | WriteOp::ClearNamespace { span, .. }
+            | WriteOp::DropNamespace { span, .. } => span,
+        }
+    }
+
+    fn into_ack(self) -> Ack {
+        match self {
+            WriteOp::Upsert { ack, .. }
+            | WriteOp::UpsertBatch { ack, .. }
+            | WriteOp::Delete { ack, .. }
+            | WriteOp::InsertTrajectory { ack, .. }
+            | WriteOp::ClearNamespace { ack, .. }
+            | WriteOp::DropNamespace { ack, .. } => ack,
+        }
+    }
+
+    /// Short description for logs (payloads omitted).
+    fn describe(&self) -> String {
+        match self {
+            WriteOp::Upsert { namespace, id, .. } => format!("upsert {namespace}/{id}"),
+            WriteOp::UpsertBatch {
+                namespace, items, ..
+            } => format!("upsert_batch {namespace} ({} items)", items.len()),
+            WriteOp::Delete { namespace, id, .. } => format!("delete {namespace}/{id}"),
+            WriteOp::InsertTrajectory { namespace, id, .. } => {
+                format!("insert_trajectory {namespace}/{id}")
+            }
+            WriteOp::ClearNamespace { namespace, .. } => format!("clear_namespace {namespace}"),
+            WriteOp::DropNamespace { namespace, .. } => format!("drop_namespace {namespace}"),
+        }
+    }
+}
+
 /// Spawn the dedicated writer thread.
 ///
 /// Returns the sender used by the handler and the thread's
@@ -42,41 +106,25 @@ pub fn spawn_background_writer(
     db: Arc<Spatio>,
     buffer_size: usize,
 ) -> (mpsc::Sender<WriteOp>, std::thread::JoinHandle<()>) {
-    let (tx, mut rx) = mpsc::channel(buffer_size);
+    let (tx, mut rx) = mpsc::channel::<WriteOp>(buffer_size);
 
     // A dedicated OS thread keeps the blocking DB writes off the tokio runtime.
     let handle = std::thread::spawn(move || {
-        while let Some(op) = rx.blocking_recv() {
-            match op {
-                WriteOp::Upsert {
-                    namespace,
-                    id,
-                    point,
-                    metadata,
-                    ack,
-                } => {
-                    let result = db
-                        .upsert(&namespace, &id, point, metadata, None)
-                        .map_err(|e| e.to_string());
-                    let _ = ack.send(result);
-                }
-                WriteOp::Delete { namespace, id, ack } => {
-                    let result = db.delete(&namespace, &id).map_err(|e| e.to_string());
-                    let _ = ack.send(result);
-                }
-                WriteOp::InsertTrajectory {
-                    namespace,
-                    id,
-                    trajectory,
-                    ack,
-                } => {
-                    let result = build_trajectory(trajectory).and_then(|updates| {
-                        db.insert_trajectory(&namespace, &id, &updates)
-                            .map_err(|e| e.to_string())
-                    });
-                    let _ = ack.send(result);
-                }
-            }
+        while let Some(mut op) = rx.blocking_recv() {
+            let span = op.span().clone();
+            let _entered = span.enter();
+            // A panicking write fails only its own request; the writer keeps
+            // serving everyone else.
+            let result = std::panic::catch_unwind(AssertUnwindSafe(|| apply(&db, &mut op)))
+                .unwrap_or_else(|panic| {
+                    tracing::error!(
+                        command = %op.describe(),
+                        "Write panicked: {}",
+                        panic_message(&*panic)
+                    );
+                    Err("Internal error: write panicked".to_string())
+                });
+            let _ = op.into_ack().send(result);
         }
         tracing::info!("Background writer shutting down");
     });
@@ -84,6 +132,45 @@ pub fn spawn_background_writer(
     (tx, handle)
 }
 
+/// Execute `op` against the database. Owned payloads are taken out of `op`,
+/// leaving its ack and identifiers for the caller.
+fn apply(db: &Spatio, op: &mut WriteOp) -> Result<u64, String> {
+    match op {
+        WriteOp::Upsert {
+            namespace,
+            id,
+            point,
+            metadata,
+            ..
+        } => db
+            .upsert(namespace, id, point.clone(), std::mem::take(metadata), None)
+            .map_err(|e| e.to_string()),
+        WriteOp::UpsertBatch {
+            namespace, items, ..
+        } => db
+            .upsert_batch(namespace, std::mem::take(items))
+            .map_err(|e| e.to_string()),
+        WriteOp::Delete { namespace, id, .. } => {
+            db.delete(namespace, id).map_err(|e| e.to_string())
+        }
+        WriteOp::InsertTrajectory {
+            namespace,
+            id,
+            trajectory,
+            ..
+        } => build_trajectory(std::mem::take(trajectory)).and_then(|updates| {
+            db.insert_trajectory(namespace, id, &updates)
+                .map_err(|e| e.to_string())
+        }),
+        WriteOp::ClearNamespace { namespace, .. } => {
+            db.clear_namespace(namespace).map_err(|e| e.to_string())
+        }
+        WriteOp::DropNamespace { namespace, .. } => {
+            db.drop_namespace(namespace).map_err(|e| e.to_string())
+        }
+    }
+}
+
 fn build_trajectory(
     trajectory: Vec<(f64, Point3d, serde_json::Value)>,
 ) -> Result<Vec<spatio::config::TemporalPoint>, String> {
@@ -0,0 +1,189 @@
+//! Zero-copy access with [`rkyv`] (the `rkyv` feature).
+//!
+//! [`Point`](crate::geo::Point), [`Point3d`](crate::point::Point3d), the
+//! temporal points, [`Trajectory`](crate::trajectory::Trajectory),
+//! [`TrajectorySummary`](crate::trajectory::TrajectorySummary) and
+//! [`LocationUpdate`](crate::wire::LocationUpdate) derive rkyv's `Archive`,
+//! `Serialize` and `Deserialize`. A reader validates a buffer once with
+//! `rkyv::access` and then reads the archived value in place, without
+//! allocating or copying the points out.
+//!
+//! Timestamps are archived as the duration since the Unix epoch, the same
+//! with or without `std`, so archives written on a server can be read on a
+//! device and the other way around.
+//!
+//! # Examples
+//!
+//! ```
+//! use spatio_types::geo::Point;
+//! use spatio_types::point::TemporalPoint3D;
+//! use spatio_types::time::UNIX_EPOCH;
+//! use spatio_types::trajectory::{ArchivedTrajectory, Trajectory};
+//! use core::time::Duration;
+//!
+//! let trajectory = Trajectory::new(vec![TemporalPoint3D::new(
+//!     Point::new(-74.0, 40.7),
+//!     30.0,
+//!     UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+//! )]);
+//! let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&trajectory).unwrap();
+//!
+//! let archived = rkyv::access::<ArchivedTrajectory, rkyv::rancor::Error>(&bytes).unwrap();
+//! let first = &archived.points()[0];
+//! assert_eq!(first.point.x(), -74.0);
+//! assert_eq!(first.altitude, 30.0);
+//! ```
+
+use crate::time::{SystemTime, UNIX_EPOCH};
+use rkyv::rancor::{Fallible, Source};
+use rkyv::time::ArchivedDuration;
+use rkyv::with::{ArchiveWith, DeserializeWith, SerializeWith};
+use rkyv::{Archive, Archived, Place};
+
+/// Archives a [`SystemTime`] as the duration since the Unix epoch. Times
+/// before the epoch fail to serialize.
+pub struct AsUnixTime;
+
+impl ArchiveWith<SystemTime> for AsUnixTime {
+    type Archived = ArchivedDuration;
+    type Resolver = ();
+
+    fn resolve_with(field: &SystemTime, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        // Checked in `serialize_with`.
+        let since_epoch = field.duration_since(UNIX_EPOCH).unwrap_or_default();
+        since_epoch.resolve(resolver, out);
+    }
+}
+
+impl<S> SerializeWith<SystemTime, S> for AsUnixTime
+where
+    S: Fallible + ?Sized,
+    S::Error: Source,
+{
+    fn serialize_with(field: &SystemTime, _: &mut S) -> Result<Self::Resolver, S::Error> {
+        field.duration_since(UNIX_EPOCH).map_err(S::Error::new)?;
+        Ok(())
+    }
+}
+
+impl<D> DeserializeWith<ArchivedDuration, SystemTime, D> for AsUnixTime
+where
+    D: Fallible + ?Sized,
+    D::Error: Source,
+{
+    fn deserialize_with(field: &ArchivedDuration, _: &mut D) -> Result<SystemTime, D::Error> {
+        UNIX_EPOCH
+            .checked_add((*field).into())
+            .ok_or_else(|| D::Error::new(OutOfRange))
+    }
+}
+
+/// An archived time past what [`SystemTime`] can hold.
+#[derive(Debug)]
+struct OutOfRange;
+
+impl core::fmt::Display for OutOfRange {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("archived time out of range")
+    }
+}
+
+impl core::error::Error for OutOfRange {}
+
+/// Archives a `geo_types` point as its `[x, y]` coordinates.
+pub struct AsCoords;
+
+impl ArchiveWith<geo_types::Point<f64>> for AsCoords {
+    type Archived = Archived<[f64; 2]>;
+    type Resolver = [(); 2];
+
+    fn resolve_with(
+        field: &geo_types::Point<f64>,
+        resolver: Self::Resolver,
+        out: Place<Self::Archived>,
+    ) {
+        [field.x(), field.y()].resolve(resolver, out);
+    }
+}
+
+impl<S> SerializeWith<geo_types::Point<f64>, S> for AsCoords
+where
+    S: Fallible + ?Sized,
+{
+    fn serialize_with(_: &geo_types::Point<f64>, _: &mut S) -> Result<Self::Resolver, S::Error> {
+        Ok([(); 2])
+    }
+}
+
+impl<D> DeserializeWith<Archived<[f64; 2]>, geo_types::Point<f64>, D> for AsCoords
+where
+    D: Fallible + ?Sized,
+{
+    fn deserialize_with(
+        field: &Archived<[f64; 2]>,
+        _: &mut D,
+    ) -> Result<geo_types::Point<f64>, D::Error> {
+        Ok(geo_types::Point::new(
+            field[0].to_native(),
+            field[1].to_native(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::geo::Point;
+    use crate::point::{ArchivedTemporalPoint, Point3d, TemporalPoint, TemporalPoint3D};
+    use crate::time::UNIX_EPOCH;
+    use crate::trajectory::{Trajectory, TrajectorySummary};
+    use crate::wire::{ArchivedLocationUpdate, LocationUpdate};
+    use core::time::Duration;
+    use rkyv::rancor::Error;
+
+    #[test]
+    fn test_archived_values_read_in_place() {
+        let update = LocationUpdate {
+            timestamp: 1_700_000_000.5,
+            position: Point3d::new(-74.0, 40.7, 12.0),
+            metadata: br#"{"speed":3}"#.to_vec(),
+        };
+        let bytes = rkyv::to_bytes::<Error>(&update).unwrap();
+        let archived = rkyv::access::<ArchivedLocationUpdate, Error>(&bytes).unwrap();
+        assert_eq!(archived.timestamp, 1_700_000_000.5);
+        assert_eq!(archived.position.point.y(), 40.7);
+        assert_eq!(archived.position.z, 12.0);
+        assert_eq!(archived.metadata.as_slice(), update.metadata.as_slice());
+        assert_eq!(rkyv::deserialize::<_, Error>(archived).unwrap(), update);
+
+        let at = UNIX_EPOCH + Duration::from_millis(1_700_000_000_250);
+        let point = TemporalPoint::new(Point::new(2.35, 48.85), at);
+        let bytes = rkyv::to_bytes::<Error>(&point).unwrap();
+        let archived = rkyv::access::<ArchivedTemporalPoint, Error>(&bytes).unwrap();
+        assert_eq!(rkyv::deserialize::<_, Error>(archived).unwrap(), point);
+    }
+
+    #[test]
+    fn test_trajectory_round_trip() {
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        let trajectory = Trajectory::new(vec![
+            TemporalPoint3D::new(Point::new(0.0, 0.0), 0.0, at(10)),
+            TemporalPoint3D::new(Point::new(0.001, 0.0), 5.0, at(20)),
+        ]);
+        let bytes = rkyv::to_bytes::<Error>(&trajectory).unwrap();
+        let restored = rkyv::from_bytes::<Trajectory, Error>(&bytes).unwrap();
+        assert_eq!(restored, trajectory);
+
+        let summary = TrajectorySummary {
+            points: 2,
+            total_distance: 111.2,
+            duration: Duration::from_secs(10),
+            average_speed: Some(11.12),
+            max_speed: None,
+        };
+        let bytes = rkyv::to_bytes::<Error>(&summary).unwrap();
+        assert_eq!(
+            rkyv::from_bytes::<TrajectorySummary, Error>(&bytes).unwrap(),
+            summary
+        );
+    }
+}
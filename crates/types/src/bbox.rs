@@ -1,7 +1,10 @@
 use crate::geo::Point;
-use geo::Rect;
+use crate::prelude::*;
+use crate::time::SystemTime;
+use crate::wkt::{Geometry, Kind, WktError};
+use core::str::FromStr;
+use geo_types::Rect;
 use serde::{Deserialize, Serialize};
-use std::time::SystemTime;
 
 /// A 2D axis-aligned bounding box.
 ///
@@ -33,8 +36,8 @@ impl BoundingBox2D {
     pub fn new(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Self {
         Self {
             rect: Rect::new(
-                geo::coord! { x: min_x, y: min_y },
-                geo::coord! { x: max_x, y: max_y },
+                geo_types::coord! { x: min_x, y: min_y },
+                geo_types::coord! { x: max_x, y: max_y },
             ),
         }
     }
@@ -107,6 +110,61 @@ impl BoundingBox2D {
             self.max_y() + amount,
         )
     }
+
+    /// Format as a WKT `POLYGON` tracing the box. Parse with [`str::parse`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use spatio_types::bbox::BoundingBox2D;
+    ///
+    /// let bbox = BoundingBox2D::new(0.0, 0.0, 2.0, 1.0);
+    /// assert_eq!(bbox.to_wkt(), "POLYGON ((0 0, 2 0, 2 1, 0 1, 0 0))");
+    /// assert_eq!("BOX(0 0,2 1)".parse::<BoundingBox2D>().unwrap(), bbox);
+    /// ```
+    pub fn to_wkt(&self) -> String {
+        self.to_geometry().to_wkt()
+    }
+
+    /// Encode as a little-endian ISO WKB `POLYGON` tracing the box.
+    pub fn to_wkb(&self) -> Vec<u8> {
+        self.to_geometry().to_wkb()
+    }
+
+    /// Decode WKB or PostGIS EWKB into the bounds of its geometry.
+    pub fn from_wkb(wkb: &[u8]) -> Result<Self, WktError> {
+        Self::from_geometry(Geometry::from_wkb(wkb)?)
+    }
+
+    fn to_geometry(&self) -> Geometry {
+        let (x0, y0, x1, y1) = (self.min_x(), self.min_y(), self.max_x(), self.max_y());
+        let ring = [[x0, y0], [x1, y0], [x1, y1], [x0, y1], [x0, y0]]
+            .map(|[x, y]| [x, y, 0.0, 0.0])
+            .to_vec();
+        Geometry::polygon(vec![ring], false)
+    }
+
+    fn from_geometry(geometry: Geometry) -> Result<Self, WktError> {
+        let (min, max) = envelope(&geometry, "BoundingBox2D")?;
+        Ok(Self::new(min[0], min[1], max[0], max[1]))
+    }
+}
+
+/// The corners of a geometry's envelope, for the bounding box named `target`.
+fn envelope(geometry: &Geometry, target: &str) -> Result<([f64; 4], [f64; 4]), WktError> {
+    geometry
+        .envelope()
+        .ok_or_else(|| WktError::InvalidGeometry(format!("{} needs a non-empty geometry", target)))
+}
+
+/// Parses the bounds of any WKT, PostGIS EWKT or hex-encoded (E)WKB geometry,
+/// or a PostGIS `BOX(...)`.
+impl FromStr for BoundingBox2D {
+    type Err = WktError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_geometry(Geometry::parse(s)?)
+    }
 }
 
 /// A 3D axis-aligned bounding box.
@@ -265,6 +323,65 @@ impl BoundingBox3D {
     pub fn to_2d(&self) -> BoundingBox2D {
         BoundingBox2D::new(self.min_x, self.min_y, self.max_x, self.max_y)
     }
+
+    /// Format as a WKT `POLYHEDRALSURFACE Z` of the box's six faces, the form
+    /// PostGIS gives a `box3d` cast to geometry. Parse with [`str::parse`].
+    pub fn to_wkt(&self) -> String {
+        self.to_geometry().to_wkt()
+    }
+
+    /// Encode as a little-endian ISO WKB `POLYHEDRALSURFACE Z` of the box's
+    /// six faces.
+    pub fn to_wkb(&self) -> Vec<u8> {
+        self.to_geometry().to_wkb()
+    }
+
+    /// Decode WKB or PostGIS EWKB into the bounds of its geometry. A 2D
+    /// geometry gets a z range of 0.0.
+    pub fn from_wkb(wkb: &[u8]) -> Result<Self, WktError> {
+        Self::from_geometry(Geometry::from_wkb(wkb)?)
+    }
+
+    fn to_geometry(&self) -> Geometry {
+        let (x0, y0, z0) = (self.min_x, self.min_y, self.min_z);
+        let (x1, y1, z1) = (self.max_x, self.max_y, self.max_z);
+        let faces = [
+            [[x0, y0, z0], [x0, y1, z0], [x1, y1, z0], [x1, y0, z0]],
+            [[x0, y0, z0], [x0, y0, z1], [x0, y1, z1], [x0, y1, z0]],
+            [[x0, y0, z0], [x1, y0, z0], [x1, y0, z1], [x0, y0, z1]],
+            [[x1, y1, z1], [x1, y0, z1], [x1, y0, z0], [x1, y1, z0]],
+            [[x1, y1, z1], [x1, y1, z0], [x0, y1, z0], [x0, y1, z1]],
+            [[x1, y1, z1], [x0, y1, z1], [x0, y0, z1], [x1, y0, z1]],
+        ];
+        Geometry {
+            kind: Kind::PolyhedralSurface,
+            has_z: true,
+            has_m: false,
+            parts: faces
+                .iter()
+                .map(|face| {
+                    let mut ring: Vec<_> = face.iter().map(|&[x, y, z]| [x, y, z, 0.0]).collect();
+                    ring.push(ring[0]);
+                    vec![ring]
+                })
+                .collect(),
+        }
+    }
+
+    fn from_geometry(geometry: Geometry) -> Result<Self, WktError> {
+        let (min, max) = envelope(&geometry, "BoundingBox3D")?;
+        Ok(Self::new(min[0], min[1], min[2], max[0], max[1], max[2]))
+    }
+}
+
+/// Parses the bounds of any WKT, PostGIS EWKT or hex-encoded (E)WKB geometry,
+/// or a PostGIS `BOX3D(...)`.
+impl FromStr for BoundingBox3D {
+    type Err = WktError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_geometry(Geometry::parse(s)?)
+    }
 }
 
 /// A 2D bounding box with an associated timestamp.
@@ -474,4 +591,24 @@ mod tests {
         assert_eq!(temporal_bbox.bbox(), &bbox);
         assert_eq!(temporal_bbox.timestamp(), &timestamp);
     }
+
+    #[test]
+    fn test_bbox_3d_wkt_and_wkb_round_trip() {
+        let bbox = BoundingBox3D::new(-74.0, 40.7, 0.0, -73.9, 40.8, 100.0);
+        assert!(bbox.to_wkt().starts_with("POLYHEDRALSURFACE Z ((("));
+        assert_eq!(bbox.to_wkt().parse::<BoundingBox3D>().unwrap(), bbox);
+        assert_eq!(BoundingBox3D::from_wkb(&bbox.to_wkb()).unwrap(), bbox);
+        assert_eq!(
+            "BOX3D(-74 40.7 0,-73.9 40.8 100)"
+                .parse::<BoundingBox3D>()
+                .unwrap(),
+            bbox
+        );
+
+        let flat = "POLYGON ((0 0, 2 0, 2 1, 0 0))"
+            .parse::<BoundingBox3D>()
+            .unwrap();
+        assert_eq!(flat, BoundingBox3D::new(0.0, 0.0, 0.0, 2.0, 1.0, 0.0));
+        assert!("POLYGON EMPTY".parse::<BoundingBox2D>().is_err());
+    }
 }
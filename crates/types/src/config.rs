@@ -1,5 +1,5 @@
+use crate::time::SystemTime;
 use serde::{Deserialize, Serialize};
-use std::time::SystemTime;
 
 /// Synchronization policy for persistence.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
@@ -11,17 +11,80 @@ pub enum SyncPolicy {
     /// so an idle database does not sync until the next write (or close).
     #[default]
     EverySecond,
-    /// `fsync` every `sync_batch_size` writes. Most durable, slowest.
+    /// `fsync` every `sync_batch_size` writes. Most durable, slowest. With
+    /// a group commit window, concurrent writes share one `fsync` instead.
     Always,
+    /// `fsync` every `sync_interval_ms` on a background thread, so writers
+    /// never wait on the disk. Callers that need durability wait for the
+    /// durability watermark to pass their write instead.
+    Background,
 }
 
-/// File synchronization strategy (fsync vs fdatasync).
+/// File synchronization strategy: which system call a sync makes.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "snake_case")]
 pub enum SyncMode {
+    /// Flush data and metadata to stable storage, past the drive's cache:
+    /// `fsync` on Linux and other Unixes, `fcntl(F_FULLFSYNC)` on macOS and
+    /// iOS, `FlushFileBuffers` on Windows.
     #[default]
     All,
+    /// Flush data, and only the metadata needed to read it back: `fdatasync`
+    /// on Linux and other Unixes. Same as `All` on macOS, iOS and Windows,
+    /// which have no cheaper call that keeps the data durable.
     Data,
+    /// On macOS and iOS, `fcntl(F_BARRIERFSYNC)`: hand the data to the drive
+    /// and order it before later writes, without flushing the drive's cache.
+    /// Much faster than `All` there, but a power loss (not a crash) can lose
+    /// the latest synced writes. Same as `Data` elsewhere.
+    Barrier,
+}
+
+/// Compression of trajectory log records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogCompression {
+    /// Records are stored as plain text.
+    #[default]
+    None,
+    /// LZ4: fast enough to keep up with appends, for a modest ratio.
+    Lz4,
+    /// Zstandard: a better ratio for more CPU per block. Needs the `zstd`
+    /// feature of the `spatio` crate.
+    Zstd,
+}
+
+impl LogCompression {
+    /// Name of the codec as written in the log header (`None` for
+    /// uncompressed logs).
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Lz4 => Some("lz4"),
+            Self::Zstd => Some("zstd"),
+        }
+    }
+}
+
+/// Order of an object ID range scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanDirection {
+    /// Ascending object IDs.
+    #[default]
+    Forward,
+    /// Descending object IDs.
+    Reverse,
+}
+
+/// Kind of a recorded object mutation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryEventKind {
+    /// The object's location was written.
+    Set,
+    /// The object was deleted.
+    Delete,
 }
 
 /// Options for setting values.
@@ -29,12 +92,23 @@ pub enum SyncMode {
 pub struct SetOptions {
     /// Optional timestamp for the update (defaults to now if None)
     pub timestamp: Option<SystemTime>,
+    /// Keep a recent-history buffer for the object even if its namespace is
+    /// not configured to
+    #[serde(default)]
+    pub track_history: bool,
 }
 
 impl SetOptions {
     pub fn with_timestamp(timestamp: SystemTime) -> Self {
         Self {
             timestamp: Some(timestamp),
+            ..Self::default()
         }
     }
+
+    /// Buffer the object's recent history for fast trajectory reads.
+    pub fn track_history(mut self) -> Self {
+        self.track_history = true;
+        self
+    }
 }
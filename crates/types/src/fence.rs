@@ -0,0 +1,121 @@
+//! Geofence shapes.
+
+use crate::bbox::BoundingBox2D;
+use crate::geo::{Point, Polygon};
+use crate::query::Predicate;
+use serde::{Deserialize, Serialize};
+
+/// The area a geofence covers.
+///
+/// Circles and boxes are tested with a distance or a few comparisons, so
+/// prefer them to a polygon approximating the same shape.
+///
+/// # Examples
+///
+/// ```
+/// use spatio_types::fence::Fence;
+/// use spatio_types::geo::Point;
+///
+/// let depot = Fence::circle(Point::new(-74.0060, 40.7128), 250.0);
+/// assert!(depot.contains(&Point::new(-74.0061, 40.7129)));
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fence {
+    /// Horizontal (haversine) distance from `center` of at most `radius`
+    /// meters.
+    Circle { center: Point, radius: f64 },
+    /// A longitude/latitude box, edges included.
+    BBox(BoundingBox2D),
+    /// A polygon (points on the boundary are not inside).
+    Polygon(Polygon),
+}
+
+impl Fence {
+    pub fn circle(center: Point, radius: f64) -> Self {
+        Fence::Circle { center, radius }
+    }
+
+    pub fn bbox(min_x: f64, min_y: f64, max_x: f64, max_y: f64) -> Self {
+        Fence::BBox(BoundingBox2D::new(min_x, min_y, max_x, max_y))
+    }
+
+    /// Whether `point` is inside the fence, by the same rules as
+    /// [`Fence::to_predicate`].
+    #[cfg(feature = "std")]
+    pub fn contains(&self, point: &Point) -> bool {
+        match self {
+            Fence::Circle { center, radius } => center.haversine_distance(point) <= *radius,
+            Fence::BBox(bbox) => bbox.contains_point(point),
+            Fence::Polygon(polygon) => polygon.contains(point),
+        }
+    }
+
+    /// The equivalent query predicate.
+    pub fn to_predicate(&self) -> Predicate {
+        match self {
+            Fence::Circle { center, radius } => Predicate::WithinRadius {
+                center: *center,
+                radius: *radius,
+            },
+            Fence::BBox(bbox) => Predicate::WithinBbox {
+                min_x: bbox.min_x(),
+                min_y: bbox.min_y(),
+                max_x: bbox.max_x(),
+                max_y: bbox.max_y(),
+            },
+            Fence::Polygon(polygon) => Predicate::WithinPolygon(polygon.clone()),
+        }
+    }
+}
+
+impl From<Polygon> for Fence {
+    fn from(polygon: Polygon) -> Self {
+        Fence::Polygon(polygon)
+    }
+}
+
+impl From<BoundingBox2D> for Fence {
+    fn from(bbox: BoundingBox2D) -> Self {
+        Fence::BBox(bbox)
+    }
+}
+
+impl From<Fence> for Predicate {
+    fn from(fence: Fence) -> Self {
+        match fence {
+            Fence::Polygon(polygon) => Predicate::WithinPolygon(polygon),
+            other => other.to_predicate(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fence_containment() {
+        let circle = Fence::circle(Point::new(0.0, 0.0), 1_000.0);
+        assert!(circle.contains(&Point::new(0.005, 0.0)));
+        assert!(!circle.contains(&Point::new(0.01, 0.0)));
+
+        let bbox = Fence::bbox(0.0, 0.0, 1.0, 1.0);
+        assert!(bbox.contains(&Point::new(1.0, 0.5)));
+        assert!(!bbox.contains(&Point::new(1.1, 0.5)));
+
+        let square = Fence::from(Polygon::from_coords(
+            &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.0, 0.0)],
+            vec![],
+        ));
+        assert!(square.contains(&Point::new(0.5, 0.5)));
+        assert!(!square.contains(&Point::new(1.0, 0.5)));
+
+        let json = serde_json::to_string(&circle).unwrap();
+        assert_eq!(serde_json::from_str::<Fence>(&json).unwrap(), circle);
+        assert!(matches!(
+            Predicate::from(circle),
+            Predicate::WithinRadius { radius, .. } if radius == 1_000.0
+        ));
+    }
+}
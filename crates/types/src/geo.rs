@@ -3,6 +3,7 @@
 //! This module provides wrapper types around `geo` crate primitives with additional
 //! methods for GeoJSON serialization, distance calculations, and other spatial operations.
 
+use crate::prelude::*;
 use serde::{Deserialize, Serialize};
 
 /// Error type for GeoJSON conversions.
@@ -34,8 +35,8 @@ pub enum DistanceMetric {
     Euclidean,
 }
 
-impl std::fmt::Display for GeoJsonError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for GeoJsonError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::Serialization(msg) => write!(f, "GeoJSON serialization error: {}", msg),
             Self::Deserialization(msg) => write!(f, "GeoJSON deserialization error: {}", msg),
@@ -45,7 +46,7 @@ impl std::fmt::Display for GeoJsonError {
     }
 }
 
-impl std::error::Error for GeoJsonError {}
+impl core::error::Error for GeoJsonError {}
 
 /// A geographic point with longitude/latitude coordinates.
 ///
@@ -62,8 +63,24 @@ impl std::error::Error for GeoJsonError {}
 /// assert_eq!(nyc.y(), 40.7128);
 /// ```
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
 pub struct Point {
-    inner: geo::Point<f64>,
+    #[cfg_attr(feature = "rkyv", rkyv(with = crate::archive::AsCoords))]
+    inner: geo_types::Point<f64>,
+}
+
+#[cfg(feature = "rkyv")]
+impl ArchivedPoint {
+    pub fn x(&self) -> f64 {
+        self.inner[0].to_native()
+    }
+
+    pub fn y(&self) -> f64 {
+        self.inner[1].to_native()
+    }
 }
 
 impl Point {
@@ -84,7 +101,7 @@ impl Point {
     #[inline]
     pub fn new(x: f64, y: f64) -> Self {
         Self {
-            inner: geo::Point::new(x, y),
+            inner: geo_types::Point::new(x, y),
         }
     }
 
@@ -114,13 +131,13 @@ impl Point {
 
     /// Access the inner `geo::Point`.
     #[inline]
-    pub fn inner(&self) -> &geo::Point<f64> {
+    pub fn inner(&self) -> &geo_types::Point<f64> {
         &self.inner
     }
 
     /// Convert into the inner `geo::Point`.
     #[inline]
-    pub fn into_inner(self) -> geo::Point<f64> {
+    pub fn into_inner(self) -> geo_types::Point<f64> {
         self.inner
     }
 
@@ -138,6 +155,7 @@ impl Point {
     /// let distance = nyc.haversine_distance(&la);
     /// assert!(distance > 3_900_000.0); // ~3,944 km
     /// ```
+    #[cfg(feature = "std")]
     #[inline]
     pub fn haversine_distance(&self, other: &Point) -> f64 {
         use geo::Distance;
@@ -159,6 +177,7 @@ impl Point {
     /// let p2 = Point::new(-74.0070, 40.7138);
     /// let distance = p1.geodesic_distance(&p2);
     /// ```
+    #[cfg(feature = "std")]
     #[inline]
     pub fn geodesic_distance(&self, other: &Point) -> f64 {
         use geo::Distance;
@@ -180,6 +199,7 @@ impl Point {
     /// let distance = p1.euclidean_distance(&p2);
     /// assert_eq!(distance, 5.0); // 3-4-5 triangle
     /// ```
+    #[cfg(feature = "std")]
     #[inline]
     pub fn euclidean_distance(&self, other: &Point) -> f64 {
         use geo::Distance;
@@ -247,13 +267,13 @@ impl Point {
     }
 }
 
-impl From<geo::Point<f64>> for Point {
-    fn from(point: geo::Point<f64>) -> Self {
+impl From<geo_types::Point<f64>> for Point {
+    fn from(point: geo_types::Point<f64>) -> Self {
         Self { inner: point }
     }
 }
 
-impl From<Point> for geo::Point<f64> {
+impl From<Point> for geo_types::Point<f64> {
     fn from(point: Point) -> Self {
         point.inner
     }
@@ -293,7 +313,7 @@ impl From<Point> for (f64, f64) {
 /// ```
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Polygon {
-    inner: geo::Polygon<f64>,
+    inner: geo_types::Polygon<f64>,
 }
 
 impl Polygon {
@@ -303,9 +323,12 @@ impl Polygon {
     ///
     /// * `exterior` - The outer boundary of the polygon
     /// * `interiors` - Optional holes within the polygon
-    pub fn new(exterior: geo::LineString<f64>, interiors: Vec<geo::LineString<f64>>) -> Self {
+    pub fn new(
+        exterior: geo_types::LineString<f64>,
+        interiors: Vec<geo_types::LineString<f64>>,
+    ) -> Self {
         Self {
-            inner: geo::Polygon::new(exterior, interiors),
+            inner: geo_types::Polygon::new(exterior, interiors),
         }
     }
 
@@ -357,18 +380,20 @@ impl Polygon {
     /// );
     /// ```
     pub fn from_coords(exterior: &[(f64, f64)], interiors: Vec<Vec<(f64, f64)>>) -> Self {
-        let exterior_coords: Vec<geo::Coord> =
-            exterior.iter().map(|&(x, y)| geo::Coord { x, y }).collect();
-        let exterior_line = geo::LineString::from(exterior_coords);
+        let exterior_coords: Vec<geo_types::Coord> = exterior
+            .iter()
+            .map(|&(x, y)| geo_types::Coord { x, y })
+            .collect();
+        let exterior_line = geo_types::LineString::from(exterior_coords);
 
-        let interior_lines: Vec<geo::LineString<f64>> = interiors
+        let interior_lines: Vec<geo_types::LineString<f64>> = interiors
             .into_iter()
             .map(|interior| {
-                let coords: Vec<geo::Coord> = interior
+                let coords: Vec<geo_types::Coord> = interior
                     .into_iter()
-                    .map(|(x, y)| geo::Coord { x, y })
+                    .map(|(x, y)| geo_types::Coord { x, y })
                     .collect();
-                geo::LineString::from(coords)
+                geo_types::LineString::from(coords)
             })
             .collect();
 
@@ -377,25 +402,25 @@ impl Polygon {
 
     /// Get a reference to the exterior ring.
     #[inline]
-    pub fn exterior(&self) -> &geo::LineString<f64> {
+    pub fn exterior(&self) -> &geo_types::LineString<f64> {
         self.inner.exterior()
     }
 
     /// Get references to the interior rings (holes).
     #[inline]
-    pub fn interiors(&self) -> &[geo::LineString<f64>] {
+    pub fn interiors(&self) -> &[geo_types::LineString<f64>] {
         self.inner.interiors()
     }
 
     /// Access the inner `geo::Polygon`.
     #[inline]
-    pub fn inner(&self) -> &geo::Polygon<f64> {
+    pub fn inner(&self) -> &geo_types::Polygon<f64> {
         &self.inner
     }
 
     /// Convert into the inner `geo::Polygon`.
     #[inline]
-    pub fn into_inner(self) -> geo::Polygon<f64> {
+    pub fn into_inner(self) -> geo_types::Polygon<f64> {
         self.inner
     }
 
@@ -418,6 +443,7 @@ impl Polygon {
     /// let point = Point::new(-75.0, 40.0);
     /// assert!(polygon.contains(&point));
     /// ```
+    #[cfg(feature = "std")]
     #[inline]
     pub fn contains(&self, point: &Point) -> bool {
         use geo::Contains;
@@ -503,7 +529,7 @@ impl Polygon {
                     ));
                 }
 
-                let exterior: Result<Vec<geo::Coord>, GeoJsonError> = rings[0]
+                let exterior: Result<Vec<geo_types::Coord>, GeoJsonError> = rings[0]
                     .iter()
                     .map(|coords| {
                         if coords.len() < 2 {
@@ -511,7 +537,7 @@ impl Polygon {
                                 "Coordinate must have at least 2 values".to_string(),
                             ));
                         }
-                        Ok(geo::Coord {
+                        Ok(geo_types::Coord {
                             x: coords[0],
                             y: coords[1],
                         })
@@ -519,11 +545,11 @@ impl Polygon {
                     .collect();
 
                 let exterior_coords = exterior?;
-                let exterior_line = geo::LineString::from(exterior_coords);
+                let exterior_line = geo_types::LineString::from(exterior_coords);
 
                 let mut interiors = Vec::new();
                 for ring in rings.iter().skip(1) {
-                    let interior: Result<Vec<geo::Coord>, GeoJsonError> = ring
+                    let interior: Result<Vec<geo_types::Coord>, GeoJsonError> = ring
                         .iter()
                         .map(|coords| {
                             if coords.len() < 2 {
@@ -531,14 +557,14 @@ impl Polygon {
                                     "Coordinate must have at least 2 values".to_string(),
                                 ));
                             }
-                            Ok(geo::Coord {
+                            Ok(geo_types::Coord {
                                 x: coords[0],
                                 y: coords[1],
                             })
                         })
                         .collect();
                     let interior_coords = interior?;
-                    interiors.push(geo::LineString::from(interior_coords));
+                    interiors.push(geo_types::LineString::from(interior_coords));
                 }
 
                 Ok(Polygon::new(exterior_line, interiors))
@@ -550,13 +576,13 @@ impl Polygon {
     }
 }
 
-impl From<geo::Polygon<f64>> for Polygon {
-    fn from(polygon: geo::Polygon<f64>) -> Self {
+impl From<geo_types::Polygon<f64>> for Polygon {
+    fn from(polygon: geo_types::Polygon<f64>) -> Self {
         Self { inner: polygon }
     }
 }
 
-impl From<Polygon> for geo::Polygon<f64> {
+impl From<Polygon> for geo_types::Polygon<f64> {
     fn from(polygon: Polygon) -> Self {
         polygon.inner
     }
@@ -609,7 +635,7 @@ mod tests {
 
     #[test]
     fn test_polygon_creation() {
-        use geo::polygon;
+        use geo_types::polygon;
 
         let poly = polygon![
             (x: -80.0, y: 35.0),
@@ -625,7 +651,7 @@ mod tests {
 
     #[test]
     fn test_polygon_contains() {
-        use geo::polygon;
+        use geo_types::polygon;
 
         let poly = polygon![
             (x: -80.0, y: 35.0),
@@ -657,7 +683,7 @@ mod tests {
     #[cfg(feature = "geojson")]
     #[test]
     fn test_polygon_geojson_roundtrip() {
-        use geo::polygon;
+        use geo_types::polygon;
 
         let poly = polygon![
             (x: -80.0, y: 35.0),
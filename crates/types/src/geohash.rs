@@ -0,0 +1,156 @@
+//! Geohash cells.
+//!
+//! A geohash interleaves longitude and latitude bisections into a base32
+//! string; longer hashes name smaller cells. The router uses them as shard
+//! keys, and namespaces can snap positions to their cell's center.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+const BASE32: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// Longest supported geohash (cells of a few centimeters).
+pub const MAX_PRECISION: usize = 12;
+
+/// Geohash of `(lon, lat)` with `precision` characters.
+///
+/// Coordinates are clamped to the valid range, so `lon = 180` and `lat = 90`
+/// land in the easternmost and northernmost cells.
+pub fn encode(lon: f64, lat: f64, precision: usize) -> String {
+    let precision = precision.clamp(1, MAX_PRECISION);
+    let (mut lon_range, mut lat_range) = ((-180.0, 180.0), (-90.0, 90.0));
+    let lon = lon.clamp(-180.0, 180.0);
+    let lat = lat.clamp(-90.0, 90.0);
+
+    let mut hash = String::with_capacity(precision);
+    let mut even = true;
+    for _ in 0..precision {
+        let mut index = 0usize;
+        for _ in 0..5 {
+            let (value, range) = if even {
+                (lon, &mut lon_range)
+            } else {
+                (lat, &mut lat_range)
+            };
+            let mid = (range.0 + range.1) / 2.0;
+            index <<= 1;
+            if value >= mid {
+                index |= 1;
+                range.0 = mid;
+            } else {
+                range.1 = mid;
+            }
+            even = !even;
+        }
+        hash.push(BASE32[index] as char);
+    }
+    hash
+}
+
+/// Width and height in degrees of a cell at `precision`.
+pub fn cell_size(precision: usize) -> (f64, f64) {
+    let bits = 5 * precision.clamp(1, MAX_PRECISION) as i32;
+    let lon_bits = (bits + 1) / 2;
+    let lat_bits = bits / 2;
+    (360.0 / 2f64.powi(lon_bits), 180.0 / 2f64.powi(lat_bits))
+}
+
+/// Center of the cell at `precision` holding `(lon, lat)`, clamped as by
+/// [`encode`].
+pub fn cell_center(lon: f64, lat: f64, precision: usize) -> (f64, f64) {
+    let (width, height) = cell_size(precision);
+    let center = |value: f64, min: f64, max: f64, size: f64| {
+        let cells = ((max - min) / size).round();
+        // The upper edge belongs to the last cell.
+        let index = ((value.clamp(min, max) - min) / size)
+            .floor()
+            .min(cells - 1.0);
+        min + (index + 0.5) * size
+    };
+    (
+        center(lon, -180.0, 180.0, width),
+        center(lat, -90.0, 90.0, height),
+    )
+}
+
+/// Geohashes of every cell overlapping the box, or `None` if that is more
+/// than `max_cells` cells.
+pub fn cover(
+    min_lon: f64,
+    min_lat: f64,
+    max_lon: f64,
+    max_lat: f64,
+    precision: usize,
+    max_cells: usize,
+) -> Option<Vec<String>> {
+    let (width, height) = cell_size(precision);
+    let min_lon = min_lon.clamp(-180.0, 180.0);
+    let max_lon = max_lon.clamp(-180.0, 180.0);
+    let min_lat = min_lat.clamp(-90.0, 90.0);
+    let max_lat = max_lat.clamp(-90.0, 90.0);
+
+    // Cell grid indices spanned by the box (cells are aligned to -180/-90).
+    let first_col = ((min_lon + 180.0) / width).floor();
+    let last_col = ((max_lon + 180.0) / width).floor();
+    let first_row = ((min_lat + 90.0) / height).floor();
+    let last_row = ((max_lat + 90.0) / height).floor();
+    let cells = (last_col - first_col + 1.0) * (last_row - first_row + 1.0);
+    if !(cells >= 1.0 && cells <= max_cells as f64) {
+        return None;
+    }
+
+    let mut hashes = Vec::with_capacity(cells as usize);
+    let mut row = first_row;
+    while row <= last_row {
+        let mut col = first_col;
+        while col <= last_col {
+            // Encode the cell center, which is unambiguous at cell edges.
+            let lon = -180.0 + (col + 0.5) * width;
+            let lat = -90.0 + (row + 0.5) * height;
+            hashes.push(encode(lon.min(180.0), lat.min(90.0), precision));
+            col += 1.0;
+        }
+        row += 1.0;
+    }
+    Some(hashes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_known_hashes() {
+        assert_eq!(encode(-5.6, 42.6, 5), "ezs42");
+        assert_eq!(encode(10.40744, 57.64911, 11), "u4pruydqqvj");
+        assert_eq!(encode(180.0, 90.0, 4), "zzzz");
+        assert_eq!(encode(-180.0, -90.0, 4), "0000");
+    }
+
+    #[test]
+    fn test_cover_includes_corner_cells() {
+        let (width, height) = cell_size(4);
+        assert!((width - 0.3515625).abs() < 1e-12);
+        assert!((height - 0.17578125).abs() < 1e-12);
+
+        let cells = cover(-74.1, 40.6, -73.9, 40.8, 4, 64).unwrap();
+        for (lon, lat) in [(-74.1, 40.6), (-73.9, 40.8), (-74.0, 40.7)] {
+            assert!(cells.contains(&encode(lon, lat, 4)));
+        }
+        assert!(cover(-10.0, -10.0, 10.0, 10.0, 6, 64).is_none());
+    }
+
+    #[test]
+    fn test_cell_center_stays_in_its_cell() {
+        for (lon, lat) in [
+            (-5.6, 42.6),
+            (10.40744, 57.64911),
+            (180.0, 90.0),
+            (-180.0, -90.0),
+        ] {
+            let (center_lon, center_lat) = cell_center(lon, lat, 5);
+            assert_eq!(encode(center_lon, center_lat, 5), encode(lon, lat, 5));
+        }
+        assert_eq!(cell_center(180.0, 90.0, 1), (157.5, 67.5));
+    }
+}
@@ -7,13 +7,39 @@
 //! - **Point types**: `Point`, `Point3d`, `TemporalPoint`, `TemporalPoint3D`
 //! - **Polygon types**: `Polygon`, `Polygon3D`, `PolygonDynamic`, `PolygonDynamic3D`
 //! - **Bounding box types**: `BoundingBox2D`, `BoundingBox3D`, `TemporalBoundingBox2D`, `TemporalBoundingBox3D`
+//! - **Query predicates**: `Predicate`, composable spatial conditions
+//! - **Geofences**: `Fence`, a circle, box or polygon
+//! - **Trajectories**: `Trajectory`, with Douglas–Peucker and Visvalingam–Whyatt simplification
+//! - **Wire types**: `RadiusQuery`, `KnnQuery`, `TrajectorySlice`, `QueryHit`, the query
+//!   parameters and results shared by the network transports and clients
+//! - **Statements**: `Statement`, parsed from a small text query language
+//!   (`NEARBY fleet POINT(-74 40.7) 500 LIMIT 10`)
 //!
 //! All types are serializable with Serde and built on top of the `geo` crate's
-//! geometric primitives.
+//! geometric primitives. `Point3d`, `Polygon3D`, the bounding boxes and
+//! `Trajectory` also read and write WKT/WKB (see [`wkt`]), including the
+//! PostGIS EWKT/EWKB found in database dumps.
 //!
 //! ## Features
 //!
+//! - **`std`** (default) - Distance and containment calculations, trajectory
+//!   simplification and kinematics, and timestamps as [`std::time::SystemTime`]
 //! - **`geojson`** - Enable GeoJSON serialization/deserialization for types
+//!   (implies `std`)
+//! - **`rkyv`** - Zero-copy archives of points, trajectories and location
+//!   updates (see [`archive`]); works with or without `std`
+//!
+//! ## `no_std`
+//!
+//! With default features off the crate builds on `core` and `alloc` alone, so
+//! firmware on a tracker can build, serialize and parse the same points,
+//! trajectories and wire types the server does. Timestamps are then
+//! [`time::SystemTime`], a duration since the Unix epoch that serializes
+//! exactly like the standard library's; the device sets it from its own clock.
+//!
+//! ```toml
+//! spatio-types = { version = "0.2", default-features = false }
+//! ```
 //!
 //! ## Examples
 //!
@@ -47,10 +73,35 @@
 //! # }
 //! ```
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "rkyv")]
+pub mod archive;
 pub mod bbox;
 pub mod config;
+pub mod fence;
 pub mod geo;
+#[cfg(feature = "std")]
+pub mod geohash;
 pub mod point;
 pub mod polygon;
+pub mod query;
+pub mod statement;
 pub mod stats;
 pub mod time;
+pub mod trajectory;
+pub mod wire;
+pub mod wkt;
+
+/// `alloc` items the standard prelude would otherwise provide.
+mod prelude {
+    pub use alloc::boxed::Box;
+    pub use alloc::format;
+    pub use alloc::string::{String, ToString};
+    pub use alloc::vec;
+    pub use alloc::vec::Vec;
+    #[cfg(not(feature = "std"))]
+    pub use num_traits::Float;
+}
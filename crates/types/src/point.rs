@@ -1,6 +1,9 @@
 use crate::geo::Point;
+use crate::prelude::*;
+use crate::time::SystemTime;
+use crate::wkt::{Geometry, Kind, WktError};
+use core::str::FromStr;
 use serde::{Deserialize, Serialize};
-use std::time::SystemTime;
 
 /// A 3D geographic point with x, y (longitude/latitude) and z (altitude/elevation).
 ///
@@ -22,6 +25,10 @@ use std::time::SystemTime;
 /// let distance = drone_position.distance_3d(&other);
 /// ```
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
 pub struct Point3d {
     /// The 2D geographic point (longitude/latitude or x/y)
     pub point: Point,
@@ -146,6 +153,7 @@ impl Point3d {
     /// let p2 = Point3d::new(-74.0070, 40.7138, 100.0);
     /// let (h_dist, alt_diff, dist_3d) = p1.haversine_distances(&p2);
     /// ```
+    #[cfg(feature = "std")]
     pub fn haversine_distances(&self, other: &Point3d) -> (f64, f64, f64) {
         let horizontal_distance = self.point.haversine_distance(&other.point);
         let altitude_diff = (self.z - other.z).abs();
@@ -172,6 +180,7 @@ impl Point3d {
     /// let p2 = Point3d::new(-74.0070, 40.7138, 100.0);  // Nearby, 100m up
     /// let distance = p1.haversine_3d(&p2);
     /// ```
+    #[cfg(feature = "std")]
     #[inline]
     pub fn haversine_3d(&self, other: &Point3d) -> f64 {
         let (_, _, dist_3d) = self.haversine_distances(other);
@@ -183,6 +192,7 @@ impl Point3d {
     /// # Returns
     ///
     /// Distance in meters.
+    #[cfg(feature = "std")]
     #[inline]
     pub fn haversine_2d(&self, other: &Point3d) -> f64 {
         self.point.haversine_distance(&other.point)
@@ -261,12 +271,65 @@ impl Point3d {
             )),
         }
     }
+
+    /// Format as WKT (`POINT Z`). Parse with [`str::parse`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use spatio_types::point::Point3d;
+    ///
+    /// let point = Point3d::new(-74.006, 40.7128, 100.0);
+    /// assert_eq!(point.to_wkt(), "POINT Z (-74.006 40.7128 100)");
+    /// assert_eq!(point.to_wkt().parse::<Point3d>().unwrap(), point);
+    /// ```
+    pub fn to_wkt(&self) -> String {
+        self.to_geometry().to_wkt()
+    }
+
+    /// Encode as little-endian ISO WKB (`POINT Z`).
+    pub fn to_wkb(&self) -> Vec<u8> {
+        self.to_geometry().to_wkb()
+    }
+
+    /// Decode WKB or PostGIS EWKB. Defaults altitude to 0.0 for a 2D point.
+    pub fn from_wkb(wkb: &[u8]) -> Result<Self, WktError> {
+        Self::from_geometry(Geometry::from_wkb(wkb)?)
+    }
+
+    fn to_geometry(&self) -> Geometry {
+        Geometry::point([self.x(), self.y(), self.z(), 0.0], true)
+    }
+
+    fn from_geometry(geometry: Geometry) -> Result<Self, WktError> {
+        geometry.expect(Kind::Point, "Point3d")?;
+        let c = geometry
+            .coords()
+            .next()
+            .ok_or_else(|| WktError::InvalidGeometry("Point3d needs a non-empty POINT".into()))?;
+        Ok(Point3d::new(c[0], c[1], c[2]))
+    }
+}
+
+/// Parses WKT, PostGIS EWKT or hex-encoded (E)WKB. Defaults altitude to 0.0
+/// for a 2D point.
+impl FromStr for Point3d {
+    type Err = WktError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_geometry(Geometry::parse(s)?)
+    }
 }
 
 /// A geographic point with an associated timestamp.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
 pub struct TemporalPoint {
     pub point: Point,
+    #[cfg_attr(feature = "rkyv", rkyv(with = crate::archive::AsUnixTime))]
     pub timestamp: SystemTime,
 }
 
@@ -286,9 +349,14 @@ impl TemporalPoint {
 
 /// A geographic point with an associated altitude and timestamp.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
 pub struct TemporalPoint3D {
     pub point: Point,
     pub altitude: f64,
+    #[cfg_attr(feature = "rkyv", rkyv(with = crate::archive::AsUnixTime))]
     pub timestamp: SystemTime,
 }
 
@@ -319,6 +387,7 @@ impl TemporalPoint3D {
     }
 
     /// Calculate 3D haversine distance to another temporal 3D point.
+    #[cfg(feature = "std")]
     pub fn distance_to(&self, other: &TemporalPoint3D) -> f64 {
         self.to_point_3d().haversine_3d(&other.to_point_3d())
     }
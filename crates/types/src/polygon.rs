@@ -1,7 +1,10 @@
 use crate::point::Point3d;
-use geo::Polygon;
+use crate::prelude::*;
+use crate::time::SystemTime;
+use crate::wkt::{Geometry, Kind, WktError};
+use core::str::FromStr;
+use geo_types::Polygon;
 use serde::{Deserialize, Serialize};
-use std::time::SystemTime;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Polygon3D {
@@ -16,6 +19,63 @@ impl Polygon3D {
     pub fn points(&self) -> &Vec<Point3d> {
         &self.points
     }
+
+    /// Format as WKT (`POLYGON Z`), closing the ring if it isn't already.
+    /// Parse with [`str::parse`]; the parsed ring keeps its closing point.
+    pub fn to_wkt(&self) -> String {
+        self.to_geometry().to_wkt()
+    }
+
+    /// Encode as little-endian ISO WKB (`POLYGON Z`), closing the ring as
+    /// [`Polygon3D::to_wkt`] does.
+    pub fn to_wkb(&self) -> Vec<u8> {
+        self.to_geometry().to_wkb()
+    }
+
+    /// Decode WKB or PostGIS EWKB.
+    pub fn from_wkb(wkb: &[u8]) -> Result<Self, WktError> {
+        Self::from_geometry(Geometry::from_wkb(wkb)?)
+    }
+
+    fn to_geometry(&self) -> Geometry {
+        let mut ring: Vec<_> = self
+            .points
+            .iter()
+            .map(|p| [p.x(), p.y(), p.z(), 0.0])
+            .collect();
+        if ring.first() != ring.last() {
+            ring.push(ring[0]);
+        }
+        let rings = if ring.is_empty() { vec![] } else { vec![ring] };
+        Geometry::polygon(rings, true)
+    }
+
+    fn from_geometry(mut geometry: Geometry) -> Result<Self, WktError> {
+        geometry.expect(Kind::Polygon, "Polygon3D")?;
+        let mut rings = geometry.parts.remove(0);
+        if rings.len() > 1 {
+            return Err(WktError::InvalidGeometry(
+                "Polygon3D has no interior rings".into(),
+            ));
+        }
+        let points = rings
+            .pop()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|c| Point3d::new(c[0], c[1], c[2]))
+            .collect();
+        Ok(Self::new(points))
+    }
+}
+
+/// Parses WKT, PostGIS EWKT or hex-encoded (E)WKB. A 2D polygon gets
+/// altitude 0.0.
+impl FromStr for Polygon3D {
+    type Err = WktError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_geometry(Geometry::parse(s)?)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
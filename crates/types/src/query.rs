@@ -0,0 +1,114 @@
+//! Composite spatial predicates.
+
+use crate::geo::{Point, Polygon};
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A condition on an object's current position or metadata, combinable into a
+/// single query such as "inside polygon A, not inside polygon B, below 500 m".
+///
+/// # Examples
+///
+/// ```
+/// use spatio_types::geo::Polygon;
+/// use spatio_types::query::Predicate;
+///
+/// let city = Polygon::from_coords(&[(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0), (0.0, 0.0)], vec![]);
+/// let airport = Polygon::from_coords(&[(1.0, 1.0), (2.0, 1.0), (2.0, 2.0), (1.0, 2.0), (1.0, 1.0)], vec![]);
+///
+/// let low_over_city = Predicate::WithinPolygon(city)
+///     .and(!Predicate::WithinPolygon(airport))
+///     .and(Predicate::Altitude { min: None, max: Some(500.0) });
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Predicate {
+    /// Horizontal (haversine) distance from `center` of at most `radius`
+    /// meters, at any altitude.
+    WithinRadius { center: Point, radius: f64 },
+    /// Inside a longitude/latitude box, edges included.
+    WithinBbox {
+        min_x: f64,
+        min_y: f64,
+        max_x: f64,
+        max_y: f64,
+    },
+    /// Inside a polygon (points on the boundary are not inside).
+    WithinPolygon(Polygon),
+    /// Altitude within `[min, max]` meters; `None` leaves that side open.
+    Altitude { min: Option<f64>, max: Option<f64> },
+    /// The object's metadata is a JSON object whose top-level `field` equals
+    /// `value`. Numbers compare as stored, so `1` does not equal `1.0`.
+    MetadataEquals {
+        field: String,
+        value: serde_json::Value,
+    },
+    /// Every predicate holds (true when empty).
+    All(Vec<Predicate>),
+    /// At least one predicate holds (false when empty).
+    Any(Vec<Predicate>),
+    /// The predicate does not hold.
+    Not(Box<Predicate>),
+}
+
+impl Predicate {
+    /// Both `self` and `other` hold.
+    pub fn and(self, other: Predicate) -> Predicate {
+        match self {
+            Predicate::All(mut all) => {
+                all.push(other);
+                Predicate::All(all)
+            }
+            first => Predicate::All(vec![first, other]),
+        }
+    }
+
+    /// Either `self` or `other` holds.
+    pub fn or(self, other: Predicate) -> Predicate {
+        match self {
+            Predicate::Any(mut any) => {
+                any.push(other);
+                Predicate::Any(any)
+            }
+            first => Predicate::Any(vec![first, other]),
+        }
+    }
+}
+
+impl core::ops::Not for Predicate {
+    type Output = Predicate;
+
+    fn not(self) -> Predicate {
+        match self {
+            Predicate::Not(inner) => *inner,
+            other => Predicate::Not(Box::new(other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combinators_flatten() {
+        let low = Predicate::Altitude {
+            min: None,
+            max: Some(500.0),
+        };
+        let near = Predicate::WithinRadius {
+            center: Point::new(0.0, 0.0),
+            radius: 100.0,
+        };
+
+        let both = low.clone().and(near.clone()).and(low.clone());
+        assert!(matches!(&both, Predicate::All(all) if all.len() == 3));
+        assert!(
+            matches!(low.clone().or(near.clone()).or(near.clone()), Predicate::Any(any) if any.len() == 3)
+        );
+        assert_eq!(!!low.clone(), low);
+
+        let json = serde_json::to_string(&both).unwrap();
+        assert_eq!(serde_json::from_str::<Predicate>(&json).unwrap(), both);
+    }
+}
@@ -0,0 +1,352 @@
+//! A small text language for reading and writing objects, and the
+//! [`Statement`]s it parses into.
+//!
+//! ```text
+//! GET fleet van-1
+//! NEARBY fleet POINT(-74 40.7) 500 LIMIT 10
+//! NEAREST fleet POINT(-74 40.7) 3
+//! WITHIN fleet BOX(-75 40, -73 41) LIMIT 10
+//! HISTORY fleet van-1 SINCE 1700000000 UNTIL 1700003600 LIMIT 20
+//! HISTORY fleet van-1 LAST 3600
+//! INSERT fleet van-1 POINT Z(-74 40.7 120) {"kind": "van"}
+//! ```
+//!
+//! Keywords are case-insensitive, namespaces and object ids are single words,
+//! and geometries are WKT, read by the [`wkt`](crate::wkt) codec. Distances
+//! are meters and times Unix seconds. `LIMIT` defaults to [`DEFAULT_LIMIT`].
+//!
+//! Queries parse into the [`wire`](crate::wire) structs the transports
+//! already take, so a server can accept a [`Statement`], as text or
+//! serialized, and answer it with the code behind its other requests.
+
+use crate::bbox::BoundingBox2D;
+use crate::point::Point3d;
+use crate::prelude::*;
+use crate::time::TimeRange;
+use crate::wire::{KnnQuery, RadiusQuery, TrajectorySlice};
+use crate::wkt::WktError;
+use core::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+/// Results returned when a statement has no `LIMIT`.
+pub const DEFAULT_LIMIT: usize = 100;
+
+/// One parsed statement.
+///
+/// # Examples
+///
+/// ```
+/// use spatio_types::statement::Statement;
+///
+/// let statement: Statement = "NEARBY vehicles POINT(-74 40.7) 500 LIMIT 10".parse().unwrap();
+/// let Statement::Nearby(query) = &statement else { unreachable!() };
+/// assert_eq!(query.namespace, "vehicles");
+/// assert_eq!((query.radius, query.limit), (500.0, 10));
+///
+/// let json = serde_json::to_string(&statement).unwrap();
+/// assert_eq!(serde_json::from_str::<Statement>(&json).unwrap(), statement);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Statement {
+    /// `GET ns id`: an object's current location.
+    Get {
+        namespace: String,
+        object_id: String,
+    },
+    /// `NEARBY ns POINT(..) radius [LIMIT n]`: objects within `radius`
+    /// meters, nearest first.
+    Nearby(RadiusQuery),
+    /// `NEAREST ns POINT(..) k`: the `k` nearest objects.
+    Nearest(KnnQuery),
+    /// `WITHIN ns BOX(..) [LIMIT n]`: objects inside a longitude/latitude box.
+    Within {
+        namespace: String,
+        bbox: BoundingBox2D,
+        limit: usize,
+    },
+    /// `HISTORY ns id [SINCE t | LAST secs] [UNTIL t] [LIMIT n]`: an object's
+    /// trajectory, newest first.
+    History(TrajectorySlice),
+    /// `INSERT ns id POINT(..) [metadata]`: insert or move an object. The
+    /// metadata is the JSON after the point, `{}` when there is none.
+    Insert {
+        namespace: String,
+        object_id: String,
+        position: Point3d,
+        metadata: serde_json::Value,
+    },
+}
+
+impl Statement {
+    /// Whether running the statement changes the database.
+    pub fn is_write(&self) -> bool {
+        matches!(self, Statement::Insert { .. })
+    }
+}
+
+/// Error type for parsing statements.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum StatementError {
+    /// Not a statement of the language
+    Syntax(String),
+    /// A geometry that isn't valid WKT or not of the kind expected
+    Geometry(WktError),
+}
+
+impl core::fmt::Display for StatementError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Syntax(msg) => write!(f, "Syntax error: {}", msg),
+            Self::Geometry(e) => e.fmt(f),
+        }
+    }
+}
+
+impl core::error::Error for StatementError {}
+
+impl From<WktError> for StatementError {
+    fn from(e: WktError) -> Self {
+        StatementError::Geometry(e)
+    }
+}
+
+fn syntax(msg: impl Into<String>) -> StatementError {
+    StatementError::Syntax(msg.into())
+}
+
+impl FromStr for Statement {
+    type Err = StatementError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { rest: s };
+        let keyword = parser.word("a statement")?.to_ascii_uppercase();
+        let statement = match keyword.as_str() {
+            "GET" => Statement::Get {
+                namespace: parser.word("a namespace")?.into(),
+                object_id: parser.word("an object id")?.into(),
+            },
+            "NEARBY" => {
+                let namespace = parser.word("a namespace")?;
+                let center = parser.geometry("POINT")?;
+                let radius = parser.number("a radius")?;
+                let limit = parser.limit()?;
+                Statement::Nearby(RadiusQuery::new(namespace, center, radius, limit))
+            }
+            "NEAREST" => {
+                let namespace = parser.word("a namespace")?;
+                let center = parser.geometry("POINT")?;
+                let k = parser.number("a count")?;
+                Statement::Nearest(KnnQuery::new(namespace, center, k))
+            }
+            "WITHIN" => Statement::Within {
+                namespace: parser.word("a namespace")?.into(),
+                bbox: parser.geometry("BOX")?,
+                limit: parser.limit()?,
+            },
+            "HISTORY" => {
+                let namespace = parser.word("a namespace")?;
+                let object_id = parser.word("an object id")?;
+                let (mut since, mut until, mut last) = (None, None, None);
+                while let Some(clause) = parser.keyword(&["SINCE", "UNTIL", "LAST"]) {
+                    let slot = match clause {
+                        "SINCE" => &mut since,
+                        "UNTIL" => &mut until,
+                        _ => &mut last,
+                    };
+                    *slot = Some(parser.number("seconds")?);
+                }
+                let range = TimeRange::from_secs(since, until, last).map_err(syntax)?;
+                let limit = parser.limit()?;
+                Statement::History(TrajectorySlice::new(namespace, object_id, range, limit))
+            }
+            "INSERT" => {
+                let namespace = parser.word("a namespace")?.into();
+                let object_id = parser.word("an object id")?.into();
+                let position = parser.geometry("POINT")?;
+                let metadata = match parser.rest.trim() {
+                    "" => serde_json::Value::Object(Default::default()),
+                    json => serde_json::from_str(json)
+                        .map_err(|e| syntax(format!("metadata is not valid JSON: {e}")))?,
+                };
+                parser.rest = "";
+                Statement::Insert {
+                    namespace,
+                    object_id,
+                    position,
+                    metadata,
+                }
+            }
+            _ => return Err(syntax(format!("unknown statement {keyword:?}"))),
+        };
+        match parser.rest.trim() {
+            "" => Ok(statement),
+            rest => Err(syntax(format!("unexpected trailing text {rest:?}"))),
+        }
+    }
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    /// The next whitespace-separated word.
+    fn word(&mut self, expected: &str) -> Result<&'a str, StatementError> {
+        let rest = self.rest.trim_start();
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if end == 0 {
+            return Err(syntax(format!("expected {expected}")));
+        }
+        self.rest = &rest[end..];
+        Ok(&rest[..end])
+    }
+
+    fn number<T: FromStr>(&mut self, expected: &str) -> Result<T, StatementError> {
+        let word = self.word(expected)?;
+        word.parse()
+            .map_err(|_| syntax(format!("expected {expected}, found {word:?}")))
+    }
+
+    /// Consume the next word if it is one of `keywords`, ignoring case.
+    fn keyword(&mut self, keywords: &[&'static str]) -> Option<&'static str> {
+        let mut peek = Parser { rest: self.rest };
+        let word = peek.word("").ok()?;
+        let keyword = keywords
+            .iter()
+            .find(|keyword| word.eq_ignore_ascii_case(keyword))?;
+        self.rest = peek.rest;
+        Some(keyword)
+    }
+
+    /// An optional trailing `LIMIT n`.
+    fn limit(&mut self) -> Result<usize, StatementError> {
+        match self.keyword(&["LIMIT"]) {
+            Some(_) => self.number("a limit"),
+            None => Ok(DEFAULT_LIMIT),
+        }
+    }
+
+    /// A WKT geometry starting with `kind`, up to its closing parenthesis.
+    fn geometry<T>(&mut self, kind: &str) -> Result<T, StatementError>
+    where
+        T: FromStr<Err = WktError>,
+    {
+        let rest = self.rest.trim_start();
+        let starts_with_kind = rest
+            .get(..kind.len())
+            .is_some_and(|head| head.eq_ignore_ascii_case(kind));
+        let open = rest.find('(').filter(|_| starts_with_kind);
+        let Some(open) = open else {
+            return Err(syntax(format!("expected {kind}(...)")));
+        };
+        let mut depth = 0;
+        for (i, c) in rest[open..].char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => continue,
+            }
+            if depth == 0 {
+                let end = open + i + 1;
+                self.rest = &rest[end..];
+                return Ok(rest[..end].parse()?);
+            }
+        }
+        Err(syntax(format!("unclosed parenthesis in {kind}(...)")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::TimeBound;
+
+    fn parse(s: &str) -> Result<Statement, StatementError> {
+        s.parse()
+    }
+
+    #[test]
+    fn test_parses_queries() {
+        assert_eq!(
+            parse("nearby vehicles point(-74 40.7) 500").unwrap(),
+            Statement::Nearby(RadiusQuery::new(
+                "vehicles",
+                Point3d::new(-74.0, 40.7, 0.0),
+                500.0,
+                DEFAULT_LIMIT
+            ))
+        );
+        assert_eq!(
+            parse("NEAREST fleet POINT Z (1 2 30) 3").unwrap(),
+            Statement::Nearest(KnnQuery::new("fleet", Point3d::new(1.0, 2.0, 30.0), 3))
+        );
+        assert_eq!(
+            parse("WITHIN fleet BOX(-75 40, -73 41) LIMIT 5").unwrap(),
+            Statement::Within {
+                namespace: "fleet".into(),
+                bbox: BoundingBox2D::new(-75.0, 40.0, -73.0, 41.0),
+                limit: 5,
+            }
+        );
+
+        let Statement::History(slice) = parse("HISTORY fleet van-1 LAST 60 LIMIT 2").unwrap()
+        else {
+            panic!("not a history statement");
+        };
+        assert_eq!(slice.object_id, "van-1");
+        assert_eq!(slice.limit, 2);
+        assert_eq!(
+            slice.range.start,
+            TimeBound::Ago(core::time::Duration::from_secs(60))
+        );
+        assert!(parse("HISTORY fleet van-1 SINCE 10 LAST 60").is_err());
+    }
+
+    #[test]
+    fn test_parses_insert_metadata() {
+        let insert = parse(r#"INSERT fleet van-1 POINT(1 2) {"kind": "van", "seats": 3}"#).unwrap();
+        assert!(insert.is_write());
+        let Statement::Insert { metadata, .. } = insert else {
+            panic!("not an insert");
+        };
+        assert_eq!(metadata["seats"], 3);
+
+        let Statement::Insert { metadata, .. } = parse("INSERT fleet van-1 POINT(1 2)").unwrap()
+        else {
+            panic!("not an insert");
+        };
+        assert_eq!(metadata, serde_json::json!({}));
+    }
+
+    #[test]
+    fn test_rejects_malformed_statements() {
+        assert!(matches!(parse(""), Err(StatementError::Syntax(_))));
+        assert!(matches!(
+            parse("DELETE fleet van-1"),
+            Err(StatementError::Syntax(_))
+        ));
+        assert!(matches!(parse("GET fleet"), Err(StatementError::Syntax(_))));
+        assert!(matches!(
+            parse("NEARBY fleet POINT(1 2) far"),
+            Err(StatementError::Syntax(_))
+        ));
+        assert!(matches!(
+            parse("NEARBY fleet POINT(1 2 500"),
+            Err(StatementError::Syntax(_))
+        ));
+        assert!(matches!(
+            parse("NEARBY fleet POINT(1) 500"),
+            Err(StatementError::Geometry(_))
+        ));
+        assert!(matches!(
+            parse("WITHIN fleet POINT(1 2)"),
+            Err(StatementError::Syntax(_))
+        ));
+        assert!(matches!(
+            parse("GET fleet van-1 now"),
+            Err(StatementError::Syntax(_))
+        ));
+    }
+}
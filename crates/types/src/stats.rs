@@ -1,9 +1,179 @@
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
 use serde::{Deserialize, Serialize};
 
+/// Kind of database operation, as counted in [`MinuteStats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Upsert,
+    InsertTrajectory,
+    /// Deletes and erasures.
+    Delete,
+    Get,
+    Range,
+    /// Radius queries, around a point or an object.
+    QueryRadius,
+    QueryBbox,
+    QueryBbox3d,
+    QueryCylinder,
+    Knn,
+    QueryPolygon,
+    /// Composite predicate queries and materialized view reads.
+    Query,
+    QueryTrajectory,
+    /// Zone inserts and deletes.
+    ZoneWrite,
+    /// Zone lookups and zone distance queries.
+    ZoneRead,
+    /// Distances between objects or to points.
+    Distance,
+    /// Convex hulls, bounding boxes and k-anonymous aggregates.
+    Aggregate,
+    Export,
+}
+
+impl Operation {
+    /// Every operation kind, in declaration order.
+    pub const ALL: [Operation; 18] = [
+        Operation::Upsert,
+        Operation::InsertTrajectory,
+        Operation::Delete,
+        Operation::Get,
+        Operation::Range,
+        Operation::QueryRadius,
+        Operation::QueryBbox,
+        Operation::QueryBbox3d,
+        Operation::QueryCylinder,
+        Operation::Knn,
+        Operation::QueryPolygon,
+        Operation::Query,
+        Operation::QueryTrajectory,
+        Operation::ZoneWrite,
+        Operation::ZoneRead,
+        Operation::Distance,
+        Operation::Aggregate,
+        Operation::Export,
+    ];
+
+    /// Whether the operation writes objects (zones aside), as counted by
+    /// [`DbStats::updates_per_sec`].
+    pub fn is_update(self) -> bool {
+        matches!(
+            self,
+            Operation::Upsert | Operation::InsertTrajectory | Operation::Delete
+        )
+    }
+
+    /// Whether the operation only reads, as counted by
+    /// [`DbStats::queries_count`].
+    pub fn is_query(self) -> bool {
+        !self.is_update() && self != Operation::ZoneWrite
+    }
+}
+
+/// Operations performed during one wall-clock minute.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MinuteStats {
+    /// Start of the minute, in minutes since the Unix epoch
+    pub minute: u64,
+    /// Non-zero counts by namespace, then operation
+    pub counts: BTreeMap<String, BTreeMap<Operation, u64>>,
+}
+
+impl MinuteStats {
+    /// Count of `operation` across all namespaces.
+    pub fn total(&self, operation: Operation) -> u64 {
+        self.counts
+            .values()
+            .filter_map(|ops| ops.get(&operation))
+            .sum()
+    }
+}
+
+/// Upper bounds, in seconds, of the buckets of a default
+/// [`LatencyHistogram`]: 100µs to 10s.
+pub const DEFAULT_LATENCY_BUCKETS: [f64; 16] = [
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+    5.0, 10.0,
+];
+
+/// Latencies counted in buckets, like a Prometheus histogram.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatencyHistogram {
+    /// Upper bounds of the buckets, in seconds, ascending
+    pub bounds_secs: Vec<f64>,
+    /// Latencies in each bucket: `counts[i]` are at most `bounds_secs[i]`
+    /// and above the bound before it; the last entry counts the ones above
+    /// every bound
+    pub counts: Vec<u64>,
+    /// Sum of every latency, in seconds
+    pub sum_secs: f64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::with_bounds(DEFAULT_LATENCY_BUCKETS.to_vec())
+    }
+}
+
+impl LatencyHistogram {
+    /// An empty histogram with buckets up to `bounds_secs`, which must be
+    /// ascending.
+    pub fn with_bounds(bounds_secs: Vec<f64>) -> Self {
+        let counts = vec![0; bounds_secs.len() + 1];
+        Self {
+            bounds_secs,
+            counts,
+            sum_secs: 0.0,
+        }
+    }
+
+    /// Count a latency of `secs` seconds.
+    pub fn observe(&mut self, secs: f64) {
+        let bucket = self.bounds_secs.partition_point(|bound| *bound < secs);
+        if let Some(count) = self.counts.get_mut(bucket) {
+            *count += 1;
+        }
+        self.sum_secs += secs;
+    }
+
+    /// Number of latencies counted.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Each bucket's upper bound with the number of latencies at most that
+    /// bound, ending with `f64::INFINITY` and the total.
+    pub fn cumulative(&self) -> impl Iterator<Item = (f64, u64)> + '_ {
+        self.bounds_secs
+            .iter()
+            .copied()
+            .chain(core::iter::once(f64::INFINITY))
+            .zip(self.counts.iter().scan(0, |total, count| {
+                *total += count;
+                Some(*total)
+            }))
+    }
+}
+
+/// Whether writes are reaching disk.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "state")]
+pub enum PersistenceStatus {
+    #[default]
+    Healthy,
+    /// The log can't be written (read-only or full disk). Reads and
+    /// in-memory writes go on, durable writes fail, and nothing written
+    /// since survives a restart.
+    DegradedPersistence { reason: String },
+}
+
 /// Database statistics
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DbStats {
-    /// Number of items that have expired
+    /// Trajectory history records removed by expiration since the database
+    /// was opened
     pub expired_count: u64,
     /// Total number of operations performed
     pub operations_count: u64,
@@ -11,12 +181,42 @@ pub struct DbStats {
     pub size_bytes: usize,
     /// Total number of objects currently tracked in hot state
     pub hot_state_objects: usize,
+    /// Objects currently tracked, by namespace
+    #[serde(default)]
+    pub namespace_objects: BTreeMap<String, usize>,
+    /// Approximate memory used by the hot state, in bytes
+    #[serde(default)]
+    pub hot_state_memory_bytes: usize,
     /// Number of trajectories stored in cold state
     pub cold_state_trajectories: usize,
     /// Bytes used in cold state buffer
     pub cold_state_buffer_bytes: usize,
     /// Approximate total memory usage in bytes
     pub memory_usage_bytes: usize,
+    /// Bytes in the trajectory log on disk, 0 for an in-memory database
+    #[serde(default)]
+    pub cold_log_bytes: u64,
+    /// Reads and queries served since the database was opened
+    #[serde(default)]
+    pub queries_count: u64,
+    /// Upserts, trajectory inserts and deletes per second during the last
+    /// complete minute
+    #[serde(default)]
+    pub updates_per_sec: f64,
+    /// Operation counts for each recent minute that had any, oldest first;
+    /// the last entry may be the current, partial minute
+    #[serde(default)]
+    pub per_minute: Vec<MinuteStats>,
+    /// Free bytes on the log's file system at the last check, when disk
+    /// watermarks are configured
+    #[serde(default)]
+    pub disk_available_bytes: Option<u64>,
+    /// Whether writes are reaching disk
+    #[serde(default)]
+    pub persistence: PersistenceStatus,
+    /// Time taken by each `fsync` of the trajectory log since open
+    #[serde(default)]
+    pub fsync_latency: LatencyHistogram,
 }
 
 impl DbStats {
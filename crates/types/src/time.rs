@@ -1,6 +1,19 @@
 //! Time-conversion helpers shared across the workspace.
+//!
+//! Timestamps are [`SystemTime`]s: the standard library's with the `std`
+//! feature, and otherwise a stand-in with the same methods the crate uses and
+//! the same serialized form.
 
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::prelude::*;
+use core::ops::{Range, RangeFrom, RangeFull, RangeInclusive, RangeTo, RangeToInclusive};
+use core::time::Duration;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "std")]
+pub use std::time::{SystemTime, SystemTimeError, UNIX_EPOCH};
+
+#[cfg(not(feature = "std"))]
+pub use self::no_std_time::{SystemTime, SystemTimeError, UNIX_EPOCH};
 
 /// Convert an `f64` of seconds-since-the-Unix-epoch into a [`SystemTime`].
 ///
@@ -15,3 +28,333 @@ pub fn system_time_from_secs(secs: f64) -> Result<SystemTime, String> {
         .checked_add(dur)
         .ok_or_else(|| format!("timestamp out of range: {secs}"))
 }
+
+/// One end of a [`TimeRange`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TimeBound {
+    /// No bound on this end.
+    #[default]
+    Open,
+    /// A fixed point in time.
+    At(SystemTime),
+    /// This long before the moment the range is resolved, so "the last 15
+    /// minutes" means the same thing whenever it is sent.
+    Ago(Duration),
+}
+
+/// A window of time for the trajectory and history queries. Both ends are
+/// inclusive and either may be open or relative to now.
+///
+/// Built from Rust ranges of [`SystemTime`], where `a..b` includes `b` like
+/// `a..=b` does, or with the constructors below:
+///
+/// ```rust
+/// use spatio_types::time::TimeRange;
+/// use std::time::{Duration, SystemTime, UNIX_EPOCH};
+///
+/// let now = SystemTime::now();
+/// let last = TimeRange::last(Duration::from_secs(15 * 60));
+/// assert_eq!(last.resolve(now).0, now - Duration::from_secs(15 * 60));
+///
+/// let (start, end) = TimeRange::from(..now).resolve(now);
+/// assert_eq!((start, end), (UNIX_EPOCH, now));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub start: TimeBound,
+    pub end: TimeBound,
+}
+
+impl TimeRange {
+    /// All of time.
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// From `start` to `end`.
+    pub fn between(start: SystemTime, end: SystemTime) -> Self {
+        Self {
+            start: TimeBound::At(start),
+            end: TimeBound::At(end),
+        }
+    }
+
+    /// Everything from `start` on.
+    pub fn since(start: SystemTime) -> Self {
+        Self {
+            start: TimeBound::At(start),
+            end: TimeBound::Open,
+        }
+    }
+
+    /// Everything up to `end`.
+    pub fn until(end: SystemTime) -> Self {
+        Self {
+            start: TimeBound::Open,
+            end: TimeBound::At(end),
+        }
+    }
+
+    /// The `duration` before the range is resolved, and anything after it.
+    pub fn last(duration: Duration) -> Self {
+        Self {
+            start: TimeBound::Ago(duration),
+            end: TimeBound::Open,
+        }
+    }
+
+    /// Build a range from optional seconds since the Unix epoch, the way the
+    /// RPC and Python APIs take them, with `last` seconds standing in for
+    /// the start. Fails on invalid timestamps or if both `start` and `last`
+    /// are given.
+    pub fn from_secs(
+        start: Option<f64>,
+        end: Option<f64>,
+        last: Option<f64>,
+    ) -> Result<Self, String> {
+        let start = match (start, last) {
+            (Some(_), Some(_)) => return Err("pass either a start time or `last`, not both".into()),
+            (Some(secs), None) => TimeBound::At(system_time_from_secs(secs)?),
+            (None, Some(secs)) => TimeBound::Ago(
+                Duration::try_from_secs_f64(secs)
+                    .map_err(|e| format!("invalid duration {secs}: {e}"))?,
+            ),
+            (None, None) => TimeBound::Open,
+        };
+        let end = match end {
+            Some(secs) => TimeBound::At(system_time_from_secs(secs)?),
+            None => TimeBound::Open,
+        };
+        Ok(Self { start, end })
+    }
+
+    /// The concrete `(start, end)` of the range as of `now`. An open start is
+    /// the Unix epoch and an open end lies far enough in the future to cover
+    /// any stored timestamp.
+    pub fn resolve(&self, now: SystemTime) -> (SystemTime, SystemTime) {
+        let resolve = |bound: TimeBound, open: SystemTime| match bound {
+            TimeBound::Open => open,
+            TimeBound::At(t) => t,
+            TimeBound::Ago(d) => now.checked_sub(d).unwrap_or(UNIX_EPOCH).max(UNIX_EPOCH),
+        };
+        (
+            resolve(self.start, UNIX_EPOCH),
+            resolve(self.end, far_future()),
+        )
+    }
+}
+
+/// The end of an open range: about 35,000 years out, which every platform's
+/// [`SystemTime`] can represent and which still fits in `u64` microseconds.
+fn far_future() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(1 << 40)
+}
+
+impl From<RangeFull> for TimeRange {
+    fn from(_: RangeFull) -> Self {
+        Self::all()
+    }
+}
+
+impl From<Range<SystemTime>> for TimeRange {
+    fn from(range: Range<SystemTime>) -> Self {
+        Self::between(range.start, range.end)
+    }
+}
+
+impl From<RangeInclusive<SystemTime>> for TimeRange {
+    fn from(range: RangeInclusive<SystemTime>) -> Self {
+        let (start, end) = range.into_inner();
+        Self::between(start, end)
+    }
+}
+
+impl From<RangeFrom<SystemTime>> for TimeRange {
+    fn from(range: RangeFrom<SystemTime>) -> Self {
+        Self::since(range.start)
+    }
+}
+
+impl From<RangeTo<SystemTime>> for TimeRange {
+    fn from(range: RangeTo<SystemTime>) -> Self {
+        Self::until(range.end)
+    }
+}
+
+impl From<RangeToInclusive<SystemTime>> for TimeRange {
+    fn from(range: RangeToInclusive<SystemTime>) -> Self {
+        Self::until(range.end)
+    }
+}
+
+#[cfg(any(test, not(feature = "std")))]
+mod no_std_time {
+    use core::fmt;
+    use core::ops::{Add, AddAssign, Sub, SubAssign};
+    use core::time::Duration;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+
+    /// A point in wall-clock time, held as the time since the Unix epoch.
+    ///
+    /// Without an operating system there is no clock to read, so there is no
+    /// `now`; build one from the device's clock as
+    /// `UNIX_EPOCH + Duration::from_secs(..)`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct SystemTime(Duration);
+
+    /// The Unix epoch, 1970-01-01 00:00:00 UTC.
+    pub const UNIX_EPOCH: SystemTime = SystemTime(Duration::ZERO);
+
+    impl SystemTime {
+        pub const UNIX_EPOCH: SystemTime = UNIX_EPOCH;
+
+        /// Time elapsed from `earlier` to `self`, or how far `earlier` is
+        /// ahead as the error.
+        pub fn duration_since(&self, earlier: SystemTime) -> Result<Duration, SystemTimeError> {
+            self.0
+                .checked_sub(earlier.0)
+                .ok_or_else(|| SystemTimeError(earlier.0 - self.0))
+        }
+
+        pub fn checked_add(&self, duration: Duration) -> Option<SystemTime> {
+            self.0.checked_add(duration).map(SystemTime)
+        }
+
+        /// `None` before the Unix epoch, which this type cannot represent.
+        pub fn checked_sub(&self, duration: Duration) -> Option<SystemTime> {
+            self.0.checked_sub(duration).map(SystemTime)
+        }
+    }
+
+    impl Add<Duration> for SystemTime {
+        type Output = SystemTime;
+
+        fn add(self, duration: Duration) -> SystemTime {
+            self.checked_add(duration)
+                .expect("overflow when adding duration to instant")
+        }
+    }
+
+    impl AddAssign<Duration> for SystemTime {
+        fn add_assign(&mut self, duration: Duration) {
+            *self = *self + duration;
+        }
+    }
+
+    impl Sub<Duration> for SystemTime {
+        type Output = SystemTime;
+
+        fn sub(self, duration: Duration) -> SystemTime {
+            self.checked_sub(duration)
+                .expect("overflow when subtracting duration from instant")
+        }
+    }
+
+    impl SubAssign<Duration> for SystemTime {
+        fn sub_assign(&mut self, duration: Duration) {
+            *self = *self - duration;
+        }
+    }
+
+    /// Returned by [`SystemTime::duration_since`] when the argument is later.
+    #[derive(Debug, Clone)]
+    pub struct SystemTimeError(Duration);
+
+    impl SystemTimeError {
+        /// How far the argument was ahead.
+        pub fn duration(&self) -> Duration {
+            self.0
+        }
+    }
+
+    impl fmt::Display for SystemTimeError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "second time provided was later than self")
+        }
+    }
+
+    impl core::error::Error for SystemTimeError {}
+
+    /// Serde's representation of `std::time::SystemTime`.
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "SystemTime")]
+    struct Repr {
+        secs_since_epoch: u64,
+        nanos_since_epoch: u32,
+    }
+
+    impl Serialize for SystemTime {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Repr {
+                secs_since_epoch: self.0.as_secs(),
+                nanos_since_epoch: self.0.subsec_nanos(),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for SystemTime {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let repr = Repr::deserialize(deserializer)?;
+            if repr.nanos_since_epoch >= 1_000_000_000 {
+                return Err(de::Error::custom("nanos_since_epoch out of range"));
+            }
+            Ok(SystemTime(Duration::new(
+                repr.secs_since_epoch,
+                repr.nanos_since_epoch,
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_range_resolution() {
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        let now = at(1_000);
+
+        assert_eq!(TimeRange::from(at(5)..at(9)).resolve(now), (at(5), at(9)));
+        assert_eq!(TimeRange::from(at(5)..).resolve(now).0, at(5));
+        assert!(TimeRange::from(at(5)..).resolve(now).1 > at(u32::MAX as u64));
+        assert_eq!(TimeRange::from(..=at(9)).resolve(now), (UNIX_EPOCH, at(9)));
+
+        let last = TimeRange::last(Duration::from_secs(60));
+        assert_eq!(last.resolve(now).0, at(940));
+        assert_eq!(last.resolve(at(10)).0, UNIX_EPOCH);
+
+        let range = TimeRange::from_secs(None, Some(9.0), Some(60.0)).unwrap();
+        assert_eq!(range.resolve(now), (at(940), at(9)));
+        assert!(TimeRange::from_secs(Some(1.0), None, Some(60.0)).is_err());
+        assert!(TimeRange::from_secs(None, None, Some(-1.0)).is_err());
+    }
+
+    #[test]
+    fn test_no_std_time_matches_std() {
+        let at = std::time::UNIX_EPOCH + Duration::new(1_700_000_000, 250);
+        let stand_in = no_std_time::SystemTime::UNIX_EPOCH + Duration::new(1_700_000_000, 250);
+
+        let json = serde_json::to_string(&at).unwrap();
+        assert_eq!(serde_json::to_string(&stand_in).unwrap(), json);
+        assert_eq!(
+            serde_json::from_str::<no_std_time::SystemTime>(&json).unwrap(),
+            stand_in
+        );
+
+        let earlier = stand_in - Duration::from_secs(10);
+        assert_eq!(
+            stand_in.duration_since(earlier).unwrap(),
+            Duration::from_secs(10)
+        );
+        assert_eq!(
+            earlier.duration_since(stand_in).unwrap_err().duration(),
+            Duration::from_secs(10)
+        );
+        assert_eq!(
+            no_std_time::UNIX_EPOCH.checked_sub(Duration::from_nanos(1)),
+            None
+        );
+    }
+}
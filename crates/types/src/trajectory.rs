@@ -0,0 +1,461 @@
+use crate::geo::Point;
+use crate::point::TemporalPoint3D;
+use crate::prelude::*;
+use crate::time::{UNIX_EPOCH, system_time_from_secs};
+use crate::wkt::{Geometry, Kind, WktError};
+use core::str::FromStr;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use geo::{Bearing, Coord, Haversine, LineString, SimplifyIdx, SimplifyVwIdx};
+use serde::{Deserialize, Serialize};
+
+/// The points an object reported, oldest first.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct Trajectory {
+    points: Vec<TemporalPoint3D>,
+}
+
+impl Trajectory {
+    pub fn new(points: Vec<TemporalPoint3D>) -> Self {
+        Self { points }
+    }
+
+    pub fn points(&self) -> &[TemporalPoint3D] {
+        &self.points
+    }
+
+    pub fn into_points(self) -> Vec<TemporalPoint3D> {
+        self.points
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Drop points using Douglas–Peucker: a point is kept only if leaving it
+    /// out moves the path by more than `epsilon`, measured in coordinate units
+    /// (degrees for longitude/latitude) in the horizontal plane.
+    ///
+    /// Retained points keep their timestamps and altitudes, and the first and
+    /// last points are always kept. A non-positive `epsilon` keeps every point.
+    ///
+    /// ```
+    /// use spatio_types::geo::Point;
+    /// use spatio_types::point::TemporalPoint3D;
+    /// use spatio_types::trajectory::Trajectory;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let trajectory = Trajectory::new(
+    ///     [(0.0, 0.0), (1.0, 0.01), (2.0, 0.0)]
+    ///         .into_iter()
+    ///         .enumerate()
+    ///         .map(|(i, (x, y))| {
+    ///             let at = UNIX_EPOCH + Duration::from_secs(i as u64);
+    ///             TemporalPoint3D::new(Point::new(x, y), 0.0, at)
+    ///         })
+    ///         .collect(),
+    /// );
+    /// let simplified = trajectory.simplify(0.1);
+    /// assert_eq!(simplified.len(), 2);
+    /// assert_eq!(simplified.points()[1].timestamp, UNIX_EPOCH + Duration::from_secs(2));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn simplify(&self, epsilon: f64) -> Trajectory {
+        if epsilon <= 0.0 {
+            return self.clone();
+        }
+        self.retain_indices(self.line().simplify_idx(epsilon))
+    }
+
+    /// Drop points using Visvalingam–Whyatt: points are removed, smallest
+    /// first, while the triangle each forms with its neighbours has an area
+    /// below `epsilon` in squared coordinate units.
+    ///
+    /// Retained points keep their timestamps and altitudes, and the first and
+    /// last points are always kept. A non-positive `epsilon` keeps every point.
+    #[cfg(feature = "std")]
+    pub fn simplify_vw(&self, epsilon: f64) -> Trajectory {
+        if epsilon <= 0.0 {
+            return self.clone();
+        }
+        self.retain_indices(self.line().simplify_vw_idx(epsilon))
+    }
+
+    #[cfg(feature = "std")]
+    fn line(&self) -> LineString<f64> {
+        self.points
+            .iter()
+            .map(|p| Coord {
+                x: p.point.x(),
+                y: p.point.y(),
+            })
+            .collect()
+    }
+
+    /// The points at `indices`, which are ascending.
+    #[cfg(feature = "std")]
+    fn retain_indices(&self, indices: Vec<usize>) -> Trajectory {
+        // The simplifiers return nothing for a line of fewer than two points.
+        if self.points.len() < 2 {
+            return self.clone();
+        }
+        Trajectory::new(
+            indices
+                .into_iter()
+                .map(|i| self.points[i].clone())
+                .collect(),
+        )
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl ArchivedTrajectory {
+    pub fn points(&self) -> &[crate::point::ArchivedTemporalPoint3D] {
+        &self.points
+    }
+}
+
+/// Kinematics of a trajectory, from [`Trajectory::summary`].
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct TrajectorySummary {
+    /// Number of points.
+    pub points: usize,
+    /// Haversine distance along the points, in meters.
+    pub total_distance: f64,
+    /// Time from the first point to the last.
+    pub duration: Duration,
+    /// `total_distance` over `duration`, in meters per second; `None` if no
+    /// time elapsed.
+    pub average_speed: Option<f64>,
+    /// Highest segment speed, in meters per second; `None` if no segment
+    /// has a speed.
+    pub max_speed: Option<f64>,
+}
+
+/// Derived kinematics. Distances are haversine distances in meters with
+/// altitude ignored, and each segment joins two consecutive points. All but
+/// [`Trajectory::duration`] need the `std` feature.
+impl Trajectory {
+    /// Distance along the points, in meters.
+    #[cfg(feature = "std")]
+    pub fn total_distance(&self) -> f64 {
+        self.points
+            .windows(2)
+            .map(|w| w[0].point.haversine_distance(&w[1].point))
+            .sum()
+    }
+
+    /// Time from the first point to the last; zero for fewer than two points.
+    pub fn duration(&self) -> Duration {
+        match (self.points.first(), self.points.last()) {
+            (Some(first), Some(last)) => last
+                .timestamp
+                .duration_since(first.timestamp)
+                .unwrap_or_default(),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// [`Trajectory::total_distance`] over [`Trajectory::duration`], in
+    /// meters per second; `None` if no time elapsed.
+    #[cfg(feature = "std")]
+    pub fn average_speed(&self) -> Option<f64> {
+        let secs = self.duration().as_secs_f64();
+        (secs > 0.0).then(|| self.total_distance() / secs)
+    }
+
+    /// Speed of each segment in meters per second, oldest first. Segments
+    /// whose points share a timestamp have no speed and are skipped.
+    ///
+    /// ```
+    /// use spatio_types::geo::Point;
+    /// use spatio_types::point::TemporalPoint3D;
+    /// use spatio_types::trajectory::Trajectory;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// // Due north along a meridian, 0.001° (~111 m) every 10 seconds.
+    /// let trajectory = Trajectory::new(
+    ///     (0..3)
+    ///         .map(|i| {
+    ///             let at = UNIX_EPOCH + Duration::from_secs(10 * i);
+    ///             TemporalPoint3D::new(Point::new(0.0, 0.001 * i as f64), 0.0, at)
+    ///         })
+    ///         .collect(),
+    /// );
+    /// let speeds = trajectory.speeds();
+    /// assert_eq!(speeds.len(), 2);
+    /// assert!((speeds[0] - 11.1).abs() < 0.1);
+    /// assert!(trajectory.headings()[0].abs() < 1e-6);
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn speeds(&self) -> Vec<f64> {
+        self.timed_speeds().map(|(_, speed)| speed).collect()
+    }
+
+    /// Change of speed between consecutive timed segments, in meters per
+    /// second squared, oldest first. Each is taken over the time between the
+    /// segments' midpoints.
+    #[cfg(feature = "std")]
+    pub fn accelerations(&self) -> Vec<f64> {
+        let speeds: Vec<_> = self.timed_speeds().collect();
+        speeds
+            .windows(2)
+            .map(|w| (w[1].1 - w[0].1) / (w[1].0 - w[0].0))
+            .collect()
+    }
+
+    /// Initial bearing of each segment in degrees clockwise from north
+    /// (`0.0..360.0`), oldest first. Segments where the object didn't move
+    /// have no heading and are skipped.
+    #[cfg(feature = "std")]
+    pub fn headings(&self) -> Vec<f64> {
+        self.points
+            .windows(2)
+            .filter(|w| w[0].point != w[1].point)
+            .map(|w| Haversine.bearing(*w[0].point.inner(), *w[1].point.inner()))
+            .collect()
+    }
+
+    /// Point count, distance, duration and average and top speed together.
+    #[cfg(feature = "std")]
+    pub fn summary(&self) -> TrajectorySummary {
+        TrajectorySummary {
+            points: self.len(),
+            total_distance: self.total_distance(),
+            duration: self.duration(),
+            average_speed: self.average_speed(),
+            max_speed: self.timed_speeds().map(|(_, speed)| speed).reduce(f64::max),
+        }
+    }
+
+    /// `(midpoint, speed)` of each segment with elapsed time, the midpoint in
+    /// seconds since the first point.
+    #[cfg(feature = "std")]
+    fn timed_speeds(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        let origin = self.points.first().map(|p| p.timestamp);
+        self.points.windows(2).filter_map(move |w| {
+            let elapsed = w[1]
+                .timestamp
+                .duration_since(w[0].timestamp)
+                .ok()
+                .filter(|d| !d.is_zero())?
+                .as_secs_f64();
+            let since = w[0]
+                .timestamp
+                .duration_since(origin?)
+                .unwrap_or_default()
+                .as_secs_f64();
+            let speed = w[0].point.haversine_distance(&w[1].point) / elapsed;
+            Some((since + elapsed / 2.0, speed))
+        })
+    }
+}
+
+impl Trajectory {
+    /// Format as a WKT `LINESTRING ZM`, with each point's timestamp as its M
+    /// value in seconds since the Unix epoch. Parse with [`str::parse`].
+    ///
+    /// ```
+    /// use spatio_types::geo::Point;
+    /// use spatio_types::point::TemporalPoint3D;
+    /// use spatio_types::trajectory::Trajectory;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    /// let trajectory = Trajectory::new(vec![TemporalPoint3D::new(Point::new(1.5, 2.0), 10.0, at)]);
+    /// assert_eq!(trajectory.to_wkt(), "LINESTRING ZM (1.5 2 10 1700000000)");
+    /// assert_eq!(trajectory.to_wkt().parse::<Trajectory>().unwrap(), trajectory);
+    /// ```
+    pub fn to_wkt(&self) -> String {
+        self.to_geometry().to_wkt()
+    }
+
+    /// Encode as a little-endian ISO WKB `LINESTRING ZM`, timestamps as for
+    /// [`Trajectory::to_wkt`].
+    pub fn to_wkb(&self) -> Vec<u8> {
+        self.to_geometry().to_wkb()
+    }
+
+    /// Decode WKB or PostGIS EWKB: a line string whose M values are
+    /// timestamps in seconds since the Unix epoch.
+    pub fn from_wkb(wkb: &[u8]) -> Result<Self, WktError> {
+        Self::from_geometry(Geometry::from_wkb(wkb)?)
+    }
+
+    fn to_geometry(&self) -> Geometry {
+        let coords = self
+            .points
+            .iter()
+            .map(|p| {
+                let secs = p
+                    .timestamp
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64();
+                [p.point.x(), p.point.y(), p.altitude, secs]
+            })
+            .collect();
+        Geometry::line_string(coords, true, true)
+    }
+
+    fn from_geometry(geometry: Geometry) -> Result<Self, WktError> {
+        geometry.expect(Kind::LineString, "Trajectory")?;
+        if !geometry.has_m && geometry.coords().next().is_some() {
+            return Err(WktError::InvalidGeometry(
+                "Trajectory needs M values holding the timestamps".into(),
+            ));
+        }
+        let points = geometry
+            .coords()
+            .map(|c| {
+                let timestamp = system_time_from_secs(c[3]).map_err(WktError::InvalidGeometry)?;
+                Ok(TemporalPoint3D::new(
+                    Point::new(c[0], c[1]),
+                    c[2],
+                    timestamp,
+                ))
+            })
+            .collect::<Result<_, WktError>>()?;
+        Ok(Self::new(points))
+    }
+}
+
+/// Parses WKT, PostGIS EWKT or hex-encoded (E)WKB of a `LINESTRING M` or
+/// `LINESTRING ZM`; see [`Trajectory::to_wkt`].
+impl FromStr for Trajectory {
+    type Err = WktError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_geometry(Geometry::parse(s)?)
+    }
+}
+
+impl From<Vec<TemporalPoint3D>> for Trajectory {
+    fn from(points: Vec<TemporalPoint3D>) -> Self {
+        Self::new(points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geo::Point;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn trajectory(coords: &[(f64, f64)]) -> Trajectory {
+        coords
+            .iter()
+            .enumerate()
+            .map(|(i, &(x, y))| TemporalPoint3D::new(Point::new(x, y), i as f64, at(i as u64)))
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    fn timestamps(trajectory: &Trajectory) -> Vec<SystemTime> {
+        trajectory.points().iter().map(|p| p.timestamp).collect()
+    }
+
+    #[test]
+    fn test_simplify_keeps_corners_with_their_timestamps() {
+        // A straight run east, a corner, then a straight run north.
+        let t = trajectory(&[
+            (0.0, 0.0),
+            (1.0, 0.001),
+            (2.0, 0.0),
+            (3.0, 0.0),
+            (3.0, 1.0),
+            (3.001, 2.0),
+        ]);
+        let simplified = t.simplify(0.01);
+        assert_eq!(timestamps(&simplified), [at(0), at(3), at(5)]);
+        assert_eq!(simplified.points()[1].altitude, 3.0);
+
+        assert_eq!(t.simplify(0.0), t);
+        assert_eq!(t.simplify(0.0001).len(), t.len());
+    }
+
+    #[test]
+    fn test_simplify_vw_drops_small_triangles() {
+        let t = trajectory(&[(0.0, 0.0), (1.0, 0.001), (2.0, 0.0), (2.0, 2.0)]);
+        assert_eq!(timestamps(&t.simplify_vw(0.01)), [at(0), at(2), at(3)]);
+    }
+
+    #[test]
+    fn test_kinematics_skip_undefined_segments() {
+        // East along the equator: 0.001° (~111 m) in 10 s, a 10 s stop, a
+        // duplicate fix, then 0.002° in 10 s.
+        let points = [(0, 0.0), (10, 0.001), (20, 0.001), (20, 0.001), (30, 0.003)]
+            .map(|(secs, x)| TemporalPoint3D::new(Point::new(x, 0.0), 0.0, at(secs)));
+        let t = Trajectory::new(points.to_vec());
+
+        let speeds = t.speeds();
+        assert_eq!(speeds.len(), 3);
+        assert!((speeds[0] - 11.12).abs() < 0.01);
+        assert_eq!(speeds[1], 0.0);
+        assert!((speeds[2] - 2.0 * speeds[0]).abs() < 1e-6);
+
+        let headings = t.headings();
+        assert_eq!(headings.len(), 2);
+        assert!(headings.iter().all(|h| (h - 90.0).abs() < 1e-6));
+
+        // Segment midpoints at 5, 15 and 25 s.
+        let accelerations = t.accelerations();
+        assert!((accelerations[0] + speeds[0] / 10.0).abs() < 1e-9);
+        assert!((accelerations[1] - speeds[2] / 10.0).abs() < 1e-9);
+
+        let summary = t.summary();
+        assert_eq!(summary.points, 5);
+        assert_eq!(summary.duration, Duration::from_secs(30));
+        assert!((summary.total_distance - 3.0 * (speeds[0] * 10.0)).abs() < 1e-6);
+        assert_eq!(summary.max_speed, Some(speeds[2]));
+        assert!((summary.average_speed.unwrap() - summary.total_distance / 30.0).abs() < 1e-9);
+
+        let still = trajectory(&[(1.0, 1.0)]);
+        assert_eq!(still.summary().average_speed, None);
+        assert_eq!(still.summary().max_speed, None);
+        assert!(still.accelerations().is_empty());
+    }
+
+    #[test]
+    fn test_wkt_and_wkb_round_trip() {
+        let t = trajectory(&[(0.5, 1.0), (2.0, 3.25), (4.0, 5.0)]);
+        assert_eq!(t.to_wkt().parse::<Trajectory>().unwrap(), t);
+        assert_eq!(Trajectory::from_wkb(&t.to_wkb()).unwrap(), t);
+
+        // A 2D line string with measures gets altitude 0.
+        let flat: Trajectory = "LINESTRING M (1 2 10, 3 4 20)".parse().unwrap();
+        assert_eq!(timestamps(&flat), [at(10), at(20)]);
+        assert_eq!(flat.points()[1].altitude, 0.0);
+
+        assert!(matches!(
+            "LINESTRING Z (1 2 3)".parse::<Trajectory>(),
+            Err(WktError::InvalidGeometry(_))
+        ));
+        assert!("LINESTRING M (1 2 -5)".parse::<Trajectory>().is_err());
+        assert!("POINT ZM (1 2 3 4)".parse::<Trajectory>().is_err());
+    }
+
+    #[test]
+    fn test_short_trajectories_are_unchanged() {
+        for t in [trajectory(&[]), trajectory(&[(1.0, 1.0)])] {
+            assert_eq!(t.simplify(1.0), t);
+            assert_eq!(t.simplify_vw(1.0), t);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Serializable query parameters and results shared by the network
+//! transports and clients.
+//!
+//! Transports deserialize these structs and hand them to the database as is,
+//! so a query means the same thing whichever way it arrives. Object metadata
+//! travels as JSON-encoded bytes, keeping the structs usable with formats
+//! that cannot represent arbitrary JSON values.
+
+use crate::geo::DistanceMetric;
+use crate::point::Point3d;
+use crate::prelude::*;
+use crate::time::TimeRange;
+use serde::{Deserialize, Serialize};
+
+/// Objects within `radius` of `center`, nearest first.
+///
+/// # Examples
+///
+/// ```
+/// use spatio_types::geo::DistanceMetric;
+/// use spatio_types::point::Point3d;
+/// use spatio_types::wire::RadiusQuery;
+///
+/// let query = RadiusQuery::new("fleet", Point3d::new(-74.0, 40.7, 0.0), 500.0, 10)
+///     .with_metric(DistanceMetric::Geodesic);
+/// let json = serde_json::to_string(&query).unwrap();
+/// assert_eq!(serde_json::from_str::<RadiusQuery>(&json).unwrap(), query);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RadiusQuery {
+    pub namespace: String,
+    pub center: Point3d,
+    /// Meters, or coordinate units under [`DistanceMetric::Euclidean`].
+    pub radius: f64,
+    pub limit: usize,
+    /// Metric for horizontal distance; `None` uses the namespace's.
+    #[serde(default)]
+    pub metric: Option<DistanceMetric>,
+}
+
+impl RadiusQuery {
+    pub fn new(namespace: impl Into<String>, center: Point3d, radius: f64, limit: usize) -> Self {
+        Self {
+            namespace: namespace.into(),
+            center,
+            radius,
+            limit,
+            metric: None,
+        }
+    }
+
+    pub fn with_metric(mut self, metric: DistanceMetric) -> Self {
+        self.metric = Some(metric);
+        self
+    }
+}
+
+/// The `k` objects nearest to `center` in 3D, nearest first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KnnQuery {
+    pub namespace: String,
+    pub center: Point3d,
+    pub k: usize,
+}
+
+impl KnnQuery {
+    pub fn new(namespace: impl Into<String>, center: Point3d, k: usize) -> Self {
+        Self {
+            namespace: namespace.into(),
+            center,
+            k,
+        }
+    }
+}
+
+/// The points of one object's trajectory within a time range, newest first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrajectorySlice {
+    pub namespace: String,
+    pub object_id: String,
+    /// All of time when omitted.
+    #[serde(default)]
+    pub range: TimeRange,
+    pub limit: usize,
+}
+
+impl TrajectorySlice {
+    pub fn new(
+        namespace: impl Into<String>,
+        object_id: impl Into<String>,
+        range: impl Into<TimeRange>,
+        limit: usize,
+    ) -> Self {
+        Self {
+            namespace: namespace.into(),
+            object_id: object_id.into(),
+            range: range.into(),
+            limit,
+        }
+    }
+}
+
+/// An object's current location.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CurrentLocation {
+    pub object_id: String,
+    pub position: Point3d,
+    /// JSON-encoded metadata.
+    pub metadata: Vec<u8>,
+    /// Seconds since the Unix epoch of the write that put the object here.
+    #[serde(default)]
+    pub timestamp: f64,
+}
+
+/// One point of a trajectory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct LocationUpdate {
+    /// Seconds since the Unix epoch.
+    pub timestamp: f64,
+    pub position: Point3d,
+    /// JSON-encoded metadata.
+    pub metadata: Vec<u8>,
+}
+
+/// An object found by a distance query, with its distance from the query's
+/// center or anchor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryHit {
+    pub object_id: String,
+    pub position: Point3d,
+    /// JSON-encoded metadata.
+    pub metadata: Vec<u8>,
+    /// Seconds since the Unix epoch of the write that put the object here.
+    #[serde(default)]
+    pub timestamp: f64,
+    /// Meters, or coordinate units under [`DistanceMetric::Euclidean`].
+    pub distance: f64,
+}
+
+impl QueryHit {
+    pub fn new(location: CurrentLocation, distance: f64) -> Self {
+        Self {
+            object_id: location.object_id,
+            position: location.position,
+            metadata: location.metadata,
+            timestamp: location.timestamp,
+            distance,
+        }
+    }
+
+    /// The hit without its distance.
+    pub fn location(&self) -> CurrentLocation {
+        CurrentLocation {
+            object_id: self.object_id.clone(),
+            position: self.position.clone(),
+            metadata: self.metadata.clone(),
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+impl From<(CurrentLocation, f64)> for QueryHit {
+    fn from((location, distance): (CurrentLocation, f64)) -> Self {
+        Self::new(location, distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_and_round_trip() {
+        let slice: TrajectorySlice =
+            serde_json::from_str(r#"{"namespace":"fleet","object_id":"truck","limit":5}"#).unwrap();
+        assert_eq!(slice, TrajectorySlice::new("fleet", "truck", .., 5));
+
+        let query = RadiusQuery::new("fleet", Point3d::new(1.0, 2.0, 0.0), 5.0, 1);
+        let mut json = serde_json::to_value(&query).unwrap();
+        json.as_object_mut().unwrap().remove("metric");
+        let radius: RadiusQuery = serde_json::from_value(json).unwrap();
+        assert_eq!(radius, query);
+        assert_eq!(radius.metric, None);
+
+        let hit = QueryHit::new(
+            CurrentLocation {
+                object_id: "a".into(),
+                position: Point3d::new(1.0, 2.0, 3.0),
+                metadata: b"{}".to_vec(),
+                timestamp: 1.5,
+            },
+            4.0,
+        );
+        let json = serde_json::to_string(&hit).unwrap();
+        assert_eq!(serde_json::from_str::<QueryHit>(&json).unwrap(), hit);
+        assert_eq!(QueryHit::from((hit.location(), 4.0)), hit);
+    }
+}
@@ -0,0 +1,742 @@
+//! Well-Known Text and Well-Known Binary encoding.
+//!
+//! Types are written as ISO WKT and little-endian ISO WKB. Parsing also
+//! accepts the PostGIS extensions found in dumps: an `SRID=...;` prefix on
+//! text, EWKB type flags and SRID on binary, hex-encoded WKB where text is
+//! expected, and `BOX(...)`/`BOX3D(...)` extents. SRIDs are ignored.
+//!
+//! The per-type entry points (`to_wkt`, `to_wkb`, `from_wkb` and `FromStr`)
+//! live beside each type; this module holds the shared codec.
+
+use crate::prelude::*;
+
+/// Error type for WKT/WKB conversions.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum WktError {
+    /// The text or bytes are not well-formed WKT/WKB
+    Malformed(String),
+    /// Well-formed, but not a geometry the target type can hold
+    InvalidGeometry(String),
+}
+
+impl core::fmt::Display for WktError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Malformed(msg) => write!(f, "Malformed WKT/WKB: {}", msg),
+            Self::InvalidGeometry(msg) => write!(f, "Invalid WKT/WKB geometry: {}", msg),
+        }
+    }
+}
+
+impl core::error::Error for WktError {}
+
+fn malformed(msg: impl Into<String>) -> WktError {
+    WktError::Malformed(msg.into())
+}
+
+/// Geometry kinds the spatio types map to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    Point,
+    LineString,
+    Polygon,
+    PolyhedralSurface,
+    /// A PostGIS `BOX`/`BOX3D` extent: two corners. Text only.
+    Box,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Point => "POINT",
+            Kind::LineString => "LINESTRING",
+            Kind::Polygon => "POLYGON",
+            Kind::PolyhedralSurface => "POLYHEDRALSURFACE",
+            Kind::Box => "BOX",
+        }
+    }
+
+    fn wkb_code(self) -> u32 {
+        match self {
+            Kind::Point => 1,
+            Kind::LineString => 2,
+            Kind::Polygon => 3,
+            Kind::PolyhedralSurface => 15,
+            Kind::Box => unreachable!("boxes have no WKB form"),
+        }
+    }
+}
+
+/// `[x, y, z, m]`; `z` and `m` are 0 where the geometry has none.
+pub(crate) type Coord = [f64; 4];
+
+/// A parsed or to-be-written geometry.
+///
+/// Coordinates are nested uniformly as polygons → rings → coordinates: a point
+/// is one ring of one coordinate, a line string (or box) one ring, a polygon
+/// one polygon of rings, and a polyhedral surface a list of polygons.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Geometry {
+    pub kind: Kind,
+    pub has_z: bool,
+    pub has_m: bool,
+    pub parts: Vec<Vec<Vec<Coord>>>,
+}
+
+impl Geometry {
+    pub fn point(coord: Coord, has_z: bool) -> Self {
+        Self::line_string(vec![coord], has_z, false).with_kind(Kind::Point)
+    }
+
+    pub fn line_string(coords: Vec<Coord>, has_z: bool, has_m: bool) -> Self {
+        Self {
+            kind: Kind::LineString,
+            has_z,
+            has_m,
+            parts: vec![vec![coords]],
+        }
+    }
+
+    pub fn polygon(rings: Vec<Vec<Coord>>, has_z: bool) -> Self {
+        Self {
+            kind: Kind::Polygon,
+            has_z,
+            has_m: false,
+            parts: vec![rings],
+        }
+    }
+
+    fn with_kind(mut self, kind: Kind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Error unless this is a `kind`, for the type named `target`.
+    pub fn expect(&self, kind: Kind, target: &str) -> Result<(), WktError> {
+        if self.kind == kind {
+            Ok(())
+        } else {
+            Err(WktError::InvalidGeometry(format!(
+                "{} needs a {}, got a {}",
+                target,
+                kind.name(),
+                self.kind.name()
+            )))
+        }
+    }
+
+    /// Every coordinate, in order.
+    pub fn coords(&self) -> impl Iterator<Item = &Coord> {
+        self.parts.iter().flatten().flatten()
+    }
+
+    /// `(min, max)` corners over every coordinate, or `None` when empty.
+    pub fn envelope(&self) -> Option<(Coord, Coord)> {
+        let mut coords = self.coords();
+        let first = *coords.next()?;
+        Some(coords.fold((first, first), |(mut min, mut max), c| {
+            for axis in 0..4 {
+                min[axis] = min[axis].min(c[axis]);
+                max[axis] = max[axis].max(c[axis]);
+            }
+            (min, max)
+        }))
+    }
+
+    /// The geometry as ISO WKT.
+    pub fn to_wkt(&self) -> String {
+        let mut out = String::from(self.kind.name());
+        match (self.has_z, self.has_m) {
+            (true, true) => out.push_str(" ZM"),
+            (true, false) => out.push_str(" Z"),
+            (false, true) => out.push_str(" M"),
+            (false, false) => {}
+        }
+        if self.coords().next().is_none() {
+            out.push_str(" EMPTY");
+            return out;
+        }
+        out.push(' ');
+        match self.kind {
+            Kind::Point | Kind::LineString | Kind::Box => {
+                self.write_ring(&mut out, &self.parts[0][0])
+            }
+            Kind::Polygon => self.write_polygon(&mut out, &self.parts[0]),
+            Kind::PolyhedralSurface => {
+                out.push('(');
+                for (i, polygon) in self.parts.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    self.write_polygon(&mut out, polygon);
+                }
+                out.push(')');
+            }
+        }
+        out
+    }
+
+    fn write_polygon(&self, out: &mut String, rings: &[Vec<Coord>]) {
+        out.push('(');
+        for (i, ring) in rings.iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            self.write_ring(out, ring);
+        }
+        out.push(')');
+    }
+
+    fn write_ring(&self, out: &mut String, coords: &[Coord]) {
+        use core::fmt::Write;
+        out.push('(');
+        for (i, c) in coords.iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            let _ = write!(out, "{} {}", c[0], c[1]);
+            if self.has_z {
+                let _ = write!(out, " {}", c[2]);
+            }
+            if self.has_m {
+                let _ = write!(out, " {}", c[3]);
+            }
+        }
+        out.push(')');
+    }
+
+    /// The geometry as little-endian ISO WKB.
+    pub fn to_wkb(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self.kind {
+            Kind::Point => {
+                self.write_header(&mut out, Kind::Point);
+                self.write_coord(&mut out, &self.parts[0][0][0]);
+            }
+            Kind::LineString => {
+                self.write_header(&mut out, Kind::LineString);
+                self.write_coords(&mut out, &self.parts[0][0]);
+            }
+            Kind::Polygon => self.write_wkb_polygon(&mut out, &self.parts[0]),
+            Kind::PolyhedralSurface => {
+                self.write_header(&mut out, Kind::PolyhedralSurface);
+                write_count(&mut out, self.parts.len());
+                for polygon in &self.parts {
+                    self.write_wkb_polygon(&mut out, polygon);
+                }
+            }
+            Kind::Box => unreachable!("boxes have no WKB form"),
+        }
+        out
+    }
+
+    fn write_header(&self, out: &mut Vec<u8>, kind: Kind) {
+        let dims = match (self.has_z, self.has_m) {
+            (false, false) => 0,
+            (true, false) => 1000,
+            (false, true) => 2000,
+            (true, true) => 3000,
+        };
+        out.push(1);
+        out.extend_from_slice(&(kind.wkb_code() + dims).to_le_bytes());
+    }
+
+    fn write_wkb_polygon(&self, out: &mut Vec<u8>, rings: &[Vec<Coord>]) {
+        self.write_header(out, Kind::Polygon);
+        write_count(out, rings.len());
+        for ring in rings {
+            self.write_coords(out, ring);
+        }
+    }
+
+    fn write_coords(&self, out: &mut Vec<u8>, coords: &[Coord]) {
+        write_count(out, coords.len());
+        for c in coords {
+            self.write_coord(out, c);
+        }
+    }
+
+    fn write_coord(&self, out: &mut Vec<u8>, c: &Coord) {
+        out.extend_from_slice(&c[0].to_le_bytes());
+        out.extend_from_slice(&c[1].to_le_bytes());
+        if self.has_z {
+            out.extend_from_slice(&c[2].to_le_bytes());
+        }
+        if self.has_m {
+            out.extend_from_slice(&c[3].to_le_bytes());
+        }
+    }
+
+    /// Parse WKT, EWKT, a PostGIS box, or hex-encoded (E)WKB.
+    pub fn parse(input: &str) -> Result<Self, WktError> {
+        let input = input.trim();
+        if !input.is_empty() && input.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Self::from_wkb(&decode_hex(input)?);
+        }
+        let input = match input.split_once(';') {
+            Some((srid, rest)) if srid.trim().to_ascii_uppercase().starts_with("SRID=") => rest,
+            _ => input,
+        };
+        let mut parser = TextParser { rest: input };
+        let geometry = parser.geometry()?;
+        if !parser.rest.trim().is_empty() {
+            return Err(malformed(format!(
+                "unexpected trailing text {:?}",
+                parser.rest
+            )));
+        }
+        Ok(geometry)
+    }
+
+    /// Parse ISO WKB or PostGIS EWKB, in either byte order.
+    pub fn from_wkb(bytes: &[u8]) -> Result<Self, WktError> {
+        let mut reader = WkbReader { bytes };
+        let geometry = reader.geometry(None)?;
+        if !reader.bytes.is_empty() {
+            return Err(malformed(format!("{} trailing bytes", reader.bytes.len())));
+        }
+        Ok(geometry)
+    }
+}
+
+fn write_count(out: &mut Vec<u8>, n: usize) {
+    let n = u32::try_from(n).expect("WKB counts are 32-bit");
+    out.extend_from_slice(&n.to_le_bytes());
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, WktError> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(malformed("hex WKB has an odd number of digits"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| malformed(e.to_string())))
+        .collect()
+}
+
+/// Recursive-descent WKT parser over the unconsumed input.
+struct TextParser<'a> {
+    rest: &'a str,
+}
+
+impl TextParser<'_> {
+    fn skip_space(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    fn word(&mut self) -> String {
+        self.skip_space();
+        let end = self
+            .rest
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(self.rest.len());
+        let (word, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        word.to_ascii_uppercase()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_space();
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), WktError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(malformed(format!("expected '{}' at {:?}", c, self.rest)))
+        }
+    }
+
+    fn geometry(&mut self) -> Result<Geometry, WktError> {
+        let word = self.word();
+        let (kind, mut dims) = match word.as_str() {
+            "BOX" => (Kind::Box, Some((false, false))),
+            "BOX3D" => (Kind::Box, Some((true, false))),
+            _ => {
+                // PostGIS writes `POINTM`; ISO writes `POINT M`.
+                let (name, suffix) = ["ZM", "Z", "M", ""]
+                    .into_iter()
+                    .find_map(|suffix| {
+                        let name = word.strip_suffix(suffix)?;
+                        KINDS
+                            .iter()
+                            .any(|(n, _)| *n == name)
+                            .then_some((name, suffix))
+                    })
+                    .ok_or_else(|| malformed(format!("unknown geometry type {:?}", word)))?;
+                let kind = KINDS.iter().find(|(n, _)| *n == name).map(|(_, k)| *k);
+                (kind.expect("matched above"), dimension_suffix(suffix))
+            }
+        };
+        if dims.is_none() {
+            let saved = self.rest;
+            match self.word().as_str() {
+                "" => self.rest = saved,
+                "EMPTY" => self.rest = saved,
+                other => {
+                    dims = dimension_suffix(other);
+                    if dims.is_none() {
+                        return Err(malformed(format!("unexpected {:?}", other)));
+                    }
+                }
+            }
+        }
+
+        let mut geometry = Geometry {
+            kind,
+            has_z: dims.is_some_and(|(z, _)| z),
+            has_m: dims.is_some_and(|(_, m)| m),
+            parts: Vec::new(),
+        };
+        let saved = self.rest;
+        if self.word() == "EMPTY" {
+            geometry.parts = match kind {
+                Kind::PolyhedralSurface => Vec::new(),
+                Kind::Polygon => vec![Vec::new()],
+                _ => vec![vec![Vec::new()]],
+            };
+            return Ok(geometry);
+        }
+        self.rest = saved;
+
+        let mut width = dims.map(|(z, m)| 2 + usize::from(z) + usize::from(m));
+        geometry.parts = match kind {
+            Kind::Point | Kind::LineString | Kind::Box => vec![vec![self.ring(&mut width)?]],
+            Kind::Polygon => vec![self.rings(&mut width)?],
+            Kind::PolyhedralSurface => {
+                let mut polygons = Vec::new();
+                self.expect('(')?;
+                loop {
+                    polygons.push(self.rings(&mut width)?);
+                    if !self.eat(',') {
+                        break;
+                    }
+                }
+                self.expect(')')?;
+                polygons
+            }
+        };
+        if dims.is_none() {
+            // Undeclared dimensions follow the coordinate width, as in EWKT.
+            geometry.has_z = width >= Some(3);
+            geometry.has_m = width == Some(4);
+        } else if geometry.has_m && !geometry.has_z {
+            // The third ordinate of an `M` geometry is the measure.
+            for c in geometry.parts.iter_mut().flatten().flatten() {
+                c.swap(2, 3);
+            }
+        }
+        let count = geometry.parts[0][0].len();
+        match kind {
+            Kind::Point if count != 1 => Err(malformed("a POINT has one coordinate")),
+            Kind::Box if count != 2 => Err(malformed("a BOX has two corners")),
+            _ => Ok(geometry),
+        }
+    }
+
+    fn rings(&mut self, width: &mut Option<usize>) -> Result<Vec<Vec<Coord>>, WktError> {
+        let mut rings = Vec::new();
+        self.expect('(')?;
+        loop {
+            rings.push(self.ring(width)?);
+            if !self.eat(',') {
+                break;
+            }
+        }
+        self.expect(')')?;
+        Ok(rings)
+    }
+
+    fn ring(&mut self, width: &mut Option<usize>) -> Result<Vec<Coord>, WktError> {
+        let mut coords = Vec::new();
+        self.expect('(')?;
+        loop {
+            coords.push(self.coord(width)?);
+            if !self.eat(',') {
+                break;
+            }
+        }
+        self.expect(')')?;
+        Ok(coords)
+    }
+
+    /// One coordinate; `width` is the number of ordinates every coordinate of
+    /// the geometry must have, fixed by the first when not declared.
+    fn coord(&mut self, width: &mut Option<usize>) -> Result<Coord, WktError> {
+        let mut values = Vec::with_capacity(4);
+        loop {
+            self.skip_space();
+            let end = self
+                .rest
+                .find(|c: char| c.is_whitespace() || c == ',' || c == ')')
+                .unwrap_or(self.rest.len());
+            if end == 0 {
+                break;
+            }
+            let (number, rest) = self.rest.split_at(end);
+            let value = number
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .ok_or_else(|| malformed(format!("invalid number {:?}", number)))?;
+            values.push(value);
+            self.rest = rest;
+        }
+        if !(2..=4).contains(&values.len()) {
+            return Err(malformed(format!(
+                "a coordinate has 2 to 4 ordinates, got {}",
+                values.len()
+            )));
+        }
+        match *width {
+            Some(w) if w != values.len() => {
+                return Err(malformed(format!(
+                    "expected {} ordinates per coordinate, got {}",
+                    w,
+                    values.len()
+                )));
+            }
+            _ => *width = Some(values.len()),
+        }
+        Ok(match values[..] {
+            [x, y] => [x, y, 0.0, 0.0],
+            [x, y, z] => [x, y, z, 0.0],
+            [x, y, z, m] => [x, y, z, m],
+            _ => unreachable!(),
+        })
+    }
+}
+
+const KINDS: [(&str, Kind); 4] = [
+    ("POINT", Kind::Point),
+    ("LINESTRING", Kind::LineString),
+    ("POLYGON", Kind::Polygon),
+    ("POLYHEDRALSURFACE", Kind::PolyhedralSurface),
+];
+
+/// `(has_z, has_m)` for a dimension suffix, `None` if absent.
+fn dimension_suffix(suffix: &str) -> Option<(bool, bool)> {
+    match suffix {
+        "Z" => Some((true, false)),
+        "M" => Some((false, true)),
+        "ZM" => Some((true, true)),
+        _ => None,
+    }
+}
+
+/// Reader over the unconsumed WKB bytes.
+struct WkbReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl WkbReader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], WktError> {
+        if self.bytes.len() < N {
+            return Err(malformed("WKB ends early"));
+        }
+        let (head, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        Ok(head.try_into().expect("split at N"))
+    }
+
+    fn u32(&mut self, little: bool) -> Result<u32, WktError> {
+        let bytes = self.take::<4>()?;
+        Ok(if little {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn f64(&mut self, little: bool) -> Result<f64, WktError> {
+        let bytes = self.take::<8>()?;
+        Ok(if little {
+            f64::from_le_bytes(bytes)
+        } else {
+            f64::from_be_bytes(bytes)
+        })
+    }
+
+    /// A count of items at least `min_bytes` long each, checked against the
+    /// bytes left so a corrupt count can't drive a huge allocation.
+    fn count(&mut self, little: bool, min_bytes: usize) -> Result<usize, WktError> {
+        let n = self.u32(little)? as usize;
+        if n.saturating_mul(min_bytes) > self.bytes.len() {
+            return Err(malformed(format!("count {} exceeds the WKB length", n)));
+        }
+        Ok(n)
+    }
+
+    /// One geometry; `only` restricts its kind, for the members of a surface.
+    fn geometry(&mut self, only: Option<Kind>) -> Result<Geometry, WktError> {
+        let little = match self.take::<1>()?[0] {
+            0 => false,
+            1 => true,
+            other => return Err(malformed(format!("invalid byte order {}", other))),
+        };
+        let code = self.u32(little)?;
+        // EWKB flags the dimensions and an SRID in the high bits; ISO WKB
+        // adds 1000/2000/3000 to the type.
+        let (mut has_z, mut has_m) = (code & 0x8000_0000 != 0, code & 0x4000_0000 != 0);
+        if code & 0x2000_0000 != 0 {
+            self.u32(little)?;
+        }
+        let base = code & 0x0FFF_FFFF;
+        match base / 1000 {
+            0 => {}
+            1 => has_z = true,
+            2 => has_m = true,
+            3 => (has_z, has_m) = (true, true),
+            _ => return Err(malformed(format!("unknown WKB type {}", code))),
+        }
+        let kind = match base % 1000 {
+            1 => Kind::Point,
+            2 => Kind::LineString,
+            3 => Kind::Polygon,
+            15 => Kind::PolyhedralSurface,
+            other => {
+                return Err(WktError::InvalidGeometry(format!(
+                    "unsupported WKB geometry type {}",
+                    other
+                )));
+            }
+        };
+        if only.is_some_and(|only| only != kind) {
+            return Err(malformed(format!("unexpected {} in WKB", kind.name())));
+        }
+
+        let width = 2 + usize::from(has_z) + usize::from(has_m);
+        let coord = |reader: &mut Self| -> Result<Coord, WktError> {
+            let mut c = [0.0; 4];
+            c[0] = reader.f64(little)?;
+            c[1] = reader.f64(little)?;
+            if has_z {
+                c[2] = reader.f64(little)?;
+            }
+            if has_m {
+                c[3] = reader.f64(little)?;
+            }
+            Ok(c)
+        };
+        let coords = |reader: &mut Self| -> Result<Vec<Coord>, WktError> {
+            let n = reader.count(little, width * 8)?;
+            (0..n).map(|_| coord(reader)).collect()
+        };
+        let parts = match kind {
+            Kind::Point => {
+                let c = coord(self)?;
+                // WKB writes an empty point as NaN ordinates.
+                if c[0].is_nan() && c[1].is_nan() {
+                    vec![vec![Vec::new()]]
+                } else {
+                    vec![vec![vec![c]]]
+                }
+            }
+            Kind::LineString => vec![vec![coords(self)?]],
+            Kind::Polygon => {
+                let n = self.count(little, 4)?;
+                vec![(0..n).map(|_| coords(self)).collect::<Result<_, _>>()?]
+            }
+            Kind::PolyhedralSurface => {
+                let n = self.count(little, 9)?;
+                (0..n)
+                    .map(|_| {
+                        let mut polygon = self.geometry(Some(Kind::Polygon))?;
+                        Ok(polygon.parts.remove(0))
+                    })
+                    .collect::<Result<_, WktError>>()?
+            }
+            Kind::Box => unreachable!("boxes have no WKB form"),
+        };
+        if parts
+            .iter()
+            .flatten()
+            .flatten()
+            .flatten()
+            .any(|v| !v.is_finite())
+        {
+            return Err(malformed("WKB coordinates must be finite"));
+        }
+        Ok(Geometry {
+            kind,
+            has_z,
+            has_m,
+            parts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dimension_spellings() {
+        for text in ["POINT Z (1 2 3)", "pointz(1 2 3)", "SRID=4326;POINT(1 2 3)"] {
+            let geometry = Geometry::parse(text).unwrap();
+            assert_eq!(geometry.kind, Kind::Point);
+            assert!(geometry.has_z && !geometry.has_m, "{text}");
+            assert_eq!(geometry.parts[0][0][0], [1.0, 2.0, 3.0, 0.0]);
+        }
+        let geometry = Geometry::parse("LINESTRING M (1 2 10, 3 4 20)").unwrap();
+        assert!(!geometry.has_z && geometry.has_m);
+        assert_eq!(geometry.parts[0][0][1], [3.0, 4.0, 0.0, 20.0]);
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_text() {
+        for text in [
+            "POINT (1)",
+            "POINT (1 2, 3 4)",
+            "POINT Z (1 2)",
+            "LINESTRING (1 2, 3 4 5)",
+            "POLYGON ((0 0, 1 0, 1 1, 0 0)",
+            "CIRCLE (0 0)",
+            "POINT (1 2) junk",
+        ] {
+            assert!(
+                matches!(Geometry::parse(text), Err(WktError::Malformed(_))),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_wkb_round_trips_and_reads_ewkb() {
+        let surface = Geometry::parse(
+            "POLYHEDRALSURFACE Z (((0 0 0, 1 0 0, 1 1 0, 0 0 0)), ((0 0 1, 1 0 1, 1 1 1, 0 0 1)))",
+        )
+        .unwrap();
+        assert_eq!(Geometry::from_wkb(&surface.to_wkb()).unwrap(), surface);
+
+        // Big-endian EWKB of POINT Z (1 2 3) with SRID 4326, as PostGIS
+        // dumps it in hex.
+        let mut ewkb = vec![0u8];
+        ewkb.extend_from_slice(&(0x8000_0001u32 | 0x2000_0000).to_be_bytes());
+        ewkb.extend_from_slice(&4326u32.to_be_bytes());
+        for v in [1.0f64, 2.0, 3.0] {
+            ewkb.extend_from_slice(&v.to_be_bytes());
+        }
+        let hex: String = ewkb.iter().map(|b| format!("{:02X}", b)).collect();
+        let point = Geometry::parse(&hex).unwrap();
+        assert_eq!(point, Geometry::point([1.0, 2.0, 3.0, 0.0], true));
+    }
+
+    #[test]
+    fn test_wkb_rejects_truncated_and_oversized_input() {
+        let wkb = Geometry::parse("LINESTRING (1 2, 3 4)").unwrap().to_wkb();
+        assert!(Geometry::from_wkb(&wkb[..wkb.len() - 1]).is_err());
+
+        let mut huge = wkb[..5].to_vec();
+        huge.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Geometry::from_wkb(&huge).is_err());
+    }
+}
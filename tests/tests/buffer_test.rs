@@ -14,7 +14,10 @@ fn test_buffered_writes() -> anyhow::Result<()> {
         ;
 
     let mut config = config;
-    config.persistence = PersistenceConfig { buffer_size: 10 };
+    config.persistence = PersistenceConfig {
+        buffer_size: 10,
+        ..Default::default()
+    };
 
     let db = Spatio::open_with_config(&db_path, config)?;
     let namespace = "test_ns";
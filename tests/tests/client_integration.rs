@@ -1,5 +1,8 @@
-use spatio::{Point3d, Spatio};
-use spatio_client::SpatioClient;
+use spatio::{DistanceMetric, Point, Point3d, Spatio};
+use spatio_client::{
+    HistoryEventKind, Predicate, QueryArgs, QueryTemplate, RegionEvent, ScanDirection,
+    SpatioClient, TimeRange,
+};
 use spatio_server::run_server;
 use std::sync::Arc;
 use std::time::Duration;
@@ -107,19 +110,36 @@ async fn test_spatial_queries() -> anyhow::Result<()> {
 
     // Query Radius (search around 0,0 with r=15 meters -> expect p1, p2)
     let results = client
-        .query_radius("geo", Point3d::new(0.0, 0.0, 0.0), 15.0, 10)
+        .query_radius("geo", Point3d::new(0.0, 0.0, 0.0), 15.0, 10, None)
         .await?;
     assert_eq!(results.len(), 2);
     // Sort by ID to ensure consistent order for assertion if not guaranteed by server
     // (Though basic implementation usually returns in some order, safe to check inclusion)
-    let ids: Vec<String> = results.iter().map(|(l, _)| l.object_id.clone()).collect();
+    let ids: Vec<String> = results.iter().map(|h| h.object_id.clone()).collect();
     assert!(ids.contains(&"p1".to_string()));
     assert!(ids.contains(&"p2".to_string()));
 
     // KNN (k=2 near 0,0 -> p1, p2)
     let results = client.knn("geo", Point3d::new(0.0, 0.0, 0.0), 2).await?;
     assert_eq!(results.len(), 2);
-    assert_eq!(results[0].0.object_id, "p1");
+    assert_eq!(results[0].object_id, "p1");
+
+    // 2D KNN with a distance cap leaves out p3, about 111 km away
+    let results = client
+        .knn_2d("geo", Point::new(0.0, 0.0), 10, Some(15.0), None)
+        .await?;
+    let ids: Vec<&str> = results.iter().map(|h| h.object_id.as_str()).collect();
+    assert_eq!(ids, ["p1", "p2"]);
+    let results = client
+        .knn_2d(
+            "geo",
+            Point::new(0.0, 0.0),
+            1,
+            None,
+            Some(DistanceMetric::Euclidean),
+        )
+        .await?;
+    assert_eq!(results[0].object_id, "p1");
 
     // BBox (containing p1, p2 but not p3)
     let results = client
@@ -160,17 +180,560 @@ async fn test_trajectory() -> anyhow::Result<()> {
     tokio::time::sleep(Duration::from_millis(100)).await;
 
     // Query whole range
-    let traj = client
-        .query_trajectory("traj", "v1", None, None, 100)
-        .await?;
+    let traj = client.query_trajectory("traj", "v1", .., 100).await?;
     assert_eq!(traj.len(), 3);
 
     // Query subset
-    let traj = client
-        .query_trajectory("traj", "v1", Some(now - 60.0), Some(now - 40.0), 100)
-        .await?;
+    let range = TimeRange::from_secs(Some(now - 60.0), Some(now - 40.0), None).unwrap();
+    let traj = client.query_trajectory("traj", "v1", range, 100).await?;
     assert_eq!(traj.len(), 1);
     assert_eq!(traj[0].position.x(), 10.0);
 
+    // Relative ranges resolve against the server's clock.
+    let traj = client
+        .query_trajectory("traj", "v1", TimeRange::last(Duration::from_secs(75)), 100)
+        .await?;
+    assert_eq!(traj.len(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_history_kind_filter() -> anyhow::Result<()> {
+    let addr = spawn_test_server().await?;
+    let client = SpatioClient::connect(addr).await?;
+
+    client
+        .upsert(
+            "audit",
+            "v1",
+            Point3d::new(1.0, 2.0, 0.0),
+            serde_json::json!({"step": 1}),
+        )
+        .await?;
+    client.delete("audit", "v1").await?;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let history = client.history("audit", "v1", .., None, 100).await?;
+    let kinds: Vec<_> = history.iter().map(|entry| entry.kind).collect();
+    assert_eq!(kinds, [HistoryEventKind::Set, HistoryEventKind::Delete]);
+    assert_eq!(history[0].position.as_ref().unwrap().x(), 1.0);
+    assert!(history[1].position.is_none());
+
+    let deletes = client
+        .history("audit", "v1", .., Some(HistoryEventKind::Delete), 100)
+        .await?;
+    assert_eq!(deletes.len(), 1);
+    assert!(deletes[0].metadata.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bbox_pages_resume_after_reconnect() -> anyhow::Result<()> {
+    // Two servers holding the same objects, each with its own log.
+    let addrs = [spawn_test_server().await?, spawn_test_server().await?];
+    for addr in addrs {
+        let client = SpatioClient::connect(addr).await?;
+        for i in 0..25 {
+            client
+                .upsert(
+                    "pages",
+                    &format!("o{i}"),
+                    Point3d::new(i as f64 * 0.1, 1.0, 0.0),
+                    serde_json::json!({}),
+                )
+                .await?;
+        }
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Every page goes over a fresh connection.
+    let mut ids = std::collections::HashSet::new();
+    let mut token = None;
+    loop {
+        let client = SpatioClient::connect(addrs[0]).await?;
+        let page = client
+            .query_bbox_page("pages", 0.0, 0.0, 3.0, 2.0, 10, token)
+            .await?;
+        for loc in page.items {
+            assert!(ids.insert(loc.object_id));
+        }
+        token = page.next_token;
+        if token.is_none() {
+            break;
+        }
+    }
+    assert_eq!(ids.len(), 25);
+
+    // The other server's log didn't issue the token, so it refuses it.
+    let first = SpatioClient::connect(addrs[0])
+        .await?
+        .query_bbox_page("pages", 0.0, 0.0, 3.0, 2.0, 10, None)
+        .await?;
+    let other = SpatioClient::connect(addrs[1]).await?;
+    assert!(other
+        .query_bbox_page("pages", 0.0, 0.0, 3.0, 2.0, 10, first.next_token)
+        .await
+        .is_err());
+
+    let client = SpatioClient::connect(addrs[0]).await?;
+    let err = client
+        .query_bbox_page("pages", 0.0, 0.0, 3.0, 2.0, 10, Some("bogus".into()))
+        .await;
+    assert!(err.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_radius_and_trajectory_pages() -> anyhow::Result<()> {
+    let addr = spawn_test_server().await?;
+    let client = SpatioClient::connect(addr).await?;
+    for i in 0..12 {
+        client
+            .upsert(
+                "pages",
+                &format!("o{i:02}"),
+                Point3d::new(i as f64 * 0.001, 0.0, 0.0),
+                serde_json::json!({}),
+            )
+            .await?;
+    }
+    for i in 0..5 {
+        client
+            .upsert(
+                "pages",
+                "mover",
+                Point3d::new(1.0, i as f64, 0.0),
+                serde_json::json!({}),
+            )
+            .await?;
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let center = Point3d::new(0.0, 0.0, 0.0);
+    let mut ids = Vec::new();
+    let mut token = None;
+    loop {
+        let page = client
+            .query_radius_page("pages", center.clone(), 5_000.0, 5, token)
+            .await?;
+        assert!(page
+            .items
+            .windows(2)
+            .all(|pair| pair[0].distance <= pair[1].distance));
+        ids.extend(page.items.into_iter().map(|hit| hit.object_id));
+        token = page.next_token;
+        if token.is_none() {
+            break;
+        }
+    }
+    let expected: Vec<String> = (0..12).map(|i| format!("o{i:02}")).collect();
+    assert_eq!(ids, expected);
+
+    let mut ys = Vec::new();
+    let mut token = None;
+    loop {
+        let page = client
+            .query_trajectory_page("pages", "mover", .., 2, token)
+            .await?;
+        ys.extend(page.items.iter().map(|update| update.position.y()));
+        token = page.next_token;
+        if token.is_none() {
+            break;
+        }
+    }
+    assert_eq!(ys, [4.0, 3.0, 2.0, 1.0, 0.0]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_range_scans_time_prefixed_ids() -> anyhow::Result<()> {
+    let addr = spawn_test_server().await?;
+    let client = SpatioClient::connect(addr).await?;
+    for minute in [3, 0, 4, 1, 2] {
+        client
+            .upsert(
+                "events",
+                &format!("2024-05-01T12:0{minute}:00Z/gate"),
+                Point3d::new(0.0, 0.0, 0.0),
+                serde_json::json!({ "minute": minute }),
+            )
+            .await?;
+    }
+
+    let ids = |locs: Vec<spatio_client::CurrentLocation>| {
+        locs.into_iter()
+            .map(|loc| loc.object_id)
+            .collect::<Vec<_>>()
+    };
+    let window = "2024-05-01T12:01".."2024-05-01T12:04";
+    assert_eq!(
+        ids(client
+            .range("events", window.clone(), 10, ScanDirection::Forward)
+            .await?),
+        [
+            "2024-05-01T12:01:00Z/gate",
+            "2024-05-01T12:02:00Z/gate",
+            "2024-05-01T12:03:00Z/gate"
+        ]
+    );
+    assert_eq!(
+        ids(client
+            .range("events", .., 2, ScanDirection::Reverse)
+            .await?),
+        ["2024-05-01T12:04:00Z/gate", "2024-05-01T12:03:00Z/gate"]
+    );
+    assert!(client
+        .range("events", "b".."a", 10, ScanDirection::Forward)
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_composite_query() -> anyhow::Result<()> {
+    let addr = spawn_test_server().await?;
+    let client = SpatioClient::connect(addr).await?;
+    for (id, x, z) in [
+        ("low", 0.5, 100.0),
+        ("high", 0.5, 900.0),
+        ("far", 5.0, 100.0),
+    ] {
+        client
+            .upsert("fleet", id, Point3d::new(x, 0.0, z), serde_json::json!({}))
+            .await?;
+    }
+
+    let predicate = Predicate::WithinRadius {
+        center: spatio::Point::new(0.0, 0.0),
+        radius: 100_000.0,
+    }
+    .and(Predicate::Altitude {
+        min: None,
+        max: Some(500.0),
+    });
+    let found = client.query("fleet", predicate, 10).await?;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].object_id, "low");
+
+    let invalid = Predicate::WithinRadius {
+        center: spatio::Point::new(0.0, 0.0),
+        radius: f64::NAN,
+    };
+    assert!(client.query("fleet", invalid, 10).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_saved_query_runs_by_name() -> anyhow::Result<()> {
+    let addr = spawn_test_server().await?;
+    let client = SpatioClient::connect(addr).await?;
+    for (id, x) in [("near", 0.001), ("far", 0.1)] {
+        client
+            .upsert(
+                "fleet",
+                id,
+                Point3d::new(x, 0.0, 0.0),
+                serde_json::json!({}),
+            )
+            .await?;
+    }
+
+    let busy_zone = QueryTemplate::new(Predicate::WithinRadius {
+        center: spatio::Point::new(0.0, 0.0),
+        radius: 500.0,
+    })
+    .param("center", "/within_radius/center")
+    .param("radius", "/within_radius/radius");
+    client.register_query("busy_zone", busy_zone).await?;
+
+    let found = client
+        .run_query("fleet", "busy_zone", QueryArgs::new(), 10)
+        .await?;
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].object_id, "near");
+
+    let wide = QueryArgs::from([("radius".to_string(), serde_json::json!(50_000.0))]);
+    assert_eq!(
+        client
+            .run_query("fleet", "busy_zone", wide, 10)
+            .await?
+            .len(),
+        2
+    );
+
+    let bad = QueryArgs::from([("radius".to_string(), serde_json::json!(-1.0))]);
+    assert!(client
+        .run_query("fleet", "busy_zone", bad, 10)
+        .await
+        .is_err());
+
+    assert!(client.unregister_query("busy_zone").await?);
+    assert!(client
+        .run_query("fleet", "busy_zone", QueryArgs::new(), 10)
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_materialized_view_follows_writes() -> anyhow::Result<()> {
+    let addr = spawn_test_server().await?;
+    let client = SpatioClient::connect(addr).await?;
+    let low = Predicate::Altitude {
+        min: None,
+        max: Some(120.0),
+    };
+    client.create_view("low", "drones", low.clone()).await?;
+    assert!(client.create_view("low", "drones", low).await.is_err());
+
+    client
+        .upsert(
+            "drones",
+            "d1",
+            Point3d::new(0.0, 0.0, 50.0),
+            serde_json::json!({}),
+        )
+        .await?;
+    client
+        .upsert(
+            "drones",
+            "d2",
+            Point3d::new(0.0, 0.0, 500.0),
+            serde_json::json!({}),
+        )
+        .await?;
+    let members = client.view("low", 10).await?;
+    assert_eq!(members.len(), 1);
+    assert_eq!(members[0].object_id, "d1");
+
+    assert!(client.drop_view("low").await?);
+    assert!(client.view("low", 10).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_list_clear_and_drop_namespaces() -> anyhow::Result<()> {
+    let addr = spawn_test_server().await?;
+    let client = SpatioClient::connect(addr).await?;
+    for (namespace, id) in [("cars", "c1"), ("cars", "c2"), ("bikes", "b1")] {
+        client
+            .upsert(
+                namespace,
+                id,
+                Point3d::new(1.0, 2.0, 0.0),
+                serde_json::json!({}),
+            )
+            .await?;
+    }
+    assert_eq!(client.list_namespaces().await?, ["bikes", "cars"]);
+
+    assert_eq!(client.clear_namespace("cars").await?, 2);
+    assert!(client.get("cars", "c1").await?.is_none());
+    assert_eq!(client.list_namespaces().await?, ["bikes"]);
+
+    assert_eq!(client.drop_namespace("bikes").await?, 1);
+    assert!(client.list_namespaces().await?.is_empty());
+    assert!(client.clear_namespace("bad|name").await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_idempotent_writes_apply_once() -> anyhow::Result<()> {
+    let addr = spawn_test_server().await?;
+    let client = SpatioClient::connect(addr).await?;
+    let point = Point3d::new(1.0, 2.0, 0.0);
+
+    let first = client
+        .upsert_idempotent(
+            "fleet",
+            "truck",
+            point.clone(),
+            serde_json::json!({}),
+            "req-1",
+        )
+        .await?;
+    // A retry of the same write reports the original sequence.
+    let retry = client
+        .upsert_idempotent(
+            "fleet",
+            "truck",
+            point.clone(),
+            serde_json::json!({}),
+            "req-1",
+        )
+        .await?;
+    assert_eq!(retry, first);
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs_f64();
+    let batch = vec![(now - 10.0, point.clone(), serde_json::json!({}))];
+    let inserted = client
+        .insert_trajectory_idempotent("fleet", "truck", batch.clone(), "req-2")
+        .await?;
+    assert!(inserted > first);
+    assert_eq!(
+        client
+            .insert_trajectory_idempotent("fleet", "truck", batch, "req-2")
+            .await?,
+        inserted
+    );
+
+    let traj = client.query_trajectory("fleet", "truck", .., 100).await?;
+    assert_eq!(traj.len(), 2);
+
+    // Unkeyed writes are applied every time.
+    let again = client
+        .upsert("fleet", "truck", point, serde_json::json!({}))
+        .await?;
+    assert!(again > inserted);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_upsert_batch_applies_all_or_nothing() -> anyhow::Result<()> {
+    let addr = spawn_test_server().await?;
+    let client = SpatioClient::connect(addr).await?;
+    let items: Vec<_> = (0..1_000)
+        .map(|i| {
+            let x = f64::from(i) * 0.001;
+            (
+                format!("v{i:04}"),
+                Point3d::new(x, 0.0, 0.0),
+                serde_json::json!({"i": i}),
+            )
+        })
+        .collect();
+
+    let sequence = client.upsert_batch("fleet", items.clone()).await?;
+    assert_eq!(sequence, 1_000);
+    assert_eq!(client.stats().await?.object_count, 1_000);
+    let last = client.get("fleet", "v0999").await?.expect("batch applied");
+    assert_eq!(last.position, Point3d::new(0.999, 0.0, 0.0));
+
+    // One bad item rejects the rest of the batch with it.
+    let mut bad = vec![(
+        "fresh".to_string(),
+        Point3d::new(1.0, 1.0, 0.0),
+        serde_json::json!({}),
+    )];
+    bad.push((
+        "broken".to_string(),
+        Point3d::new(500.0, 0.0, 0.0),
+        serde_json::json!({}),
+    ));
+    let err = client.upsert_batch("fleet", bad).await.unwrap_err();
+    assert!(err.to_string().contains("batch item 1"), "{err}");
+    assert!(client.get("fleet", "fresh").await?.is_none());
+
+    // A retried batch is applied once.
+    let first = client
+        .upsert_batch_idempotent("fleet", items[..2].to_vec(), "batch-1")
+        .await?;
+    let retry = client
+        .upsert_batch_idempotent("fleet", items[..2].to_vec(), "batch-1")
+        .await?;
+    assert_eq!((first, retry), (1_002, 1_002));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_region_subscription_streams_updates() -> anyhow::Result<()> {
+    let addr = spawn_test_server().await?;
+    let client = SpatioClient::connect(addr).await?;
+    let region = spatio::BoundingBox2D::new(0.0, 0.0, 1.0, 1.0);
+    let id = client.subscribe("fleet", region).await?;
+
+    // A poll waits for the first event to arrive.
+    let writer = client.clone();
+    let write = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        writer
+            .upsert(
+                "fleet",
+                "truck",
+                Point3d::new(0.5, 0.5, 0.0),
+                serde_json::json!({}),
+            )
+            .await
+    });
+    let events = client
+        .poll_subscription(id, 10, Duration::from_secs(5))
+        .await?;
+    write.await??;
+    assert_eq!(events.len(), 1);
+    assert!(matches!(&events[0], RegionEvent::Updated(loc) if loc.object_id == "truck"));
+
+    client
+        .upsert(
+            "fleet",
+            "truck",
+            Point3d::new(5.0, 5.0, 0.0),
+            serde_json::json!({}),
+        )
+        .await?;
+    client
+        .upsert(
+            "fleet",
+            "van",
+            Point3d::new(6.0, 6.0, 0.0),
+            serde_json::json!({}),
+        )
+        .await?;
+    let events = client
+        .poll_subscription(id, 10, Duration::from_millis(100))
+        .await?;
+    assert_eq!(events.len(), 1);
+    assert!(matches!(&events[0], RegionEvent::Left(loc) if loc.position.x() == 5.0));
+
+    assert!(client.unsubscribe(id).await?);
+    assert!(client
+        .poll_subscription(id, 10, Duration::ZERO)
+        .await
+        .is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_warm_up_loads_a_reopened_database() -> anyhow::Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("standby.db");
+    {
+        let db = Spatio::builder().path(&path).build()?;
+        for id in ["truck", "van"] {
+            db.upsert(
+                "fleet",
+                id,
+                Point3d::new(1.0, 2.0, 0.0),
+                serde_json::json!({}),
+                None,
+            )?;
+        }
+    }
+
+    // A standby opening the same files, warmed up before traffic arrives.
+    let db = Arc::new(Spatio::builder().path(&path).build()?);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(run_server(listener, db, futures::future::pending()));
+    let client = SpatioClient::connect(addr).await?;
+
+    let report = client.warm_up(Duration::from_secs(3600)).await?;
+    assert_eq!(report.objects, 2);
+    assert!(report.bytes_read > 0);
+    // Nothing is left to read the second time.
+    assert_eq!(
+        client.warm_up(Duration::from_secs(3600)).await?.bytes_read,
+        0
+    );
     Ok(())
 }
@@ -0,0 +1,99 @@
+use spatio::{Point3d, Spatio};
+use spatio_client::{PoolConfig, SpatioClientPool};
+use spatio_server::run_server;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Serve `db` on `addr` until the returned sender is dropped or fired.
+async fn serve(
+    db: Arc<Spatio>,
+    addr: SocketAddr,
+) -> anyhow::Result<(SocketAddr, oneshot::Sender<()>, tokio::task::JoinHandle<()>)> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    let addr = listener.local_addr()?;
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(async move {
+        let _ = run_server(
+            listener,
+            db,
+            Box::pin(async move {
+                let _ = stopped.await;
+            }),
+        )
+        .await;
+    });
+    Ok((addr, stop, server))
+}
+
+#[tokio::test]
+async fn test_pool_survives_server_restart() -> anyhow::Result<()> {
+    let db = Arc::new(Spatio::builder().build()?);
+    let (addr, stop, server) = serve(db.clone(), "127.0.0.1:0".parse()?).await?;
+
+    let config = PoolConfig {
+        size: 3,
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(100),
+        health_check_interval: None,
+        ..PoolConfig::default()
+    };
+    let pool = SpatioClientPool::with_config(addr, config).await?;
+    assert_eq!(pool.connected().await, 3);
+
+    let point = Point3d::new(-74.0, 40.7, 0.0);
+    pool.call(|client| {
+        let point = point.clone();
+        async move {
+            client
+                .upsert("fleet", "truck", point, serde_json::json!({}))
+                .await
+        }
+    })
+    .await?;
+
+    // Restart on the same port; the old connections are cut.
+    stop.send(()).ok();
+    server.await?;
+    let restarted = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        serve(db, addr).await
+    });
+
+    // Issued while the server is down: redials with backoff until it's back.
+    for _ in 0..6 {
+        let stats = pool
+            .call(|client| async move { client.stats().await })
+            .await?;
+        assert_eq!(stats.object_count, 1);
+    }
+    let (_, _stop, _server) = restarted.await??;
+    assert!(pool.connected().await >= 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_pool_gives_up_when_server_is_gone() -> anyhow::Result<()> {
+    let db = Arc::new(Spatio::builder().build()?);
+    let (addr, stop, server) = serve(db, "127.0.0.1:0".parse()?).await?;
+    let config = PoolConfig {
+        size: 1,
+        initial_backoff: Duration::from_millis(1),
+        max_attempts: 3,
+        health_check_interval: None,
+        ..PoolConfig::default()
+    };
+    let pool = SpatioClientPool::with_config(addr, config).await?;
+    stop.send(()).ok();
+    server.await?;
+
+    let result = pool
+        .call(|client| async move { client.stats().await })
+        .await;
+    assert!(result.is_err());
+    assert_eq!(pool.connected().await, 0);
+
+    assert!(SpatioClientPool::new(addr, 2).await.is_err());
+    Ok(())
+}
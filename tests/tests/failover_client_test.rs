@@ -0,0 +1,153 @@
+use spatio::{Point3d, Spatio};
+use spatio_client::{ClientError, FailoverClient, FailoverConfig, Role, SpatioClient};
+use spatio_server::{ServerTopology, SpatioServer};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+struct Node {
+    db: Arc<Spatio>,
+    addr: SocketAddr,
+    topology: ServerTopology,
+    stop: oneshot::Sender<()>,
+    server: tokio::task::JoinHandle<anyhow::Result<()>>,
+}
+
+/// Serve an empty database with `topology` until the node's `stop` fires.
+async fn node(topology: ServerTopology) -> anyhow::Result<Node> {
+    let db = Arc::new(Spatio::builder().build()?);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = SpatioServer::builder()
+        .db(db.clone())
+        .listener(listener)
+        .topology(topology.clone())
+        .shutdown(async move {
+            let _ = stopped.await;
+        });
+    let server = tokio::spawn(server.serve());
+    Ok(Node {
+        db,
+        addr,
+        topology,
+        stop,
+        server,
+    })
+}
+
+fn config() -> FailoverConfig {
+    FailoverConfig {
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(100),
+        ..FailoverConfig::default()
+    }
+}
+
+async fn upsert(client: &FailoverClient, id: &str) -> spatio_client::Result<u64> {
+    let id = id.to_string();
+    client
+        .call(|c| {
+            let id = id.clone();
+            async move {
+                c.upsert(
+                    "fleet",
+                    &id,
+                    Point3d::new(1.0, 2.0, 0.0),
+                    serde_json::json!({}),
+                )
+                .await
+            }
+        })
+        .await
+}
+
+#[tokio::test]
+async fn test_replica_refuses_writes_naming_its_primary() -> anyhow::Result<()> {
+    let primary: SocketAddr = "10.0.0.1:3000".parse()?;
+    let replica = node(ServerTopology::replica_of(primary, 1)).await?;
+    let client = SpatioClient::connect(replica.addr).await?;
+
+    let topology = client.topology().await?;
+    assert_eq!(topology.role, Role::Replica);
+    assert_eq!(topology.primary, Some(primary));
+    assert_eq!(topology.term, 1);
+
+    let refused = client
+        .upsert(
+            "fleet",
+            "van",
+            Point3d::new(1.0, 2.0, 0.0),
+            serde_json::json!({}),
+        )
+        .await;
+    assert!(matches!(
+        refused,
+        Err(ClientError::NotPrimary { primary: Some(addr), .. }) if addr == primary
+    ));
+    assert!(replica.db.get("fleet", "van")?.is_none());
+    // Reads are still served.
+    assert!(client.get("fleet", "van").await?.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_writes_follow_a_promoted_replica() -> anyhow::Result<()> {
+    let a = node(ServerTopology::primary(1)).await?;
+    let b = node(ServerTopology::replica_of(a.addr, 1)).await?;
+
+    // Found through either seed.
+    let client = FailoverClient::with_config(vec![b.addr, a.addr], config()).await?;
+    assert_eq!(client.primary().await, Some(a.addr));
+    upsert(&client, "truck").await?;
+    assert!(a.db.get("fleet", "truck")?.is_some());
+
+    // Fail over to b while a keeps running.
+    assert!(b.topology.promote(2));
+    assert!(a.topology.follow(b.addr, 2));
+    upsert(&client, "van").await?;
+    assert_eq!(client.primary().await, Some(b.addr));
+    assert!(b.db.get("fleet", "van")?.is_some());
+    assert!(a.db.get("fleet", "van")?.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_finds_the_new_primary_when_the_old_one_is_gone() -> anyhow::Result<()> {
+    let a = node(ServerTopology::primary(1)).await?;
+    let b = node(ServerTopology::replica_of(a.addr, 1)).await?;
+    let client = FailoverClient::with_config(vec![a.addr, b.addr], config()).await?;
+    upsert(&client, "truck").await?;
+
+    a.stop.send(()).ok();
+    a.server.await??;
+    let topology = b.topology.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        topology.promote(2);
+    });
+
+    // A request in flight when the connection dropped may fail; the next
+    // one finds b once it takes over.
+    if upsert(&client, "van").await.is_err() {
+        upsert(&client, "van").await?;
+    }
+    assert_eq!(client.primary().await, Some(b.addr));
+    assert!(b.db.get("fleet", "van")?.is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fails_without_a_primary() -> anyhow::Result<()> {
+    let primary: SocketAddr = "10.0.0.1:3000".parse()?;
+    let replica = node(ServerTopology::replica_of(primary, 1)).await?;
+    let config = FailoverConfig {
+        max_attempts: 2,
+        ..config()
+    };
+    assert!(FailoverClient::with_config(vec![replica.addr], config)
+        .await
+        .is_err());
+    Ok(())
+}
@@ -0,0 +1,86 @@
+#![cfg(feature = "grpc")]
+
+use spatio::{Point3d, Spatio};
+use spatio_client::{GrpcClient, TimeRange};
+use spatio_server::{run_grpc_server, ServerOptions};
+use std::sync::Arc;
+use std::time::Duration;
+
+async fn spawn_grpc_server() -> anyhow::Result<std::net::SocketAddr> {
+    let db = Arc::new(Spatio::builder().build()?);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        let _ = run_grpc_server(
+            listener,
+            db,
+            ServerOptions::default(),
+            futures::future::pending(),
+        )
+        .await;
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    Ok(addr)
+}
+
+#[tokio::test]
+async fn test_grpc_client_round_trip() -> anyhow::Result<()> {
+    let addr = spawn_grpc_server().await?;
+    let client = GrpcClient::connect(addr).await?;
+
+    client
+        .upsert(
+            "fleet",
+            "truck",
+            Point3d::new(0.001, 0.0, 5.0),
+            serde_json::json!({"driver": "ana"}),
+            Some("first".into()),
+        )
+        .await?;
+    client
+        .upsert(
+            "fleet",
+            "van",
+            Point3d::new(0.1, 0.0, 0.0),
+            serde_json::json!({}),
+            None,
+        )
+        .await?;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let truck = client.get("fleet", "truck").await?.expect("should exist");
+    assert_eq!(truck.position.z(), 5.0);
+    let meta: serde_json::Value = serde_json::from_slice(&truck.metadata)?;
+    assert_eq!(meta["driver"], "ana");
+
+    let hits = client
+        .query_radius("fleet", Point3d::new(0.0, 0.0, 0.0), 1000.0, 10, None)
+        .await?;
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].object_id, "truck");
+
+    let nearest = client.knn("fleet", Point3d::new(0.2, 0.0, 0.0), 1).await?;
+    assert_eq!(nearest[0].object_id, "van");
+
+    let boxed = client.query_bbox("fleet", 0.05, -1.0, 1.0, 1.0, 10).await?;
+    assert_eq!(boxed.len(), 1);
+
+    let track = client
+        .query_trajectory("fleet", "truck", TimeRange::all(), 10)
+        .await?;
+    assert_eq!(track.len(), 1);
+
+    assert_eq!(client.stats().await?.object_count, 2);
+
+    client.delete("fleet", "truck").await?;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(client.get("fleet", "truck").await?.is_none());
+
+    let err = client
+        .query_radius("fleet", Point3d::new(0.0, 100.0, 0.0), 1000.0, 10, None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, spatio_client::ClientError::Grpc(_)), "{err}");
+
+    Ok(())
+}
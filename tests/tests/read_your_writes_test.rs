@@ -0,0 +1,139 @@
+use spatio::{Point3d, Spatio};
+use spatio_client::{ClientError, Session, SessionToken, SpatioClient};
+use spatio_server::{ServerTopology, SpatioServer};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Serve an empty database with `topology`, reads waiting up to
+/// `applied_wait` for a session's writes.
+async fn node(
+    topology: ServerTopology,
+    applied_wait: Duration,
+) -> anyhow::Result<(Arc<Spatio>, SocketAddr)> {
+    let db = Arc::new(Spatio::builder().build()?);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let server = SpatioServer::builder()
+        .db(db.clone())
+        .listener(listener)
+        .topology(topology)
+        .applied_wait(applied_wait)
+        .shutdown(std::future::pending());
+    tokio::spawn(server.serve());
+    Ok((db, addr))
+}
+
+fn upsert(db: &Spatio, id: &str) -> anyhow::Result<u64> {
+    Ok(db.upsert(
+        "fleet",
+        id,
+        Point3d::new(1.0, 2.0, 0.0),
+        serde_json::json!({}),
+        None,
+    )?)
+}
+
+#[tokio::test]
+async fn test_reads_wait_for_the_session_writes() -> anyhow::Result<()> {
+    let (db, addr) = node(ServerTopology::primary(1), Duration::from_secs(5)).await?;
+    let client = SpatioClient::connect(addr).await?;
+
+    let session = Session::new();
+    let sequence = session
+        .write(
+            &client,
+            client.upsert(
+                "fleet",
+                "van",
+                Point3d::new(1.0, 2.0, 0.0),
+                serde_json::json!({}),
+            ),
+        )
+        .await?;
+    assert_eq!(
+        session.token(),
+        Some(SessionToken {
+            log_id: db.log_id(),
+            sequence,
+        })
+    );
+
+    // A write the session learned of elsewhere is applied while the read
+    // waits for it.
+    session.observe(SessionToken {
+        log_id: db.log_id(),
+        sequence: sequence + 1,
+    });
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        upsert(&db, "truck").unwrap();
+    });
+    let truck = session
+        .read(&client, |c| async move { c.get("fleet", "truck").await })
+        .await?;
+    assert!(truck.is_some());
+    // Without a token, reads don't wait.
+    let fresh = Session::new();
+    assert!(fresh
+        .read(&client, |c| async move { c.get("fleet", "bus").await })
+        .await?
+        .is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_another_server_refuses_the_token() -> anyhow::Result<()> {
+    let (_, primary_addr) = node(ServerTopology::primary(1), Duration::from_secs(5)).await?;
+    let (other_db, other_addr) = node(
+        ServerTopology::replica_of(primary_addr, 1),
+        Duration::from_secs(5),
+    )
+    .await?;
+    let primary = SpatioClient::connect(primary_addr).await?;
+    let other = SpatioClient::connect(other_addr).await?;
+    // The other server has applied more writes, but none of the primary's.
+    upsert(&other_db, "truck")?;
+    upsert(&other_db, "bus")?;
+
+    let session = Session::new();
+    session
+        .write(
+            &primary,
+            primary.upsert(
+                "fleet",
+                "van",
+                Point3d::new(1.0, 2.0, 0.0),
+                serde_json::json!({}),
+            ),
+        )
+        .await?;
+    let read = session
+        .read(&other, |c| async move { c.get("fleet", "van").await })
+        .await;
+    assert!(matches!(read, Err(ClientError::ForeignLog(_))));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lagging_server_refuses_the_read() -> anyhow::Result<()> {
+    let (db, addr) = node(ServerTopology::primary(1), Duration::from_millis(20)).await?;
+    upsert(&db, "truck")?;
+    let client = SpatioClient::connect(addr).await?;
+
+    let token: SessionToken = format!("{:016x}:5", db.log_id()).parse().unwrap();
+    let session = Session::with_token(token);
+    let read = session
+        .read(&client, |c| async move { c.get("fleet", "truck").await })
+        .await;
+    assert!(matches!(
+        read,
+        Err(ClientError::StaleReplica {
+            required: 5,
+            applied: 1,
+            ..
+        })
+    ));
+    assert_eq!(client.wait_applied(db.log_id(), 1).await?, 1);
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use spatio::{Point3d, Spatio};
+use spatio_client::SpatioClient;
+use spatio_router::ShardRouter;
+use spatio_server::run_server;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+async fn spawn_test_server() -> anyhow::Result<std::net::SocketAddr> {
+    let db = Arc::new(Spatio::builder().build()?);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let bound_addr = listener.local_addr()?;
+
+    tokio::spawn(async move {
+        let _ = run_server(listener, db, futures::future::pending()).await;
+    });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    Ok(bound_addr)
+}
+
+async fn spawn_shards(n: usize) -> anyhow::Result<Vec<std::net::SocketAddr>> {
+    let mut addrs = Vec::with_capacity(n);
+    for _ in 0..n {
+        addrs.push(spawn_test_server().await?);
+    }
+    Ok(addrs)
+}
+
+/// Points on a grid around Berlin, spread over many geohash-4 cells.
+fn grid() -> Vec<(String, Point3d)> {
+    let mut points = Vec::new();
+    for i in 0..10 {
+        for j in 0..10 {
+            let lon = 12.0 + i as f64 * 0.3;
+            let lat = 51.5 + j as f64 * 0.2;
+            points.push((format!("p{}_{}", i, j), Point3d::new(lon, lat, 0.0)));
+        }
+    }
+    points
+}
+
+#[tokio::test]
+async fn test_router_places_objects_on_owning_shard() -> anyhow::Result<()> {
+    let addrs = spawn_shards(3).await?;
+    let router = ShardRouter::connect(&addrs).await?;
+    assert_eq!(router.shard_count(), 3);
+
+    let mut used = HashSet::new();
+    for (id, point) in grid() {
+        let write = router
+            .upsert("fleet", &id, point.clone(), serde_json::json!({}))
+            .await?;
+        assert_eq!(write.shard, router.shard_for("fleet", point.x(), point.y()));
+        used.insert(write.shard);
+    }
+    assert_eq!(used.len(), 3, "grid should spread over every shard");
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Each object lives on exactly its owning shard.
+    let clients =
+        futures::future::try_join_all(addrs.iter().map(|a| SpatioClient::connect(*a))).await?;
+    for (id, point) in grid().into_iter().take(20) {
+        let owner = router.shard_for("fleet", point.x(), point.y());
+        for (shard, client) in clients.iter().enumerate() {
+            let found = client.get("fleet", &id).await?.is_some();
+            assert_eq!(found, shard == owner, "{} on shard {}", id, shard);
+        }
+    }
+
+    // A fresh router has no placement cache and scatters the lookup.
+    let fresh = ShardRouter::connect(&addrs).await?;
+    let loc = fresh.get("fleet", "p3_4").await?.expect("should exist");
+    assert_eq!(loc.object_id, "p3_4");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_router_scatter_gather_queries() -> anyhow::Result<()> {
+    let addrs = spawn_shards(3).await?;
+    let router = ShardRouter::connect(&addrs).await?;
+    for (id, point) in grid() {
+        router
+            .upsert("fleet", &id, point, serde_json::json!({}))
+            .await?;
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Whole grid via bbox, across all shards.
+    let all = router
+        .query_bbox("fleet", 11.9, 51.4, 14.8, 53.4, 1000)
+        .await?;
+    assert_eq!(all.len(), 100);
+    let limited = router
+        .query_bbox("fleet", 11.9, 51.4, 14.8, 53.4, 7)
+        .await?;
+    assert_eq!(limited.len(), 7);
+
+    // Radius wide enough to catch neighbours in other cells, nearest first.
+    let center = Point3d::new(13.2, 52.3, 0.0);
+    let nearby = router
+        .query_radius("fleet", center.clone(), 40_000.0, 100, None)
+        .await?;
+    assert_eq!(nearby[0].object_id, "p4_4");
+    assert!(nearby.len() > 1);
+    assert!(nearby.windows(2).all(|w| w[0].distance <= w[1].distance));
+
+    let nearest = router.knn("fleet", center, 5).await?;
+    assert_eq!(nearest.len(), 5);
+    assert_eq!(nearest[0].object_id, "p4_4");
+    // Equidistant neighbours may tie, so compare the nearest five as sets.
+    let ids = |results: &[spatio_client::QueryHit]| {
+        results
+            .iter()
+            .take(5)
+            .map(|h| h.object_id.clone())
+            .collect::<HashSet<_>>()
+    };
+    assert_eq!(ids(&nearest), ids(&nearby));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_router_moves_object_between_shards() -> anyhow::Result<()> {
+    let addrs = spawn_shards(3).await?;
+    let router = ShardRouter::connect(&addrs).await?;
+
+    // Find two grid points owned by different shards.
+    let points = grid();
+    let (_, from) = &points[0];
+    let from_shard = router.shard_for("fleet", from.x(), from.y());
+    let (_, to) = points
+        .iter()
+        .find(|(_, p)| router.shard_for("fleet", p.x(), p.y()) != from_shard)
+        .expect("grid spans several shards");
+    let to_shard = router.shard_for("fleet", to.x(), to.y());
+
+    router
+        .upsert("fleet", "mover", from.clone(), serde_json::json!({}))
+        .await?;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    router
+        .upsert("fleet", "mover", to.clone(), serde_json::json!({}))
+        .await?;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let old = SpatioClient::connect(addrs[from_shard]).await?;
+    let new = SpatioClient::connect(addrs[to_shard]).await?;
+    assert!(old.get("fleet", "mover").await?.is_none());
+    assert!(new.get("fleet", "mover").await?.is_some());
+
+    router.delete("fleet", "mover").await?;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(router.get("fleet", "mover").await?.is_none());
+
+    Ok(())
+}
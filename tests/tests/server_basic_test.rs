@@ -1,5 +1,5 @@
 use spatio::{Point3d, Spatio};
-use spatio_client::SpatioClient;
+use spatio_client::{SpatioClient, TimeRange};
 use spatio_server::run_server;
 use std::sync::Arc;
 
@@ -71,7 +71,12 @@ async fn test_trajectory_rpc() -> anyhow::Result<()> {
 
     // QueryTrajectory
     let updates = client
-        .query_trajectory("traj_ns", "truck1", Some(now - 60.0), Some(now + 60.0), 10)
+        .query_trajectory(
+            "traj_ns",
+            "truck1",
+            TimeRange::from_secs(Some(now - 60.0), Some(now + 60.0), None).unwrap(),
+            10,
+        )
         .await?;
 
     assert_eq!(updates.len(), 1);
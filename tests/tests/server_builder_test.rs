@@ -0,0 +1,62 @@
+use spatio::Spatio;
+use spatio_client::SpatioClient;
+use spatio_server::{ConnectionInfo, SpatioServer};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_builder_serves_until_shutdown() -> anyhow::Result<()> {
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+    let server = SpatioServer::builder()
+        .db(Spatio::builder().build()?)
+        .addr("127.0.0.1:0".parse()?)
+        .shutdown(async move {
+            let _ = stop_rx.await;
+        })
+        .bind()
+        .await?;
+    let addr = server.local_addr()?;
+    let serving = tokio::spawn(server.serve());
+
+    let client = SpatioClient::connect(addr).await?;
+    assert_eq!(client.stats().await?.object_count, 0);
+
+    stop_tx.send(()).ok();
+    tokio::time::timeout(Duration::from_secs(5), serving).await???;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_builder_requires_a_database() {
+    let err = SpatioServer::builder()
+        .addr("127.0.0.1:0".parse().unwrap())
+        .bind()
+        .await
+        .err()
+        .expect("bind without a database should fail");
+    assert!(err.to_string().contains("database"));
+}
+
+#[tokio::test]
+async fn test_auth_refuses_connections() -> anyhow::Result<()> {
+    let checked = Arc::new(AtomicUsize::new(0));
+    let seen = checked.clone();
+    let server = SpatioServer::builder()
+        .db(Spatio::builder().build()?)
+        .addr("127.0.0.1:0".parse()?)
+        .auth(move |conn: &ConnectionInfo| {
+            seen.fetch_add(1, Ordering::SeqCst);
+            assert!(conn.client_certificates.is_empty());
+            Err(format!("{} is not on the allow-list", conn.peer))
+        })
+        .bind()
+        .await?;
+    let addr = server.local_addr()?;
+    tokio::spawn(server.serve());
+
+    let client = SpatioClient::connect(addr).await?;
+    assert!(client.stats().await.is_err());
+    assert_eq!(checked.load(Ordering::SeqCst), 1);
+    Ok(())
+}
@@ -41,12 +41,17 @@ async fn test_remote_client_ops() -> anyhow::Result<()> {
     // 5. Verify stats
     let stats = client.stats().await?;
     assert_eq!(stats.object_count, 1);
+    let total = |op| stats.per_minute.iter().map(|m| m.total(op)).sum::<u64>();
+    assert_eq!(total(spatio::Operation::Upsert), 1);
+    assert_eq!(total(spatio::Operation::Get), 1);
 
     // 6. Query radius
     let nyc_3d = Point3d::new(-74.0060, 40.7128, 0.0);
-    let nearby = client.query_radius("cities", nyc_3d, 100_000.0, 10).await?;
+    let nearby = client
+        .query_radius("cities", nyc_3d, 100_000.0, 10, None)
+        .await?;
     assert_eq!(nearby.len(), 1);
-    assert_eq!(nearby[0].0.object_id, "nyc");
+    assert_eq!(nearby[0].object_id, "nyc");
 
     Ok(())
 }
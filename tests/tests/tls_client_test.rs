@@ -0,0 +1,61 @@
+#![cfg(feature = "tls")]
+
+use spatio::{Point3d, Spatio};
+use spatio_client::rustls::pki_types::pem::PemObject;
+use spatio_client::rustls::pki_types::CertificateDer;
+use spatio_client::rustls::{self, ClientConfig, RootCertStore};
+use spatio_client::SpatioClient;
+use spatio_server::{SpatioServer, TlsConfig};
+use std::sync::Arc;
+
+const TESTDATA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../crates/server/testdata");
+
+fn client_config() -> anyhow::Result<Arc<ClientConfig>> {
+    let mut roots = RootCertStore::empty();
+    roots.add(CertificateDer::from_pem_file(format!(
+        "{TESTDATA}/localhost.crt"
+    ))?)?;
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+#[tokio::test]
+async fn test_client_connects_over_tls() -> anyhow::Result<()> {
+    let tls = TlsConfig::new(
+        format!("{TESTDATA}/localhost.crt"),
+        format!("{TESTDATA}/localhost.key"),
+    );
+    let server = SpatioServer::builder()
+        .db(Spatio::builder().build()?)
+        .listener(tokio::net::TcpListener::bind("127.0.0.1:0").await?)
+        .tls(tls.load()?)
+        .bind()
+        .await?;
+    let addr = server.local_addr()?;
+    tokio::spawn(server.serve());
+
+    let client = SpatioClient::connect_tls(addr, "localhost", client_config()?).await?;
+    client
+        .upsert(
+            "fleet",
+            "truck",
+            Point3d::new(-74.0, 40.7, 0.0),
+            serde_json::json!({}),
+        )
+        .await?;
+    let location = client.get("fleet", "truck").await?.expect("stored");
+    assert_eq!(location.position.x(), -74.0);
+
+    // The certificate is for localhost, not this name.
+    let wrong_name = SpatioClient::connect_tls(addr, "example.com", client_config()?).await;
+    assert!(wrong_name.is_err());
+
+    // A plain TCP client gets nowhere with a TLS server.
+    let plain = SpatioClient::connect(addr).await?;
+    assert!(plain.stats().await.is_err());
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use spatio::{Point3d, Spatio};
+use spatio_client::{with_traceparent, SpatioClient};
+use spatio_server::run_server;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+/// Fields of interest recorded on a span.
+#[derive(Default, Clone)]
+struct SpanFields {
+    trace_id: Option<String>,
+    server: bool,
+}
+
+impl Visit for SpanFields {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "rpc.trace_id" => self.trace_id = Some(format!("{:?}", value)),
+            "otel.kind" => self.server = format!("{:?}", value) == "\"server\"",
+            _ => {}
+        }
+    }
+}
+
+/// Database span name and the trace id of its enclosing server RPC span.
+type DbSpan = (String, Option<String>);
+
+/// Records, for each database span, the trace id of the enclosing server RPC
+/// span (if any).
+#[derive(Clone, Default)]
+struct Recorder {
+    db_spans: Arc<Mutex<Vec<DbSpan>>>,
+}
+
+impl<S> Layer<S> for Recorder
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let span = ctx.span(id).expect("span exists");
+        let mut fields = SpanFields::default();
+        attrs.record(&mut fields);
+        span.extensions_mut().insert(fields);
+
+        if !span.name().starts_with("spatio.") || span.name() == "spatio.client" {
+            return;
+        }
+        let rpc_trace = span.scope().skip(1).find_map(|ancestor| {
+            let extensions = ancestor.extensions();
+            let fields = extensions.get::<SpanFields>()?;
+            (ancestor.name() == "RPC" && fields.server)
+                .then(|| fields.trace_id.clone())
+                .flatten()
+        });
+        self.db_spans
+            .lock()
+            .unwrap()
+            .push((span.name().to_string(), rpc_trace));
+    }
+
+    fn on_record(&self, id: &Id, values: &tracing::span::Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(fields) = span.extensions_mut().get_mut::<SpanFields>() {
+                values.record(fields);
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_traceparent_reaches_database_spans() -> anyhow::Result<()> {
+    let recorder = Recorder::default();
+    tracing_subscriber::registry().with(recorder.clone()).init();
+
+    let db = Arc::new(Spatio::builder().build()?);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        let _ = run_server(listener, db, futures::future::pending()).await;
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let client = SpatioClient::connect(addr).await?;
+    let traceparent = format!("00-{TRACE_ID}-00f067aa0ba902b7-01");
+    with_traceparent(&traceparent, async {
+        client
+            .upsert(
+                "traced",
+                "a",
+                Point3d::new(1.0, 2.0, 0.0),
+                serde_json::json!({}),
+            )
+            .await?;
+        client
+            .query_radius("traced", Point3d::new(1.0, 2.0, 0.0), 100.0, 10, None)
+            .await?;
+        anyhow::Ok(())
+    })
+    .await?;
+
+    // Calls outside the scope start their own trace.
+    client.get("traced", "a").await?;
+
+    let spans = recorder.db_spans.lock().unwrap().clone();
+    let trace_of = |name: &str| {
+        spans
+            .iter()
+            .find(|(span, _)| span == name)
+            .unwrap_or_else(|| panic!("no {name} span in {spans:?}"))
+            .1
+            .clone()
+    };
+    // Writes run on the writer thread, reads on the blocking pool; both stay
+    // inside the request span.
+    assert_eq!(trace_of("spatio.upsert").as_deref(), Some(TRACE_ID));
+    assert_eq!(trace_of("spatio.query_radius").as_deref(), Some(TRACE_ID));
+    assert_eq!(trace_of("spatio.index_read").as_deref(), Some(TRACE_ID));
+
+    Ok(())
+}